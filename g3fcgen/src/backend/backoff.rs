@@ -0,0 +1,96 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+/// Exponential backoff for backend refresh retries
+pub(super) struct RefreshBackoff {
+    min: Duration,
+    max: Duration,
+    current: Option<Duration>,
+}
+
+impl RefreshBackoff {
+    pub(super) fn new(min: Duration, max: Duration) -> Self {
+        RefreshBackoff {
+            min,
+            max: max.max(min),
+            current: None,
+        }
+    }
+
+    /// Get the base retry interval after a new failure, without jitter
+    pub(super) fn on_failure(&mut self) -> Duration {
+        let next = match self.current {
+            Some(d) => d.saturating_mul(2).min(self.max),
+            None => self.min,
+        };
+        self.current = Some(next);
+        next
+    }
+
+    pub(super) fn reset(&mut self) {
+        self.current = None;
+    }
+
+    #[inline]
+    pub(super) fn current(&self) -> Option<Duration> {
+        self.current
+    }
+
+    /// Get a random retry interval in range [base/2, base]
+    pub(super) fn jitter(base: Duration) -> Duration {
+        let half = base / 2;
+        let millis = half.as_millis() as u64;
+        if millis == 0 {
+            return base;
+        }
+        half + Duration::from_millis(fastrand::u64(0..=millis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grow_and_reset() {
+        let mut backoff = RefreshBackoff::new(Duration::from_secs(1), Duration::from_secs(10));
+        assert!(backoff.current().is_none());
+
+        assert_eq!(backoff.on_failure(), Duration::from_secs(1));
+        assert_eq!(backoff.on_failure(), Duration::from_secs(2));
+        assert_eq!(backoff.on_failure(), Duration::from_secs(4));
+        assert_eq!(backoff.on_failure(), Duration::from_secs(8));
+        assert_eq!(backoff.on_failure(), Duration::from_secs(10));
+        assert_eq!(backoff.on_failure(), Duration::from_secs(10));
+        assert_eq!(backoff.current(), Some(Duration::from_secs(10)));
+
+        backoff.reset();
+        assert!(backoff.current().is_none());
+        assert_eq!(backoff.on_failure(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter_range() {
+        let base = Duration::from_secs(8);
+        for _ in 0..100 {
+            let d = RefreshBackoff::jitter(base);
+            assert!(d >= Duration::from_secs(4));
+            assert!(d <= base);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::anyhow;
+use flume::{Receiver, Sender};
+use log::{debug, error, warn};
+use tokio::runtime::Handle;
+use tokio::time::Instant;
+
+use g3_cert_agent::Request;
+
+use super::OpensslBackend;
+use crate::config::BackendPreference;
+use crate::frontend::GeneratedData;
+use crate::{BackendRequest, BackendResponse};
+
+/// All the backends used by a single worker, with failover between them
+pub(crate) struct BackendGroup {
+    backends: Vec<OpensslBackend>,
+    preference: BackendPreference,
+}
+
+impl BackendGroup {
+    pub(crate) fn new(backends: Vec<OpensslBackend>, preference: BackendPreference) -> Self {
+        BackendGroup {
+            backends,
+            preference,
+        }
+    }
+
+    fn preferred(&self) -> usize {
+        match self.preference {
+            BackendPreference::Ordered => 0,
+            BackendPreference::Weighted => {
+                let weights = self.backends.iter().map(|b| b.config.weight);
+                pick_weighted(weights, |total| fastrand::u64(0..total))
+            }
+        }
+    }
+
+    /// Get the backend try order, the preferred backend will be used if healthy,
+    /// and unhealthy backends will only be used as the last resort
+    fn try_order(&self, preferred: usize) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.backends.len());
+        order.push(preferred);
+        order.extend((0..self.backends.len()).filter(|i| *i != preferred));
+        order.sort_by_key(|i| !self.backends[*i].is_healthy());
+        order
+    }
+
+    fn generate(&mut self, req: &Request, id: usize) -> anyhow::Result<GeneratedData> {
+        let preferred = self.preferred();
+        let mut last_err = None;
+        for i in self.try_order(preferred) {
+            let backend = &mut self.backends[i];
+            match backend.generate(req) {
+                Ok(data) => {
+                    if i != preferred {
+                        backend.stats.add_request_failover();
+                    }
+                    return Ok(data);
+                }
+                Err(e) => {
+                    warn!(
+                        "{} - [#{id}] cert generation failed with backend {}: {e:?}",
+                        req.host(),
+                        backend.name()
+                    );
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("no backend available")))
+    }
+
+    fn next_refresh(&self) -> Instant {
+        self.backends
+            .iter()
+            .map(|b| b.next_refresh())
+            .min()
+            .unwrap_or_else(Instant::now)
+    }
+
+    fn refresh_due(&mut self, id: usize) {
+        let now = Instant::now();
+        for backend in &mut self.backends {
+            backend.refresh_if_due(now, id);
+        }
+    }
+
+    pub(crate) fn spawn(
+        mut self,
+        handle: &Handle,
+        id: usize,
+        req_receiver: Receiver<BackendRequest>,
+        rsp_sender: Sender<BackendResponse>,
+    ) {
+        handle.spawn(async move {
+            loop {
+                let next_refresh = self.next_refresh();
+
+                tokio::select! {
+                    _ = tokio::time::sleep_until(next_refresh) => {
+                        self.refresh_due(id);
+                    }
+                    r = req_receiver.recv_async() => {
+                        let Ok(req) = r else {
+                            break
+                        };
+
+                        let host = req.user_req.host();
+                        debug!("{host} - [#{id}] start cert generation");
+                        match self.generate(&req.user_req, id) {
+                            Ok(data) => {
+                                debug!("{host} - [#{id}] cert generated");
+                                if let Err(e) = rsp_sender.send_async(req.into_response(data)).await {
+                                    error!("{host} - [#{id}] failed to send cert to frontend: {e}");
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                warn!("{host} - [#{id}] cert generation failed: {e:?}");
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Pick an index by weight, backends with zero weight will never be picked unless all are zero
+fn pick_weighted<I, F>(weights: I, rand: F) -> usize
+where
+    I: Iterator<Item = u32> + Clone,
+    F: FnOnce(u64) -> u64,
+{
+    let total: u64 = weights.clone().map(u64::from).sum();
+    if total == 0 {
+        return 0;
+    }
+    let mut v = rand(total);
+    for (i, w) in weights.enumerate() {
+        let w = u64::from(w);
+        if v < w {
+            return i;
+        }
+        v -= w;
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted() {
+        let weights = [0u32, 2, 1];
+        assert_eq!(pick_weighted(weights.iter().copied(), |_| 0), 1);
+        assert_eq!(pick_weighted(weights.iter().copied(), |_| 1), 1);
+        assert_eq!(pick_weighted(weights.iter().copied(), |_| 2), 2);
+        assert_eq!(pick_weighted([0u32, 0].iter().copied(), |_| 0), 0);
+    }
+}
@@ -0,0 +1,102 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::config::BackendFailoverConfig;
+
+/// The health state of a backend, shared by all worker instances of the same backend config.
+///
+/// A backend will be marked unhealthy after continuous refresh or request failures,
+/// and will be marked healthy again after enough continuous successful probes.
+pub(crate) struct BackendHealth {
+    unhealthy_after: u32,
+    recover_after: u32,
+    healthy: AtomicBool,
+    failures: AtomicU32,
+    successes: AtomicU32,
+}
+
+impl BackendHealth {
+    pub(crate) fn new(config: &BackendFailoverConfig) -> Self {
+        BackendHealth {
+            unhealthy_after: config.unhealthy_after,
+            recover_after: config.recover_after,
+            healthy: AtomicBool::new(true),
+            failures: AtomicU32::new(0),
+            successes: AtomicU32::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn on_success(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        if self.is_healthy() {
+            return;
+        }
+        let n = self.successes.fetch_add(1, Ordering::Relaxed) + 1;
+        if n >= self.recover_after {
+            self.successes.store(0, Ordering::Relaxed);
+            self.healthy.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub(super) fn on_failure(&self) {
+        self.successes.store(0, Ordering::Relaxed);
+        let n = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if n >= self.unhealthy_after {
+            self.healthy.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failover_and_recover() {
+        let config = BackendFailoverConfig {
+            unhealthy_after: 2,
+            recover_after: 3,
+            ..Default::default()
+        };
+        let health = BackendHealth::new(&config);
+        assert!(health.is_healthy());
+
+        health.on_failure();
+        assert!(health.is_healthy());
+        health.on_success();
+        health.on_failure();
+        assert!(health.is_healthy());
+        health.on_failure();
+        assert!(!health.is_healthy());
+
+        health.on_success();
+        health.on_success();
+        assert!(!health.is_healthy());
+        health.on_failure();
+        health.on_success();
+        health.on_success();
+        assert!(!health.is_healthy());
+        health.on_success();
+        assert!(health.is_healthy());
+    }
+}
@@ -19,11 +19,10 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::anyhow;
-use flume::{Receiver, Sender};
-use log::{debug, error, warn};
+use log::warn;
 use openssl::pkey::{PKey, Private};
 use openssl::x509::X509;
-use tokio::runtime::Handle;
+use tokio::time::Instant;
 
 use g3_cert_agent::Request;
 use g3_tls_cert::builder::{MimicCertBuilder, ServerCertBuilder, TlsServerCertBuilder};
@@ -32,7 +31,15 @@ use g3_types::net::{Host, TlsCertUsage};
 mod stats;
 pub(crate) use stats::BackendStats;
 
-use super::{BackendRequest, BackendResponse};
+mod backoff;
+use backoff::RefreshBackoff;
+
+mod health;
+pub(crate) use health::BackendHealth;
+
+mod group;
+pub(crate) use group::BackendGroup;
+
 use crate::config::OpensslBackendConfig;
 use crate::frontend::GeneratedData;
 
@@ -40,22 +47,78 @@ pub(crate) struct OpensslBackend {
     config: Arc<OpensslBackendConfig>,
     builder: ServerCertBuilder,
     stats: Arc<BackendStats>,
+    health: Arc<BackendHealth>,
+    probe_interval: Duration,
+    backoff: RefreshBackoff,
+    next_refresh: Instant,
 }
 
 impl OpensslBackend {
     pub(crate) fn new(
         config: &Arc<OpensslBackendConfig>,
         stats: &Arc<BackendStats>,
+        health: &Arc<BackendHealth>,
+        probe_interval: Duration,
     ) -> anyhow::Result<Self> {
         let builder = TlsServerCertBuilder::new_ec256()?;
         Ok(OpensslBackend {
             config: Arc::clone(config),
             builder,
             stats: Arc::clone(stats),
+            health: Arc::clone(health),
+            probe_interval,
+            backoff: RefreshBackoff::new(config.refresh_backoff_min, config.refresh_backoff_max),
+            next_refresh: Instant::now(),
         })
     }
 
-    pub(crate) fn refresh(&mut self) -> anyhow::Result<()> {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    #[inline]
+    fn is_healthy(&self) -> bool {
+        self.health.is_healthy()
+    }
+
+    #[inline]
+    fn next_refresh(&self) -> Instant {
+        self.next_refresh
+    }
+
+    /// Refresh the backend if it's due, the refresh result will also be used as health probe
+    fn refresh_if_due(&mut self, now: Instant, id: usize) {
+        if now < self.next_refresh {
+            return;
+        }
+
+        let wait = match self.refresh() {
+            Ok(_) => {
+                self.backoff.reset();
+                self.health.on_success();
+                if self.health.is_healthy() {
+                    self.config.refresh_interval
+                } else {
+                    self.probe_interval
+                }
+            }
+            Err(e) => {
+                self.health.on_failure();
+                let wait = RefreshBackoff::jitter(self.backoff.on_failure());
+                warn!(
+                    "[#{id}] failed to refresh backend {}: {e:?}, will retry in {wait:?}",
+                    self.name()
+                );
+                wait
+            }
+        };
+        self.stats.set_refresh_backoff(self.backoff.current());
+        self.stats.set_healthy(self.health.is_healthy());
+        self.next_refresh = now + wait;
+    }
+
+    fn refresh(&mut self) -> anyhow::Result<()> {
         self.stats.add_refresh_total();
         self.builder.refresh_datetime()?;
         self.builder.refresh_ec256()?;
@@ -64,6 +127,17 @@ impl OpensslBackend {
     }
 
     fn generate(&mut self, req: &Request) -> anyhow::Result<GeneratedData> {
+        let r = self.do_generate(req);
+        if r.is_ok() {
+            self.health.on_success();
+        } else {
+            self.health.on_failure();
+        }
+        self.stats.set_healthy(self.health.is_healthy());
+        r
+    }
+
+    fn do_generate(&mut self, req: &Request) -> anyhow::Result<GeneratedData> {
         self.stats.add_request_total();
         if let Some(mimic_cert) = req.cert() {
             self.generate_mimic(mimic_cert, req.cert_usage())
@@ -137,46 +211,4 @@ impl OpensslBackend {
         self.stats.add_request_ok();
         Ok(data)
     }
-
-    pub(crate) fn spawn(
-        mut self,
-        handle: &Handle,
-        id: usize,
-        req_receiver: Receiver<BackendRequest>,
-        rsp_sender: Sender<BackendResponse>,
-    ) {
-        handle.spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(300));
-
-            loop {
-                tokio::select! {
-                    _ = interval.tick() => {
-                        if let Err(e) = self.refresh() {
-                            warn!("failed to refresh backend: {e:?}");
-                        }
-                    }
-                    r = req_receiver.recv_async() => {
-                        let Ok(req) = r else {
-                            break
-                        };
-
-                        let host = req.user_req.host();
-                        debug!("{host} - [#{id}] start cert generation");
-                        match self.generate(&req.user_req) {
-                            Ok(data) => {
-                                debug!("{host} - [#{id}] cert generated");
-                                if let Err(e) = rsp_sender.send_async(req.into_response(data)).await {
-                                    error!("{host} - [#{id}] failed to send cert to frontend: {e}");
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                warn!("{host} - [#{id}] cert generation failed: {e:?}");
-                            }
-                        }
-                    }
-                }
-            }
-        });
-    }
 }
@@ -14,14 +14,18 @@
  * limitations under the License.
  */
 
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
 
-#[derive(Default)]
 pub(crate) struct BackendStats {
+    name: String,
     refresh_total: AtomicU64,
     refresh_ok: AtomicU64,
     request_total: AtomicU64,
     request_ok: AtomicU64,
+    request_failover: AtomicU64,
+    refresh_backoff_ms: AtomicU64,
+    healthy: AtomicBool,
 }
 
 macro_rules! impl_for_field {
@@ -37,8 +41,49 @@ macro_rules! impl_for_field {
 }
 
 impl BackendStats {
+    pub(crate) fn new(name: &str) -> Self {
+        BackendStats {
+            name: name.to_string(),
+            refresh_total: Default::default(),
+            refresh_ok: Default::default(),
+            request_total: Default::default(),
+            request_ok: Default::default(),
+            request_failover: Default::default(),
+            refresh_backoff_ms: Default::default(),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
     impl_for_field!(add_refresh_total, take_refresh_total, refresh_total);
     impl_for_field!(add_refresh_ok, take_refresh_ok, refresh_ok);
     impl_for_field!(add_request_total, take_request_total, request_total);
     impl_for_field!(add_request_ok, take_request_ok, request_ok);
+    impl_for_field!(
+        add_request_failover,
+        take_request_failover,
+        request_failover
+    );
+
+    pub(super) fn set_refresh_backoff(&self, backoff: Option<Duration>) {
+        let ms = backoff.map(|d| d.as_millis() as u64).unwrap_or_default();
+        self.refresh_backoff_ms.store(ms, Ordering::Relaxed);
+    }
+
+    pub(super) fn set_healthy(&self, healthy: bool) {
+        self.healthy.store(healthy, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Get the current refresh retry backoff in milliseconds, 0 means no backoff
+    pub(crate) fn get_refresh_backoff_ms(&self) -> u64 {
+        self.refresh_backoff_ms.load(Ordering::Relaxed)
+    }
 }
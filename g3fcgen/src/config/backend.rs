@@ -16,6 +16,7 @@
 
 use std::sync::Arc;
 use std::sync::OnceLock;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context};
 use openssl::pkey::{PKey, Private};
@@ -24,23 +25,61 @@ use yaml_rust::Yaml;
 
 use g3_histogram::HistogramMetricsConfig;
 
-static BACKEND_CONFIG_LOCK: OnceLock<Arc<OpensslBackendConfig>> = OnceLock::new();
+static BACKEND_CONFIG_LOCK: OnceLock<Arc<Vec<Arc<OpensslBackendConfig>>>> = OnceLock::new();
 
-pub(crate) fn get_config() -> Option<Arc<OpensslBackendConfig>> {
+/// Get all the configured backends, in the order of preference
+pub(crate) fn get_config() -> Option<Arc<Vec<Arc<OpensslBackendConfig>>>> {
     BACKEND_CONFIG_LOCK.get().cloned()
 }
 
 pub(crate) struct OpensslBackendConfig {
+    pub(crate) name: String,
+    pub(crate) weight: u32,
     pub(crate) ca_cert: X509,
     pub(crate) ca_key: PKey<Private>,
     pub(crate) ca_cert_pem: Vec<u8>,
     pub(crate) keep_serial: bool,
     pub(crate) max_ttl: i32,
     pub(crate) duration_stats: HistogramMetricsConfig,
+    pub(crate) refresh_interval: Duration,
+    pub(crate) refresh_backoff_min: Duration,
+    pub(crate) refresh_backoff_max: Duration,
 }
 
 pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
+    let backends = match value {
+        Yaml::Hash(_) => vec![Arc::new(parse_backend(value, "default".to_string())?)],
+        Yaml::Array(seq) => {
+            let mut backends: Vec<Arc<OpensslBackendConfig>> = Vec::with_capacity(seq.len());
+            for (i, v) in seq.iter().enumerate() {
+                let backend = parse_backend(v, format!("backend{i}"))
+                    .context(format!("invalid backend config #{i}"))?;
+                if backends.iter().any(|b| b.name == backend.name) {
+                    return Err(anyhow!("duplicate backend name {}", backend.name));
+                }
+                backends.push(Arc::new(backend));
+            }
+            if backends.is_empty() {
+                return Err(anyhow!("no backend config set"));
+            }
+            backends
+        }
+        _ => {
+            return Err(anyhow!(
+                "yam value type for the backend config should be 'map' or 'seq'"
+            ))
+        }
+    };
+    BACKEND_CONFIG_LOCK
+        .set(Arc::new(backends))
+        .map_err(|_| anyhow!("duplicate backend config"))?;
+    Ok(())
+}
+
+fn parse_backend(value: &Yaml, default_name: String) -> anyhow::Result<OpensslBackendConfig> {
     if let Yaml::Hash(map) = value {
+        let mut name = default_name;
+        let mut weight = 1;
         let mut no_append_ca_cert = false;
         let mut ca_cert_pem = Vec::new();
         let mut ca_cert: Option<X509> = None;
@@ -48,9 +87,22 @@ pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
         let mut keep_serial = false;
         let mut max_ttl = 24 * 3600; // 1 day
         let mut duration_stats = HistogramMetricsConfig::default();
+        let mut refresh_interval = Duration::from_secs(300);
+        let mut refresh_backoff_min = Duration::from_secs(1);
+        let mut refresh_backoff_max = Duration::from_secs(60);
         let lookup_dir = g3_daemon::config::get_lookup_dir(None)?;
 
         g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "name" => {
+                name = g3_yaml::value::as_string(v)
+                    .context(format!("invalid string value for key {k}"))?;
+                Ok(())
+            }
+            "weight" => {
+                weight =
+                    g3_yaml::value::as_u32(v).context(format!("invalid u32 value for key {k}"))?;
+                Ok(())
+            }
             "ca_certificate" => {
                 let certs = g3_yaml::value::as_openssl_certificates(v, Some(lookup_dir))
                     .context(format!("invalid openssl certificate value for key {k}"))?;
@@ -93,6 +145,21 @@ pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
                 )?;
                 Ok(())
             }
+            "refresh_interval" => {
+                refresh_interval = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
+            "refresh_backoff_min" => {
+                refresh_backoff_min = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
+            "refresh_backoff_max" => {
+                refresh_backoff_max = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         })?;
 
@@ -103,20 +170,34 @@ pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
             return Err(anyhow!("no ca private key set"));
         };
 
+        if refresh_interval.is_zero() {
+            return Err(anyhow!("refresh interval should not be zero"));
+        }
+        if refresh_backoff_min.is_zero() {
+            return Err(anyhow!("refresh backoff min should not be zero"));
+        }
+        if refresh_backoff_max < refresh_backoff_min {
+            return Err(anyhow!(
+                "refresh backoff max should not be less than refresh backoff min"
+            ));
+        }
+
         if no_append_ca_cert {
             ca_cert_pem.clear();
         }
-        BACKEND_CONFIG_LOCK
-            .set(Arc::new(OpensslBackendConfig {
-                ca_cert,
-                ca_key,
-                ca_cert_pem,
-                keep_serial,
-                max_ttl,
-                duration_stats,
-            }))
-            .map_err(|_| anyhow!("duplicate backend config"))?;
-        Ok(())
+        Ok(OpensslBackendConfig {
+            name,
+            weight,
+            ca_cert,
+            ca_key,
+            ca_cert_pem,
+            keep_serial,
+            max_ttl,
+            duration_stats,
+            refresh_interval,
+            refresh_backoff_min,
+            refresh_backoff_max,
+        })
     } else {
         Err(anyhow!(
             "yam value type for the backend config should be 'map'"
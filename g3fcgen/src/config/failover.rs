@@ -0,0 +1,115 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+static FAILOVER_CONFIG_LOCK: OnceLock<BackendFailoverConfig> = OnceLock::new();
+
+pub(crate) fn get_config() -> BackendFailoverConfig {
+    FAILOVER_CONFIG_LOCK.get().cloned().unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum BackendPreference {
+    /// Prefer the backends in the configured order
+    #[default]
+    Ordered,
+    /// Prefer the backends by random pick according to their weight
+    Weighted,
+}
+
+impl FromStr for BackendPreference {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ordered" | "order" => Ok(BackendPreference::Ordered),
+            "weighted" | "weight" => Ok(BackendPreference::Weighted),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct BackendFailoverConfig {
+    pub(crate) preference: BackendPreference,
+    pub(crate) unhealthy_after: u32,
+    pub(crate) recover_after: u32,
+    pub(crate) probe_interval: Duration,
+}
+
+impl Default for BackendFailoverConfig {
+    fn default() -> Self {
+        BackendFailoverConfig {
+            preference: BackendPreference::Ordered,
+            unhealthy_after: 3,
+            recover_after: 3,
+            probe_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
+    if let Yaml::Hash(map) = value {
+        let mut config = BackendFailoverConfig::default();
+
+        g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "preference" => {
+                let s = g3_yaml::value::as_string(v)
+                    .context(format!("invalid string value for key {k}"))?;
+                config.preference = BackendPreference::from_str(&s)
+                    .map_err(|_| anyhow!("invalid backend preference value {s}"))?;
+                Ok(())
+            }
+            "unhealthy_after" => {
+                config.unhealthy_after = g3_yaml::value::as_nonzero_u32(v)
+                    .context(format!("invalid nonzero u32 value for key {k}"))?
+                    .get();
+                Ok(())
+            }
+            "recover_after" => {
+                config.recover_after = g3_yaml::value::as_nonzero_u32(v)
+                    .context(format!("invalid nonzero u32 value for key {k}"))?
+                    .get();
+                Ok(())
+            }
+            "probe_interval" => {
+                config.probe_interval = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+
+        if config.probe_interval.is_zero() {
+            return Err(anyhow!("probe interval should not be zero"));
+        }
+
+        FAILOVER_CONFIG_LOCK
+            .set(config)
+            .map_err(|_| anyhow!("duplicate backend failover config"))?;
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "yam value type for the backend failover config should be 'map'"
+        ))
+    }
+}
@@ -22,6 +22,11 @@ use yaml_rust::{yaml, Yaml};
 mod backend;
 pub(crate) use backend::{get_config as get_backend_config, OpensslBackendConfig};
 
+mod failover;
+pub(crate) use failover::{
+    get_config as get_backend_failover_config, BackendFailoverConfig, BackendPreference,
+};
+
 pub fn load() -> anyhow::Result<&'static Path> {
     let config_file =
         g3_daemon::opts::config_file().ok_or_else(|| anyhow!("no config file set"))?;
@@ -40,7 +45,8 @@ fn load_doc(map: &yaml::Hash) -> anyhow::Result<()> {
         "runtime" => g3_daemon::runtime::config::load(v),
         "worker" => g3_daemon::runtime::config::load_worker(v),
         "stat" => g3_daemon::stat::config::load(v, crate::build::PKG_NAME),
-        "backend" => backend::load_config(v),
+        "backend" | "backends" => backend::load_config(v),
+        "backend_failover" => failover::load_config(v),
         _ => Err(anyhow!("invalid key {k} in main conf")),
     })?;
     Ok(())
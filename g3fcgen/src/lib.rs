@@ -35,7 +35,7 @@ use opts::ProcArgs;
 mod stat;
 
 mod backend;
-use backend::{BackendStats, OpensslBackend};
+use backend::{BackendGroup, BackendHealth, BackendStats, OpensslBackend};
 
 mod frontend;
 use frontend::{FrontendStats, GeneratedData, UdpDgramFrontend};
@@ -74,22 +74,44 @@ pub async fn run(proc_args: &ProcArgs) -> anyhow::Result<()> {
     let (req_sender, req_receiver) = flume::bounded::<BackendRequest>(1024);
     let (rsp_sender, rsp_receiver) = flume::bounded::<BackendResponse>(1024);
 
-    let backend_config =
+    let backend_configs =
         config::get_backend_config().ok_or_else(|| anyhow!("no backend config available"))?;
-    let backend_stats = Arc::new(BackendStats::default());
-
-    let (duration_recorder, duration_stats) = backend_config.duration_stats.build_spawned(None);
+    let failover_config = config::get_backend_failover_config();
+    let backend_stats: Vec<Arc<BackendStats>> = backend_configs
+        .iter()
+        .map(|c| Arc::new(BackendStats::new(&c.name)))
+        .collect();
+    let backend_health: Vec<Arc<BackendHealth>> = backend_configs
+        .iter()
+        .map(|_| Arc::new(BackendHealth::new(&failover_config)))
+        .collect();
+
+    let (duration_recorder, duration_stats) = backend_configs[0].duration_stats.build_spawned(None);
+
+    let build_group = || {
+        let mut backends = Vec::with_capacity(backend_configs.len());
+        for (i, config) in backend_configs.iter().enumerate() {
+            let backend = OpensslBackend::new(
+                config,
+                &backend_stats[i],
+                &backend_health[i],
+                failover_config.probe_interval,
+            )
+            .context(format!("failed to build backend {}", config.name))?;
+            backends.push(backend);
+        }
+        Ok::<BackendGroup, anyhow::Error>(BackendGroup::new(backends, failover_config.preference))
+    };
 
     let workers = g3_daemon::runtime::worker::foreach(|h| {
-        let backend = OpensslBackend::new(&backend_config, &backend_stats)
-            .context(format!("failed to build backend for worker {}", h.id))?;
-        backend.spawn(&h.handle, h.id, req_receiver.clone(), rsp_sender.clone());
+        let group =
+            build_group().context(format!("failed to build backend for worker {}", h.id))?;
+        group.spawn(&h.handle, h.id, req_receiver.clone(), rsp_sender.clone());
         Ok::<(), anyhow::Error>(())
     })?;
     if workers < 1 {
-        let backend = OpensslBackend::new(&backend_config, &backend_stats)
-            .context("failed to build backend for main runtime")?;
-        backend.spawn(&Handle::current(), 0, req_receiver, rsp_sender);
+        let group = build_group().context("failed to build backend for main runtime")?;
+        group.spawn(&Handle::current(), 0, req_receiver, rsp_sender);
     }
 
     let frontend_stats = Arc::new(FrontendStats::default());
@@ -20,11 +20,16 @@ use g3_statsd_client::StatsdClient;
 
 use crate::BackendStats;
 
+const TAG_KEY_BACKEND: &str = "backend";
+
 pub(crate) fn emit_stats(client: &mut StatsdClient, s: &BackendStats) {
     macro_rules! emit_count {
         ($take:ident, $name:literal) => {
             let v = s.$take();
-            client.count(concat!("backend.", $name), v).send();
+            client
+                .count(concat!("backend.", $name), v)
+                .with_tag(TAG_KEY_BACKEND, s.name())
+                .send();
         };
     }
 
@@ -32,6 +37,16 @@ pub(crate) fn emit_stats(client: &mut StatsdClient, s: &BackendStats) {
     emit_count!(take_refresh_ok, "refresh_ok");
     emit_count!(take_request_total, "request_total");
     emit_count!(take_request_ok, "request_ok");
+    emit_count!(take_request_failover, "request_failover");
+
+    client
+        .gauge("backend.refresh_backoff", s.get_refresh_backoff_ms())
+        .with_tag(TAG_KEY_BACKEND, s.name())
+        .send();
+    client
+        .gauge("backend.healthy", u8::from(s.is_healthy()))
+        .with_tag(TAG_KEY_BACKEND, s.name())
+        .send();
 }
 
 pub(crate) fn emit_duration_stats(client: &mut StatsdClient, s: &HistogramStats) {
@@ -29,7 +29,7 @@ use super::{BackendStats, FrontendStats};
 
 pub(crate) fn spawn_working_thread(
     config: StatsdClientConfig,
-    backend_stats: Arc<BackendStats>,
+    backend_stats: Vec<Arc<BackendStats>>,
     backend_duration_stats: Arc<HistogramStats>,
     frontend_stats: Arc<FrontendStats>,
 ) -> anyhow::Result<JoinHandle<()>> {
@@ -42,7 +42,9 @@ pub(crate) fn spawn_working_thread(
         .spawn(move || loop {
             let instant_start = Instant::now();
 
-            metrics::backend::emit_stats(&mut client, &backend_stats);
+            for s in &backend_stats {
+                metrics::backend::emit_stats(&mut client, s);
+            }
             metrics::backend::emit_duration_stats(&mut client, &backend_duration_stats);
             metrics::frontend::emit_stats(&mut client, &frontend_stats);
             g3_daemon::runtime::metrics::emit_stats(&mut client);
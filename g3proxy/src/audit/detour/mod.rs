@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::fmt;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -21,7 +22,7 @@ use anyhow::anyhow;
 use tokio::sync::oneshot;
 
 use g3_daemon::server::ServerQuitPolicy;
-use g3_dpi::Protocol;
+use g3_dpi::{Protocol, ProtocolInspectAction};
 use g3_types::net::UpstreamAddr;
 
 use crate::config::audit::AuditStreamDetourConfig;
@@ -36,6 +37,10 @@ use pool::{StreamDetourPool, StreamDetourPoolHandle};
 mod stream;
 use stream::StreamDetourStream;
 
+mod payload;
+use payload::StreamDetourPayloadEncoder;
+pub(crate) use payload::{StreamDetourPayload, PAYLOAD_VERSION_LEGACY, PAYLOAD_VERSION_MAX};
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(u16)]
 pub(crate) enum DetourAction {
@@ -54,19 +59,76 @@ impl From<u16> for DetourAction {
     }
 }
 
+pub(crate) enum StreamDetourFallbackAction {
+    Block,
+    Bypass,
+    Close(anyhow::Error),
+}
+
+/// The way to handle the stream if the stream detour is unavailable.
+///
+/// The action is taken from `stream_detour_on_error`. If not set, the stream will be
+/// bypassed if no stream detour service is configured, and closed with error if we
+/// failed to open the detour stream.
+pub(crate) struct StreamDetourFallback {
+    reason: String,
+    configured: bool,
+    pub(crate) action: StreamDetourFallbackAction,
+}
+
+impl StreamDetourFallback {
+    pub(crate) fn new(on_error: Option<ProtocolInspectAction>, e: Option<anyhow::Error>) -> Self {
+        let reason = match &e {
+            Some(e) => format!("{e:#}"),
+            None => "no stream detour service".to_string(),
+        };
+        let action = match (on_error, e) {
+            (Some(ProtocolInspectAction::Block), _) => StreamDetourFallbackAction::Block,
+            (Some(_), _) => StreamDetourFallbackAction::Bypass,
+            (None, Some(e)) => StreamDetourFallbackAction::Close(e),
+            (None, None) => StreamDetourFallbackAction::Bypass,
+        };
+        StreamDetourFallback {
+            reason,
+            configured: on_error.is_some(),
+            action,
+        }
+    }
+}
+
+impl fmt::Display for StreamDetourFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.action {
+            StreamDetourFallbackAction::Block => "block",
+            StreamDetourFallbackAction::Bypass => "bypass",
+            StreamDetourFallbackAction::Close(_) => "close",
+        };
+        let source = if self.configured {
+            "as set in stream_detour_on_error"
+        } else {
+            "by default"
+        };
+        write!(
+            f,
+            "stream detour unavailable: {}, {action} {source}",
+            self.reason
+        )
+    }
+}
+
 pub(crate) struct StreamDetourContext<'a, SC> {
     server_config: &'a Arc<SC>,
     server_quit_policy: &'a Arc<ServerQuitPolicy>,
     task_notes: &'a StreamInspectTaskNotes,
     upstream: &'a UpstreamAddr,
     protocol: Protocol,
-    payload: Vec<u8>,
+    payload: StreamDetourPayloadEncoder,
     request_timeout: Duration,
 }
 
 impl<SC> StreamDetourContext<'_, SC> {
-    pub(crate) fn set_payload(&mut self, payload: Vec<u8>) {
-        self.payload = payload;
+    pub(crate) fn set_payload(&mut self, payload: StreamDetourPayload<'_>) {
+        self.payload.push_payload(payload);
     }
 }
 
@@ -103,7 +165,11 @@ impl StreamDetourClient {
             task_notes,
             upstream,
             protocol,
-            payload: Vec::new(),
+            payload: StreamDetourPayloadEncoder::new(
+                self.config.payload_version,
+                task_notes,
+                upstream,
+            ),
             request_timeout: self.config.request_timeout,
         }
     }
@@ -133,3 +199,69 @@ impl StreamDetourClient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_unset() {
+        let fallback = StreamDetourFallback::new(None, None);
+        assert!(matches!(
+            fallback.action,
+            StreamDetourFallbackAction::Bypass
+        ));
+        assert_eq!(
+            fallback.to_string(),
+            "stream detour unavailable: no stream detour service, bypass by default"
+        );
+
+        let fallback = StreamDetourFallback::new(None, Some(anyhow!("connect refused")));
+        assert!(matches!(
+            fallback.action,
+            StreamDetourFallbackAction::Close(_)
+        ));
+        assert_eq!(
+            fallback.to_string(),
+            "stream detour unavailable: connect refused, close by default"
+        );
+    }
+
+    #[test]
+    fn fallback_block() {
+        let fallback = StreamDetourFallback::new(Some(ProtocolInspectAction::Block), None);
+        assert!(matches!(fallback.action, StreamDetourFallbackAction::Block));
+        assert_eq!(
+            fallback.to_string(),
+            "stream detour unavailable: no stream detour service, block as set in stream_detour_on_error"
+        );
+
+        let fallback = StreamDetourFallback::new(
+            Some(ProtocolInspectAction::Block),
+            Some(anyhow!("connect refused")),
+        );
+        assert!(matches!(fallback.action, StreamDetourFallbackAction::Block));
+    }
+
+    #[test]
+    fn fallback_bypass() {
+        let fallback = StreamDetourFallback::new(Some(ProtocolInspectAction::Bypass), None);
+        assert!(matches!(
+            fallback.action,
+            StreamDetourFallbackAction::Bypass
+        ));
+
+        let fallback = StreamDetourFallback::new(
+            Some(ProtocolInspectAction::Bypass),
+            Some(anyhow!("connect refused")),
+        );
+        assert!(matches!(
+            fallback.action,
+            StreamDetourFallbackAction::Bypass
+        ));
+        assert_eq!(
+            fallback.to_string(),
+            "stream detour unavailable: connect refused, bypass as set in stream_detour_on_error"
+        );
+    }
+}
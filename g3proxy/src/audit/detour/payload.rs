@@ -0,0 +1,157 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use g3_types::net::{UpstreamAddr, WebSocketNotes};
+
+use crate::inspect::StreamInspectTaskNotes;
+
+/// The legacy payload format, which is protocol specific and has no common fields
+pub(crate) const PAYLOAD_VERSION_LEGACY: u8 = 0;
+/// The typed payload format, which is a list of TLV entries
+pub(crate) const PAYLOAD_VERSION_TLV: u8 = 1;
+pub(crate) const PAYLOAD_VERSION_MAX: u8 = PAYLOAD_VERSION_TLV;
+
+const TLV_TYPE_UPSTREAM: u8 = 0x01;
+const TLV_TYPE_USERNAME: u8 = 0x02;
+const TLV_TYPE_TLS_SERVER_NAME: u8 = 0x03;
+const TLV_TYPE_TLS_CLIENT_SUBJECT: u8 = 0x04;
+
+const TLV_TYPE_WEBSOCKET_RESOURCE_NAME: u8 = 0x10;
+const TLV_TYPE_WEBSOCKET_ORIGIN: u8 = 0x11;
+const TLV_TYPE_WEBSOCKET_SUB_PROTOCOL: u8 = 0x12;
+const TLV_TYPE_WEBSOCKET_VERSION: u8 = 0x13;
+const TLV_TYPE_WEBSOCKET_EXTENSIONS: u8 = 0x14;
+
+/// Protocol specific payload data that will be sent to the detour server
+pub(crate) enum StreamDetourPayload<'a> {
+    WebSocket(&'a WebSocketNotes),
+}
+
+/// Encoder for the detour payload data.
+///
+/// For the TLV format, each entry is a 1-byte type, followed by a 2-bytes
+/// big-endian length and the value. Entries with too long value will be
+/// skipped, and the detour server should ignore unknown types.
+pub(super) struct StreamDetourPayloadEncoder {
+    version: u8,
+    buf: Vec<u8>,
+}
+
+impl StreamDetourPayloadEncoder {
+    pub(super) fn new(
+        version: u8,
+        task_notes: &StreamInspectTaskNotes,
+        upstream: &UpstreamAddr,
+    ) -> Self {
+        let mut encoder = StreamDetourPayloadEncoder {
+            version,
+            buf: Vec::new(),
+        };
+        if version >= PAYLOAD_VERSION_TLV {
+            encoder.push_tlv(TLV_TYPE_UPSTREAM, upstream.to_string().as_bytes());
+            if let Some(name) = task_notes.raw_username() {
+                encoder.push_tlv(TLV_TYPE_USERNAME, name.as_bytes());
+            }
+            if let Some(name) = task_notes.tls_server_name() {
+                encoder.push_tlv(TLV_TYPE_TLS_SERVER_NAME, name.as_bytes());
+            }
+            if let Some(subject) = task_notes.tls_client_subject() {
+                encoder.push_tlv(TLV_TYPE_TLS_CLIENT_SUBJECT, subject.as_bytes());
+            }
+        }
+        encoder
+    }
+
+    #[inline]
+    pub(super) fn version(&self) -> u8 {
+        self.version
+    }
+
+    #[inline]
+    pub(super) fn data(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn push_tlv(&mut self, t: u8, v: &[u8]) {
+        let Ok(len) = u16::try_from(v.len()) else {
+            return;
+        };
+        self.buf.push(t);
+        self.buf.extend_from_slice(&len.to_be_bytes());
+        self.buf.extend_from_slice(v);
+    }
+
+    pub(super) fn push_payload(&mut self, payload: StreamDetourPayload<'_>) {
+        match payload {
+            StreamDetourPayload::WebSocket(notes) => {
+                if self.version < PAYLOAD_VERSION_TLV {
+                    self.buf = notes.serialize();
+                    return;
+                }
+
+                self.push_tlv(
+                    TLV_TYPE_WEBSOCKET_RESOURCE_NAME,
+                    notes.resource_name().as_bytes(),
+                );
+                if let Some(v) = notes.origin() {
+                    self.push_tlv(TLV_TYPE_WEBSOCKET_ORIGIN, v.as_bytes());
+                }
+                if let Some(v) = notes.sub_protocol() {
+                    self.push_tlv(TLV_TYPE_WEBSOCKET_SUB_PROTOCOL, v.as_bytes());
+                }
+                if let Some(v) = notes.version() {
+                    self.push_tlv(TLV_TYPE_WEBSOCKET_VERSION, v.as_bytes());
+                }
+                for v in notes.extensions() {
+                    self.push_tlv(TLV_TYPE_WEBSOCKET_EXTENSIONS, v.as_bytes());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{header, HeaderValue, Uri};
+
+    #[test]
+    fn encode_websocket() {
+        let mut notes = WebSocketNotes::new(Uri::from_static("/chat?a=b"));
+        notes.append_request_header(&header::ORIGIN, &HeaderValue::from_static("a.example"));
+        notes.append_response_header(
+            &header::SEC_WEBSOCKET_PROTOCOL,
+            &HeaderValue::from_static("chat"),
+        );
+
+        let mut encoder = StreamDetourPayloadEncoder {
+            version: PAYLOAD_VERSION_TLV,
+            buf: Vec::new(),
+        };
+        encoder.push_payload(StreamDetourPayload::WebSocket(&notes));
+        assert_eq!(
+            encoder.data(),
+            b"\x10\x00\x05/chat\x11\x00\x09a.example\x12\x00\x04chat"
+        );
+
+        let mut encoder = StreamDetourPayloadEncoder {
+            version: PAYLOAD_VERSION_LEGACY,
+            buf: Vec::new(),
+        };
+        encoder.push_payload(StreamDetourPayload::WebSocket(&notes));
+        assert_eq!(encoder.data(), notes.serialize().as_slice());
+    }
+}
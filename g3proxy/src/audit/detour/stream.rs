@@ -14,6 +14,8 @@
  * limitations under the License.
  */
 
+use std::time::Duration;
+
 use anyhow::anyhow;
 use quinn::{RecvStream, SendStream};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
@@ -82,13 +84,34 @@ where
             tokio::time::interval_at(Instant::now() + idle_duration, idle_duration);
         let mut idle_count = 0;
 
+        let half_close_linger_timeout = self.server_config.tcp_half_close_linger_timeout();
+        // the error to return when the relay finished, set when the first direction is half-closed
+        let mut half_closed: Option<ServerTaskError> = None;
+        let mut clt_to_d_done = false;
+        let mut d_to_ups_done = false;
+        let mut ups_to_d_done = false;
+        let mut d_to_clt_done = false;
+        // will be reset when the first direction is half-closed
+        let linger_sleep = tokio::time::sleep(Duration::ZERO);
+        tokio::pin!(linger_sleep);
+
         loop {
             tokio::select! {
                 biased;
 
-                r = &mut clt_to_d => {
+                r = &mut clt_to_d, if !clt_to_d_done => {
                     return match r {
                         Ok(_) => {
+                            if let Some(timeout) = half_close_linger_timeout {
+                                // propagate the FIN to detour service and keep relaying
+                                clt_to_d_done = true;
+                                let _ = clt_to_d.writer().finish();
+                                if half_closed.is_none() {
+                                    half_closed = Some(ServerTaskError::ClosedByClient);
+                                    linger_sleep.as_mut().reset(Instant::now() + timeout);
+                                }
+                                continue;
+                            }
                             self.relay_after_client_closed(north_send, south_send, d_to_ups).await;
                             Err(ServerTaskError::ClosedByClient)
                         },
@@ -106,8 +129,17 @@ where
                         },
                     };
                 }
-                r = &mut d_to_ups => {
+                r = &mut d_to_ups, if !d_to_ups_done => {
                     return match r {
+                        Ok(_) if clt_to_d_done => {
+                            // the detour service has finished the client stream after the client half-closed
+                            d_to_ups_done = true;
+                            let _ = d_to_ups.writer().shutdown().await;
+                            if !d_to_clt_done {
+                                continue;
+                            }
+                            Err(half_closed.take().unwrap_or(ServerTaskError::ClosedByClient))
+                        }
                         Ok(_) => {
                             self.relay_after_detour_failed(south_send, d_to_ups, d_to_clt).await;
                             Err(
@@ -130,9 +162,19 @@ where
                         },
                     };
                 }
-                r = &mut ups_to_d => {
+                r = &mut ups_to_d, if !ups_to_d_done => {
                     return match r {
                         Ok(_) => {
+                            if let Some(timeout) = half_close_linger_timeout {
+                                // propagate the FIN to detour service and keep relaying
+                                ups_to_d_done = true;
+                                let _ = ups_to_d.writer().finish();
+                                if half_closed.is_none() {
+                                    half_closed = Some(ServerTaskError::ClosedByUpstream);
+                                    linger_sleep.as_mut().reset(Instant::now() + timeout);
+                                }
+                                continue;
+                            }
                             self.relay_after_remote_closed(north_send, south_send, d_to_clt).await;
                             Err(ServerTaskError::ClosedByUpstream)
                         },
@@ -150,8 +192,17 @@ where
                         },
                     };
                 }
-                r = &mut d_to_clt => {
+                r = &mut d_to_clt, if !d_to_clt_done => {
                     return match r {
+                        Ok(_) if ups_to_d_done => {
+                            // the detour service has finished the remote stream after the upstream half-closed
+                            d_to_clt_done = true;
+                            let _ = d_to_clt.writer().shutdown().await;
+                            if !d_to_ups_done {
+                                continue;
+                            }
+                            Err(half_closed.take().unwrap_or(ServerTaskError::ClosedByUpstream))
+                        }
                         Ok(_) => {
                             self.relay_after_detour_failed(north_send, d_to_ups, d_to_clt).await;
                             Err(
@@ -174,6 +225,11 @@ where
                         },
                     };
                 }
+                _ = &mut linger_sleep, if half_closed.is_some() => {
+                    let _ = d_to_ups.write_flush().await;
+                    let _ = d_to_clt.write_flush().await;
+                    return Err(half_closed.take().unwrap());
+                }
                 _ = idle_interval.tick() => {
                     if clt_to_d.is_idle() && d_to_clt.is_idle() && ups_to_d.is_idle() && d_to_ups.is_idle() {
                         idle_count += 1;
@@ -229,7 +285,11 @@ where
         ppv2.push_upstream(self.upstream)?;
         ppv2.push_match_id(match_id)?;
         ppv2.push_protocol(self.protocol.as_str())?;
-        ppv2.push_payload_len(self.payload.len())?;
+        let payload_version = self.payload.version();
+        if payload_version > 0 {
+            ppv2.push_payload_version(payload_version)?;
+        }
+        ppv2.push_payload_len(self.payload.data().len())?;
         Ok(ppv2)
     }
 
@@ -257,10 +317,11 @@ where
             .write_all(client_ppv2.finalize())
             .await
             .map_err(|e| anyhow!("failed to send ppv2 header for client stream: {e}"))?;
-        if !self.payload.is_empty() {
+        let payload = self.payload.data();
+        if !payload.is_empty() {
             detour_stream
                 .north_send
-                .write_all(&self.payload)
+                .write_all(payload)
                 .await
                 .map_err(|e| anyhow!("failed to send payload data: {e}"))?;
         }
@@ -15,12 +15,14 @@
  */
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use slog::Logger;
 
 use g3_dpi::{
-    H1InterceptionConfig, H2InterceptionConfig, ImapInterceptionConfig, ProtocolInspectPolicy,
-    ProtocolInspectionConfig, ProtocolPortMap, SmtpInterceptionConfig,
+    H1InterceptionConfig, H2InterceptionConfig, ImapInterceptionConfig, Pop3InterceptionConfig,
+    Protocol, ProtocolInspectAction, ProtocolInspectPolicy, ProtocolInspectPolicyBuilder,
+    ProtocolInspectionConfig, ProtocolPortMap, SmtpInterceptionConfig, WebSocketInterceptionConfig,
 };
 use g3_icap_client::reqmod::IcapReqmodClient;
 use g3_icap_client::respmod::IcapRespmodClient;
@@ -29,13 +31,16 @@ use super::Auditor;
 #[cfg(feature = "quic")]
 use super::StreamDetourClient;
 use crate::config::audit::AuditorConfig;
-use crate::inspect::tls::TlsInterceptionContext;
+use crate::config::server::ProtocolInspectPolicyOverride;
+use crate::inspect::tls::{TlsInterceptionContext, TlsSniFilter};
+use crate::inspect::InspectPolicyStats;
 
 pub(crate) struct AuditHandle {
     auditor_config: Arc<AuditorConfig>,
     server_tcp_portmap: Arc<ProtocolPortMap>,
     client_tcp_portmap: Arc<ProtocolPortMap>,
     tls_interception: Option<TlsInterceptionContext>,
+    tls_sni_filter: Option<Arc<TlsSniFilter>>,
     inspect_logger: Logger,
     intercept_logger: Logger,
     icap_reqmod_client: Option<IcapReqmodClient>,
@@ -46,10 +51,15 @@ pub(crate) struct AuditHandle {
     pub(crate) websocket_inspect_policy: ProtocolInspectPolicy,
     pub(crate) smtp_inspect_policy: ProtocolInspectPolicy,
     pub(crate) imap_inspect_policy: ProtocolInspectPolicy,
+    pub(crate) pop3_inspect_policy: ProtocolInspectPolicy,
+    inspect_policy_stats: Arc<InspectPolicyStats>,
 }
 
 impl AuditHandle {
-    pub(super) fn new(auditor: &Auditor) -> Self {
+    pub(super) fn new(
+        auditor: &Auditor,
+        policy_override: Option<&ProtocolInspectPolicyOverride>,
+    ) -> Self {
         let icap_reqmod_service = auditor
             .icap_reqmod_service
             .as_ref()
@@ -63,16 +73,38 @@ impl AuditHandle {
             server_tcp_portmap: auditor.server_tcp_portmap.clone(),
             client_tcp_portmap: auditor.client_tcp_portmap.clone(),
             tls_interception: None,
+            tls_sni_filter: auditor
+                .config
+                .tls_sni_filter
+                .as_ref()
+                .map(|c| Arc::new(TlsSniFilter::new(c))),
             inspect_logger: crate::log::inspect::get_logger(auditor.config.name()),
             intercept_logger: crate::log::intercept::get_logger(auditor.config.name()),
             icap_reqmod_client: icap_reqmod_service,
             icap_respmod_client: icap_respmod_service,
             #[cfg(feature = "quic")]
             stream_detour_client: auditor.stream_detour_service.clone(),
-            h2_inspect_policy: auditor.config.h2_inspect_policy.build(),
-            websocket_inspect_policy: auditor.config.websocket_inspect_policy.build(),
-            smtp_inspect_policy: auditor.config.smtp_inspect_policy.build(),
-            imap_inspect_policy: auditor.config.imap_inspect_policy.build(),
+            h2_inspect_policy: build_inspect_policy(
+                &auditor.config.h2_inspect_policy,
+                policy_override.and_then(|o| o.h2.as_ref()),
+            ),
+            websocket_inspect_policy: build_inspect_policy(
+                &auditor.config.websocket_inspect_policy,
+                policy_override.and_then(|o| o.websocket.as_ref()),
+            ),
+            smtp_inspect_policy: build_inspect_policy(
+                &auditor.config.smtp_inspect_policy,
+                policy_override.and_then(|o| o.smtp.as_ref()),
+            ),
+            imap_inspect_policy: build_inspect_policy(
+                &auditor.config.imap_inspect_policy,
+                policy_override.and_then(|o| o.imap.as_ref()),
+            ),
+            pop3_inspect_policy: build_inspect_policy(
+                &auditor.config.pop3_inspect_policy,
+                policy_override.and_then(|o| o.pop3.as_ref()),
+            ),
+            inspect_policy_stats: InspectPolicyStats::get_or_insert(auditor.config.name()),
         }
     }
 
@@ -80,6 +112,11 @@ impl AuditHandle {
         self.tls_interception = Some(ctx);
     }
 
+    #[inline]
+    pub(crate) fn inspect_policy_stats(&self) -> &InspectPolicyStats {
+        &self.inspect_policy_stats
+    }
+
     #[inline]
     pub(crate) fn inspect_logger(&self) -> &Logger {
         &self.inspect_logger
@@ -90,6 +127,11 @@ impl AuditHandle {
         &self.intercept_logger
     }
 
+    #[cfg(test)]
+    pub(super) fn set_intercept_logger(&mut self, logger: Logger) {
+        self.intercept_logger = logger;
+    }
+
     #[inline]
     pub(crate) fn protocol_inspection(&self) -> &ProtocolInspectionConfig {
         &self.auditor_config.protocol_inspection
@@ -110,6 +152,20 @@ impl AuditHandle {
         self.tls_interception.clone()
     }
 
+    #[inline]
+    pub(crate) fn tls_sni_filter(&self) -> Option<&Arc<TlsSniFilter>> {
+        self.tls_sni_filter.as_ref()
+    }
+
+    /// Get the max ClientHello size if SNI peek is enabled for TLS streams that won't be intercepted
+    pub(crate) fn tls_sni_peek(&self) -> Option<u32> {
+        if self.auditor_config.tls_sni_peek {
+            Some(self.auditor_config.tls_max_client_hello_size)
+        } else {
+            None
+        }
+    }
+
     #[inline]
     pub(crate) fn log_uri_max_chars(&self) -> usize {
         self.auditor_config.log_uri_max_chars
@@ -130,11 +186,37 @@ impl AuditHandle {
         &self.auditor_config.smtp_interception
     }
 
+    #[inline]
+    pub(crate) fn websocket_interception(&self) -> &WebSocketInterceptionConfig {
+        &self.auditor_config.websocket_interception
+    }
+
+    #[inline]
+    pub(crate) fn protocol_tcp_no_delay(&self, protocol: Protocol) -> Option<bool> {
+        self.auditor_config.protocol_tcp_no_delay.get(protocol)
+    }
+
+    #[inline]
+    pub(crate) fn protocol_max_idle_count(
+        &self,
+        protocol: Protocol,
+        idle_duration: Duration,
+    ) -> Option<i32> {
+        self.auditor_config
+            .protocol_idle_timeout
+            .max_idle_count(protocol, idle_duration)
+    }
+
     #[inline]
     pub(crate) fn imap_interception(&self) -> &ImapInterceptionConfig {
         &self.auditor_config.imap_interception
     }
 
+    #[inline]
+    pub(crate) fn pop3_interception(&self) -> &Pop3InterceptionConfig {
+        &self.auditor_config.pop3_interception
+    }
+
     #[inline]
     pub(crate) fn icap_reqmod_client(&self) -> Option<&IcapReqmodClient> {
         self.icap_reqmod_client.as_ref()
@@ -151,6 +233,18 @@ impl AuditHandle {
         self.stream_detour_client.as_ref()
     }
 
+    #[inline]
+    pub(crate) fn inspect_dry_run(&self) -> bool {
+        self.auditor_config.inspect_dry_run
+    }
+
+    /// The action to take if the stream detour service is not available
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub(crate) fn stream_detour_on_error(&self) -> Option<ProtocolInspectAction> {
+        self.auditor_config.stream_detour_on_error
+    }
+
     pub(crate) fn do_task_audit(&self) -> bool {
         use rand::distributions::Distribution;
 
@@ -158,3 +252,11 @@ impl AuditHandle {
         self.auditor_config.task_audit_ratio.sample(&mut rng)
     }
 }
+
+/// The server level policy takes precedence over the auditor level one
+fn build_inspect_policy(
+    auditor_policy: &ProtocolInspectPolicyBuilder,
+    server_policy: Option<&ProtocolInspectPolicyBuilder>,
+) -> ProtocolInspectPolicy {
+    server_policy.unwrap_or(auditor_policy).build()
+}
@@ -24,7 +24,8 @@ use g3_types::metrics::MetricsName;
 use g3_types::net::{OpensslTicketKey, RollingTicketer};
 
 use crate::config::audit::AuditorConfig;
-use crate::inspect::tls::TlsInterceptionContext;
+use crate::config::server::ProtocolInspectPolicyOverride;
+use crate::inspect::tls::{TlsHandshakeLimiter, TlsInterceptionContext};
 
 mod ops;
 pub use ops::load_all;
@@ -36,18 +37,25 @@ pub(crate) use registry::{get_names, get_or_insert_default};
 mod handle;
 pub(crate) use handle::AuditHandle;
 
+mod stats;
+pub(crate) use stats::{foreach_icap_pool_stats, IcapPoolStats};
+
 #[cfg(feature = "quic")]
 mod detour;
 #[cfg(feature = "quic")]
-pub(crate) use detour::DetourAction;
-#[cfg(feature = "quic")]
 use detour::StreamDetourClient;
+#[cfg(feature = "quic")]
+pub(crate) use detour::{
+    DetourAction, StreamDetourFallback, StreamDetourFallbackAction, StreamDetourPayload,
+    PAYLOAD_VERSION_LEGACY, PAYLOAD_VERSION_MAX,
+};
 
 pub(crate) struct Auditor {
     config: Arc<AuditorConfig>,
     server_tcp_portmap: Arc<ProtocolPortMap>,
     client_tcp_portmap: Arc<ProtocolPortMap>,
     tls_rolling_ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
+    tls_handshake_limiter: Option<Arc<TlsHandshakeLimiter>>,
     icap_reqmod_service: Option<Arc<IcapServiceClient>>,
     icap_respmod_service: Option<Arc<IcapServiceClient>>,
     #[cfg(feature = "quic")]
@@ -64,6 +72,7 @@ impl Auditor {
             server_tcp_portmap,
             client_tcp_portmap,
             tls_rolling_ticketer: None,
+            tls_handshake_limiter: None,
             icap_reqmod_service: None,
             icap_respmod_service: None,
             #[cfg(feature = "quic")]
@@ -83,11 +92,15 @@ impl Auditor {
         } else {
             None
         };
+        let tls_handshake_limiter = config
+            .tls_handshake_limit
+            .map(|c| Arc::new(TlsHandshakeLimiter::new(c)));
         let mut auditor = Auditor {
             config: Arc::new(config),
             server_tcp_portmap,
             client_tcp_portmap,
             tls_rolling_ticketer,
+            tls_handshake_limiter,
             icap_reqmod_service: None,
             icap_respmod_service: None,
             #[cfg(feature = "quic")]
@@ -110,11 +123,19 @@ impl Auditor {
         } else {
             None
         };
+        // keep the in-progress handshakes counted if the limit config is not changed
+        let tls_handshake_limiter = match (&self.tls_handshake_limiter, config.tls_handshake_limit)
+        {
+            (Some(limiter), Some(c)) if limiter.config().eq(&c) => Some(limiter.clone()),
+            (_, Some(c)) => Some(Arc::new(TlsHandshakeLimiter::new(c))),
+            (_, None) => None,
+        };
         let mut auditor = Auditor {
             config: Arc::new(config),
             server_tcp_portmap,
             client_tcp_portmap,
             tls_rolling_ticketer,
+            tls_handshake_limiter,
             icap_reqmod_service: None,
             icap_respmod_service: None,
             #[cfg(feature = "quic")]
@@ -126,13 +147,17 @@ impl Auditor {
 
     fn set_agent_clients(&mut self) -> anyhow::Result<()> {
         if let Some(c) = self.config.icap_reqmod_service.clone() {
+            let icap_pool_stats = IcapPoolStats::get_or_insert(self.config.name());
             self.icap_reqmod_service = Some(Arc::new(
-                IcapServiceClient::new(c).context("failed to create ICAP REQMOD client")?,
+                IcapServiceClient::with_pool_stats(c, icap_pool_stats.reqmod.clone())
+                    .context("failed to create ICAP REQMOD client")?,
             ));
         }
         if let Some(c) = self.config.icap_respmod_service.clone() {
+            let icap_pool_stats = IcapPoolStats::get_or_insert(self.config.name());
             self.icap_respmod_service = Some(Arc::new(
-                IcapServiceClient::new(c).context("failed to create ICAP RESPMOD client")?,
+                IcapServiceClient::with_pool_stats(c, icap_pool_stats.respmod.clone())
+                    .context("failed to create ICAP RESPMOD client")?,
             ));
         }
         #[cfg(feature = "quic")]
@@ -143,8 +168,28 @@ impl Auditor {
         Ok(())
     }
 
-    pub(crate) fn build_handle(&self) -> anyhow::Result<Arc<AuditHandle>> {
-        let mut handle = AuditHandle::new(self);
+    #[cfg(test)]
+    pub(crate) fn build_test_handle(config: AuditorConfig) -> Arc<AuditHandle> {
+        let auditor = Auditor::new_with_config(config).unwrap();
+        Arc::new(AuditHandle::new(&auditor, None))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn build_test_handle_with_intercept_logger(
+        config: AuditorConfig,
+        logger: slog::Logger,
+    ) -> Arc<AuditHandle> {
+        let auditor = Auditor::new_with_config(config).unwrap();
+        let mut handle = AuditHandle::new(&auditor, None);
+        handle.set_intercept_logger(logger);
+        Arc::new(handle)
+    }
+
+    pub(crate) fn build_handle(
+        &self,
+        policy_override: Option<&ProtocolInspectPolicyOverride>,
+    ) -> anyhow::Result<Arc<AuditHandle>> {
+        let mut handle = AuditHandle::new(self, policy_override);
 
         if let Some(cert_agent_config) = &self.config.tls_cert_agent {
             let cert_agent = cert_agent_config
@@ -160,12 +205,21 @@ impl Auditor {
                 .tls_interception_server
                 .build_with_ticketer(self.tls_rolling_ticketer.as_ref())
                 .context("failed to build tls server config")?;
-            let ctx = TlsInterceptionContext::new(
+            let mut ctx = TlsInterceptionContext::new(
+                self.config.name(),
                 cert_agent,
                 client_config,
                 server_config,
                 self.config.tls_stream_dump,
+                self.config.tls_stream_pcap.clone(),
+                self.tls_handshake_limiter.clone(),
             )?;
+            if let Some(filter) = handle.tls_sni_filter() {
+                ctx.set_sni_filter(filter.clone());
+            }
+            if let Some(builder) = &self.config.tls_client_subject_acl {
+                ctx.set_client_subject_acl(Arc::new(builder.build()));
+            }
             handle.set_tls_interception(ctx);
         }
 
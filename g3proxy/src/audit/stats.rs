@@ -0,0 +1,60 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ahash::AHashMap;
+
+use g3_types::metrics::MetricsName;
+use g3_types::stats::ConnectionPoolStats;
+
+static ICAP_POOL_STATS_REGISTRY: LazyLock<Mutex<AHashMap<MetricsName, Arc<IcapPoolStats>>>> =
+    LazyLock::new(|| Mutex::new(AHashMap::new()));
+
+/// ICAP connection pool stats of an auditor, which will be kept across reload
+pub(crate) struct IcapPoolStats {
+    auditor: MetricsName,
+    pub(crate) reqmod: Arc<ConnectionPoolStats>,
+    pub(crate) respmod: Arc<ConnectionPoolStats>,
+}
+
+impl IcapPoolStats {
+    pub(super) fn get_or_insert(auditor: &MetricsName) -> Arc<Self> {
+        let mut ht = ICAP_POOL_STATS_REGISTRY.lock().unwrap();
+        ht.entry(auditor.clone())
+            .or_insert_with(|| {
+                Arc::new(IcapPoolStats {
+                    auditor: auditor.clone(),
+                    reqmod: Arc::new(ConnectionPoolStats::default()),
+                    respmod: Arc::new(ConnectionPoolStats::default()),
+                })
+            })
+            .clone()
+    }
+
+    #[inline]
+    pub(crate) fn auditor(&self) -> &MetricsName {
+        &self.auditor
+    }
+}
+
+pub(crate) fn foreach_icap_pool_stats<F>(f: F)
+where
+    F: FnMut(&Arc<IcapPoolStats>),
+{
+    let ht = ICAP_POOL_STATS_REGISTRY.lock().unwrap();
+    ht.values().for_each(f);
+}
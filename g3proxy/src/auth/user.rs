@@ -40,6 +40,7 @@ use super::{
     UserSites, UserTrafficStats, UserType, UserUpstreamTrafficStats,
 };
 use crate::config::auth::{UserAuditConfig, UserConfig};
+use crate::module::http_header::ConditionalAppendHeaders;
 
 pub(crate) struct User {
     config: Arc<UserConfig>,
@@ -179,6 +180,14 @@ impl User {
         Ok(user)
     }
 
+    #[cfg(test)]
+    pub(crate) fn build_test_user(config: UserConfig) -> Arc<User> {
+        use std::str::FromStr;
+
+        let group = MetricsName::from_str("test").unwrap();
+        Arc::new(User::new(&group, &Arc::new(config), &Utc::now()).unwrap())
+    }
+
     pub(super) fn new_for_reload(
         &self,
         config: &Arc<UserConfig>,
@@ -663,6 +672,14 @@ impl User {
         self.config.log_uri_max_chars
     }
 
+    pub(crate) fn smtp_max_recipients(&self) -> Option<usize> {
+        self.config.smtp_max_recipients
+    }
+
+    pub(crate) fn conditional_append_headers(&self) -> &[ConditionalAppendHeaders] {
+        &self.config.conditional_append_headers
+    }
+
     #[inline]
     pub(crate) fn tcp_all_upload_speed_limit(&self) -> Option<&Arc<GlobalStreamLimiter>> {
         self.tcp_all_upload_speed_limit.as_ref()
@@ -22,12 +22,13 @@ use yaml_rust::{yaml, Yaml};
 
 use g3_cert_agent::CertAgentConfig;
 use g3_dpi::{
-    H1InterceptionConfig, H2InterceptionConfig, ImapInterceptionConfig,
-    ProtocolInspectPolicyBuilder, ProtocolInspectionConfig, ProtocolPortMap,
-    SmtpInterceptionConfig,
+    H1InterceptionConfig, H2InterceptionConfig, ImapInterceptionConfig, Pop3InterceptionConfig,
+    ProtocolInspectAction, ProtocolInspectPolicyBuilder, ProtocolInspectionConfig, ProtocolPortMap,
+    SmtpInterceptionConfig, WebSocketInterceptionConfig,
 };
 use g3_icap_client::IcapServiceConfig;
 use g3_tls_ticket::TlsTicketConfig;
+use g3_types::acl::AclRegexSetRuleBuilder;
 use g3_types::metrics::MetricsName;
 use g3_types::net::{
     OpensslInterceptionClientConfigBuilder, OpensslInterceptionServerConfigBuilder,
@@ -37,12 +38,19 @@ use g3_yaml::YamlDocPosition;
 
 #[cfg(feature = "quic")]
 use super::AuditStreamDetourConfig;
+use super::{
+    AuditStreamPcapConfig, ProtocolIdleTimeoutConfig, ProtocolTcpNoDelayConfig,
+    TlsHandshakeLimitConfig, TlsSniFilterConfig,
+};
 
 #[derive(Clone)]
 pub(crate) struct AuditorConfig {
     name: MetricsName,
     position: Option<YamlDocPosition>,
     pub(crate) protocol_inspection: ProtocolInspectionConfig,
+    pub(crate) inspect_dry_run: bool,
+    pub(crate) protocol_tcp_no_delay: ProtocolTcpNoDelayConfig,
+    pub(crate) protocol_idle_timeout: ProtocolIdleTimeoutConfig,
     pub(crate) server_tcp_portmap: ProtocolPortMap,
     pub(crate) client_tcp_portmap: ProtocolPortMap,
     pub(crate) tls_cert_agent: Option<CertAgentConfig>,
@@ -50,19 +58,30 @@ pub(crate) struct AuditorConfig {
     pub(crate) tls_interception_client: OpensslInterceptionClientConfigBuilder,
     pub(crate) tls_interception_server: OpensslInterceptionServerConfigBuilder,
     pub(crate) tls_stream_dump: Option<StreamDumpConfig>,
+    pub(crate) tls_stream_pcap: Option<Arc<AuditStreamPcapConfig>>,
+    pub(crate) tls_handshake_limit: Option<TlsHandshakeLimitConfig>,
+    pub(crate) tls_sni_filter: Option<TlsSniFilterConfig>,
+    pub(crate) tls_client_subject_acl: Option<AclRegexSetRuleBuilder>,
+    pub(crate) tls_sni_peek: bool,
+    pub(crate) tls_max_client_hello_size: u32,
     pub(crate) log_uri_max_chars: usize,
     pub(crate) h1_interception: H1InterceptionConfig,
     pub(crate) h2_inspect_policy: ProtocolInspectPolicyBuilder,
     pub(crate) h2_interception: H2InterceptionConfig,
     pub(crate) websocket_inspect_policy: ProtocolInspectPolicyBuilder,
+    pub(crate) websocket_interception: WebSocketInterceptionConfig,
     pub(crate) smtp_inspect_policy: ProtocolInspectPolicyBuilder,
     pub(crate) smtp_interception: SmtpInterceptionConfig,
     pub(crate) imap_inspect_policy: ProtocolInspectPolicyBuilder,
     pub(crate) imap_interception: ImapInterceptionConfig,
+    pub(crate) pop3_inspect_policy: ProtocolInspectPolicyBuilder,
+    pub(crate) pop3_interception: Pop3InterceptionConfig,
     pub(crate) icap_reqmod_service: Option<Arc<IcapServiceConfig>>,
     pub(crate) icap_respmod_service: Option<Arc<IcapServiceConfig>>,
     #[cfg(feature = "quic")]
     pub(crate) stream_detour_service: Option<Arc<AuditStreamDetourConfig>>,
+    #[cfg(feature = "quic")]
+    pub(crate) stream_detour_on_error: Option<ProtocolInspectAction>,
     pub(crate) task_audit_ratio: Bernoulli,
 }
 
@@ -80,6 +99,9 @@ impl AuditorConfig {
             name,
             position,
             protocol_inspection: Default::default(),
+            inspect_dry_run: false,
+            protocol_tcp_no_delay: Default::default(),
+            protocol_idle_timeout: Default::default(),
             server_tcp_portmap: ProtocolPortMap::tcp_server(),
             client_tcp_portmap: ProtocolPortMap::tcp_client(),
             tls_cert_agent: None,
@@ -87,19 +109,30 @@ impl AuditorConfig {
             tls_interception_client: Default::default(),
             tls_interception_server: Default::default(),
             tls_stream_dump: None,
+            tls_stream_pcap: None,
+            tls_handshake_limit: None,
+            tls_sni_filter: None,
+            tls_client_subject_acl: None,
+            tls_sni_peek: false,
+            tls_max_client_hello_size: 1 << 16,
             log_uri_max_chars: 1024,
             h1_interception: Default::default(),
             h2_inspect_policy: Default::default(),
             h2_interception: Default::default(),
             websocket_inspect_policy: Default::default(),
+            websocket_interception: Default::default(),
             smtp_inspect_policy: Default::default(),
             smtp_interception: Default::default(),
             imap_inspect_policy: Default::default(),
             imap_interception: Default::default(),
+            pop3_inspect_policy: Default::default(),
+            pop3_interception: Default::default(),
             icap_reqmod_service: None,
             icap_respmod_service: None,
             #[cfg(feature = "quic")]
             stream_detour_service: None,
+            #[cfg(feature = "quic")]
+            stream_detour_on_error: None,
             task_audit_ratio: Bernoulli::new(1.0).unwrap(),
         }
     }
@@ -122,6 +155,22 @@ impl AuditorConfig {
         if self.name.is_empty() {
             return Err(anyhow!("name is not set"));
         }
+        #[cfg(feature = "quic")]
+        if let Some(action) = self.stream_detour_on_error {
+            if !matches!(
+                action,
+                ProtocolInspectAction::Block | ProtocolInspectAction::Bypass
+            ) {
+                return Err(anyhow!(
+                    "unsupported stream detour on error action {action}"
+                ));
+            }
+        }
+        if self.h1_interception.req_body_multipart.is_some() && self.icap_reqmod_service.is_some() {
+            return Err(anyhow!(
+                "request body multipart inspection can not be used with icap reqmod service"
+            ));
+        }
 
         Ok(())
     }
@@ -140,6 +189,21 @@ impl AuditorConfig {
                 self.protocol_inspection = protocol_inspection;
                 Ok(())
             }
+            "inspect_dry_run" | "dry_run" => {
+                self.inspect_dry_run = g3_yaml::value::as_bool(v)
+                    .context(format!("invalid bool value for key {k}"))?;
+                Ok(())
+            }
+            "protocol_tcp_no_delay" => {
+                self.protocol_tcp_no_delay = ProtocolTcpNoDelayConfig::parse(v)
+                    .context(format!("invalid protocol tcp no delay value for key {k}"))?;
+                Ok(())
+            }
+            "protocol_idle_timeout" => {
+                self.protocol_idle_timeout = ProtocolIdleTimeoutConfig::parse(v)
+                    .context(format!("invalid protocol idle timeout value for key {k}"))?;
+                Ok(())
+            }
             "server_tcp_portmap" => {
                 g3_yaml::value::update_protocol_portmap(&mut self.server_tcp_portmap, v)
                     .context(format!("invalid protocol portmap value for key {k}"))
@@ -173,10 +237,12 @@ impl AuditorConfig {
                 Ok(())
             }
             "tls_interception_server" => {
-                let builder = g3_yaml::value::as_tls_interception_server_config_builder(v)
-                    .context(format!(
-                        "invalid tls interception server config value for key {k}"
-                    ))?;
+                let lookup_dir = g3_daemon::config::get_lookup_dir(self.position.as_ref())?;
+                let builder =
+                    g3_yaml::value::as_tls_interception_server_config_builder(v, Some(lookup_dir))
+                        .context(format!(
+                            "invalid tls interception server config value for key {k}"
+                        ))?;
                 self.tls_interception_server = builder;
                 Ok(())
             }
@@ -186,6 +252,42 @@ impl AuditorConfig {
                 self.tls_stream_dump = Some(dump);
                 Ok(())
             }
+            "tls_stream_pcap" => {
+                let pcap = AuditStreamPcapConfig::parse(v, self.position.as_ref()).context(
+                    format!("invalid audit stream pcap config value for key {k}"),
+                )?;
+                self.tls_stream_pcap = Some(Arc::new(pcap));
+                Ok(())
+            }
+            "tls_handshake_limit" => {
+                let limit = TlsHandshakeLimitConfig::parse(v).context(format!(
+                    "invalid tls handshake limit config value for key {k}"
+                ))?;
+                self.tls_handshake_limit = Some(limit);
+                Ok(())
+            }
+            "tls_sni_filter" => {
+                let filter = TlsSniFilterConfig::parse(v)
+                    .context(format!("invalid tls sni filter config value for key {k}"))?;
+                self.tls_sni_filter = Some(filter);
+                Ok(())
+            }
+            "tls_client_subject_acl" | "tls_client_cert_subject_acl" => {
+                let builder = g3_yaml::value::acl::as_regex_set_rule_builder(v)
+                    .context(format!("invalid regex set acl rule value for key {k}"))?;
+                self.tls_client_subject_acl = Some(builder);
+                Ok(())
+            }
+            "tls_sni_peek" => {
+                self.tls_sni_peek = g3_yaml::value::as_bool(v)
+                    .context(format!("invalid bool value for key {k}"))?;
+                Ok(())
+            }
+            "tls_max_client_hello_size" => {
+                self.tls_max_client_hello_size =
+                    g3_yaml::value::as_u32(v).context(format!("invalid u32 value for key {k}"))?;
+                Ok(())
+            }
             "log_uri_max_chars" | "uri_log_max_chars" => {
                 self.log_uri_max_chars = g3_yaml::value::as_usize(v)
                     .context(format!("invalid usize value for key {k}"))?;
@@ -212,6 +314,12 @@ impl AuditorConfig {
                         .context(format!("invalid protocol inspect policy value for key {k}"))?;
                 Ok(())
             }
+            "websocket_interception" => {
+                self.websocket_interception =
+                    g3_yaml::value::as_websocket_interception_config(v)
+                        .context(format!("invalid websocket interception value for key {k}"))?;
+                Ok(())
+            }
             "smtp_inspect_policy" => {
                 self.smtp_inspect_policy = g3_yaml::value::as_protocol_inspect_policy_builder(v)
                     .context(format!("invalid protocol inspect policy value for key {k}"))?;
@@ -232,6 +340,16 @@ impl AuditorConfig {
                     .context(format!("invalid imap interception value for key {k}"))?;
                 Ok(())
             }
+            "pop3_inspect_policy" => {
+                self.pop3_inspect_policy = g3_yaml::value::as_protocol_inspect_policy_builder(v)
+                    .context(format!("invalid protocol inspect policy value for key {k}"))?;
+                Ok(())
+            }
+            "pop3_interception" => {
+                self.pop3_interception = g3_yaml::value::as_pop3_interception_config(v)
+                    .context(format!("invalid pop3 interception value for key {k}"))?;
+                Ok(())
+            }
             "icap_reqmod_service" => {
                 let lookup_dir = g3_daemon::config::get_lookup_dir(self.position.as_ref())?;
                 let service = g3_yaml::value::as_icap_reqmod_service_config(v, Some(lookup_dir))
@@ -258,6 +376,13 @@ impl AuditorConfig {
                 self.stream_detour_service = Some(Arc::new(service));
                 Ok(())
             }
+            #[cfg(feature = "quic")]
+            "stream_detour_on_error" | "detour_on_error" => {
+                let action = g3_yaml::value::as_protocol_inspect_action(v)
+                    .context(format!("invalid protocol inspect action value for key {k}"))?;
+                self.stream_detour_on_error = Some(action);
+                Ok(())
+            }
             "task_audit_ratio" | "application_audit_ratio" => {
                 self.task_audit_ratio = g3_yaml::value::as_random_ratio(v)
                     .context(format!("invalid random ratio value for key {k}"))?;
@@ -267,3 +392,67 @@ impl AuditorConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn parse_auditor(conf: &str) -> anyhow::Result<AuditorConfig> {
+        let yaml = YamlLoader::load_from_str(conf).unwrap();
+        let mut config = AuditorConfig::new(None);
+        config.parse(yaml[0].as_hash().unwrap())?;
+        Ok(config)
+    }
+
+    #[test]
+    fn tls_client_subject_acl() {
+        let config = parse_auditor(
+            r#"
+            name: test
+            tls_client_subject_acl:
+              default: forbid
+              permit:
+                - "^CN=[a-z]+\\.example\\.net, O=Example Org$"
+              forbid:
+                - "^CN=blocked\\."
+            "#,
+        )
+        .unwrap();
+        let acl = config.tls_client_subject_acl.unwrap().build();
+
+        let (_, action) = acl.check("CN=www.example.net, O=Example Org");
+        assert!(!action.forbid_early());
+        let (_, action) = acl.check("CN=blocked.example.net, O=Example Org");
+        assert!(action.forbid_early());
+        let (found, action) = acl.check("CN=www.example.com");
+        assert!(!found);
+        assert!(action.forbid_early());
+
+        let config = parse_auditor(
+            r#"
+            name: test
+            tls_client_cert_subject_acl:
+              default: permit
+              forbid:
+                - "O=Evil Org"
+            "#,
+        )
+        .unwrap();
+        let acl = config.tls_client_subject_acl.unwrap().build();
+        let (_, action) = acl.check("CN=client, O=Evil Org");
+        assert!(action.forbid_early());
+        let (_, action) = acl.check("CN=client, O=Example Org");
+        assert!(!action.forbid_early());
+
+        assert!(parse_auditor(
+            r#"
+            name: test
+            tls_client_subject_acl:
+              permit:
+                - "CN=("
+            "#,
+        )
+        .is_err());
+    }
+}
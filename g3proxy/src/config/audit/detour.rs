@@ -27,6 +27,8 @@ use g3_types::net::{
 };
 use g3_yaml::YamlDocPosition;
 
+use crate::audit::{PAYLOAD_VERSION_LEGACY, PAYLOAD_VERSION_MAX};
+
 const DEFAULT_DETOUR_PORT: u16 = 2888;
 
 pub(crate) struct AuditStreamDetourConfig {
@@ -39,6 +41,7 @@ pub(crate) struct AuditStreamDetourConfig {
     pub(crate) stream_open_timeout: Duration,
     pub(crate) request_timeout: Duration,
     pub(crate) socket_buffer: SocketBufferConfig,
+    pub(crate) payload_version: u8,
 }
 
 impl Default for AuditStreamDetourConfig {
@@ -56,6 +59,7 @@ impl Default for AuditStreamDetourConfig {
             stream_open_timeout: Duration::from_secs(30),
             request_timeout: Duration::from_secs(60),
             socket_buffer: SocketBufferConfig::default(),
+            payload_version: PAYLOAD_VERSION_LEGACY,
         }
     }
 }
@@ -114,6 +118,14 @@ impl AuditStreamDetourConfig {
                         config.socket_buffer = g3_yaml::value::as_socket_buffer_config(v)?;
                         Ok(())
                     }
+                    "payload_version" => {
+                        let version = g3_yaml::value::as_u8(v)?;
+                        if version > PAYLOAD_VERSION_MAX {
+                            return Err(anyhow!("unsupported payload version {version}"));
+                        }
+                        config.payload_version = version;
+                        Ok(())
+                    }
                     _ => Err(anyhow!("invalid key {k}")),
                 })?;
             }
@@ -0,0 +1,141 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_dpi::Protocol;
+
+/// Idle timeout to use once the protocol has been identified, which takes precedence over
+/// the idle config on the user and the server
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ProtocolIdleTimeoutConfig {
+    ssh: Option<Duration>,
+    http: Option<Duration>,
+    websocket: Option<Duration>,
+    smtp: Option<Duration>,
+    imap: Option<Duration>,
+    pop3: Option<Duration>,
+}
+
+impl ProtocolIdleTimeoutConfig {
+    pub(crate) fn get(&self, protocol: Protocol) -> Option<Duration> {
+        match protocol {
+            Protocol::Ssh | Protocol::SshLegacy => self.ssh,
+            Protocol::Http1 | Protocol::Http2 => self.http,
+            Protocol::Websocket => self.websocket,
+            Protocol::Smtp => self.smtp,
+            Protocol::Imap => self.imap,
+            Protocol::Pop3 => self.pop3,
+            _ => None,
+        }
+    }
+
+    /// Get the max idle count to use with the idle check interval
+    pub(crate) fn max_idle_count(
+        &self,
+        protocol: Protocol,
+        idle_duration: Duration,
+    ) -> Option<i32> {
+        let timeout = self.get(protocol)?;
+        let idle_nanos = idle_duration.as_nanos().max(1);
+        let count = timeout.as_nanos().div_ceil(idle_nanos).max(1);
+        Some(i32::try_from(count).unwrap_or(i32::MAX))
+    }
+
+    pub(super) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let Yaml::Hash(map) = value else {
+            return Err(anyhow!(
+                "yaml value type for 'protocol idle timeout config' should be 'map'"
+            ));
+        };
+
+        let mut config = ProtocolIdleTimeoutConfig::default();
+        g3_yaml::foreach_kv(map, |k, v| {
+            let timeout = g3_yaml::humanize::as_duration(v)
+                .context(format!("invalid humanize duration value for key {k}"))?;
+            match g3_yaml::key::normalize(k).as_str() {
+                "ssh" => config.ssh = Some(timeout),
+                "http" => config.http = Some(timeout),
+                "websocket" => config.websocket = Some(timeout),
+                "smtp" => config.smtp = Some(timeout),
+                "imap" => config.imap = Some(timeout),
+                "pop3" => config.pop3 = Some(timeout),
+                _ => return Err(anyhow!("invalid key {k}")),
+            }
+            Ok(())
+        })?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse() {
+        let doc = YamlLoader::load_from_str("ssh: 2h\nhttp: 30s\nwebsocket: 1h").unwrap();
+        let config = ProtocolIdleTimeoutConfig::parse(&doc[0]).unwrap();
+        assert_eq!(config.get(Protocol::Ssh), Some(Duration::from_secs(7200)));
+        assert_eq!(
+            config.get(Protocol::SshLegacy),
+            Some(Duration::from_secs(7200))
+        );
+        assert_eq!(config.get(Protocol::Http1), Some(Duration::from_secs(30)));
+        assert_eq!(config.get(Protocol::Http2), Some(Duration::from_secs(30)));
+        assert_eq!(
+            config.get(Protocol::Websocket),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(config.get(Protocol::Smtp), None);
+        assert_eq!(config.get(Protocol::Imap), None);
+        assert_eq!(config.get(Protocol::Pop3), None);
+
+        let doc = YamlLoader::load_from_str("ftp: 1m").unwrap();
+        assert!(ProtocolIdleTimeoutConfig::parse(&doc[0]).is_err());
+
+        let doc = YamlLoader::load_from_str("ssh: -1").unwrap();
+        assert!(ProtocolIdleTimeoutConfig::parse(&doc[0]).is_err());
+    }
+
+    #[test]
+    fn max_idle_count() {
+        let doc = YamlLoader::load_from_str("ssh: 2h\nhttp: 30s\nwebsocket: 70s\nsmtp: 0").unwrap();
+        let config = ProtocolIdleTimeoutConfig::parse(&doc[0]).unwrap();
+        let idle_duration = Duration::from_secs(60);
+        assert_eq!(
+            config.max_idle_count(Protocol::Ssh, idle_duration),
+            Some(120)
+        );
+        assert_eq!(
+            config.max_idle_count(Protocol::Http1, idle_duration),
+            Some(1)
+        );
+        assert_eq!(
+            config.max_idle_count(Protocol::Websocket, idle_duration),
+            Some(2)
+        );
+        assert_eq!(
+            config.max_idle_count(Protocol::Smtp, idle_duration),
+            Some(1)
+        );
+        assert_eq!(config.max_idle_count(Protocol::Imap, idle_duration), None);
+    }
+}
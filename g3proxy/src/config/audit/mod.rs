@@ -27,6 +27,21 @@ pub(crate) use registry::{clear, get_all};
 mod auditor;
 pub(crate) use auditor::AuditorConfig;
 
+mod pcap;
+pub(crate) use pcap::AuditStreamPcapConfig;
+
+mod tcp_no_delay;
+pub(crate) use tcp_no_delay::ProtocolTcpNoDelayConfig;
+
+mod idle_timeout;
+pub(crate) use idle_timeout::ProtocolIdleTimeoutConfig;
+
+mod tls_handshake_limit;
+pub(crate) use tls_handshake_limit::TlsHandshakeLimitConfig;
+
+mod tls_sni_filter;
+pub(crate) use tls_sni_filter::{TlsSniDenyAction, TlsSniFilterConfig};
+
 #[cfg(feature = "quic")]
 mod detour;
 #[cfg(feature = "quic")]
@@ -0,0 +1,125 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::IpAddr;
+
+use anyhow::{anyhow, Context};
+use ip_network::IpNetwork;
+use uuid::Uuid;
+use yaml_rust::Yaml;
+
+use g3_udpdump::PcapDumpConfig;
+use g3_yaml::YamlDocPosition;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct AuditStreamPcapConfig {
+    pub(crate) dump: PcapDumpConfig,
+    client_ip: Vec<IpNetwork>,
+    task_id: Vec<Uuid>,
+}
+
+impl AuditStreamPcapConfig {
+    pub(crate) fn matches(&self, client_ip: IpAddr, task_id: &Uuid) -> bool {
+        self.client_ip.iter().any(|net| net.contains(client_ip)) || self.task_id.contains(task_id)
+    }
+
+    pub(super) fn parse(value: &Yaml, position: Option<&YamlDocPosition>) -> anyhow::Result<Self> {
+        let Yaml::Hash(map) = value else {
+            return Err(anyhow!(
+                "yaml value type for 'audit stream pcap config' should be 'map'"
+            ));
+        };
+
+        let mut config = AuditStreamPcapConfig::default();
+        let mut file_set = false;
+        g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "file" | "path" => {
+                let lookup_dir = g3_daemon::config::get_lookup_dir(position)?;
+                config.dump.file = g3_yaml::value::as_file_path(v, lookup_dir, true)
+                    .context(format!("invalid file path value for key {k}"))?;
+                file_set = true;
+                Ok(())
+            }
+            "max_file_size" => {
+                config.dump.max_file_size = g3_yaml::humanize::as_u64(v)
+                    .context(format!("invalid humanize u64 value for key {k}"))?;
+                Ok(())
+            }
+            "max_sessions" => {
+                config.dump.max_sessions = g3_yaml::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                Ok(())
+            }
+            "rate_limit" | "max_bytes_per_second" => {
+                config.dump.max_bytes_per_second = g3_yaml::humanize::as_u64(v)
+                    .context(format!("invalid humanize u64 value for key {k}"))?;
+                Ok(())
+            }
+            "packet_size" => {
+                config.dump.packet_size = g3_yaml::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                Ok(())
+            }
+            "client_side" => {
+                config.dump.client_side = g3_yaml::value::as_bool(v)
+                    .context(format!("invalid bool value for key {k}"))?;
+                Ok(())
+            }
+            "client_ip" => {
+                config.client_ip = g3_yaml::value::as_list(v, g3_yaml::value::as_ip_network)
+                    .context(format!("invalid ip network list value for key {k}"))?;
+                Ok(())
+            }
+            "task_id" => {
+                config.task_id = g3_yaml::value::as_list(v, |v| {
+                    let s = g3_yaml::value::as_string(v)?;
+                    Uuid::parse_str(&s).map_err(|e| anyhow!("invalid uuid string: {e}"))
+                })
+                .context(format!("invalid uuid list value for key {k}"))?;
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+
+        if !file_set {
+            return Err(anyhow!("no pcap file set"));
+        }
+        if config.client_ip.is_empty() && config.task_id.is_empty() {
+            return Err(anyhow!(
+                "no client_ip or task_id set, capturing all traffic is not allowed"
+            ));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn matches() {
+        let config = AuditStreamPcapConfig {
+            client_ip: vec![IpNetwork::from_str("192.168.1.0/24").unwrap()],
+            task_id: vec![Uuid::from_u128(1)],
+            ..Default::default()
+        };
+        assert!(config.matches(IpAddr::from_str("192.168.1.1").unwrap(), &Uuid::nil()));
+        assert!(config.matches(IpAddr::from_str("10.0.0.1").unwrap(), &Uuid::from_u128(1)));
+        assert!(!config.matches(IpAddr::from_str("10.0.0.1").unwrap(), &Uuid::nil()));
+    }
+}
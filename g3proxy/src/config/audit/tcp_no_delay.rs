@@ -0,0 +1,82 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_dpi::Protocol;
+
+/// TCP_NODELAY to set on the tcp connections once the protocol has been identified
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ProtocolTcpNoDelayConfig {
+    ssh: Option<bool>,
+    websocket: Option<bool>,
+}
+
+impl ProtocolTcpNoDelayConfig {
+    pub(crate) fn get(&self, protocol: Protocol) -> Option<bool> {
+        match protocol {
+            Protocol::Ssh | Protocol::SshLegacy => self.ssh,
+            Protocol::Websocket => self.websocket,
+            _ => None,
+        }
+    }
+
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let Yaml::Hash(map) = value else {
+            return Err(anyhow!(
+                "yaml value type for 'protocol tcp no delay config' should be 'map'"
+            ));
+        };
+
+        let mut config = ProtocolTcpNoDelayConfig::default();
+        g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "ssh" => {
+                let no_delay = g3_yaml::value::as_bool(v)
+                    .context(format!("invalid bool value for key {k}"))?;
+                config.ssh = Some(no_delay);
+                Ok(())
+            }
+            "websocket" => {
+                let no_delay = g3_yaml::value::as_bool(v)
+                    .context(format!("invalid bool value for key {k}"))?;
+                config.websocket = Some(no_delay);
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse() {
+        let doc = YamlLoader::load_from_str("ssh: true\nwebsocket: false").unwrap();
+        let config = ProtocolTcpNoDelayConfig::parse(&doc[0]).unwrap();
+        assert_eq!(config.get(Protocol::Ssh), Some(true));
+        assert_eq!(config.get(Protocol::SshLegacy), Some(true));
+        assert_eq!(config.get(Protocol::Websocket), Some(false));
+        assert_eq!(config.get(Protocol::Http1), None);
+
+        let doc = YamlLoader::load_from_str("http: true").unwrap();
+        assert!(ProtocolTcpNoDelayConfig::parse(&doc[0]).is_err());
+    }
+}
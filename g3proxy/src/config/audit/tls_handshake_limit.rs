@@ -0,0 +1,124 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+/// Limit of the concurrent in-progress handshakes in TLS interception
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct TlsHandshakeLimitConfig {
+    pub(crate) max_concurrent: usize,
+    pub(crate) max_queued: usize,
+    pub(crate) queue_timeout: Duration,
+}
+
+impl TlsHandshakeLimitConfig {
+    fn new(max_concurrent: usize) -> Self {
+        TlsHandshakeLimitConfig {
+            max_concurrent,
+            max_queued: max_concurrent.saturating_mul(4),
+            queue_timeout: Duration::from_secs(4),
+        }
+    }
+
+    pub(super) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        match value {
+            Yaml::Integer(_) => {
+                let max_concurrent = g3_yaml::value::as_usize(value)?;
+                let config = TlsHandshakeLimitConfig::new(max_concurrent);
+                config.check()?;
+                Ok(config)
+            }
+            Yaml::Hash(map) => {
+                let mut max_concurrent = 0;
+                let mut max_queued = None;
+                let mut queue_timeout = None;
+                g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                    "max_concurrent" | "concurrency" => {
+                        max_concurrent = g3_yaml::value::as_usize(v)
+                            .context(format!("invalid usize value for key {k}"))?;
+                        Ok(())
+                    }
+                    "max_queued" | "backlog" => {
+                        let n = g3_yaml::value::as_usize(v)
+                            .context(format!("invalid usize value for key {k}"))?;
+                        max_queued = Some(n);
+                        Ok(())
+                    }
+                    "queue_timeout" => {
+                        let t = g3_yaml::humanize::as_duration(v)
+                            .context(format!("invalid humanize duration value for key {k}"))?;
+                        queue_timeout = Some(t);
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("invalid key {k}")),
+                })?;
+
+                let mut config = TlsHandshakeLimitConfig::new(max_concurrent);
+                if let Some(n) = max_queued {
+                    config.max_queued = n;
+                }
+                if let Some(t) = queue_timeout {
+                    config.queue_timeout = t;
+                }
+                config.check()?;
+                Ok(config)
+            }
+            _ => Err(anyhow!(
+                "invalid yaml value type for 'tls handshake limit config'"
+            )),
+        }
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        if self.max_concurrent == 0 {
+            return Err(anyhow!("max concurrent should not be 0"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse() {
+        let v = Yaml::Integer(16);
+        let config = TlsHandshakeLimitConfig::parse(&v).unwrap();
+        assert_eq!(config.max_concurrent, 16);
+        assert_eq!(config.max_queued, 64);
+        assert_eq!(config.queue_timeout, Duration::from_secs(4));
+
+        let v = YamlLoader::load_from_str("max_concurrent: 8\nbacklog: 0\nqueue_timeout: 1s")
+            .unwrap()
+            .pop()
+            .unwrap();
+        let config = TlsHandshakeLimitConfig::parse(&v).unwrap();
+        assert_eq!(config.max_concurrent, 8);
+        assert_eq!(config.max_queued, 0);
+        assert_eq!(config.queue_timeout, Duration::from_secs(1));
+
+        let v = YamlLoader::load_from_str("max_queued: 8")
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert!(TlsHandshakeLimitConfig::parse(&v).is_err());
+    }
+}
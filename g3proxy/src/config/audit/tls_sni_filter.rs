@@ -0,0 +1,146 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_types::acl_set::AclDstHostRuleSetBuilder;
+use g3_types::net::HttpBlockResponse;
+
+/// The action to take if the TLS server name is not allowed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum TlsSniDenyAction {
+    /// send an `unrecognized_name` fatal alert and close the connection
+    Alert,
+    /// complete the handshake with a generated certificate and reply a fixed HTTP response
+    DenyPage,
+}
+
+impl TlsSniDenyAction {
+    fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let s = g3_yaml::value::as_string(value)?;
+        match g3_yaml::key::normalize(&s).as_str() {
+            "alert" | "alert_and_close" => Ok(TlsSniDenyAction::Alert),
+            "deny_page" | "serve_deny_page" => Ok(TlsSniDenyAction::DenyPage),
+            _ => Err(anyhow!("invalid tls sni deny action {s}")),
+        }
+    }
+}
+
+/// Allowlist of the TLS server names in TLS interception
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct TlsSniFilterConfig {
+    pub(crate) allowed: AclDstHostRuleSetBuilder,
+    pub(crate) deny_no_sni: bool,
+    pub(crate) deny_action: TlsSniDenyAction,
+    pub(crate) deny_response: Arc<HttpBlockResponse>,
+}
+
+impl TlsSniFilterConfig {
+    pub(super) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let Yaml::Hash(map) = value else {
+            return Err(anyhow!(
+                "invalid yaml value type for 'tls sni filter config'"
+            ));
+        };
+
+        let mut allowed = None;
+        let mut deny_no_sni = true;
+        let mut deny_action = TlsSniDenyAction::Alert;
+        let mut deny_response = None;
+        g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "allowed" | "allow" | "allowlist" => {
+                let builder = g3_yaml::value::acl_set::as_dst_host_rule_set_builder(v)
+                    .context(format!("invalid dst host acl rule set value for key {k}"))?;
+                allowed = Some(builder);
+                Ok(())
+            }
+            "deny_no_sni" => {
+                deny_no_sni = g3_yaml::value::as_bool(v)
+                    .context(format!("invalid bool value for key {k}"))?;
+                Ok(())
+            }
+            "deny_action" | "action" => {
+                deny_action = TlsSniDenyAction::parse(v)
+                    .context(format!("invalid tls sni deny action value for key {k}"))?;
+                Ok(())
+            }
+            "deny_response" | "response" => {
+                let rsp = g3_yaml::value::as_http_block_response(v)
+                    .context(format!("invalid http response value for key {k}"))?;
+                deny_response = Some(Arc::new(rsp));
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+
+        let Some(allowed) = allowed else {
+            return Err(anyhow!("no allowed server names set"));
+        };
+        Ok(TlsSniFilterConfig {
+            allowed,
+            deny_no_sni,
+            deny_action,
+            deny_response: deny_response.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use g3_types::net::Host;
+    use http::StatusCode;
+    use std::str::FromStr;
+    use yaml_rust::YamlLoader;
+
+    fn load(s: &str) -> Yaml {
+        YamlLoader::load_from_str(s).unwrap().pop().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let v = load("allowed:\n  child_match: example.net\n");
+        let config = TlsSniFilterConfig::parse(&v).unwrap();
+        assert!(config.deny_no_sni);
+        assert_eq!(config.deny_action, TlsSniDenyAction::Alert);
+        assert_eq!(config.deny_response.status(), StatusCode::FORBIDDEN);
+        let filter = config.allowed.build();
+        let (_, action) = filter.check(&Host::from_str("www.example.net").unwrap());
+        assert!(!action.forbid_early());
+        let (_, action) = filter.check(&Host::from_str("www.example.com").unwrap());
+        assert!(action.forbid_early());
+
+        let v = load(
+            "allowed:\n  exact_match: www.example.net\ndeny_action: deny_page\ndeny_response: 451\ndeny_no_sni: false\n",
+        );
+        let config = TlsSniFilterConfig::parse(&v).unwrap();
+        assert!(!config.deny_no_sni);
+        assert_eq!(config.deny_action, TlsSniDenyAction::DenyPage);
+        assert_eq!(
+            config.deny_response.status(),
+            StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS
+        );
+
+        let v = load("deny_action: alert\n");
+        assert!(TlsSniFilterConfig::parse(&v).is_err());
+
+        let v = load("allowed:\n  exact_match: www.example.net\ndeny_action: drop\n");
+        assert!(TlsSniFilterConfig::parse(&v).is_err());
+    }
+}
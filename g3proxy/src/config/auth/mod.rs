@@ -31,7 +31,7 @@ mod audit;
 pub(crate) use audit::UserAuditConfig;
 
 mod user;
-pub(crate) use user::UserConfig;
+pub(crate) use user::{UserConfig, UserEgressBind};
 
 mod group;
 pub(crate) use group::UserGroupConfig;
@@ -0,0 +1,152 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use anyhow::anyhow;
+
+use g3_socket::util::AddressFamily;
+
+pub(crate) enum UserEgressBind {
+    /// Use this ip as the source address
+    Ip(IpAddr),
+    /// No ip of this family is set, use the escaper default
+    Fallback,
+    /// No ip of this family is set, and fallback is disabled
+    Mismatch,
+}
+
+#[derive(Clone)]
+pub(crate) struct UserEgressBindConfig {
+    ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+    fallback: bool,
+}
+
+impl Default for UserEgressBindConfig {
+    fn default() -> Self {
+        UserEgressBindConfig {
+            ipv4: None,
+            ipv6: None,
+            fallback: true,
+        }
+    }
+}
+
+impl UserEgressBindConfig {
+    pub(super) fn set_ips(&mut self, ips: Vec<IpAddr>) -> anyhow::Result<()> {
+        self.ipv4 = None;
+        self.ipv6 = None;
+        for ip in ips {
+            match ip {
+                IpAddr::V4(ip4) => {
+                    if self.ipv4.replace(ip4).is_some() {
+                        return Err(anyhow!("only one ipv4 address is allowed"));
+                    }
+                }
+                IpAddr::V6(ip6) => {
+                    if self.ipv6.replace(ip6).is_some() {
+                        return Err(anyhow!("only one ipv6 address is allowed"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub(super) fn set_fallback(&mut self, fallback: bool) {
+        self.fallback = fallback;
+    }
+
+    #[inline]
+    pub(crate) fn is_set(&self) -> bool {
+        self.ipv4.is_some() || self.ipv6.is_some()
+    }
+
+    pub(crate) fn select(&self, family: AddressFamily) -> UserEgressBind {
+        let ip = match family {
+            AddressFamily::Ipv4 => self.ipv4.map(IpAddr::V4),
+            AddressFamily::Ipv6 => self.ipv6.map(IpAddr::V6),
+        };
+        match ip {
+            Some(ip) => UserEgressBind::Ip(ip),
+            None if self.fallback || !self.is_set() => UserEgressBind::Fallback,
+            None => UserEgressBind::Mismatch,
+        }
+    }
+
+    /// Get the only usable address family, if fallback is disabled and only one family is set
+    pub(crate) fn only_family(&self) -> Option<AddressFamily> {
+        if self.fallback {
+            return None;
+        }
+        match (self.ipv4, self.ipv6) {
+            (Some(_), None) => Some(AddressFamily::Ipv4),
+            (None, Some(_)) => Some(AddressFamily::Ipv6),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select() {
+        let mut config = UserEgressBindConfig::default();
+        assert!(matches!(
+            config.select(AddressFamily::Ipv4),
+            UserEgressBind::Fallback
+        ));
+
+        config
+            .set_ips(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)])
+            .unwrap();
+        assert!(matches!(
+            config.select(AddressFamily::Ipv4),
+            UserEgressBind::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        ));
+        assert!(matches!(
+            config.select(AddressFamily::Ipv6),
+            UserEgressBind::Fallback
+        ));
+        assert!(config.only_family().is_none());
+
+        config.set_fallback(false);
+        assert!(matches!(
+            config.select(AddressFamily::Ipv6),
+            UserEgressBind::Mismatch
+        ));
+        assert!(matches!(config.only_family(), Some(AddressFamily::Ipv4)));
+    }
+
+    #[test]
+    fn invalid_ips() {
+        let mut config = UserEgressBindConfig::default();
+        assert!(config
+            .set_ips(vec![
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V4(Ipv4Addr::LOCALHOST)
+            ])
+            .is_err());
+        // not checked at load time, as the ip may be assigned later
+        assert!(config
+            .set_ips(vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))])
+            .is_ok());
+    }
+}
@@ -37,10 +37,15 @@ use g3_types::resolve::{ResolveRedirectionBuilder, ResolveStrategy};
 
 use super::{PasswordToken, UserAuditConfig, UserSiteConfig};
 use crate::escape::EgressPathSelection;
+use crate::module::http_header::ConditionalAppendHeaders;
 
 mod json;
 mod yaml;
 
+mod egress;
+pub(crate) use egress::UserEgressBind;
+use egress::UserEgressBindConfig;
+
 #[derive(Clone)]
 pub(crate) struct UserConfig {
     name: Arc<str>,
@@ -67,6 +72,8 @@ pub(crate) struct UserConfig {
     pub(crate) udp_all_download_speed_limit: Option<GlobalDatagramSpeedLimitConfig>,
     pub(crate) log_rate_limit: Option<RateLimitQuotaConfig>,
     pub(crate) log_uri_max_chars: Option<usize>,
+    pub(crate) smtp_max_recipients: Option<usize>,
+    pub(crate) conditional_append_headers: Vec<ConditionalAppendHeaders>,
     pub(crate) ingress_net_filter: Option<AclNetworkRuleBuilder>,
     pub(crate) proxy_request_filter: Option<AclProxyRequestRule>,
     pub(crate) dst_host_filter: Option<AclDstHostRuleSetBuilder>,
@@ -77,6 +84,7 @@ pub(crate) struct UserConfig {
     pub(crate) task_idle_max_count: i32,
     pub(crate) socks_use_udp_associate: bool,
     pub(crate) egress_path_selection: Option<EgressPathSelection>,
+    pub(crate) egress_bind: UserEgressBindConfig,
     pub(crate) explicit_sites: BTreeMap<MetricsName, Arc<UserSiteConfig>>,
 }
 
@@ -107,6 +115,8 @@ impl Default for UserConfig {
             udp_all_download_speed_limit: None,
             log_rate_limit: None,
             log_uri_max_chars: None,
+            smtp_max_recipients: None,
+            conditional_append_headers: Vec::new(),
             ingress_net_filter: None,
             proxy_request_filter: None,
             dst_host_filter: None,
@@ -117,6 +127,7 @@ impl Default for UserConfig {
             task_idle_max_count: 1,
             socks_use_udp_associate: false,
             egress_path_selection: None,
+            egress_bind: Default::default(),
             explicit_sites: BTreeMap::new(),
         }
     }
@@ -24,6 +24,7 @@ use g3_yaml::YamlDocPosition;
 
 use super::{PasswordToken, UserConfig, UserSiteConfig};
 use crate::escape::EgressPathSelection;
+use crate::module::http_header::ConditionalAppendHeaders;
 
 impl UserConfig {
     pub(crate) fn parse_yaml(
@@ -222,6 +223,19 @@ impl UserConfig {
                 self.log_uri_max_chars = Some(max_chars);
                 Ok(())
             }
+            "smtp_max_recipients" => {
+                let max = g3_yaml::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                self.smtp_max_recipients = Some(max);
+                Ok(())
+            }
+            "conditional_append_headers" => {
+                self.conditional_append_headers = ConditionalAppendHeaders::parse_yaml_list(v)
+                    .context(format!(
+                        "invalid conditional append headers value for key {k}"
+                    ))?;
+                Ok(())
+            }
             "task_idle_max_count" => {
                 self.task_idle_max_count =
                     g3_yaml::value::as_i32(v).context(format!("invalid i32 value for key {k}"))?;
@@ -273,6 +287,18 @@ impl UserConfig {
                 ));
                 Ok(())
             }
+            "egress_bind_ip" => {
+                let ips = g3_yaml::value::as_list(v, g3_yaml::value::as_ipaddr)
+                    .context(format!("invalid ip address list value for key {k}"))?;
+                self.egress_bind
+                    .set_ips(ips)
+                    .context(format!("invalid egress bind ip value for key {k}"))
+            }
+            "egress_bind_fallback" => {
+                let fallback = g3_yaml::value::as_bool(v)?;
+                self.egress_bind.set_fallback(fallback);
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
@@ -0,0 +1,143 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+const DEFAULT_QUEUE_TIMEOUT: Duration = Duration::from_secs(4);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum UpstreamConnLimitAction {
+    /// fail the connection setup at once
+    Reject,
+    /// wait for a free slot, and fail if the timeout reached
+    Queue(Duration),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct UpstreamConnLimitConfig {
+    /// max alive connections to each upstream, 0 means no limit
+    pub(crate) max_per_upstream: usize,
+    /// max alive connections to all upstreams, 0 means no limit
+    pub(crate) max_total: usize,
+    pub(crate) action: UpstreamConnLimitAction,
+}
+
+impl Default for UpstreamConnLimitConfig {
+    fn default() -> Self {
+        UpstreamConnLimitConfig {
+            max_per_upstream: 0,
+            max_total: 0,
+            action: UpstreamConnLimitAction::Reject,
+        }
+    }
+}
+
+impl UpstreamConnLimitConfig {
+    #[inline]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.max_per_upstream > 0 || self.max_total > 0
+    }
+
+    pub(crate) fn parse_yaml(v: &Yaml) -> anyhow::Result<Self> {
+        let mut config = UpstreamConnLimitConfig::default();
+        match v {
+            Yaml::Integer(_) => {
+                config.max_per_upstream = g3_yaml::value::as_usize(v)?;
+            }
+            Yaml::Hash(map) => {
+                let mut queue = false;
+                let mut queue_timeout = DEFAULT_QUEUE_TIMEOUT;
+                g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                    "max_per_upstream" | "per_upstream" => {
+                        config.max_per_upstream = g3_yaml::value::as_usize(v)
+                            .context(format!("invalid usize value for key {k}"))?;
+                        Ok(())
+                    }
+                    "max_total" | "total" => {
+                        config.max_total = g3_yaml::value::as_usize(v)
+                            .context(format!("invalid usize value for key {k}"))?;
+                        Ok(())
+                    }
+                    "action" => {
+                        let action = g3_yaml::value::as_string(v)
+                            .context(format!("invalid string value for key {k}"))?;
+                        match action.to_lowercase().as_str() {
+                            "reject" => queue = false,
+                            "queue" | "wait" => queue = true,
+                            _ => return Err(anyhow!("invalid action {action} for key {k}")),
+                        }
+                        Ok(())
+                    }
+                    "queue_timeout" => {
+                        queue_timeout = g3_yaml::humanize::as_duration(v)
+                            .context(format!("invalid humanize duration value for key {k}"))?;
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("invalid key {k}")),
+                })?;
+                if queue {
+                    if queue_timeout.is_zero() {
+                        return Err(anyhow!("queue timeout should not be zero"));
+                    }
+                    config.action = UpstreamConnLimitAction::Queue(queue_timeout);
+                }
+            }
+            _ => {
+                return Err(anyhow!(
+                    "yaml value type for 'upstream conn limit' should be 'map' or 'integer'"
+                ))
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse_yaml() {
+        let v = Yaml::Integer(8);
+        let config = UpstreamConnLimitConfig::parse_yaml(&v).unwrap();
+        assert_eq!(config.max_per_upstream, 8);
+        assert_eq!(config.max_total, 0);
+        assert_eq!(config.action, UpstreamConnLimitAction::Reject);
+
+        let docs = YamlLoader::load_from_str(
+            "max_per_upstream: 4\nmax_total: 100\naction: queue\nqueue_timeout: 2s",
+        )
+        .unwrap();
+        let config = UpstreamConnLimitConfig::parse_yaml(&docs[0]).unwrap();
+        assert_eq!(config.max_per_upstream, 4);
+        assert_eq!(config.max_total, 100);
+        assert_eq!(
+            config.action,
+            UpstreamConnLimitAction::Queue(Duration::from_secs(2))
+        );
+        assert!(config.is_enabled());
+
+        let docs = YamlLoader::load_from_str("action: drop").unwrap();
+        assert!(UpstreamConnLimitConfig::parse_yaml(&docs[0]).is_err());
+
+        let docs = YamlLoader::load_from_str("action: queue\nqueue_timeout: 0").unwrap();
+        assert!(UpstreamConnLimitConfig::parse_yaml(&docs[0]).is_err());
+    }
+}
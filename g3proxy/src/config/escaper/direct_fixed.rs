@@ -14,25 +14,35 @@
  * limitations under the License.
  */
 
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
 
 use anyhow::{anyhow, Context};
 use ascii::AsciiString;
+use ip_network::IpNetwork;
 use yaml_rust::{yaml, Yaml};
 
 use g3_types::acl::{AclAction, AclNetworkRuleBuilder};
+use g3_types::collection::SelectivePickPolicy;
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use g3_types::net::InterfaceName;
-use g3_types::net::{HappyEyeballsConfig, TcpKeepAliveConfig, TcpMiscSockOpts, UdpMiscSockOpts};
+use g3_types::net::{
+    HappyEyeballsConfig, OpensslCertificatePair, TcpKeepAliveConfig, TcpMiscSockOpts,
+    UdpMiscSockOpts,
+};
 use g3_types::resolve::{QueryStrategy, ResolveRedirectionBuilder, ResolveStrategy};
 use g3_yaml::YamlDocPosition;
 
+use super::conn_limit::UpstreamConnLimitConfig;
 use super::{AnyEscaperConfig, EscaperConfig, EscaperConfigDiffAction, GeneralEscaperConfig};
+use crate::config::audit::ProtocolTcpNoDelayConfig;
 
 const ESCAPER_CONFIG_TYPE: &str = "DirectFixed";
 
+/// The max number of bind addresses that a single network in `bind_ip` can expand to
+const BIND_NETWORK_MAX_SIZE: u128 = 4096;
+
 #[derive(Clone, Eq, PartialEq)]
 pub(crate) struct DirectFixedEscaperConfig {
     pub(crate) name: MetricsName,
@@ -42,6 +52,9 @@ pub(crate) struct DirectFixedEscaperConfig {
     pub(crate) bind_interface: Option<InterfaceName>,
     pub(crate) bind4: Vec<IpAddr>,
     pub(crate) bind6: Vec<IpAddr>,
+    pub(crate) bind_pick_policy: SelectivePickPolicy,
+    #[cfg(target_os = "linux")]
+    pub(crate) bind_client_ip: bool,
     pub(crate) no_ipv4: bool,
     pub(crate) no_ipv6: bool,
     pub(crate) resolver: MetricsName,
@@ -52,8 +65,13 @@ pub(crate) struct DirectFixedEscaperConfig {
     pub(crate) happy_eyeballs: HappyEyeballsConfig,
     pub(crate) tcp_keepalive: TcpKeepAliveConfig,
     pub(crate) tcp_misc_opts: TcpMiscSockOpts,
+    pub(crate) protocol_tcp_no_delay: Option<ProtocolTcpNoDelayConfig>,
+    pub(crate) tls_interception_client_cert: Option<Arc<OpensslCertificatePair>>,
     pub(crate) udp_misc_opts: UdpMiscSockOpts,
+    #[cfg(target_os = "linux")]
+    netfilter_mark: Option<u32>,
     pub(crate) enable_path_selection: bool,
+    pub(crate) upstream_conn_limit: UpstreamConnLimitConfig,
     pub(crate) extra_metrics_tags: Option<Arc<StaticMetricsTags>>,
 }
 
@@ -67,6 +85,9 @@ impl DirectFixedEscaperConfig {
             bind_interface: None,
             bind4: Vec::new(),
             bind6: Vec::new(),
+            bind_pick_policy: SelectivePickPolicy::Random,
+            #[cfg(target_os = "linux")]
+            bind_client_ip: false,
             no_ipv4: false,
             no_ipv6: false,
             resolver: MetricsName::default(),
@@ -77,8 +98,13 @@ impl DirectFixedEscaperConfig {
             happy_eyeballs: Default::default(),
             tcp_keepalive: Default::default(),
             tcp_misc_opts: Default::default(),
+            protocol_tcp_no_delay: None,
+            tls_interception_client_cert: None,
             udp_misc_opts: Default::default(),
+            #[cfg(target_os = "linux")]
+            netfilter_mark: None,
             enable_path_selection: false,
+            upstream_conn_limit: Default::default(),
             extra_metrics_tags: None,
         }
     }
@@ -117,17 +143,33 @@ impl DirectFixedEscaperConfig {
             "bind_interface" => {
                 let interface = g3_yaml::value::as_interface_name(v)
                     .context(format!("invalid interface name value for key {k}"))?;
+                interface
+                    .index()
+                    .map_err(|e| anyhow!("unusable interface {interface}: {e}"))?;
                 self.bind_interface = Some(interface);
                 Ok(())
             }
             "bind_ip" => {
-                let ips = g3_yaml::value::as_list(v, g3_yaml::value::as_ipaddr)
-                    .context(format!("invalid ip address list value for key {k}"))?;
-                for ip in ips {
-                    self.add_bind_address(ip)?;
+                let nets = g3_yaml::value::as_list(v, g3_yaml::value::as_ip_network).context(
+                    format!("invalid ip address / network list value for key {k}"),
+                )?;
+                for net in nets {
+                    self.add_bind_network(net)
+                        .context(format!("invalid bind network {net} for key {k}"))?;
                 }
                 Ok(())
             }
+            "bind_ip_pick_policy" | "bind_pick_policy" => {
+                self.bind_pick_policy = g3_yaml::value::as_selective_pick_policy(v)
+                    .context(format!("invalid selective pick policy value for key {k}"))?;
+                Ok(())
+            }
+            #[cfg(target_os = "linux")]
+            "bind_client_ip" | "transparent_client_ip" => {
+                self.bind_client_ip = g3_yaml::value::as_bool(v)
+                    .context(format!("invalid bool value for key {k}"))?;
+                Ok(())
+            }
             "resolver" => {
                 self.resolver = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
@@ -147,6 +189,11 @@ impl DirectFixedEscaperConfig {
                 self.enable_path_selection = g3_yaml::value::as_bool(v)?;
                 Ok(())
             }
+            "upstream_conn_limit" | "upstream_connection_limit" => {
+                self.upstream_conn_limit = UpstreamConnLimitConfig::parse_yaml(v)
+                    .context(format!("invalid upstream conn limit value for key {k}"))?;
+                Ok(())
+            }
             "egress_network_filter" | "egress_net_filter" => {
                 self.egress_net_filter = g3_yaml::value::acl::as_egress_network_rule_builder(v)
                     .context(format!("invalid network acl rule value for key {k}"))?;
@@ -172,11 +219,31 @@ impl DirectFixedEscaperConfig {
                     .context(format!("invalid tcp misc sock opts value for key {k}"))?;
                 Ok(())
             }
+            "protocol_tcp_no_delay" => {
+                let config = ProtocolTcpNoDelayConfig::parse(v)
+                    .context(format!("invalid protocol tcp no delay value for key {k}"))?;
+                self.protocol_tcp_no_delay = Some(config);
+                Ok(())
+            }
+            "tls_interception_client_cert" | "tls_interception_client_cert_pair" => {
+                let lookup_dir = g3_daemon::config::get_lookup_dir(self.position.as_ref())?;
+                let pair = g3_yaml::value::as_openssl_certificate_pair(v, Some(lookup_dir))
+                    .context(format!("invalid tls cert pair value for key {k}"))?;
+                self.tls_interception_client_cert = Some(Arc::new(pair));
+                Ok(())
+            }
             "udp_misc_opts" => {
                 self.udp_misc_opts = g3_yaml::value::as_udp_misc_sock_opts(v)
                     .context(format!("invalid udp misc sock opts value for key {k}"))?;
                 Ok(())
             }
+            #[cfg(target_os = "linux")]
+            "netfilter_mark" | "socket_mark" | "so_mark" => {
+                let mark =
+                    g3_yaml::value::as_u32(v).context(format!("invalid u32 value for key {k}"))?;
+                self.netfilter_mark = Some(mark);
+                Ok(())
+            }
             "no_ipv4" => {
                 self.no_ipv4 = g3_yaml::value::as_bool(v)?;
                 Ok(())
@@ -221,13 +288,61 @@ impl DirectFixedEscaperConfig {
             }
         }
 
+        // the mark set in misc sock opts takes precedence
+        #[cfg(target_os = "linux")]
+        if let Some(mark) = self.netfilter_mark {
+            self.tcp_misc_opts.netfilter_mark.get_or_insert(mark);
+            self.udp_misc_opts.netfilter_mark.get_or_insert(mark);
+        }
+
         Ok(())
     }
 
-    fn add_bind_address(&mut self, ip: IpAddr) -> anyhow::Result<()> {
-        match ip {
-            IpAddr::V4(_) => self.bind4.push(ip),
-            IpAddr::V6(_) => self.bind6.push(ip),
+    fn add_bind_address(&mut self, ip: IpAddr) {
+        let vec = match ip {
+            IpAddr::V4(_) => &mut self.bind4,
+            IpAddr::V6(_) => &mut self.bind6,
+        };
+        if !vec.contains(&ip) {
+            vec.push(ip);
+        }
+    }
+
+    fn add_bind_network(&mut self, net: IpNetwork) -> anyhow::Result<()> {
+        match net {
+            IpNetwork::V4(net4) => {
+                let start = u32::from(net4.network_address());
+                let size = 1u128 << (32 - net4.netmask());
+                if size > BIND_NETWORK_MAX_SIZE {
+                    return Err(anyhow!(
+                        "too many addresses in network, the max allowed is {BIND_NETWORK_MAX_SIZE}"
+                    ));
+                }
+                let size = size as u32;
+                if size <= 2 {
+                    // single address or point-to-point link
+                    for i in 0..size {
+                        self.add_bind_address(IpAddr::V4(Ipv4Addr::from(start + i)));
+                    }
+                } else {
+                    // skip the network address and the broadcast address
+                    for i in 1..size - 1 {
+                        self.add_bind_address(IpAddr::V4(Ipv4Addr::from(start + i)));
+                    }
+                }
+            }
+            IpNetwork::V6(net6) => {
+                let start = u128::from(net6.network_address());
+                let bits = 128 - net6.netmask() as u32;
+                if bits >= 128 || (1u128 << bits) > BIND_NETWORK_MAX_SIZE {
+                    return Err(anyhow!(
+                        "too many addresses in network, the max allowed is {BIND_NETWORK_MAX_SIZE}"
+                    ));
+                }
+                for i in 0..(1u128 << bits) {
+                    self.add_bind_address(IpAddr::V6(Ipv6Addr::from(start + i)));
+                }
+            }
         }
         Ok(())
     }
@@ -266,3 +381,46 @@ impl EscaperConfig for DirectFixedEscaperConfig {
         self.shared_logger.as_ref().map(|s| s.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn bind_network() {
+        let mut config = DirectFixedEscaperConfig::new(None);
+
+        config
+            .add_bind_network(IpNetwork::from_str("192.168.1.0/30").unwrap())
+            .unwrap();
+        config
+            .add_bind_network(IpNetwork::from_str("192.168.1.2/32").unwrap())
+            .unwrap();
+        config
+            .add_bind_network(IpNetwork::from_str("10.0.0.0/31").unwrap())
+            .unwrap();
+        assert_eq!(
+            config.bind4,
+            vec![
+                IpAddr::from_str("192.168.1.1").unwrap(),
+                IpAddr::from_str("192.168.1.2").unwrap(),
+                IpAddr::from_str("10.0.0.0").unwrap(),
+                IpAddr::from_str("10.0.0.1").unwrap(),
+            ]
+        );
+
+        config
+            .add_bind_network(IpNetwork::from_str("2001:db8::/126").unwrap())
+            .unwrap();
+        assert_eq!(config.bind6.len(), 4);
+        assert_eq!(config.bind6[3], IpAddr::from_str("2001:db8::3").unwrap());
+
+        assert!(config
+            .add_bind_network(IpNetwork::from_str("10.0.0.0/16").unwrap())
+            .is_err());
+        assert!(config
+            .add_bind_network(IpNetwork::from_str("2001:db8::/64").unwrap())
+            .is_err());
+    }
+}
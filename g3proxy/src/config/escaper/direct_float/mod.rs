@@ -40,11 +40,15 @@ use yaml_rust::{yaml, Yaml};
 
 use g3_types::acl::{AclAction, AclNetworkRuleBuilder};
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
-use g3_types::net::{HappyEyeballsConfig, TcpKeepAliveConfig, TcpMiscSockOpts, UdpMiscSockOpts};
+use g3_types::net::{
+    HappyEyeballsConfig, OpensslCertificatePair, TcpKeepAliveConfig, TcpMiscSockOpts,
+    UdpMiscSockOpts,
+};
 use g3_types::resolve::{QueryStrategy, ResolveRedirectionBuilder, ResolveStrategy};
 use g3_yaml::YamlDocPosition;
 
 use super::{AnyEscaperConfig, EscaperConfig, EscaperConfigDiffAction, GeneralEscaperConfig};
+use crate::config::audit::ProtocolTcpNoDelayConfig;
 
 mod bind;
 pub(crate) use bind::{BindSet, DirectFloatBindIp};
@@ -68,7 +72,11 @@ pub(crate) struct DirectFloatEscaperConfig {
     pub(crate) happy_eyeballs: HappyEyeballsConfig,
     pub(crate) tcp_keepalive: TcpKeepAliveConfig,
     pub(crate) tcp_misc_opts: TcpMiscSockOpts,
+    pub(crate) protocol_tcp_no_delay: Option<ProtocolTcpNoDelayConfig>,
+    pub(crate) tls_interception_client_cert: Option<Arc<OpensslCertificatePair>>,
     pub(crate) udp_misc_opts: UdpMiscSockOpts,
+    #[cfg(target_os = "linux")]
+    netfilter_mark: Option<u32>,
     pub(crate) extra_metrics_tags: Option<Arc<StaticMetricsTags>>,
 }
 
@@ -90,7 +98,11 @@ impl DirectFloatEscaperConfig {
             happy_eyeballs: Default::default(),
             tcp_keepalive: TcpKeepAliveConfig::default_enabled(),
             tcp_misc_opts: Default::default(),
+            protocol_tcp_no_delay: None,
+            tls_interception_client_cert: None,
             udp_misc_opts: Default::default(),
+            #[cfg(target_os = "linux")]
+            netfilter_mark: None,
             extra_metrics_tags: None,
         }
     }
@@ -198,11 +210,31 @@ impl DirectFloatEscaperConfig {
                     .context(format!("invalid tcp misc sock opts value for key {k}"))?;
                 Ok(())
             }
+            "protocol_tcp_no_delay" => {
+                let config = ProtocolTcpNoDelayConfig::parse(v)
+                    .context(format!("invalid protocol tcp no delay value for key {k}"))?;
+                self.protocol_tcp_no_delay = Some(config);
+                Ok(())
+            }
+            "tls_interception_client_cert" | "tls_interception_client_cert_pair" => {
+                let lookup_dir = g3_daemon::config::get_lookup_dir(self.position.as_ref())?;
+                let pair = g3_yaml::value::as_openssl_certificate_pair(v, Some(lookup_dir))
+                    .context(format!("invalid tls cert pair value for key {k}"))?;
+                self.tls_interception_client_cert = Some(Arc::new(pair));
+                Ok(())
+            }
             "udp_misc_opts" => {
                 self.udp_misc_opts = g3_yaml::value::as_udp_misc_sock_opts(v)
                     .context(format!("invalid udp misc sock opts value for key {k}"))?;
                 Ok(())
             }
+            #[cfg(target_os = "linux")]
+            "netfilter_mark" | "socket_mark" | "so_mark" => {
+                let mark =
+                    g3_yaml::value::as_u32(v).context(format!("invalid u32 value for key {k}"))?;
+                self.netfilter_mark = Some(mark);
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
@@ -229,6 +261,13 @@ impl DirectFloatEscaperConfig {
             }
         }
 
+        // the mark set in misc sock opts takes precedence
+        #[cfg(target_os = "linux")]
+        if let Some(mark) = self.netfilter_mark {
+            self.tcp_misc_opts.netfilter_mark.get_or_insert(mark);
+            self.udp_misc_opts.netfilter_mark.get_or_insert(mark);
+        }
+
         if !self.no_ipv4 && self.cache_ipv4.is_none() {
             warn!(
                 "It is very recommended to set ipv4 local cache for escaper {}",
@@ -21,7 +21,7 @@ use anyhow::{anyhow, Context};
 use ascii::AsciiString;
 use yaml_rust::{yaml, Yaml};
 
-use g3_types::collection::SelectivePickPolicy;
+use g3_types::collection::{SelectivePickOverride, SelectivePickPolicy};
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use g3_types::net::InterfaceName;
@@ -42,6 +42,7 @@ pub(crate) struct DivertTcpEscaperConfig {
     pub(crate) shared_logger: Option<AsciiString>,
     pub(crate) proxy_nodes: Vec<WeightedUpstreamAddr>,
     pub(crate) proxy_pick_policy: SelectivePickPolicy,
+    pub(crate) proxy_pick_override: Option<SelectivePickOverride>,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub(crate) bind_interface: Option<InterfaceName>,
     pub(crate) bind_v4: Option<Ipv4Addr>,
@@ -65,6 +66,7 @@ impl DivertTcpEscaperConfig {
             shared_logger: None,
             proxy_nodes: Vec::with_capacity(1),
             proxy_pick_policy: SelectivePickPolicy::Random,
+            proxy_pick_override: None,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             bind_interface: None,
             bind_v4: None,
@@ -124,10 +126,19 @@ impl DivertTcpEscaperConfig {
                 self.proxy_pick_policy = g3_yaml::value::as_selective_pick_policy(v)?;
                 Ok(())
             }
+            "proxy_addr_pick_override" => {
+                let pick_override = g3_yaml::value::as_selective_pick_override(v)
+                    .context(format!("invalid selective pick override value for key {k}"))?;
+                self.proxy_pick_override = Some(pick_override);
+                Ok(())
+            }
             #[cfg(any(target_os = "linux", target_os = "android"))]
             "bind_interface" => {
                 let interface = g3_yaml::value::as_interface_name(v)
                     .context(format!("invalid interface name value for key {k}"))?;
+                interface
+                    .index()
+                    .map_err(|e| anyhow!("unusable interface {interface}: {e}"))?;
                 self.bind_interface = Some(interface);
                 Ok(())
             }
@@ -193,6 +204,7 @@ impl DivertTcpEscaperConfig {
         if self.proxy_nodes.is_empty() {
             return Err(anyhow!("proxy addr is not set"));
         }
+        super::check_pick_override(&mut self.proxy_pick_override, &self.proxy_nodes, |_| true)?;
         self.proxy_nodes.reverse(); // reverse as we push to the back
         if self.no_ipv4 && self.no_ipv6 {
             return Err(anyhow!("both ipv4 and ipv6 are disabled"));
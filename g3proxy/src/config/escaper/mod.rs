@@ -24,11 +24,13 @@ use slog::Logger;
 use yaml_rust::{yaml, Yaml};
 
 use g3_daemon::config::sort_nodes_in_dependency_graph;
+use g3_types::collection::SelectivePickOverride;
 use g3_types::metrics::MetricsName;
 use g3_types::net::{TcpConnectConfig, TcpSockSpeedLimitConfig, UdpSockSpeedLimitConfig};
 use g3_yaml::{HybridParser, YamlDocPosition};
 
 pub(crate) mod comply_audit;
+pub(crate) mod conn_limit;
 pub(crate) mod direct_fixed;
 pub(crate) mod direct_float;
 pub(crate) mod divert_tcp;
@@ -38,6 +40,7 @@ pub(crate) mod proxy_http;
 pub(crate) mod proxy_https;
 pub(crate) mod proxy_socks5;
 pub(crate) mod proxy_socks5s;
+pub(crate) mod proxy_ssh;
 pub(crate) mod route_client;
 pub(crate) mod route_failover;
 pub(crate) mod route_geoip;
@@ -57,6 +60,9 @@ use verify::EscaperConfigVerifier;
 const CONFIG_KEY_ESCAPER_TYPE: &str = "type";
 const CONFIG_KEY_ESCAPER_NAME: &str = "name";
 
+/// max UDP payload size for a 1500 bytes MTU IPv4 path
+pub(crate) const DEFAULT_UDP_MAX_DATAGRAM_SIZE: usize = 1472;
+
 pub(crate) enum EscaperConfigDiffAction {
     NoAction,
     SpawnNew,
@@ -107,6 +113,7 @@ pub(crate) enum AnyEscaperConfig {
     ProxyHttps(Box<proxy_https::ProxyHttpsEscaperConfig>),
     ProxySocks5(proxy_socks5::ProxySocks5EscaperConfig),
     ProxySocks5s(proxy_socks5s::ProxySocks5sEscaperConfig),
+    ProxySsh(Box<proxy_ssh::ProxySshEscaperConfig>),
     RouteFailover(route_failover::RouteFailoverEscaperConfig),
     RouteResolved(route_resolved::RouteResolvedEscaperConfig),
     RouteGeoIp(route_geoip::RouteGeoIpEscaperConfig),
@@ -132,6 +139,7 @@ macro_rules! impl_transparent0 {
                 AnyEscaperConfig::ProxyHttps(s) => s.$f(),
                 AnyEscaperConfig::ProxySocks5(s) => s.$f(),
                 AnyEscaperConfig::ProxySocks5s(s) => s.$f(),
+                AnyEscaperConfig::ProxySsh(s) => s.$f(),
                 AnyEscaperConfig::RouteFailover(s) => s.$f(),
                 AnyEscaperConfig::RouteResolved(s) => s.$f(),
                 AnyEscaperConfig::RouteGeoIp(s) => s.$f(),
@@ -160,6 +168,7 @@ macro_rules! impl_transparent1 {
                 AnyEscaperConfig::ProxyHttps(s) => s.$f(p),
                 AnyEscaperConfig::ProxySocks5(s) => s.$f(p),
                 AnyEscaperConfig::ProxySocks5s(s) => s.$f(p),
+                AnyEscaperConfig::ProxySsh(s) => s.$f(p),
                 AnyEscaperConfig::RouteFailover(s) => s.$f(p),
                 AnyEscaperConfig::RouteResolved(s) => s.$f(p),
                 AnyEscaperConfig::RouteGeoIp(s) => s.$f(p),
@@ -183,6 +192,31 @@ impl AnyEscaperConfig {
     impl_transparent1!(diff_action, EscaperConfigDiffAction, &Self);
 }
 
+/// Check the pinned index, which is in the configured order, and convert it to the insertion order
+/// of the selective vec. This should be called before the nodes get reversed.
+fn check_pick_override<T, F>(
+    pick_override: &mut Option<SelectivePickOverride>,
+    nodes: &[T],
+    selectable: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(&T) -> bool,
+{
+    let Some(SelectivePickOverride::Pinned(index)) = *pick_override else {
+        return Ok(());
+    };
+    let node = nodes
+        .get(index)
+        .ok_or_else(|| anyhow!("pinned index {index} is out of range"))?;
+    if !selectable(node) {
+        return Err(anyhow!("the pinned node #{index} is not selectable"));
+    }
+    // the nodes will be reversed before inserting into the selective vec
+    let pos = nodes[index + 1..].iter().filter(|v| selectable(v)).count();
+    *pick_override = Some(SelectivePickOverride::Pinned(pos));
+    Ok(())
+}
+
 pub(crate) fn load_all(v: &Yaml, conf_dir: &Path) -> anyhow::Result<()> {
     let parser = HybridParser::new(conf_dir, g3_daemon::opts::config_file_extension());
     parser.foreach_map(v, |map, position| {
@@ -264,6 +298,10 @@ fn load_escaper(
             let config = proxy_socks5s::ProxySocks5sEscaperConfig::parse(map, position)?;
             Ok(AnyEscaperConfig::ProxySocks5s(config))
         }
+        "proxy_ssh" | "proxyssh" => {
+            let config = proxy_ssh::ProxySshEscaperConfig::parse(map, position)?;
+            Ok(AnyEscaperConfig::ProxySsh(Box::new(config)))
+        }
         "proxy_float" | "proxyfloat" | "proxy_dynamic" | "proxydynamic" => {
             let config = proxy_float::ProxyFloatEscaperConfig::parse(map, position)?;
             Ok(AnyEscaperConfig::ProxyFloat(config))
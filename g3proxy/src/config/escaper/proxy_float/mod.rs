@@ -120,6 +120,9 @@ impl ProxyFloatEscaperConfig {
             "bind_interface" => {
                 let interface = g3_yaml::value::as_interface_name(v)
                     .context(format!("invalid interface name value for key {k}"))?;
+                interface
+                    .index()
+                    .map_err(|e| anyhow!("unusable interface {interface}: {e}"))?;
                 self.bind_interface = Some(interface);
                 Ok(())
             }
@@ -23,7 +23,7 @@ use ascii::AsciiString;
 use yaml_rust::{yaml, Yaml};
 
 use g3_types::auth::{Password, Username};
-use g3_types::collection::SelectivePickPolicy;
+use g3_types::collection::{SelectivePickOverride, SelectivePickPolicy};
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use g3_types::net::InterfaceName;
@@ -35,6 +35,7 @@ use g3_types::resolve::{QueryStrategy, ResolveStrategy};
 use g3_yaml::YamlDocPosition;
 
 use super::{AnyEscaperConfig, EscaperConfig, EscaperConfigDiffAction, GeneralEscaperConfig};
+use crate::module::http_header::{ConditionalAppendHeaders, StaticAppendHeaders};
 
 const ESCAPER_CONFIG_TYPE: &str = "ProxyHttp";
 
@@ -45,6 +46,7 @@ pub(crate) struct ProxyHttpEscaperConfig {
     pub(crate) shared_logger: Option<AsciiString>,
     pub(crate) proxy_nodes: Vec<WeightedUpstreamAddr>,
     pub(crate) proxy_pick_policy: SelectivePickPolicy,
+    pub(crate) proxy_pick_override: Option<SelectivePickOverride>,
     proxy_username: Username,
     proxy_password: Password,
     #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -61,7 +63,8 @@ pub(crate) struct ProxyHttpEscaperConfig {
     pub(crate) tcp_keepalive: TcpKeepAliveConfig,
     pub(crate) tcp_misc_opts: TcpMiscSockOpts,
     pub(crate) http_connect_rsp_hdr_max_size: usize,
-    pub(crate) append_http_headers: Vec<String>,
+    pub(crate) append_http_headers: StaticAppendHeaders,
+    pub(crate) conditional_append_headers: Vec<ConditionalAppendHeaders>,
     pub(crate) pass_proxy_userid: bool,
     pub(crate) use_proxy_protocol: Option<ProxyProtocolVersion>,
     pub(crate) peer_negotiation_timeout: Duration,
@@ -76,6 +79,7 @@ impl ProxyHttpEscaperConfig {
             shared_logger: None,
             proxy_nodes: Vec::with_capacity(1),
             proxy_pick_policy: SelectivePickPolicy::Random,
+            proxy_pick_override: None,
             proxy_username: Username::empty(),
             proxy_password: Password::empty(),
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -92,7 +96,8 @@ impl ProxyHttpEscaperConfig {
             tcp_keepalive: Default::default(),
             tcp_misc_opts: Default::default(),
             http_connect_rsp_hdr_max_size: 4096,
-            append_http_headers: Vec::new(),
+            append_http_headers: StaticAppendHeaders::default(),
+            conditional_append_headers: Vec::new(),
             pass_proxy_userid: false,
             use_proxy_protocol: None,
             peer_negotiation_timeout: Duration::from_secs(10),
@@ -143,6 +148,12 @@ impl ProxyHttpEscaperConfig {
                 self.proxy_pick_policy = g3_yaml::value::as_selective_pick_policy(v)?;
                 Ok(())
             }
+            "proxy_addr_pick_override" => {
+                let pick_override = g3_yaml::value::as_selective_pick_override(v)
+                    .context(format!("invalid selective pick override value for key {k}"))?;
+                self.proxy_pick_override = Some(pick_override);
+                Ok(())
+            }
             "proxy_username" | "proxy_user" => {
                 self.proxy_username = g3_yaml::value::as_username(v)
                     .context(format!("invalid username value for key {k}"))?;
@@ -157,6 +168,9 @@ impl ProxyHttpEscaperConfig {
             "bind_interface" => {
                 let interface = g3_yaml::value::as_interface_name(v)
                     .context(format!("invalid interface name value for key {k}"))?;
+                interface
+                    .index()
+                    .map_err(|e| anyhow!("unusable interface {interface}: {e}"))?;
                 self.bind_interface = Some(interface);
                 Ok(())
             }
@@ -221,6 +235,13 @@ impl ProxyHttpEscaperConfig {
                     .context(format!("invalid humanize usize value for key {k}"))?;
                 Ok(())
             }
+            "conditional_append_headers" => {
+                self.conditional_append_headers = ConditionalAppendHeaders::parse_yaml_list(v)
+                    .context(format!(
+                        "invalid conditional append headers value for key {k}"
+                    ))?;
+                Ok(())
+            }
             "pass_proxy_userid" => {
                 self.pass_proxy_userid = g3_yaml::value::as_bool(v)
                     .context(format!("invalid bool value for key {k}"))?;
@@ -248,6 +269,7 @@ impl ProxyHttpEscaperConfig {
         if self.proxy_nodes.is_empty() {
             return Err(anyhow!("proxy addr is not set"));
         }
+        super::check_pick_override(&mut self.proxy_pick_override, &self.proxy_nodes, |_| true)?;
         self.proxy_nodes.reverse(); // reverse as we push to the back
         if self.no_ipv4 && self.no_ipv6 {
             return Err(anyhow!("both ipv4 and ipv6 are disabled"));
@@ -307,10 +329,10 @@ impl ProxyHttpEscaperConfig {
             }
 
             self.append_http_headers
-                .push(g3_http::header::proxy_authorization_basic(
+                .push_line(g3_http::header::proxy_authorization_basic(
                     &self.proxy_username,
                     &self.proxy_password,
-                ));
+                ))?;
         }
 
         Ok(())
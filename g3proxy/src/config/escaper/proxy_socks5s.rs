@@ -24,7 +24,7 @@ use ascii::AsciiString;
 use yaml_rust::{yaml, Yaml};
 
 use g3_types::auth::{Password, Username};
-use g3_types::collection::SelectivePickPolicy;
+use g3_types::collection::{SelectivePickOverride, SelectivePickPolicy};
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use g3_types::net::InterfaceName;
@@ -46,6 +46,7 @@ pub(crate) struct ProxySocks5sEscaperConfig {
     pub(crate) shared_logger: Option<AsciiString>,
     pub(crate) proxy_nodes: Vec<WeightedUpstreamAddr>,
     pub(crate) proxy_pick_policy: SelectivePickPolicy,
+    pub(crate) proxy_pick_override: Option<SelectivePickOverride>,
     proxy_username: Username,
     proxy_password: Password,
     #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -67,6 +68,8 @@ pub(crate) struct ProxySocks5sEscaperConfig {
     pub(crate) peer_negotiation_timeout: Duration,
     transmute_udp_peer_ip: Option<AHashMap<IpAddr, IpAddr>>,
     pub(crate) end_on_control_closed: bool,
+    pub(crate) udp_peer_source_check: bool,
+    pub(crate) udp_max_datagram_size: usize,
     pub(crate) extra_metrics_tags: Option<Arc<StaticMetricsTags>>,
 }
 
@@ -78,6 +81,7 @@ impl ProxySocks5sEscaperConfig {
             shared_logger: None,
             proxy_nodes: Vec::with_capacity(1),
             proxy_pick_policy: SelectivePickPolicy::Random,
+            proxy_pick_override: None,
             proxy_username: Username::empty(),
             proxy_password: Password::empty(),
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -99,6 +103,8 @@ impl ProxySocks5sEscaperConfig {
             peer_negotiation_timeout: Duration::from_secs(10),
             transmute_udp_peer_ip: None,
             end_on_control_closed: false,
+            udp_peer_source_check: false,
+            udp_max_datagram_size: super::DEFAULT_UDP_MAX_DATAGRAM_SIZE,
             extra_metrics_tags: None,
         }
     }
@@ -146,6 +152,12 @@ impl ProxySocks5sEscaperConfig {
                 self.proxy_pick_policy = g3_yaml::value::as_selective_pick_policy(v)?;
                 Ok(())
             }
+            "proxy_addr_pick_override" => {
+                let pick_override = g3_yaml::value::as_selective_pick_override(v)
+                    .context(format!("invalid selective pick override value for key {k}"))?;
+                self.proxy_pick_override = Some(pick_override);
+                Ok(())
+            }
             "proxy_username" | "proxy_user" => {
                 self.proxy_username = g3_yaml::value::as_username(v)
                     .context(format!("invalid username value for key {k}"))?;
@@ -160,6 +172,9 @@ impl ProxySocks5sEscaperConfig {
             "bind_interface" => {
                 let interface = g3_yaml::value::as_interface_name(v)
                     .context(format!("invalid interface name value for key {k}"))?;
+                interface
+                    .index()
+                    .map_err(|e| anyhow!("unusable interface {interface}: {e}"))?;
                 self.bind_interface = Some(interface);
                 Ok(())
             }
@@ -270,6 +285,15 @@ impl ProxySocks5sEscaperConfig {
                 self.end_on_control_closed = g3_yaml::value::as_bool(v)?;
                 Ok(())
             }
+            "udp_peer_source_check" => {
+                self.udp_peer_source_check = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
+            "udp_max_datagram_size" => {
+                self.udp_max_datagram_size = g3_yaml::humanize::as_usize(v)
+                    .context(format!("invalid humanize usize value for key {k}"))?;
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
@@ -281,6 +305,7 @@ impl ProxySocks5sEscaperConfig {
         if self.proxy_nodes.is_empty() {
             return Err(anyhow!("proxy addr is not set"));
         }
+        super::check_pick_override(&mut self.proxy_pick_override, &self.proxy_nodes, |_| true)?;
         self.proxy_nodes.reverse(); // reverse as we push to the back
         if self.no_ipv4 && self.no_ipv6 {
             return Err(anyhow!("both ipv4 and ipv6 are disabled"));
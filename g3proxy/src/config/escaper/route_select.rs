@@ -19,7 +19,7 @@ use std::collections::BTreeSet;
 use anyhow::{anyhow, Context};
 use yaml_rust::{yaml, Yaml};
 
-use g3_types::collection::{SelectivePickPolicy, WeightedValue};
+use g3_types::collection::{SelectivePickOverride, SelectivePickPolicy, WeightedValue};
 use g3_types::metrics::MetricsName;
 use g3_yaml::YamlDocPosition;
 
@@ -33,6 +33,7 @@ pub(crate) struct RouteSelectEscaperConfig {
     position: Option<YamlDocPosition>,
     pub(crate) next_nodes: Vec<WeightedValue<MetricsName>>,
     pub(crate) next_pick_policy: SelectivePickPolicy,
+    pub(crate) next_pick_override: Option<SelectivePickOverride>,
 }
 
 impl RouteSelectEscaperConfig {
@@ -42,6 +43,7 @@ impl RouteSelectEscaperConfig {
             position,
             next_nodes: Vec::new(),
             next_pick_policy: SelectivePickPolicy::Ketama,
+            next_pick_override: None,
         }
     }
 
@@ -76,6 +78,12 @@ impl RouteSelectEscaperConfig {
                     .context(format!("invalid selective pick policy value for key {k}"))?;
                 Ok(())
             }
+            "next_pick_override" => {
+                let pick_override = g3_yaml::value::as_selective_pick_override(v)
+                    .context(format!("invalid selective pick override value for key {k}"))?;
+                self.next_pick_override = Some(pick_override);
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
@@ -87,6 +95,10 @@ impl RouteSelectEscaperConfig {
         if self.next_nodes.is_empty() {
             return Err(anyhow!("no next escapers found"));
         }
+        // only nodes with positive weight will be selected
+        super::check_pick_override(&mut self.next_pick_override, &self.next_nodes, |v| {
+            v.weight() > 0f64
+        })?;
         self.next_nodes.reverse(); // reverse as we push to the back
 
         Ok(())
@@ -34,6 +34,7 @@ pub(crate) mod escaper;
 pub(crate) mod log;
 pub(crate) mod resolver;
 pub(crate) mod server;
+pub(crate) mod status_page;
 
 pub fn load() -> anyhow::Result<&'static Path> {
     let config_file =
@@ -78,7 +79,7 @@ fn reload_doc(map: &yaml::Hash) -> anyhow::Result<()> {
     let conf_dir =
         g3_daemon::opts::config_dir().ok_or_else(|| anyhow!("no valid config dir has been set"))?;
     g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
-        "runtime" | "worker" | "log" | "stat" | "controller" => Ok(()),
+        "runtime" | "worker" | "log" | "stat" | "status_page" | "controller" => Ok(()),
         "escaper" => escaper::load_all(v, conf_dir),
         "server" => server::load_all(v, conf_dir),
         "resolver" => resolver::load_all(v, conf_dir),
@@ -97,6 +98,7 @@ fn load_doc(map: &yaml::Hash) -> anyhow::Result<()> {
         "worker" => g3_daemon::runtime::config::load_worker(v),
         "log" => log::load(v, conf_dir),
         "stat" => g3_daemon::stat::config::load(v, crate::build::PKG_NAME),
+        "status_page" => status_page::load(v),
         "controller" => g3_daemon::control::config::load(v),
         "escaper" => escaper::load_all(v, conf_dir),
         "server" => server::load_all(v, conf_dir),
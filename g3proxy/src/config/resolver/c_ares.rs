@@ -17,6 +17,7 @@
 use std::collections::BTreeSet;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::{anyhow, Context};
 use yaml_rust::{yaml, Yaml};
@@ -26,7 +27,7 @@ use g3_resolver::{AnyResolveDriverConfig, ResolverRuntimeConfig};
 use g3_types::metrics::MetricsName;
 use g3_yaml::YamlDocPosition;
 
-use super::{AnyResolverConfig, ResolverConfigDiffAction};
+use super::{AnyResolverConfig, ResolverConfigDiffAction, ResolverWarmupConfig};
 
 const RESOLVER_CONFIG_TYPE: &str = "c-ares";
 
@@ -35,6 +36,7 @@ pub(crate) struct CAresResolverConfig {
     name: MetricsName,
     position: Option<YamlDocPosition>,
     runtime: ResolverRuntimeConfig,
+    warmup: Option<Arc<ResolverWarmupConfig>>,
     driver: CAresDriverConfig,
 }
 
@@ -54,6 +56,7 @@ impl CAresResolverConfig {
             name: MetricsName::default(),
             position,
             runtime: Default::default(),
+            warmup: None,
             driver: Default::default(),
         }
     }
@@ -155,6 +158,18 @@ impl CAresResolverConfig {
                 self.runtime.protective_query_timeout = g3_yaml::humanize::as_duration(v)?;
                 Ok(())
             }
+            "static_hosts" => {
+                let lookup_dir = g3_daemon::config::get_lookup_dir(self.position.as_ref())?;
+                let hosts = super::parse_static_hosts(v, Some(lookup_dir))
+                    .context(format!("invalid static hosts value for key {k}"))?;
+                self.runtime.static_hosts = Some(Arc::new(hosts));
+                Ok(())
+            }
+            "warmup" => {
+                let warmup = ResolverWarmupConfig::parse(v)?;
+                self.warmup = Some(Arc::new(warmup));
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
@@ -237,6 +252,10 @@ impl super::ResolverConfig for CAresResolverConfig {
         ResolverConfigDiffAction::Update
     }
 
+    fn warmup(&self) -> Option<Arc<ResolverWarmupConfig>> {
+        self.warmup.clone()
+    }
+
     fn dependent_resolver(&self) -> Option<BTreeSet<MetricsName>> {
         None
     }
@@ -15,6 +15,7 @@
  */
 
 use std::collections::BTreeSet;
+use std::sync::Arc;
 
 use g3_types::metrics::MetricsName;
 use g3_yaml::YamlDocPosition;
@@ -26,6 +27,7 @@ use super::hickory;
 
 use super::deny_all;
 use super::fail_over;
+use super::ResolverWarmupConfig;
 
 pub(super) const CONFIG_KEY_RESOLVER_TYPE: &str = "type";
 pub(super) const CONFIG_KEY_RESOLVER_NAME: &str = "name";
@@ -43,6 +45,7 @@ pub(crate) trait ResolverConfig {
 
     fn diff_action(&self, new: &AnyResolverConfig) -> ResolverConfigDiffAction;
     fn dependent_resolver(&self) -> Option<BTreeSet<MetricsName>>;
+    fn warmup(&self) -> Option<Arc<ResolverWarmupConfig>>;
 }
 
 #[derive(Clone)]
@@ -89,6 +92,7 @@ impl AnyResolverConfig {
     impl_transparent0!(name, &MetricsName);
     impl_transparent0!(position, Option<YamlDocPosition>);
     impl_transparent0!(dependent_resolver, Option<BTreeSet<MetricsName>>);
+    impl_transparent0!(warmup, Option<Arc<ResolverWarmupConfig>>);
 
     impl_transparent1!(diff_action, ResolverConfigDiffAction, &Self);
 }
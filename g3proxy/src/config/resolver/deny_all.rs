@@ -15,6 +15,7 @@
  */
 
 use std::collections::BTreeSet;
+use std::sync::Arc;
 
 use anyhow::anyhow;
 use yaml_rust::{yaml, Yaml};
@@ -22,7 +23,7 @@ use yaml_rust::{yaml, Yaml};
 use g3_types::metrics::MetricsName;
 use g3_yaml::YamlDocPosition;
 
-use super::{AnyResolverConfig, ResolverConfig, ResolverConfigDiffAction};
+use super::{AnyResolverConfig, ResolverConfig, ResolverConfigDiffAction, ResolverWarmupConfig};
 
 const RESOLVER_CONFIG_TYPE: &str = "deny-all";
 
@@ -89,6 +90,10 @@ impl ResolverConfig for DenyAllResolverConfig {
         ResolverConfigDiffAction::NoAction
     }
 
+    fn warmup(&self) -> Option<Arc<ResolverWarmupConfig>> {
+        None
+    }
+
     fn dependent_resolver(&self) -> Option<BTreeSet<MetricsName>> {
         None
     }
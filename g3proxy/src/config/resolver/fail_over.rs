@@ -15,8 +15,9 @@
  */
 
 use std::collections::BTreeSet;
+use std::sync::Arc;
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use yaml_rust::{yaml, Yaml};
 
 use g3_resolver::driver::fail_over::FailOverDriverStaticConfig;
@@ -24,7 +25,7 @@ use g3_resolver::ResolverRuntimeConfig;
 use g3_types::metrics::MetricsName;
 use g3_yaml::YamlDocPosition;
 
-use super::{AnyResolverConfig, ResolverConfig, ResolverConfigDiffAction};
+use super::{AnyResolverConfig, ResolverConfig, ResolverConfigDiffAction, ResolverWarmupConfig};
 
 const RESOLVER_CONFIG_TYPE: &str = "fail-over";
 
@@ -33,6 +34,7 @@ pub(crate) struct FailOverResolverConfig {
     position: Option<YamlDocPosition>,
     name: MetricsName,
     pub(crate) runtime: ResolverRuntimeConfig,
+    warmup: Option<Arc<ResolverWarmupConfig>>,
     pub(crate) primary: MetricsName,
     pub(crate) standby: MetricsName,
     pub(crate) static_conf: FailOverDriverStaticConfig,
@@ -44,6 +46,7 @@ impl FailOverResolverConfig {
             name: MetricsName::default(),
             position,
             runtime: Default::default(),
+            warmup: None,
             primary: MetricsName::default(),
             standby: MetricsName::default(),
             static_conf: FailOverDriverStaticConfig::default(),
@@ -100,6 +103,18 @@ impl FailOverResolverConfig {
                 self.runtime.protective_query_timeout = g3_yaml::humanize::as_duration(v)?;
                 Ok(())
             }
+            "static_hosts" => {
+                let lookup_dir = g3_daemon::config::get_lookup_dir(self.position.as_ref())?;
+                let hosts = super::parse_static_hosts(v, Some(lookup_dir))
+                    .context(format!("invalid static hosts value for key {k}"))?;
+                self.runtime.static_hosts = Some(Arc::new(hosts));
+                Ok(())
+            }
+            "warmup" => {
+                let warmup = ResolverWarmupConfig::parse(v)?;
+                self.warmup = Some(Arc::new(warmup));
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
@@ -149,6 +164,10 @@ impl ResolverConfig for FailOverResolverConfig {
         ResolverConfigDiffAction::Update
     }
 
+    fn warmup(&self) -> Option<Arc<ResolverWarmupConfig>> {
+        self.warmup.clone()
+    }
+
     fn dependent_resolver(&self) -> Option<BTreeSet<MetricsName>> {
         let mut set = BTreeSet::new();
         set.insert(self.primary.clone());
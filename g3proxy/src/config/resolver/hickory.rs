@@ -17,16 +17,17 @@
 use std::collections::BTreeSet;
 use std::net::IpAddr;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::{anyhow, Context};
 use yaml_rust::{yaml, Yaml};
 
-use g3_resolver::driver::hickory::HickoryDriverConfig;
+use g3_resolver::driver::hickory::{HickoryDriverConfig, HickoryServerStrategy};
 use g3_resolver::{AnyResolveDriverConfig, ResolverRuntimeConfig};
 use g3_types::metrics::MetricsName;
 use g3_yaml::YamlDocPosition;
 
-use super::{AnyResolverConfig, ResolverConfigDiffAction};
+use super::{AnyResolverConfig, ResolverConfigDiffAction, ResolverWarmupConfig};
 
 const RESOLVER_CONFIG_TYPE: &str = "hickory";
 
@@ -35,6 +36,7 @@ pub(crate) struct HickoryResolverConfig {
     name: MetricsName,
     position: Option<YamlDocPosition>,
     runtime: ResolverRuntimeConfig,
+    warmup: Option<Arc<ResolverWarmupConfig>>,
     driver: HickoryDriverConfig,
 }
 
@@ -54,6 +56,7 @@ impl HickoryResolverConfig {
             name: MetricsName::default(),
             position,
             runtime: Default::default(),
+            warmup: None,
             driver: Default::default(),
         }
     }
@@ -137,6 +140,19 @@ impl HickoryResolverConfig {
                 self.driver.set_each_tries(attempts);
                 Ok(())
             }
+            "retry_interval" => {
+                let interval = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                self.driver.set_retry_interval(interval);
+                Ok(())
+            }
+            "server_strategy" => {
+                let s = g3_yaml::value::as_string(v)?;
+                let strategy = HickoryServerStrategy::from_str(&s)
+                    .map_err(|_| anyhow!("invalid server strategy value {s} for key {k}"))?;
+                self.driver.set_server_strategy(strategy);
+                Ok(())
+            }
             "bind_ip" => {
                 let ip = g3_yaml::value::as_ipaddr(v)?;
                 self.driver.set_bind_ip(ip);
@@ -166,6 +182,18 @@ impl HickoryResolverConfig {
                 self.runtime.protective_query_timeout = g3_yaml::humanize::as_duration(v)?;
                 Ok(())
             }
+            "static_hosts" => {
+                let lookup_dir = g3_daemon::config::get_lookup_dir(self.position.as_ref())?;
+                let hosts = super::parse_static_hosts(v, Some(lookup_dir))
+                    .context(format!("invalid static hosts value for key {k}"))?;
+                self.runtime.static_hosts = Some(Arc::new(hosts));
+                Ok(())
+            }
+            "warmup" => {
+                let warmup = ResolverWarmupConfig::parse(v)?;
+                self.warmup = Some(Arc::new(warmup));
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
@@ -230,6 +258,10 @@ impl super::ResolverConfig for HickoryResolverConfig {
         ResolverConfigDiffAction::Update
     }
 
+    fn warmup(&self) -> Option<Arc<ResolverWarmupConfig>> {
+        self.warmup.clone()
+    }
+
     fn dependent_resolver(&self) -> Option<BTreeSet<MetricsName>> {
         None
     }
@@ -36,6 +36,12 @@ pub(crate) mod fail_over;
 
 mod config;
 
+mod warmup;
+pub(crate) use warmup::ResolverWarmupConfig;
+
+mod static_hosts;
+use static_hosts::parse_static_hosts;
+
 pub(crate) use config::{AnyResolverConfig, ResolverConfig, ResolverConfigDiffAction};
 
 use config::{CONFIG_KEY_RESOLVER_NAME, CONFIG_KEY_RESOLVER_TYPE};
@@ -0,0 +1,114 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_resolver::{ResolverStaticHosts, SYSTEM_HOSTS_FILE};
+
+pub(crate) fn parse_static_hosts(
+    value: &Yaml,
+    lookup_dir: Option<&Path>,
+) -> anyhow::Result<ResolverStaticHosts> {
+    let Yaml::Hash(map) = value else {
+        return Err(anyhow!("invalid yaml value type, expect map"));
+    };
+
+    let mut hosts = ResolverStaticHosts::default();
+    let mut hosts_files: Vec<PathBuf> = Vec::new();
+    g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+        "ttl" => {
+            let ttl =
+                g3_yaml::value::as_u32(v).context(format!("invalid u32 value for key {k}"))?;
+            hosts.set_ttl(ttl);
+            Ok(())
+        }
+        "system_hosts" | "use_system_hosts" => {
+            if g3_yaml::value::as_bool(v).context(format!("invalid bool value for key {k}"))? {
+                hosts_files.push(PathBuf::from(SYSTEM_HOSTS_FILE));
+            }
+            Ok(())
+        }
+        "hosts_file" => {
+            let path = match lookup_dir {
+                Some(dir) => g3_yaml::value::as_file_path(v, dir, false),
+                None => g3_yaml::value::as_absolute_path(v),
+            }
+            .context(format!("invalid file path value for key {k}"))?;
+            hosts_files.push(path);
+            Ok(())
+        }
+        "records" | "hosts" => {
+            let Yaml::Hash(map) = v else {
+                return Err(anyhow!("invalid yaml value type for key {k}, expect map"));
+            };
+            g3_yaml::foreach_kv(map, |name, v| {
+                let name = g3_yaml::value::as_domain(&Yaml::String(name.to_string()))
+                    .context(format!("invalid domain {name}"))?;
+                match v {
+                    Yaml::Array(seq) => {
+                        for (i, v) in seq.iter().enumerate() {
+                            let ip = g3_yaml::value::as_ipaddr(v).context(format!(
+                                "invalid ip address value #{i} for domain {name}"
+                            ))?;
+                            hosts.add_record(&name, ip);
+                        }
+                    }
+                    _ => {
+                        let ip = g3_yaml::value::as_ipaddr(v)
+                            .context(format!("invalid ip address value for domain {name}"))?;
+                        hosts.add_record(&name, ip);
+                    }
+                }
+                Ok(())
+            })
+            .context(format!("invalid static host records value for key {k}"))
+        }
+        _ => Err(anyhow!("invalid key {k}")),
+    })?;
+
+    for path in hosts_files {
+        hosts
+            .load_hosts_file(&path)
+            .map_err(|e| anyhow!("failed to load hosts file {}: {e}", path.display()))?;
+    }
+    Ok(hosts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse() {
+        let v = YamlLoader::load_from_str(
+            "{ttl: 60, records: {example.com: [192.168.1.1, 'fe80::1'], example.net: 10.0.0.1}}",
+        )
+        .unwrap();
+        let hosts = parse_static_hosts(&v[0], None).unwrap();
+        assert_eq!(hosts.ttl(), 60);
+        assert!(!hosts.is_empty());
+
+        let v = YamlLoader::load_from_str("{records: {example.com: example.net}}").unwrap();
+        assert!(parse_static_hosts(&v[0], None).is_err());
+
+        let v = YamlLoader::load_from_str("{hosts_file: relative/hosts}").unwrap();
+        assert!(parse_static_hosts(&v[0], None).is_err());
+    }
+}
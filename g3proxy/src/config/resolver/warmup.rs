@@ -0,0 +1,117 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_types::resolve::QueryStrategy;
+
+const DEFAULT_WARMUP_PARALLELISM: usize = 8;
+
+/// The domains to resolve to prime the resolver cache
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ResolverWarmupConfig {
+    pub(crate) domains: Vec<Arc<str>>,
+    pub(crate) query_strategy: QueryStrategy,
+    pub(crate) parallelism: usize,
+}
+
+impl Default for ResolverWarmupConfig {
+    fn default() -> Self {
+        ResolverWarmupConfig {
+            domains: Vec::new(),
+            query_strategy: QueryStrategy::Ipv4First,
+            parallelism: DEFAULT_WARMUP_PARALLELISM,
+        }
+    }
+}
+
+impl ResolverWarmupConfig {
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let mut config = ResolverWarmupConfig::default();
+        match value {
+            Yaml::Hash(map) => {
+                g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                    "domains" | "domain" | "names" => config
+                        .parse_domains(v)
+                        .context(format!("invalid domain list value for key {k}")),
+                    "query_strategy" | "strategy" => {
+                        config.query_strategy = g3_yaml::value::as_query_strategy(v)
+                            .context(format!("invalid query strategy value for key {k}"))?;
+                        Ok(())
+                    }
+                    "parallelism" | "concurrency" => {
+                        config.parallelism = g3_yaml::value::as_usize(v)
+                            .context(format!("invalid usize value for key {k}"))?;
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("invalid key {k}")),
+                })?;
+            }
+            _ => config.parse_domains(value)?,
+        }
+        if config.parallelism == 0 {
+            return Err(anyhow!("warmup parallelism should not be zero"));
+        }
+        Ok(config)
+    }
+
+    fn parse_domains(&mut self, value: &Yaml) -> anyhow::Result<()> {
+        match value {
+            Yaml::Array(seq) => {
+                for (i, v) in seq.iter().enumerate() {
+                    let domain = g3_yaml::value::as_domain(v)
+                        .context(format!("invalid domain value #{i}"))?;
+                    self.domains.push(Arc::from(domain));
+                }
+                Ok(())
+            }
+            _ => {
+                let domain = g3_yaml::value::as_domain(value)?;
+                self.domains.push(Arc::from(domain));
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse() {
+        let v = YamlLoader::load_from_str("[example.com, example.net]").unwrap();
+        let config = ResolverWarmupConfig::parse(&v[0]).unwrap();
+        assert_eq!(config.domains.len(), 2);
+        assert_eq!(config.parallelism, DEFAULT_WARMUP_PARALLELISM);
+
+        let v = YamlLoader::load_from_str(
+            "{domains: [example.com], query_strategy: ipv4_only, parallelism: 2}",
+        )
+        .unwrap();
+        let config = ResolverWarmupConfig::parse(&v[0]).unwrap();
+        assert_eq!(config.domains.len(), 1);
+        assert_eq!(config.query_strategy, QueryStrategy::Ipv4Only);
+        assert_eq!(config.parallelism, 2);
+
+        let v = YamlLoader::load_from_str("{domains: [example.com], parallelism: 0}").unwrap();
+        assert!(ResolverWarmupConfig::parse(&v[0]).is_err());
+    }
+}
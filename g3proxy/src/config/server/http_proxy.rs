@@ -26,19 +26,22 @@ use yaml_rust::{yaml, Yaml};
 use g3_ftp_client::FtpClientConfig;
 use g3_io_ext::LimitedCopyConfig;
 use g3_tls_ticket::TlsTicketConfig;
-use g3_types::acl::{AclExactPortRule, AclNetworkRuleBuilder};
+use g3_types::acl::{AclExactPortRule, AclNetworkRuleBuilder, AclOrderedNetworkRuleBuilder};
 use g3_types::acl_set::AclDstHostRuleSetBuilder;
+use g3_types::limit::ClientConnLimitConfig;
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
 use g3_types::net::{
     HttpKeepAliveConfig, HttpServerId, OpensslClientConfigBuilder, RustlsServerConfigBuilder,
-    TcpListenConfig, TcpMiscSockOpts, TcpSockSpeedLimitConfig,
+    TcpKeepAliveConfig, TcpListenConfig, TcpMiscSockOpts, TcpSockSpeedLimitConfig,
 };
 use g3_yaml::YamlDocPosition;
 
 use super::{
-    AnyServerConfig, ServerConfig, ServerConfigDiffAction, IDLE_CHECK_DEFAULT_DURATION,
+    AnyServerConfig, ProtocolInspectPolicyOverride, ServerConfig, ServerConfigDiffAction,
+    TarpitConfig, TenantTagConfig, TlsOcspStaplingConfig, IDLE_CHECK_DEFAULT_DURATION,
     IDLE_CHECK_MAXIMUM_DURATION,
 };
+use crate::config::audit::ProtocolTcpNoDelayConfig;
 
 const SERVER_CONFIG_TYPE: &str = "HttpProxy";
 
@@ -66,15 +69,22 @@ pub(crate) struct HttpProxyServerConfig {
     position: Option<YamlDocPosition>,
     pub(crate) escaper: MetricsName,
     pub(crate) auditor: MetricsName,
+    pub(crate) inspect_policy_override: Option<ProtocolInspectPolicyOverride>,
+    pub(crate) protocol_tcp_no_delay: Option<ProtocolTcpNoDelayConfig>,
     pub(crate) user_group: MetricsName,
+    pub(crate) tenant_tag: Option<Arc<TenantTagConfig>>,
     pub(crate) shared_logger: Option<AsciiString>,
     pub(crate) listen: Option<TcpListenConfig>,
     pub(crate) listen_in_worker: bool,
     pub(crate) server_tls_config: Option<RustlsServerConfigBuilder>,
     pub(crate) tls_ticketer: Option<TlsTicketConfig>,
+    pub(crate) tls_ocsp_stapling: Option<TlsOcspStaplingConfig>,
     pub(crate) client_tls_config: OpensslClientConfigBuilder,
     pub(crate) ftp_client_config: Arc<FtpClientConfig>,
     pub(crate) ingress_net_filter: Option<AclNetworkRuleBuilder>,
+    pub(crate) client_net_acl: Option<AclOrderedNetworkRuleBuilder>,
+    pub(crate) client_conn_limit: Option<ClientConnLimitConfig>,
+    pub(crate) tarpit: Option<TarpitConfig>,
     pub(crate) dst_host_filter: Option<AclDstHostRuleSetBuilder>,
     pub(crate) dst_port_filter: Option<AclExactPortRule>,
     pub(crate) server_id: Option<HttpServerId>,
@@ -83,9 +93,13 @@ pub(crate) struct HttpProxyServerConfig {
     pub(crate) timeout: HttpProxyServerTimeoutConfig,
     pub(crate) task_idle_check_duration: Duration,
     pub(crate) task_idle_max_count: i32,
+    pub(crate) max_task_lifetime: Option<Duration>,
+    pub(crate) tcp_half_close_linger_timeout: Option<Duration>,
     pub(crate) tcp_copy: LimitedCopyConfig,
+    pub(crate) tcp_keepalive: TcpKeepAliveConfig,
     pub(crate) tcp_misc_opts: TcpMiscSockOpts,
     pub(crate) req_hdr_max_size: usize,
+    pub(crate) req_hdr_max_count: usize,
     pub(crate) rsp_hdr_max_size: usize,
     pub(crate) log_uri_max_chars: usize,
     pub(crate) pipeline_size: usize,
@@ -96,6 +110,7 @@ pub(crate) struct HttpProxyServerConfig {
     pub(crate) http_forward_upstream_keepalive: HttpKeepAliveConfig,
     pub(crate) http_forward_mark_upstream: bool,
     pub(crate) echo_chained_info: bool,
+    pub(crate) echo_error_code: bool,
     pub(crate) untrusted_read_limit: Option<TcpSockSpeedLimitConfig>,
     pub(crate) egress_path_selection_header: Option<HeaderName>,
     pub(crate) steal_forwarded_for: bool,
@@ -109,15 +124,22 @@ impl HttpProxyServerConfig {
             position,
             escaper: MetricsName::default(),
             auditor: MetricsName::default(),
+            inspect_policy_override: None,
+            protocol_tcp_no_delay: None,
             user_group: MetricsName::default(),
+            tenant_tag: None,
             shared_logger: None,
             listen: None,
             listen_in_worker: false,
             server_tls_config: None,
             tls_ticketer: None,
+            tls_ocsp_stapling: None,
             client_tls_config: OpensslClientConfigBuilder::with_cache_for_many_sites(),
             ftp_client_config: Arc::new(Default::default()),
             ingress_net_filter: None,
+            client_net_acl: None,
+            client_conn_limit: None,
+            tarpit: None,
             dst_host_filter: None,
             dst_port_filter: None,
             server_id: None,
@@ -126,9 +148,13 @@ impl HttpProxyServerConfig {
             timeout: HttpProxyServerTimeoutConfig::default(),
             task_idle_check_duration: IDLE_CHECK_DEFAULT_DURATION,
             task_idle_max_count: 1,
+            max_task_lifetime: None,
+            tcp_half_close_linger_timeout: None,
             tcp_copy: Default::default(),
+            tcp_keepalive: Default::default(),
             tcp_misc_opts: Default::default(),
             req_hdr_max_size: 65536, // 64KiB
+            req_hdr_max_count: 128,
             rsp_hdr_max_size: 65536, // 64KiB
             log_uri_max_chars: 1024,
             pipeline_size: 10,
@@ -139,6 +165,7 @@ impl HttpProxyServerConfig {
             http_forward_upstream_keepalive: Default::default(),
             http_forward_mark_upstream: false,
             echo_chained_info: false,
+            echo_error_code: false,
             untrusted_read_limit: None,
             egress_path_selection_header: None,
             steal_forwarded_for: false,
@@ -173,10 +200,29 @@ impl HttpProxyServerConfig {
                 self.auditor = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
             }
+            "inspect_policy_override" | "protocol_inspect_policy" => {
+                let policy = ProtocolInspectPolicyOverride::parse(v).context(format!(
+                    "invalid protocol inspect policy override value for key {k}"
+                ))?;
+                self.inspect_policy_override = Some(policy);
+                Ok(())
+            }
+            "protocol_tcp_no_delay" => {
+                let config = ProtocolTcpNoDelayConfig::parse(v)
+                    .context(format!("invalid protocol tcp no delay value for key {k}"))?;
+                self.protocol_tcp_no_delay = Some(config);
+                Ok(())
+            }
             "user_group" => {
                 self.user_group = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
             }
+            "tenant_tag" | "tenant" => {
+                let config = TenantTagConfig::parse(v)
+                    .context(format!("invalid tenant tag config value for key {k}"))?;
+                self.tenant_tag = Some(Arc::new(config));
+                Ok(())
+            }
             "shared_logger" => {
                 let name = g3_yaml::value::as_ascii(v)?;
                 self.shared_logger = Some(name);
@@ -212,6 +258,17 @@ impl HttpProxyServerConfig {
                 self.tls_ticketer = Some(ticketer);
                 Ok(())
             }
+            "tls_ocsp_stapling" => {
+                if let Yaml::Boolean(false) = v {
+                    self.tls_ocsp_stapling = None;
+                } else {
+                    let config = TlsOcspStaplingConfig::parse(v).context(format!(
+                        "invalid tls ocsp stapling config value for key {k}"
+                    ))?;
+                    self.tls_ocsp_stapling = Some(config);
+                }
+                Ok(())
+            }
             "tls_client" => {
                 let lookup_dir = g3_daemon::config::get_lookup_dir(self.position.as_ref())?;
                 self.client_tls_config =
@@ -237,6 +294,25 @@ impl HttpProxyServerConfig {
                 self.ingress_net_filter = Some(filter);
                 Ok(())
             }
+            "client_network_acl" | "client_net_acl" => {
+                let acl = g3_yaml::value::acl::as_ordered_network_rule_builder(v)
+                    .context(format!("invalid client network acl rule value for key {k}"))?;
+                self.client_net_acl = Some(acl);
+                Ok(())
+            }
+            "client_connection_limit" | "client_conn_limit" => {
+                let limit = g3_yaml::value::as_client_conn_limit_config(v).context(format!(
+                    "invalid client connection limit config value for key {k}"
+                ))?;
+                self.client_conn_limit = Some(limit);
+                Ok(())
+            }
+            "tarpit" => {
+                let tarpit = TarpitConfig::parse(v)
+                    .context(format!("invalid tarpit config value for key {k}"))?;
+                self.tarpit = Some(tarpit);
+                Ok(())
+            }
             "dst_host_filter_set" => {
                 let filter_set = g3_yaml::value::acl_set::as_dst_host_rule_set_builder(v)
                     .context(format!("invalid dst host acl rule set value for key {k}"))?;
@@ -271,12 +347,23 @@ impl HttpProxyServerConfig {
                 self.tcp_copy.set_buffer_size(buffer_size);
                 Ok(())
             }
+            "tcp_copy_max_buffer_size" => {
+                let max_buffer_size = g3_yaml::humanize::as_usize(v)
+                    .context(format!("invalid humanize usize value for key {k}"))?;
+                self.tcp_copy.set_max_buffer_size(max_buffer_size);
+                Ok(())
+            }
             "tcp_copy_yield_size" => {
                 let yield_size = g3_yaml::humanize::as_usize(v)
                     .context(format!("invalid humanize usize value for key {k}"))?;
                 self.tcp_copy.set_yield_size(yield_size);
                 Ok(())
             }
+            "tcp_keepalive" | "tcp_client_keepalive" => {
+                self.tcp_keepalive = g3_yaml::value::as_tcp_keepalive_config(v)
+                    .context(format!("invalid tcp keepalive config value for key {k}"))?;
+                Ok(())
+            }
             "tcp_misc_opts" => {
                 self.tcp_misc_opts = g3_yaml::value::as_tcp_misc_sock_opts(v)
                     .context(format!("invalid tcp misc sock opts value for key {k}"))?;
@@ -292,6 +379,21 @@ impl HttpProxyServerConfig {
                     g3_yaml::value::as_i32(v).context(format!("invalid i32 value for key {k}"))?;
                 Ok(())
             }
+            "max_task_lifetime" | "task_max_lifetime" => {
+                self.max_task_lifetime = super::as_max_task_lifetime(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
+            "tcp_half_close_linger_timeout" | "half_close_linger_timeout" => {
+                let timeout = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                self.tcp_half_close_linger_timeout = if timeout.is_zero() {
+                    None
+                } else {
+                    Some(timeout)
+                };
+                Ok(())
+            }
             "req_header_recv_timeout" => {
                 self.timeout.recv_req_header = g3_yaml::humanize::as_duration(v)
                     .context(format!("invalid humanize duration value for key {k}"))?;
@@ -307,6 +409,11 @@ impl HttpProxyServerConfig {
                     .context(format!("invalid humanize usize value for key {k}"))?;
                 Ok(())
             }
+            "req_header_max_count" => {
+                self.req_hdr_max_count = g3_yaml::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                Ok(())
+            }
             "rsp_header_max_size" => {
                 self.rsp_hdr_max_size = g3_yaml::humanize::as_usize(v)
                     .context(format!("invalid humanize usize value for key {k}"))?;
@@ -355,6 +462,10 @@ impl HttpProxyServerConfig {
                 self.echo_chained_info = g3_yaml::value::as_bool(v)?;
                 Ok(())
             }
+            "echo_error_code" => {
+                self.echo_error_code = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
             "untrusted_read_speed_limit" | "untrusted_read_limit" => {
                 let limit = g3_yaml::value::as_tcp_sock_speed_limit(v)
                     .context(format!("invalid tcp socket speed limit value for key {k}"))?;
@@ -399,6 +510,16 @@ impl HttpProxyServerConfig {
         if self.task_idle_check_duration > IDLE_CHECK_MAXIMUM_DURATION {
             self.task_idle_check_duration = IDLE_CHECK_MAXIMUM_DURATION;
         }
+        if let Some(c) = &self.tls_ocsp_stapling {
+            if self.server_tls_config.is_none() {
+                return Err(anyhow!(
+                    "tls ocsp stapling is set but tls server is not enabled"
+                ));
+            }
+            if c.hard_fail {
+                return Err(anyhow!("hard fail of tls ocsp stapling is not supported"));
+            }
+        }
 
         Ok(())
     }
@@ -461,4 +582,78 @@ impl ServerConfig for HttpProxyServerConfig {
     fn task_max_idle_count(&self) -> i32 {
         self.task_idle_max_count
     }
+
+    #[inline]
+    fn max_task_lifetime(&self) -> Option<Duration> {
+        self.max_task_lifetime
+    }
+
+    #[inline]
+    fn tcp_half_close_linger_timeout(&self) -> Option<Duration> {
+        self.tcp_half_close_linger_timeout
+    }
+
+    #[inline]
+    fn tenant_tag(&self) -> Option<&Arc<TenantTagConfig>> {
+        self.tenant_tag.as_ref()
+    }
+
+    #[inline]
+    fn inspect_policy_override(&self) -> Option<&ProtocolInspectPolicyOverride> {
+        self.inspect_policy_override.as_ref()
+    }
+
+    fn protocol_tcp_no_delay(&self) -> Option<&ProtocolTcpNoDelayConfig> {
+        self.protocol_tcp_no_delay.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Version;
+    use tokio::io::BufReader;
+
+    use g3_http::server::{HttpProxyClientRequest, HttpRequestParseError};
+
+    fn request_with_headers(count: usize) -> Vec<u8> {
+        let mut req = b"GET http://example.com/ HTTP/1.1\r\nHost: example.com\r\n".to_vec();
+        for i in 1..count {
+            req.extend_from_slice(format!("X-Test-{i}: {i}\r\n").as_bytes());
+        }
+        req.extend_from_slice(b"\r\n");
+        req
+    }
+
+    async fn parse_request(
+        config: &HttpProxyServerConfig,
+        content: &[u8],
+    ) -> Result<HttpProxyClientRequest, HttpRequestParseError> {
+        let mut reader = BufReader::new(content);
+        let mut version = Version::HTTP_11;
+        HttpProxyClientRequest::parse(
+            &mut reader,
+            config.req_hdr_max_size,
+            config.req_hdr_max_count,
+            &mut version,
+            |req, name, header| req.append_header(name, header),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn default_req_header_max_count() {
+        let config = HttpProxyServerConfig::new(None);
+        assert_eq!(config.req_hdr_max_count, 128);
+
+        let req = parse_request(&config, &request_with_headers(128))
+            .await
+            .unwrap();
+        assert_eq!(req.end_to_end_headers.len(), 128);
+
+        let Err(e) = parse_request(&config, &request_with_headers(129)).await else {
+            panic!("the request should be rejected");
+        };
+        assert!(matches!(e, HttpRequestParseError::TooManyHeaders(128)));
+    }
 }
@@ -17,7 +17,9 @@
 use anyhow::{anyhow, Context};
 use yaml_rust::Yaml;
 
-use g3_types::net::{Host, OpensslClientConfigBuilder, RustlsServerConfigBuilder, UpstreamAddr};
+use g3_types::net::{
+    Host, HttpPathRewriter, OpensslClientConfigBuilder, RustlsServerConfigBuilder, UpstreamAddr,
+};
 use g3_yaml::{YamlDocPosition, YamlMapCallback};
 
 #[derive(Debug, PartialEq)]
@@ -26,6 +28,7 @@ pub(crate) struct HttpHostConfig {
     pub(crate) tls_server_builder: Option<RustlsServerConfigBuilder>,
     pub(crate) tls_client_builder: Option<OpensslClientConfigBuilder>,
     pub(crate) tls_name: Host,
+    pub(crate) path_rewrite: HttpPathRewriter,
 }
 
 impl Default for HttpHostConfig {
@@ -35,6 +38,7 @@ impl Default for HttpHostConfig {
             tls_server_builder: None,
             tls_client_builder: None,
             tls_name: Host::empty(),
+            path_rewrite: HttpPathRewriter::default(),
         }
     }
 }
@@ -89,6 +93,11 @@ impl YamlMapCallback for HttpHostConfig {
                     .context(format!("invalid tls name value for key {key}"))?;
                 Ok(())
             }
+            "path_rewrite" => {
+                self.path_rewrite = g3_yaml::value::as_http_path_rewriter(value)
+                    .context(format!("invalid http path rewrite value for key {key}"))?;
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {key}")),
         }
     }
@@ -23,18 +23,18 @@ use yaml_rust::{yaml, Yaml};
 
 use g3_io_ext::LimitedCopyConfig;
 use g3_tls_ticket::TlsTicketConfig;
-use g3_types::acl::AclNetworkRuleBuilder;
+use g3_types::acl::{AclNetworkRuleBuilder, AclOrderedNetworkRuleBuilder};
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
 use g3_types::net::{
     HttpForwardedHeaderType, HttpKeepAliveConfig, HttpServerId, RustlsServerConfigBuilder,
-    TcpListenConfig, TcpMiscSockOpts, TcpSockSpeedLimitConfig,
+    TcpKeepAliveConfig, TcpListenConfig, TcpMiscSockOpts, TcpSockSpeedLimitConfig,
 };
 use g3_types::route::HostMatch;
 use g3_yaml::YamlDocPosition;
 
 use super::{
-    AnyServerConfig, ServerConfig, ServerConfigDiffAction, IDLE_CHECK_DEFAULT_DURATION,
-    IDLE_CHECK_MAXIMUM_DURATION,
+    AnyServerConfig, HttpLocalResponseConfig, ServerConfig, ServerConfigDiffAction,
+    TenantTagConfig, IDLE_CHECK_DEFAULT_DURATION, IDLE_CHECK_MAXIMUM_DURATION,
 };
 
 mod host;
@@ -66,19 +66,24 @@ pub(crate) struct HttpRProxyServerConfig {
     position: Option<YamlDocPosition>,
     pub(crate) escaper: MetricsName,
     pub(crate) user_group: MetricsName,
+    pub(crate) tenant_tag: Option<Arc<TenantTagConfig>>,
     pub(crate) shared_logger: Option<AsciiString>,
     pub(crate) listen: Option<TcpListenConfig>,
     pub(crate) listen_in_worker: bool,
     pub(crate) ingress_net_filter: Option<AclNetworkRuleBuilder>,
+    pub(crate) client_net_acl: Option<AclOrderedNetworkRuleBuilder>,
     pub(crate) server_id: Option<HttpServerId>,
     pub(crate) auth_realm: AsciiString,
     pub(crate) tcp_sock_speed_limit: TcpSockSpeedLimitConfig,
     pub(crate) timeout: HttpRProxyServerTimeoutConfig,
     pub(crate) task_idle_check_duration: Duration,
     pub(crate) task_idle_max_count: i32,
+    pub(crate) max_task_lifetime: Option<Duration>,
     pub(crate) tcp_copy: LimitedCopyConfig,
+    pub(crate) tcp_keepalive: TcpKeepAliveConfig,
     pub(crate) tcp_misc_opts: TcpMiscSockOpts,
     pub(crate) req_hdr_max_size: usize,
+    pub(crate) req_hdr_max_count: usize,
     pub(crate) rsp_hdr_max_size: usize,
     pub(crate) log_uri_max_chars: usize,
     pub(crate) pipeline_size: usize,
@@ -90,6 +95,7 @@ pub(crate) struct HttpRProxyServerConfig {
     pub(crate) append_forwarded_for: HttpForwardedHeaderType,
     pub(crate) extra_metrics_tags: Option<Arc<StaticMetricsTags>>,
     pub(crate) hosts: HostMatch<Arc<HttpHostConfig>>,
+    pub(crate) local_response: HttpLocalResponseConfig,
     pub(crate) enable_tls_server: bool,
     pub(crate) global_tls_server: Option<RustlsServerConfigBuilder>,
     pub(crate) tls_ticketer: Option<TlsTicketConfig>,
@@ -103,19 +109,24 @@ impl HttpRProxyServerConfig {
             position,
             escaper: MetricsName::default(),
             user_group: MetricsName::default(),
+            tenant_tag: None,
             shared_logger: None,
             listen: None,
             listen_in_worker: false,
             ingress_net_filter: None,
+            client_net_acl: None,
             server_id: None,
             auth_realm: AsciiString::from_ascii("g3proxy").unwrap(),
             tcp_sock_speed_limit: TcpSockSpeedLimitConfig::default(),
             timeout: HttpRProxyServerTimeoutConfig::default(),
             task_idle_check_duration: IDLE_CHECK_DEFAULT_DURATION,
             task_idle_max_count: 1,
+            max_task_lifetime: None,
             tcp_copy: Default::default(),
+            tcp_keepalive: Default::default(),
             tcp_misc_opts: Default::default(),
             req_hdr_max_size: 65536, // 64KiB
+            req_hdr_max_count: 128,
             rsp_hdr_max_size: 65536, // 64KiB
             log_uri_max_chars: 1024,
             pipeline_size: 10,
@@ -127,6 +138,7 @@ impl HttpRProxyServerConfig {
             append_forwarded_for: HttpForwardedHeaderType::default(),
             extra_metrics_tags: None,
             hosts: Default::default(),
+            local_response: HttpLocalResponseConfig::default(),
             enable_tls_server: false,
             global_tls_server: None,
             tls_ticketer: None,
@@ -161,6 +173,12 @@ impl HttpRProxyServerConfig {
                 self.user_group = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
             }
+            "tenant_tag" | "tenant" => {
+                let config = TenantTagConfig::parse(v)
+                    .context(format!("invalid tenant tag config value for key {k}"))?;
+                self.tenant_tag = Some(Arc::new(config));
+                Ok(())
+            }
             "shared_logger" => {
                 let name = g3_yaml::value::as_ascii(v)?;
                 self.shared_logger = Some(name);
@@ -189,6 +207,12 @@ impl HttpRProxyServerConfig {
                 self.ingress_net_filter = Some(filter);
                 Ok(())
             }
+            "client_network_acl" | "client_net_acl" => {
+                let acl = g3_yaml::value::acl::as_ordered_network_rule_builder(v)
+                    .context(format!("invalid client network acl rule value for key {k}"))?;
+                self.client_net_acl = Some(acl);
+                Ok(())
+            }
             "server_id" => {
                 let server_id = g3_yaml::value::as_http_server_id(v)
                     .context(format!("invalid http server id value for key {k}"))?;
@@ -211,12 +235,23 @@ impl HttpRProxyServerConfig {
                 self.tcp_copy.set_buffer_size(buffer_size);
                 Ok(())
             }
+            "tcp_copy_max_buffer_size" => {
+                let max_buffer_size = g3_yaml::humanize::as_usize(v)
+                    .context(format!("invalid humanize usize value for key {k}"))?;
+                self.tcp_copy.set_max_buffer_size(max_buffer_size);
+                Ok(())
+            }
             "tcp_copy_yield_size" => {
                 let yield_size = g3_yaml::humanize::as_usize(v)
                     .context(format!("invalid humanize usize value for key {k}"))?;
                 self.tcp_copy.set_yield_size(yield_size);
                 Ok(())
             }
+            "tcp_keepalive" | "tcp_client_keepalive" => {
+                self.tcp_keepalive = g3_yaml::value::as_tcp_keepalive_config(v)
+                    .context(format!("invalid tcp keepalive config value for key {k}"))?;
+                Ok(())
+            }
             "tcp_misc_opts" => {
                 self.tcp_misc_opts = g3_yaml::value::as_tcp_misc_sock_opts(v)
                     .context(format!("invalid tcp misc sock opts value for key {k}"))?;
@@ -232,6 +267,11 @@ impl HttpRProxyServerConfig {
                     g3_yaml::value::as_i32(v).context(format!("invalid i32 value for key {k}"))?;
                 Ok(())
             }
+            "max_task_lifetime" | "task_max_lifetime" => {
+                self.max_task_lifetime = super::as_max_task_lifetime(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
             "req_header_recv_timeout" => {
                 self.timeout.recv_req_header = g3_yaml::humanize::as_duration(v)
                     .context(format!("invalid humanize duration value for key {k}"))?;
@@ -247,6 +287,11 @@ impl HttpRProxyServerConfig {
                     .context(format!("invalid humanize usize value for key {k}"))?;
                 Ok(())
             }
+            "req_header_max_count" => {
+                self.req_hdr_max_count = g3_yaml::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                Ok(())
+            }
             "rsp_header_max_size" => {
                 self.rsp_hdr_max_size = g3_yaml::humanize::as_usize(v)
                     .context(format!("invalid humanize usize value for key {k}"))?;
@@ -302,6 +347,11 @@ impl HttpRProxyServerConfig {
                     ))?;
                 Ok(())
             }
+            "local_response" | "local_responses" => {
+                self.local_response = HttpLocalResponseConfig::parse(v)
+                    .context(format!("invalid local response config value for key {k}"))?;
+                Ok(())
+            }
             "enable_tls_server" => {
                 self.enable_tls_server = g3_yaml::value::as_bool(v)
                     .context(format!("invalid bool value for key {k}"))?;
@@ -408,4 +458,14 @@ impl ServerConfig for HttpRProxyServerConfig {
     fn task_max_idle_count(&self) -> i32 {
         self.task_idle_max_count
     }
+
+    #[inline]
+    fn max_task_lifetime(&self) -> Option<Duration> {
+        self.max_task_lifetime
+    }
+
+    #[inline]
+    fn tenant_tag(&self) -> Option<&Arc<TenantTagConfig>> {
+        self.tenant_tag.as_ref()
+    }
 }
@@ -0,0 +1,85 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_dpi::ProtocolInspectPolicyBuilder;
+
+/// Server level protocol inspect policies, which take precedence over the ones in auditor.
+///
+/// Only the protocols set here will be overridden.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ProtocolInspectPolicyOverride {
+    pub(crate) h2: Option<ProtocolInspectPolicyBuilder>,
+    pub(crate) websocket: Option<ProtocolInspectPolicyBuilder>,
+    pub(crate) smtp: Option<ProtocolInspectPolicyBuilder>,
+    pub(crate) imap: Option<ProtocolInspectPolicyBuilder>,
+    pub(crate) pop3: Option<ProtocolInspectPolicyBuilder>,
+}
+
+impl ProtocolInspectPolicyOverride {
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        if let Yaml::Hash(map) = value {
+            let mut config = ProtocolInspectPolicyOverride::default();
+            g3_yaml::foreach_kv(map, |k, v| {
+                let policy = match g3_yaml::key::normalize(k).as_str() {
+                    "h2" | "http2" => &mut config.h2,
+                    "websocket" => &mut config.websocket,
+                    "smtp" => &mut config.smtp,
+                    "imap" => &mut config.imap,
+                    "pop3" => &mut config.pop3,
+                    _ => return Err(anyhow!("invalid key {k}")),
+                };
+                *policy = Some(
+                    g3_yaml::value::as_protocol_inspect_policy_builder(v)
+                        .context(format!("invalid protocol inspect policy value for key {k}"))?,
+                );
+                Ok(())
+            })?;
+            Ok(config)
+        } else {
+            Err(anyhow!(
+                "yaml value type for 'protocol inspect policy override' should be 'map'"
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use g3_dpi::ProtocolInspectAction;
+    use g3_types::net::Host;
+    use std::str::FromStr;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn override_one() {
+        let v = YamlLoader::load_from_str("{websocket: bypass}").unwrap();
+        let config = ProtocolInspectPolicyOverride::parse(&v[0]).unwrap();
+        assert!(config.h2.is_none());
+        assert!(config.smtp.is_none());
+        assert!(config.imap.is_none());
+        assert!(config.pop3.is_none());
+
+        let policy = config.websocket.unwrap().build();
+        let host = Host::from_str("www.example.net").unwrap();
+        let (found, action) = policy.check(&host);
+        assert!(!found);
+        assert_eq!(action, ProtocolInspectAction::Bypass);
+    }
+}
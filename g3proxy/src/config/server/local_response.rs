@@ -0,0 +1,183 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use http::Method;
+use yaml_rust::Yaml;
+
+use g3_types::net::HttpBlockResponse;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum HttpLocalPathMatch {
+    Exact(String),
+    Prefix(String),
+}
+
+impl HttpLocalPathMatch {
+    fn matches(&self, path: &str) -> bool {
+        match self {
+            HttpLocalPathMatch::Exact(p) => path.eq(p),
+            HttpLocalPathMatch::Prefix(p) => path.starts_with(p.as_str()),
+        }
+    }
+}
+
+/// A fixed response that will be sent to the client directly for the matched request
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct HttpLocalResponseRule {
+    method: Option<Method>,
+    path: HttpLocalPathMatch,
+    response: Arc<HttpBlockResponse>,
+}
+
+impl HttpLocalResponseRule {
+    fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let Yaml::Hash(map) = value else {
+            return Err(anyhow!(
+                "yaml value type for 'http local response rule' should be 'map'"
+            ));
+        };
+
+        let mut method = None;
+        let mut path = None;
+        let mut response = None;
+        g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "method" => {
+                let s = g3_yaml::value::as_string(v)
+                    .context(format!("invalid string value for key {k}"))?;
+                let m = Method::from_bytes(s.to_ascii_uppercase().as_bytes())
+                    .map_err(|e| anyhow!("invalid http method {s}: {e}"))?;
+                method = Some(m);
+                Ok(())
+            }
+            "path" | "exact_path" => {
+                let s = as_path(v).context(format!("invalid path value for key {k}"))?;
+                path = Some(HttpLocalPathMatch::Exact(s));
+                Ok(())
+            }
+            "path_prefix" | "prefix" => {
+                let s = as_path(v).context(format!("invalid path value for key {k}"))?;
+                path = Some(HttpLocalPathMatch::Prefix(s));
+                Ok(())
+            }
+            "response" => {
+                let rsp = g3_yaml::value::as_http_block_response(v)
+                    .context(format!("invalid http response value for key {k}"))?;
+                response = Some(Arc::new(rsp));
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+
+        let Some(path) = path else {
+            return Err(anyhow!("no path or path prefix set"));
+        };
+        let Some(response) = response else {
+            return Err(anyhow!("no response set"));
+        };
+        Ok(HttpLocalResponseRule {
+            method,
+            path,
+            response,
+        })
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        if let Some(expected) = &self.method {
+            if method.ne(expected) {
+                return false;
+            }
+        }
+        self.path.matches(path)
+    }
+}
+
+fn as_path(value: &Yaml) -> anyhow::Result<String> {
+    let path = g3_yaml::value::as_string(value)?;
+    if !path.starts_with('/') {
+        return Err(anyhow!("the path should start with '/'"));
+    }
+    Ok(path)
+}
+
+/// Requests that will be answered by the server itself without going to the upstream
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct HttpLocalResponseConfig {
+    rules: Vec<HttpLocalResponseRule>,
+}
+
+impl HttpLocalResponseConfig {
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let rules = g3_yaml::value::as_list(value, HttpLocalResponseRule::parse)?;
+        Ok(HttpLocalResponseConfig { rules })
+    }
+
+    /// Find the response of the first matched rule
+    pub(crate) fn find(&self, method: &Method, path: &str) -> Option<&Arc<HttpBlockResponse>> {
+        self.rules
+            .iter()
+            .find(|r| r.matches(method, path))
+            .map(|r| &r.response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::StatusCode;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse_and_find() {
+        let yaml = r#"
+- path: /healthz
+  method: get
+  response: 200
+- path_prefix: /old/
+  response:
+    status: 301
+    headers:
+      Location: https://www.example.net/
+- path_prefix: /
+  method: POST
+  response: 405
+"#;
+        let doc = YamlLoader::load_from_str(yaml).unwrap();
+        let config = HttpLocalResponseConfig::parse(&doc[0]).unwrap();
+        assert_eq!(config.rules.len(), 3);
+
+        let rsp = config.find(&Method::GET, "/healthz").unwrap();
+        assert_eq!(rsp.status(), StatusCode::OK);
+        assert!(config.find(&Method::GET, "/healthz/more").is_none());
+        assert!(config.find(&Method::HEAD, "/healthz").is_none());
+
+        let rsp = config.find(&Method::GET, "/old/index.html").unwrap();
+        assert_eq!(rsp.status(), StatusCode::MOVED_PERMANENTLY);
+
+        // the first matched one wins
+        let rsp = config.find(&Method::POST, "/old/form").unwrap();
+        assert_eq!(rsp.status(), StatusCode::MOVED_PERMANENTLY);
+        let rsp = config.find(&Method::POST, "/healthz").unwrap();
+        assert_eq!(rsp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let doc = YamlLoader::load_from_str("- path: healthz\n  response: 200").unwrap();
+        assert!(HttpLocalResponseConfig::parse(&doc[0]).is_err());
+        let doc = YamlLoader::load_from_str("- path: /healthz").unwrap();
+        assert!(HttpLocalResponseConfig::parse(&doc[0]).is_err());
+    }
+}
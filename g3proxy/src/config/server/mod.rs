@@ -31,6 +31,7 @@ use g3_yaml::{HybridParser, YamlDocPosition};
 
 use crate::audit::AuditHandle;
 use crate::auth::UserGroup;
+use crate::config::audit::ProtocolTcpNoDelayConfig;
 
 pub(crate) mod dummy_close;
 pub(crate) mod intelli_proxy;
@@ -54,6 +55,27 @@ pub(crate) mod tcp_stream;
 pub(crate) mod tcp_tproxy;
 pub(crate) mod tls_stream;
 
+mod inspect_policy;
+pub(crate) use inspect_policy::ProtocolInspectPolicyOverride;
+
+mod tenant;
+pub(crate) use tenant::TenantTagConfig;
+
+mod udp_dest_allowlist;
+pub(crate) use udp_dest_allowlist::{UdpDestAllowlistConfig, UdpDestViolationAction};
+
+mod tls_ocsp_stapling;
+pub(crate) use tls_ocsp_stapling::TlsOcspStaplingConfig;
+
+mod tls_alpn_servers;
+pub(crate) use tls_alpn_servers::TlsAlpnServersConfig;
+
+mod tarpit;
+pub(crate) use tarpit::{TarpitConfig, TarpitTrigger};
+
+mod local_response;
+pub(crate) use local_response::HttpLocalResponseConfig;
+
 mod registry;
 pub(crate) use registry::clear;
 
@@ -106,6 +128,23 @@ pub(crate) trait ServerConfig {
     fn task_max_idle_count(&self) -> i32 {
         1
     }
+    fn max_task_lifetime(&self) -> Option<Duration> {
+        None
+    }
+    /// The max time to wait for the other direction to close after one direction has been
+    /// half-closed. Both directions will be closed at once if set to None.
+    fn tcp_half_close_linger_timeout(&self) -> Option<Duration> {
+        None
+    }
+    fn inspect_policy_override(&self) -> Option<&ProtocolInspectPolicyOverride> {
+        None
+    }
+    fn protocol_tcp_no_delay(&self) -> Option<&ProtocolTcpNoDelayConfig> {
+        None
+    }
+    fn tenant_tag(&self) -> Option<&Arc<TenantTagConfig>> {
+        None
+    }
 
     fn get_user_group(&self) -> Option<Arc<UserGroup>> {
         if self.user_group().is_empty() {
@@ -121,7 +160,7 @@ pub(crate) trait ServerConfig {
         } else {
             let auditor = crate::audit::get_or_insert_default(self.auditor());
             let handle = auditor
-                .build_handle()
+                .build_handle(self.inspect_policy_override())
                 .context("failed to build audit handle")?;
             Ok(Some(handle))
         }
@@ -222,6 +261,16 @@ impl AnyServerConfig {
     impl_transparent1!(diff_action, ServerConfigDiffAction, &Self);
 }
 
+/// Parse the `max_task_lifetime` value, 0 means no limit
+pub(crate) fn as_max_task_lifetime(v: &Yaml) -> anyhow::Result<Option<Duration>> {
+    let lifetime = g3_yaml::humanize::as_duration(v)?;
+    if lifetime.is_zero() {
+        Ok(None)
+    } else {
+        Ok(Some(lifetime))
+    }
+}
+
 pub(crate) fn load_all(v: &Yaml, conf_dir: &Path) -> anyhow::Result<()> {
     let parser = HybridParser::new(conf_dir, g3_daemon::opts::config_file_extension());
     parser.foreach_map(v, |map, position| {
@@ -234,11 +283,16 @@ pub(crate) fn load_all(v: &Yaml, conf_dir: &Path) -> anyhow::Result<()> {
 }
 
 pub(crate) fn load_at_position(position: &YamlDocPosition) -> anyhow::Result<AnyServerConfig> {
+    let server = parse_at_position(position)?;
+    registry::add(server.clone(), true)?;
+    Ok(server)
+}
+
+/// Parse the server config at the position, without updating the registry
+pub(crate) fn parse_at_position(position: &YamlDocPosition) -> anyhow::Result<AnyServerConfig> {
     let doc = g3_yaml::load_doc(position)?;
     if let Yaml::Hash(map) = doc {
-        let server = load_server(&map, Some(position.clone()))?;
-        registry::add(server.clone(), true)?;
-        Ok(server)
+        load_server(&map, Some(position.clone()))
     } else {
         Err(anyhow!("yaml doc {position} is not a map"))
     }
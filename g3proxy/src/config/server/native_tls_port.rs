@@ -27,7 +27,9 @@ use g3_types::net::{OpensslServerConfigBuilder, ProxyProtocolVersion, TcpListenC
 use g3_yaml::YamlDocPosition;
 
 use super::ServerConfig;
-use crate::config::server::{AnyServerConfig, ServerConfigDiffAction};
+use crate::config::server::{
+    AnyServerConfig, ServerConfigDiffAction, TlsAlpnServersConfig, TlsOcspStaplingConfig,
+};
 
 const SERVER_CONFIG_TYPE: &str = "NativeTlsPort";
 
@@ -40,7 +42,9 @@ pub(crate) struct NativeTlsPortConfig {
     pub(crate) ingress_net_filter: Option<AclNetworkRuleBuilder>,
     pub(crate) server_tls_config: Option<OpensslServerConfigBuilder>,
     pub(crate) tls_ticketer: Option<TlsTicketConfig>,
+    pub(crate) tls_ocsp_stapling: Option<TlsOcspStaplingConfig>,
     pub(crate) server: MetricsName,
+    pub(crate) alpn_servers: TlsAlpnServersConfig,
     pub(crate) proxy_protocol: Option<ProxyProtocolVersion>,
     pub(crate) proxy_protocol_read_timeout: Duration,
 }
@@ -55,7 +59,9 @@ impl NativeTlsPortConfig {
             ingress_net_filter: None,
             server_tls_config: None,
             tls_ticketer: None,
+            tls_ocsp_stapling: None,
             server: MetricsName::default(),
+            alpn_servers: TlsAlpnServersConfig::default(),
             proxy_protocol: None,
             proxy_protocol_read_timeout: Duration::from_secs(5),
         }
@@ -111,10 +117,26 @@ impl NativeTlsPortConfig {
                 self.tls_ticketer = Some(ticketer);
                 Ok(())
             }
+            "tls_ocsp_stapling" => {
+                if let Yaml::Boolean(false) = v {
+                    self.tls_ocsp_stapling = None;
+                } else {
+                    let config = TlsOcspStaplingConfig::parse(v).context(format!(
+                        "invalid tls ocsp stapling config value for key {k}"
+                    ))?;
+                    self.tls_ocsp_stapling = Some(config);
+                }
+                Ok(())
+            }
             "server" => {
                 self.server = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
             }
+            "alpn_servers" | "alpn_server_map" => {
+                self.alpn_servers = TlsAlpnServersConfig::parse(v)
+                    .context(format!("invalid tls alpn servers config value for key {k}"))?;
+                Ok(())
+            }
             "proxy_protocol" => {
                 let p = g3_yaml::value::as_proxy_protocol_version(v)
                     .context(format!("invalid proxy protocol version value for key {k}"))?;
@@ -192,6 +214,7 @@ impl ServerConfig for NativeTlsPortConfig {
     fn dependent_server(&self) -> Option<BTreeSet<MetricsName>> {
         let mut set = BTreeSet::new();
         set.insert(self.server.clone());
+        self.alpn_servers.collect_servers(&mut set);
         Some(set)
     }
 }
@@ -27,7 +27,9 @@ use g3_types::net::{ProxyProtocolVersion, RustlsServerConfigBuilder, TcpListenCo
 use g3_yaml::YamlDocPosition;
 
 use super::ServerConfig;
-use crate::config::server::{AnyServerConfig, ServerConfigDiffAction};
+use crate::config::server::{
+    AnyServerConfig, ServerConfigDiffAction, TlsAlpnServersConfig, TlsOcspStaplingConfig,
+};
 
 const SERVER_CONFIG_TYPE: &str = "PlainTlsPort";
 
@@ -40,7 +42,9 @@ pub(crate) struct PlainTlsPortConfig {
     pub(crate) ingress_net_filter: Option<AclNetworkRuleBuilder>,
     pub(crate) server_tls_config: Option<RustlsServerConfigBuilder>,
     pub(crate) tls_ticketer: Option<TlsTicketConfig>,
+    pub(crate) tls_ocsp_stapling: Option<TlsOcspStaplingConfig>,
     pub(crate) server: MetricsName,
+    pub(crate) alpn_servers: TlsAlpnServersConfig,
     pub(crate) proxy_protocol: Option<ProxyProtocolVersion>,
     pub(crate) proxy_protocol_read_timeout: Duration,
 }
@@ -55,7 +59,9 @@ impl PlainTlsPortConfig {
             ingress_net_filter: None,
             server_tls_config: None,
             tls_ticketer: None,
+            tls_ocsp_stapling: None,
             server: MetricsName::default(),
+            alpn_servers: TlsAlpnServersConfig::default(),
             proxy_protocol: None,
             proxy_protocol_read_timeout: Duration::from_secs(5),
         }
@@ -110,10 +116,26 @@ impl PlainTlsPortConfig {
                 self.tls_ticketer = Some(ticketer);
                 Ok(())
             }
+            "tls_ocsp_stapling" => {
+                if let Yaml::Boolean(false) = v {
+                    self.tls_ocsp_stapling = None;
+                } else {
+                    let config = TlsOcspStaplingConfig::parse(v).context(format!(
+                        "invalid tls ocsp stapling config value for key {k}"
+                    ))?;
+                    self.tls_ocsp_stapling = Some(config);
+                }
+                Ok(())
+            }
             "server" => {
                 self.server = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
             }
+            "alpn_servers" | "alpn_server_map" => {
+                self.alpn_servers = TlsAlpnServersConfig::parse(v)
+                    .context(format!("invalid tls alpn servers config value for key {k}"))?;
+                Ok(())
+            }
             "proxy_protocol" => {
                 let p = g3_yaml::value::as_proxy_protocol_version(v)
                     .context(format!("invalid proxy protocol version value for key {k}"))?;
@@ -142,6 +164,11 @@ impl PlainTlsPortConfig {
         if self.server_tls_config.is_none() {
             return Err(anyhow!("tls server config is not set"));
         }
+        if let Some(c) = &self.tls_ocsp_stapling {
+            if c.hard_fail {
+                return Err(anyhow!("hard fail of tls ocsp stapling is not supported"));
+            }
+        }
 
         Ok(())
     }
@@ -191,6 +218,7 @@ impl ServerConfig for PlainTlsPortConfig {
     fn dependent_server(&self) -> Option<BTreeSet<MetricsName>> {
         let mut set = BTreeSet::new();
         set.insert(self.server.clone());
+        self.alpn_servers.collect_servers(&mut set);
         Some(set)
     }
 }
@@ -23,13 +23,19 @@ use yaml_rust::{yaml, Yaml};
 
 use g3_dpi::{ProtocolInspectionConfig, ProtocolPortMap};
 use g3_io_ext::LimitedCopyConfig;
-use g3_types::acl::AclNetworkRuleBuilder;
+use g3_types::acl::{AclNetworkRuleBuilder, AclOrderedNetworkRuleBuilder};
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
-use g3_types::net::{TcpListenConfig, TcpMiscSockOpts, TcpSockSpeedLimitConfig};
+use g3_types::net::{
+    TcpKeepAliveConfig, TcpListenConfig, TcpMiscSockOpts, TcpSockSpeedLimitConfig,
+};
 use g3_types::route::HostMatch;
 use g3_yaml::YamlDocPosition;
 
-use super::{AnyServerConfig, ServerConfig, ServerConfigDiffAction, IDLE_CHECK_MAXIMUM_DURATION};
+use super::{
+    AnyServerConfig, ProtocolInspectPolicyOverride, ServerConfig, ServerConfigDiffAction,
+    TenantTagConfig, IDLE_CHECK_MAXIMUM_DURATION,
+};
+use crate::config::audit::ProtocolTcpNoDelayConfig;
 
 mod host;
 pub(crate) use host::SniHostConfig;
@@ -42,14 +48,22 @@ pub(crate) struct SniProxyServerConfig {
     position: Option<YamlDocPosition>,
     pub(crate) escaper: MetricsName,
     pub(crate) auditor: MetricsName,
+    pub(crate) inspect_policy_override: Option<ProtocolInspectPolicyOverride>,
+    pub(crate) protocol_tcp_no_delay: Option<ProtocolTcpNoDelayConfig>,
+    pub(crate) tenant_tag: Option<Arc<TenantTagConfig>>,
     pub(crate) shared_logger: Option<AsciiString>,
     pub(crate) listen: Option<TcpListenConfig>,
     pub(crate) listen_in_worker: bool,
     pub(crate) ingress_net_filter: Option<AclNetworkRuleBuilder>,
+    pub(crate) client_net_acl: Option<AclOrderedNetworkRuleBuilder>,
     pub(crate) tcp_sock_speed_limit: TcpSockSpeedLimitConfig,
     pub(crate) task_idle_check_duration: Duration,
     pub(crate) task_idle_max_count: i32,
+    pub(crate) max_task_lifetime: Option<Duration>,
+    pub(crate) tcp_half_close_linger_timeout: Option<Duration>,
+    pub(crate) task_log_on_connect: bool,
     pub(crate) tcp_copy: LimitedCopyConfig,
+    pub(crate) tcp_keepalive: TcpKeepAliveConfig,
     pub(crate) tcp_misc_opts: TcpMiscSockOpts,
     pub(crate) tls_max_client_hello_size: u32,
     pub(crate) request_wait_timeout: Duration,
@@ -68,14 +82,22 @@ impl SniProxyServerConfig {
             position,
             escaper: MetricsName::default(),
             auditor: MetricsName::default(),
+            inspect_policy_override: None,
+            protocol_tcp_no_delay: None,
+            tenant_tag: None,
             shared_logger: None,
             listen: None,
             listen_in_worker: false,
             ingress_net_filter: None,
+            client_net_acl: None,
             tcp_sock_speed_limit: TcpSockSpeedLimitConfig::default(),
             task_idle_check_duration: Duration::from_secs(300),
             task_idle_max_count: 1,
+            max_task_lifetime: None,
+            tcp_half_close_linger_timeout: None,
+            task_log_on_connect: false,
             tcp_copy: Default::default(),
+            tcp_keepalive: Default::default(),
             tcp_misc_opts: Default::default(),
             tls_max_client_hello_size: 1 << 16,
             request_wait_timeout: Duration::from_secs(60),
@@ -115,6 +137,25 @@ impl SniProxyServerConfig {
                 self.auditor = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
             }
+            "inspect_policy_override" | "protocol_inspect_policy" => {
+                let policy = ProtocolInspectPolicyOverride::parse(v).context(format!(
+                    "invalid protocol inspect policy override value for key {k}"
+                ))?;
+                self.inspect_policy_override = Some(policy);
+                Ok(())
+            }
+            "protocol_tcp_no_delay" => {
+                let config = ProtocolTcpNoDelayConfig::parse(v)
+                    .context(format!("invalid protocol tcp no delay value for key {k}"))?;
+                self.protocol_tcp_no_delay = Some(config);
+                Ok(())
+            }
+            "tenant_tag" | "tenant" => {
+                let config = TenantTagConfig::parse(v)
+                    .context(format!("invalid tenant tag config value for key {k}"))?;
+                self.tenant_tag = Some(Arc::new(config));
+                Ok(())
+            }
             "shared_logger" => {
                 let name = g3_yaml::value::as_ascii(v)?;
                 self.shared_logger = Some(name);
@@ -143,6 +184,12 @@ impl SniProxyServerConfig {
                 self.ingress_net_filter = Some(filter);
                 Ok(())
             }
+            "client_network_acl" | "client_net_acl" => {
+                let acl = g3_yaml::value::acl::as_ordered_network_rule_builder(v)
+                    .context(format!("invalid client network acl rule value for key {k}"))?;
+                self.client_net_acl = Some(acl);
+                Ok(())
+            }
             "tcp_sock_speed_limit" | "tcp_conn_speed_limit" | "tcp_conn_limit" | "conn_limit" => {
                 self.tcp_sock_speed_limit = g3_yaml::value::as_tcp_sock_speed_limit(v)
                     .context(format!("invalid tcp socket speed limit value for key {k}"))?;
@@ -154,12 +201,23 @@ impl SniProxyServerConfig {
                 self.tcp_copy.set_buffer_size(buffer_size);
                 Ok(())
             }
+            "tcp_copy_max_buffer_size" => {
+                let max_buffer_size = g3_yaml::humanize::as_usize(v)
+                    .context(format!("invalid humanize usize value for key {k}"))?;
+                self.tcp_copy.set_max_buffer_size(max_buffer_size);
+                Ok(())
+            }
             "tcp_copy_yield_size" => {
                 let yield_size = g3_yaml::humanize::as_usize(v)
                     .context(format!("invalid humanize usize value for key {k}"))?;
                 self.tcp_copy.set_yield_size(yield_size);
                 Ok(())
             }
+            "tcp_keepalive" | "tcp_client_keepalive" => {
+                self.tcp_keepalive = g3_yaml::value::as_tcp_keepalive_config(v)
+                    .context(format!("invalid tcp keepalive config value for key {k}"))?;
+                Ok(())
+            }
             "tcp_misc_opts" => {
                 self.tcp_misc_opts = g3_yaml::value::as_tcp_misc_sock_opts(v)
                     .context(format!("invalid tcp misc sock opts value for key {k}"))?;
@@ -179,6 +237,25 @@ impl SniProxyServerConfig {
                     g3_yaml::value::as_i32(v).context(format!("invalid i32 value for key {k}"))?;
                 Ok(())
             }
+            "max_task_lifetime" | "task_max_lifetime" => {
+                self.max_task_lifetime = super::as_max_task_lifetime(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
+            "tcp_half_close_linger_timeout" | "half_close_linger_timeout" => {
+                let timeout = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                self.tcp_half_close_linger_timeout = if timeout.is_zero() {
+                    None
+                } else {
+                    Some(timeout)
+                };
+                Ok(())
+            }
+            "task_log_on_connect" | "log_task_on_connect" => {
+                self.task_log_on_connect = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
             "request_wait_timeout" => {
                 self.request_wait_timeout = g3_yaml::humanize::as_duration(v)
                     .context(format!("invalid humanize duration value for key {k}"))?;
@@ -289,4 +366,28 @@ impl ServerConfig for SniProxyServerConfig {
     fn task_max_idle_count(&self) -> i32 {
         self.task_idle_max_count
     }
+
+    #[inline]
+    fn max_task_lifetime(&self) -> Option<Duration> {
+        self.max_task_lifetime
+    }
+
+    #[inline]
+    fn tcp_half_close_linger_timeout(&self) -> Option<Duration> {
+        self.tcp_half_close_linger_timeout
+    }
+
+    #[inline]
+    fn tenant_tag(&self) -> Option<&Arc<TenantTagConfig>> {
+        self.tenant_tag.as_ref()
+    }
+
+    #[inline]
+    fn inspect_policy_override(&self) -> Option<&ProtocolInspectPolicyOverride> {
+        self.inspect_policy_override.as_ref()
+    }
+
+    fn protocol_tcp_no_delay(&self) -> Option<&ProtocolTcpNoDelayConfig> {
+        self.protocol_tcp_no_delay.as_ref()
+    }
 }
@@ -24,19 +24,22 @@ use ascii::AsciiString;
 use yaml_rust::{yaml, Yaml};
 
 use g3_io_ext::{LimitedCopyConfig, LimitedUdpRelayConfig};
-use g3_types::acl::{AclExactPortRule, AclNetworkRuleBuilder};
+use g3_types::acl::{AclExactPortRule, AclNetworkRuleBuilder, AclOrderedNetworkRuleBuilder};
 use g3_types::acl_set::AclDstHostRuleSetBuilder;
+use g3_types::limit::ClientConnLimitConfig;
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
 use g3_types::net::{
-    PortRange, SocketBufferConfig, TcpListenConfig, TcpMiscSockOpts, TcpSockSpeedLimitConfig,
-    UdpMiscSockOpts, UdpSockSpeedLimitConfig,
+    PortRange, SocketBufferConfig, TcpKeepAliveConfig, TcpListenConfig, TcpMiscSockOpts,
+    TcpSockSpeedLimitConfig, UdpMiscSockOpts, UdpSockSpeedLimitConfig,
 };
 use g3_yaml::YamlDocPosition;
 
 use super::{
-    AnyServerConfig, ServerConfig, ServerConfigDiffAction, IDLE_CHECK_DEFAULT_DURATION,
+    AnyServerConfig, ProtocolInspectPolicyOverride, ServerConfig, ServerConfigDiffAction,
+    TarpitConfig, TenantTagConfig, UdpDestAllowlistConfig, IDLE_CHECK_DEFAULT_DURATION,
     IDLE_CHECK_MAXIMUM_DURATION,
 };
+use crate::config::audit::ProtocolTcpNoDelayConfig;
 
 const SERVER_CONFIG_TYPE: &str = "SocksProxy";
 
@@ -59,13 +62,40 @@ impl Default for SocksProxyServerTimeoutConfig {
     }
 }
 
+/// The socks5 auth methods that can be selected by the server
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Socks5ServerAuthMethod {
+    /// no auth, the anonymous user will be used if user group is set
+    None,
+    /// username/password auth
+    User,
+}
+
+impl Socks5ServerAuthMethod {
+    fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let s = g3_yaml::value::as_string(value)?;
+        match g3_yaml::key::normalize(&s).as_str() {
+            "none" | "no_auth" | "anonymous" => Ok(Socks5ServerAuthMethod::None),
+            "user" | "username" | "password" | "user_password" => Ok(Socks5ServerAuthMethod::User),
+            "gssapi" => Err(anyhow!("gssapi auth method is not supported")),
+            _ => Err(anyhow!("invalid socks5 auth method {s}")),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(crate) struct SocksProxyServerConfig {
     name: MetricsName,
     position: Option<YamlDocPosition>,
     pub(crate) escaper: MetricsName,
     pub(crate) auditor: MetricsName,
+    pub(crate) inspect_policy_override: Option<ProtocolInspectPolicyOverride>,
+    pub(crate) protocol_tcp_no_delay: Option<ProtocolTcpNoDelayConfig>,
     pub(crate) user_group: MetricsName,
+    /// the offered socks5 auth methods, in the order of preference
+    pub(crate) socks5_auth_methods: Vec<Socks5ServerAuthMethod>,
+    pub(crate) enable_socks4: bool,
+    pub(crate) tenant_tag: Option<Arc<TenantTagConfig>>,
     pub(crate) shared_logger: Option<AsciiString>,
     pub(crate) listen: Option<TcpListenConfig>,
     pub(crate) listen_in_worker: bool,
@@ -75,15 +105,23 @@ pub(crate) struct SocksProxyServerConfig {
     pub(crate) udp_bind_port_range: Option<PortRange>,
     pub(crate) udp_socket_buffer: SocketBufferConfig,
     pub(crate) ingress_net_filter: Option<AclNetworkRuleBuilder>,
+    pub(crate) client_net_acl: Option<AclOrderedNetworkRuleBuilder>,
+    pub(crate) client_conn_limit: Option<ClientConnLimitConfig>,
+    pub(crate) tarpit: Option<TarpitConfig>,
     pub(crate) dst_host_filter: Option<AclDstHostRuleSetBuilder>,
     pub(crate) dst_port_filter: Option<AclExactPortRule>,
+    pub(crate) udp_dest_allowlist: Option<Arc<UdpDestAllowlistConfig>>,
     pub(crate) tcp_sock_speed_limit: TcpSockSpeedLimitConfig,
     pub(crate) udp_sock_speed_limit: UdpSockSpeedLimitConfig,
     pub(crate) timeout: SocksProxyServerTimeoutConfig,
     pub(crate) task_idle_check_duration: Duration,
     pub(crate) task_idle_max_count: i32,
+    pub(crate) max_task_lifetime: Option<Duration>,
+    pub(crate) tcp_half_close_linger_timeout: Option<Duration>,
+    pub(crate) task_log_on_connect: bool,
     pub(crate) tcp_copy: LimitedCopyConfig,
     pub(crate) udp_relay: LimitedUdpRelayConfig,
+    pub(crate) tcp_keepalive: TcpKeepAliveConfig,
     pub(crate) tcp_misc_opts: TcpMiscSockOpts,
     pub(crate) udp_misc_opts: UdpMiscSockOpts,
     pub(crate) transmute_udp_echo_ip: Option<AHashMap<IpAddr, IpAddr>>,
@@ -97,7 +135,12 @@ impl SocksProxyServerConfig {
             position,
             escaper: MetricsName::default(),
             auditor: MetricsName::default(),
+            inspect_policy_override: None,
+            protocol_tcp_no_delay: None,
             user_group: MetricsName::default(),
+            socks5_auth_methods: Vec::new(),
+            enable_socks4: true,
+            tenant_tag: None,
             shared_logger: None,
             listen: None,
             listen_in_worker: false,
@@ -107,15 +150,23 @@ impl SocksProxyServerConfig {
             udp_bind_port_range: None,
             udp_socket_buffer: SocketBufferConfig::default(),
             ingress_net_filter: None,
+            client_net_acl: None,
+            client_conn_limit: None,
+            tarpit: None,
             dst_host_filter: None,
             dst_port_filter: None,
+            udp_dest_allowlist: None,
             tcp_sock_speed_limit: TcpSockSpeedLimitConfig::default(),
             udp_sock_speed_limit: UdpSockSpeedLimitConfig::default(),
             timeout: SocksProxyServerTimeoutConfig::default(),
             task_idle_check_duration: IDLE_CHECK_DEFAULT_DURATION,
             task_idle_max_count: 1,
+            max_task_lifetime: None,
+            tcp_half_close_linger_timeout: None,
+            task_log_on_connect: false,
             tcp_copy: Default::default(),
             udp_relay: Default::default(),
+            tcp_keepalive: Default::default(),
             tcp_misc_opts: Default::default(),
             udp_misc_opts: Default::default(),
             transmute_udp_echo_ip: None,
@@ -150,10 +201,44 @@ impl SocksProxyServerConfig {
                 self.auditor = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
             }
+            "inspect_policy_override" | "protocol_inspect_policy" => {
+                let policy = ProtocolInspectPolicyOverride::parse(v).context(format!(
+                    "invalid protocol inspect policy override value for key {k}"
+                ))?;
+                self.inspect_policy_override = Some(policy);
+                Ok(())
+            }
+            "protocol_tcp_no_delay" => {
+                let config = ProtocolTcpNoDelayConfig::parse(v)
+                    .context(format!("invalid protocol tcp no delay value for key {k}"))?;
+                self.protocol_tcp_no_delay = Some(config);
+                Ok(())
+            }
             "user_group" => {
                 self.user_group = g3_yaml::value::as_metrics_name(v)?;
                 Ok(())
             }
+            "socks5_auth_methods" | "socks5_auth_method" => {
+                let methods = g3_yaml::value::as_list(v, Socks5ServerAuthMethod::parse)
+                    .context(format!("invalid socks5 auth method list value for key {k}"))?;
+                self.socks5_auth_methods.clear();
+                for m in methods {
+                    if !self.socks5_auth_methods.contains(&m) {
+                        self.socks5_auth_methods.push(m);
+                    }
+                }
+                Ok(())
+            }
+            "enable_socks4" | "allow_socks4" => {
+                self.enable_socks4 = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
+            "tenant_tag" | "tenant" => {
+                let config = TenantTagConfig::parse(v)
+                    .context(format!("invalid tenant tag config value for key {k}"))?;
+                self.tenant_tag = Some(Arc::new(config));
+                Ok(())
+            }
             "shared_logger" => {
                 let name = g3_yaml::value::as_ascii(v)?;
                 self.shared_logger = Some(name);
@@ -193,6 +278,22 @@ impl SocksProxyServerConfig {
                 })?;
                 Ok(())
             }
+            "udp_advertise_ipv4" => {
+                let ip4 = g3_yaml::value::as_ipv4addr(v)
+                    .context(format!("invalid ipv4 address value for key {k}"))?;
+                self.transmute_udp_echo_ip
+                    .get_or_insert_with(AHashMap::default)
+                    .insert(IpAddr::V4(Ipv4Addr::UNSPECIFIED), IpAddr::V4(ip4));
+                Ok(())
+            }
+            "udp_advertise_ipv6" => {
+                let ip6 = g3_yaml::value::as_ipv6addr(v)
+                    .context(format!("invalid ipv6 address value for key {k}"))?;
+                self.transmute_udp_echo_ip
+                    .get_or_insert_with(AHashMap::default)
+                    .insert(IpAddr::V6(Ipv6Addr::UNSPECIFIED), IpAddr::V6(ip6));
+                Ok(())
+            }
             "udp_bind_port_range" => {
                 let range = g3_yaml::value::as_port_range(v)
                     .context(format!("invalid port range value for key {k}"))?;
@@ -211,6 +312,25 @@ impl SocksProxyServerConfig {
                 self.ingress_net_filter = Some(filter);
                 Ok(())
             }
+            "client_network_acl" | "client_net_acl" => {
+                let acl = g3_yaml::value::acl::as_ordered_network_rule_builder(v)
+                    .context(format!("invalid client network acl rule value for key {k}"))?;
+                self.client_net_acl = Some(acl);
+                Ok(())
+            }
+            "client_connection_limit" | "client_conn_limit" => {
+                let limit = g3_yaml::value::as_client_conn_limit_config(v).context(format!(
+                    "invalid client connection limit config value for key {k}"
+                ))?;
+                self.client_conn_limit = Some(limit);
+                Ok(())
+            }
+            "tarpit" => {
+                let tarpit = TarpitConfig::parse(v)
+                    .context(format!("invalid tarpit config value for key {k}"))?;
+                self.tarpit = Some(tarpit);
+                Ok(())
+            }
             "dst_host_filter_set" => {
                 let filter_set = g3_yaml::value::acl_set::as_dst_host_rule_set_builder(v)
                     .context(format!("invalid dst host acl rule set value for key {k}"))?;
@@ -242,6 +362,12 @@ impl SocksProxyServerConfig {
                 self.tcp_copy.set_buffer_size(buffer_size);
                 Ok(())
             }
+            "tcp_copy_max_buffer_size" => {
+                let max_buffer_size = g3_yaml::humanize::as_usize(v)
+                    .context(format!("invalid humanize usize value for key {k}"))?;
+                self.tcp_copy.set_max_buffer_size(max_buffer_size);
+                Ok(())
+            }
             "tcp_copy_yield_size" => {
                 let yield_size = g3_yaml::humanize::as_usize(v)
                     .context(format!("invalid humanize usize value for key {k}"))?;
@@ -260,11 +386,22 @@ impl SocksProxyServerConfig {
                 self.udp_relay.set_yield_size(yield_size);
                 Ok(())
             }
+            "udp_dest_allowlist" | "udp_relay_allowlist" => {
+                let allowlist = UdpDestAllowlistConfig::parse(v)
+                    .context(format!("invalid udp dest allowlist value for key {k}"))?;
+                self.udp_dest_allowlist = Some(Arc::new(allowlist));
+                Ok(())
+            }
             "udp_relay_batch_size" => {
                 let batch_size = g3_yaml::value::as_usize(v)?;
                 self.udp_relay.set_batch_size(batch_size);
                 Ok(())
             }
+            "tcp_keepalive" | "tcp_client_keepalive" => {
+                self.tcp_keepalive = g3_yaml::value::as_tcp_keepalive_config(v)
+                    .context(format!("invalid tcp keepalive config value for key {k}"))?;
+                Ok(())
+            }
             "tcp_misc_opts" => {
                 self.tcp_misc_opts = g3_yaml::value::as_tcp_misc_sock_opts(v)
                     .context(format!("invalid tcp misc sock opts value for key {k}"))?;
@@ -295,6 +432,25 @@ impl SocksProxyServerConfig {
                     g3_yaml::value::as_i32(v).context(format!("invalid i32 value for key {k}"))?;
                 Ok(())
             }
+            "max_task_lifetime" | "task_max_lifetime" => {
+                self.max_task_lifetime = super::as_max_task_lifetime(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
+            "tcp_half_close_linger_timeout" | "half_close_linger_timeout" => {
+                let timeout = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                self.tcp_half_close_linger_timeout = if timeout.is_zero() {
+                    None
+                } else {
+                    Some(timeout)
+                };
+                Ok(())
+            }
+            "task_log_on_connect" | "log_task_on_connect" => {
+                self.task_log_on_connect = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
             "transmute_udp_echo_ip" | "auto_reply_local_ip_map" => {
                 if let Yaml::Hash(_) = v {
                     let map = g3_yaml::value::as_hashmap(
@@ -302,11 +458,15 @@ impl SocksProxyServerConfig {
                         g3_yaml::value::as_ipaddr,
                         g3_yaml::value::as_ipaddr,
                     )?;
-                    self.transmute_udp_echo_ip = Some(map.into_iter().collect::<AHashMap<_, _>>());
+                    // merge as the udp advertise ip may have been added
+                    self.transmute_udp_echo_ip
+                        .get_or_insert_with(AHashMap::default)
+                        .extend(map);
                 } else {
                     let enable = g3_yaml::value::as_bool(v)?;
                     if enable {
-                        self.transmute_udp_echo_ip = Some(AHashMap::default());
+                        self.transmute_udp_echo_ip
+                            .get_or_insert_with(AHashMap::default);
                     }
                 }
                 Ok(())
@@ -322,6 +482,22 @@ impl SocksProxyServerConfig {
         if self.escaper.is_empty() {
             return Err(anyhow!("escaper is not set"));
         }
+        if self.socks5_auth_methods.is_empty() {
+            if self.user_group.is_empty() {
+                self.socks5_auth_methods.push(Socks5ServerAuthMethod::None);
+            } else {
+                self.socks5_auth_methods.push(Socks5ServerAuthMethod::User);
+                self.socks5_auth_methods.push(Socks5ServerAuthMethod::None);
+            }
+        } else if self.user_group.is_empty()
+            && self
+                .socks5_auth_methods
+                .contains(&Socks5ServerAuthMethod::User)
+        {
+            return Err(anyhow!(
+                "user_group should be set if the socks5 user auth method is offered"
+            ));
+        }
         if self.task_idle_check_duration > IDLE_CHECK_MAXIMUM_DURATION {
             self.task_idle_check_duration = IDLE_CHECK_MAXIMUM_DURATION;
         }
@@ -329,16 +505,21 @@ impl SocksProxyServerConfig {
         Ok(())
     }
 
+    /// Get the udp relay address that should be sent to the client in the reply.
+    ///
+    /// The unspecified ip key in the transmute map is used as the default value of the same family,
+    /// which is how the udp advertise ip is stored. The port is always the real listen port.
     pub(crate) fn transmute_udp_echo_addr(&self, local_addr: SocketAddr) -> SocketAddr {
         if let Some(map) = &self.transmute_udp_echo_ip {
-            let ip = if let Some(ip) = map.get(&local_addr.ip()) {
-                *ip
-            } else {
-                match local_addr.ip() {
-                    IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
-                    IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
-                }
+            let unspecified = match local_addr.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
             };
+            let ip = map
+                .get(&local_addr.ip())
+                .or_else(|| map.get(&unspecified))
+                .copied()
+                .unwrap_or(unspecified);
             return SocketAddr::new(ip, local_addr.port());
         }
         local_addr
@@ -402,4 +583,147 @@ impl ServerConfig for SocksProxyServerConfig {
     fn task_max_idle_count(&self) -> i32 {
         self.task_idle_max_count
     }
+
+    #[inline]
+    fn max_task_lifetime(&self) -> Option<Duration> {
+        self.max_task_lifetime
+    }
+
+    #[inline]
+    fn tcp_half_close_linger_timeout(&self) -> Option<Duration> {
+        self.tcp_half_close_linger_timeout
+    }
+
+    #[inline]
+    fn tenant_tag(&self) -> Option<&Arc<TenantTagConfig>> {
+        self.tenant_tag.as_ref()
+    }
+
+    #[inline]
+    fn inspect_policy_override(&self) -> Option<&ProtocolInspectPolicyOverride> {
+        self.inspect_policy_override.as_ref()
+    }
+
+    fn protocol_tcp_no_delay(&self) -> Option<&ProtocolTcpNoDelayConfig> {
+        self.protocol_tcp_no_delay.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn socks5_auth_methods() {
+        let mut config = SocksProxyServerConfig::new(None);
+        config.name = MetricsName::from_str("test").unwrap();
+        config.escaper = MetricsName::from_str("default").unwrap();
+        config.check().unwrap();
+        assert_eq!(
+            config.socks5_auth_methods,
+            vec![Socks5ServerAuthMethod::None]
+        );
+
+        let mut config = SocksProxyServerConfig::new(None);
+        config.name = MetricsName::from_str("test").unwrap();
+        config.escaper = MetricsName::from_str("default").unwrap();
+        config.user_group = MetricsName::from_str("default").unwrap();
+        config.check().unwrap();
+        assert_eq!(
+            config.socks5_auth_methods,
+            vec![Socks5ServerAuthMethod::User, Socks5ServerAuthMethod::None]
+        );
+
+        let v = YamlLoader::load_from_str("[user, user]").unwrap();
+        config.set("socks5_auth_methods", &v[0]).unwrap();
+        config.check().unwrap();
+        assert_eq!(
+            config.socks5_auth_methods,
+            vec![Socks5ServerAuthMethod::User]
+        );
+
+        let v = YamlLoader::load_from_str("gssapi").unwrap();
+        assert!(config.set("socks5_auth_methods", &v[0]).is_err());
+
+        let v = YamlLoader::load_from_str("user").unwrap();
+        config.set("socks5_auth_methods", &v[0]).unwrap();
+        config.user_group = MetricsName::default();
+        assert!(config.check().is_err());
+    }
+
+    #[test]
+    fn enable_socks4() {
+        let mut config = SocksProxyServerConfig::new(None);
+        assert!(config.enable_socks4);
+
+        let v = YamlLoader::load_from_str("false").unwrap();
+        config.set("allow_socks4", &v[0]).unwrap();
+        assert!(!config.enable_socks4);
+    }
+
+    #[test]
+    fn udp_echo_addr_behind_nat() {
+        let mut config = SocksProxyServerConfig::new(None);
+        let local4 = SocketAddr::from_str("10.0.0.2:40000").unwrap();
+        let local6 = SocketAddr::from_str("[fd00::2]:40001").unwrap();
+        assert_eq!(config.transmute_udp_echo_addr(local4), local4);
+
+        let mut map = AHashMap::new();
+        map.insert(
+            IpAddr::from_str("10.0.0.2").unwrap(),
+            IpAddr::from_str("192.0.2.1").unwrap(),
+        );
+        config.transmute_udp_echo_ip = Some(map);
+        assert_eq!(
+            config.transmute_udp_echo_addr(local4),
+            SocketAddr::from_str("192.0.2.1:40000").unwrap()
+        );
+        assert_eq!(
+            config.transmute_udp_echo_addr(local6),
+            SocketAddr::from_str("[::]:40001").unwrap()
+        );
+
+        let v = YamlLoader::load_from_str("203.0.113.10").unwrap();
+        config.set("udp_advertise_ipv4", &v[0]).unwrap();
+        let v = YamlLoader::load_from_str("2001:db8::10").unwrap();
+        config.set("udp_advertise_ipv6", &v[0]).unwrap();
+        // the exact match in the transmute map still wins
+        assert_eq!(
+            config.transmute_udp_echo_addr(local4),
+            SocketAddr::from_str("192.0.2.1:40000").unwrap()
+        );
+        assert_eq!(
+            config.transmute_udp_echo_addr(SocketAddr::from_str("10.0.0.3:40002").unwrap()),
+            SocketAddr::from_str("203.0.113.10:40002").unwrap()
+        );
+        assert_eq!(
+            config.transmute_udp_echo_addr(local6),
+            SocketAddr::from_str("[2001:db8::10]:40001").unwrap()
+        );
+
+        // the advertise ip is kept if the transmute map is set later
+        let mut config = SocksProxyServerConfig::new(None);
+        let v = YamlLoader::load_from_str("203.0.113.10").unwrap();
+        config.set("udp_advertise_ipv4", &v[0]).unwrap();
+        assert_eq!(
+            config.transmute_udp_echo_addr(local4),
+            SocketAddr::from_str("203.0.113.10:40000").unwrap()
+        );
+        assert_eq!(
+            config.transmute_udp_echo_addr(local6),
+            SocketAddr::from_str("[::]:40001").unwrap()
+        );
+        let v = YamlLoader::load_from_str("{10.0.0.2: 192.0.2.1}").unwrap();
+        config.set("transmute_udp_echo_ip", &v[0]).unwrap();
+        assert_eq!(
+            config.transmute_udp_echo_addr(local4),
+            SocketAddr::from_str("192.0.2.1:40000").unwrap()
+        );
+        assert_eq!(
+            config.transmute_udp_echo_addr(SocketAddr::from_str("10.0.0.3:40002").unwrap()),
+            SocketAddr::from_str("203.0.113.10:40002").unwrap()
+        );
+    }
 }
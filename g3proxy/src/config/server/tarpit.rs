@@ -0,0 +1,217 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+const DEFAULT_DURATION: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_CONCURRENT: usize = 1024;
+
+/// The block decisions that the tarpit can be applied to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TarpitTrigger {
+    /// forbidden by the ingress network filter or the client network acl
+    NetworkAcl,
+    /// rejected by the client connection limit
+    ClientConnLimit,
+    /// rejected by the user request rate limit
+    RateLimit,
+    /// forbidden by the proxy request type acl
+    ProxyRequestAcl,
+    /// forbidden by the dst host or port acl
+    DstHostAcl,
+    /// forbidden by the egress ip acl of the escaper or the user resolve policy
+    DstIpAcl,
+    /// forbidden by the http user agent acl
+    UserAgentAcl,
+    /// blocked by the protocol inspection policy
+    InspectionBlock,
+}
+
+impl TarpitTrigger {
+    pub(crate) const fn as_str(&self) -> &'static str {
+        match self {
+            TarpitTrigger::NetworkAcl => "network_acl",
+            TarpitTrigger::ClientConnLimit => "client_connection_limit",
+            TarpitTrigger::RateLimit => "rate_limit",
+            TarpitTrigger::ProxyRequestAcl => "proxy_request_acl",
+            TarpitTrigger::DstHostAcl => "dst_host_acl",
+            TarpitTrigger::DstIpAcl => "dst_ip_acl",
+            TarpitTrigger::UserAgentAcl => "user_agent_acl",
+            TarpitTrigger::InspectionBlock => "inspection_block",
+        }
+    }
+
+    fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let s = g3_yaml::value::as_string(value)?;
+        match g3_yaml::key::normalize(&s).as_str() {
+            "network_acl" | "ingress_network_filter" => Ok(TarpitTrigger::NetworkAcl),
+            "client_connection_limit" | "client_conn_limit" => Ok(TarpitTrigger::ClientConnLimit),
+            "rate_limit" | "request_rate_limit" => Ok(TarpitTrigger::RateLimit),
+            "proxy_request_acl" | "proxy_request" => Ok(TarpitTrigger::ProxyRequestAcl),
+            "dst_host_acl" | "dst_host_filter" => Ok(TarpitTrigger::DstHostAcl),
+            "dst_ip_acl" | "egress_acl" => Ok(TarpitTrigger::DstIpAcl),
+            "user_agent_acl" | "http_user_agent_filter" => Ok(TarpitTrigger::UserAgentAcl),
+            "inspection_block" | "inspect_block" => Ok(TarpitTrigger::InspectionBlock),
+            _ => Err(anyhow!("unsupported tarpit trigger {s}")),
+        }
+    }
+}
+
+/// Hold the blocked connections open for a while instead of closing them immediately
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TarpitConfig {
+    pub(crate) duration: Duration,
+    pub(crate) keepalive_interval: Option<Duration>,
+    pub(crate) keepalive_data: Vec<u8>,
+    pub(crate) max_concurrent: usize,
+    triggers: Vec<TarpitTrigger>,
+}
+
+impl Default for TarpitConfig {
+    fn default() -> Self {
+        TarpitConfig {
+            duration: DEFAULT_DURATION,
+            keepalive_interval: None,
+            keepalive_data: b"\r\n".to_vec(),
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            triggers: vec![TarpitTrigger::NetworkAcl, TarpitTrigger::ClientConnLimit],
+        }
+    }
+}
+
+impl TarpitConfig {
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let mut config = TarpitConfig::default();
+        match value {
+            Yaml::Hash(map) => {
+                g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                    "duration" => {
+                        config.duration = g3_yaml::humanize::as_duration(v)
+                            .context(format!("invalid humanize duration value for key {k}"))?;
+                        Ok(())
+                    }
+                    "keepalive_interval" => {
+                        let interval = g3_yaml::humanize::as_duration(v)
+                            .context(format!("invalid humanize duration value for key {k}"))?;
+                        config.keepalive_interval = (!interval.is_zero()).then_some(interval);
+                        Ok(())
+                    }
+                    "keepalive_data" => {
+                        let data = g3_yaml::value::as_string(v)
+                            .context(format!("invalid string value for key {k}"))?;
+                        if data.is_empty() {
+                            return Err(anyhow!("empty keepalive data for key {k}"));
+                        }
+                        config.keepalive_data = data.into_bytes();
+                        Ok(())
+                    }
+                    "max_concurrent" | "max_count" => {
+                        config.max_concurrent = g3_yaml::value::as_usize(v)
+                            .context(format!("invalid usize value for key {k}"))?;
+                        Ok(())
+                    }
+                    "apply_to" | "triggers" => {
+                        config.triggers.clear();
+                        if let Yaml::Array(seq) = v {
+                            for (i, v) in seq.iter().enumerate() {
+                                let trigger = TarpitTrigger::parse(v)
+                                    .context(format!("invalid tarpit trigger value for {k}#{i}"))?;
+                                config.add_trigger(trigger);
+                            }
+                        } else {
+                            let trigger = TarpitTrigger::parse(v)
+                                .context(format!("invalid tarpit trigger value for key {k}"))?;
+                            config.add_trigger(trigger);
+                        }
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("invalid key {k}")),
+                })?;
+            }
+            Yaml::String(_) | Yaml::Integer(_) => {
+                config.duration = g3_yaml::humanize::as_duration(value)
+                    .context("invalid humanize duration value")?;
+            }
+            _ => {
+                return Err(anyhow!(
+                    "yaml value type for 'tarpit config' should be 'map' or 'duration'"
+                ))
+            }
+        }
+        Ok(config)
+    }
+
+    fn add_trigger(&mut self, trigger: TarpitTrigger) {
+        if !self.triggers.contains(&trigger) {
+            self.triggers.push(trigger);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn applies_to(&self, trigger: TarpitTrigger) -> bool {
+        self.triggers.contains(&trigger)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse() {
+        let doc = YamlLoader::load_from_str("10s").unwrap();
+        let config = TarpitConfig::parse(&doc[0]).unwrap();
+        assert_eq!(config.duration, Duration::from_secs(10));
+        assert!(config.keepalive_interval.is_none());
+        assert!(config.applies_to(TarpitTrigger::NetworkAcl));
+        assert!(config.applies_to(TarpitTrigger::ClientConnLimit));
+
+        let doc = YamlLoader::load_from_str(
+            r#"
+            duration: 1m
+            keepalive_interval: 5s
+            keepalive_data: "x"
+            max_concurrent: 10
+            apply_to: ingress_network_filter
+            "#,
+        )
+        .unwrap();
+        let config = TarpitConfig::parse(&doc[0]).unwrap();
+        assert_eq!(config.duration, Duration::from_secs(60));
+        assert_eq!(config.keepalive_interval, Some(Duration::from_secs(5)));
+        assert_eq!(config.keepalive_data, b"x");
+        assert_eq!(config.max_concurrent, 10);
+        assert!(config.applies_to(TarpitTrigger::NetworkAcl));
+        assert!(!config.applies_to(TarpitTrigger::ClientConnLimit));
+
+        let doc =
+            YamlLoader::load_from_str("apply_to: [rate_limit, dst_host_acl, inspection_block]")
+                .unwrap();
+        let config = TarpitConfig::parse(&doc[0]).unwrap();
+        assert!(!config.applies_to(TarpitTrigger::NetworkAcl));
+        assert!(config.applies_to(TarpitTrigger::RateLimit));
+        assert!(config.applies_to(TarpitTrigger::DstHostAcl));
+        assert!(!config.applies_to(TarpitTrigger::DstIpAcl));
+        assert!(config.applies_to(TarpitTrigger::InspectionBlock));
+
+        let doc = YamlLoader::load_from_str("apply_to: [auth_failure]").unwrap();
+        assert!(TarpitConfig::parse(&doc[0]).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_types::metrics::MetricsName;
+
+const DEFAULT_TENANT: &str = "unknown";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum SniPattern {
+    Exact(String),
+    Child(String),
+}
+
+impl SniPattern {
+    fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let s = g3_yaml::value::as_string(value)?.to_lowercase();
+        if let Some(parent) = s.strip_prefix("*.") {
+            if parent.is_empty() {
+                return Err(anyhow!("empty parent domain in sni pattern"));
+            }
+            Ok(SniPattern::Child(format!(".{parent}")))
+        } else if s.is_empty() {
+            Err(anyhow!("empty sni pattern"))
+        } else {
+            Ok(SniPattern::Exact(s))
+        }
+    }
+
+    fn is_match(&self, sni: &str) -> bool {
+        match self {
+            SniPattern::Exact(s) => s.eq_ignore_ascii_case(sni),
+            SniPattern::Child(suffix) => {
+                sni.len() > suffix.len()
+                    && sni.is_char_boundary(sni.len() - suffix.len())
+                    && sni[sni.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TenantTagSource {
+    /// use the name of the server
+    Listener,
+    /// use the tenant of the first matched rule
+    Sni(Vec<(SniPattern, Arc<str>)>),
+    /// use the user group name of the authenticated user
+    UserGroup,
+    /// use the name of the authenticated user
+    User,
+}
+
+/// Config to tag each task with a tenant value, which will be used in logs and metrics
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TenantTagConfig {
+    source: TenantTagSource,
+    allowlist: Option<BTreeSet<Arc<str>>>,
+    default: Arc<str>,
+}
+
+impl TenantTagConfig {
+    fn new(source: TenantTagSource) -> Self {
+        TenantTagConfig {
+            source,
+            allowlist: None,
+            default: Arc::from(DEFAULT_TENANT),
+        }
+    }
+
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        match value {
+            Yaml::String(s) => {
+                let source = TenantTagConfig::parse_simple_source(s)?;
+                Ok(TenantTagConfig::new(source))
+            }
+            Yaml::Hash(map) => {
+                let mut source = None;
+                let mut sni_rules = Vec::new();
+                let mut config = TenantTagConfig::new(TenantTagSource::Listener);
+                g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                    "source" => {
+                        let s = g3_yaml::value::as_string(v)?;
+                        if s == "sni" {
+                            source = Some(TenantTagSource::Sni(Vec::new()));
+                        } else {
+                            source = Some(TenantTagConfig::parse_simple_source(&s)?);
+                        }
+                        Ok(())
+                    }
+                    "sni_rules" | "sni_map" => {
+                        let Yaml::Hash(map) = v else {
+                            return Err(anyhow!("invalid yaml value type for key {k}"));
+                        };
+                        g3_yaml::foreach_kv(map, |tenant, v| {
+                            let tenant = Arc::<str>::from(tenant);
+                            if let Yaml::Array(seq) = v {
+                                for (i, v) in seq.iter().enumerate() {
+                                    let pattern = SniPattern::parse(v).context(format!(
+                                        "invalid sni pattern value for {tenant}#{i}"
+                                    ))?;
+                                    sni_rules.push((pattern, tenant.clone()));
+                                }
+                            } else {
+                                let pattern = SniPattern::parse(v)
+                                    .context(format!("invalid sni pattern value for {tenant}"))?;
+                                sni_rules.push((pattern, tenant));
+                            }
+                            Ok(())
+                        })
+                        .context(format!("invalid sni rules value for key {k}"))
+                    }
+                    "allowlist" | "allowed_values" => {
+                        let values = g3_yaml::value::as_list(v, g3_yaml::value::as_string)
+                            .context(format!("invalid string list value for key {k}"))?;
+                        config.allowlist = Some(values.into_iter().map(Arc::from).collect());
+                        Ok(())
+                    }
+                    "default" | "fallback" => {
+                        let s = g3_yaml::value::as_string(v)
+                            .context(format!("invalid string value for key {k}"))?;
+                        config.default = Arc::from(s);
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("invalid key {k}")),
+                })?;
+                match source {
+                    Some(TenantTagSource::Sni(_)) => {
+                        if sni_rules.is_empty() {
+                            return Err(anyhow!("no sni rules set for sni source"));
+                        }
+                        config.source = TenantTagSource::Sni(sni_rules);
+                    }
+                    Some(source) => config.source = source,
+                    None => return Err(anyhow!("no tenant tag source set")),
+                }
+                Ok(config)
+            }
+            _ => Err(anyhow!(
+                "yaml value type for 'tenant tag config' should be 'map' or 'string'"
+            )),
+        }
+    }
+
+    fn parse_simple_source(s: &str) -> anyhow::Result<TenantTagSource> {
+        match s {
+            "listener" | "server" => Ok(TenantTagSource::Listener),
+            "user_group" | "auth_group" => Ok(TenantTagSource::UserGroup),
+            "user" | "username" => Ok(TenantTagSource::User),
+            "sni" => Err(anyhow!("sni rules is required for sni source")),
+            _ => Err(anyhow!("invalid tenant tag source {s}")),
+        }
+    }
+
+    /// Get the tenant value for a task.
+    ///
+    /// The default value will be used if not found or not allowed.
+    pub(crate) fn select(
+        &self,
+        server: &MetricsName,
+        sni: Option<&str>,
+        user_group: Option<&MetricsName>,
+        user: Option<&str>,
+    ) -> Arc<str> {
+        let value: Option<&str> = match &self.source {
+            TenantTagSource::Listener => Some(server.as_str()),
+            TenantTagSource::Sni(rules) => sni.and_then(|sni| {
+                rules
+                    .iter()
+                    .find(|(pattern, _)| pattern.is_match(sni))
+                    .map(|(_, tenant)| tenant.as_ref())
+            }),
+            TenantTagSource::UserGroup => user_group.map(|g| g.as_str()),
+            TenantTagSource::User => user,
+        };
+        let Some(value) = value else {
+            return self.default.clone();
+        };
+        match &self.allowlist {
+            Some(allowlist) => allowlist
+                .get(value)
+                .cloned()
+                .unwrap_or_else(|| self.default.clone()),
+            None => Arc::from(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn select_sni() {
+        let v = YamlLoader::load_from_str(
+            "{source: sni, sni_rules: {a: ['*.a.example.com', a.example.net], b: b.example.com}, allowlist: [a]}",
+        )
+        .unwrap();
+        let config = TenantTagConfig::parse(&v[0]).unwrap();
+        let server = MetricsName::from_str("server").unwrap();
+
+        let select = |sni: Option<&str>| config.select(&server, sni, None, None);
+        assert_eq!(select(Some("www.a.example.com")).as_ref(), "a");
+        assert_eq!(select(Some("a.example.net")).as_ref(), "a");
+        assert_eq!(select(Some("a.example.com")).as_ref(), "unknown");
+        // not in the allowlist
+        assert_eq!(select(Some("b.example.com")).as_ref(), "unknown");
+        assert_eq!(select(None).as_ref(), "unknown");
+    }
+
+    #[test]
+    fn select_listener() {
+        let v = YamlLoader::load_from_str("listener").unwrap();
+        let config = TenantTagConfig::parse(&v[0]).unwrap();
+        let server = MetricsName::from_str("server").unwrap();
+        assert_eq!(config.select(&server, None, None, None).as_ref(), "server");
+
+        let v = YamlLoader::load_from_str("sni").unwrap();
+        assert!(TenantTagConfig::parse(&v[0]).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_types::metrics::MetricsName;
+use g3_types::net::AlpnProtocol;
+
+/// Map the negotiated ALPN protocol to the next server, in the server preference order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct TlsAlpnServersConfig {
+    servers: Vec<(AlpnProtocol, MetricsName)>,
+}
+
+impl TlsAlpnServersConfig {
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let Yaml::Hash(map) = value else {
+            return Err(anyhow!(
+                "yaml value type for 'tls alpn servers config' should be 'map'"
+            ));
+        };
+
+        let mut config = TlsAlpnServersConfig::default();
+        g3_yaml::foreach_kv(map, |k, v| {
+            let Some(protocol) = AlpnProtocol::from_buf(k.as_bytes()) else {
+                return Err(anyhow!("unsupported alpn protocol {k}"));
+            };
+            if config.servers.iter().any(|(p, _)| *p == protocol) {
+                return Err(anyhow!("duplicate alpn protocol {k}"));
+            }
+            let server = g3_yaml::value::as_metrics_name(v)
+                .context(format!("invalid server name value for alpn protocol {k}"))?;
+            config.servers.push((protocol, server));
+            Ok(())
+        })?;
+        Ok(config)
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    pub(crate) fn protocols(&self) -> Vec<AlpnProtocol> {
+        self.servers.iter().map(|(p, _)| *p).collect()
+    }
+
+    pub(crate) fn contains(&self, protocol: &[u8]) -> bool {
+        self.servers
+            .iter()
+            .any(|(p, _)| p.identification_sequence() == protocol)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &(AlpnProtocol, MetricsName)> {
+        self.servers.iter()
+    }
+
+    pub(crate) fn depend_on_server(&self, name: &MetricsName) -> bool {
+        self.servers.iter().any(|(_, s)| s.eq(name))
+    }
+
+    pub(crate) fn collect_servers(&self, set: &mut BTreeSet<MetricsName>) {
+        for (_, server) in &self.servers {
+            set.insert(server.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse() {
+        let v = YamlLoader::load_from_str("{h2: http2, http/1.1: http1}").unwrap();
+        let config = TlsAlpnServersConfig::parse(&v[0]).unwrap();
+        assert_eq!(
+            config.protocols(),
+            vec![AlpnProtocol::Http2, AlpnProtocol::Http11]
+        );
+        assert!(config.depend_on_server(&MetricsName::from_str("http1").unwrap()));
+        assert!(!config.depend_on_server(&MetricsName::from_str("raw").unwrap()));
+
+        let v = YamlLoader::load_from_str("{spdy/3: http2}").unwrap();
+        assert!(TlsAlpnServersConfig::parse(&v[0]).is_err());
+
+        let v = YamlLoader::load_from_str("[h2]").unwrap();
+        assert!(TlsAlpnServersConfig::parse(&v[0]).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use url::Url;
+use yaml_rust::Yaml;
+
+use g3_types::metrics::MetricsName;
+
+/// Config for the OCSP stapling of the server certificates
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TlsOcspStaplingConfig {
+    /// fail the handshake if the client requested a staple but none is available
+    pub(crate) hard_fail: bool,
+    /// the interval to check if the staples should be refreshed
+    pub(crate) check_interval: Duration,
+    /// refresh the staple this long before the nextUpdate time of the response
+    pub(crate) refresh_ahead: Duration,
+    /// the cache lifetime of responses that have no nextUpdate time
+    pub(crate) default_lifetime: Duration,
+    pub(crate) request_timeout: Duration,
+    /// use this responder instead of the one in the AIA extension of the cert
+    pub(crate) responder: Option<Url>,
+    /// the resolver used to resolve the responder domain, the system resolver will be used if empty
+    pub(crate) resolver: MetricsName,
+}
+
+impl Default for TlsOcspStaplingConfig {
+    fn default() -> Self {
+        TlsOcspStaplingConfig {
+            hard_fail: false,
+            check_interval: Duration::from_secs(60),
+            refresh_ahead: Duration::from_secs(3600),
+            default_lifetime: Duration::from_secs(3600),
+            request_timeout: Duration::from_secs(10),
+            responder: None,
+            resolver: MetricsName::default(),
+        }
+    }
+}
+
+impl TlsOcspStaplingConfig {
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let mut config = TlsOcspStaplingConfig::default();
+        match value {
+            Yaml::Boolean(true) => Ok(config),
+            Yaml::Hash(map) => {
+                g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                    "hard_fail" => {
+                        config.hard_fail = g3_yaml::value::as_bool(v)
+                            .context(format!("invalid bool value for key {k}"))?;
+                        Ok(())
+                    }
+                    "check_interval" => {
+                        config.check_interval = g3_yaml::humanize::as_duration(v)
+                            .context(format!("invalid humanize duration value for key {k}"))?;
+                        Ok(())
+                    }
+                    "refresh_ahead" => {
+                        config.refresh_ahead = g3_yaml::humanize::as_duration(v)
+                            .context(format!("invalid humanize duration value for key {k}"))?;
+                        Ok(())
+                    }
+                    "default_lifetime" => {
+                        config.default_lifetime = g3_yaml::humanize::as_duration(v)
+                            .context(format!("invalid humanize duration value for key {k}"))?;
+                        Ok(())
+                    }
+                    "request_timeout" => {
+                        config.request_timeout = g3_yaml::humanize::as_duration(v)
+                            .context(format!("invalid humanize duration value for key {k}"))?;
+                        Ok(())
+                    }
+                    "responder" | "responder_url" => {
+                        let url = g3_yaml::value::as_url(v)
+                            .context(format!("invalid url value for key {k}"))?;
+                        if url.scheme() != "http" {
+                            return Err(anyhow!("only http ocsp responder is supported"));
+                        }
+                        config.responder = Some(url);
+                        Ok(())
+                    }
+                    "resolver" => {
+                        config.resolver = g3_yaml::value::as_metrics_name(v)
+                            .context(format!("invalid metrics name value for key {k}"))?;
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("invalid key {k}")),
+                })?;
+                if config.check_interval.is_zero() {
+                    return Err(anyhow!("check interval should not be zero"));
+                }
+                Ok(config)
+            }
+            _ => Err(anyhow!(
+                "yaml value type for 'tls ocsp stapling config' should be 'map' or 'true'"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse() {
+        let v = YamlLoader::load_from_str("true").unwrap();
+        let config = TlsOcspStaplingConfig::parse(&v[0]).unwrap();
+        assert_eq!(config, TlsOcspStaplingConfig::default());
+
+        let v = YamlLoader::load_from_str(
+            "{hard_fail: true, refresh_ahead: 2h, responder: 'http://ocsp.example.net/', resolver: default}",
+        )
+        .unwrap();
+        let config = TlsOcspStaplingConfig::parse(&v[0]).unwrap();
+        assert!(config.hard_fail);
+        assert_eq!(config.refresh_ahead, Duration::from_secs(7200));
+        assert!(config.responder.is_some());
+        assert_eq!(config.resolver.as_str(), "default");
+
+        let v = YamlLoader::load_from_str("{responder: 'https://ocsp.example.net/'}").unwrap();
+        assert!(TlsOcspStaplingConfig::parse(&v[0]).is_err());
+
+        let v = YamlLoader::load_from_str("false").unwrap();
+        assert!(TlsOcspStaplingConfig::parse(&v[0]).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use ip_network::IpNetwork;
+use yaml_rust::Yaml;
+
+use g3_types::net::{Host, UpstreamAddr};
+
+const DEFAULT_LOG_MAX_VIOLATIONS: usize = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum UdpDestViolationAction {
+    /// drop the packet silently
+    #[default]
+    Drop,
+    /// close the whole udp session
+    Close,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct UdpDestAllowRule {
+    network: IpNetwork,
+    /// inclusive port ranges, all ports are allowed if empty
+    ports: Vec<(u16, u16)>,
+}
+
+impl UdpDestAllowRule {
+    fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        match value {
+            Yaml::String(_) => {
+                let network = g3_yaml::value::as_ip_network(value)?;
+                Ok(UdpDestAllowRule {
+                    network,
+                    ports: Vec::new(),
+                })
+            }
+            Yaml::Hash(map) => {
+                let mut network = None;
+                let mut ports = Vec::new();
+                g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                    "network" | "net" | "cidr" => {
+                        let net = g3_yaml::value::as_ip_network(v)
+                            .context(format!("invalid ip network value for key {k}"))?;
+                        network = Some(net);
+                        Ok(())
+                    }
+                    "ports" | "port" => {
+                        if let Yaml::Array(seq) = v {
+                            for (i, v) in seq.iter().enumerate() {
+                                let range = parse_port_range(v)
+                                    .context(format!("invalid port range value for {k}#{i}"))?;
+                                ports.push(range);
+                            }
+                        } else {
+                            let range = parse_port_range(v)
+                                .context(format!("invalid port range value for key {k}"))?;
+                            ports.push(range);
+                        }
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("invalid key {k}")),
+                })?;
+                let Some(network) = network else {
+                    return Err(anyhow!("no network set"));
+                };
+                Ok(UdpDestAllowRule { network, ports })
+            }
+            _ => Err(anyhow!(
+                "yaml value type for 'udp dest allow rule' should be 'map' or 'string'"
+            )),
+        }
+    }
+
+    fn is_match(&self, ip: IpAddr, port: u16) -> bool {
+        if !self.network.contains(ip) {
+            return false;
+        }
+        self.ports.is_empty()
+            || self
+                .ports
+                .iter()
+                .any(|(start, end)| *start <= port && port <= *end)
+    }
+}
+
+fn parse_port_range(value: &Yaml) -> anyhow::Result<(u16, u16)> {
+    match value {
+        Yaml::Integer(_) => {
+            let port = g3_yaml::value::as_u16(value)?;
+            Ok((port, port))
+        }
+        Yaml::String(s) => {
+            if let Some((start, end)) = s.split_once('-') {
+                let start =
+                    u16::from_str(start.trim()).map_err(|e| anyhow!("invalid start port: {e}"))?;
+                let end =
+                    u16::from_str(end.trim()).map_err(|e| anyhow!("invalid end port: {e}"))?;
+                if start > end {
+                    return Err(anyhow!("start port is greater than end port"));
+                }
+                Ok((start, end))
+            } else {
+                let port = u16::from_str(s.trim()).map_err(|e| anyhow!("invalid port: {e}"))?;
+                Ok((port, port))
+            }
+        }
+        _ => Err(anyhow!(
+            "yaml value type for 'port range' should be 'integer' or 'string'"
+        )),
+    }
+}
+
+/// Config to restrict the destinations that a udp relay session may reach
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct UdpDestAllowlistConfig {
+    rules: Vec<UdpDestAllowRule>,
+    allow_domain: bool,
+    on_violation: UdpDestViolationAction,
+    log_max_violations: usize,
+}
+
+impl Default for UdpDestAllowlistConfig {
+    fn default() -> Self {
+        UdpDestAllowlistConfig {
+            rules: Vec::new(),
+            allow_domain: false,
+            on_violation: UdpDestViolationAction::Drop,
+            log_max_violations: DEFAULT_LOG_MAX_VIOLATIONS,
+        }
+    }
+}
+
+impl UdpDestAllowlistConfig {
+    pub(crate) fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let mut config = UdpDestAllowlistConfig::default();
+        match value {
+            Yaml::Array(seq) => config.parse_rules(seq)?,
+            Yaml::Hash(map) => {
+                g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                    "rules" | "allow" => {
+                        let Yaml::Array(seq) = v else {
+                            return Err(anyhow!("invalid yaml value type for key {k}"));
+                        };
+                        config
+                            .parse_rules(seq)
+                            .context(format!("invalid rules value for key {k}"))
+                    }
+                    "allow_domain" => {
+                        config.allow_domain = g3_yaml::value::as_bool(v)
+                            .context(format!("invalid bool value for key {k}"))?;
+                        Ok(())
+                    }
+                    "on_violation" | "violation_action" => {
+                        let s = g3_yaml::value::as_string(v)
+                            .context(format!("invalid string value for key {k}"))?;
+                        config.on_violation = match s.to_lowercase().as_str() {
+                            "drop" => UdpDestViolationAction::Drop,
+                            "close" | "error" => UdpDestViolationAction::Close,
+                            _ => return Err(anyhow!("invalid violation action {s}")),
+                        };
+                        Ok(())
+                    }
+                    "log_max_violations" | "log_max" => {
+                        config.log_max_violations = g3_yaml::value::as_usize(v)
+                            .context(format!("invalid usize value for key {k}"))?;
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("invalid key {k}")),
+                })?;
+            }
+            _ => {
+                return Err(anyhow!(
+                    "yaml value type for 'udp dest allowlist' should be 'map' or 'seq'"
+                ))
+            }
+        }
+        Ok(config)
+    }
+
+    fn parse_rules(&mut self, seq: &[Yaml]) -> anyhow::Result<()> {
+        for (i, v) in seq.iter().enumerate() {
+            let rule = UdpDestAllowRule::parse(v).context(format!("invalid rule #{i}"))?;
+            self.rules.push(rule);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn on_violation(&self) -> UdpDestViolationAction {
+        self.on_violation
+    }
+
+    #[inline]
+    pub(crate) fn log_max_violations(&self) -> usize {
+        self.log_max_violations
+    }
+
+    pub(crate) fn allow_upstream(&self, ups: &UpstreamAddr) -> bool {
+        match ups.host() {
+            Host::Ip(ip) => self.allow_ip_port(*ip, ups.port()),
+            Host::Domain(_) => self.allow_domain,
+        }
+    }
+
+    fn allow_ip_port(&self, ip: IpAddr, port: u16) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6
+                .to_ipv4_mapped()
+                .map(IpAddr::V4)
+                .unwrap_or(IpAddr::V6(v6)),
+            IpAddr::V4(_) => ip,
+        };
+        self.rules.iter().any(|r| r.is_match(ip, port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn check() {
+        let v = YamlLoader::load_from_str(
+            "{rules: ['192.0.2.0/24', {network: '2001:db8::/32', ports: [53, '5000-5010']}], on_violation: close}",
+        )
+        .unwrap();
+        let config = UdpDestAllowlistConfig::parse(&v[0]).unwrap();
+        assert_eq!(config.on_violation(), UdpDestViolationAction::Close);
+
+        let allow = |s: &str| config.allow_upstream(&UpstreamAddr::from_str(s).unwrap());
+        assert!(allow("192.0.2.1:1234"));
+        assert!(allow("[::ffff:192.0.2.1]:1234"));
+        assert!(!allow("198.51.100.1:53"));
+        assert!(allow("[2001:db8::1]:53"));
+        assert!(allow("[2001:db8::1]:5005"));
+        assert!(!allow("[2001:db8::1]:5011"));
+        assert!(!allow("www.example.net:53"));
+
+        let v = YamlLoader::load_from_str("['10.0.0.0/8']").unwrap();
+        let config = UdpDestAllowlistConfig::parse(&v[0]).unwrap();
+        assert_eq!(config.on_violation(), UdpDestViolationAction::Drop);
+        assert!(config.allow_upstream(&UpstreamAddr::from_str("10.1.2.3:53").unwrap()));
+    }
+}
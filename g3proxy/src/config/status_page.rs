@@ -0,0 +1,103 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use log::warn;
+use yaml_rust::{yaml, Yaml};
+
+static GLOBAL_STATUS_PAGE_CONFIG: OnceLock<StatusPageConfig> = OnceLock::new();
+
+#[derive(Clone)]
+pub(crate) struct StatusPageConfig {
+    pub(crate) listen: SocketAddr,
+    pub(crate) request_timeout: Duration,
+    pub(crate) max_connections: usize,
+}
+
+impl StatusPageConfig {
+    fn new(listen: SocketAddr) -> Self {
+        StatusPageConfig {
+            listen,
+            request_timeout: Duration::from_secs(4),
+            max_connections: 16,
+        }
+    }
+
+    fn parse(map: &yaml::Hash) -> anyhow::Result<Self> {
+        let mut listen: Option<SocketAddr> = None;
+        let mut request_timeout: Option<Duration> = None;
+        let mut max_connections: Option<usize> = None;
+        g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "listen" => {
+                let addr = g3_yaml::value::as_env_sockaddr(v)
+                    .context(format!("invalid socket address value for key {k}"))?;
+                listen = Some(addr);
+                Ok(())
+            }
+            "request_timeout" => {
+                let timeout = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                request_timeout = Some(timeout);
+                Ok(())
+            }
+            "max_connections" => {
+                let max = g3_yaml::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                if max == 0 {
+                    return Err(anyhow!("invalid zero value for key {k}"));
+                }
+                max_connections = Some(max);
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+
+        let listen = listen.ok_or_else(|| anyhow!("no listen address set"))?;
+        let mut config = StatusPageConfig::new(listen);
+        if let Some(timeout) = request_timeout {
+            config.request_timeout = timeout;
+        }
+        if let Some(max) = max_connections {
+            config.max_connections = max;
+        }
+        Ok(config)
+    }
+}
+
+pub(crate) fn get_global_config() -> Option<&'static StatusPageConfig> {
+    GLOBAL_STATUS_PAGE_CONFIG.get()
+}
+
+pub(crate) fn load(v: &Yaml) -> anyhow::Result<()> {
+    let config = match v {
+        Yaml::Hash(map) => StatusPageConfig::parse(map)?,
+        Yaml::String(_) => {
+            let addr = g3_yaml::value::as_env_sockaddr(v)
+                .context("invalid socket address value for status page")?;
+            StatusPageConfig::new(addr)
+        }
+        Yaml::Null => return Ok(()),
+        _ => return Err(anyhow!("invalid value type")),
+    };
+    if GLOBAL_STATUS_PAGE_CONFIG.set(config).is_err() {
+        warn!("Global status page config has already been set");
+    }
+    Ok(())
+}
@@ -51,4 +51,27 @@ impl escaper_control::Server for EscaperControlImpl {
             Ok(())
         })
     }
+
+    fn upstream_health(
+        &mut self,
+        _params: escaper_control::UpstreamHealthParams,
+        mut results: escaper_control::UpstreamHealthResults,
+    ) -> Promise<(), capnp::Error> {
+        if let Some(health) = self.escaper.upstream_health() {
+            let mut obj = serde_json::Map::new();
+            obj.insert(
+                "escaper".to_string(),
+                serde_json::Value::String(self.escaper.name().to_string()),
+            );
+            obj.insert("upstreams".to_string(), health);
+            results
+                .get()
+                .set_health(serde_json::Value::Object(obj).to_string().as_str());
+            Promise::ok(())
+        } else {
+            Promise::err(capnp::Error::failed(
+                "upstream health is not supported on this escaper".to_string(),
+            ))
+        }
+    }
 }
@@ -25,9 +25,11 @@ use g3_types::resolve::{QueryStrategy as ResolveQueryStrategy, ResolveStrategy};
 
 use g3proxy_proto::resolver_capnp::{resolver_control, QueryStrategy};
 
+use super::set_operation_result;
 use crate::resolve::{ArcIntegratedResolverHandle, HappyEyeballsResolveJob};
 
 pub(super) struct ResolverControlImpl {
+    name: MetricsName,
     resolver_handler: ArcIntegratedResolverHandle,
 }
 
@@ -36,6 +38,7 @@ impl ResolverControlImpl {
         let name = unsafe { MetricsName::new_unchecked(name) };
         let handler = crate::resolve::get_handle(&name)?;
         Ok(capnp_rpc::new_client(ResolverControlImpl {
+            name,
             resolver_handler: handler,
         }))
     }
@@ -87,6 +90,36 @@ impl resolver_control::Server for ResolverControlImpl {
             Ok(())
         })
     }
+
+    fn warmup(
+        &mut self,
+        _params: resolver_control::WarmupParams,
+        mut results: resolver_control::WarmupResults,
+    ) -> Promise<(), capnp::Error> {
+        set_operation_result(
+            results.get().init_result(),
+            crate::resolve::start_warmup(&self.name),
+        );
+        Promise::ok(())
+    }
+
+    fn warmup_status(
+        &mut self,
+        _params: resolver_control::WarmupStatusParams,
+        mut results: resolver_control::WarmupStatusResults,
+    ) -> Promise<(), capnp::Error> {
+        let status = crate::resolve::get_warmup_status(&self.name);
+        let mut obj = serde_json::Map::new();
+        obj.insert(
+            "resolver".to_string(),
+            serde_json::Value::String(self.name.to_string()),
+        );
+        obj.insert("warmup".to_string(), status.to_json());
+        results
+            .get()
+            .set_status(serde_json::Value::Object(obj).to_string().as_str());
+        Promise::ok(())
+    }
 }
 
 fn get_resolver_strategy(q: QueryStrategy) -> ResolveStrategy {
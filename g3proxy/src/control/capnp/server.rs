@@ -20,6 +20,7 @@ use g3_types::metrics::MetricsName;
 
 use g3proxy_proto::server_capnp::server_control;
 
+use super::set_operation_result;
 use crate::serve::ArcServer;
 
 pub(super) struct ServerControlImpl {
@@ -53,4 +54,13 @@ impl server_control::Server for ServerControlImpl {
             ))
         }
     }
+
+    fn reload_tls_cert(
+        &mut self,
+        _params: server_control::ReloadTlsCertParams,
+        mut results: server_control::ReloadTlsCertResults,
+    ) -> Promise<(), capnp::Error> {
+        set_operation_result(results.get().init_result(), self.server.reload_tls_cert());
+        Promise::ok(())
+    }
 }
@@ -32,6 +32,8 @@ impl UniqueController {
         // make sure we always shut down protected io
         crate::control::disable_protected_io().await;
 
+        crate::stat::stop_status_page();
+
         debug!("stopping all servers");
         crate::serve::stop_all().await;
         debug!("stopped all servers");
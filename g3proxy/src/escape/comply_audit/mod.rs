@@ -60,7 +60,7 @@ impl ComplyAuditEscaper {
         let next = super::registry::get_or_insert_default(&config.next);
         let auditor = crate::audit::get_or_insert_default(&config.auditor);
         let audit_handle = auditor
-            .build_handle()
+            .build_handle(None)
             .context("failed to build audit handle")?;
 
         let escaper = ComplyAuditEscaper {
@@ -0,0 +1,323 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use ahash::AHashMap;
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore, TryAcquireError};
+
+use g3_types::net::UpstreamAddr;
+
+use super::EscaperConnLimitStats;
+use crate::config::escaper::conn_limit::{UpstreamConnLimitAction, UpstreamConnLimitConfig};
+use crate::module::tcp_connect::TcpConnectError;
+
+type UpstreamSemaphoreMap = Mutex<AHashMap<UpstreamAddr, Arc<Semaphore>>>;
+
+/// A reference to the semaphore of a single upstream.
+///
+/// The map entry will be removed when the last reference is dropped.
+struct UpstreamSemaphoreRef {
+    upstream: UpstreamAddr,
+    semaphore: Arc<Semaphore>,
+    map: Arc<UpstreamSemaphoreMap>,
+}
+
+impl Drop for UpstreamSemaphoreRef {
+    fn drop(&mut self) {
+        let mut map = self.map.lock().unwrap();
+        // one is held in the map and the other one is held by us
+        if Arc::strong_count(&self.semaphore) <= 2 {
+            if let Some(v) = map.get(&self.upstream) {
+                if Arc::ptr_eq(v, &self.semaphore) {
+                    map.remove(&self.upstream);
+                }
+            }
+        }
+    }
+}
+
+/// The permit for a single upstream connection.
+///
+/// It should be held as long as the connection is alive.
+pub(crate) struct UpstreamConnPermit {
+    // the permits should be dropped before the semaphore ref
+    _upstream_permit: Option<OwnedSemaphorePermit>,
+    _total_permit: Option<OwnedSemaphorePermit>,
+    _upstream_ref: Option<UpstreamSemaphoreRef>,
+    stats: Arc<EscaperConnLimitStats>,
+}
+
+impl Drop for UpstreamConnPermit {
+    fn drop(&mut self) {
+        self.stats.dec_in_use();
+    }
+}
+
+pub(crate) struct UpstreamConnLimiter {
+    config: UpstreamConnLimitConfig,
+    total: Option<Arc<Semaphore>>,
+    upstreams: Arc<UpstreamSemaphoreMap>,
+    stats: Arc<EscaperConnLimitStats>,
+}
+
+impl UpstreamConnLimiter {
+    pub(crate) fn new(
+        config: UpstreamConnLimitConfig,
+        stats: Arc<EscaperConnLimitStats>,
+    ) -> Option<Self> {
+        if !config.is_enabled() {
+            return None;
+        }
+        let total = if config.max_total > 0 {
+            Some(Arc::new(Semaphore::new(config.max_total)))
+        } else {
+            None
+        };
+        Some(UpstreamConnLimiter {
+            config,
+            total,
+            upstreams: Arc::new(Mutex::new(AHashMap::new())),
+            stats,
+        })
+    }
+
+    fn upstream_semaphore(&self, upstream: &UpstreamAddr) -> Option<UpstreamSemaphoreRef> {
+        if self.config.max_per_upstream == 0 {
+            return None;
+        }
+        let mut map = self.upstreams.lock().unwrap();
+        let semaphore = map
+            .entry(upstream.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.config.max_per_upstream)))
+            .clone();
+        Some(UpstreamSemaphoreRef {
+            upstream: upstream.clone(),
+            semaphore,
+            map: self.upstreams.clone(),
+        })
+    }
+
+    pub(crate) async fn acquire(
+        &self,
+        upstream: &UpstreamAddr,
+    ) -> Result<UpstreamConnPermit, TcpConnectError> {
+        let upstream_ref = self.upstream_semaphore(upstream);
+
+        let (total_permit, upstream_permit) = match self.config.action {
+            UpstreamConnLimitAction::Reject => {
+                let total_permit = match &self.total {
+                    Some(s) => Some(self.try_acquire(s)?),
+                    None => None,
+                };
+                let upstream_permit = match &upstream_ref {
+                    Some(r) => Some(self.try_acquire(&r.semaphore)?),
+                    None => None,
+                };
+                (total_permit, upstream_permit)
+            }
+            UpstreamConnLimitAction::Queue(timeout) => {
+                let acquire = async {
+                    let total_permit = match &self.total {
+                        Some(s) => Some(s.clone().acquire_owned().await?),
+                        None => None,
+                    };
+                    let upstream_permit = match &upstream_ref {
+                        Some(r) => Some(r.semaphore.clone().acquire_owned().await?),
+                        None => None,
+                    };
+                    Ok::<_, AcquireError>((total_permit, upstream_permit))
+                };
+                match tokio::time::timeout(timeout, acquire).await {
+                    Ok(Ok(v)) => v,
+                    Ok(Err(_)) => {
+                        return Err(TcpConnectError::InternalServerError(
+                            "upstream connection limit semaphore closed",
+                        ));
+                    }
+                    Err(_) => {
+                        self.stats.add_rejected();
+                        return Err(TcpConnectError::UpstreamConnLimitReached);
+                    }
+                }
+            }
+        };
+
+        self.stats.inc_in_use();
+        Ok(UpstreamConnPermit {
+            _upstream_permit: upstream_permit,
+            _total_permit: total_permit,
+            _upstream_ref: upstream_ref,
+            stats: self.stats.clone(),
+        })
+    }
+
+    fn try_acquire(
+        &self,
+        semaphore: &Arc<Semaphore>,
+    ) -> Result<OwnedSemaphorePermit, TcpConnectError> {
+        match semaphore.clone().try_acquire_owned() {
+            Ok(permit) => Ok(permit),
+            Err(TryAcquireError::NoPermits) => {
+                self.stats.add_rejected();
+                Err(TcpConnectError::UpstreamConnLimitReached)
+            }
+            Err(TryAcquireError::Closed) => Err(TcpConnectError::InternalServerError(
+                "upstream connection limit semaphore closed",
+            )),
+        }
+    }
+}
+
+pin_project! {
+    /// Hold the connection permit along with the io object, so it will be released
+    /// on every exit path of the task, including panics.
+    pub(crate) struct UpstreamConnLimited<S> {
+        #[pin]
+        inner: S,
+        _permit: Option<UpstreamConnPermit>,
+    }
+}
+
+impl<S> UpstreamConnLimited<S> {
+    pub(crate) fn new(inner: S, permit: Option<UpstreamConnPermit>) -> Self {
+        UpstreamConnLimited {
+            inner,
+            _permit: permit,
+        }
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for UpstreamConnLimited<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.project().inner.poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for UpstreamConnLimited<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn new_limiter(
+        max_per_upstream: usize,
+        max_total: usize,
+        action: UpstreamConnLimitAction,
+    ) -> (UpstreamConnLimiter, Arc<EscaperConnLimitStats>) {
+        let stats = Arc::new(EscaperConnLimitStats::default());
+        let config = UpstreamConnLimitConfig {
+            max_per_upstream,
+            max_total,
+            action,
+        };
+        let limiter = UpstreamConnLimiter::new(config, stats.clone()).unwrap();
+        (limiter, stats)
+    }
+
+    #[tokio::test]
+    async fn reject() {
+        let (limiter, stats) = new_limiter(1, 2, UpstreamConnLimitAction::Reject);
+        let ups1 = UpstreamAddr::from_ip_and_port("127.0.0.1".parse().unwrap(), 80);
+        let ups2 = UpstreamAddr::from_ip_and_port("127.0.0.2".parse().unwrap(), 80);
+        let ups3 = UpstreamAddr::from_ip_and_port("127.0.0.3".parse().unwrap(), 80);
+
+        let p1 = limiter.acquire(&ups1).await.unwrap();
+        assert!(matches!(
+            limiter.acquire(&ups1).await,
+            Err(TcpConnectError::UpstreamConnLimitReached)
+        ));
+        let p2 = limiter.acquire(&ups2).await.unwrap();
+        assert!(matches!(
+            limiter.acquire(&ups3).await,
+            Err(TcpConnectError::UpstreamConnLimitReached)
+        ));
+        let snap = stats.snapshot();
+        assert_eq!(snap.in_use, 2);
+        assert_eq!(snap.rejected, 2);
+
+        drop(p1);
+        let _p3 = limiter.acquire(&ups3).await.unwrap();
+        drop(p2);
+        assert_eq!(stats.snapshot().in_use, 1);
+        assert_eq!(limiter.upstreams.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn queue() {
+        let (limiter, stats) = new_limiter(
+            1,
+            0,
+            UpstreamConnLimitAction::Queue(Duration::from_millis(100)),
+        );
+        let limiter = Arc::new(limiter);
+        let ups = UpstreamAddr::from_ip_and_port("127.0.0.1".parse().unwrap(), 80);
+
+        let p1 = limiter.acquire(&ups).await.unwrap();
+        assert!(matches!(
+            limiter.acquire(&ups).await,
+            Err(TcpConnectError::UpstreamConnLimitReached)
+        ));
+        assert_eq!(stats.snapshot().rejected, 1);
+
+        let limiter2 = limiter.clone();
+        let ups2 = ups.clone();
+        let handle = tokio::spawn(async move { limiter2.acquire(&ups2).await.is_ok() });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(p1);
+        assert!(handle.await.unwrap());
+        assert_eq!(stats.snapshot().in_use, 0);
+        assert!(limiter.upstreams.lock().unwrap().is_empty());
+    }
+}
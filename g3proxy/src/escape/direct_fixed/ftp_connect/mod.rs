@@ -19,6 +19,7 @@ use std::sync::Arc;
 use g3_io_ext::LimitedStream;
 
 use super::DirectFixedEscaper;
+use crate::escape::UpstreamConnLimited;
 use crate::module::ftp_over_http::{
     ArcFtpTaskRemoteControlStats, ArcFtpTaskRemoteTransferStats, BoxFtpRemoteConnection,
     FtpControlRemoteWrapperStats, FtpTransferRemoteWrapperStats,
@@ -33,7 +34,11 @@ impl DirectFixedEscaper {
         task_notes: &'a ServerTaskNotes,
         task_stats: ArcFtpTaskRemoteControlStats,
     ) -> Result<BoxFtpRemoteConnection, TcpConnectError> {
+        let permit = self
+            .acquire_upstream_conn_permit(&tcp_notes.upstream)
+            .await?;
         let stream = self.tcp_connect_to(tcp_notes, task_notes).await?;
+        let stream = UpstreamConnLimited::new(stream, permit);
 
         let mut wrapper_stats = FtpControlRemoteWrapperStats::new(&self.stats, task_stats);
         wrapper_stats.push_user_io_stats(self.fetch_user_upstream_io_stats(task_notes));
@@ -58,9 +63,13 @@ impl DirectFixedEscaper {
         task_notes: &'a ServerTaskNotes,
         task_stats: ArcFtpTaskRemoteTransferStats,
     ) -> Result<BoxFtpRemoteConnection, TcpConnectError> {
+        let permit = self
+            .acquire_upstream_conn_permit(&transfer_tcp_notes.upstream)
+            .await?;
         let stream = self
             .tcp_connect_to_again(transfer_tcp_notes, control_tcp_notes, task_notes)
             .await?;
+        let stream = UpstreamConnLimited::new(stream, permit);
 
         let mut wrapper_stats = FtpTransferRemoteWrapperStats::new(&self.stats, task_stats);
         wrapper_stats.push_user_io_stats(self.fetch_user_upstream_io_stats(task_notes));
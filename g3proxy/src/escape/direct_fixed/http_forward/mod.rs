@@ -20,6 +20,7 @@ use g3_io_ext::{AsyncStream, LimitedBufReader, LimitedWriter, NilLimitedReaderSt
 use g3_types::net::{Host, OpensslClientConfig};
 
 use super::{DirectFixedEscaper, DirectFixedEscaperStats};
+use crate::escape::UpstreamConnLimited;
 use crate::log::escape::tls_handshake::TlsApplication;
 use crate::module::http_forward::{
     ArcHttpForwardTaskRemoteStats, BoxHttpForwardConnection, HttpForwardRemoteWrapperStats,
@@ -41,9 +42,13 @@ impl DirectFixedEscaper {
         task_notes: &'a ServerTaskNotes,
         task_stats: ArcHttpForwardTaskRemoteStats,
     ) -> Result<BoxHttpForwardConnection, TcpConnectError> {
+        let permit = self
+            .acquire_upstream_conn_permit(&tcp_notes.upstream)
+            .await?;
         let stream = self.tcp_connect_to(tcp_notes, task_notes).await?;
 
         let (ups_r, ups_w) = stream.into_split();
+        let ups_r = UpstreamConnLimited::new(ups_r, permit);
 
         let mut w_wrapper_stats = HttpForwardRemoteWrapperStats::new(&self.stats, &task_stats);
         let mut r_wrapper_stats = HttpForwardTaskRemoteWrapperStats::new(task_stats);
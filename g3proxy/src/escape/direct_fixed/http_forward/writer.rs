@@ -29,8 +29,9 @@ use g3_types::net::UpstreamAddr;
 
 use crate::auth::UserUpstreamTrafficStats;
 use crate::module::http_forward::{
-    send_req_header_to_origin, ArcHttpForwardTaskRemoteStats, HttpForwardRemoteWrapperStats,
-    HttpForwardTaskRemoteStats, HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
+    send_req_header_to_origin, ArcHttpForwardTaskRemoteStats, HttpForwardConnectionMeta,
+    HttpForwardRemoteWrapperStats, HttpForwardTaskRemoteStats, HttpForwardTaskRemoteWrapperStats,
+    HttpForwardWrite,
 };
 use crate::serve::ServerTaskNotes;
 
@@ -39,6 +40,7 @@ pin_project! {
         #[pin]
         inner: W,
         escaper_stats: Option<Arc<S>>,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -51,6 +53,7 @@ where
         DirectHttpForwardWriter {
             inner: ups_w,
             escaper_stats,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -86,7 +89,18 @@ where
     W: AsyncWrite + Send + Unpin,
     S: HttpForwardTaskRemoteStats + Send + Sync + 'static,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, _upstream: &UpstreamAddr) {}
+    fn prepare_new(
+        &mut self,
+        _task_notes: &ServerTaskNotes,
+        _upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
 
     fn update_stats(
         &mut self,
@@ -15,7 +15,9 @@
  */
 
 use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
 use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use anyhow::anyhow;
@@ -27,12 +29,14 @@ use g3_resolver::ResolveError;
 use g3_socket::util::AddressFamily;
 use g3_socket::BindAddr;
 use g3_types::acl::AclNetworkRule;
+use g3_types::collection::{SelectiveItem, SelectivePickPolicy, SelectiveVec, SelectiveVecBuilder};
 use g3_types::metrics::MetricsName;
 use g3_types::net::{Host, OpensslClientConfig, UpstreamAddr};
 use g3_types::resolve::{ResolveRedirection, ResolveStrategy};
 
 use super::{
     ArcEscaper, ArcEscaperStats, EgressPathSelection, Escaper, EscaperInternal, EscaperStats,
+    UpstreamConnLimiter, UpstreamConnPermit,
 };
 use crate::audit::AuditContext;
 use crate::auth::UserUpstreamTrafficStats;
@@ -67,12 +71,37 @@ mod tls_connect;
 pub(crate) mod udp_connect;
 pub(crate) mod udp_relay;
 
+struct BindIpNode {
+    ip: IpAddr,
+    selected: Arc<AtomicU64>,
+}
+
+impl SelectiveItem for BindIpNode {
+    fn weight(&self) -> f64 {
+        1.0
+    }
+
+    fn selective_hash<H: Hasher>(&self, state: &mut H) {
+        self.ip.hash(state);
+    }
+}
+
+impl BindIpNode {
+    fn select(&self) -> BindAddr {
+        self.selected.fetch_add(1, Ordering::Relaxed);
+        BindAddr::Ip(self.ip)
+    }
+}
+
 pub(super) struct DirectFixedEscaper {
     config: Arc<DirectFixedEscaperConfig>,
     stats: Arc<DirectFixedEscaperStats>,
+    bind4: Option<SelectiveVec<BindIpNode>>,
+    bind6: Option<SelectiveVec<BindIpNode>>,
     resolver_handle: ArcIntegratedResolverHandle,
     egress_net_filter: Arc<AclNetworkRule>,
     resolve_redirection: Option<ResolveRedirection>,
+    conn_limiter: Option<UpstreamConnLimiter>,
     escape_logger: Logger,
 }
 
@@ -89,16 +118,35 @@ impl DirectFixedEscaper {
             .as_ref()
             .map(|builder| builder.build());
 
+        let conn_limiter =
+            UpstreamConnLimiter::new(config.upstream_conn_limit, stats.conn_limit.clone());
+
         let escape_logger = config.get_escape_logger();
 
         stats.set_extra_tags(config.extra_metrics_tags.clone());
 
+        let build_bind_nodes = |ips: &[IpAddr]| {
+            let mut builder = SelectiveVecBuilder::with_capacity(ips.len());
+            for ip in ips {
+                builder.insert(BindIpNode {
+                    ip: *ip,
+                    selected: stats.bind_ip.selected_counter(*ip),
+                });
+            }
+            builder.build()
+        };
+        let bind4 = build_bind_nodes(&config.bind4);
+        let bind6 = build_bind_nodes(&config.bind6);
+
         let escaper = DirectFixedEscaper {
             config: Arc::new(config),
             stats,
+            bind4,
+            bind6,
             resolver_handle,
             egress_net_filter,
             resolve_redirection,
+            conn_limiter,
             escape_logger,
         };
 
@@ -121,37 +169,70 @@ impl DirectFixedEscaper {
         }
     }
 
-    fn get_bind_random(
+    /// Select the bind address of the given family.
+    ///
+    /// The `key` will be used by the consistent hash pick policies,
+    /// so the same bind ip will be used for the same key.
+    fn get_bind_addr<K: Hash + ?Sized>(
         &self,
         family: AddressFamily,
         path_selection: Option<&EgressPathSelection>,
+        key: &K,
     ) -> BindAddr {
-        let vec = match family {
-            AddressFamily::Ipv4 => &self.config.bind4,
-            AddressFamily::Ipv6 => &self.config.bind6,
+        let (nodes, count) = match family {
+            AddressFamily::Ipv4 => (&self.bind4, self.config.bind4.len()),
+            AddressFamily::Ipv6 => (&self.bind6, self.config.bind6.len()),
         };
-        match vec.len() {
+        let Some(nodes) = nodes else {
             #[cfg(any(target_os = "linux", target_os = "android"))]
-            0 => self
+            return self
                 .config
                 .bind_interface
                 .map(BindAddr::Interface)
-                .unwrap_or_default(),
+                .unwrap_or_default();
             #[cfg(not(any(target_os = "linux", target_os = "android")))]
-            0 => BindAddr::None,
-            1 => BindAddr::Ip(vec[0]),
-            n => {
-                if self.config.enable_path_selection {
-                    if let Some(path_selection) = path_selection {
-                        if let Some(i) = path_selection.select_by_index(n) {
-                            return BindAddr::Ip(vec[i]);
-                        }
-                    }
-                }
+            return BindAddr::None;
+        };
 
-                fastrand::choice(vec).map(|ip| BindAddr::Ip(*ip)).unwrap()
+        if count > 1 && self.config.enable_path_selection {
+            if let Some(path_selection) = path_selection {
+                if let Some(i) = path_selection.select_by_index(count) {
+                    return nodes.pick_pinned(i).select();
+                }
             }
         }
+
+        let node = match self.config.bind_pick_policy {
+            SelectivePickPolicy::Random => nodes.pick_random(),
+            SelectivePickPolicy::Serial => nodes.pick_serial(),
+            SelectivePickPolicy::RoundRobin => nodes.pick_round_robin(),
+            SelectivePickPolicy::Ketama => nodes.pick_ketama(key),
+            SelectivePickPolicy::Rendezvous => nodes.pick_rendezvous(key),
+            SelectivePickPolicy::JumpHash => nodes.pick_jump(key),
+        };
+        node.select()
+    }
+
+    /// Get the transparent bind address if the client ip should be used as the source address
+    #[cfg(target_os = "linux")]
+    fn get_bind_transparent(&self, task_notes: &ServerTaskNotes) -> Option<BindAddr> {
+        if self.config.bind_client_ip {
+            Some(BindAddr::Transparent(
+                task_notes.client_addr().ip().to_canonical(),
+            ))
+        } else {
+            None
+        }
+    }
+
+    async fn acquire_upstream_conn_permit(
+        &self,
+        upstream: &UpstreamAddr,
+    ) -> Result<Option<UpstreamConnPermit>, TcpConnectError> {
+        match &self.conn_limiter {
+            Some(limiter) => limiter.acquire(upstream).await.map(Some),
+            None => Ok(None),
+        }
     }
 
     fn get_resolve_strategy(&self, task_notes: &ServerTaskNotes) -> ResolveStrategy {
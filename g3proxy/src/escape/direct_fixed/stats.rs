@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use arc_swap::ArcSwapOption;
@@ -24,8 +25,9 @@ use g3_types::metrics::{MetricsName, StaticMetricsTags};
 use g3_types::stats::{StatId, TcpIoSnapshot, UdpIoSnapshot};
 
 use crate::escape::{
-    EscaperForbiddenSnapshot, EscaperForbiddenStats, EscaperInterfaceStats, EscaperInternalStats,
-    EscaperStats, EscaperTcpStats, EscaperUdpStats,
+    EscaperBindIpStats, EscaperConnLimitSnapshot, EscaperConnLimitStats, EscaperForbiddenSnapshot,
+    EscaperForbiddenStats, EscaperInterfaceStats, EscaperInternalStats, EscaperStats,
+    EscaperTcpStats, EscaperUdpStats,
 };
 use crate::module::ftp_over_http::{FtpTaskRemoteControlStats, FtpTaskRemoteTransferStats};
 use crate::module::http_forward::HttpForwardTaskRemoteStats;
@@ -40,6 +42,8 @@ pub(crate) struct DirectFixedEscaperStats {
     pub(crate) interface: EscaperInterfaceStats,
     pub(crate) udp: EscaperUdpStats,
     pub(crate) tcp: EscaperTcpStats,
+    pub(crate) conn_limit: Arc<EscaperConnLimitStats>,
+    pub(crate) bind_ip: EscaperBindIpStats,
 }
 
 impl DirectFixedEscaperStats {
@@ -52,6 +56,8 @@ impl DirectFixedEscaperStats {
             interface: Default::default(),
             udp: Default::default(),
             tcp: Default::default(),
+            conn_limit: Default::default(),
+            bind_ip: Default::default(),
         }
     }
 
@@ -115,6 +121,16 @@ impl EscaperStats for DirectFixedEscaperStats {
     fn forbidden_snapshot(&self) -> Option<EscaperForbiddenSnapshot> {
         Some(self.forbidden.snapshot())
     }
+
+    #[inline]
+    fn conn_limit_snapshot(&self) -> Option<EscaperConnLimitSnapshot> {
+        Some(self.conn_limit.snapshot())
+    }
+
+    #[inline]
+    fn bind_ip_snapshot(&self) -> Option<Vec<(IpAddr, u64)>> {
+        Some(self.bind_ip.snapshot())
+    }
 }
 
 impl LimitedReaderStats for DirectFixedEscaperStats {
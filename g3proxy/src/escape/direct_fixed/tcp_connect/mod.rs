@@ -25,11 +25,14 @@ use tokio::time::Instant;
 use g3_daemon::stat::remote::ArcTcpConnectionTaskRemoteStats;
 use g3_io_ext::{LimitedReader, LimitedWriter};
 use g3_socket::util::AddressFamily;
-use g3_socket::BindAddr;
+use g3_socket::{BindAddr, RawSocket};
 use g3_types::acl::AclAction;
 use g3_types::net::{ConnectError, Host, TcpConnectConfig, TcpKeepAliveConfig, TcpMiscSockOpts};
+use g3_types::resolve::ResolveStrategy;
 
 use super::DirectFixedEscaper;
+use crate::config::auth::UserEgressBind;
+use crate::escape::UpstreamConnLimited;
 use crate::log::escape::tcp_connect::EscapeLogForTcpConnect;
 use crate::module::tcp_connect::{
     TcpConnectError, TcpConnectRemoteWrapperStats, TcpConnectResult, TcpConnectTaskNotes,
@@ -91,7 +94,32 @@ impl DirectFixedEscaper {
         self.handle_tcp_target_ip_acl_action(action, task_notes)?;
 
         if bind.is_none() {
-            bind = self.get_bind_random(AddressFamily::from(&peer_ip), task_notes.egress_path());
+            if let Some(user_ctx) = task_notes.user_ctx() {
+                match user_ctx
+                    .user_config()
+                    .egress_bind
+                    .select(AddressFamily::from(&peer_ip))
+                {
+                    UserEgressBind::Ip(ip) => bind = BindAddr::Ip(ip),
+                    UserEgressBind::Fallback => {}
+                    UserEgressBind::Mismatch => {
+                        return Err(TcpConnectError::ForbiddenAddressFamily);
+                    }
+                }
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if bind.is_none() {
+            if let Some(transparent) = self.get_bind_transparent(task_notes) {
+                bind = transparent;
+            }
+        }
+        if bind.is_none() {
+            bind = self.get_bind_addr(
+                AddressFamily::from(&peer_ip),
+                task_notes.egress_path(),
+                &peer_ip,
+            );
         }
 
         let sock = g3_socket::tcp::new_socket_to(peer_ip, &bind, keepalive, misc_opts, true)
@@ -126,18 +154,25 @@ impl DirectFixedEscaper {
         match tokio::time::timeout(tcp_connect_config.each_timeout(), sock.connect(peer)).await {
             Ok(Ok(ups_stream)) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 self.stats.tcp.add_connection_established();
                 let local_addr = ups_stream
                     .local_addr()
                     .map_err(TcpConnectError::SetupSocketFailed)?;
                 tcp_notes.local = Some(local_addr);
+                tcp_notes.raw_socket = Some(RawSocket::from(&ups_stream));
+                tcp_notes.protocol_tcp_no_delay = self.config.protocol_tcp_no_delay;
+                tcp_notes
+                    .tls_interception_client_cert
+                    .clone_from(&self.config.tls_interception_client_cert);
                 tcp_notes.chained.target_addr = Some(peer);
                 tcp_notes.chained.outgoing_addr = Some(local_addr);
                 Ok(ups_stream)
             }
             Ok(Err(e)) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 let e = TcpConnectError::ConnectFailed(ConnectError::from(e));
                 EscapeLogForTcpConnect {
@@ -149,6 +184,7 @@ impl DirectFixedEscaper {
             }
             Err(_) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 let e = TcpConnectError::TimeoutByRule;
                 EscapeLogForTcpConnect {
@@ -161,6 +197,31 @@ impl DirectFixedEscaper {
         }
     }
 
+    fn get_tcp_resolve_strategy(&self, task_notes: &ServerTaskNotes) -> ResolveStrategy {
+        // the user egress ip can only be used to connect to peers of the same family
+        if let Some(user_ctx) = task_notes.user_ctx() {
+            if let Some(family) = user_ctx.user_config().egress_bind.only_family() {
+                let mut resolve_strategy = self.get_resolve_strategy(task_notes);
+                match family {
+                    AddressFamily::Ipv4 => resolve_strategy.query_v4only(),
+                    AddressFamily::Ipv6 => resolve_strategy.query_v6only(),
+                }
+                return resolve_strategy;
+            }
+        }
+        // the client ip can only be used to connect to peers of the same family
+        #[cfg(target_os = "linux")]
+        if let Some(BindAddr::Transparent(ip)) = self.get_bind_transparent(task_notes) {
+            let mut resolve_strategy = self.get_resolve_strategy(task_notes);
+            match ip {
+                IpAddr::V4(_) => resolve_strategy.query_v4only(),
+                IpAddr::V6(_) => resolve_strategy.query_v6only(),
+            }
+            return resolve_strategy;
+        }
+        self.get_resolve_strategy(task_notes)
+    }
+
     fn merge_ip_list(&self, tried: usize, ips: &mut Vec<IpAddr>, new: Vec<IpAddr>) {
         self.config.happy_eyeballs.merge_list(tried, ips, new);
     }
@@ -216,15 +277,15 @@ impl DirectFixedEscaper {
                     tcp_notes.tries += 1;
                     self.stats.tcp.add_connection_attempted();
                     c_set.spawn(async move {
-                        match tokio::time::timeout(each_timeout, sock.connect(peer)).await {
-                            Ok(Ok(stream)) => (Ok(stream), peer, bind),
-                            Ok(Err(e)) => (
-                                Err(TcpConnectError::ConnectFailed(ConnectError::from(e))),
-                                peer,
-                                bind,
-                            ),
-                            Err(_) => (Err(TcpConnectError::TimeoutByRule), peer, bind),
-                        }
+                        let try_instant = Instant::now();
+                        let r = match tokio::time::timeout(each_timeout, sock.connect(peer)).await {
+                            Ok(Ok(stream)) => Ok(stream),
+                            Ok(Err(e)) => {
+                                Err(TcpConnectError::ConnectFailed(ConnectError::from(e)))
+                            }
+                            Err(_) => Err(TcpConnectError::TimeoutByRule),
+                        };
+                        (r, peer, bind, try_instant.elapsed())
                     });
                     connect_interval.reset();
                 }
@@ -240,6 +301,7 @@ impl DirectFixedEscaper {
                             Some(Ok(r)) => {
                                 running_connection -= 1;
                                 let peer_addr = r.1;
+                                tcp_notes.try_duration = r.3;
                                 tcp_notes.next = Some(peer_addr);
                                 tcp_notes.bind = r.2;
                                 match r.0 {
@@ -249,6 +311,11 @@ impl DirectFixedEscaper {
                                             .local_addr()
                                             .map_err(TcpConnectError::SetupSocketFailed)?;
                                         tcp_notes.local = Some(local_addr);
+                                        tcp_notes.raw_socket = Some(RawSocket::from(&ups_stream));
+                                        tcp_notes.protocol_tcp_no_delay = self.config.protocol_tcp_no_delay;
+                                        tcp_notes
+                                            .tls_interception_client_cert
+                                            .clone_from(&self.config.tls_interception_client_cert);
                                         tcp_notes.chained.target_addr = Some(peer_addr);
                                         tcp_notes.chained.outgoing_addr = Some(local_addr);
                                         return Ok(ups_stream);
@@ -356,7 +423,7 @@ impl DirectFixedEscaper {
             Host::Domain(domain) => {
                 let resolver_job = self.resolve_happy(
                     domain.clone(),
-                    self.get_resolve_strategy(task_notes),
+                    self.get_tcp_resolve_strategy(task_notes),
                     task_notes,
                 )?;
 
@@ -430,10 +497,10 @@ impl DirectFixedEscaper {
                 }
                 Host::Domain(domain) => {
                     let mut resolve_strategy = self.get_resolve_strategy(task_notes);
-                    match new_tcp_notes.bind {
-                        BindAddr::Ip(IpAddr::V4(_)) => resolve_strategy.query_v4only(),
-                        BindAddr::Ip(IpAddr::V6(_)) => resolve_strategy.query_v6only(),
-                        _ => {}
+                    match new_tcp_notes.bind.ip() {
+                        Some(IpAddr::V4(_)) => resolve_strategy.query_v4only(),
+                        Some(IpAddr::V6(_)) => resolve_strategy.query_v6only(),
+                        None => {}
                     }
 
                     let resolver_job =
@@ -458,8 +525,12 @@ impl DirectFixedEscaper {
         task_notes: &'a ServerTaskNotes,
         task_stats: ArcTcpConnectionTaskRemoteStats,
     ) -> TcpConnectResult {
+        let permit = self
+            .acquire_upstream_conn_permit(&tcp_notes.upstream)
+            .await?;
         let stream = self.tcp_connect_to(tcp_notes, task_notes).await?;
         let (r, w) = stream.into_split();
+        let r = UpstreamConnLimited::new(r, permit);
 
         let mut wrapper_stats = TcpConnectRemoteWrapperStats::new(&self.stats, task_stats);
         wrapper_stats.push_user_io_stats(self.fetch_user_upstream_io_stats(task_notes));
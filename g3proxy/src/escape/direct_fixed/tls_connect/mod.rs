@@ -27,6 +27,7 @@ use g3_openssl::{SslConnector, SslStream};
 use g3_types::net::{Host, OpensslClientConfig};
 
 use super::DirectFixedEscaper;
+use crate::escape::UpstreamConnLimited;
 use crate::log::escape::tls_handshake::{EscapeLogForTlsHandshake, TlsApplication};
 use crate::module::tcp_connect::{TcpConnectError, TcpConnectResult, TcpConnectTaskNotes};
 use crate::serve::ServerTaskNotes;
@@ -40,7 +41,11 @@ impl DirectFixedEscaper {
         tls_name: &'a Host,
         tls_application: TlsApplication,
     ) -> Result<SslStream<impl AsyncRead + AsyncWrite>, TcpConnectError> {
+        let permit = self
+            .acquire_upstream_conn_permit(&tcp_notes.upstream)
+            .await?;
         let stream = self.tcp_connect_to(tcp_notes, task_notes).await?;
+        let stream = UpstreamConnLimited::new(stream, permit);
 
         // set limit config and add escaper stats, do not count in task stats
         let limit_config = &self.config.general.tcp_sock_speed_limit;
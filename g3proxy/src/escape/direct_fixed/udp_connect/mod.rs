@@ -83,7 +83,7 @@ impl DirectFixedEscaper {
         self.handle_udp_target_ip_acl_action(action, task_notes)?;
 
         let family = AddressFamily::from(&peer_addr);
-        let bind = self.get_bind_random(family, task_notes.egress_path());
+        let bind = self.get_bind_addr(family, task_notes.egress_path(), &peer_addr.ip());
         udp_notes.bind = bind;
 
         let misc_opts = if let Some(user_ctx) = task_notes.user_ctx() {
@@ -86,7 +86,8 @@ impl DirectFixedEscaper {
         ),
         UdpRelaySetupError,
     > {
-        let bind = self.get_bind_random(family, task_notes.egress_path());
+        // the destination is not known yet, so use the client address as the key
+        let bind = self.get_bind_addr(family, task_notes.egress_path(), &task_notes.client_ip());
 
         let misc_opts = if let Some(user_ctx) = task_notes.user_ctx() {
             user_ctx
@@ -31,8 +31,8 @@ use super::DirectFloatBindIp;
 use crate::auth::UserUpstreamTrafficStats;
 use crate::escape::direct_fixed::DirectFixedEscaperStats;
 use crate::module::http_forward::{
-    send_req_header_to_origin, ArcHttpForwardTaskRemoteStats, HttpForwardRemoteWrapperStats,
-    HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
+    send_req_header_to_origin, ArcHttpForwardTaskRemoteStats, HttpForwardConnectionMeta,
+    HttpForwardRemoteWrapperStats, HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
 };
 use crate::serve::ServerTaskNotes;
 
@@ -42,6 +42,7 @@ pin_project! {
         #[pin]
         inner: W,
         escaper_stats: Option<Arc<DirectFixedEscaperStats>>,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -58,6 +59,7 @@ where
             bind,
             inner: ups_w,
             escaper_stats,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -91,7 +93,18 @@ impl<W> HttpForwardWrite for DirectFloatHttpForwardWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, _upstream: &UpstreamAddr) {}
+    fn prepare_new(
+        &mut self,
+        _task_notes: &ServerTaskNotes,
+        _upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
 
     fn update_stats(
         &mut self,
@@ -25,7 +25,7 @@ use tokio::time::Instant;
 use g3_daemon::stat::remote::ArcTcpConnectionTaskRemoteStats;
 use g3_io_ext::{LimitedReader, LimitedWriter};
 use g3_socket::util::AddressFamily;
-use g3_socket::BindAddr;
+use g3_socket::{BindAddr, RawSocket};
 use g3_types::acl::AclAction;
 use g3_types::net::{ConnectError, Host, TcpConnectConfig, TcpKeepAliveConfig, TcpMiscSockOpts};
 
@@ -138,18 +138,25 @@ impl DirectFloatEscaper {
         match tokio::time::timeout(tcp_connect_config.each_timeout(), sock.connect(peer)).await {
             Ok(Ok(ups_stream)) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 self.stats.tcp.add_connection_established();
                 let local_addr = ups_stream
                     .local_addr()
                     .map_err(TcpConnectError::SetupSocketFailed)?;
                 tcp_notes.local = Some(local_addr);
+                tcp_notes.raw_socket = Some(RawSocket::from(&ups_stream));
+                tcp_notes.protocol_tcp_no_delay = self.config.protocol_tcp_no_delay;
+                tcp_notes
+                    .tls_interception_client_cert
+                    .clone_from(&self.config.tls_interception_client_cert);
                 tcp_notes.chained.target_addr = Some(peer);
                 tcp_notes.chained.outgoing_addr = Some(local_addr);
                 Ok((ups_stream, bind))
             }
             Ok(Err(e)) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 let e = TcpConnectError::ConnectFailed(ConnectError::from(e));
                 EscapeLogForTcpConnect {
@@ -161,6 +168,7 @@ impl DirectFloatEscaper {
             }
             Err(_) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 let e = TcpConnectError::TimeoutByRule;
                 EscapeLogForTcpConnect {
@@ -228,15 +236,15 @@ impl DirectFloatEscaper {
                     tcp_notes.tries += 1;
                     self.stats.tcp.add_connection_attempted();
                     c_set.spawn(async move {
-                        match tokio::time::timeout(each_timeout, sock.connect(peer)).await {
-                            Ok(Ok(stream)) => (Ok(stream), peer, bind),
-                            Ok(Err(e)) => (
-                                Err(TcpConnectError::ConnectFailed(ConnectError::from(e))),
-                                peer,
-                                bind,
-                            ),
-                            Err(_) => (Err(TcpConnectError::TimeoutByRule), peer, bind),
-                        }
+                        let try_instant = Instant::now();
+                        let r = match tokio::time::timeout(each_timeout, sock.connect(peer)).await {
+                            Ok(Ok(stream)) => Ok(stream),
+                            Ok(Err(e)) => {
+                                Err(TcpConnectError::ConnectFailed(ConnectError::from(e)))
+                            }
+                            Err(_) => Err(TcpConnectError::TimeoutByRule),
+                        };
+                        (r, peer, bind, try_instant.elapsed())
                     });
                     connect_interval.reset();
                 }
@@ -252,6 +260,7 @@ impl DirectFloatEscaper {
                             Some(Ok(r)) => {
                                 running_connection -= 1;
                                 let peer_addr = r.1;
+                                tcp_notes.try_duration = r.3;
                                 let bind = r.2;
                                 tcp_notes.next = Some(peer_addr);
                                 tcp_notes.bind = BindAddr::Ip(bind.ip);
@@ -264,6 +273,11 @@ impl DirectFloatEscaper {
                                             .local_addr()
                                             .map_err(TcpConnectError::SetupSocketFailed)?;
                                         tcp_notes.local = Some(local_addr);
+                                        tcp_notes.raw_socket = Some(RawSocket::from(&ups_stream));
+                                        tcp_notes.protocol_tcp_no_delay = self.config.protocol_tcp_no_delay;
+                                        tcp_notes
+                                            .tls_interception_client_cert
+                                            .clone_from(&self.config.tls_interception_client_cert);
                                         tcp_notes.chained.target_addr = Some(peer_addr);
                                         tcp_notes.chained.outgoing_addr = Some(local_addr);
                                         return Ok((ups_stream, bind));
@@ -21,7 +21,9 @@ use std::sync::Arc;
 use async_trait::async_trait;
 
 use g3_daemon::stat::remote::ArcTcpConnectionTaskRemoteStats;
-use g3_types::collection::{SelectiveItem, SelectivePickPolicy, SelectiveVec};
+use g3_types::collection::{
+    SelectiveItem, SelectivePickOverride, SelectivePickPolicy, SelectiveVec,
+};
 use g3_types::metrics::MetricsName;
 use g3_types::net::{Host, HttpForwardCapability, OpensslClientConfig, UpstreamAddr};
 
@@ -48,14 +50,18 @@ pub(crate) use registry::{foreach as foreach_escaper, get_names, get_or_insert_d
 
 mod stats;
 pub(crate) use stats::{
-    ArcEscaperInternalStats, ArcEscaperStats, EscaperForbiddenSnapshot, EscaperForbiddenStats,
-    EscaperInterfaceStats, EscaperInternalStats, EscaperStats, EscaperTcpStats, EscaperUdpStats,
-    RouteEscaperSnapshot, RouteEscaperStats,
+    ArcEscaperInternalStats, ArcEscaperStats, EscaperBindIpStats, EscaperConnLimitSnapshot,
+    EscaperConnLimitStats, EscaperForbiddenSnapshot, EscaperForbiddenStats, EscaperInterfaceStats,
+    EscaperInternalStats, EscaperStats, EscaperTcpStats, EscaperUdpStats, RouteEscaperSnapshot,
+    RouteEscaperStats,
 };
 
 mod egress_path;
 pub(crate) use egress_path::EgressPathSelection;
 
+mod conn_limit;
+use conn_limit::{UpstreamConnLimited, UpstreamConnLimiter, UpstreamConnPermit};
+
 mod comply_audit;
 mod direct_fixed;
 mod direct_float;
@@ -66,6 +72,7 @@ mod proxy_http;
 mod proxy_https;
 mod proxy_socks5;
 mod proxy_socks5s;
+mod proxy_ssh;
 mod route_client;
 mod route_failover;
 mod route_geoip;
@@ -162,6 +169,9 @@ pub(crate) trait Escaper: EscaperInternal {
     fn ref_route_stats(&self) -> Option<&Arc<RouteEscaperStats>> {
         None
     }
+    fn upstream_health(&self) -> Option<serde_json::Value> {
+        None
+    }
 
     async fn publish(&self, data: String) -> anyhow::Result<()>;
 
@@ -214,6 +224,7 @@ pub(crate) trait EscaperExt: Escaper {
         &'a self,
         nodes: &'b SelectiveVec<T>,
         pick_policy: SelectivePickPolicy,
+        pick_override: Option<SelectivePickOverride>,
         task_notes: &'a ServerTaskNotes,
         host: &'a Host,
     ) -> &'b T
@@ -227,6 +238,19 @@ pub(crate) trait EscaperExt: Escaper {
             host: &'a Host,
         }
 
+        match pick_override {
+            Some(SelectivePickOverride::Pinned(index)) => return nodes.pick_pinned(index),
+            Some(SelectivePickOverride::Seeded(seed)) => {
+                let key = ConsistentKey {
+                    client_ip: task_notes.client_ip(),
+                    user: task_notes.raw_user_name().map(|s| s.as_ref()),
+                    host,
+                };
+                return nodes.pick_seeded(seed, &key);
+            }
+            None => {}
+        }
+
         match pick_policy {
             SelectivePickPolicy::Random => nodes.pick_random(),
             SelectivePickPolicy::Serial => nodes.pick_serial(),
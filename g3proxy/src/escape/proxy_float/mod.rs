@@ -196,6 +196,10 @@ impl Escaper for ProxyFloatEscaper {
         Some(self.stats.clone())
     }
 
+    fn upstream_health(&self) -> Option<serde_json::Value> {
+        Some(self.peers.load().health_json())
+    }
+
     async fn publish(&self, data: String) -> anyhow::Result<()> {
         source::publish_peers(&self.config, &self.peers, data).await
     }
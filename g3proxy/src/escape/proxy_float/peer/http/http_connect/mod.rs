@@ -43,8 +43,10 @@ impl ProxyFloatHttpPeer {
             .tcp_new_connection(self, tcp_notes, task_notes)
             .await?;
 
-        let req =
-            HttpConnectRequest::new(&tcp_notes.upstream, &self.shared_config.append_http_headers);
+        let req = HttpConnectRequest::new(
+            &tcp_notes.upstream,
+            self.shared_config.append_http_headers.lines(),
+        );
         req.send(&mut stream)
             .await
             .map_err(TcpConnectError::NegotiationWriteFailed)?;
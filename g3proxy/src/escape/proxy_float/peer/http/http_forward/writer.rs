@@ -29,10 +29,11 @@ use g3_io_ext::LimitedWriter;
 use g3_types::net::UpstreamAddr;
 
 use super::{ProxyFloatEscaperStats, ProxyFloatHttpPeerSharedConfig};
-use crate::auth::UserUpstreamTrafficStats;
+use crate::auth::{User, UserUpstreamTrafficStats};
 use crate::module::http_forward::{
     send_req_header_to_origin, send_req_header_via_proxy, ArcHttpForwardTaskRemoteStats,
-    HttpForwardRemoteWrapperStats, HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
+    HttpForwardConnectionMeta, HttpForwardRemoteWrapperStats, HttpForwardTaskRemoteWrapperStats,
+    HttpForwardWrite,
 };
 use crate::serve::ServerTaskNotes;
 
@@ -42,7 +43,9 @@ pin_project! {
         #[pin]
         inner: W,
         upstream: UpstreamAddr,
+        user: Option<Arc<User>>,
         escaper_stats: Option<Arc<ProxyFloatEscaperStats>>,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -60,7 +63,9 @@ where
             config: Arc::clone(config),
             inner: ups_w,
             upstream,
+            user: None,
             escaper_stats,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -94,8 +99,19 @@ impl<W> HttpForwardWrite for HttpPeerHttpForwardWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, upstream: &UpstreamAddr) {
+    fn prepare_new(
+        &mut self,
+        task_notes: &ServerTaskNotes,
+        upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
         self.upstream = upstream.clone();
+        self.user = task_notes.user_ctx().map(|ctx| Arc::clone(ctx.user()));
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
     }
 
     fn update_stats(
@@ -124,14 +140,15 @@ where
                 return Err(io::Error::other("connection has expired"));
             }
         }
-        send_req_header_via_proxy(
-            &mut self.inner,
-            req,
-            &self.upstream,
-            &self.config.append_http_headers,
-            None,
-        )
-        .await
+        let user_headers = self
+            .user
+            .as_ref()
+            .map(|user| user.conditional_append_headers())
+            .unwrap_or_default();
+        let header_lines = self
+            .config
+            .forward_header_lines(req, &self.upstream, user_headers);
+        send_req_header_via_proxy(&mut self.inner, req, &self.upstream, &header_lines, None).await
     }
 }
 
@@ -141,6 +158,7 @@ pin_project! {
         #[pin]
         inner: W,
         escaper_stats: Option<Arc<ProxyFloatEscaperStats>>,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -157,6 +175,7 @@ where
             config: Arc::clone(config),
             inner: ups_w,
             escaper_stats,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -190,7 +209,18 @@ impl<W> HttpForwardWrite for HttpPeerHttpRequestWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, _upstream: &UpstreamAddr) {}
+    fn prepare_new(
+        &mut self,
+        _task_notes: &ServerTaskNotes,
+        _upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
 
     fn update_stats(
         &mut self,
@@ -221,3 +251,109 @@ where
         send_req_header_to_origin(&mut self.inner, req).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use arc_swap::ArcSwapOption;
+    use http::Version;
+    use serde_json::json;
+    use tokio::io::BufReader;
+
+    use g3_daemon::server::ClientConnectionInfo;
+    use g3_io_ext::NilLimitedWriterStats;
+    use g3_types::metrics::MetricsName;
+
+    use crate::auth::{UserContext, UserType};
+    use crate::config::auth::UserConfig;
+
+    fn task_notes(user_config: Option<serde_json::Value>) -> ServerTaskNotes {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080);
+        let user_ctx = user_config.map(|v| {
+            let config = UserConfig::parse_json(v.as_object().unwrap()).unwrap();
+            UserContext::new(
+                None,
+                User::build_test_user(config),
+                UserType::Static,
+                &MetricsName::from_str("test").unwrap(),
+                &Arc::new(ArcSwapOption::empty()),
+            )
+        });
+        ServerTaskNotes::new(
+            ClientConnectionInfo::new(addr, addr),
+            user_ctx,
+            Duration::ZERO,
+        )
+    }
+
+    async fn send_header(
+        config: &Arc<ProxyFloatHttpPeerSharedConfig>,
+        task_notes: &ServerTaskNotes,
+        req: &str,
+    ) -> String {
+        let mut version = Version::HTTP_11;
+        let req = HttpProxyClientRequest::parse_basic(
+            &mut BufReader::new(req.as_bytes()),
+            4096,
+            64,
+            &mut version,
+        )
+        .await
+        .unwrap();
+        let upstream = UpstreamAddr::from_str("www.example.com:80").unwrap();
+
+        let mut buf = Vec::new();
+        let ups_w = LimitedWriter::new(&mut buf, Arc::new(NilLimitedWriterStats::default()));
+        let mut writer = HttpPeerHttpForwardWriter::new(ups_w, None, config, upstream.clone());
+        writer.prepare_new(task_notes, &upstream, false);
+        writer.send_request_header(&req).await.unwrap();
+        drop(writer);
+        String::from_utf8(buf).unwrap()
+    }
+
+    fn header_values(data: &str, name: &str) -> Vec<String> {
+        data.split("\r\n")
+            .filter_map(|line| line.split_once(": "))
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn user_conditional_headers() {
+        let mut config = ProxyFloatHttpPeerSharedConfig::default();
+        config.set_header("X-Tag", "static").unwrap();
+        config
+            .set_conditional_headers(
+                &json!({"host": ".example.com", "headers": {"X-Tag": "escaper"}}),
+            )
+            .unwrap();
+        let config = Arc::new(config);
+
+        let get = "GET http://www.example.com/api HTTP/1.1\r\nHost: www.example.com\r\n\r\n";
+        let post = "POST http://www.example.com/api HTTP/1.1\r\nHost: www.example.com\r\nContent-Length: 0\r\n\r\n";
+
+        // no user
+        let data = send_header(&config, &task_notes(None), get).await;
+        assert!(data.starts_with("GET "));
+        assert_eq!(header_values(&data, "x-tag"), ["static", "escaper"]);
+
+        let task_notes = task_notes(Some(json!({
+            "name": "u1",
+            "conditional_append_headers": [
+                {"headers": {"X-Tag": "user"}},
+                {"method": "POST", "mode": "set", "headers": {"X-Tag": "user-post"}},
+            ],
+        })));
+        let data = send_header(&config, &task_notes, get).await;
+        assert_eq!(header_values(&data, "x-tag"), ["static", "escaper", "user"]);
+
+        // the user layer overrides both the static and the escaper layers
+        let data = send_header(&config, &task_notes, post).await;
+        assert_eq!(header_values(&data, "x-tag"), ["user-post"]);
+    }
+}
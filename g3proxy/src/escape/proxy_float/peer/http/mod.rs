@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::borrow::Cow;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
@@ -24,14 +25,18 @@ use serde_json::Value;
 use tokio::time::Instant;
 
 use g3_daemon::stat::remote::ArcTcpConnectionTaskRemoteStats;
+use g3_http::server::HttpProxyClientRequest;
 use g3_types::auth::{Password, Username};
-use g3_types::net::{EgressInfo, Host, OpensslClientConfig, TcpSockSpeedLimitConfig};
+use g3_types::net::{EgressInfo, Host, OpensslClientConfig, TcpSockSpeedLimitConfig, UpstreamAddr};
 
 use super::{
     ArcNextProxyPeer, NextProxyPeer, NextProxyPeerInternal, ProxyFloatEscaper,
     ProxyFloatEscaperStats,
 };
 use crate::module::http_forward::{ArcHttpForwardTaskRemoteStats, BoxHttpForwardConnection};
+use crate::module::http_header::{
+    merge_header_lines, ConditionalAppendHeaders, StaticAppendHeaders,
+};
 use crate::module::tcp_connect::{TcpConnectError, TcpConnectResult, TcpConnectTaskNotes};
 use crate::module::udp_connect::{
     ArcUdpConnectTaskRemoteStats, UdpConnectError, UdpConnectResult, UdpConnectTaskNotes,
@@ -51,20 +56,46 @@ pub(crate) struct ProxyFloatHttpPeerSharedConfig {
     pub(crate) tcp_sock_speed_limit: TcpSockSpeedLimitConfig,
     pub(crate) expire_datetime: Option<DateTime<Utc>>,
     pub(crate) expire_instant: Option<Instant>,
-    pub(crate) append_http_headers: Vec<String>,
+    pub(crate) append_http_headers: StaticAppendHeaders,
+    conditional_append_headers: Vec<ConditionalAppendHeaders>,
 }
 
 impl ProxyFloatHttpPeerSharedConfig {
-    pub(crate) fn set_user(&mut self, username: &Username, password: &Password) {
+    pub(crate) fn set_user(
+        &mut self,
+        username: &Username,
+        password: &Password,
+    ) -> anyhow::Result<()> {
         self.append_http_headers
-            .push(g3_http::header::proxy_authorization_basic(
+            .push_line(g3_http::header::proxy_authorization_basic(
                 username, password,
-            ));
+            ))
     }
 
-    pub(crate) fn set_header(&mut self, name: &str, value: &str) {
-        self.append_http_headers
-            .push(format!("{name}: {value}\r\n"));
+    pub(crate) fn set_header(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        self.append_http_headers.push_header(name, value)
+    }
+
+    pub(crate) fn set_conditional_headers(&mut self, v: &Value) -> anyhow::Result<()> {
+        let list = ConditionalAppendHeaders::parse_json_list(v)?;
+        self.conditional_append_headers.extend(list);
+        Ok(())
+    }
+
+    /// Get the header lines that should be appended to the forward request
+    pub(crate) fn forward_header_lines(
+        &self,
+        req: &HttpProxyClientRequest,
+        upstream: &UpstreamAddr,
+        user_headers: &[ConditionalAppendHeaders],
+    ) -> Cow<'_, [String]> {
+        merge_header_lines(
+            &self.append_http_headers,
+            &[&self.conditional_append_headers, user_headers],
+            &req.method,
+            req.uri.path(),
+            upstream,
+        )
     }
 }
 
@@ -129,13 +160,19 @@ impl NextProxyPeerInternal for ProxyFloatHttpPeer {
                         let value = g3_json::value::as_ascii(value).context(format!(
                             "invalid ascii string value for extra header {name}"
                         ))?;
-                        shared_config.set_header(name, value.as_str());
+                        shared_config.set_header(name, value.as_str())?;
                     }
                     Ok(())
                 } else {
                     Err(anyhow!("invalid map value for key {k}"))
                 }
             }
+            "conditional_append_headers" => {
+                let shared_config = Arc::make_mut(&mut self.shared_config);
+                shared_config
+                    .set_conditional_headers(v)
+                    .context(format!("invalid value for key {k}"))
+            }
             _ => Ok(()),
         }
     }
@@ -143,7 +180,7 @@ impl NextProxyPeerInternal for ProxyFloatHttpPeer {
     fn finalize(&mut self) -> anyhow::Result<()> {
         let shared_config = Arc::make_mut(&mut self.shared_config);
         if !self.username.is_empty() {
-            shared_config.set_user(&self.username, &self.password);
+            shared_config.set_user(&self.username, &self.password)?;
         }
         Ok(())
     }
@@ -42,8 +42,10 @@ impl ProxyFloatHttpsPeer {
             .tls_handshake_with_peer(tcp_notes, task_notes, &self.tls_name, self)
             .await?;
 
-        let req =
-            HttpConnectRequest::new(&tcp_notes.upstream, &self.shared_config.append_http_headers);
+        let req = HttpConnectRequest::new(
+            &tcp_notes.upstream,
+            self.shared_config.append_http_headers.lines(),
+        );
         req.send(&mut stream)
             .await
             .map_err(TcpConnectError::NegotiationWriteFailed)?;
@@ -28,11 +28,11 @@ use g3_http::server::HttpProxyClientRequest;
 use g3_io_ext::LimitedWriter;
 use g3_types::net::UpstreamAddr;
 
-use crate::auth::UserUpstreamTrafficStats;
+use crate::auth::{User, UserUpstreamTrafficStats};
 use crate::escape::proxy_float::peer::http::ProxyFloatHttpPeerSharedConfig;
 use crate::module::http_forward::{
     send_req_header_to_origin, send_req_header_via_proxy, ArcHttpForwardTaskRemoteStats,
-    HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
+    HttpForwardConnectionMeta, HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
 };
 use crate::serve::ServerTaskNotes;
 
@@ -42,6 +42,9 @@ pin_project! {
         #[pin]
         inner: W,
         upstream: UpstreamAddr,
+        user: Option<Arc<User>>,
+        check_expire: bool,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -58,6 +61,9 @@ where
             config: Arc::clone(config),
             inner: ups_w,
             upstream,
+            user: None,
+            check_expire: true,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -91,8 +97,16 @@ impl<W> HttpForwardWrite for HttpsPeerHttpForwardWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, upstream: &UpstreamAddr) {
+    fn prepare_new(&mut self, task_notes: &ServerTaskNotes, upstream: &UpstreamAddr, reused: bool) {
+        self.conn_meta.mark_new_request();
         self.upstream = upstream.clone();
+        self.user = task_notes.user_ctx().map(|ctx| Arc::clone(ctx.user()));
+        // the expire time of the peer only matters when the connection is fresh
+        self.check_expire = !reused;
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
     }
 
     fn update_stats(
@@ -109,20 +123,23 @@ where
         &'a mut self,
         req: &'a HttpProxyClientRequest,
     ) -> io::Result<()> {
-        if let Some(expire) = &self.config.expire_instant {
-            let now = Instant::now();
-            if expire.checked_duration_since(now).is_none() {
-                return Err(io::Error::other("connection has expired"));
+        if self.check_expire {
+            if let Some(expire) = &self.config.expire_instant {
+                let now = Instant::now();
+                if expire.checked_duration_since(now).is_none() {
+                    return Err(io::Error::other("connection has expired"));
+                }
             }
         }
-        send_req_header_via_proxy(
-            &mut self.inner,
-            req,
-            &self.upstream,
-            &self.config.append_http_headers,
-            None,
-        )
-        .await
+        let user_headers = self
+            .user
+            .as_ref()
+            .map(|user| user.conditional_append_headers())
+            .unwrap_or_default();
+        let header_lines = self
+            .config
+            .forward_header_lines(req, &self.upstream, user_headers);
+        send_req_header_via_proxy(&mut self.inner, req, &self.upstream, &header_lines, None).await
     }
 }
 
@@ -131,6 +148,8 @@ pin_project! {
         config: Arc<ProxyFloatHttpPeerSharedConfig>,
         #[pin]
         inner: W,
+        check_expire: bool,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -142,6 +161,8 @@ where
         HttpsPeerHttpRequestWriter {
             config: Arc::clone(config),
             inner: ups_w,
+            check_expire: true,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -175,7 +196,19 @@ impl<W> HttpForwardWrite for HttpsPeerHttpRequestWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, _upstream: &UpstreamAddr) {}
+    fn prepare_new(
+        &mut self,
+        _task_notes: &ServerTaskNotes,
+        _upstream: &UpstreamAddr,
+        reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
+        self.check_expire = !reused;
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
 
     fn update_stats(
         &mut self,
@@ -191,10 +224,12 @@ where
         &'a mut self,
         req: &'a HttpProxyClientRequest,
     ) -> io::Result<()> {
-        if let Some(expire) = &self.config.expire_instant {
-            let now = Instant::now();
-            if expire.checked_duration_since(now).is_none() {
-                return Err(io::Error::other("connection has expired"));
+        if self.check_expire {
+            if let Some(expire) = &self.config.expire_instant {
+                let now = Instant::now();
+                if expire.checked_duration_since(now).is_none() {
+                    return Err(io::Error::other("connection has expired"));
+                }
             }
         }
         send_req_header_to_origin(&mut self.inner, req).await
@@ -110,13 +110,19 @@ impl NextProxyPeerInternal for ProxyFloatHttpsPeer {
                         let value = g3_json::value::as_ascii(value).context(format!(
                             "invalid ascii string value for extra header {name}"
                         ))?;
-                        shared_config.set_header(name, value.as_str());
+                        shared_config.set_header(name, value.as_str())?;
                     }
                     Ok(())
                 } else {
                     Err(anyhow!("invalid map value for key {k}"))
                 }
             }
+            "conditional_append_headers" => {
+                let shared_config = Arc::make_mut(&mut self.shared_config);
+                shared_config
+                    .set_conditional_headers(v)
+                    .context(format!("invalid value for key {k}"))
+            }
             _ => Ok(()),
         }
     }
@@ -124,7 +130,7 @@ impl NextProxyPeerInternal for ProxyFloatHttpsPeer {
     fn finalize(&mut self) -> anyhow::Result<()> {
         let shared_config = Arc::make_mut(&mut self.shared_config);
         if !self.username.is_empty() {
-            shared_config.set_user(&self.username, &self.password);
+            shared_config.set_user(&self.username, &self.password)?;
         }
         if self.tls_name.is_empty() {
             self.tls_name = Host::Ip(self.addr.ip());
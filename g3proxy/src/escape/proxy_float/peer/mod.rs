@@ -217,4 +217,40 @@ impl PeerSet {
     pub(super) fn select_named_peer(&self, id: &str) -> Option<ArcNextProxyPeer> {
         self.named.get(id).cloned()
     }
+
+    pub(super) fn health_json(&self) -> Value {
+        let mut upstreams = Vec::with_capacity(self.unnamed.len() + self.named.len());
+        for peer in &self.unnamed {
+            upstreams.push(peer_health_json(None, peer));
+        }
+        for (id, peer) in &self.named {
+            upstreams.push(peer_health_json(Some(id), peer));
+        }
+        Value::Array(upstreams)
+    }
+}
+
+fn peer_health_json(id: Option<&str>, peer: &ArcNextProxyPeer) -> Value {
+    let mut map = serde_json::Map::new();
+    if let Some(id) = id {
+        map.insert(CONFIG_KEY_PEER_ID.to_string(), Value::from(id));
+    }
+    map.insert(
+        CONFIG_KEY_PEER_ADDR.to_string(),
+        Value::String(peer.peer_addr().to_string()),
+    );
+    if let Some(expire) = peer.expire_datetime() {
+        map.insert(
+            CONFIG_KEY_PEER_EXPIRE.to_string(),
+            Value::String(expire.to_rfc3339()),
+        );
+    }
+    // there is no active probe for peers, so the health state is only based on the expire time
+    let state = if peer.is_expired() {
+        "expired"
+    } else {
+        "alive"
+    };
+    map.insert("state".to_string(), Value::from(state));
+    Value::Object(map)
 }
@@ -73,7 +73,12 @@ impl ProxyFloatSocks5Peer {
 
         let recv =
             ProxySocks5UdpConnectRemoteRecv::new(recv, ctl_stream, self.end_on_control_closed);
-        let send = ProxySocks5UdpConnectRemoteSend::new(send, upstream);
+        let send = ProxySocks5UdpConnectRemoteSend::new(
+            send,
+            upstream,
+            self.udp_max_datagram_size,
+            escaper.stats.clone(),
+        );
 
         Ok((
             Box::new(recv),
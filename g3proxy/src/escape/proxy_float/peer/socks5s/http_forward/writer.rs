@@ -31,8 +31,8 @@ use g3_types::net::UpstreamAddr;
 use super::ProxyFloatSocks5PeerSharedConfig;
 use crate::auth::UserUpstreamTrafficStats;
 use crate::module::http_forward::{
-    send_req_header_to_origin, ArcHttpForwardTaskRemoteStats, HttpForwardTaskRemoteWrapperStats,
-    HttpForwardWrite,
+    send_req_header_to_origin, ArcHttpForwardTaskRemoteStats, HttpForwardConnectionMeta,
+    HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
 };
 use crate::serve::ServerTaskNotes;
 
@@ -41,6 +41,7 @@ pin_project! {
         config: Arc<ProxyFloatSocks5PeerSharedConfig>,
         #[pin]
         inner: W,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -52,6 +53,7 @@ where
         Socks5sPeerHttpForwardWriter {
             config: Arc::clone(config),
             inner: ups_w,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -85,7 +87,18 @@ impl<W> HttpForwardWrite for Socks5sPeerHttpForwardWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, _upstream: &UpstreamAddr) {}
+    fn prepare_new(
+        &mut self,
+        _task_notes: &ServerTaskNotes,
+        _upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
 
     fn update_stats(
         &mut self,
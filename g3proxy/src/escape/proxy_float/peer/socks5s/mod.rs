@@ -33,6 +33,7 @@ use g3_types::net::{
 
 use super::socks5::ProxyFloatSocks5PeerSharedConfig;
 use super::{ArcNextProxyPeer, NextProxyPeer, NextProxyPeerInternal, ProxyFloatEscaper};
+use crate::config::escaper::DEFAULT_UDP_MAX_DATAGRAM_SIZE;
 use crate::module::http_forward::{ArcHttpForwardTaskRemoteStats, BoxHttpForwardConnection};
 use crate::module::tcp_connect::{TcpConnectError, TcpConnectResult, TcpConnectTaskNotes};
 use crate::module::udp_connect::{
@@ -58,6 +59,7 @@ pub(super) struct ProxyFloatSocks5sPeer {
     transmute_udp_peer_ip: Option<AHashMap<IpAddr, IpAddr>>,
     udp_sock_speed_limit: UdpSockSpeedLimitConfig,
     end_on_control_closed: bool,
+    udp_max_datagram_size: usize,
 }
 
 impl ProxyFloatSocks5sPeer {
@@ -72,6 +74,7 @@ impl ProxyFloatSocks5sPeer {
             transmute_udp_peer_ip: None,
             udp_sock_speed_limit: Default::default(),
             end_on_control_closed: false,
+            udp_max_datagram_size: DEFAULT_UDP_MAX_DATAGRAM_SIZE,
         })
     }
 
@@ -152,6 +155,11 @@ impl NextProxyPeerInternal for ProxyFloatSocks5sPeer {
                 self.end_on_control_closed = g3_json::value::as_bool(v)?;
                 Ok(())
             }
+            "udp_max_datagram_size" => {
+                self.udp_max_datagram_size = g3_json::humanize::as_usize(v)
+                    .context(format!("invalid humanize usize value for key {k}"))?;
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
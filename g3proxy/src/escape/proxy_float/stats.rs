@@ -66,6 +66,11 @@ impl EscaperInternalStats for ProxyFloatEscaperStats {
     fn add_https_forward_request_attempted(&self) {
         self.interface.add_https_forward_request_attempted();
     }
+
+    #[inline]
+    fn add_udp_oversized_packet_dropped(&self) {
+        self.udp.add_oversized_packet_dropped();
+    }
 }
 
 impl EscaperStats for ProxyFloatEscaperStats {
@@ -104,6 +109,10 @@ impl EscaperStats for ProxyFloatEscaperStats {
     fn udp_io_snapshot(&self) -> Option<UdpIoSnapshot> {
         Some(self.udp.io.snapshot())
     }
+
+    fn get_udp_oversized_packet_dropped(&self) -> u64 {
+        self.udp.get_oversized_packet_dropped()
+    }
 }
 
 impl LimitedReaderStats for ProxyFloatEscaperStats {
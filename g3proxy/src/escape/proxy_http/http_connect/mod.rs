@@ -46,7 +46,7 @@ impl ProxyHttpEscaper {
         let mut stream = self.tcp_new_connection(tcp_notes, task_notes).await?;
 
         let mut req =
-            HttpConnectRequest::new(&tcp_notes.upstream, &self.config.append_http_headers);
+            HttpConnectRequest::new(&tcp_notes.upstream, self.config.append_http_headers.lines());
 
         if self.config.pass_proxy_userid {
             if let Some(name) = task_notes.raw_user_name() {
@@ -28,11 +28,13 @@ use g3_io_ext::LimitedWriter;
 use g3_types::net::UpstreamAddr;
 
 use super::{ProxyHttpEscaperConfig, ProxyHttpEscaperStats};
-use crate::auth::UserUpstreamTrafficStats;
+use crate::auth::{User, UserUpstreamTrafficStats};
 use crate::module::http_forward::{
     send_req_header_to_origin, send_req_header_via_proxy, ArcHttpForwardTaskRemoteStats,
-    HttpForwardRemoteWrapperStats, HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
+    HttpForwardConnectionMeta, HttpForwardRemoteWrapperStats, HttpForwardTaskRemoteWrapperStats,
+    HttpForwardWrite,
 };
+use crate::module::http_header::merge_header_lines;
 use crate::serve::ServerTaskNotes;
 
 pin_project! {
@@ -42,7 +44,9 @@ pin_project! {
         inner: W,
         escaper_stats: Option<Arc<ProxyHttpEscaperStats>>,
         upstream: UpstreamAddr,
+        user: Option<Arc<User>>,
         pass_userid: Option<String>,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -61,7 +65,9 @@ where
             inner: ups_w,
             escaper_stats,
             upstream,
+            user: None,
             pass_userid: None,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -95,11 +101,22 @@ impl<W> HttpForwardWrite for ProxyHttpHttpForwardWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, task_notes: &ServerTaskNotes, upstream: &UpstreamAddr) {
+    fn prepare_new(
+        &mut self,
+        task_notes: &ServerTaskNotes,
+        upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
         self.upstream = upstream.clone();
+        self.user = task_notes.user_ctx().map(|ctx| Arc::clone(ctx.user()));
         self.pass_userid = task_notes.raw_user_name().map(|s| s.to_string());
     }
 
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
+
     fn update_stats(
         &mut self,
         task_stats: &ArcHttpForwardTaskRemoteStats,
@@ -121,14 +138,19 @@ where
         req: &'a HttpProxyClientRequest,
     ) -> io::Result<()> {
         let userid = self.pass_userid.as_deref();
-        send_req_header_via_proxy(
-            &mut self.inner,
-            req,
-            &self.upstream,
+        let user_headers = self
+            .user
+            .as_ref()
+            .map(|user| user.conditional_append_headers())
+            .unwrap_or_default();
+        let header_lines = merge_header_lines(
             &self.config.append_http_headers,
-            userid,
-        )
-        .await
+            &[&self.config.conditional_append_headers, user_headers],
+            &req.method,
+            req.uri.path(),
+            &self.upstream,
+        );
+        send_req_header_via_proxy(&mut self.inner, req, &self.upstream, &header_lines, userid).await
     }
 }
 
@@ -138,6 +160,7 @@ pin_project! {
         #[pin]
         inner: W,
         escaper_stats: Option<Arc<ProxyHttpEscaperStats>>,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -154,6 +177,7 @@ where
             config: Arc::clone(config),
             inner: ups_w,
             escaper_stats,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -187,7 +211,18 @@ impl<W> HttpForwardWrite for ProxyHttpHttpRequestWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, _upstream: &UpstreamAddr) {}
+    fn prepare_new(
+        &mut self,
+        _task_notes: &ServerTaskNotes,
+        _upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
 
     fn update_stats(
         &mut self,
@@ -127,6 +127,7 @@ impl ProxyHttpEscaper {
         self.select_consistent(
             &self.proxy_nodes,
             self.config.proxy_pick_policy,
+            self.config.proxy_pick_override,
             task_notes,
             target_host,
         )
@@ -93,6 +93,7 @@ impl ProxyHttpEscaper {
         {
             Ok(Ok(ups_stream)) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 self.stats.tcp.add_connection_established();
                 let local_addr = ups_stream
@@ -104,6 +105,7 @@ impl ProxyHttpEscaper {
             }
             Ok(Err(e)) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 let e = TcpConnectError::ConnectFailed(ConnectError::from(e));
                 EscapeLogForTcpConnect {
@@ -115,6 +117,7 @@ impl ProxyHttpEscaper {
             }
             Err(_) => {
                 tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
 
                 let e = TcpConnectError::TimeoutByRule;
                 EscapeLogForTcpConnect {
@@ -173,15 +176,15 @@ impl ProxyHttpEscaper {
                     tcp_notes.tries += 1;
                     self.stats.tcp.add_connection_attempted();
                     c_set.spawn(async move {
-                        match tokio::time::timeout(each_timeout, sock.connect(peer)).await {
-                            Ok(Ok(stream)) => (Ok(stream), peer, bind),
-                            Ok(Err(e)) => (
-                                Err(TcpConnectError::ConnectFailed(ConnectError::from(e))),
-                                peer,
-                                bind,
-                            ),
-                            Err(_) => (Err(TcpConnectError::TimeoutByRule), peer, bind),
-                        }
+                        let try_instant = Instant::now();
+                        let r = match tokio::time::timeout(each_timeout, sock.connect(peer)).await {
+                            Ok(Ok(stream)) => Ok(stream),
+                            Ok(Err(e)) => {
+                                Err(TcpConnectError::ConnectFailed(ConnectError::from(e)))
+                            }
+                            Err(_) => Err(TcpConnectError::TimeoutByRule),
+                        };
+                        (r, peer, bind, try_instant.elapsed())
                     });
                     connect_interval.reset();
                 }
@@ -197,6 +200,7 @@ impl ProxyHttpEscaper {
                             Some(Ok(r)) => {
                                 running_connection -= 1;
                                 let peer_addr = r.1;
+                                tcp_notes.try_duration = r.3;
                                 tcp_notes.next = Some(peer_addr);
                                 tcp_notes.bind = r.2;
                                 match r.0 {
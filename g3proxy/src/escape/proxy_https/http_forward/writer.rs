@@ -28,11 +28,12 @@ use g3_io_ext::LimitedWriter;
 use g3_types::net::UpstreamAddr;
 
 use super::ProxyHttpsEscaperConfig;
-use crate::auth::UserUpstreamTrafficStats;
+use crate::auth::{User, UserUpstreamTrafficStats};
 use crate::module::http_forward::{
     send_req_header_to_origin, send_req_header_via_proxy, ArcHttpForwardTaskRemoteStats,
-    HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
+    HttpForwardConnectionMeta, HttpForwardTaskRemoteWrapperStats, HttpForwardWrite,
 };
+use crate::module::http_header::merge_header_lines;
 use crate::serve::ServerTaskNotes;
 
 pin_project! {
@@ -41,7 +42,9 @@ pin_project! {
         #[pin]
         inner: W,
         upstream: UpstreamAddr,
+        user: Option<Arc<User>>,
         pass_userid: Option<String>,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -58,7 +61,9 @@ where
             config: Arc::clone(config),
             inner: ups_w,
             upstream,
+            user: None,
             pass_userid: None,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -92,11 +97,22 @@ impl<W> HttpForwardWrite for ProxyHttpsHttpForwardWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, task_notes: &ServerTaskNotes, upstream: &UpstreamAddr) {
+    fn prepare_new(
+        &mut self,
+        task_notes: &ServerTaskNotes,
+        upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
         self.upstream = upstream.clone();
+        self.user = task_notes.user_ctx().map(|ctx| Arc::clone(ctx.user()));
         self.pass_userid = task_notes.raw_user_name().map(|s| s.to_string());
     }
 
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
+
     fn update_stats(
         &mut self,
         task_stats: &ArcHttpForwardTaskRemoteStats,
@@ -112,14 +128,19 @@ where
         req: &'a HttpProxyClientRequest,
     ) -> io::Result<()> {
         let userid = self.pass_userid.as_deref();
-        send_req_header_via_proxy(
-            &mut self.inner,
-            req,
-            &self.upstream,
+        let user_headers = self
+            .user
+            .as_ref()
+            .map(|user| user.conditional_append_headers())
+            .unwrap_or_default();
+        let header_lines = merge_header_lines(
             &self.config.append_http_headers,
-            userid,
-        )
-        .await
+            &[&self.config.conditional_append_headers, user_headers],
+            &req.method,
+            req.uri.path(),
+            &self.upstream,
+        );
+        send_req_header_via_proxy(&mut self.inner, req, &self.upstream, &header_lines, userid).await
     }
 }
 
@@ -128,6 +149,7 @@ pin_project! {
         config: Arc<ProxyHttpsEscaperConfig>,
         #[pin]
         inner: W,
+        conn_meta: HttpForwardConnectionMeta,
     }
 }
 
@@ -139,6 +161,7 @@ where
         ProxyHttpsHttpRequestWriter {
             config: Arc::clone(config),
             inner: ups_w,
+            conn_meta: HttpForwardConnectionMeta::new(),
         }
     }
 }
@@ -172,7 +195,18 @@ impl<W> HttpForwardWrite for ProxyHttpsHttpRequestWriter<LimitedWriter<W>>
 where
     W: AsyncWrite + Send + Unpin,
 {
-    fn prepare_new(&mut self, _task_notes: &ServerTaskNotes, _upstream: &UpstreamAddr) {}
+    fn prepare_new(
+        &mut self,
+        _task_notes: &ServerTaskNotes,
+        _upstream: &UpstreamAddr,
+        _reused: bool,
+    ) {
+        self.conn_meta.mark_new_request();
+    }
+
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta {
+        &self.conn_meta
+    }
 
     fn update_stats(
         &mut self,
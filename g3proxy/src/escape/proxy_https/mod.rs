@@ -132,6 +132,7 @@ impl ProxyHttpsEscaper {
         self.select_consistent(
             &self.proxy_nodes,
             self.config.proxy_pick_policy,
+            self.config.proxy_pick_override,
             task_notes,
             target_host,
         )
@@ -66,6 +66,16 @@ impl EscaperInternalStats for ProxySocks5EscaperStats {
     fn add_https_forward_request_attempted(&self) {
         self.interface.add_https_forward_request_attempted();
     }
+
+    #[inline]
+    fn add_udp_oversized_packet_dropped(&self) {
+        self.udp.add_oversized_packet_dropped();
+    }
+
+    #[inline]
+    fn add_udp_unexpected_source_dropped(&self) {
+        self.udp.add_unexpected_source_dropped();
+    }
 }
 
 impl EscaperStats for ProxySocks5EscaperStats {
@@ -104,6 +114,14 @@ impl EscaperStats for ProxySocks5EscaperStats {
     fn udp_io_snapshot(&self) -> Option<UdpIoSnapshot> {
         Some(self.udp.io.snapshot())
     }
+
+    fn get_udp_oversized_packet_dropped(&self) -> u64 {
+        self.udp.get_oversized_packet_dropped()
+    }
+
+    fn get_udp_unexpected_source_dropped(&self) -> u64 {
+        self.udp.get_unexpected_source_dropped()
+    }
 }
 
 impl LimitedReaderStats for ProxySocks5EscaperStats {
@@ -99,7 +99,7 @@ where
 
         let nr = ready!(self.inner.poll_recv(cx, buf)).map_err(UdpCopyRemoteError::RecvFailed)?;
 
-        let (off, _upstream) = UdpInput::parse_header(buf)
+        let off = UdpInput::parse_header_len(&buf[..nr])
             .map_err(|e| UdpCopyRemoteError::InvalidPacket(e.to_string()))?;
 
         self.end_on_control_closed = true;
@@ -134,7 +134,7 @@ where
         let mut r = Vec::with_capacity(count);
         for h in hdr_v.into_iter().take(count) {
             let iov = &h.iov[0];
-            let (off, _upstream) = UdpInput::parse_header(&iov[0..h.n_recv])
+            let off = UdpInput::parse_header_len(&iov[0..h.n_recv])
                 .map_err(|e| UdpCopyRemoteError::InvalidPacket(e.to_string()))?;
             r.push(UdpCopyPacketMeta::new(iov, off, h.n_recv));
         }
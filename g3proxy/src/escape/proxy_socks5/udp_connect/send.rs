@@ -16,6 +16,9 @@
 
 use std::io::{self, IoSlice};
 use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use log::warn;
 
 use g3_io_ext::{AsyncUdpSend, UdpCopyRemoteError, UdpCopyRemoteSend};
 #[cfg(any(
@@ -30,24 +33,95 @@ use g3_io_ext::{SendMsgHdr, UdpCopyPacket};
 use g3_socks::v5::UdpOutput;
 use g3_types::net::UpstreamAddr;
 
+use crate::escape::ArcEscaperInternalStats;
+
+const OVERSIZED_DROP_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
 pub(crate) struct ProxySocks5UdpConnectRemoteSend<T> {
     inner: T,
     socks5_header: Vec<u8>,
+    max_datagram_size: usize,
+    escaper_stats: ArcEscaperInternalStats,
+    oversized_dropped: u64,
+    oversized_logged_at: Option<Instant>,
 }
 
 impl<T> ProxySocks5UdpConnectRemoteSend<T>
 where
     T: AsyncUdpSend,
 {
-    pub(crate) fn new(send: T, upstream: &UpstreamAddr) -> Self {
+    pub(crate) fn new(
+        send: T,
+        upstream: &UpstreamAddr,
+        max_datagram_size: usize,
+        escaper_stats: ArcEscaperInternalStats,
+    ) -> Self {
         let header_len = UdpOutput::calc_header_len(upstream);
         let mut socks5_header = vec![0; header_len];
         UdpOutput::generate_header(&mut socks5_header, upstream);
         ProxySocks5UdpConnectRemoteSend {
             inner: send,
             socks5_header,
+            max_datagram_size,
+            escaper_stats,
+            oversized_dropped: 0,
+            oversized_logged_at: None,
         }
     }
+
+    #[inline]
+    fn is_oversized(&self, payload_len: usize) -> bool {
+        self.socks5_header.len() + payload_len > self.max_datagram_size
+    }
+
+    fn check_oversized(&mut self, payload_len: usize) -> bool {
+        if !self.is_oversized(payload_len) {
+            return false;
+        }
+
+        self.escaper_stats.add_udp_oversized_packet_dropped();
+        self.oversized_dropped += 1;
+        let now = Instant::now();
+        let log_now = self
+            .oversized_logged_at
+            .map(|t| now.duration_since(t) >= OVERSIZED_DROP_LOG_INTERVAL)
+            .unwrap_or(true);
+        if log_now {
+            warn!(
+                "dropped {} oversized udp packet(s), the last one has size {} with socks5 header, the max size is {}",
+                self.oversized_dropped,
+                self.socks5_header.len() + payload_len,
+                self.max_datagram_size
+            );
+            self.oversized_dropped = 0;
+            self.oversized_logged_at = Some(now);
+        }
+        true
+    }
+
+    /// get the count of leading packets that can be sent in a batch,
+    /// or return None if the first packet is oversized and has been dropped.
+    /// The socks5 udp header is counted in the size of each packet
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "macos",
+    ))]
+    fn batch_send_count<I>(&mut self, payload_sizes: I) -> Option<usize>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut sizes = payload_sizes.into_iter();
+        let first = sizes.next()?;
+        if self.check_oversized(first) {
+            return None;
+        }
+        let count = 1 + sizes.take_while(|len| !self.is_oversized(*len)).count();
+        Some(count)
+    }
 }
 
 impl<T> UdpCopyRemoteSend for ProxySocks5UdpConnectRemoteSend<T>
@@ -59,6 +133,11 @@ where
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<Result<usize, UdpCopyRemoteError>> {
+        if self.check_oversized(buf.len()) {
+            // drop it silently, just like it's lost on the way
+            return Poll::Ready(Ok(buf.len()));
+        }
+
         let nw = ready!(self.inner.poll_sendmsg(
             cx,
             &[IoSlice::new(&self.socks5_header), IoSlice::new(buf)],
@@ -87,7 +166,10 @@ where
         cx: &mut Context<'_>,
         packets: &[UdpCopyPacket],
     ) -> Poll<Result<usize, UdpCopyRemoteError>> {
-        let mut msgs: Vec<SendMsgHdr<2>> = packets
+        let Some(count) = self.batch_send_count(packets.iter().map(|p| p.payload().len())) else {
+            return Poll::Ready(Ok(1));
+        };
+        let mut msgs: Vec<SendMsgHdr<2>> = packets[..count]
             .iter()
             .map(|p| {
                 SendMsgHdr::new(
@@ -115,7 +197,10 @@ where
         cx: &mut Context<'_>,
         packets: &[UdpCopyPacket],
     ) -> Poll<Result<usize, UdpCopyRemoteError>> {
-        let mut msgs: Vec<SendMsgHdr<2>> = packets
+        let Some(count) = self.batch_send_count(packets.iter().map(|p| p.payload().len())) else {
+            return Poll::Ready(Ok(1));
+        };
+        let mut msgs: Vec<SendMsgHdr<2>> = packets[..count]
             .iter()
             .map(|p| {
                 SendMsgHdr::new(
@@ -137,3 +222,158 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use g3_types::metrics::MetricsName;
+
+    use crate::escape::proxy_socks5::ProxySocks5EscaperStats;
+    use crate::escape::EscaperStats;
+
+    #[derive(Default)]
+    struct RecordUdpSend {
+        sent: Vec<Vec<u8>>,
+    }
+
+    impl AsyncUdpSend for RecordUdpSend {
+        fn poll_send_to(
+            &mut self,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+            _target: SocketAddr,
+        ) -> Poll<io::Result<usize>> {
+            self.poll_send(cx, buf)
+        }
+
+        fn poll_send(&mut self, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            self.sent.push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_sendmsg(
+            &mut self,
+            _cx: &mut Context<'_>,
+            iov: &[IoSlice<'_>],
+            _target: Option<SocketAddr>,
+        ) -> Poll<io::Result<usize>> {
+            let buf = iov
+                .iter()
+                .flat_map(|v| v.iter().copied())
+                .collect::<Vec<_>>();
+            let len = buf.len();
+            self.sent.push(buf);
+            Poll::Ready(Ok(len))
+        }
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+        ))]
+        fn poll_batch_sendmsg<const C: usize>(
+            &mut self,
+            cx: &mut Context<'_>,
+            msgs: &mut [SendMsgHdr<'_, C>],
+        ) -> Poll<io::Result<usize>> {
+            for msg in msgs.iter_mut() {
+                match self.poll_sendmsg(cx, msg.as_ref(), None) {
+                    Poll::Ready(Ok(n)) => msg.n_send = n,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            Poll::Ready(Ok(msgs.len()))
+        }
+
+        #[cfg(target_os = "macos")]
+        fn poll_batch_sendmsg_x<const C: usize>(
+            &mut self,
+            cx: &mut Context<'_>,
+            msgs: &mut [SendMsgHdr<'_, C>],
+        ) -> Poll<io::Result<usize>> {
+            for msg in msgs.iter_mut() {
+                match self.poll_sendmsg(cx, msg.as_ref(), None) {
+                    Poll::Ready(Ok(n)) => msg.n_send = n,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            Poll::Ready(Ok(msgs.len()))
+        }
+    }
+
+    fn build_send(
+        max_datagram_size: usize,
+    ) -> (
+        ProxySocks5UdpConnectRemoteSend<RecordUdpSend>,
+        Arc<ProxySocks5EscaperStats>,
+        usize,
+    ) {
+        let stats = Arc::new(ProxySocks5EscaperStats::new(&MetricsName::default()));
+        let upstream = UpstreamAddr::from_ip_and_port("192.0.2.1".parse().unwrap(), 53);
+        let header_len = UdpOutput::calc_header_len(&upstream);
+        let send = ProxySocks5UdpConnectRemoteSend::new(
+            RecordUdpSend::default(),
+            &upstream,
+            max_datagram_size,
+            stats.clone(),
+        );
+        (send, stats, header_len)
+    }
+
+    #[test]
+    fn drop_oversized() {
+        let (mut send, stats, header_len) = build_send(100);
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+
+        let payload = vec![0u8; 100 - header_len];
+        let Poll::Ready(Ok(nw)) = send.poll_send_packet(&mut cx, &payload) else {
+            panic!("send failed");
+        };
+        assert_eq!(nw, 100);
+        assert_eq!(send.inner.sent.len(), 1);
+        assert_eq!(stats.get_udp_oversized_packet_dropped(), 0);
+
+        // the payload fits the limit, but not with the socks5 header
+        let payload = vec![0u8; 100 - header_len + 1];
+        let Poll::Ready(Ok(nw)) = send.poll_send_packet(&mut cx, &payload) else {
+            panic!("send failed");
+        };
+        assert_eq!(nw, payload.len());
+        assert_eq!(send.inner.sent.len(), 1);
+        assert_eq!(stats.get_udp_oversized_packet_dropped(), 1);
+
+        let Poll::Ready(Ok(_)) = send.poll_send_packet(&mut cx, &payload) else {
+            panic!("send failed");
+        };
+        assert_eq!(stats.get_udp_oversized_packet_dropped(), 2);
+        // only the first drop is logged within the log interval
+        assert_eq!(send.oversized_dropped, 1);
+    }
+
+    #[test]
+    fn batch_cut_off() {
+        let (mut send, stats, header_len) = build_send(100);
+        let max_payload = 100 - header_len;
+
+        assert_eq!(send.batch_send_count([]), None);
+        assert_eq!(
+            send.batch_send_count([max_payload, 10, max_payload]),
+            Some(3)
+        );
+        assert_eq!(
+            send.batch_send_count([10, max_payload, max_payload + 1, 10]),
+            Some(2)
+        );
+        assert_eq!(stats.get_udp_oversized_packet_dropped(), 0);
+
+        assert_eq!(send.batch_send_count([max_payload + 1, 10]), None);
+        assert_eq!(stats.get_udp_oversized_packet_dropped(), 1);
+    }
+}
@@ -65,13 +65,16 @@ impl ProxySocks5Escaper {
             wrapper_stats,
         );
 
-        let recv = ProxySocks5UdpRelayRemoteRecv::new(
+        let mut recv = ProxySocks5UdpRelayRemoteRecv::new(
             recv,
             udp_local_addr,
             udp_peer_addr,
             ctl_stream,
             self.config.end_on_control_closed,
         );
+        if self.config.udp_peer_source_check {
+            recv.set_peer_source_check(self.stats.clone());
+        }
         let send = ProxySocks5UdpRelayRemoteSend::new(send, udp_local_addr, udp_peer_addr);
 
         Ok((Box::new(recv), Box::new(send), self.escape_logger.clone()))
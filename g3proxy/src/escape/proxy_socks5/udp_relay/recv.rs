@@ -19,6 +19,7 @@ use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{ready, Context, Poll};
 
+use log::debug;
 use tokio::io::{AsyncRead, ReadBuf};
 
 use g3_io_ext::{AsyncUdpRecv, UdpRelayRemoteError, UdpRelayRemoteRecv};
@@ -34,6 +35,8 @@ use g3_io_ext::{RecvMsgHdr, UdpRelayPacket, UdpRelayPacketMeta};
 use g3_socks::v5::UdpInput;
 use g3_types::net::UpstreamAddr;
 
+use crate::escape::ArcEscaperInternalStats;
+
 pub(crate) struct ProxySocks5UdpRelayRemoteRecv<T, C> {
     local_addr: SocketAddr,
     peer_addr: SocketAddr,
@@ -41,6 +44,7 @@ pub(crate) struct ProxySocks5UdpRelayRemoteRecv<T, C> {
     ctl_stream: C,
     end_on_control_closed: bool,
     ignore_ctl_stream: bool,
+    peer_source_check: Option<ArcEscaperInternalStats>,
 }
 
 impl<T, C> ProxySocks5UdpRelayRemoteRecv<T, C>
@@ -62,7 +66,34 @@ where
             ctl_stream,
             end_on_control_closed,
             ignore_ctl_stream: false,
+            peer_source_check: None,
+        }
+    }
+
+    /// Drop the packets that are not sent from the peer udp address
+    pub(crate) fn set_peer_source_check(&mut self, escaper_stats: ArcEscaperInternalStats) {
+        self.peer_source_check = Some(escaper_stats);
+    }
+
+    fn check_peer_source(&self, source: Option<SocketAddr>) -> bool {
+        let Some(escaper_stats) = &self.peer_source_check else {
+            return true;
+        };
+        if source == Some(self.peer_addr) {
+            return true;
         }
+        escaper_stats.add_udp_unexpected_source_dropped();
+        match source {
+            Some(addr) => debug!(
+                "dropped udp packet from unexpected source {addr}, the peer address is {}",
+                self.peer_addr
+            ),
+            None => debug!(
+                "dropped udp packet from unknown source, the peer address is {}",
+                self.peer_addr
+            ),
+        }
+        false
     }
 
     fn check_tcp_close(&mut self, cx: &mut Context<'_>) -> Result<(), UdpRelayRemoteError> {
@@ -98,6 +129,61 @@ where
             )),
         }
     }
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "macos",
+    ))]
+    fn poll_recv_packets_checked(
+        &mut self,
+        cx: &mut Context<'_>,
+        packets: &mut [UdpRelayPacket],
+    ) -> Poll<Result<usize, UdpRelayRemoteError>> {
+        loop {
+            let mut hdr_v: Vec<RecvMsgHdr<1>> = packets
+                .iter_mut()
+                .map(|p| RecvMsgHdr::new([io::IoSliceMut::new(p.buf_mut())]))
+                .collect();
+
+            let count = ready!(self.inner.poll_batch_recvmsg(cx, &mut hdr_v))
+                .map_err(|e| UdpRelayRemoteError::RecvFailed(self.local_addr, e))?;
+            if count == 0 {
+                return Poll::Ready(Ok(0));
+            }
+
+            let mut r = Vec::with_capacity(count);
+            for h in hdr_v.into_iter().take(count) {
+                if !self.check_peer_source(h.addr()) {
+                    r.push(None);
+                    continue;
+                }
+                let iov = &h.iov[0];
+                let (off, ups) = UdpInput::parse_header(&iov[0..h.n_recv]).map_err(|e| {
+                    UdpRelayRemoteError::InvalidPacket(self.local_addr, e.to_string())
+                })?;
+                r.push(Some(UdpRelayPacketMeta::new(iov, off, h.n_recv, ups)));
+            }
+
+            // move the valid packets to the front, the meta should be set before moving,
+            // as it is bound to the buffer of the packet
+            let mut valid = 0;
+            for (i, m) in r.into_iter().enumerate() {
+                if let Some(m) = m {
+                    m.set_packet(&mut packets[i]);
+                    packets.swap(valid, i);
+                    valid += 1;
+                }
+            }
+            if valid > 0 {
+                self.end_on_control_closed = true;
+                return Poll::Ready(Ok(valid));
+            }
+        }
+    }
 }
 
 impl<T, C> UdpRelayRemoteRecv for ProxySocks5UdpRelayRemoteRecv<T, C>
@@ -118,8 +204,18 @@ where
             self.check_tcp_close(cx)?;
         }
 
-        let nr = ready!(self.inner.poll_recv(cx, buf))
-            .map_err(|e| UdpRelayRemoteError::RecvFailed(self.local_addr, e))?;
+        let nr = if self.peer_source_check.is_some() {
+            loop {
+                let (nr, source) = ready!(self.inner.poll_recv_from(cx, buf))
+                    .map_err(|e| UdpRelayRemoteError::RecvFailed(self.local_addr, e))?;
+                if self.check_peer_source(Some(source)) {
+                    break nr;
+                }
+            }
+        } else {
+            ready!(self.inner.poll_recv(cx, buf))
+                .map_err(|e| UdpRelayRemoteError::RecvFailed(self.local_addr, e))?
+        };
 
         let (off, upstream) = UdpInput::parse_header(buf)
             .map_err(|e| UdpRelayRemoteError::InvalidPacket(self.local_addr, e.to_string()))?;
@@ -145,6 +241,10 @@ where
             self.check_tcp_close(cx)?;
         }
 
+        if self.peer_source_check.is_some() {
+            return self.poll_recv_packets_checked(cx, packets);
+        }
+
         let mut hdr_v: Vec<RecvMsgHdr<1>> = packets
             .iter_mut()
             .map(|p| RecvMsgHdr::new([io::IoSliceMut::new(p.buf_mut())]))
@@ -136,6 +136,7 @@ impl ProxySocks5sEscaper {
         self.select_consistent(
             &self.proxy_nodes,
             self.config.proxy_pick_policy,
+            self.config.proxy_pick_override,
             task_notes,
             target_host,
         )
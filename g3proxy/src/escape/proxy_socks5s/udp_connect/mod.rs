@@ -75,7 +75,12 @@ impl ProxySocks5sEscaper {
             ctl_stream,
             self.config.end_on_control_closed,
         );
-        let send = ProxySocks5UdpConnectRemoteSend::new(send, upstream);
+        let send = ProxySocks5UdpConnectRemoteSend::new(
+            send,
+            upstream,
+            self.config.udp_max_datagram_size,
+            self.stats.clone(),
+        );
 
         Ok((Box::new(recv), Box::new(send), self.escape_logger.clone()))
     }
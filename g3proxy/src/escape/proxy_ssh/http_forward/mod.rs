@@ -0,0 +1,94 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use g3_io_ext::{AsyncStream, LimitedBufReader, LimitedWriter, NilLimitedReaderStats};
+use g3_types::net::{Host, OpensslClientConfig};
+
+use super::{ProxySshEscaper, ProxySshEscaperStats};
+use crate::escape::direct_fixed::http_forward::{DirectHttpForwardReader, DirectHttpForwardWriter};
+use crate::log::escape::tls_handshake::TlsApplication;
+use crate::module::http_forward::{
+    ArcHttpForwardTaskRemoteStats, BoxHttpForwardConnection, HttpForwardTaskRemoteWrapperStats,
+};
+use crate::module::tcp_connect::{TcpConnectError, TcpConnectTaskNotes};
+use crate::serve::ServerTaskNotes;
+
+impl ProxySshEscaper {
+    pub(super) async fn http_forward_new_connection<'a>(
+        &'a self,
+        tcp_notes: &'a mut TcpConnectTaskNotes,
+        task_notes: &'a ServerTaskNotes,
+        task_stats: ArcHttpForwardTaskRemoteStats,
+    ) -> Result<BoxHttpForwardConnection, TcpConnectError> {
+        let tunnel = self.ssh_connect_to(tcp_notes, task_notes).await?;
+        let (ups_r, ups_w) = tokio::io::split(tunnel);
+
+        // add task and user stats
+        let mut wrapper_stats = HttpForwardTaskRemoteWrapperStats::new(task_stats);
+        wrapper_stats.push_user_io_stats(self.fetch_user_upstream_io_stats(task_notes));
+        let wrapper_stats = Arc::new(wrapper_stats);
+
+        let ups_r = LimitedBufReader::new_unlimited(
+            ups_r,
+            Arc::new(NilLimitedReaderStats::default()),
+            wrapper_stats.clone(),
+        );
+        let ups_w = LimitedWriter::new(ups_w, wrapper_stats);
+
+        let writer = DirectHttpForwardWriter::new(ups_w, Some(Arc::clone(&self.stats)));
+        let reader = DirectHttpForwardReader::new(ups_r);
+        Ok((Box::new(writer), Box::new(reader)))
+    }
+
+    pub(super) async fn https_forward_new_connection<'a>(
+        &'a self,
+        tcp_notes: &'a mut TcpConnectTaskNotes,
+        task_notes: &'a ServerTaskNotes,
+        task_stats: ArcHttpForwardTaskRemoteStats,
+        tls_config: &'a OpensslClientConfig,
+        tls_name: &'a Host,
+    ) -> Result<BoxHttpForwardConnection, TcpConnectError> {
+        let tls_stream = self
+            .tls_connect_to(
+                tcp_notes,
+                task_notes,
+                tls_config,
+                tls_name,
+                TlsApplication::HttpForward,
+            )
+            .await?;
+
+        let (ups_r, ups_w) = tls_stream.into_split();
+
+        // add task and user stats
+        let mut wrapper_stats = HttpForwardTaskRemoteWrapperStats::new(task_stats);
+        wrapper_stats.push_user_io_stats(self.fetch_user_upstream_io_stats(task_notes));
+        let wrapper_stats = Arc::new(wrapper_stats);
+
+        let ups_r = LimitedBufReader::new_unlimited(
+            ups_r,
+            Arc::new(NilLimitedReaderStats::default()),
+            wrapper_stats.clone(),
+        );
+        let ups_w = LimitedWriter::new(ups_w, wrapper_stats);
+
+        let writer = DirectHttpForwardWriter::<_, ProxySshEscaperStats>::new(ups_w, None);
+        let reader = DirectHttpForwardReader::new(ups_r);
+        Ok((Box::new(writer), Box::new(reader)))
+    }
+}
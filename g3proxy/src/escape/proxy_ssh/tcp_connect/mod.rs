@@ -0,0 +1,488 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use russh::keys::PrivateKeyWithHashAlg;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use g3_daemon::stat::remote::{
+    ArcTcpConnectionTaskRemoteStats, TcpConnectionTaskRemoteStatsWrapper,
+};
+use g3_io_ext::{LimitedReader, LimitedStream, LimitedWriter};
+use g3_socket::BindAddr;
+use g3_types::net::{ConnectError, Host, UpstreamAddr};
+
+use super::tunnel::map_ssh_error;
+use super::{ProxySshEscaper, SshClientHandler, SshSession, SshTunnelStream};
+use crate::log::escape::tcp_connect::EscapeLogForTcpConnect;
+use crate::module::tcp_connect::{TcpConnectError, TcpConnectResult, TcpConnectTaskNotes};
+use crate::resolve::HappyEyeballsResolveJob;
+use crate::serve::ServerTaskNotes;
+
+impl ProxySshEscaper {
+    fn prepare_connect_socket(
+        &self,
+        peer_ip: IpAddr,
+    ) -> Result<(TcpSocket, BindAddr), TcpConnectError> {
+        let bind_ip = match peer_ip {
+            IpAddr::V4(_) => {
+                if self.config.no_ipv4 {
+                    return Err(TcpConnectError::ForbiddenAddressFamily);
+                }
+                self.config.bind_v4.map(IpAddr::V4)
+            }
+            IpAddr::V6(_) => {
+                if self.config.no_ipv6 {
+                    return Err(TcpConnectError::ForbiddenAddressFamily);
+                }
+                self.config.bind_v6.map(IpAddr::V6)
+            }
+        };
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let bind = bind_ip.map(BindAddr::Ip).unwrap_or_else(|| {
+            self.config
+                .bind_interface
+                .map(BindAddr::Interface)
+                .unwrap_or_default()
+        });
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let bind = bind_ip.map(BindAddr::Ip).unwrap_or_default();
+        let sock = g3_socket::tcp::new_socket_to(
+            peer_ip,
+            &bind,
+            &self.config.tcp_keepalive,
+            &self.config.tcp_misc_opts,
+            true,
+        )
+        .map_err(TcpConnectError::SetupSocketFailed)?;
+        Ok((sock, bind))
+    }
+
+    async fn fixed_try_connect(
+        &self,
+        peer: SocketAddr,
+        tcp_notes: &mut TcpConnectTaskNotes,
+        task_notes: &ServerTaskNotes,
+    ) -> Result<TcpStream, TcpConnectError> {
+        let (sock, bind) = self.prepare_connect_socket(peer.ip())?;
+        tcp_notes.next = Some(peer);
+        tcp_notes.bind = bind;
+
+        let instant_now = Instant::now();
+
+        self.stats.tcp.add_connection_attempted();
+        tcp_notes.tries = 1;
+        match tokio::time::timeout(
+            self.config.general.tcp_connect.each_timeout(),
+            sock.connect(peer),
+        )
+        .await
+        {
+            Ok(Ok(ups_stream)) => {
+                tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
+
+                self.stats.tcp.add_connection_established();
+                let local_addr = ups_stream
+                    .local_addr()
+                    .map_err(TcpConnectError::SetupSocketFailed)?;
+                tcp_notes.local = Some(local_addr);
+                // the chained outgoing addr is not detected at here
+                Ok(ups_stream)
+            }
+            Ok(Err(e)) => {
+                tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
+
+                let e = TcpConnectError::ConnectFailed(ConnectError::from(e));
+                EscapeLogForTcpConnect {
+                    tcp_notes,
+                    task_id: &task_notes.id,
+                }
+                .log(&self.escape_logger, &e);
+                Err(e)
+            }
+            Err(_) => {
+                tcp_notes.duration = instant_now.elapsed();
+                tcp_notes.try_duration = tcp_notes.duration;
+
+                let e = TcpConnectError::TimeoutByRule;
+                EscapeLogForTcpConnect {
+                    tcp_notes,
+                    task_id: &task_notes.id,
+                }
+                .log(&self.escape_logger, &e);
+                Err(e)
+            }
+        }
+    }
+
+    fn merge_ip_list(&self, tried: usize, ips: &mut Vec<IpAddr>, new: Vec<IpAddr>) {
+        self.config.happy_eyeballs.merge_list(tried, ips, new);
+    }
+
+    async fn happy_try_connect(
+        &self,
+        mut resolver_job: HappyEyeballsResolveJob,
+        peer_port: u16,
+        tcp_notes: &mut TcpConnectTaskNotes,
+        task_notes: &ServerTaskNotes,
+    ) -> Result<TcpStream, TcpConnectError> {
+        let max_tries_each_family = self.config.general.tcp_connect.max_tries();
+        let mut ips = resolver_job
+            .get_r1_or_first(
+                self.config.happy_eyeballs.resolution_delay(),
+                max_tries_each_family,
+            )
+            .await?;
+
+        let mut c_set = JoinSet::new();
+
+        let mut connect_interval =
+            tokio::time::interval(self.config.happy_eyeballs.connection_attempt_delay());
+        // connect_interval.tick().await; will take 1ms
+        // let's use local vars to skip the first tick()
+        let mut skip_first_tick = true;
+
+        let mut spawn_new_connection = true;
+        let mut running_connection = 0;
+        let mut resolver_r2_done = false;
+        let each_timeout = self.config.general.tcp_connect.each_timeout();
+
+        tcp_notes.tries = 0;
+        let instant_now = Instant::now();
+        let mut returned_err = TcpConnectError::NoAddressConnected;
+
+        loop {
+            if spawn_new_connection {
+                if let Some(ip) = ips.pop() {
+                    let (sock, bind) = self.prepare_connect_socket(ip)?;
+                    let peer = SocketAddr::new(ip, peer_port);
+                    running_connection += 1;
+                    spawn_new_connection = false;
+                    tcp_notes.tries += 1;
+                    self.stats.tcp.add_connection_attempted();
+                    c_set.spawn(async move {
+                        let try_instant = Instant::now();
+                        let r = match tokio::time::timeout(each_timeout, sock.connect(peer)).await {
+                            Ok(Ok(stream)) => Ok(stream),
+                            Ok(Err(e)) => {
+                                Err(TcpConnectError::ConnectFailed(ConnectError::from(e)))
+                            }
+                            Err(_) => Err(TcpConnectError::TimeoutByRule),
+                        };
+                        (r, peer, bind, try_instant.elapsed())
+                    });
+                    connect_interval.reset();
+                }
+            }
+
+            if running_connection > 0 {
+                tokio::select! {
+                    biased;
+
+                    r = c_set.join_next() => {
+                        tcp_notes.duration = instant_now.elapsed();
+                        match r {
+                            Some(Ok(r)) => {
+                                running_connection -= 1;
+                                let peer_addr = r.1;
+                                tcp_notes.try_duration = r.3;
+                                tcp_notes.next = Some(peer_addr);
+                                tcp_notes.bind = r.2;
+                                match r.0 {
+                                    Ok(ups_stream) => {
+                                        self.stats.tcp.add_connection_established();
+                                        let local_addr = ups_stream
+                                            .local_addr()
+                                            .map_err(TcpConnectError::SetupSocketFailed)?;
+                                        tcp_notes.local = Some(local_addr);
+                                        // the chained outgoing addr is not detected at here
+                                        return Ok(ups_stream);
+                                    }
+                                    Err(e) => {
+                                        EscapeLogForTcpConnect {
+                                            tcp_notes,
+                                            task_id: &task_notes.id,
+                                        }
+                                        .log(&self.escape_logger, &e);
+                                        // TODO tell resolver to remove addr
+                                        returned_err = e;
+                                        spawn_new_connection = true;
+                                    }
+                                }
+                            }
+                            Some(Err(r)) => {
+                                running_connection -= 1;
+                                if r.is_panic() {
+                                    return Err(TcpConnectError::InternalServerError("connect task panic"));
+                                }
+                                spawn_new_connection = true;
+                            }
+                            None => unreachable!(),
+                        }
+                    }
+                    _ = connect_interval.tick() => {
+                        if skip_first_tick {
+                            skip_first_tick = false;
+                        } else {
+                            spawn_new_connection = true;
+                        }
+                    }
+                    r = resolver_job.get_r2_or_never(max_tries_each_family) => {
+                        resolver_r2_done = true;
+                        if let Ok(ips2) = r {
+                            self.merge_ip_list(tcp_notes.tries, &mut ips, ips2);
+                        }
+                    }
+                }
+            } else if resolver_r2_done {
+                tcp_notes.duration = instant_now.elapsed();
+                return Err(returned_err);
+            } else {
+                match tokio::time::timeout(
+                    self.config.happy_eyeballs.second_resolution_timeout(),
+                    resolver_job.get_r2_or_never(max_tries_each_family),
+                )
+                .await
+                {
+                    Ok(Ok(ips2)) => {
+                        resolver_r2_done = true;
+                        self.merge_ip_list(tcp_notes.tries, &mut ips, ips2);
+                        spawn_new_connection = true;
+                    }
+                    Ok(Err(_e)) => {
+                        tcp_notes.duration = instant_now.elapsed();
+                        return Err(returned_err);
+                    }
+                    Err(_) => {
+                        tcp_notes.duration = instant_now.elapsed();
+                        return Err(TcpConnectError::TimeoutByRule);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn tcp_connect_to<'a>(
+        &'a self,
+        peer_proxy: &'a UpstreamAddr,
+        tcp_notes: &'a mut TcpConnectTaskNotes,
+        task_notes: &'a ServerTaskNotes,
+    ) -> Result<TcpStream, TcpConnectError> {
+        match peer_proxy.host() {
+            Host::Ip(ip) => {
+                self.fixed_try_connect(
+                    SocketAddr::new(*ip, peer_proxy.port()),
+                    tcp_notes,
+                    task_notes,
+                )
+                .await
+            }
+            Host::Domain(domain) => {
+                let resolver_job = self.resolve_happy(domain.clone())?;
+
+                self.happy_try_connect(resolver_job, peer_proxy.port(), tcp_notes, task_notes)
+                    .await
+            }
+        }
+    }
+
+    fn get_pooled_session(&self, peer_proxy: &UpstreamAddr) -> Option<Arc<SshSession>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(peer_proxy)?;
+        if session.is_closed() {
+            sessions.remove(peer_proxy);
+            None
+        } else {
+            Some(session.clone())
+        }
+    }
+
+    fn drop_pooled_session(&self, peer_proxy: &UpstreamAddr, session: &Arc<SshSession>) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions
+            .get(peer_proxy)
+            .map(|s| Arc::ptr_eq(s, session))
+            .unwrap_or(false)
+        {
+            sessions.remove(peer_proxy);
+        }
+    }
+
+    async fn ssh_new_session<S>(
+        &self,
+        stream: S,
+        peer_proxy: &UpstreamAddr,
+        tcp_notes: &TcpConnectTaskNotes,
+    ) -> Result<Arc<SshSession>, TcpConnectError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let handler = SshClientHandler::new(peer_proxy, self.config.known_host_keys.clone());
+        let mut session = russh::client::connect_stream(self.ssh_config.clone(), stream, handler)
+            .await
+            .map_err(map_ssh_error)?;
+
+        let hash_alg = session
+            .best_supported_rsa_hash()
+            .await
+            .map_err(map_ssh_error)?
+            .flatten();
+        let auth_result = session
+            .authenticate_publickey(
+                self.config.username.as_str(),
+                PrivateKeyWithHashAlg::new(self.private_key.clone(), hash_alg),
+            )
+            .await
+            .map_err(map_ssh_error)?;
+        if !auth_result.success() {
+            return Err(TcpConnectError::NegotiationRejected(
+                "ssh public key auth failed".to_string(),
+            ));
+        }
+
+        let session = Arc::new(SshSession::new(session, tcp_notes));
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(peer_proxy.clone(), session.clone());
+        Ok(session)
+    }
+
+    async fn ssh_open_tunnel(
+        &self,
+        session: Arc<SshSession>,
+        tcp_notes: &TcpConnectTaskNotes,
+        task_notes: &ServerTaskNotes,
+    ) -> Result<SshTunnelStream, russh::Error> {
+        let upstream = &tcp_notes.upstream;
+        let originator = task_notes.client_addr();
+        let channel = session
+            .handle()
+            .channel_open_direct_tcpip(
+                upstream.host().to_string(),
+                upstream.port() as u32,
+                originator.ip().to_string(),
+                originator.port() as u32,
+            )
+            .await?;
+
+        Ok(SshTunnelStream::new(channel.into_stream(), session))
+    }
+
+    async fn ssh_connect_new<'a>(
+        &'a self,
+        peer_proxy: &'a UpstreamAddr,
+        tcp_notes: &'a mut TcpConnectTaskNotes,
+        task_notes: &'a ServerTaskNotes,
+    ) -> Result<SshTunnelStream, TcpConnectError> {
+        let stream = self
+            .tcp_connect_to(peer_proxy, tcp_notes, task_notes)
+            .await?;
+
+        // set limit config and add escaper stats, do not count in task stats
+        let limit_config = &self.config.general.tcp_sock_speed_limit;
+        let stream = LimitedStream::local_limited(
+            stream,
+            limit_config.shift_millis,
+            limit_config.max_south,
+            limit_config.max_north,
+            self.stats.clone(),
+        );
+
+        match tokio::time::timeout(self.config.peer_negotiation_timeout, async {
+            let session = self.ssh_new_session(stream, peer_proxy, tcp_notes).await?;
+            self.ssh_open_tunnel(session, tcp_notes, task_notes)
+                .await
+                .map_err(map_ssh_error)
+        })
+        .await
+        {
+            Ok(Ok(tunnel)) => Ok(tunnel),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(TcpConnectError::NegotiationPeerTimeout),
+        }
+    }
+
+    pub(super) async fn ssh_connect_to<'a>(
+        &'a self,
+        tcp_notes: &'a mut TcpConnectTaskNotes,
+        task_notes: &'a ServerTaskNotes,
+    ) -> Result<SshTunnelStream, TcpConnectError> {
+        let peer_proxy = self
+            .get_next_proxy(task_notes, tcp_notes.upstream.host())
+            .clone();
+
+        let Some(session) = self.get_pooled_session(&peer_proxy) else {
+            return self
+                .ssh_connect_new(&peer_proxy, tcp_notes, task_notes)
+                .await;
+        };
+
+        // open a new channel on the existing session
+        session.fill_tcp_notes(tcp_notes);
+        tcp_notes.tries = 0;
+        match tokio::time::timeout(
+            self.config.peer_negotiation_timeout,
+            self.ssh_open_tunnel(session.clone(), tcp_notes, task_notes),
+        )
+        .await
+        {
+            Ok(Ok(tunnel)) => Ok(tunnel),
+            Ok(Err(e @ russh::Error::ChannelOpenFailure(_))) => {
+                // rejected by the jump host, the session is still usable
+                Err(map_ssh_error(e))
+            }
+            Ok(Err(_)) => {
+                // the session is broken, retry with a new one
+                self.drop_pooled_session(&peer_proxy, &session);
+                self.ssh_connect_new(&peer_proxy, tcp_notes, task_notes)
+                    .await
+            }
+            Err(_) => {
+                self.drop_pooled_session(&peer_proxy, &session);
+                Err(TcpConnectError::NegotiationPeerTimeout)
+            }
+        }
+    }
+
+    pub(super) async fn tcp_new_connection<'a>(
+        &'a self,
+        tcp_notes: &'a mut TcpConnectTaskNotes,
+        task_notes: &'a ServerTaskNotes,
+        task_stats: ArcTcpConnectionTaskRemoteStats,
+    ) -> TcpConnectResult {
+        let tunnel = self.ssh_connect_to(tcp_notes, task_notes).await?;
+        let (r, w) = tokio::io::split(tunnel);
+
+        // add task and user stats
+        let mut wrapper_stats = TcpConnectionTaskRemoteStatsWrapper::new(task_stats);
+        wrapper_stats.push_other_stats(self.fetch_user_upstream_io_stats(task_notes));
+        let wrapper_stats = Arc::new(wrapper_stats);
+
+        let r = LimitedReader::new(r, wrapper_stats.clone());
+        let w = LimitedWriter::new(w, wrapper_stats);
+
+        Ok((Box::new(r), Box::new(w)))
+    }
+}
@@ -0,0 +1,216 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use pin_project_lite::pin_project;
+use russh::client::{Handle, Handler, Msg};
+use russh::keys::PublicKey;
+use russh::ChannelStream;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use g3_socket::BindAddr;
+use g3_types::net::UpstreamAddr;
+
+use crate::config::escaper::proxy_ssh::SshKnownHostKeys;
+use crate::module::tcp_connect::{TcpConnectError, TcpConnectTaskNotes};
+
+pub(super) struct SshClientHandler {
+    peer: UpstreamAddr,
+    known_host_keys: Option<Arc<SshKnownHostKeys>>,
+}
+
+impl SshClientHandler {
+    pub(super) fn new(peer: &UpstreamAddr, known_host_keys: Option<Arc<SshKnownHostKeys>>) -> Self {
+        SshClientHandler {
+            peer: peer.clone(),
+            known_host_keys,
+        }
+    }
+}
+
+/// Check the server key against the recorded ones, in the same way as `russh::keys::check_known_hosts_path`
+fn check_host_key(
+    recorded: &[(usize, PublicKey)],
+    server_public_key: &PublicKey,
+) -> Result<bool, russh::keys::Error> {
+    let mut found = false;
+    for (line, key) in recorded {
+        if key.algorithm() != server_public_key.algorithm() {
+            continue;
+        }
+        if key != server_public_key {
+            return Err(russh::keys::Error::KeyChanged { line: *line });
+        }
+        found = true;
+    }
+    Ok(found)
+}
+
+impl Handler for SshClientHandler {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        match &self.known_host_keys {
+            Some(map) => {
+                let Some(recorded) = map.get(&self.peer) else {
+                    return Ok(false);
+                };
+                let found = check_host_key(recorded, server_public_key)?;
+                Ok(found)
+            }
+            None => Ok(true),
+        }
+    }
+}
+
+/// An authenticated ssh session to the jump host, which may be shared by many tunnels
+pub(super) struct SshSession {
+    handle: Handle<SshClientHandler>,
+    bind: BindAddr,
+    next: Option<SocketAddr>,
+    local: Option<SocketAddr>,
+}
+
+impl SshSession {
+    pub(super) fn new(handle: Handle<SshClientHandler>, tcp_notes: &TcpConnectTaskNotes) -> Self {
+        SshSession {
+            handle,
+            bind: tcp_notes.bind,
+            next: tcp_notes.next,
+            local: tcp_notes.local,
+        }
+    }
+
+    #[inline]
+    pub(super) fn handle(&self) -> &Handle<SshClientHandler> {
+        &self.handle
+    }
+
+    #[inline]
+    pub(super) fn is_closed(&self) -> bool {
+        self.handle.is_closed()
+    }
+
+    pub(super) fn fill_tcp_notes(&self, tcp_notes: &mut TcpConnectTaskNotes) {
+        tcp_notes.bind = self.bind;
+        tcp_notes.next = self.next;
+        tcp_notes.local = self.local;
+    }
+}
+
+pub(super) fn map_ssh_error(e: russh::Error) -> TcpConnectError {
+    match e {
+        russh::Error::IO(e) => TcpConnectError::NegotiationReadFailed(e),
+        russh::Error::UnknownKey => {
+            TcpConnectError::NegotiationRejected("ssh host key not trusted".to_string())
+        }
+        russh::Error::Keys(e) => {
+            TcpConnectError::NegotiationRejected(format!("ssh host key check failed: {e}"))
+        }
+        russh::Error::ChannelOpenFailure(reason) => {
+            TcpConnectError::NegotiationRejected(format!("ssh channel open failed: {reason:?}"))
+        }
+        _ => TcpConnectError::NegotiationProtocolErr,
+    }
+}
+
+pin_project! {
+    /// The direct-tcpip channel stream, which also holds the ssh session that it lives in
+    pub(super) struct SshTunnelStream {
+        #[pin]
+        inner: ChannelStream<Msg>,
+        _session: Arc<SshSession>,
+    }
+}
+
+impl SshTunnelStream {
+    pub(super) fn new(inner: ChannelStream<Msg>, session: Arc<SshSession>) -> Self {
+        SshTunnelStream {
+            inner,
+            _session: session,
+        }
+    }
+}
+
+impl AsyncRead for SshTunnelStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.project().inner.poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for SshTunnelStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public_key(s: &str) -> PublicKey {
+        PublicKey::from_openssh(s).unwrap()
+    }
+
+    #[test]
+    fn host_key_check() {
+        let ed25519_a = public_key(
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJedwF9roOgv1ODYCt93QWHeqY7NE6HKGw3i8QV1LnVK",
+        );
+        let ed25519_b = public_key(
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFkLKnrEWdZEPyPAF0NNF0/rGySIL/asywV8xIl9WNUS",
+        );
+        let ecdsa = public_key(
+            "ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBKZFYXMwrv9mi+LXFo58NsrBBhrSZAihuSWKOm3+BdXUIpqi6oBNPd28vyLweVnsA0RKJR5dcz7eVc25d7X91yI=",
+        );
+
+        let recorded = vec![(1, ecdsa.clone()), (3, ed25519_a.clone())];
+        assert!(check_host_key(&recorded, &ed25519_a).unwrap());
+        assert!(check_host_key(&recorded, &ecdsa).unwrap());
+        match check_host_key(&recorded, &ed25519_b) {
+            Err(russh::keys::Error::KeyChanged { line }) => assert_eq!(line, 3),
+            r => panic!("unexpected result {r:?}"),
+        }
+
+        let recorded = vec![(1, ecdsa)];
+        assert!(!check_host_key(&recorded, &ed25519_a).unwrap());
+        assert!(!check_host_key(&[], &ed25519_a).unwrap());
+    }
+}
@@ -131,6 +131,7 @@ impl RouteSelectEscaper {
         let v = self.select_consistent(
             &self.select_nodes,
             self.config.next_pick_policy,
+            self.config.next_pick_override,
             task_notes,
             upstream.host(),
         );
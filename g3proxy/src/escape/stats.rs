@@ -14,9 +14,11 @@
  * limitations under the License.
  */
 
+use std::net::IpAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+use ahash::AHashMap;
 use arc_swap::ArcSwapOption;
 
 use g3_types::metrics::{MetricsName, StaticMetricsTags};
@@ -25,6 +27,8 @@ use g3_types::stats::{StatId, TcpIoSnapshot, TcpIoStats, UdpIoSnapshot, UdpIoSta
 pub(crate) trait EscaperInternalStats {
     fn add_http_forward_request_attempted(&self);
     fn add_https_forward_request_attempted(&self);
+    fn add_udp_oversized_packet_dropped(&self) {}
+    fn add_udp_unexpected_source_dropped(&self) {}
 }
 
 pub(crate) trait EscaperStats: EscaperInternalStats {
@@ -48,9 +52,25 @@ pub(crate) trait EscaperStats: EscaperInternalStats {
         None
     }
 
+    fn get_udp_oversized_packet_dropped(&self) -> u64 {
+        0
+    }
+
+    fn get_udp_unexpected_source_dropped(&self) -> u64 {
+        0
+    }
+
     fn forbidden_snapshot(&self) -> Option<EscaperForbiddenSnapshot> {
         None
     }
+
+    fn conn_limit_snapshot(&self) -> Option<EscaperConnLimitSnapshot> {
+        None
+    }
+
+    fn bind_ip_snapshot(&self) -> Option<Vec<(IpAddr, u64)>> {
+        None
+    }
 }
 
 pub(crate) type ArcEscaperInternalStats = Arc<dyn EscaperInternalStats + Send + Sync>;
@@ -78,6 +98,59 @@ impl EscaperForbiddenStats {
     }
 }
 
+/// Selection count of each bind ip, which will be kept across reload
+#[derive(Default)]
+pub(crate) struct EscaperBindIpStats {
+    selected: Mutex<AHashMap<IpAddr, Arc<AtomicU64>>>,
+}
+
+impl EscaperBindIpStats {
+    pub(crate) fn selected_counter(&self, ip: IpAddr) -> Arc<AtomicU64> {
+        let mut map = self.selected.lock().unwrap();
+        map.entry(ip).or_default().clone()
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<(IpAddr, u64)> {
+        let map = self.selected.lock().unwrap();
+        map.iter()
+            .map(|(ip, count)| (*ip, count.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct EscaperConnLimitSnapshot {
+    pub(crate) in_use: u64,
+    pub(crate) rejected: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct EscaperConnLimitStats {
+    in_use: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl EscaperConnLimitStats {
+    pub(crate) fn inc_in_use(&self) {
+        self.in_use.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dec_in_use(&self) {
+        self.in_use.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> EscaperConnLimitSnapshot {
+        EscaperConnLimitSnapshot {
+            in_use: self.in_use.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct EscaperInterfaceStats {
     tcp_connect_attempted: AtomicU64,
@@ -187,6 +260,28 @@ impl EscaperTcpStats {
 #[derive(Default)]
 pub(crate) struct EscaperUdpStats {
     pub(crate) io: UdpIoStats,
+    oversized_packet_dropped: AtomicU64,
+    unexpected_source_dropped: AtomicU64,
+}
+
+impl EscaperUdpStats {
+    pub(crate) fn add_oversized_packet_dropped(&self) {
+        self.oversized_packet_dropped
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get_oversized_packet_dropped(&self) -> u64 {
+        self.oversized_packet_dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_unexpected_source_dropped(&self) {
+        self.unexpected_source_dropped
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get_unexpected_source_dropped(&self) -> u64 {
+        self.unexpected_source_dropped.load(Ordering::Relaxed)
+    }
 }
 
 #[derive(Default)]
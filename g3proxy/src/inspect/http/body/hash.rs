@@ -0,0 +1,204 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use openssl::sha::Sha256;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+use g3_io_ext::FlexBufReader;
+
+/// Streaming sha256 hasher of a http body
+pub(crate) struct HttpBodyHasher {
+    sha256: Sha256,
+    size: u64,
+    eof: bool,
+    partial: bool,
+}
+
+impl HttpBodyHasher {
+    pub(crate) fn new() -> Self {
+        HttpBodyHasher {
+            sha256: Sha256::new(),
+            size: 0,
+            eof: false,
+            partial: false,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        self.size += data.len() as u64;
+    }
+
+    /// Mark that all the body data has been fed
+    pub(crate) fn set_eof(&mut self) {
+        self.eof = true;
+    }
+
+    /// Mark that some of the body data won't be fed
+    pub(crate) fn set_partial(&mut self) {
+        self.partial = true;
+    }
+
+    /// Get the digest. It will be marked as partial if the body is not fully hashed,
+    /// or if the transfer of the body has been aborted.
+    pub(crate) fn finish(self, aborted: bool) -> HttpBodyDigest {
+        HttpBodyDigest {
+            sha256: self.sha256.finish(),
+            size: self.size,
+            partial: aborted || self.partial || !self.eof,
+        }
+    }
+}
+
+pub(crate) struct HttpBodyDigest {
+    sha256: [u8; 32],
+    size: u64,
+    partial: bool,
+}
+
+impl HttpBodyDigest {
+    pub(crate) fn sha256_hex(&self) -> String {
+        hex::encode(self.sha256)
+    }
+
+    #[inline]
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[inline]
+    pub(crate) fn partial(&self) -> bool {
+        self.partial
+    }
+}
+
+/// Hash the body data while reading it.
+///
+/// The buffered read interface is only available on the connection reader, and only the consumed
+/// data will be hashed, so it can be used by the ICAP adapters, which read the body from the
+/// connection directly.
+pub(crate) struct BodyHashReader<'a, R> {
+    inner: R,
+    hasher: Option<&'a mut HttpBodyHasher>,
+}
+
+impl<'a, R> BodyHashReader<'a, R> {
+    pub(crate) fn new(inner: R, hasher: Option<&'a mut HttpBodyHasher>) -> Self {
+        BodyHashReader { inner, hasher }
+    }
+}
+
+impl<R> AsyncRead for BodyHashReader<'_, R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        if let Some(hasher) = &mut this.hasher {
+            let data = &buf.filled()[filled..];
+            if data.is_empty() {
+                if buf.remaining() > 0 {
+                    hasher.set_eof();
+                }
+            } else {
+                hasher.update(data);
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R> AsyncBufRead for BodyHashReader<'_, &mut FlexBufReader<R>>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.inner).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        if let Some(hasher) = &mut this.hasher {
+            let buf = this.inner.buffer();
+            hasher.update(&buf[..amt.min(buf.len())]);
+        }
+        Pin::new(&mut *this.inner).consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[tokio::test]
+    async fn hash_reader() {
+        let mut hasher = HttpBodyHasher::new();
+        let mut reader = BodyHashReader::new(b"abc".as_slice(), Some(&mut hasher));
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).await.unwrap();
+        assert_eq!(body.as_slice(), b"abc");
+
+        let digest = hasher.finish(false);
+        assert_eq!(digest.sha256_hex(), ABC_SHA256);
+        assert_eq!(digest.size(), 3);
+        assert!(!digest.partial());
+    }
+
+    #[tokio::test]
+    async fn hash_buf_reader() {
+        let mut hasher = HttpBodyHasher::new();
+        let mut conn = FlexBufReader::new(b"abc\ndef".as_slice());
+        let mut reader = BodyHashReader::new(&mut conn, Some(&mut hasher));
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line).await.unwrap();
+        assert_eq!(line.as_slice(), b"abc\n");
+
+        // the remaining buffered data is not consumed and should not be hashed
+        assert_eq!(conn.buffer(), b"def");
+        let digest = hasher.finish(false);
+        assert_eq!(digest.size(), 4);
+        assert!(digest.partial());
+    }
+
+    #[test]
+    fn partial() {
+        let mut hasher = HttpBodyHasher::new();
+        hasher.update(b"abc");
+        let digest = hasher.finish(false);
+        assert_eq!(digest.sha256_hex(), ABC_SHA256);
+        assert!(digest.partial());
+
+        let mut hasher = HttpBodyHasher::new();
+        hasher.update(b"abc");
+        hasher.set_eof();
+        assert!(hasher.finish(true).partial());
+    }
+}
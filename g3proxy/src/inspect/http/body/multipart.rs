@@ -0,0 +1,226 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use thiserror::Error;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use g3_dpi::HttpMultipartInspectionConfig;
+use g3_http::{HttpMultipartEvent, HttpMultipartParseError, HttpMultipartParser};
+
+use crate::inspect::DryRunBlockLogger;
+
+/// The error returned by the reader if some part of the request body has been blocked
+#[derive(Debug, Error)]
+#[error("request body part blocked: {0}")]
+pub(crate) struct RequestBodyPartBlocked(String);
+
+impl RequestBodyPartBlocked {
+    pub(crate) fn from_io_error(e: &io::Error) -> Option<&Self> {
+        e.get_ref().and_then(|e| e.downcast_ref::<Self>())
+    }
+}
+
+enum MultipartInspectError {
+    Parse(HttpMultipartParseError),
+    Blocked(String),
+}
+
+impl From<HttpMultipartParseError> for MultipartInspectError {
+    fn from(e: HttpMultipartParseError) -> Self {
+        MultipartInspectError::Parse(e)
+    }
+}
+
+/// Parse the multipart request body while reading it, and check the metadata of each part.
+///
+/// The data is still returned as is to the caller, and a [RequestBodyPartBlocked] error
+/// will be returned if any part is blocked.
+///
+/// In dry-run mode, the block event will be logged and the inspection will be stopped,
+/// and no error will be returned.
+pub(crate) struct RequestBodyMultipartReader<'a, R> {
+    inner: R,
+    inspector: Option<(HttpMultipartParser, &'a HttpMultipartInspectionConfig)>,
+    parse_error: Option<HttpMultipartParseError>,
+    dry_run: Option<DryRunBlockLogger>,
+}
+
+impl<'a, R> RequestBodyMultipartReader<'a, R> {
+    pub(crate) fn new(
+        inner: R,
+        config: Option<&'a HttpMultipartInspectionConfig>,
+        boundary: Option<String>,
+        dry_run: Option<DryRunBlockLogger>,
+    ) -> Self {
+        let inspector = match (config, boundary) {
+            (Some(config), Some(boundary)) => Some((
+                HttpMultipartParser::new(&boundary, config.part_head_max_size),
+                config,
+            )),
+            _ => None,
+        };
+        RequestBodyMultipartReader {
+            inner,
+            inspector,
+            parse_error: None,
+            dry_run,
+        }
+    }
+
+    /// Get the error that aborted the inspection
+    pub(crate) fn take_parse_error(&mut self) -> Option<HttpMultipartParseError> {
+        self.parse_error.take()
+    }
+
+    fn inspect(&mut self, data: &[u8]) -> Result<(), MultipartInspectError> {
+        let Some((parser, config)) = &mut self.inspector else {
+            return Ok(());
+        };
+        if data.is_empty() {
+            return parser.finish().map_err(MultipartInspectError::Parse);
+        }
+
+        let config = *config;
+        parser.feed(data, |event| {
+            let (part, reason) = match event {
+                HttpMultipartEvent::PartStart(part) => (
+                    part,
+                    config.check_part_head(part.filename.as_deref(), part.content_type.as_deref()),
+                ),
+                HttpMultipartEvent::PartData(part) => (part, config.check_part_size(part.size)),
+                HttpMultipartEvent::PartEnd(_) => return Ok(()),
+            };
+            let Some(reason) = reason else {
+                return Ok(());
+            };
+            Err(MultipartInspectError::Blocked(format!(
+                "{reason}, name: {}, filename: {}, content-type: {}",
+                part.name.as_deref().unwrap_or_default(),
+                part.filename.as_deref().unwrap_or_default(),
+                part.content_type.as_deref().unwrap_or_default(),
+            )))
+        })
+    }
+}
+
+impl<R> AsyncRead for RequestBodyMultipartReader<'_, R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        let data = &buf.filled()[filled..];
+        if data.is_empty() && buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        match this.inspect(data) {
+            Ok(_) => Poll::Ready(Ok(())),
+            Err(MultipartInspectError::Blocked(reason)) => {
+                this.inspector = None;
+                if let Some(logger) = &this.dry_run {
+                    logger.log(&format!("request body part blocked: {reason}"));
+                    return Poll::Ready(Ok(()));
+                }
+                // the data read in will be dropped
+                buf.set_filled(filled);
+                Poll::Ready(Err(io::Error::other(RequestBodyPartBlocked(reason))))
+            }
+            Err(MultipartInspectError::Parse(e)) => {
+                let block_on_parse_error = this
+                    .inspector
+                    .take()
+                    .map(|(_, config)| config.block_on_parse_error)
+                    .unwrap_or_default();
+                if !block_on_parse_error {
+                    this.parse_error = Some(e);
+                    return Poll::Ready(Ok(()));
+                }
+                let reason = format!("invalid multipart body: {e}");
+                if let Some(logger) = &this.dry_run {
+                    logger.log(&reason);
+                    this.parse_error = Some(e);
+                    return Poll::Ready(Ok(()));
+                }
+                buf.set_filled(filled);
+                Poll::Ready(Err(io::Error::other(RequestBodyPartBlocked(reason))))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    const BODY: &[u8] = b"--AaB03x\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"setup.exe\"\r\n\
+        Content-Type: application/octet-stream\r\n\
+        \r\n\
+        MZ\r\n\
+        --AaB03x--\r\n";
+
+    #[tokio::test]
+    async fn block_part() {
+        let config = HttpMultipartInspectionConfig {
+            block_filename_ext: vec!["exe".to_string()],
+            ..Default::default()
+        };
+        let mut reader =
+            RequestBodyMultipartReader::new(BODY, Some(&config), Some("AaB03x".to_string()), None);
+        let mut body = Vec::new();
+        let e = reader.read_to_end(&mut body).await.unwrap_err();
+        assert!(RequestBodyPartBlocked::from_io_error(&e).is_some());
+
+        let config = HttpMultipartInspectionConfig {
+            block_filename_ext: vec!["dll".to_string()],
+            ..Default::default()
+        };
+        let mut reader =
+            RequestBodyMultipartReader::new(BODY, Some(&config), Some("AaB03x".to_string()), None);
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).await.unwrap();
+        assert_eq!(body.as_slice(), BODY);
+        assert!(reader.take_parse_error().is_none());
+    }
+    #[tokio::test]
+    async fn dry_run() {
+        let config = HttpMultipartInspectionConfig {
+            block_filename_ext: vec!["exe".to_string()],
+            ..Default::default()
+        };
+        let mut reader = RequestBodyMultipartReader::new(
+            BODY,
+            Some(&config),
+            Some("AaB03x".to_string()),
+            Some(DryRunBlockLogger::discard("http_1")),
+        );
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).await.unwrap();
+        assert_eq!(body.as_slice(), BODY);
+    }
+}
@@ -14,6 +14,8 @@
  * limitations under the License.
  */
 
+mod body;
+
 mod v2;
 pub(super) use v2::{H2InterceptObject, H2InterceptionError};
 
@@ -67,6 +67,8 @@ struct HttpForwardTaskNotes {
     dur_req_send_hdr: Duration,
     dur_req_pipeline: Duration,
     dur_rsp_recv_hdr: Duration,
+    /// set if the response is generated by the ICAP REQMOD service, which is usually a block page
+    reqmod_responded: bool,
 }
 
 impl HttpForwardTaskNotes {
@@ -80,6 +82,7 @@ impl HttpForwardTaskNotes {
             dur_req_send_hdr: Duration::default(),
             dur_req_pipeline,
             dur_rsp_recv_hdr: Duration::default(),
+            reqmod_responded: false,
         }
     }
 
@@ -153,7 +156,11 @@ where
     {
         match self.do_forward(rsp_io, reqmod_client).await {
             Ok(v) => {
-                intercept_log!(self, &v, "ok");
+                if self.http_notes.reqmod_responded {
+                    intercept_log!(self, &v, "responded by icap reqmod service");
+                } else if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, &v, "ok");
+                }
                 v
             }
             Err(e) => {
@@ -177,7 +184,9 @@ where
     {
         match self.send_request(None, rsp_io).await {
             Ok(v) => {
-                intercept_log!(self, &v, "ok");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, &v, "ok");
+                }
                 v
             }
             Err(e) => {
@@ -272,6 +281,7 @@ where
             .await
             .map_err(ServerTaskError::ClientTcpWriteFailed)?;
         self.http_notes.rsp_status = rsp.status.as_u16();
+        self.http_notes.reqmod_responded = true;
 
         if let Some(mut recv_body) = rsp_recv_body {
             let mut body_reader = recv_body.body_reader();
@@ -0,0 +1,514 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+use g3_dpi::ProtocolInspectAction;
+use g3_http::{HttpBodyType, HttpContentDecodeError, HttpContentDecoder};
+use g3_io_ext::FlexBufReader;
+
+use crate::inspect::http::body::HttpBodyHasher;
+
+enum ChunkedState {
+    Size { size: u64, ext: bool },
+    Data(u64),
+    DataEnd,
+    Trailer { empty_line: bool },
+    End,
+}
+
+/// Strip the transfer framing from the on-the-wire body data, to get the content for decoding.
+///
+/// The framing has already been validated by the body reader that forwards the data,
+/// so only the chunk size is checked here.
+enum BodyFraming {
+    ContentLength(u64),
+    ReadUntilEnd,
+    Chunked(ChunkedState),
+}
+
+impl BodyFraming {
+    fn new(body_type: HttpBodyType) -> Self {
+        match body_type {
+            HttpBodyType::ContentLength(len) => BodyFraming::ContentLength(len),
+            HttpBodyType::ReadUntilEnd => BodyFraming::ReadUntilEnd,
+            HttpBodyType::Chunked => BodyFraming::Chunked(ChunkedState::Size {
+                size: 0,
+                ext: false,
+            }),
+        }
+    }
+
+    fn finished(&self) -> bool {
+        matches!(
+            self,
+            BodyFraming::ContentLength(0) | BodyFraming::Chunked(ChunkedState::End)
+        )
+    }
+
+    /// Take the next piece of content data, return None if all the data has been consumed
+    fn take<'d>(
+        &mut self,
+        data: &mut &'d [u8],
+    ) -> Result<Option<&'d [u8]>, HttpContentDecodeError> {
+        let mut buf: &'d [u8] = data;
+        let r = match self {
+            BodyFraming::ContentLength(left) => {
+                let len = usize::try_from(*left).unwrap_or(usize::MAX).min(buf.len());
+                *left -= len as u64;
+                let (piece, rest) = buf.split_at(len);
+                buf = rest;
+                Ok((len > 0).then_some(piece))
+            }
+            BodyFraming::ReadUntilEnd => {
+                let piece = buf;
+                buf = &[];
+                Ok((!piece.is_empty()).then_some(piece))
+            }
+            BodyFraming::Chunked(state) => Self::take_chunked(state, &mut buf),
+        };
+        *data = buf;
+        r
+    }
+
+    fn take_chunked<'d>(
+        state: &mut ChunkedState,
+        buf: &mut &'d [u8],
+    ) -> Result<Option<&'d [u8]>, HttpContentDecodeError> {
+        while let Some((&b, rest)) = buf.split_first() {
+            match state {
+                ChunkedState::Data(left) => {
+                    let len = usize::try_from(*left).unwrap_or(usize::MAX).min(buf.len());
+                    *left -= len as u64;
+                    if *left == 0 {
+                        *state = ChunkedState::DataEnd;
+                    }
+                    let (piece, rest) = buf.split_at(len);
+                    *buf = rest;
+                    return Ok(Some(piece));
+                }
+                ChunkedState::Size { size, ext } => {
+                    if b == b'\n' {
+                        *state = if *size == 0 {
+                            ChunkedState::Trailer { empty_line: true }
+                        } else {
+                            ChunkedState::Data(*size)
+                        };
+                    } else if !*ext {
+                        match (b as char).to_digit(16) {
+                            Some(d) => {
+                                *size = size
+                                    .checked_mul(16)
+                                    .and_then(|v| v.checked_add(d as u64))
+                                    .ok_or_else(|| {
+                                        HttpContentDecodeError::InvalidData(
+                                            "chunked",
+                                            io::Error::other("too large chunk size"),
+                                        )
+                                    })?;
+                            }
+                            None => *ext = true,
+                        }
+                    }
+                }
+                ChunkedState::DataEnd => {
+                    if b == b'\n' {
+                        *state = ChunkedState::Size {
+                            size: 0,
+                            ext: false,
+                        };
+                    }
+                }
+                ChunkedState::Trailer { empty_line } => {
+                    if b == b'\n' {
+                        if *empty_line {
+                            *state = ChunkedState::End;
+                        } else {
+                            *empty_line = true;
+                        }
+                    } else if b != b'\r' {
+                        *empty_line = false;
+                    }
+                }
+                ChunkedState::End => {}
+            }
+            *buf = rest;
+        }
+        Ok(None)
+    }
+}
+
+/// Inspection state of the response body, which is fed with the on-the-wire body data
+struct DecompressState<'a> {
+    framing: BodyFraming,
+    decoder: Option<HttpContentDecoder>,
+    exceed_action: ProtocolInspectAction,
+    blocked: bool,
+    decode_error: Option<HttpContentDecodeError>,
+    hasher: Option<&'a mut HttpBodyHasher>,
+    hash_decoded: bool,
+}
+
+impl DecompressState<'_> {
+    fn feed(&mut self, data: &[u8]) {
+        if !self.hash_decoded {
+            if let Some(hasher) = &mut self.hasher {
+                hasher.update(data);
+            }
+        }
+        if let Err(e) = self.decode(data) {
+            self.set_decode_error(e);
+        }
+    }
+
+    fn feed_eof(&mut self) {
+        if !self.hash_decoded {
+            if let Some(hasher) = &mut self.hasher {
+                hasher.set_eof();
+            }
+        }
+        if let Err(e) = self.finish_decode() {
+            self.set_decode_error(e);
+        }
+    }
+
+    fn decode(&mut self, mut data: &[u8]) -> Result<(), HttpContentDecodeError> {
+        let Some(decoder) = &mut self.decoder else {
+            return Ok(());
+        };
+        while let Some(piece) = self.framing.take(&mut data)? {
+            let decoded = decoder.decode(piece)?;
+            if self.hash_decoded {
+                if let Some(hasher) = &mut self.hasher {
+                    hasher.update(decoded);
+                }
+            }
+        }
+        if self.framing.finished() {
+            self.finish_decode()?;
+        }
+        Ok(())
+    }
+
+    fn finish_decode(&mut self) -> Result<(), HttpContentDecodeError> {
+        let Some(mut decoder) = self.decoder.take() else {
+            return Ok(());
+        };
+        let decoded = decoder.finish()?;
+        if self.hash_decoded {
+            if let Some(hasher) = &mut self.hasher {
+                hasher.update(decoded);
+                hasher.set_eof();
+            }
+        }
+        Ok(())
+    }
+
+    fn set_decode_error(&mut self, e: HttpContentDecodeError) {
+        self.decoder = None;
+        if self.hash_decoded {
+            if let Some(hasher) = &mut self.hasher {
+                hasher.set_partial();
+            }
+        }
+        let exceeded = matches!(
+            e,
+            HttpContentDecodeError::SizeLimitExceeded(_)
+                | HttpContentDecodeError::RatioLimitExceeded(_)
+        );
+        if exceeded && self.exceed_action == ProtocolInspectAction::Block {
+            self.blocked = true;
+        }
+        self.decode_error = Some(e);
+    }
+}
+
+/// Feed the original response body data to the content decoder for inspection,
+/// while still returning the original data to the caller.
+///
+/// The body hasher will be fed with the decoded data if `hash_decoded` is set,
+/// or with the original data if not.
+///
+/// The buffered read interface is only available on the connection reader, and only the consumed
+/// data will be inspected, so it can be used by the ICAP RESPMOD adapter, which reads the body
+/// from the connection directly.
+///
+/// The response will not be cut off in dry-run mode, even if it should be blocked.
+pub(super) struct ResponseBodyDecompressReader<'a, R> {
+    inner: R,
+    state: DecompressState<'a>,
+    dry_run: bool,
+}
+
+impl<'a, R> ResponseBodyDecompressReader<'a, R> {
+    pub(super) fn new(
+        inner: R,
+        body_type: HttpBodyType,
+        decoder: Option<HttpContentDecoder>,
+        exceed_action: ProtocolInspectAction,
+        dry_run: bool,
+        hasher: Option<&'a mut HttpBodyHasher>,
+        hash_decoded: bool,
+    ) -> Self {
+        // the original data is the decoded data if there is no content coding
+        let hash_decoded = hash_decoded && decoder.is_some();
+        ResponseBodyDecompressReader {
+            inner,
+            state: DecompressState {
+                framing: BodyFraming::new(body_type),
+                decoder,
+                exceed_action,
+                blocked: false,
+                decode_error: None,
+                hasher,
+                hash_decoded,
+            },
+            dry_run,
+        }
+    }
+
+    /// Get the error that aborted the inspection, and whether the response has been blocked
+    pub(super) fn take_decode_error(&mut self) -> Option<(HttpContentDecodeError, bool)> {
+        self.state
+            .decode_error
+            .take()
+            .map(|e| (e, self.state.blocked))
+    }
+
+    fn blocked_error(&self) -> Option<io::Error> {
+        (self.state.blocked && !self.dry_run)
+            .then(|| io::Error::other("response body decompression limit exceeded"))
+    }
+}
+
+impl<R> AsyncRead for ResponseBodyDecompressReader<'_, R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if let Some(e) = this.blocked_error() {
+            return Poll::Ready(Err(e));
+        }
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        let data = &buf.filled()[filled..];
+        if data.is_empty() {
+            if buf.remaining() > 0 {
+                this.state.feed_eof();
+            }
+        } else {
+            this.state.feed(data);
+        }
+        match this.blocked_error() {
+            Some(e) => {
+                // the data that triggered the block should not be returned
+                buf.set_filled(filled);
+                Poll::Ready(Err(e))
+            }
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl<R> AsyncBufRead for ResponseBodyDecompressReader<'_, &mut FlexBufReader<R>>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if let Some(e) = this.blocked_error() {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut *this.inner).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        let buf = this.inner.buffer();
+        this.state.feed(&buf[..amt.min(buf.len())]);
+        Pin::new(&mut *this.inner).consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    use g3_http::HttpContentCoding;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn chunked(data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut body = Vec::new();
+        for chunk in data.chunks(chunk_size) {
+            body.extend_from_slice(format!("{:x};ext=1\r\n", chunk.len()).as_bytes());
+            body.extend_from_slice(chunk);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"0\r\nTrailer: x\r\n\r\n");
+        body
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        let mut hasher = HttpBodyHasher::new();
+        hasher.update(data);
+        hasher.set_eof();
+        hasher.finish(false).sha256_hex()
+    }
+
+    #[tokio::test]
+    async fn decoded_hash_chunked() {
+        let content = b"hello world, hello world, hello world".repeat(16);
+        let body = chunked(&gzip(&content), 7);
+
+        let mut hasher = HttpBodyHasher::new();
+        let decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 0, 0);
+        let mut reader = ResponseBodyDecompressReader::new(
+            body.as_slice(),
+            HttpBodyType::Chunked,
+            Some(decoder),
+            ProtocolInspectAction::Bypass,
+            false,
+            Some(&mut hasher),
+            true,
+        );
+        let mut forwarded = Vec::new();
+        reader.read_to_end(&mut forwarded).await.unwrap();
+        assert!(reader.take_decode_error().is_none());
+        // the original data is forwarded unchanged
+        assert_eq!(forwarded, body);
+
+        let digest = hasher.finish(false);
+        assert_eq!(digest.size(), content.len() as u64);
+        assert_eq!(digest.sha256_hex(), sha256_hex(&content));
+        assert!(!digest.partial());
+    }
+
+    #[tokio::test]
+    async fn decoded_hash_buf_read() {
+        let content = b"hello world, hello world, hello world".repeat(16);
+        let encoded = gzip(&content);
+        let mut data = encoded.clone();
+        data.extend_from_slice(b"next");
+
+        let mut hasher = HttpBodyHasher::new();
+        let decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 0, 0);
+        let mut conn = FlexBufReader::new(data.as_slice());
+        let mut reader = ResponseBodyDecompressReader::new(
+            &mut conn,
+            HttpBodyType::ContentLength(encoded.len() as u64),
+            Some(decoder),
+            ProtocolInspectAction::Bypass,
+            false,
+            Some(&mut hasher),
+            true,
+        );
+        let buf = reader.fill_buf().await.unwrap();
+        assert_eq!(&buf[..encoded.len()], encoded.as_slice());
+        reader.consume(encoded.len());
+        assert!(reader.take_decode_error().is_none());
+        assert_eq!(conn.buffer(), b"next");
+
+        let digest = hasher.finish(false);
+        assert_eq!(digest.sha256_hex(), sha256_hex(&content));
+        assert!(!digest.partial());
+    }
+
+    #[tokio::test]
+    async fn bomb_bypass() {
+        let content = vec![0u8; 4 * 1024 * 1024];
+        let body = gzip(&content);
+
+        let mut hasher = HttpBodyHasher::new();
+        let decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 1024 * 1024, 100);
+        let mut reader = ResponseBodyDecompressReader::new(
+            body.as_slice(),
+            HttpBodyType::ReadUntilEnd,
+            Some(decoder),
+            ProtocolInspectAction::Bypass,
+            false,
+            Some(&mut hasher),
+            true,
+        );
+        let mut forwarded = Vec::new();
+        reader.read_to_end(&mut forwarded).await.unwrap();
+        assert_eq!(forwarded, body);
+        let (e, blocked) = reader.take_decode_error().unwrap();
+        assert!(matches!(e, HttpContentDecodeError::RatioLimitExceeded(100)));
+        assert!(!blocked);
+        assert!(hasher.finish(false).partial());
+    }
+
+    #[tokio::test]
+    async fn bomb_block() {
+        let content = vec![0u8; 4 * 1024 * 1024];
+        let body = gzip(&content);
+
+        let decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 1024 * 1024, 0);
+        let mut reader = ResponseBodyDecompressReader::new(
+            body.as_slice(),
+            HttpBodyType::ContentLength(body.len() as u64),
+            Some(decoder),
+            ProtocolInspectAction::Block,
+            false,
+            None,
+            false,
+        );
+        let mut forwarded = Vec::new();
+        assert!(reader.read_to_end(&mut forwarded).await.is_err());
+        let (e, blocked) = reader.take_decode_error().unwrap();
+        assert!(matches!(
+            e,
+            HttpContentDecodeError::SizeLimitExceeded(1048576)
+        ));
+        assert!(blocked);
+
+        // not cut off in dry-run mode
+        let decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 1024 * 1024, 0);
+        let mut reader = ResponseBodyDecompressReader::new(
+            body.as_slice(),
+            HttpBodyType::ContentLength(body.len() as u64),
+            Some(decoder),
+            ProtocolInspectAction::Block,
+            true,
+            None,
+            false,
+        );
+        let mut forwarded = Vec::new();
+        reader.read_to_end(&mut forwarded).await.unwrap();
+        assert_eq!(forwarded, body);
+        assert!(reader.take_decode_error().unwrap().1);
+    }
+}
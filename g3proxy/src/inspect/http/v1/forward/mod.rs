@@ -24,9 +24,13 @@ use slog::slog_info;
 use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::time::Instant;
 
+use g3_dpi::{HttpBodyHashMode, HttpBodySizeLimitPolicy};
 use g3_http::client::HttpTransparentResponse;
 use g3_http::server::HttpTransparentRequest;
-use g3_http::{HttpBodyReader, HttpBodyType};
+use g3_http::{
+    multipart_boundary, HttpBodyReader, HttpBodyType, HttpContentCoding, HttpContentDecodeError,
+    HttpContentDecoder,
+};
 use g3_icap_client::reqmod::h1::{
     HttpAdapterErrorResponse, HttpRequestAdapter, ReqmodAdaptationEndState,
     ReqmodAdaptationRunState, ReqmodRecvHttpResponseBody,
@@ -41,13 +45,25 @@ use g3_types::net::HttpHeaderMap;
 
 use super::{HttpRequest, HttpRequestIo, HttpResponseIo};
 use crate::config::server::ServerConfig;
-use crate::inspect::StreamInspectContext;
+use crate::inspect::http::body::{
+    BodyHashReader, HttpBodyDigest, HttpBodyHasher, RequestBodyMultipartReader,
+    RequestBodyPartBlocked,
+};
+use crate::inspect::{DryRunBlockLogger, StreamInspectContext};
 use crate::module::http_forward::HttpProxyClientResponse;
-use crate::serve::{ServerIdleChecker, ServerTaskError, ServerTaskResult};
+use crate::serve::{
+    ServerIdleChecker, ServerTaskError, ServerTaskForbiddenError, ServerTaskResult,
+};
 
 mod adaptation;
 pub(crate) use adaptation::HttpRequestWriterForAdaptation;
 
+mod decompress;
+use decompress::ResponseBodyDecompressReader;
+
+mod size_limit;
+use size_limit::ResponseBodySizeLimitReader;
+
 macro_rules! intercept_log {
     ($obj:tt, $($args:tt)+) => {
         slog_info!($obj.ctx.intercept_logger(), $($args)+;
@@ -65,6 +81,12 @@ macro_rules! intercept_log {
             "dur_req_send_all" => LtDuration($obj.http_notes.dur_req_send_all),
             "dur_rsp_recv_hdr" => LtDuration($obj.http_notes.dur_rsp_recv_hdr),
             "dur_rsp_recv_all" => LtDuration($obj.http_notes.dur_rsp_recv_all),
+            "req_body_sha256" => $obj.http_notes.req_body_digest.as_ref().map(|d| d.sha256_hex()),
+            "req_body_size" => $obj.http_notes.req_body_digest.as_ref().map(|d| d.size()),
+            "req_body_hash_partial" => $obj.http_notes.req_body_digest.as_ref().map(|d| d.partial()),
+            "rsp_body_sha256" => $obj.http_notes.rsp_body_digest.as_ref().map(|d| d.sha256_hex()),
+            "rsp_body_size" => $obj.http_notes.rsp_body_digest.as_ref().map(|d| d.size()),
+            "rsp_body_hash_partial" => $obj.http_notes.rsp_body_digest.as_ref().map(|d| d.partial()),
         )
     };
 }
@@ -79,6 +101,10 @@ struct HttpForwardTaskNotes {
     dur_req_send_all: Duration,
     dur_rsp_recv_hdr: Duration,
     dur_rsp_recv_all: Duration,
+    req_body_digest: Option<HttpBodyDigest>,
+    rsp_body_digest: Option<HttpBodyDigest>,
+    /// set if the response is generated by the ICAP REQMOD service, which is usually a block page
+    reqmod_responded: bool,
 }
 
 impl HttpForwardTaskNotes {
@@ -94,6 +120,9 @@ impl HttpForwardTaskNotes {
             dur_req_send_all: Duration::default(),
             dur_rsp_recv_hdr: Duration::default(),
             dur_rsp_recv_all: Duration::default(),
+            req_body_digest: None,
+            rsp_body_digest: None,
+            reqmod_responded: false,
         }
     }
 
@@ -128,6 +157,7 @@ pub(super) struct H1ForwardTask<'a, SC: ServerConfig> {
     req_id: usize,
     send_error_response: bool,
     should_close: bool,
+    continue_sent: bool,
     http_notes: HttpForwardTaskNotes,
 }
 
@@ -141,6 +171,7 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
             req_id,
             send_error_response: true,
             should_close,
+            continue_sent: false,
             http_notes,
         }
     }
@@ -176,6 +207,30 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         }
     }
 
+    /// Reply the custom block response if the request host is blocked with response.
+    ///
+    /// Return true if the request has been blocked and should not be forwarded.
+    pub(super) async fn reply_if_blocked<CW>(&mut self, clt_w: &mut CW) -> bool
+    where
+        CW: AsyncWrite + Unpin,
+    {
+        let Some(http_host) = &self.req.host else {
+            return false;
+        };
+        let Some(rsp) = self.ctx.h1_block_response(http_host.host()) else {
+            return false;
+        };
+
+        self.should_close = true;
+        let buf = rsp.serialize_h1(self.req.version);
+        if clt_w.write_all_flush(&buf).await.is_ok() {
+            self.http_notes.rsp_status = rsp.status().as_u16();
+        }
+        intercept_log!(self, "blocked by inspection policy");
+        self.ctx.tarpit_blocked(clt_w).await;
+        true
+    }
+
     pub(super) async fn forward_without_body<CW, UR, UW>(
         &mut self,
         rsp_io: &mut HttpResponseIo<CW, UR, UW>,
@@ -189,7 +244,7 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
                 self.reply_task_err(&e, &mut rsp_io.clt_w).await;
             }
             intercept_log!(self, "{e}");
-        } else {
+        } else if self.ctx.intercept_log_sampled() {
             intercept_log!(self, "ok");
         }
     }
@@ -234,9 +289,22 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         };
 
         let mut adaptation_state = ReqmodAdaptationRunState::new(self.http_notes.receive_ins);
+        let mut req_body_hasher = self.req.body_type().and_then(|_| self.new_body_hasher());
         let r = self
-            .run_with_adaptation(req_io, rsp_io, adapter, &mut adaptation_state)
+            .run_with_adaptation(
+                req_io,
+                rsp_io,
+                adapter,
+                &mut adaptation_state,
+                req_body_hasher.as_mut(),
+            )
             .await;
+        self.http_notes.req_body_digest = req_body_hasher.map(|mut h| {
+            if adaptation_state.clt_read_finished {
+                h.set_eof();
+            }
+            h.finish(false)
+        });
 
         if let Some(dur) = adaptation_state.dur_ups_send_header {
             self.http_notes.dur_req_send_hdr = dur;
@@ -250,7 +318,11 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
 
         match r {
             Ok(_) => {
-                intercept_log!(self, "ok");
+                if self.http_notes.reqmod_responded {
+                    intercept_log!(self, "responded by icap reqmod service");
+                } else if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "ok");
+                }
             }
             Err(e) => {
                 if self.send_error_response {
@@ -272,13 +344,22 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         UW: AsyncWrite + Unpin,
     {
         let r = if let Some(body_type) = self.req.body_type() {
-            self.do_forward_with_body(req_io, rsp_io, body_type).await
+            let mut req_body_hasher = self.new_body_hasher();
+            let r = self
+                .do_forward_with_body(req_io, rsp_io, body_type, req_body_hasher.as_mut())
+                .await;
+            self.http_notes.req_body_digest = req_body_hasher.map(|h| h.finish(false));
+            r
         } else {
             self.do_forward_without_body(rsp_io).await
         };
         match r {
             Ok(_) => {
-                intercept_log!(self, "ok");
+                if self.http_notes.reqmod_responded {
+                    intercept_log!(self, "responded by icap reqmod service");
+                } else if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "ok");
+                }
             }
             Err(e) => {
                 if self.send_error_response {
@@ -295,6 +376,7 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         rsp_io: &mut HttpResponseIo<CW, UR, UW>,
         icap_adapter: HttpRequestAdapter<ServerIdleChecker>,
         adaptation_state: &mut ReqmodAdaptationRunState,
+        req_body_hasher: Option<&mut HttpBodyHasher>,
     ) -> ServerTaskResult<()>
     where
         CR: AsyncRead + Send + Unpin,
@@ -302,14 +384,22 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         UR: AsyncRead + Unpin,
         UW: AsyncWrite + Send + Unpin,
     {
+        if self.req.expect_continue() {
+            // the request body is needed by the adapter before sending to upstream,
+            // so we need to tell the client to continue by ourselves
+            self.send_local_continue(&mut rsp_io.clt_w).await?;
+        }
+
         let mut ups_w_adaptation = HttpRequestWriterForAdaptation {
             inner: &mut rsp_io.ups_w,
         };
+        // the raw body data will be hashed, as it's consumed by the adapter
+        let mut clt_body_reader = BodyHashReader::new(&mut req_io.clt_r, req_body_hasher);
         let mut adaptation_fut = icap_adapter
             .xfer(
                 adaptation_state,
                 self.req,
-                Some(&mut req_io.clt_r),
+                Some(&mut clt_body_reader),
                 &mut ups_w_adaptation,
             )
             .boxed();
@@ -324,15 +414,11 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
                         Ok(true) => {
                             // we got some data from upstream
                             let (rsp, bytes) = self.recv_response_header(&mut rsp_io.ups_r).await?;
-                            match rsp.code {
-                                100 | 103 => {
-                                    // CONTINUE | Early Hints
-                                    self.send_response_header(&mut rsp_io.clt_w, bytes).await?;
-                                }
-                                _ => {
-                                    rsp_head = Some((rsp, bytes));
-                                    break;
-                                }
+                            if is_interim_response(rsp.code) {
+                                self.send_interim_response(&mut rsp_io.clt_w, rsp.code, bytes).await?;
+                            } else {
+                                rsp_head = Some((rsp, bytes));
+                                break;
                             }
                         }
                         Ok(false) => return Err(ServerTaskError::ClosedByUpstream),
@@ -413,6 +499,7 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
             .await
             .map_err(ServerTaskError::ClientTcpWriteFailed)?;
         self.http_notes.rsp_status = rsp.status.as_u16();
+        self.http_notes.reqmod_responded = true;
 
         if let Some(mut recv_body) = rsp_recv_body {
             let mut body_reader = recv_body.body_reader();
@@ -488,6 +575,7 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         req_io: &mut HttpRequestIo<CR>,
         rsp_io: &mut HttpResponseIo<CW, UR, UW>,
         body_type: HttpBodyType,
+        body_hasher: Option<&mut HttpBodyHasher>,
     ) -> ServerTaskResult<()>
     where
         CR: AsyncRead + Unpin,
@@ -502,10 +590,20 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
             body_type,
             self.ctx.h1_interception().body_line_max_len,
         );
+        let audit_handle = self.ctx.audit_handle.clone();
+        let multipart_config = audit_handle.h1_interception().req_body_multipart.as_ref();
+        let boundary = multipart_config.and_then(|_| self.multipart_boundary());
+        let mut clt_multipart_reader = RequestBodyMultipartReader::new(
+            &mut clt_body_reader,
+            multipart_config,
+            boundary,
+            self.ctx.dry_run_block_logger("http_1"),
+        );
+        let mut clt_hash_reader = BodyHashReader::new(&mut clt_multipart_reader, body_hasher);
         let mut rsp_head: Option<(HttpTransparentResponse, Bytes)> = None;
 
         let mut clt_to_ups = LimitedCopy::new(
-            &mut clt_body_reader,
+            &mut clt_hash_reader,
             &mut rsp_io.ups_w,
             &self.ctx.server_config.limited_copy_config(),
         );
@@ -525,15 +623,11 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
                         Ok(true) => {
                             // we got some data from upstream
                             let (rsp, bytes) = self.recv_response_header(&mut rsp_io.ups_r).await?;
-                            match rsp.code {
-                                100 | 103 => {
-                                    // CONTINUE | Early Hints
-                                    self.send_response_header(&mut rsp_io.clt_w, bytes).await?;
-                                }
-                                _ => {
-                                    rsp_head = Some((rsp, bytes));
-                                    break;
-                                }
+                            if is_interim_response(rsp.code) {
+                                self.send_interim_response(&mut rsp_io.clt_w, rsp.code, bytes).await?;
+                            } else {
+                                rsp_head = Some((rsp, bytes));
+                                break;
                             }
                         }
                         Ok(false) => return Err(ServerTaskError::ClosedByUpstream),
@@ -542,7 +636,17 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
                 }
                 r = &mut clt_to_ups => {
                     r.map_err(|e| match e {
-                        LimitedCopyError::ReadFailed(e) => ServerTaskError::ClientTcpReadFailed(e),
+                        LimitedCopyError::ReadFailed(e) => {
+                            match RequestBodyPartBlocked::from_io_error(&e) {
+                                Some(blocked) => {
+                                    // the remaining request body is still pending on both connections
+                                    self.should_close = true;
+                                    intercept_log!(self, "{blocked}");
+                                    ServerTaskError::ForbiddenByRule(ServerTaskForbiddenError::BodyBlocked)
+                                }
+                                None => ServerTaskError::ClientTcpReadFailed(e),
+                            }
+                        }
                         LimitedCopyError::WriteFailed(e) => ServerTaskError::UpstreamWriteFailed(e),
                     })?;
                     self.http_notes.mark_req_send_all();
@@ -575,6 +679,9 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         }
 
         let copy_done = clt_to_ups.finished();
+        if let Some(e) = clt_multipart_reader.take_parse_error() {
+            intercept_log!(self, "request body multipart inspection aborted: {e}");
+        }
         let rsp_head = match rsp_head {
             Some(header) => {
                 if !clt_body_reader.finished() {
@@ -639,24 +746,54 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
     {
         loop {
             let (rsp, bytes) = self.recv_response_header(&mut rsp_io.ups_r).await?;
-            match rsp.code {
-                100 => {
-                    // HTTP CONTINUE
-                    self.send_response_header(&mut rsp_io.clt_w, bytes).await?;
-                    // recv the final response header
-                    return self.recv_response_header(&mut rsp_io.ups_r).await;
-                }
-                103 => {
-                    // HTTP Early Hints
-                    self.send_response_header(&mut rsp_io.clt_w, bytes).await?;
-                }
-                _ => {
-                    return Ok((rsp, bytes));
-                }
+            if is_interim_response(rsp.code) {
+                self.send_interim_response(&mut rsp_io.clt_w, rsp.code, bytes)
+                    .await?;
+            } else {
+                return Ok((rsp, bytes));
             }
         }
     }
 
+    async fn send_interim_response<CW>(
+        &mut self,
+        clt_w: &mut CW,
+        code: u16,
+        head_bytes: Bytes,
+    ) -> ServerTaskResult<()>
+    where
+        CW: AsyncWrite + Unpin,
+    {
+        if matches!(
+            self.req.version,
+            http::Version::HTTP_09 | http::Version::HTTP_10
+        ) {
+            // 1xx responses should not be sent to HTTP/1.0 clients
+            return Ok(());
+        }
+        if code == 100 {
+            if self.continue_sent {
+                return Ok(());
+            }
+            self.continue_sent = true;
+        }
+        self.send_response_header(clt_w, head_bytes).await
+    }
+
+    async fn send_local_continue<CW>(&mut self, clt_w: &mut CW) -> ServerTaskResult<()>
+    where
+        CW: AsyncWrite + Unpin,
+    {
+        if self.continue_sent {
+            return Ok(());
+        }
+        HttpProxyClientResponse::reply_continue(self.req.version, clt_w)
+            .await
+            .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+        self.continue_sent = true;
+        Ok(())
+    }
+
     async fn send_response<CW, UR, UW>(
         &mut self,
         mut rsp: HttpTransparentResponse,
@@ -679,6 +816,10 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         self.http_notes.rsp_status = 0;
         self.http_notes.mark_rsp_recv_hdr();
 
+        let rsp_head = self.inject_response_headers(&mut rsp, rsp_head);
+
+        self.check_response_content_length(&rsp)?;
+
         if let Some(respmod) = self.ctx.audit_handle.icap_respmod_client() {
             match respmod
                 .h1_adapter(
@@ -722,6 +863,45 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
             .await
     }
 
+    /// Inject the configured headers, the response header will be serialized again if changed
+    fn inject_response_headers(&self, rsp: &mut HttpTransparentResponse, rsp_head: Bytes) -> Bytes {
+        let mut injected = false;
+        for injection in &self.ctx.h1_interception().rsp_header_inject {
+            if injection.inject(rsp.code, &mut rsp.end_to_end_headers) {
+                injected = true;
+            }
+        }
+        if injected {
+            Bytes::from(rsp.serialize())
+        } else {
+            rsp_head
+        }
+    }
+
+    /// Abort early if the Content-Length of the response already exceeds the body size limit
+    fn check_response_content_length(
+        &mut self,
+        rsp: &HttpTransparentResponse,
+    ) -> ServerTaskResult<()> {
+        if let Some(HttpBodyType::ContentLength(size)) = rsp.body_type(&self.req.method) {
+            let config = self.ctx.h1_interception();
+            if config.rsp_body_max_size > 0
+                && size > config.rsp_body_max_size
+                && config.rsp_body_exceed_policy == HttpBodySizeLimitPolicy::Abort
+            {
+                let reason = format!(
+                    "response body size {size} exceeds the limit {}",
+                    config.rsp_body_max_size
+                );
+                if !self.ctx.skip_block_in_dry_run("http_1", &reason) {
+                    self.should_close = true;
+                    return Err(ServerTaskError::UpstreamAppError(anyhow!(reason)));
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn send_response_with_adaptation<CW, UR, UW>(
         &mut self,
         rsp: HttpTransparentResponse,
@@ -734,26 +914,69 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         CW: AsyncWrite + Send + Unpin,
         UW: AsyncWrite + Unpin,
     {
-        match icap_adapter
-            .xfer(
-                adaptation_state,
-                self.req,
-                &rsp,
+        let config = self.ctx.h1_interception();
+        let dry_run = self.ctx.dry_run_block_logger("http_1");
+        let body_type = rsp.body_type(&self.req.method);
+        let mut rsp_body_hasher = body_type.and_then(|_| self.new_body_hasher());
+        let decoder = body_type.and_then(|_| self.response_content_decoder(&rsp));
+        let (r, size_exceeded, decode_error) = {
+            // the body data will be hashed and decoded as it's consumed by the adapter, the original
+            // data is sent to the ICAP server, as the adapted response will replace the original one
+            let mut decompress_reader = ResponseBodyDecompressReader::new(
                 &mut rsp_io.ups_r,
-                &mut rsp_io.clt_w,
-            )
-            .await
-        {
-            Ok(RespmodAdaptationEndState::OriginalTransferred) => {
-                self.http_notes.rsp_status = rsp.code;
-                Ok(())
-            }
-            Ok(RespmodAdaptationEndState::AdaptedTransferred(adapted_rsp)) => {
-                self.http_notes.rsp_status = adapted_rsp.code;
-                Ok(())
+                body_type.unwrap_or(HttpBodyType::ContentLength(0)),
+                decoder,
+                config.rsp_body_decompress_exceed_action,
+                dry_run.is_some(),
+                rsp_body_hasher.as_mut(),
+                config.body_sha256 == Some(HttpBodyHashMode::Decoded),
+            );
+            // the limit is applied to the body bytes read from the upstream connection, and as the
+            // ICAP adapter can not send a partial body, truncate will also abort the response
+            let mut size_limit_reader = ResponseBodySizeLimitReader::new(
+                &mut decompress_reader,
+                config.rsp_body_max_size,
+                config.rsp_body_exceed_policy,
+                dry_run.is_some(),
+            );
+            let r = match icap_adapter
+                .xfer(
+                    adaptation_state,
+                    self.req,
+                    &rsp,
+                    &mut size_limit_reader,
+                    &mut rsp_io.clt_w,
+                )
+                .await
+            {
+                Ok(RespmodAdaptationEndState::OriginalTransferred) => {
+                    self.http_notes.rsp_status = rsp.code;
+                    Ok(())
+                }
+                Ok(RespmodAdaptationEndState::AdaptedTransferred(adapted_rsp)) => {
+                    self.http_notes.rsp_status = adapted_rsp.code;
+                    Ok(())
+                }
+                Err(e) => Err(e.into()),
+            };
+            let size_exceeded = size_limit_reader.exceeded();
+            (r, size_exceeded, decompress_reader.take_decode_error())
+        };
+        self.http_notes.rsp_body_digest = rsp_body_hasher.map(|mut h| {
+            if adaptation_state.ups_read_finished {
+                h.set_eof();
             }
-            Err(e) => Err(e.into()),
+            h.finish(r.is_err())
+        });
+
+        if let Some((e, blocked)) = decode_error {
+            self.handle_response_decode_error(dry_run.as_ref(), e, blocked)?;
+        }
+
+        if size_exceeded {
+            return self.handle_response_size_exceeded(dry_run.as_ref(), r);
         }
+        r
     }
 
     async fn send_response_without_adaptation<CW, UR, UW>(
@@ -770,14 +993,21 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         self.send_error_response = false;
 
         if let Some(body_type) = rsp.body_type(&self.req.method) {
+            let decoder = self.response_content_decoder(&rsp);
             self.http_notes.rsp_status = self.http_notes.origin_status; // the following function must send rsp header out
-            self.send_response_body(
-                rsp_head.into(),
-                &mut rsp_io.ups_r,
-                &mut rsp_io.clt_w,
-                body_type,
-            )
-            .await
+            let mut rsp_body_hasher = self.new_body_hasher();
+            let r = self
+                .send_response_body(
+                    rsp_head.into(),
+                    &mut rsp_io.ups_r,
+                    &mut rsp_io.clt_w,
+                    body_type,
+                    decoder,
+                    rsp_body_hasher.as_mut(),
+                )
+                .await;
+            self.http_notes.rsp_body_digest = rsp_body_hasher.map(|h| h.finish(r.is_err()));
+            r
         } else {
             self.send_response_header(&mut rsp_io.clt_w, rsp_head)
                 .await?;
@@ -787,6 +1017,46 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         }
     }
 
+    fn multipart_boundary(&self) -> Option<String> {
+        let value = self
+            .req
+            .end_to_end_headers
+            .get(http::header::CONTENT_TYPE)?;
+        multipart_boundary(value.to_str())
+    }
+
+    fn new_body_hasher(&self) -> Option<HttpBodyHasher> {
+        self.ctx
+            .h1_interception()
+            .body_sha256
+            .map(|_| HttpBodyHasher::new())
+    }
+
+    fn response_content_decoder(
+        &self,
+        rsp: &HttpTransparentResponse,
+    ) -> Option<HttpContentDecoder> {
+        let config = self.ctx.h1_interception();
+        if !config.rsp_body_decompress {
+            return None;
+        }
+        let mut values = rsp
+            .end_to_end_headers
+            .get_all(http::header::CONTENT_ENCODING)
+            .iter();
+        let value = values.next()?;
+        if values.next().is_some() {
+            // multiple content codings are not supported
+            return None;
+        }
+        let coding = HttpContentCoding::from_header_value(value.to_str())?;
+        Some(HttpContentDecoder::new(
+            coding,
+            config.rsp_body_decompress_max_size as u64,
+            config.rsp_body_decompress_max_ratio,
+        ))
+    }
+
     async fn send_response_header<CW>(
         &mut self,
         clt_w: &mut CW,
@@ -807,15 +1077,30 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         ups_r: &mut UR,
         clt_w: &mut CW,
         body_type: HttpBodyType,
+        decoder: Option<HttpContentDecoder>,
+        body_hasher: Option<&mut HttpBodyHasher>,
     ) -> ServerTaskResult<()>
     where
         UR: AsyncBufRead + Unpin,
         CW: AsyncWrite + Unpin,
     {
-        let mut body_reader = HttpBodyReader::new(
-            ups_r,
+        let config = self.ctx.h1_interception();
+        let body_reader = HttpBodyReader::new(ups_r, body_type, config.body_line_max_len);
+        let dry_run = self.ctx.dry_run_block_logger("http_1");
+        let mut size_limit_reader = ResponseBodySizeLimitReader::new(
+            body_reader,
+            config.rsp_body_max_size,
+            config.rsp_body_exceed_policy,
+            dry_run.is_some(),
+        );
+        let mut body_reader = ResponseBodyDecompressReader::new(
+            &mut size_limit_reader,
             body_type,
-            self.ctx.h1_interception().body_line_max_len,
+            decoder,
+            config.rsp_body_decompress_exceed_action,
+            dry_run.is_some(),
+            body_hasher,
+            config.body_sha256 == Some(HttpBodyHashMode::Decoded),
         );
 
         let mut ups_to_clt = LimitedCopy::with_data(
@@ -831,12 +1116,12 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
         let mut idle_count = 0;
         let max_idle_count = self.ctx.task_max_idle_count();
 
-        loop {
+        let r = loop {
             tokio::select! {
                 biased;
 
                 r = &mut ups_to_clt => {
-                    return match r {
+                    break match r {
                         Ok(_) => {
                             self.http_notes.mark_rsp_recv_all();
                             // clt_w is already flushed
@@ -875,6 +1160,222 @@ impl<'a, SC: ServerConfig> H1ForwardTask<'a, SC> {
                     }
                 }
             }
+        };
+        drop(ups_to_clt);
+
+        if let Some((e, blocked)) = body_reader.take_decode_error() {
+            self.handle_response_decode_error(dry_run.as_ref(), e, blocked)?;
+        }
+
+        if size_limit_reader.exceeded() {
+            return self.handle_response_size_exceeded(dry_run.as_ref(), r);
+        }
+        r
+    }
+
+    fn handle_response_decode_error(
+        &mut self,
+        dry_run: Option<&DryRunBlockLogger>,
+        e: HttpContentDecodeError,
+        blocked: bool,
+    ) -> ServerTaskResult<()> {
+        if blocked {
+            match dry_run {
+                Some(logger) => logger.log(&format!("response body blocked: {e}")),
+                None => {
+                    // the remaining response body is still pending on the upstream connection
+                    self.should_close = true;
+                    intercept_log!(self, "response body blocked: {e}");
+                    return Err(ServerTaskError::UpstreamAppError(anyhow!(
+                        "response body blocked: {e}"
+                    )));
+                }
+            }
+        }
+        intercept_log!(self, "response body inspection aborted: {e}");
+        Ok(())
+    }
+
+    fn handle_response_size_exceeded(
+        &mut self,
+        dry_run: Option<&DryRunBlockLogger>,
+        r: ServerTaskResult<()>,
+    ) -> ServerTaskResult<()> {
+        let max_size = self.ctx.h1_interception().rsp_body_max_size;
+        if let Some(logger) = dry_run {
+            logger.log(&format!("response body size limit {max_size} exceeded"));
+            return r;
+        }
+        // the remaining response body is still pending on the upstream connection
+        self.should_close = true;
+        match self.ctx.h1_interception().rsp_body_exceed_policy {
+            HttpBodySizeLimitPolicy::Abort => Err(ServerTaskError::UpstreamAppError(anyhow!(
+                "response body aborted as the size limit {max_size} exceeded"
+            ))),
+            HttpBodySizeLimitPolicy::Truncate => Err(ServerTaskError::UpstreamAppError(anyhow!(
+                "response body truncated at the size limit {max_size}"
+            ))),
+        }
+    }
+}
+
+/// 1xx responses except 101 (Switching Protocols) are interim ones, and more may follow
+fn is_interim_response(code: u16) -> bool {
+    (100..200).contains(&code) && code != 101
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use slog::{Drain, Logger, Never, OwnedKVList, Record};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use url::Url;
+    use yaml_rust::YamlLoader;
+
+    use g3_daemon::server::{ClientConnectionInfo, ServerQuitPolicy};
+    use g3_icap_client::{IcapMethod, IcapServiceConfig};
+    use g3_io_ext::FlexBufReader;
+    use g3_types::metrics::MetricsName;
+    use g3_types::net::UpstreamAddr;
+
+    use crate::audit::Auditor;
+    use crate::config::audit::AuditorConfig;
+    use crate::config::server::dummy_close::DummyCloseServerConfig;
+    use crate::inspect::http::H1InterceptObject;
+    use crate::inspect::StreamInspectContext;
+    use crate::module::tcp_connect::TcpConnectTaskNotes;
+    use crate::serve::{ServerTaskNotes, TcpStreamServerStats};
+
+    /// Record the messages of all the logged events
+    #[derive(Clone, Default)]
+    struct RecordDrain(Arc<Mutex<Vec<String>>>);
+
+    impl Drain for RecordDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, record: &Record, _values: &OwnedKVList) -> Result<(), Never> {
+            self.0.lock().unwrap().push(record.msg().to_string());
+            Ok(())
         }
     }
+
+    /// Run an ICAP REQMOD service that replies a 403 response to all requests
+    async fn serve_icap_block(mut stream: TcpStream) {
+        let http_rsp = b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n";
+        let mut buf = Vec::new();
+        let mut tmp = [0u8; 4096];
+        loop {
+            if buf.starts_with(b"OPTIONS ") {
+                if let Some(p) = memchr::memmem::find(&buf, b"\r\n\r\n") {
+                    buf.drain(..p + 4);
+                    stream
+                        .write_all(
+                            b"ICAP/1.0 200 OK\r\nMethods: REQMOD\r\nISTag: \"t1\"\r\n\
+                              Encapsulated: null-body=0\r\n\r\n",
+                        )
+                        .await
+                        .unwrap();
+                    continue;
+                }
+            } else if buf.starts_with(b"REQMOD ") {
+                if let Some(p) = memchr::memmem::find(&buf, b"\r\n0\r\n\r\n") {
+                    buf.drain(..p + 7);
+                    let icap_hdr = format!(
+                        "ICAP/1.0 200 OK\r\nISTag: \"t1\"\r\n\
+                         Encapsulated: res-hdr=0, null-body={}\r\n\r\n",
+                        http_rsp.len()
+                    );
+                    stream.write_all(icap_hdr.as_bytes()).await.unwrap();
+                    stream.write_all(http_rsp).await.unwrap();
+                    continue;
+                }
+            }
+            match stream.read(&mut tmp).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&tmp[..n]),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reqmod_block_logged_when_not_sampled() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let icap_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_icap_block(stream));
+            }
+        });
+
+        let yaml = YamlLoader::load_from_str("{name: test}").unwrap();
+        let mut auditor_config = AuditorConfig::new(None);
+        auditor_config.parse(yaml[0].as_hash().unwrap()).unwrap();
+        let icap_url = Url::from_str(&format!("icap://{icap_addr}/reqmod")).unwrap();
+        let icap_config = IcapServiceConfig::new(IcapMethod::Reqmod, icap_url).unwrap();
+        auditor_config.icap_reqmod_service = Some(Arc::new(icap_config));
+        let records = RecordDrain::default();
+        let logger = Logger::root(records.clone(), slog::o!());
+
+        let name = MetricsName::from_str("test").unwrap();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 80);
+        let upstream = UpstreamAddr::from_str("example.net:80").unwrap();
+        let task_notes =
+            ServerTaskNotes::new(ClientConnectionInfo::new(addr, addr), None, Duration::ZERO);
+        let mut ctx = StreamInspectContext::new(
+            Auditor::build_test_handle_with_intercept_logger(auditor_config, logger),
+            Arc::new(DummyCloseServerConfig::new(&name, None)),
+            Arc::new(TcpStreamServerStats::new(&name)),
+            Arc::new(ServerQuitPolicy::default()),
+            &task_notes,
+            &TcpConnectTaskNotes::new(upstream),
+        );
+        ctx.set_intercept_log_sampled(false);
+
+        let (clt_io, mut clt_peer) = tokio::io::duplex(4096);
+        let (ups_io, mut ups_peer) = tokio::io::duplex(4096);
+        let (clt_r, clt_w) = tokio::io::split(clt_io);
+        let (ups_r, ups_w) = tokio::io::split(ups_io);
+        let mut h1_obj = H1InterceptObject::new(ctx);
+        h1_obj.set_io(
+            FlexBufReader::new(Box::new(clt_r)),
+            Box::new(clt_w),
+            Box::new(ups_r),
+            Box::new(ups_w),
+        );
+        let intercept = tokio::spawn(h1_obj.intercept());
+
+        clt_peer
+            .write_all(b"POST /upload HTTP/1.1\r\nHost: example.net\r\nContent-Length: 5\r\n\r\nhello")
+            .await
+            .unwrap();
+        let mut rsp = Vec::new();
+        let mut tmp = [0u8; 1024];
+        while memchr::memmem::find(&rsp, b"\r\n\r\n").is_none() {
+            let n = clt_peer.read(&mut tmp).await.unwrap();
+            assert_ne!(n, 0, "unexpected eof");
+            rsp.extend_from_slice(&tmp[..n]);
+        }
+        assert!(rsp.starts_with(b"HTTP/1.1 403 "));
+
+        let r = tokio::time::timeout(Duration::from_secs(4), intercept)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(r.unwrap().is_none());
+
+        // nothing should be sent to upstream
+        drop(clt_peer);
+        let mut ups_buf = [0u8; 16];
+        let r = tokio::time::timeout(Duration::from_millis(100), ups_peer.read(&mut ups_buf)).await;
+        assert!(!matches!(r, Ok(Ok(n)) if n > 0));
+
+        let logs = records.0.lock().unwrap();
+        assert_eq!(logs.as_slice(), ["responded by icap reqmod service"]);
+    }
 }
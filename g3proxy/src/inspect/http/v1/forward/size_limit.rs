@@ -0,0 +1,241 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+use g3_dpi::HttpBodySizeLimitPolicy;
+
+/// Limit the size of the on-the-wire response body, which includes the chunked encoding data.
+///
+/// No more than `max_size` bytes will be returned. If there is more data, an error will be
+/// returned for the abort policy, or EOF will be returned for the truncate policy.
+///
+/// In dry-run mode, all data will be returned and only the exceeded flag will be set.
+///
+/// The buffered read interface can be used on the upstream connection reader directly, which is
+/// needed when the response body is sent to the ICAP RESPMOD service.
+pub(super) struct ResponseBodySizeLimitReader<R> {
+    inner: R,
+    max_size: u64,
+    policy: HttpBodySizeLimitPolicy,
+    dry_run: bool,
+    read_size: u64,
+    exceeded: bool,
+}
+
+impl<R> ResponseBodySizeLimitReader<R> {
+    pub(super) fn new(
+        inner: R,
+        max_size: u64,
+        policy: HttpBodySizeLimitPolicy,
+        dry_run: bool,
+    ) -> Self {
+        ResponseBodySizeLimitReader {
+            inner,
+            max_size,
+            policy,
+            dry_run,
+            read_size: 0,
+            exceeded: false,
+        }
+    }
+
+    /// Check if the response body has been cut off because of the size limit,
+    /// or would have been in dry-run mode
+    #[inline]
+    pub(super) fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+impl<R> AsyncRead for ResponseBodySizeLimitReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.max_size == 0 {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        if this.dry_run {
+            let filled = buf.filled().len();
+            ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+            this.read_size += (buf.filled().len() - filled) as u64;
+            if this.read_size > this.max_size {
+                this.exceeded = true;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        if this.exceeded {
+            // the body has been truncated
+            return Poll::Ready(Ok(()));
+        }
+
+        let left = this.max_size - this.read_size;
+        if left == 0 {
+            // check if there is any data left after the limit
+            let filled = buf.filled().len();
+            ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+            if buf.filled().len() == filled {
+                return Poll::Ready(Ok(()));
+            }
+            buf.set_filled(filled);
+            this.exceeded = true;
+            return match this.policy {
+                HttpBodySizeLimitPolicy::Abort => {
+                    Poll::Ready(Err(io::Error::other("response body size limit exceeded")))
+                }
+                HttpBodySizeLimitPolicy::Truncate => Poll::Ready(Ok(())),
+            };
+        }
+
+        let len = usize::try_from(left)
+            .unwrap_or(usize::MAX)
+            .min(buf.remaining());
+        let mut limited_buf = ReadBuf::new(buf.initialize_unfilled_to(len));
+        ready!(Pin::new(&mut this.inner).poll_read(cx, &mut limited_buf))?;
+        let nr = limited_buf.filled().len();
+        buf.advance(nr);
+        this.read_size += nr as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R> AsyncBufRead for ResponseBodySizeLimitReader<R>
+where
+    R: AsyncBufRead + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.max_size == 0 || this.dry_run {
+            return Pin::new(&mut this.inner).poll_fill_buf(cx);
+        }
+        if this.exceeded {
+            // the body has been truncated
+            return Poll::Ready(Ok(&[]));
+        }
+
+        let left = this.max_size - this.read_size;
+        let buf = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(buf));
+        }
+        if left == 0 {
+            this.exceeded = true;
+            return match this.policy {
+                HttpBodySizeLimitPolicy::Abort => {
+                    Poll::Ready(Err(io::Error::other("response body size limit exceeded")))
+                }
+                HttpBodySizeLimitPolicy::Truncate => Poll::Ready(Ok(&[])),
+            };
+        }
+        let len = usize::try_from(left).unwrap_or(usize::MAX).min(buf.len());
+        Poll::Ready(Ok(&buf[..len]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.read_size += amt as u64;
+        if this.dry_run && this.max_size > 0 && this.read_size > this.max_size {
+            this.exceeded = true;
+        }
+        Pin::new(&mut this.inner).consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    #[tokio::test]
+    async fn within_limit() {
+        let data: &[u8] = b"0123456789";
+        let mut reader = ResponseBodySizeLimitReader::new(data, 10, Default::default(), false);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, data);
+        assert!(!reader.exceeded());
+    }
+
+    #[tokio::test]
+    async fn abort() {
+        let data: &[u8] = b"0123456789";
+        let mut reader =
+            ResponseBodySizeLimitReader::new(data, 4, HttpBodySizeLimitPolicy::Abort, false);
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).await.is_err());
+        assert_eq!(buf, b"0123");
+        assert!(reader.exceeded());
+    }
+
+    #[tokio::test]
+    async fn truncate() {
+        let data: &[u8] = b"0123456789";
+        let mut reader =
+            ResponseBodySizeLimitReader::new(data, 4, HttpBodySizeLimitPolicy::Truncate, false);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"0123");
+        assert!(reader.exceeded());
+    }
+
+    #[tokio::test]
+    async fn buf_read() {
+        let data: &[u8] = b"0123456789";
+        let mut reader =
+            ResponseBodySizeLimitReader::new(data, 4, HttpBodySizeLimitPolicy::Abort, false);
+        assert_eq!(reader.fill_buf().await.unwrap(), b"0123");
+        reader.consume(2);
+        assert_eq!(reader.fill_buf().await.unwrap(), b"23");
+        reader.consume(2);
+        assert!(reader.fill_buf().await.is_err());
+        assert!(reader.exceeded());
+
+        let mut reader =
+            ResponseBodySizeLimitReader::new(data, 4, HttpBodySizeLimitPolicy::Truncate, false);
+        let mut buf = Vec::new();
+        reader.read_until(b'9', &mut buf).await.unwrap();
+        assert_eq!(buf, b"0123");
+        assert!(reader.exceeded());
+
+        let mut reader =
+            ResponseBodySizeLimitReader::new(data, 4, HttpBodySizeLimitPolicy::Abort, true);
+        let mut buf = Vec::new();
+        reader.read_until(b'9', &mut buf).await.unwrap();
+        assert_eq!(buf, data);
+        assert!(reader.exceeded());
+    }
+
+    #[tokio::test]
+    async fn dry_run() {
+        let data: &[u8] = b"0123456789";
+        let mut reader =
+            ResponseBodySizeLimitReader::new(data, 4, HttpBodySizeLimitPolicy::Abort, true);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, data);
+        assert!(reader.exceeded());
+    }
+}
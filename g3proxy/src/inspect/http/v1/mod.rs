@@ -71,7 +71,8 @@ pub(crate) struct H1InterceptObject<SC: ServerConfig> {
 }
 
 impl<SC: ServerConfig> H1InterceptObject<SC> {
-    pub(crate) fn new(ctx: StreamInspectContext<SC>) -> Self {
+    pub(crate) fn new(mut ctx: StreamInspectContext<SC>) -> Self {
+        ctx.set_protocol_idle_timeout(Protocol::Http1);
         H1InterceptObject {
             io: None,
             ctx,
@@ -114,7 +115,9 @@ where
     pub(crate) async fn intercept(mut self) -> ServerTaskResult<Option<StreamInspection<SC>>> {
         match self.do_intercept().await {
             Ok(v) => {
-                intercept_log!(self, "finished");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished");
+                }
                 Ok(v)
             }
             Err(e) => {
@@ -175,8 +178,10 @@ where
                 }
                 HttpRecvRequest::RequestWithoutIo(r) => {
                     let mut forward_task = H1ForwardTask::new(self.ctx.clone(), &r, self.req_id);
-                    // not ICAP in this case
-                    forward_task.forward_without_body(&mut rsp_io).await;
+                    if !forward_task.reply_if_blocked(&mut rsp_io.clt_w).await {
+                        // not ICAP in this case
+                        forward_task.forward_without_body(&mut rsp_io).await;
+                    }
                     pipeline_stats.del_task();
                     if forward_task.should_close() {
                         req_acceptor.close();
@@ -229,7 +234,11 @@ where
                     } else {
                         let mut forward_task =
                             H1ForwardTask::new(self.ctx.clone(), &r, self.req_id);
-                        if let Some(reqmod_client) = self.ctx.audit_handle.icap_reqmod_client() {
+                        if forward_task.reply_if_blocked(&mut rsp_io.clt_w).await {
+                            // the request body is left unread, so the connection will be closed
+                        } else if let Some(reqmod_client) =
+                            self.ctx.audit_handle.icap_reqmod_client()
+                        {
                             forward_task
                                 .adapt_with_io(&mut req_io, &mut rsp_io, reqmod_client)
                                 .await;
@@ -154,6 +154,7 @@ where
                     HttpTransparentRequest::parse(
                         &mut io.clt_r,
                         http_config.req_head_max_size,
+                        http_config.req_head_max_count,
                         http_config.steal_forwarded_for,
                     ),
                 )
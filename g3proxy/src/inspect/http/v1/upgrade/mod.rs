@@ -14,17 +14,19 @@
  * limitations under the License.
  */
 
+use std::cell::Cell;
+use std::str::FromStr;
 use std::time::Duration;
 
 use anyhow::anyhow;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use http::{StatusCode, Version};
+use http::{header, StatusCode, Version};
 use slog::slog_info;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::time::Instant;
 
-use g3_dpi::Protocol;
+use g3_dpi::{Protocol, ProtocolInspectAction};
 use g3_http::client::HttpTransparentResponse;
 use g3_http::server::{HttpTransparentRequest, UriExt};
 use g3_http::{HttpBodyReader, HttpBodyType};
@@ -35,11 +37,15 @@ use g3_icap_client::reqmod::h1::{
 use g3_icap_client::reqmod::IcapReqmodClient;
 use g3_io_ext::{LimitedCopy, LimitedCopyError, LimitedWriteExt, OnceBufReader};
 use g3_slog_types::{LtDateTime, LtDuration, LtHttpUri, LtUpstreamAddr, LtUuid};
-use g3_types::net::{HttpUpgradeToken, UpstreamAddr, WebSocketNotes};
+use g3_types::net::{
+    HttpBlockResponse, HttpUpgradeToken, UpstreamAddr, WebSocketHandshakeValidator, WebSocketNotes,
+};
 
 use super::{H1InterceptionError, HttpRequest, HttpRequestIo, HttpResponseIo};
 use crate::config::server::ServerConfig;
-use crate::inspect::{BoxAsyncRead, BoxAsyncWrite, StreamInspectContext, StreamInspection};
+use crate::inspect::{
+    BoxAsyncRead, BoxAsyncWrite, InspectPolicyProtocol, StreamInspectContext, StreamInspection,
+};
 use crate::log::inspect::stream::StreamInspectLog;
 use crate::log::inspect::InspectSource;
 use crate::module::http_forward::HttpProxyClientResponse;
@@ -73,6 +79,8 @@ struct HttpForwardTaskNotes {
     dur_req_send_hdr: Duration,
     dur_req_pipeline: Duration,
     dur_rsp_recv_hdr: Duration,
+    /// set if the response is generated by the ICAP REQMOD service, which is usually a block page
+    reqmod_responded: bool,
 }
 
 impl HttpForwardTaskNotes {
@@ -86,6 +94,7 @@ impl HttpForwardTaskNotes {
             dur_req_send_hdr: Duration::default(),
             dur_req_pipeline,
             dur_rsp_recv_hdr: Duration::default(),
+            reqmod_responded: false,
         }
     }
 
@@ -106,6 +115,21 @@ pub(super) struct H1UpgradeTask<SC: ServerConfig> {
     should_close: bool,
     http_notes: HttpForwardTaskNotes,
     ws_notes: Option<WebSocketNotes>,
+    ws_validator: Option<WebSocketHandshakeValidator>,
+}
+
+fn websocket_requested(req: &HttpTransparentRequest) -> bool {
+    req.hop_by_hop_headers
+        .get_all(header::UPGRADE)
+        .iter()
+        .any(|v| {
+            v.to_str().split(',').any(|s| {
+                matches!(
+                    HttpUpgradeToken::from_str(s.trim()),
+                    Ok(HttpUpgradeToken::Websocket)
+                )
+            })
+        })
 }
 
 impl<SC> H1UpgradeTask<SC>
@@ -122,6 +146,7 @@ where
             should_close: false,
             http_notes,
             ws_notes: None,
+            ws_validator: None,
         }
     }
 
@@ -159,27 +184,56 @@ where
         }
     }
 
+    async fn reply_block_response<CW>(&mut self, rsp: &HttpBlockResponse, clt_w: &mut CW)
+    where
+        CW: AsyncWrite + Unpin,
+    {
+        self.should_close = true;
+        let buf = rsp.serialize_h1(self.req.version);
+        if clt_w.write_all_flush(&buf).await.is_ok() {
+            self.http_notes.rsp_status = rsp.status().as_u16();
+        }
+    }
+
     async fn check_blocked<CW>(&mut self, clt_w: &mut CW) -> ServerTaskResult<()>
     where
         CW: AsyncWrite + Unpin,
     {
+        let block_response = Cell::new(None);
         match self.req.retain_upgrade_token(|req, p| {
             if matches!(p, HttpUpgradeToken::Websocket) {
                 let Some(http_host) = &req.host else {
                     return false;
                 };
-                return !self
-                    .ctx
-                    .websocket_inspect_action(http_host.host())
-                    .is_block();
+                let action = self.ctx.effective_inspect_action(
+                    &self.ctx.audit_handle.websocket_inspect_policy,
+                    http_host.host(),
+                );
+                if matches!(action, ProtocolInspectAction::BlockWithResponse) {
+                    block_response.set(Some(self.ctx.websocket_block_response(http_host.host())));
+                }
+                if action.is_block() {
+                    // the websocket stream would be inspected at the next depth
+                    self.ctx.add_inspect_decision(
+                        InspectPolicyProtocol::Websocket,
+                        action,
+                        self.ctx.inspection_depth + 1,
+                    );
+                    return false;
+                }
+                return true;
             } else if matches!(p, HttpUpgradeToken::ConnectIp) {
                 return false;
             }
             true
         }) {
             Some(0) => {
-                self.reply_fatal(HttpProxyClientResponse::forbidden(self.req.version), clt_w)
-                    .await;
+                if let Some(rsp) = block_response.take() {
+                    self.reply_block_response(&rsp, clt_w).await;
+                } else {
+                    self.reply_fatal(HttpProxyClientResponse::forbidden(self.req.version), clt_w)
+                        .await;
+                }
                 Err(ServerTaskError::InternalAdapterError(anyhow!(
                     "upgrade protocol blocked by inspection policy"
                 )))
@@ -209,7 +263,9 @@ where
     {
         match self.do_forward_original(rsp_io).await {
             Ok(v) => {
-                intercept_log!(self, &v, "ok");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, &v, "ok");
+                }
                 v
             }
             Err(e) => {
@@ -247,7 +303,11 @@ where
     {
         match self.do_forward_icap(rsp_io, reqmod_client).await {
             Ok(v) => {
-                intercept_log!(self, &v, "ok");
+                if self.http_notes.reqmod_responded {
+                    intercept_log!(self, &v, "responded by icap reqmod service");
+                } else if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, &v, "ok");
+                }
                 v
             }
             Err(e) => {
@@ -343,6 +403,7 @@ where
             .await
             .map_err(ServerTaskError::ClientTcpWriteFailed)?;
         self.http_notes.rsp_status = rsp.status.as_u16();
+        self.http_notes.reqmod_responded = true;
 
         if let Some(mut recv_body) = rsp_recv_body {
             let mut body_reader = recv_body.body_reader();
@@ -370,7 +431,7 @@ where
 
     async fn send_request<CW, UR, UW>(
         &mut self,
-        adapted_req: Option<HttpTransparentRequest>,
+        mut adapted_req: Option<HttpTransparentRequest>,
         rsp_io: &mut HttpResponseIo<CW, UR, UW>,
     ) -> ServerTaskResult<Option<(HttpUpgradeToken, UpstreamAddr)>>
     where
@@ -378,7 +439,20 @@ where
         UR: AsyncRead + Unpin,
         UW: AsyncWrite + Unpin,
     {
-        let req = adapted_req.as_ref().unwrap_or(&self.req);
+        let strict_ws_handshake = self.ctx.websocket_interception().strict_handshake;
+        let req = match &mut adapted_req {
+            Some(req) => req,
+            None => &mut self.req,
+        };
+        if strict_ws_handshake && websocket_requested(req) {
+            let validator = WebSocketHandshakeValidator::check_request(&mut req.end_to_end_headers)
+                .map_err(|e| {
+                    ServerTaskError::ClientAppError(anyhow!(
+                        "invalid websocket handshake request: {e}"
+                    ))
+                })?;
+            self.ws_validator = Some(validator);
+        }
         let head_bytes = req.serialize_for_origin();
         rsp_io
             .ups_w
@@ -421,7 +495,7 @@ where
     async fn send_response<CW, UR, UW>(
         &mut self,
         mut rsp: HttpTransparentResponse,
-        rsp_head: Bytes,
+        mut rsp_head: Bytes,
         rsp_io: &mut HttpResponseIo<CW, UR, UW>,
     ) -> ServerTaskResult<Option<(HttpUpgradeToken, UpstreamAddr)>>
     where
@@ -429,9 +503,26 @@ where
         UR: AsyncRead + Unpin,
         UW: AsyncWrite + Unpin,
     {
-        self.send_error_response = false;
         self.http_notes.origin_status = rsp.code;
         self.http_notes.mark_rsp_recv_hdr();
+        if rsp.code == StatusCode::SWITCHING_PROTOCOLS
+            && matches!(rsp.upgrade, Some(HttpUpgradeToken::Websocket))
+        {
+            if let Some(validator) = &self.ws_validator {
+                // reject before the relay start, and the client will get a 502 response
+                match validator.check_response(&mut rsp.end_to_end_headers) {
+                    Ok(true) => rsp_head = Bytes::from(rsp.serialize()),
+                    Ok(false) => {}
+                    Err(e) => {
+                        self.should_close = true;
+                        return Err(ServerTaskError::UpstreamAppError(anyhow!(
+                            "invalid websocket handshake response: {e}"
+                        )));
+                    }
+                }
+            }
+        }
+        self.send_error_response = false;
         if !rsp.keep_alive() {
             self.should_close = true;
         }
@@ -587,6 +678,7 @@ where
                 let mut ws_notes = self.ws_notes.unwrap();
                 ws_notes.append_request_headers(self.req.end_to_end_headers.drain());
                 StreamInspectLog::new(&ctx).log(InspectSource::HttpUpgrade, Protocol::Websocket);
+                ctx.set_protocol_tcp_no_delay(Protocol::Websocket);
                 let mut websocket_obj = crate::inspect::websocket::H1WebsocketInterceptObject::new(
                     ctx, upstream, ws_notes,
                 );
@@ -23,15 +23,15 @@ use h2::{RecvStream, StreamId};
 use http::{header, Request, Response, StatusCode, Version};
 use slog::slog_info;
 
-use g3_dpi::Protocol;
+use g3_dpi::{Protocol, ProtocolInspectAction};
 use g3_h2::{H2StreamReader, H2StreamWriter};
 use g3_http::server::UriExt;
 use g3_slog_types::{LtDateTime, LtDuration, LtH2StreamId, LtUpstreamAddr, LtUuid};
-use g3_types::net::{HttpUpgradeToken, UpstreamAddr, WebSocketNotes};
+use g3_types::net::{Host, HttpUpgradeToken, UpstreamAddr, WebSocketNotes};
 
 use super::{ExchangeHead, H2StreamTransferError, HttpForwardTaskNotes};
 use crate::config::server::ServerConfig;
-use crate::inspect::StreamInspectContext;
+use crate::inspect::{InspectPolicyProtocol, StreamInspectContext};
 use crate::log::inspect::{stream::StreamInspectLog, InspectSource};
 
 macro_rules! intercept_log {
@@ -122,6 +122,18 @@ where
         }
     }
 
+    fn reply_block_response(&mut self, host: &Host, mut clt_send_rsp: SendResponse<Bytes>) {
+        let block_rsp = self.ctx.websocket_block_response(host);
+        let rsp = block_rsp.build_h2_response();
+        let body = block_rsp.body();
+        if let Ok(mut send_stream) = clt_send_rsp.send_response(rsp, body.is_empty()) {
+            self.http_notes.rsp_status = block_rsp.status().as_u16();
+            if !body.is_empty() {
+                let _ = send_stream.send_data(body.clone(), true);
+            }
+        }
+    }
+
     pub(crate) async fn into_running(
         mut self,
         clt_req: Request<RecvStream>,
@@ -178,14 +190,31 @@ where
             }
         };
 
-        if self
-            .ctx
-            .websocket_inspect_action(upstream.host())
-            .is_block()
-        {
-            self.reply_forbidden(clt_send_rsp);
-            intercept_log!(self, "websocket blocked by inspection policy");
-            return;
+        // the action will be checked and logged again by the websocket interception
+        let action = self.ctx.effective_inspect_action(
+            &self.ctx.audit_handle.websocket_inspect_policy,
+            upstream.host(),
+        );
+        if action.is_block() {
+            // the websocket stream would be inspected at the next depth
+            self.ctx.add_inspect_decision(
+                InspectPolicyProtocol::Websocket,
+                action,
+                self.ctx.inspection_depth + 1,
+            );
+        }
+        match action {
+            ProtocolInspectAction::Block => {
+                self.reply_forbidden(clt_send_rsp);
+                intercept_log!(self, "websocket blocked by inspection policy");
+                return;
+            }
+            ProtocolInspectAction::BlockWithResponse => {
+                self.reply_block_response(upstream.host(), clt_send_rsp);
+                intercept_log!(self, "websocket blocked by inspection policy");
+                return;
+            }
+            _ => {}
         }
 
         let mut ws_notes = WebSocketNotes::new(clt_req.uri().clone());
@@ -198,7 +227,9 @@ where
         self.ups_stream_id = exchange_head.ups_stream_id.take();
         match exchange_head_result {
             Ok(Some((clt_r, clt_w, ups_r, ups_w))) => {
-                intercept_log!(self, "ok");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "ok");
+                }
 
                 self.ctx.increase_inspection_depth();
                 StreamInspectLog::new(&self.ctx)
@@ -209,7 +240,11 @@ where
                 websocket_obj.intercept(clt_r, clt_w, ups_r, ups_w).await;
             }
             Ok(None) => {
-                intercept_log!(self, "finished without data");
+                if self.http_notes.reqmod_responded {
+                    intercept_log!(self, "responded by icap reqmod service");
+                } else if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished without data");
+                }
             }
             Err(e) => {
                 intercept_log!(self, "head transfer error: {e}");
@@ -246,7 +281,9 @@ where
         self.ups_stream_id = exchange_head.ups_stream_id.take();
         match exchange_head_result {
             Ok(Some((clt_r, clt_w, ups_r, ups_w))) => {
-                intercept_log!(self, "ok");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "ok");
+                }
 
                 self.ctx.increase_inspection_depth();
                 StreamInspectLog::new(&self.ctx)
@@ -261,12 +298,16 @@ where
                 // Just treat it as unknown. Unknown protocol should be forbidden if needed.
                 if let Err(e) = self.ctx.transit_unknown(clt_r, clt_w, ups_r, ups_w).await {
                     intercept_log!(self, "stream transfer error: {e}");
-                } else {
+                } else if self.ctx.intercept_log_sampled() {
                     intercept_log!(self, "finished");
                 }
             }
             Ok(None) => {
-                intercept_log!(self, "finished without data");
+                if self.http_notes.reqmod_responded {
+                    intercept_log!(self, "responded by icap reqmod service");
+                } else if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished without data");
+                }
             }
             Err(e) => {
                 intercept_log!(self, "head transfer error: {e}");
@@ -52,6 +52,8 @@ struct HttpForwardTaskNotes {
     started_datetime: DateTime<Utc>,
     dur_req_send_hdr: Duration,
     dur_rsp_recv_hdr: Duration,
+    /// set if the response is generated by the ICAP REQMOD service, which is usually a block page
+    reqmod_responded: bool,
 }
 
 impl Default for HttpForwardTaskNotes {
@@ -64,6 +66,7 @@ impl Default for HttpForwardTaskNotes {
             started_ins: Instant::now(),
             dur_req_send_hdr: Duration::default(),
             dur_rsp_recv_hdr: Duration::default(),
+            reqmod_responded: false,
         }
     }
 }
@@ -261,6 +264,7 @@ impl<'a, SC: ServerConfig> ExchangeHead<'a, SC> {
 
         self.send_error_response = false;
         let rsp_status = response.status().as_u16();
+        self.http_notes.reqmod_responded = true;
         if let Some(mut recv_body) = rsp_recv_body {
             let mut clt_send_stream = clt_send_rsp
                 .send_response(response, false)
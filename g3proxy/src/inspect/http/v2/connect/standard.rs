@@ -107,13 +107,19 @@ where
         self.ups_stream_id = exchange_head.ups_stream_id.take();
         match exchange_head_result {
             Ok(Some((clt_r, clt_w, ups_r, ups_w))) => {
-                intercept_log!(self, "started");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "started");
+                }
 
                 self.run_standard_transfer(upstream, clt_r, clt_w, ups_r, ups_w)
                     .await;
             }
             Ok(None) => {
-                intercept_log!(self, "finished without data");
+                if self.http_notes.reqmod_responded {
+                    intercept_log!(self, "responded by icap reqmod service");
+                } else if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished without data");
+                }
             }
             Err(e) => {
                 intercept_log!(self, "head exchange error: {e}");
@@ -146,7 +152,7 @@ where
         .await
         {
             intercept_log!(self, "data transfer error: {e}");
-        } else {
+        } else if self.ctx.intercept_log_sampled() {
             intercept_log!(self, "finished");
         }
     }
@@ -98,12 +98,20 @@ pub(crate) enum H2StreamTransferError {
     ResponseHeadSendFailed(h2::Error),
     #[error("failed to transfer request body: {0}")]
     RequestBodyTransferFailed(H2StreamBodyTransferError),
+    #[error("request body blocked: {0}")]
+    RequestBodyBlocked(String),
     #[error("failed to transfer response body: {0}")]
     ResponseBodyTransferFailed(H2StreamBodyTransferError),
+    #[error("response body size {0} exceeds the limit {1}")]
+    ResponseBodyTooLarge(u64, u64),
     #[error("canceled as user blocked")]
     CanceledAsUserBlocked,
     #[error("canceled as server quit")]
     CanceledAsServerQuit,
+    #[error("read from http client failed: {0:?}")]
+    HttpClientReadFailed(io::Error),
+    #[error("write to http upstream failed: {0:?}")]
+    HttpUpstreamWriteFailed(io::Error),
     #[error("read from http client idle")]
     HttpClientReadIdle,
     #[error("write to http client idle")]
@@ -128,6 +136,8 @@ impl H2StreamTransferError {
             H2StreamTransferError::InvalidHostHeader => StatusCode::BAD_REQUEST,
             H2StreamTransferError::ResponseHeadRecvFailed(_) => StatusCode::BAD_GATEWAY,
             H2StreamTransferError::ResponseHeadRecvTimeout => StatusCode::GATEWAY_TIMEOUT,
+            H2StreamTransferError::ResponseBodyTooLarge(_, _) => StatusCode::BAD_GATEWAY,
+            H2StreamTransferError::RequestBodyBlocked(_) => StatusCode::FORBIDDEN,
             _ => return None,
         };
         let rsp = Response::builder()
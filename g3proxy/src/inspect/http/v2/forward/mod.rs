@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::str::FromStr;
 use std::time::Duration;
 
 use anyhow::anyhow;
@@ -24,9 +25,15 @@ use h2::server::SendResponse;
 use h2::{Reason, RecvStream, StreamId};
 use http::{HeaderValue, Method, Request, Response, StatusCode, Uri, Version};
 use slog::slog_info;
+use tokio::io::AsyncWriteExt;
 use tokio::time::Instant;
 
-use g3_h2::{H2StreamBodyTransferError, H2StreamFromChunkedTransferError, RequestExt};
+use g3_dpi::{HttpBodySizeLimitPolicy, HttpMultipartInspectionConfig};
+use g3_h2::{
+    H2StreamBodyTransferError, H2StreamFromChunkedTransferError, H2StreamReader, H2StreamWriter,
+    RequestExt,
+};
+use g3_http::multipart_boundary;
 use g3_icap_client::reqmod::h2::{
     H2RequestAdapter, HttpAdapterErrorResponse, ReqmodAdaptationEndState, ReqmodAdaptationRunState,
     ReqmodRecvHttpResponseBody,
@@ -34,6 +41,7 @@ use g3_icap_client::reqmod::h2::{
 use g3_icap_client::respmod::h2::{
     H2ResponseAdapter, RespmodAdaptationEndState, RespmodAdaptationRunState,
 };
+use g3_io_ext::{LimitedCopy, LimitedCopyError};
 use g3_slog_types::{
     LtDateTime, LtDuration, LtH2StreamId, LtHttpHeaderValue, LtHttpMethod, LtHttpUri, LtUuid,
 };
@@ -41,6 +49,10 @@ use g3_types::net::HttpHeaderMap;
 
 use super::{H2BodyTransfer, H2StreamTransferError};
 use crate::config::server::ServerConfig;
+use crate::inspect::http::body::{
+    BodyHashReader, HttpBodyDigest, HttpBodyHasher, RequestBodyMultipartReader,
+    RequestBodyPartBlocked,
+};
 use crate::inspect::StreamInspectContext;
 use crate::serve::ServerIdleChecker;
 
@@ -63,6 +75,12 @@ macro_rules! intercept_log {
             "dur_req_send_all" => LtDuration($obj.http_notes.dur_req_send_all),
             "dur_rsp_recv_hdr" => LtDuration($obj.http_notes.dur_rsp_recv_hdr),
             "dur_rsp_recv_all" => LtDuration($obj.http_notes.dur_rsp_recv_all),
+            "req_body_sha256" => $obj.http_notes.req_body_digest.as_ref().map(|d| d.sha256_hex()),
+            "req_body_size" => $obj.http_notes.req_body_digest.as_ref().map(|d| d.size()),
+            "req_body_hash_partial" => $obj.http_notes.req_body_digest.as_ref().map(|d| d.partial()),
+            "rsp_body_sha256" => $obj.http_notes.rsp_body_digest.as_ref().map(|d| d.sha256_hex()),
+            "rsp_body_size" => $obj.http_notes.rsp_body_digest.as_ref().map(|d| d.size()),
+            "rsp_body_hash_partial" => $obj.http_notes.rsp_body_digest.as_ref().map(|d| d.partial()),
         )
     };
 }
@@ -80,6 +98,10 @@ struct HttpForwardTaskNotes {
     dur_rsp_recv_hdr: Duration,
     dur_rsp_recv_all: Duration,
     host_header: Option<HeaderValue>,
+    req_body_digest: Option<HttpBodyDigest>,
+    rsp_body_digest: Option<HttpBodyDigest>,
+    /// set if the response is generated by the ICAP REQMOD service, which is usually a block page
+    reqmod_responded: bool,
 }
 
 impl HttpForwardTaskNotes {
@@ -97,6 +119,9 @@ impl HttpForwardTaskNotes {
             dur_rsp_recv_hdr: Duration::default(),
             dur_rsp_recv_all: Duration::default(),
             host_header,
+            req_body_digest: None,
+            rsp_body_digest: None,
+            reqmod_responded: false,
         }
     }
 
@@ -201,7 +226,9 @@ where
                 self.reply_task_err(clt_send_rsp, &e);
             }
             intercept_log!(self, "{e}");
-        } else {
+        } else if self.http_notes.reqmod_responded {
+            intercept_log!(self, "responded by icap reqmod service");
+        } else if self.ctx.intercept_log_sampled() {
             intercept_log!(self, "finished");
         }
     }
@@ -352,6 +379,7 @@ where
 
         self.send_error_response = false;
         let rsp_status = response.status().as_u16();
+        self.http_notes.reqmod_responded = true;
         if let Some(mut recv_body) = rsp_recv_body {
             let mut clt_send_stream = clt_send_rsp
                 .send_response(response, false)
@@ -441,6 +469,27 @@ where
         clt_body: RecvStream,
         clt_send_rsp: &mut SendResponse<Bytes>,
     ) -> Result<(), H2StreamTransferError> {
+        let audit_handle = self.ctx.audit_handle.clone();
+        if let Some(multipart_config) = audit_handle.h1_interception().req_body_multipart.as_ref() {
+            if let Some(boundary) = ups_req
+                .headers()
+                .get(http::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(multipart_boundary)
+            {
+                return self
+                    .forward_with_multipart_body(
+                        ups_send_req,
+                        ups_req,
+                        clt_body,
+                        clt_send_rsp,
+                        multipart_config,
+                        boundary,
+                    )
+                    .await;
+            }
+        }
+
         let orig_req = ups_req.clone_header();
 
         let (mut ups_rsp_fut, ups_send_stream) = ups_send_req
@@ -454,6 +503,13 @@ where
             ups_send_stream,
             self.ctx.server_config.limited_copy_config().yield_size(),
         );
+        let mut req_body_hasher = self.new_body_hasher();
+        let mut hash_data = req_body_hasher
+            .as_mut()
+            .map(|hasher| |data: &[u8]| hasher.update(data));
+        if let Some(f) = &mut hash_data {
+            req_body_transfer.set_data_observer(f);
+        }
 
         let idle_duration = self.ctx.server_config.task_idle_check_duration();
         let mut idle_interval =
@@ -462,8 +518,9 @@ where
         let max_idle_count = self.ctx.task_max_idle_count();
 
         let mut ups_rsp: Option<Response<RecvStream>> = None;
+        let mut req_body_finished = false;
 
-        loop {
+        let r = loop {
             tokio::select! {
                 biased;
 
@@ -471,10 +528,11 @@ where
                     match r {
                         Ok(_) => {
                             self.http_notes.mark_req_send_all();
-                            break;
+                            req_body_finished = true;
+                            break Ok(());
                         }
                         Err(e) => {
-                            return Err(H2StreamTransferError::RequestBodyTransferFailed(e));
+                            break Err(H2StreamTransferError::RequestBodyTransferFailed(e));
                         }
                     }
                 }
@@ -483,10 +541,10 @@ where
                         Ok(rsp) => {
                             self.http_notes.mark_rsp_recv_hdr();
                             ups_rsp = Some(rsp);
-                            break;
+                            break Ok(());
                         }
                         Err(e) => {
-                            return Err(H2StreamTransferError::ResponseHeadRecvFailed(e));
+                            break Err(H2StreamTransferError::ResponseHeadRecvFailed(e));
                         }
                     }
                 }
@@ -495,7 +553,7 @@ where
                         idle_count += 1;
 
                         if idle_count > max_idle_count {
-                            return Err(H2StreamTransferError::Idle(idle_duration, idle_count));
+                            break Err(H2StreamTransferError::Idle(idle_duration, idle_count));
                         }
                     } else {
                         idle_count = 0;
@@ -504,15 +562,23 @@ where
                     }
 
                     if self.ctx.belongs_to_blocked_user() {
-                        return Err(H2StreamTransferError::CanceledAsUserBlocked);
+                        break Err(H2StreamTransferError::CanceledAsUserBlocked);
                     }
 
                     if self.ctx.server_force_quit() {
-                        return Err(H2StreamTransferError::CanceledAsServerQuit)
+                        break Err(H2StreamTransferError::CanceledAsServerQuit)
                     }
                 }
             }
-        }
+        };
+        drop(req_body_transfer);
+        self.http_notes.req_body_digest = req_body_hasher.map(|mut h| {
+            if req_body_finished {
+                h.set_eof();
+            }
+            h.finish(false)
+        });
+        r?;
 
         if let Some(ups_rsp) = ups_rsp {
             self.send_response(orig_req, ups_rsp, clt_send_rsp, None)
@@ -533,6 +599,131 @@ where
         }
     }
 
+    /// Forward the multipart request body while checking the metadata of each part.
+    ///
+    /// The request trailers will be dropped in this case.
+    async fn forward_with_multipart_body(
+        &mut self,
+        mut ups_send_req: SendRequest<Bytes>,
+        ups_req: Request<()>,
+        clt_body: RecvStream,
+        clt_send_rsp: &mut SendResponse<Bytes>,
+        multipart_config: &HttpMultipartInspectionConfig,
+        boundary: String,
+    ) -> Result<(), H2StreamTransferError> {
+        let orig_req = ups_req.clone_header();
+
+        let (mut ups_rsp_fut, ups_send_stream) = ups_send_req
+            .send_request(ups_req, false)
+            .map_err(H2StreamTransferError::RequestHeadSendFailed)?; // do not send REFUSED_STREAM, use the default rst in h2
+        self.ups_stream_id = Some(ups_rsp_fut.stream_id());
+        self.http_notes.mark_req_send_hdr();
+
+        let mut clt_r = RequestBodyMultipartReader::new(
+            H2StreamReader::new(clt_body),
+            Some(multipart_config),
+            Some(boundary),
+            self.ctx.dry_run_block_logger("h2"),
+        );
+        let mut req_body_hasher = self.new_body_hasher();
+        let mut clt_hash_r = BodyHashReader::new(&mut clt_r, req_body_hasher.as_mut());
+        let mut ups_w = H2StreamWriter::new(ups_send_stream);
+        let mut clt_to_ups = LimitedCopy::new(
+            &mut clt_hash_r,
+            &mut ups_w,
+            &self.ctx.server_config.limited_copy_config(),
+        );
+
+        let idle_duration = self.ctx.server_config.task_idle_check_duration();
+        let mut idle_interval =
+            tokio::time::interval_at(Instant::now() + idle_duration, idle_duration);
+        let mut idle_count = 0;
+        let max_idle_count = self.ctx.task_max_idle_count();
+
+        let mut ups_rsp: Option<Response<RecvStream>> = None;
+
+        let r = loop {
+            tokio::select! {
+                biased;
+
+                r = &mut clt_to_ups => {
+                    match r {
+                        Ok(_) => {
+                            let _ = ups_w.shutdown().await;
+                            self.http_notes.mark_req_send_all();
+                            break Ok(());
+                        }
+                        Err(LimitedCopyError::ReadFailed(e)) => {
+                            break match RequestBodyPartBlocked::from_io_error(&e) {
+                                Some(blocked) => {
+                                    Err(H2StreamTransferError::RequestBodyBlocked(blocked.to_string()))
+                                }
+                                None => Err(H2StreamTransferError::HttpClientReadFailed(e)),
+                            };
+                        }
+                        Err(LimitedCopyError::WriteFailed(e)) => {
+                            break Err(H2StreamTransferError::HttpUpstreamWriteFailed(e));
+                        }
+                    }
+                }
+                r = &mut ups_rsp_fut => {
+                    match r {
+                        Ok(rsp) => {
+                            self.http_notes.mark_rsp_recv_hdr();
+                            ups_rsp = Some(rsp);
+                            break Ok(());
+                        }
+                        Err(e) => {
+                            break Err(H2StreamTransferError::ResponseHeadRecvFailed(e));
+                        }
+                    }
+                }
+                _ = idle_interval.tick() => {
+                    if clt_to_ups.is_idle() {
+                        idle_count += 1;
+
+                        if idle_count > max_idle_count {
+                            break Err(H2StreamTransferError::Idle(idle_duration, idle_count));
+                        }
+                    } else {
+                        idle_count = 0;
+
+                        clt_to_ups.reset_active();
+                    }
+
+                    if self.ctx.belongs_to_blocked_user() {
+                        break Err(H2StreamTransferError::CanceledAsUserBlocked);
+                    }
+
+                    if self.ctx.server_force_quit() {
+                        break Err(H2StreamTransferError::CanceledAsServerQuit)
+                    }
+                }
+            }
+        };
+        self.http_notes.req_body_digest = req_body_hasher.map(|h| h.finish(r.is_err()));
+        r?;
+        if let Some(e) = clt_r.take_parse_error() {
+            intercept_log!(self, "request body multipart inspection aborted: {e}");
+        }
+
+        let ups_rsp = match ups_rsp {
+            Some(rsp) => rsp,
+            None => {
+                match tokio::time::timeout(self.ctx.h2_rsp_hdr_recv_timeout(), ups_rsp_fut).await {
+                    Ok(Ok(d)) => {
+                        self.http_notes.mark_rsp_recv_hdr();
+                        d
+                    }
+                    Ok(Err(e)) => return Err(H2StreamTransferError::ResponseHeadRecvFailed(e)),
+                    Err(_) => return Err(H2StreamTransferError::ResponseHeadRecvTimeout),
+                }
+            }
+        };
+        self.send_response(orig_req, ups_rsp, clt_send_rsp, None)
+            .await
+    }
+
     async fn send_response(
         &mut self,
         ups_req: Request<()>,
@@ -545,6 +736,8 @@ where
 
         self.http_notes.origin_status = clt_rsp.status().as_u16();
 
+        self.check_response_content_length(&clt_rsp)?;
+
         if let Some(respmod) = self.ctx.audit_handle.icap_respmod_client() {
             match respmod
                 .h2_adapter(
@@ -595,6 +788,37 @@ where
             .await
     }
 
+    /// Abort early if the Content-Length of the response already exceeds the body size limit.
+    ///
+    /// This is the only check on the ICAP RESPMOD path, as the upstream body stream is consumed
+    /// by the adapter directly.
+    fn check_response_content_length(
+        &self,
+        rsp: &Response<()>,
+    ) -> Result<(), H2StreamTransferError> {
+        let config = self.ctx.h2_interception();
+        if config.rsp_body_max_size == 0
+            || config.rsp_body_exceed_policy != HttpBodySizeLimitPolicy::Abort
+        {
+            return Ok(());
+        }
+        let Some(size) = rsp
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| u64::from_str(v).ok())
+        else {
+            return Ok(());
+        };
+        if size > config.rsp_body_max_size {
+            let e = H2StreamTransferError::ResponseBodyTooLarge(size, config.rsp_body_max_size);
+            if !self.ctx.skip_block_in_dry_run("h2", &e.to_string()) {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
     async fn send_response_with_adaptation(
         &mut self,
         ups_req: &Request<()>,
@@ -646,6 +870,22 @@ where
                 clt_send_stream,
                 self.ctx.server_config.limited_copy_config().yield_size(),
             );
+            let max_size = self.ctx.h2_interception().rsp_body_max_size;
+            let dry_run = self.ctx.dry_run_block_logger("h2");
+            if dry_run.is_none() {
+                rsp_body_transfer.set_size_limit(
+                    max_size,
+                    self.ctx.h2_interception().rsp_body_exceed_policy
+                        == HttpBodySizeLimitPolicy::Truncate,
+                );
+            }
+            let mut rsp_body_hasher = self.new_body_hasher();
+            let mut hash_data = rsp_body_hasher
+                .as_mut()
+                .map(|hasher| |data: &[u8]| hasher.update(data));
+            if let Some(f) = &mut hash_data {
+                rsp_body_transfer.set_data_observer(f);
+            }
 
             let idle_duration = self.ctx.server_config.task_idle_check_duration();
             let mut idle_interval =
@@ -653,7 +893,7 @@ where
             let mut idle_count = 0;
             let max_idle_count = self.ctx.task_max_idle_count();
 
-            loop {
+            let r = loop {
                 tokio::select! {
                     biased;
 
@@ -661,9 +901,10 @@ where
                         match r {
                             Ok(_) => {
                                 self.http_notes.mark_rsp_recv_all();
-                                break;
+                                break Ok(());
                             },
-                            Err(e) => return Err(H2StreamTransferError::ResponseBodyTransferFailed(e)),
+                            // the client stream will be reset if the size limit is exceeded
+                            Err(e) => break Err(H2StreamTransferError::ResponseBodyTransferFailed(e)),
                         }
                     }
                     _ = idle_interval.tick() => {
@@ -671,7 +912,7 @@ where
                             idle_count += 1;
 
                             if idle_count > max_idle_count {
-                                return Err(H2StreamTransferError::Idle(idle_duration, idle_count));
+                                break Err(H2StreamTransferError::Idle(idle_duration, idle_count));
                             }
                         } else {
                             idle_count = 0;
@@ -680,17 +921,39 @@ where
                         }
 
                         if self.ctx.belongs_to_blocked_user() {
-                            return Err(H2StreamTransferError::CanceledAsUserBlocked);
+                            break Err(H2StreamTransferError::CanceledAsUserBlocked);
                         }
 
                         if self.ctx.server_force_quit() {
-                            return Err(H2StreamTransferError::CanceledAsServerQuit)
+                            break Err(H2StreamTransferError::CanceledAsServerQuit)
                         }
                     }
                 }
+            };
+            let recv_size = rsp_body_transfer.recv_size();
+            drop(rsp_body_transfer);
+            self.http_notes.rsp_body_digest = rsp_body_hasher.map(|mut h| {
+                if r.is_ok() {
+                    h.set_eof();
+                }
+                h.finish(r.is_err())
+            });
+            r?;
+
+            if let Some(logger) = dry_run {
+                if max_size > 0 && recv_size > max_size {
+                    logger.log(&format!("response body size limit {max_size} exceeded"));
+                }
             }
         }
 
         Ok(())
     }
+
+    fn new_body_hasher(&self) -> Option<HttpBodyHasher> {
+        self.ctx
+            .h1_interception()
+            .body_sha256
+            .map(|_| HttpBodyHasher::new())
+    }
 }
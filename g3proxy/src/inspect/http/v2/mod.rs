@@ -16,22 +16,24 @@
 
 use std::future::poll_fn;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_recursion::async_recursion;
 use bytes::Bytes;
 use h2::{server::Connection, Reason};
 use slog::slog_info;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Semaphore;
 use tokio::time::Instant;
 
 use g3_dpi::{Protocol, ProtocolInspectAction};
 use g3_h2::H2BodyTransfer;
 use g3_io_ext::OnceBufReader;
 use g3_slog_types::{LtUpstreamAddr, LtUuid};
-use g3_types::net::UpstreamAddr;
+use g3_types::net::{HttpBlockResponse, UpstreamAddr};
 
 #[cfg(feature = "quic")]
-use crate::audit::DetourAction;
+use crate::audit::{DetourAction, StreamDetourFallback, StreamDetourFallbackAction};
 use crate::config::server::ServerConfig;
 use crate::inspect::{BoxAsyncRead, BoxAsyncWrite, InterceptionError, StreamInspectContext};
 use crate::serve::ServerTaskResult;
@@ -65,7 +67,8 @@ pub(crate) struct H2InterceptObject<SC: ServerConfig> {
 }
 
 impl<SC: ServerConfig> H2InterceptObject<SC> {
-    pub(crate) fn new(ctx: StreamInspectContext<SC>, upstream: UpstreamAddr) -> Self {
+    pub(crate) fn new(mut ctx: StreamInspectContext<SC>, upstream: UpstreamAddr) -> Self {
+        ctx.set_protocol_idle_timeout(Protocol::Http2);
         let stats = Arc::new(H2ConcurrencyStats::default());
         H2InterceptObject {
             io: None,
@@ -101,6 +104,9 @@ macro_rules! intercept_log {
             "upstream" => LtUpstreamAddr(&$obj.upstream),
             "total_sub_task" => $obj.stats.get_total_task(),
             "alive_sub_task" => $obj.stats.get_alive_task(),
+            "peak_alive_sub_task" => $obj.stats.get_peak_alive_task(),
+            "queued_sub_task" => $obj.stats.get_queued_task(),
+            "refused_sub_task" => $obj.stats.get_refused_task(),
         )
     };
 }
@@ -119,13 +125,19 @@ where
             ProtocolInspectAction::Detour => self.do_detour().await,
             ProtocolInspectAction::Bypass => self.do_bypass().await,
             ProtocolInspectAction::Block => self
-                .do_block()
+                .do_block(false)
+                .await
+                .map_err(|e| InterceptionError::H2(e).into_server_task_error(Protocol::Http2)),
+            ProtocolInspectAction::BlockWithResponse => self
+                .do_block(true)
                 .await
                 .map_err(|e| InterceptionError::H2(e).into_server_task_error(Protocol::Http2)),
         };
         match r {
             Ok(_) => {
-                intercept_log!(self, "finished");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished");
+                }
                 Ok(())
             }
             Err(e) => {
@@ -135,20 +147,37 @@ where
         }
     }
 
+    /// Handle the stream as decided by [StreamDetourFallback] if the stream detour is unavailable
+    #[cfg(feature = "quic")]
+    async fn do_detour_fallback(&mut self, e: Option<anyhow::Error>) -> ServerTaskResult<()> {
+        use crate::serve::ServerTaskError;
+
+        let fallback = StreamDetourFallback::new(self.ctx.audit_handle.stream_detour_on_error(), e);
+        intercept_log!(self, "{fallback}");
+        match fallback.action {
+            StreamDetourFallbackAction::Block => self
+                .do_block(false)
+                .await
+                .map_err(|e| InterceptionError::H2(e).into_server_task_error(Protocol::Http2)),
+            StreamDetourFallbackAction::Bypass => self.do_bypass().await,
+            StreamDetourFallbackAction::Close(e) => {
+                self.close_on_detour_error().await;
+                Err(ServerTaskError::InternalAdapterError(e))
+            }
+        }
+    }
+
     #[cfg(feature = "quic")]
     async fn do_detour(&mut self) -> ServerTaskResult<()> {
         use crate::serve::ServerTaskError;
 
         let Some(client) = self.ctx.audit_handle.stream_detour_client() else {
-            return self.do_bypass().await;
+            return self.do_detour_fallback(None).await;
         };
 
         let mut detour_stream = match client.open_detour_stream().await {
             Ok(s) => s,
-            Err(e) => {
-                self.close_on_detour_error().await;
-                return Err(ServerTaskError::InternalAdapterError(e));
-            }
+            Err(e) => return self.do_detour_fallback(Some(e)).await,
         };
 
         let detour_ctx = client.build_context(
@@ -178,7 +207,7 @@ where
             }
             Ok(DetourAction::Block) => {
                 detour_stream.finish();
-                self.do_block()
+                self.do_block(false)
                     .await
                     .map_err(|e| InterceptionError::H2(e).into_server_task_error(Protocol::Http2))
             }
@@ -235,19 +264,12 @@ where
             ups_w,
         } = self.io.take().unwrap();
 
-        crate::inspect::stream::transit_transparent(
-            clt_r,
-            clt_w,
-            ups_r,
-            ups_w,
-            &self.ctx.server_config,
-            &self.ctx.server_quit_policy,
-            self.ctx.user(),
-        )
-        .await
+        self.ctx
+            .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+            .await
     }
 
-    async fn do_block(&mut self) -> Result<(), H2InterceptionError> {
+    async fn do_block(&mut self, with_response: bool) -> Result<(), H2InterceptionError> {
         let H2InterceptIo {
             clt_r,
             clt_w,
@@ -259,6 +281,7 @@ where
             let _ = ups_w.shutdown().await;
         });
 
+        let block_rsp = with_response.then(|| self.ctx.h2_block_response(self.upstream.host()));
         let http_config = self.ctx.h2_interception();
         let mut server_builder = h2::server::Builder::new();
         server_builder
@@ -278,7 +301,11 @@ where
             Err(_) => return Err(H2InterceptionError::ClientHandshakeTimeout),
         };
 
-        h2c.abrupt_shutdown(Reason::HTTP_1_1_REQUIRED);
+        if let Some(block_rsp) = block_rsp {
+            send_block_response(&mut h2c, &block_rsp, http_config.client_handshake_timeout).await;
+        } else {
+            h2c.abrupt_shutdown(Reason::HTTP_1_1_REQUIRED);
+        }
 
         // TODO add timeout
         let _ = poll_fn(|ctx| h2c.poll_closed(ctx)).await;
@@ -342,6 +369,13 @@ where
             Err(_) => return Err(H2InterceptionError::ClientHandshakeTimeout),
         };
 
+        // the peer's max concurrent streams setting is respected by h2s.ready(),
+        // and this is an extra limit on our side
+        let stream_limiter = match http_config.max_upstream_concurrent_streams {
+            0 => None,
+            n => Some(Arc::new(Semaphore::new(n as usize))),
+        };
+
         // TODO spawn ping-pong
 
         let idle_duration = self.ctx.server_config.task_idle_check_duration();
@@ -379,9 +413,18 @@ where
                             let h2s = h2s.clone();
                             let ctx = self.ctx.clone();
                             let stats = self.stats.clone();
+                            let stream_limiter = stream_limiter.clone();
                             stats.add_task();
                             tokio::spawn(async move {
-                                stream::transfer(clt_req, clt_send_rsp, h2s, ctx).await;
+                                stream::transfer(
+                                    clt_req,
+                                    clt_send_rsp,
+                                    h2s,
+                                    ctx,
+                                    stream_limiter,
+                                    &stats,
+                                )
+                                .await;
                                 stats.del_task();
                             });
                             continue;
@@ -474,3 +517,192 @@ where
         }
     }
 }
+
+/// Reply the custom block response to all the requests, so the client can show it.
+///
+/// The connection will be gracefully shutdown after the first request, and the in-flight
+/// requests will still be replied until the client closes the connection.
+async fn send_block_response<T>(
+    h2c: &mut Connection<T, Bytes>,
+    block_rsp: &HttpBlockResponse,
+    timeout: Duration,
+) where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let body = block_rsp.body();
+    let mut shutdown_started = false;
+    while let Ok(Some(Ok((_clt_req, mut clt_send_rsp)))) =
+        tokio::time::timeout(timeout, h2c.accept()).await
+    {
+        if let Ok(mut send_stream) =
+            clt_send_rsp.send_response(block_rsp.build_h2_response(), body.is_empty())
+        {
+            if !body.is_empty() {
+                let _ = send_stream.send_data(body.clone(), true);
+            }
+        }
+        if !shutdown_started {
+            h2c.graceful_shutdown();
+            shutdown_started = true;
+        }
+    }
+    if !shutdown_started {
+        h2c.graceful_shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use http::{Request, Response, StatusCode};
+    use tokio::io::DuplexStream;
+    use yaml_rust::YamlLoader;
+
+    use g3_daemon::server::{ClientConnectionInfo, ServerQuitPolicy};
+    use g3_types::metrics::MetricsName;
+
+    use crate::audit::Auditor;
+    use crate::config::audit::AuditorConfig;
+    use crate::config::server::dummy_close::DummyCloseServerConfig;
+    use crate::module::tcp_connect::TcpConnectTaskNotes;
+    use crate::serve::{ServerTaskNotes, TcpStreamServerStats};
+
+    fn spawn_intercept(
+        h2_interception: &str,
+        clt_io: DuplexStream,
+        ups_io: DuplexStream,
+    ) -> tokio::task::JoinHandle<ServerTaskResult<()>> {
+        let yaml = YamlLoader::load_from_str(&format!(
+            "{{name: test, h2_interception: {h2_interception}}}"
+        ))
+        .unwrap();
+        let mut auditor_config = AuditorConfig::new(None);
+        auditor_config.parse(yaml[0].as_hash().unwrap()).unwrap();
+        let audit_handle = Auditor::build_test_handle(auditor_config);
+
+        let name = MetricsName::from_str("test").unwrap();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 443);
+        let upstream = UpstreamAddr::from_str("example.net:443").unwrap();
+        let task_notes =
+            ServerTaskNotes::new(ClientConnectionInfo::new(addr, addr), None, Duration::ZERO);
+        let ctx = StreamInspectContext::new(
+            audit_handle,
+            Arc::new(DummyCloseServerConfig::new(&name, None)),
+            Arc::new(TcpStreamServerStats::new(&name)),
+            Arc::new(ServerQuitPolicy::default()),
+            &task_notes,
+            &TcpConnectTaskNotes::new(upstream.clone()),
+        );
+
+        let (clt_r, clt_w) = tokio::io::split(clt_io);
+        let (ups_r, ups_w) = tokio::io::split(ups_io);
+        let mut h2_obj = H2InterceptObject::new(ctx, upstream);
+        h2_obj.set_io(
+            OnceBufReader::with_no_buf(Box::new(clt_r)),
+            Box::new(clt_w),
+            Box::new(ups_r),
+            Box::new(ups_w),
+        );
+        tokio::spawn(h2_obj.intercept())
+    }
+
+    /// Run an upstream server which allows only 1 concurrent stream,
+    /// and return the peak number of concurrent streams seen
+    fn spawn_upstream(ups_io: DuplexStream, rsp_delay: Duration) -> tokio::task::JoinHandle<usize> {
+        tokio::spawn(async move {
+            let mut h2c = h2::server::Builder::new()
+                .max_concurrent_streams(1)
+                .handshake::<_, Bytes>(ups_io)
+                .await
+                .unwrap();
+            let alive = Arc::new(AtomicUsize::new(0));
+            let peak = Arc::new(AtomicUsize::new(0));
+            while let Some(r) = h2c.accept().await {
+                let Ok((_req, mut send_rsp)) = r else {
+                    break;
+                };
+                let alive = alive.clone();
+                let peak = peak.clone();
+                tokio::spawn(async move {
+                    let n = alive.fetch_add(1, Ordering::Relaxed) + 1;
+                    peak.fetch_max(n, Ordering::Relaxed);
+                    tokio::time::sleep(rsp_delay).await;
+                    alive.fetch_sub(1, Ordering::Relaxed);
+                    let rsp = Response::builder().status(200).body(()).unwrap();
+                    let _ = send_rsp.send_response(rsp, true);
+                });
+            }
+            peak.load(Ordering::Relaxed)
+        })
+    }
+
+    fn new_request() -> Request<()> {
+        Request::builder()
+            .uri("https://example.net/")
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn upstream_stream_limit() {
+        let (clt_peer, clt_io) = tokio::io::duplex(64 * 1024);
+        let (ups_io, ups_peer) = tokio::io::duplex(64 * 1024);
+
+        let upstream = spawn_upstream(ups_peer, Duration::from_millis(50));
+        let relay = spawn_intercept("{max_upstream_concurrent_streams: 1}", clt_io, ups_io);
+
+        let (h2s, h2s_connection) = h2::client::handshake(clt_peer).await.unwrap();
+        tokio::spawn(h2s_connection);
+
+        // the client side is allowed to open more streams than the upstream
+        let mut requests = Vec::new();
+        for _ in 0..4 {
+            let mut h2s = h2s.clone().ready().await.unwrap();
+            let (rsp, _) = h2s.send_request(new_request(), true).unwrap();
+            requests.push(rsp);
+        }
+        for rsp in requests {
+            let rsp = rsp.await.unwrap();
+            assert_eq!(rsp.status(), StatusCode::OK);
+        }
+
+        drop(h2s);
+        relay.await.unwrap().unwrap();
+        assert_eq!(upstream.await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn upstream_stream_open_timeout() {
+        let (clt_peer, clt_io) = tokio::io::duplex(64 * 1024);
+        let (ups_io, ups_peer) = tokio::io::duplex(64 * 1024);
+
+        let upstream = spawn_upstream(ups_peer, Duration::from_millis(500));
+        let relay = spawn_intercept(
+            "{max_upstream_concurrent_streams: 1, upstream_stream_open_timeout: 100ms}",
+            clt_io,
+            ups_io,
+        );
+
+        let (h2s, h2s_connection) = h2::client::handshake(clt_peer).await.unwrap();
+        tokio::spawn(h2s_connection);
+
+        let mut h2s = h2s.ready().await.unwrap();
+        let (rsp1, _) = h2s.send_request(new_request(), true).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let mut h2s = h2s.ready().await.unwrap();
+        let (rsp2, _) = h2s.send_request(new_request(), true).unwrap();
+
+        // the queued stream should be refused after the stream open timeout
+        let e = rsp2.await.unwrap_err();
+        assert_eq!(e.reason(), Some(Reason::REFUSED_STREAM));
+        assert_eq!(rsp1.await.unwrap().status(), StatusCode::OK);
+
+        drop(h2s);
+        relay.await.unwrap().unwrap();
+        assert_eq!(upstream.await.unwrap(), 1);
+    }
+}
@@ -19,6 +19,9 @@ use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
 pub(crate) struct H2ConcurrencyStats {
     total_task: AtomicU64,
     alive_task: AtomicI32,
+    peak_alive_task: AtomicI32,
+    queued_task: AtomicI32,
+    refused_task: AtomicU64,
 }
 
 impl Default for H2ConcurrencyStats {
@@ -26,6 +29,9 @@ impl Default for H2ConcurrencyStats {
         H2ConcurrencyStats {
             total_task: AtomicU64::new(0),
             alive_task: AtomicI32::new(0),
+            peak_alive_task: AtomicI32::new(0),
+            queued_task: AtomicI32::new(0),
+            refused_task: AtomicU64::new(0),
         }
     }
 }
@@ -33,7 +39,8 @@ impl Default for H2ConcurrencyStats {
 impl H2ConcurrencyStats {
     pub(super) fn add_task(&self) {
         self.total_task.fetch_add(1, Ordering::Relaxed);
-        self.alive_task.fetch_add(1, Ordering::Relaxed);
+        let alive = self.alive_task.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_alive_task.fetch_max(alive, Ordering::Relaxed);
     }
 
     pub(super) fn del_task(&self) {
@@ -47,4 +54,28 @@ impl H2ConcurrencyStats {
     pub(super) fn get_alive_task(&self) -> i32 {
         self.alive_task.load(Ordering::Relaxed)
     }
+
+    pub(super) fn get_peak_alive_task(&self) -> i32 {
+        self.peak_alive_task.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn add_queued_task(&self) {
+        self.queued_task.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn del_queued_task(&self) {
+        self.queued_task.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn get_queued_task(&self) -> i32 {
+        self.queued_task.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn add_refused_task(&self) {
+        self.refused_task.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn get_refused_task(&self) -> u64 {
+        self.refused_task.load(Ordering::Relaxed)
+    }
 }
@@ -15,28 +15,57 @@
  */
 
 use std::str::FromStr;
+use std::sync::Arc;
 
 use bytes::Bytes;
 use h2::client::SendRequest;
 use h2::ext::Protocol;
 use h2::server::SendResponse;
-use h2::RecvStream;
+use h2::{Reason, RecvStream};
 use http::{Method, Request};
+use tokio::sync::Semaphore;
 
 use g3_types::net::HttpUpgradeToken;
 
-use super::{H2ConnectTask, H2ExtendedConnectTask, H2ForwardTask};
+use super::{H2ConcurrencyStats, H2ConnectTask, H2ExtendedConnectTask, H2ForwardTask};
 use crate::config::server::ServerConfig;
 use crate::inspect::StreamInspectContext;
 
 pub(super) async fn transfer<SC>(
     mut clt_req: Request<RecvStream>,
-    clt_send_rsp: SendResponse<Bytes>,
+    mut clt_send_rsp: SendResponse<Bytes>,
     h2s: SendRequest<Bytes>,
     ctx: StreamInspectContext<SC>,
+    stream_limiter: Option<Arc<Semaphore>>,
+    stats: &H2ConcurrencyStats,
 ) where
     SC: ServerConfig + Send + Sync + 'static,
 {
+    // hold the permit until the whole stream is finished
+    let _stream_permit = match stream_limiter {
+        Some(limiter) => match limiter.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                stats.add_queued_task();
+                let r = tokio::time::timeout(
+                    ctx.h2_interception().upstream_stream_open_timeout,
+                    limiter.acquire_owned(),
+                )
+                .await;
+                stats.del_queued_task();
+                match r {
+                    Ok(Ok(permit)) => Some(permit),
+                    _ => {
+                        stats.add_refused_task();
+                        clt_send_rsp.send_reset(Reason::REFUSED_STREAM);
+                        return;
+                    }
+                }
+            }
+        },
+        None => None,
+    };
+
     if ctx.h1_interception().steal_forwarded_for {
         clt_req.headers_mut().remove(http::header::FORWARDED);
         clt_req.headers_mut().remove("x-forwarded-for");
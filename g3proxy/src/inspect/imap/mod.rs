@@ -18,7 +18,7 @@ use anyhow::anyhow;
 use slog::slog_info;
 use tokio::io::AsyncWriteExt;
 
-use g3_dpi::ProtocolInspectAction;
+use g3_dpi::{Protocol, ProtocolInspectAction};
 use g3_imap_proto::response::ByeResponse;
 use g3_imap_proto::CommandPipeline;
 use g3_io_ext::{LineRecvVec, OnceBufReader};
@@ -27,7 +27,7 @@ use g3_types::net::UpstreamAddr;
 
 use super::StartTlsProtocol;
 #[cfg(feature = "quic")]
-use crate::audit::DetourAction;
+use crate::audit::{DetourAction, StreamDetourFallback, StreamDetourFallbackAction};
 use crate::config::server::ServerConfig;
 use crate::inspect::{BoxAsyncRead, BoxAsyncWrite, StreamInspectContext, StreamInspection};
 use crate::serve::{ServerTaskError, ServerTaskResult};
@@ -94,7 +94,8 @@ impl<SC> ImapInterceptObject<SC>
 where
     SC: ServerConfig + Send + Sync + 'static,
 {
-    pub(crate) fn new(ctx: StreamInspectContext<SC>, upstream: UpstreamAddr) -> Self {
+    pub(crate) fn new(mut ctx: StreamInspectContext<SC>, upstream: UpstreamAddr) -> Self {
+        ctx.set_protocol_idle_timeout(Protocol::Imap);
         ImapInterceptObject {
             io: None,
             ctx,
@@ -135,11 +136,15 @@ where
             #[cfg(feature = "quic")]
             ProtocolInspectAction::Detour => self.do_detour().await.map(|_| None),
             ProtocolInspectAction::Bypass => self.do_bypass().await.map(|_| None),
-            ProtocolInspectAction::Block => self.do_block().await.map(|_| None),
+            ProtocolInspectAction::Block | ProtocolInspectAction::BlockWithResponse => {
+                self.do_block().await.map(|_| None)
+            }
         };
         match r {
             Ok(obj) => {
-                intercept_log!(self, "finished");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished");
+                }
                 Ok(obj)
             }
             Err(e) => {
@@ -149,18 +154,30 @@ where
         }
     }
 
+    /// Handle the stream as decided by [StreamDetourFallback] if the stream detour is unavailable
+    #[cfg(feature = "quic")]
+    async fn do_detour_fallback(&mut self, e: Option<anyhow::Error>) -> ServerTaskResult<()> {
+        let fallback = StreamDetourFallback::new(self.ctx.audit_handle.stream_detour_on_error(), e);
+        intercept_log!(self, "{fallback}");
+        match fallback.action {
+            StreamDetourFallbackAction::Block => self.do_block().await,
+            StreamDetourFallbackAction::Bypass => self.do_bypass().await,
+            StreamDetourFallbackAction::Close(e) => {
+                self.close_on_detour_error().await;
+                Err(ServerTaskError::InternalAdapterError(e))
+            }
+        }
+    }
+
     #[cfg(feature = "quic")]
     async fn do_detour(&mut self) -> ServerTaskResult<()> {
         let Some(client) = self.ctx.audit_handle.stream_detour_client() else {
-            return self.do_bypass().await;
+            return self.do_detour_fallback(None).await;
         };
 
         let mut detour_stream = match client.open_detour_stream().await {
             Ok(s) => s,
-            Err(e) => {
-                self.close_on_detour_error().await;
-                return Err(ServerTaskError::InternalAdapterError(e));
-            }
+            Err(e) => return self.do_detour_fallback(Some(e)).await,
         };
 
         let detour_ctx = client.build_context(
@@ -168,7 +185,7 @@ where
             &self.ctx.server_quit_policy,
             &self.ctx.task_notes,
             &self.upstream,
-            g3_dpi::Protocol::Imap,
+            Protocol::Imap,
         );
 
         match detour_ctx.check_detour_action(&mut detour_stream).await {
@@ -226,16 +243,9 @@ where
             ups_w,
         } = self.io.take().unwrap();
 
-        crate::inspect::stream::transit_transparent(
-            clt_r,
-            clt_w,
-            ups_r,
-            ups_w,
-            &self.ctx.server_config,
-            &self.ctx.server_quit_policy,
-            self.ctx.user(),
-        )
-        .await
+        self.ctx
+            .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+            .await
     }
 
     async fn do_block(&mut self) -> ServerTaskResult<()> {
@@ -253,6 +263,7 @@ where
         ByeResponse::reply_blocked(&mut clt_w)
             .await
             .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+        self.ctx.tarpit_blocked(&mut clt_w).await;
         clt_w
             .shutdown()
             .await
@@ -365,17 +376,10 @@ where
                     start_tls_obj.set_io(clt_r, clt_w, ups_r, ups_w);
                     Ok(Some(StreamInspection::StartTls(start_tls_obj)))
                 } else {
-                    crate::inspect::stream::transit_transparent(
-                        clt_r,
-                        clt_w,
-                        ups_r,
-                        ups_w,
-                        &self.ctx.server_config,
-                        &self.ctx.server_quit_policy,
-                        self.ctx.user(),
-                    )
-                    .await
-                    .map(|_| None)
+                    self.ctx
+                        .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+                        .await
+                        .map(|_| None)
                 }
             }
             InitiationStatus::Authenticated => {
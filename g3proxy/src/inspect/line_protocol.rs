@@ -0,0 +1,187 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use bytes::BytesMut;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufWriter};
+
+use g3_io_ext::{LimitedWriteExt, LineRecvBuf, OnceBufReader, RecvLineError};
+
+/// What to do with a line after it has been fed to the [LineProtocolParser]
+pub(crate) enum LineRelayAction<E> {
+    /// relay the line and continue to read the next one
+    Continue,
+    /// relay the line and finish the relay of the current message
+    Finish,
+    /// relay the line and then return the error
+    Abort(E),
+}
+
+/// The protocol specific part of a line based request / reply relay.
+pub(crate) trait LineProtocolParser {
+    type Error: From<RecvLineError>;
+
+    /// Parse the line and decide what to do with it.
+    ///
+    /// The line will not be relayed if an error is returned.
+    fn feed_line(&mut self, line: &[u8]) -> Result<LineRelayAction<Self::Error>, Self::Error>;
+
+    fn client_write_error(e: io::Error) -> Self::Error;
+}
+
+/// Relay lines from the reader to the writer until the parser reports the end of the message.
+///
+/// This handles the line recv buffer, the timeout, the time stats and the write of the lines,
+/// so the protocol inspectors only need to plug in their line parser.
+pub(crate) struct LineProtocolRelay<const MAX_LINE_SIZE: usize> {
+    recv_buf: LineRecvBuf<MAX_LINE_SIZE>,
+    total_to_write: usize,
+    time_start: Instant,
+    ttfb: Option<Duration>,
+    total_time: Option<Duration>,
+    client_write_failed: bool,
+}
+
+impl<const MAX_LINE_SIZE: usize> Default for LineProtocolRelay<MAX_LINE_SIZE> {
+    fn default() -> Self {
+        LineProtocolRelay {
+            recv_buf: LineRecvBuf::default(),
+            total_to_write: 0,
+            time_start: Instant::now(),
+            ttfb: None,
+            total_time: None,
+            client_write_failed: false,
+        }
+    }
+}
+
+impl<const MAX_LINE_SIZE: usize> LineProtocolRelay<MAX_LINE_SIZE> {
+    /// Total size of the lines that have been relayed or tried to be relayed
+    #[inline]
+    pub(crate) fn total_to_write(&self) -> usize {
+        self.total_to_write
+    }
+
+    /// Truncate the too long lines instead of returning an error
+    pub(crate) fn set_truncate_long_line(&mut self) {
+        self.recv_buf.set_truncate_long_line(true);
+    }
+
+    /// Get and reset the number of lines that have been truncated
+    pub(crate) fn take_truncated_count(&mut self) -> usize {
+        self.recv_buf.take_truncated_count()
+    }
+
+    /// Time to receive the first line
+    #[inline]
+    pub(crate) fn ttfb(&self) -> Option<Duration> {
+        self.ttfb
+    }
+
+    /// Time to receive the whole message
+    #[inline]
+    pub(crate) fn total_time(&self) -> Option<Duration> {
+        self.total_time
+    }
+
+    async fn do_relay<P, R, W>(
+        &mut self,
+        parser: &mut P,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<(), P::Error>
+    where
+        P: LineProtocolParser,
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        loop {
+            self.recv_buf.consume_line();
+            let line = self.recv_buf.read_line(reader).await?;
+            if self.ttfb.is_none() {
+                self.ttfb = Some(self.time_start.elapsed());
+            }
+
+            let action = parser.feed_line(line)?;
+            self.total_to_write += line.len();
+            if let Err(e) = writer.write_all_flush(line).await {
+                self.client_write_failed = true;
+                return Err(P::client_write_error(e));
+            }
+
+            match action {
+                LineRelayAction::Continue => {}
+                LineRelayAction::Finish => {
+                    self.total_time = Some(self.time_start.elapsed());
+                    // make sure the left data of the truncated line won't be kept
+                    self.recv_buf.skip_truncated_left(reader).await?;
+                    return Ok(());
+                }
+                LineRelayAction::Abort(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Relay a whole message within the timeout.
+    ///
+    /// The bytes pre-buffered in `reader` will be consumed before any read on the inner reader.
+    /// Use [Self::keep_left_data] to get back the data received after the message.
+    pub(crate) async fn relay<P, R, W>(
+        &mut self,
+        parser: &mut P,
+        reader: &mut OnceBufReader<R>,
+        writer: &mut W,
+        timeout: Duration,
+    ) -> Result<(), P::Error>
+    where
+        P: LineProtocolParser,
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut buf_writer = BufWriter::with_capacity(1024, writer);
+        self.time_start = Instant::now();
+        let r = match tokio::time::timeout(timeout, self.do_relay(parser, reader, &mut buf_writer))
+            .await
+        {
+            Ok(r) => r,
+            Err(_) => Err(RecvLineError::Timeout.into()),
+        };
+        if !self.client_write_failed {
+            let _ = buf_writer.flush().await;
+        }
+        r
+    }
+
+    /// Put the data received after the message back to the reader
+    pub(crate) fn keep_left_data<R>(&mut self, reader: OnceBufReader<R>) -> OnceBufReader<R> {
+        self.recv_buf.consume_line();
+        let left = self.recv_buf.consume_left(MAX_LINE_SIZE);
+        let (once_buf, reader) = reader.into_parts();
+        if left.is_empty() {
+            return match once_buf {
+                Some(buf) => OnceBufReader::with_bytes(reader, buf),
+                None => OnceBufReader::with_no_buf(reader),
+            };
+        }
+        let once_buf = once_buf.unwrap_or_default();
+        let mut buf = BytesMut::with_capacity(left.len() + once_buf.len());
+        buf.extend_from_slice(left);
+        buf.extend_from_slice(&once_buf);
+        OnceBufReader::new(reader, buf)
+    }
+}
@@ -15,30 +15,49 @@
  */
 
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use slog::Logger;
+use openssl::ssl::SslRef;
+use slog::{slog_info, Logger};
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
 use uuid::Uuid;
 
-use g3_daemon::server::ServerQuitPolicy;
+use g3_daemon::server::{ClientConnectionInfo, ServerQuitPolicy};
 use g3_dpi::{
     H1InterceptionConfig, H2InterceptionConfig, ImapInterceptionConfig, MaybeProtocol,
-    ProtocolInspectAction, ProtocolInspector, SmtpInterceptionConfig,
+    Pop3InterceptionConfig, Protocol, ProtocolInspectAction, ProtocolInspectPolicy,
+    ProtocolInspector, SmtpInterceptionConfig, WebSocketInterceptionConfig,
+};
+use g3_slog_types::{LtHost, LtUuid};
+use g3_socket::RawSocket;
+use g3_types::net::{
+    Host, HttpBlockResponse, OpensslCertificatePair, OpensslClientConfig, ProxyProtocolVersion,
+    TcpMiscSockOpts,
 };
-use g3_types::net::{Host, OpensslClientConfig};
 
 use crate::audit::AuditHandle;
 use crate::auth::{User, UserForbiddenStats, UserSite};
-use crate::config::server::ServerConfig;
-use crate::serve::{ArcServerStats, ServerIdleChecker, ServerTaskNotes};
+use crate::config::audit::ProtocolTcpNoDelayConfig;
+use crate::config::server::{ServerConfig, TarpitTrigger};
+use crate::module::tcp_connect::TcpConnectTaskNotes;
+use crate::serve::{ArcServerStats, ServerIdleChecker, ServerTarpit, ServerTaskNotes};
 
 mod error;
 pub(crate) use error::InterceptionError;
 
+mod stats;
+pub(crate) use stats::{
+    foreach_stats as foreach_inspect_policy_stats, InspectPolicyProtocol, InspectPolicySnapshot,
+    InspectPolicyStats, ALL_INSPECT_DECISIONS, INSPECT_DEPTH_TAGS,
+};
+
 pub(crate) mod stream;
 
+mod line_protocol;
+
 pub(crate) mod tls;
 use tls::TlsInterceptionContext;
 
@@ -49,6 +68,7 @@ pub(crate) mod http;
 mod websocket;
 
 pub(crate) mod imap;
+pub(crate) mod pop3;
 pub(crate) mod smtp;
 
 #[derive(Clone)]
@@ -75,6 +95,13 @@ pub(crate) struct StreamInspectTaskNotes {
     pub(crate) server_addr: SocketAddr,
     worker_id: Option<usize>,
     user_ctx: Option<StreamInspectUserContext>,
+    cc_info: ClientConnectionInfo,
+    tls_server_name: Option<Arc<str>>,
+    tls_client_subject: Option<Arc<str>>,
+    log_sampled: bool,
+    ups_raw_socket: Option<RawSocket>,
+    escaper_protocol_tcp_no_delay: Option<ProtocolTcpNoDelayConfig>,
+    escaper_tls_client_cert: Option<Arc<OpensslCertificatePair>>,
 }
 
 impl StreamInspectTaskNotes {
@@ -92,6 +119,18 @@ impl StreamInspectTaskNotes {
     pub(crate) fn task_id(&self) -> &Uuid {
         &self.task_id
     }
+
+    /// The SNI sent by the client in the outermost intercepted TLS connection
+    #[inline]
+    pub(crate) fn tls_server_name(&self) -> Option<&str> {
+        self.tls_server_name.as_deref()
+    }
+
+    /// The verified client certificate subject in the outermost intercepted TLS connection
+    #[inline]
+    pub(crate) fn tls_client_subject(&self) -> Option<&str> {
+        self.tls_client_subject.as_deref()
+    }
 }
 
 impl From<&ServerTaskNotes> for StreamInspectTaskNotes {
@@ -107,10 +146,87 @@ impl From<&ServerTaskNotes> for StreamInspectTaskNotes {
                 user_site: ctx.user_site().cloned(),
                 forbidden_stats: ctx.forbidden_stats().clone(),
             }),
+            cc_info: task_notes.cc_info().clone(),
+            tls_server_name: None,
+            tls_client_subject: None,
+            log_sampled: crate::log::audit::sample(&task_notes.id),
+            ups_raw_socket: None,
+            escaper_protocol_tcp_no_delay: None,
+            escaper_tls_client_cert: None,
         }
     }
 }
 
+/// Log the block events that have been skipped in dry-run mode
+#[derive(Clone)]
+pub(crate) struct DryRunBlockLogger {
+    logger: Logger,
+    task_id: Uuid,
+    depth: usize,
+    protocol: &'static str,
+}
+
+impl DryRunBlockLogger {
+    #[cfg(test)]
+    pub(crate) fn discard(protocol: &'static str) -> Self {
+        DryRunBlockLogger {
+            logger: Logger::root(slog::Discard, slog::o!()),
+            task_id: Uuid::nil(),
+            depth: 0,
+            protocol,
+        }
+    }
+
+    pub(crate) fn log(&self, reason: &str) {
+        slog_info!(self.logger, "block skipped in dry-run mode";
+            "intercept_type" => "DryRun",
+            "task_id" => LtUuid(&self.task_id),
+            "depth" => self.depth,
+            "protocol" => self.protocol,
+            "reason" => reason,
+            "dry_run" => true,
+        );
+    }
+}
+
+/// The inspection result of a whole stream, shared by all the nested inspections of it
+#[derive(Default)]
+pub(crate) struct StreamInspectSummary {
+    max_depth: AtomicUsize,
+    tls_interception_attempted: AtomicBool,
+    tls_interception_succeeded: AtomicBool,
+}
+
+impl StreamInspectSummary {
+    fn add_depth(&self, depth: usize) {
+        self.max_depth.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    fn add_tls_interception(&self, succeeded: bool) {
+        self.tls_interception_attempted
+            .store(true, Ordering::Relaxed);
+        if succeeded {
+            self.tls_interception_succeeded
+                .store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The deepest inspection depth that has been reached
+    pub(crate) fn max_depth(&self) -> usize {
+        self.max_depth.load(Ordering::Relaxed)
+    }
+
+    /// Whether TLS interception has been attempted at any depth
+    pub(crate) fn tls_interception_attempted(&self) -> bool {
+        self.tls_interception_attempted.load(Ordering::Relaxed)
+    }
+
+    /// Whether TLS interception has succeeded at any depth
+    pub(crate) fn tls_interception_succeeded(&self) -> bool {
+        self.tls_interception_succeeded.load(Ordering::Relaxed)
+    }
+}
+
 pub(crate) struct StreamInspectContext<SC: ServerConfig> {
     audit_handle: Arc<AuditHandle>,
     server_config: Arc<SC>,
@@ -118,8 +234,12 @@ pub(crate) struct StreamInspectContext<SC: ServerConfig> {
     server_quit_policy: Arc<ServerQuitPolicy>,
     task_notes: StreamInspectTaskNotes,
     inspection_depth: usize,
+    tarpit: Option<Arc<ServerTarpit>>,
+    summary: Option<Arc<StreamInspectSummary>>,
 
     task_max_idle_count: i32,
+    protocol_max_idle_count: Option<i32>,
+    task_deadline: Option<Instant>,
 }
 
 impl<SC: ServerConfig> Clone for StreamInspectContext<SC> {
@@ -131,7 +251,11 @@ impl<SC: ServerConfig> Clone for StreamInspectContext<SC> {
             server_quit_policy: self.server_quit_policy.clone(),
             task_notes: self.task_notes.clone(),
             inspection_depth: self.inspection_depth,
+            tarpit: self.tarpit.clone(),
+            summary: self.summary.clone(),
             task_max_idle_count: self.task_max_idle_count,
+            protocol_max_idle_count: self.protocol_max_idle_count,
+            task_deadline: self.task_deadline,
         }
     }
 }
@@ -143,23 +267,45 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
         server_stats: ArcServerStats,
         server_quit_policy: Arc<ServerQuitPolicy>,
         task_notes: &ServerTaskNotes,
+        tcp_notes: &TcpConnectTaskNotes,
     ) -> Self {
         let mut task_max_idle_count = server_config.task_max_idle_count();
         if let Some(user_ctx) = task_notes.user_ctx() {
             task_max_idle_count = user_ctx.user().task_max_idle_count();
         }
+        let task_deadline = task_notes.task_deadline(server_config.max_task_lifetime());
+        let mut task_notes = StreamInspectTaskNotes::from(task_notes);
+        task_notes.ups_raw_socket.clone_from(&tcp_notes.raw_socket);
+        task_notes.escaper_protocol_tcp_no_delay = tcp_notes.protocol_tcp_no_delay;
+        task_notes
+            .escaper_tls_client_cert
+            .clone_from(&tcp_notes.tls_interception_client_cert);
 
         StreamInspectContext {
             audit_handle,
             server_config,
             server_stats,
             server_quit_policy,
-            task_notes: StreamInspectTaskNotes::from(task_notes),
+            task_notes,
             inspection_depth: 0,
+            tarpit: None,
+            summary: None,
             task_max_idle_count,
+            protocol_max_idle_count: None,
+            task_deadline,
         }
     }
 
+    /// Hold the client connections blocked by the inspection policy in the server tarpit
+    pub(crate) fn set_tarpit(&mut self, tarpit: Option<Arc<ServerTarpit>>) {
+        self.tarpit = tarpit;
+    }
+
+    /// Record the inspection result of the whole stream in the summary
+    pub(crate) fn set_summary(&mut self, summary: Arc<StreamInspectSummary>) {
+        self.summary = Some(summary);
+    }
+
     #[inline]
     fn user(&self) -> Option<&Arc<User>> {
         self.task_notes.user()
@@ -180,6 +326,11 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
         self.server_quit_policy.force_quit()
     }
 
+    #[inline]
+    pub(crate) fn task_deadline(&self) -> Option<Instant> {
+        self.task_deadline
+    }
+
     #[inline]
     fn server_offline(&self) -> bool {
         !self.server_stats.is_online()
@@ -195,11 +346,24 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
         self.audit_handle.intercept_logger()
     }
 
+    /// Whether the normal events should be logged in intercept log.
+    /// Error and block events are always logged.
+    #[inline]
+    pub(crate) fn intercept_log_sampled(&self) -> bool {
+        self.task_notes.log_sampled
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_intercept_log_sampled(&mut self, sampled: bool) {
+        self.task_notes.log_sampled = sampled;
+    }
+
     pub(crate) fn idle_checker(&self) -> ServerIdleChecker {
         ServerIdleChecker {
             idle_duration: self.server_config.task_idle_check_duration(),
             user: self.user().cloned(),
             task_max_idle_count: self.task_max_idle_count,
+            protocol_max_idle_count: self.protocol_max_idle_count,
             server_quit_policy: self.server_quit_policy.clone(),
         }
     }
@@ -226,6 +390,78 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
     #[inline]
     fn increase_inspection_depth(&mut self) {
         self.inspection_depth += 1;
+        if let Some(summary) = &self.summary {
+            summary.add_depth(self.inspection_depth);
+        }
+    }
+
+    fn record_tls_interception(&self, succeeded: bool) {
+        if let Some(summary) = &self.summary {
+            summary.add_tls_interception(succeeded);
+        }
+    }
+
+    fn set_tls_server_name(&mut self, name: &str) {
+        if self.task_notes.tls_server_name.is_none() {
+            self.task_notes.tls_server_name = Some(Arc::from(name));
+        }
+    }
+
+    fn set_tls_client_subject(&mut self, subject: &str) {
+        if self.task_notes.tls_client_subject.is_none() {
+            self.task_notes.tls_client_subject = Some(Arc::from(subject));
+        }
+    }
+
+    /// set the client cert configured on the escaper, which takes precedence over the one
+    /// in the auditor or user-site tls client config
+    fn set_escaper_tls_client_cert(&self, ssl: &mut SslRef) -> anyhow::Result<()> {
+        match &self.task_notes.escaper_tls_client_cert {
+            Some(cert_pair) => cert_pair.add_to_client_ssl(ssl),
+            None => Ok(()),
+        }
+    }
+
+    /// use the idle timeout configured for the identified protocol, which takes precedence over
+    /// the idle config on the user and the server
+    fn set_protocol_idle_timeout(&mut self, protocol: Protocol) {
+        let idle_duration = self.server_config.task_idle_check_duration();
+        if let Some(count) = self
+            .audit_handle
+            .protocol_max_idle_count(protocol, idle_duration)
+        {
+            self.task_max_idle_count = count;
+            self.protocol_max_idle_count = Some(count);
+        }
+    }
+
+    /// set TCP_NODELAY on both connections if configured for the identified protocol,
+    /// the server / escaper config takes precedence over the auditor config
+    fn set_protocol_tcp_no_delay(&self, protocol: Protocol) {
+        let auditor_no_delay = self.audit_handle.protocol_tcp_no_delay(protocol);
+
+        let clt_no_delay = self
+            .server_config
+            .protocol_tcp_no_delay()
+            .and_then(|c| c.get(protocol))
+            .or(auditor_no_delay);
+        if let Some(no_delay) = clt_no_delay {
+            let _ = self.task_notes.cc_info.tcp_sock_set_nodelay(no_delay);
+        }
+
+        let ups_no_delay = self
+            .task_notes
+            .escaper_protocol_tcp_no_delay
+            .and_then(|c| c.get(protocol))
+            .or(auditor_no_delay);
+        if let (Some(no_delay), Some(raw_socket)) = (ups_no_delay, &self.task_notes.ups_raw_socket)
+        {
+            let misc_opts = TcpMiscSockOpts {
+                no_delay: Some(no_delay),
+                ..Default::default()
+            };
+            let _ = raw_socket.set_tcp_misc_opts(&misc_opts, false);
+        }
     }
 
     #[inline]
@@ -233,6 +469,16 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
         self.audit_handle.tls_interception()
     }
 
+    #[inline]
+    fn tls_sni_peek(&self) -> Option<u32> {
+        self.audit_handle.tls_sni_peek()
+    }
+
+    #[inline]
+    fn tls_sni_filter(&self) -> Option<&Arc<tls::TlsSniFilter>> {
+        self.audit_handle.tls_sni_filter()
+    }
+
     pub(crate) fn user_site_tls_client(&self) -> Option<&OpensslClientConfig> {
         self.task_notes
             .user_ctx
@@ -262,11 +508,142 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
             .unwrap_or(self.h1_interception().rsp_head_recv_timeout)
     }
 
+    #[inline]
+    fn inspect_dry_run(&self, policy: &ProtocolInspectPolicy) -> bool {
+        policy.dry_run() || self.audit_handle.inspect_dry_run()
+    }
+
+    /// Get the inspect action to take, the block actions will be changed to intercept in dry-run mode.
+    ///
+    /// Use this for the checks that will be done again later by [Self::logged_inspect_action].
+    fn effective_inspect_action(
+        &self,
+        policy: &ProtocolInspectPolicy,
+        host: &Host,
+    ) -> ProtocolInspectAction {
+        let (_, action) = policy.check_rule(host);
+        if action.is_block() && self.inspect_dry_run(policy) {
+            ProtocolInspectAction::Intercept
+        } else {
+            action
+        }
+    }
+
+    /// Count the inspect policy decision for the stream to be inspected at the given depth
+    fn add_inspect_decision(
+        &self,
+        protocol: InspectPolicyProtocol,
+        action: ProtocolInspectAction,
+        depth: usize,
+    ) {
+        self.audit_handle
+            .inspect_policy_stats()
+            .add_decision(protocol, action, depth);
+    }
+
+    /// Get the inspect action to take, and log the would-block event in dry-run mode.
+    ///
+    /// The action taken will be counted, so this should be called only once for each stream.
+    fn logged_inspect_action(
+        &self,
+        protocol: InspectPolicyProtocol,
+        policy: &ProtocolInspectPolicy,
+        host: &Host,
+    ) -> ProtocolInspectAction {
+        let (rule, action) = policy.check_rule(host);
+        if !action.is_block() || !self.inspect_dry_run(policy) {
+            self.add_inspect_decision(protocol, action, self.inspection_depth);
+            return action;
+        }
+
+        slog_info!(self.intercept_logger(), "block skipped in dry-run mode";
+            "intercept_type" => "DryRun",
+            "task_id" => LtUuid(self.server_task_id()),
+            "depth" => self.inspection_depth,
+            "protocol" => protocol.as_str(),
+            "upstream_host" => LtHost(host),
+            "policy_rule" => rule.as_str(),
+            "action" => action.as_str(),
+            "dry_run" => true,
+        );
+        self.add_inspect_decision(
+            protocol,
+            ProtocolInspectAction::Intercept,
+            self.inspection_depth,
+        );
+        ProtocolInspectAction::Intercept
+    }
+
+    /// Get the logger for the block events that should be skipped in dry-run mode.
+    ///
+    /// Use this for the limit checks that are not decided by the inspect policies, and return
+    /// `None` if not in dry-run mode.
+    fn dry_run_block_logger(&self, protocol: &'static str) -> Option<DryRunBlockLogger> {
+        if !self.audit_handle.inspect_dry_run() {
+            return None;
+        }
+        Some(DryRunBlockLogger {
+            logger: self.intercept_logger().clone(),
+            task_id: *self.server_task_id(),
+            depth: self.inspection_depth,
+            protocol,
+        })
+    }
+
+    /// Hold the blocked client connection if the server tarpit applies to inspection blocks
+    async fn tarpit_blocked<W>(&self, clt_w: W)
+    where
+        W: AsyncWrite + Unpin,
+    {
+        if let Some(tarpit) = &self.tarpit {
+            tarpit
+                .hold(
+                    TarpitTrigger::InspectionBlock,
+                    self.server_config.name(),
+                    self.task_notes.client_addr,
+                    clt_w,
+                )
+                .await;
+        }
+    }
+
+    /// Check if the block should be skipped in dry-run mode, the skipped event will be logged
+    fn skip_block_in_dry_run(&self, protocol: &'static str, reason: &str) -> bool {
+        match self.dry_run_block_logger(protocol) {
+            Some(logger) => {
+                logger.log(reason);
+                true
+            }
+            None => false,
+        }
+    }
+
     #[inline]
     fn h2_inspect_action(&self, host: &Host) -> ProtocolInspectAction {
-        match self.audit_handle.h2_inspect_policy.check(host) {
-            (true, policy_action) => policy_action,
-            (false, missing_policy_action) => missing_policy_action,
+        self.logged_inspect_action(
+            InspectPolicyProtocol::H2,
+            &self.audit_handle.h2_inspect_policy,
+            host,
+        )
+    }
+
+    fn h2_block_response(&self, host: &Host) -> Arc<HttpBlockResponse> {
+        self.audit_handle
+            .h2_inspect_policy
+            .block_response(host)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Get the custom block response for HTTP/1.x requests if the host is blocked with response
+    /// by the h2 inspect policy
+    fn h1_block_response(&self, host: &Host) -> Option<Arc<HttpBlockResponse>> {
+        let policy = &self.audit_handle.h2_inspect_policy;
+        match self.effective_inspect_action(policy, host) {
+            ProtocolInspectAction::BlockWithResponse => {
+                Some(policy.block_response(host).cloned().unwrap_or_default())
+            }
+            _ => None,
         }
     }
 
@@ -285,18 +662,33 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
 
     #[inline]
     fn websocket_inspect_action(&self, host: &Host) -> ProtocolInspectAction {
-        match self.audit_handle.websocket_inspect_policy.check(host) {
-            (true, policy_action) => policy_action,
-            (false, missing_policy_action) => missing_policy_action,
-        }
+        self.logged_inspect_action(
+            InspectPolicyProtocol::Websocket,
+            &self.audit_handle.websocket_inspect_policy,
+            host,
+        )
+    }
+
+    fn websocket_block_response(&self, host: &Host) -> Arc<HttpBlockResponse> {
+        self.audit_handle
+            .websocket_inspect_policy
+            .block_response(host)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    fn websocket_interception(&self) -> &WebSocketInterceptionConfig {
+        self.audit_handle.websocket_interception()
     }
 
     #[inline]
     fn smtp_inspect_action(&self, host: &Host) -> ProtocolInspectAction {
-        match self.audit_handle.smtp_inspect_policy.check(host) {
-            (true, policy_action) => policy_action,
-            (false, missing_policy_action) => missing_policy_action,
-        }
+        self.logged_inspect_action(
+            InspectPolicyProtocol::Smtp,
+            &self.audit_handle.smtp_inspect_policy,
+            host,
+        )
     }
 
     #[inline]
@@ -304,12 +696,41 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
         self.audit_handle.smtp_interception()
     }
 
+    fn smtp_max_recipients(&self) -> Option<usize> {
+        self.task_notes
+            .user_ctx
+            .as_ref()
+            .and_then(|cx| cx.user.smtp_max_recipients())
+            .or(self.smtp_interception().max_recipients)
+    }
+
+    /// Get the PROXY protocol version to use if the upstream port is mapped to SMTP.
+    ///
+    /// The upstream MTA will wait for the header before sending the greeting, so the protocol
+    /// detection of the outermost stream should be skipped if set.
+    fn smtp_upstream_proxy_protocol(&self, port: u16) -> Option<ProxyProtocolVersion> {
+        if self.inspection_depth > 0 {
+            return None;
+        }
+        let version = self.smtp_interception().upstream_proxy_protocol?;
+        if self
+            .audit_handle
+            .server_tcp_portmap()
+            .contains(port, MaybeProtocol::Smtp)
+        {
+            Some(version)
+        } else {
+            None
+        }
+    }
+
     #[inline]
     fn imap_inspect_action(&self, host: &Host) -> ProtocolInspectAction {
-        match self.audit_handle.imap_inspect_policy.check(host) {
-            (true, policy_action) => policy_action,
-            (false, missing_policy_action) => missing_policy_action,
-        }
+        self.logged_inspect_action(
+            InspectPolicyProtocol::Imap,
+            &self.audit_handle.imap_inspect_policy,
+            host,
+        )
     }
 
     #[inline]
@@ -317,6 +738,20 @@ impl<SC: ServerConfig> StreamInspectContext<SC> {
         self.audit_handle.imap_interception()
     }
 
+    #[inline]
+    fn pop3_inspect_action(&self, host: &Host) -> ProtocolInspectAction {
+        self.logged_inspect_action(
+            InspectPolicyProtocol::Pop3,
+            &self.audit_handle.pop3_inspect_policy,
+            host,
+        )
+    }
+
+    #[inline]
+    fn pop3_interception(&self) -> &Pop3InterceptionConfig {
+        self.audit_handle.pop3_interception()
+    }
+
     #[inline]
     fn task_max_idle_count(&self) -> i32 {
         self.task_max_idle_count
@@ -344,7 +779,121 @@ pub(crate) enum StreamInspection<SC: ServerConfig> {
     Websocket(websocket::H1WebsocketInterceptObject<SC>),
     Smtp(smtp::SmtpInterceptObject<SC>),
     Imap(imap::ImapInterceptObject<SC>),
+    Pop3(pop3::Pop3InterceptObject<SC>),
 }
 
 type BoxAsyncRead = Box<dyn AsyncRead + Send + Unpin + 'static>;
 type BoxAsyncWrite = Box<dyn AsyncWrite + Send + Unpin + 'static>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+
+    use g3_io_ext::IdleCheck;
+    use g3_types::metrics::MetricsName;
+    use g3_types::net::UpstreamAddr;
+    use yaml_rust::YamlLoader;
+
+    use crate::audit::Auditor;
+    use crate::config::audit::AuditorConfig;
+    use crate::config::server::dummy_close::DummyCloseServerConfig;
+    use crate::serve::TcpStreamServerStats;
+
+    fn new_ctx() -> StreamInspectContext<DummyCloseServerConfig> {
+        new_ctx_with_auditor(AuditorConfig::new(None))
+    }
+
+    fn new_ctx_with_auditor(
+        auditor_config: AuditorConfig,
+    ) -> StreamInspectContext<DummyCloseServerConfig> {
+        let name = MetricsName::from_str("test").unwrap();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 443);
+        let upstream = UpstreamAddr::from_str("example.net:443").unwrap();
+        let task_notes =
+            ServerTaskNotes::new(ClientConnectionInfo::new(addr, addr), None, Duration::ZERO);
+        StreamInspectContext::new(
+            Auditor::build_test_handle(auditor_config),
+            Arc::new(DummyCloseServerConfig::new(&name, None)),
+            Arc::new(TcpStreamServerStats::new(&name)),
+            Arc::new(ServerQuitPolicy::default()),
+            &task_notes,
+            &TcpConnectTaskNotes::new(upstream),
+        )
+    }
+
+    #[test]
+    fn inspect_summary() {
+        let mut ctx = new_ctx();
+        let summary = Arc::new(StreamInspectSummary::default());
+        ctx.set_summary(summary.clone());
+        assert_eq!(summary.max_depth(), 0);
+        assert!(!summary.tls_interception_attempted());
+
+        // TLS interception failed in a nested stream
+        let mut nested = ctx.clone();
+        nested.increase_inspection_depth();
+        nested.increase_inspection_depth();
+        nested.record_tls_interception(false);
+        assert_eq!(summary.max_depth(), 2);
+        assert!(summary.tls_interception_attempted());
+        assert!(!summary.tls_interception_succeeded());
+
+        // a shallower one succeeded later
+        ctx.increase_inspection_depth();
+        ctx.record_tls_interception(true);
+        assert_eq!(summary.max_depth(), 2);
+        assert!(summary.tls_interception_succeeded());
+
+        // no summary set
+        let mut ctx = new_ctx();
+        ctx.increase_inspection_depth();
+        ctx.record_tls_interception(true);
+        assert_eq!(ctx.current_inspection_depth(), 1);
+    }
+
+    #[test]
+    fn protocol_idle_timeout() {
+        let yaml = YamlLoader::load_from_str(
+            "{name: test, protocol_idle_timeout: {ssh: 2h, http: 30s, websocket: 11m, smtp: 10m, imap: 30m, pop3: 15m}}",
+        )
+        .unwrap();
+        let mut auditor_config = AuditorConfig::new(None);
+        auditor_config.parse(yaml[0].as_hash().unwrap()).unwrap();
+        let ctx = new_ctx_with_auditor(auditor_config);
+        // the server idle check duration is 5min, and the max idle count is 1
+        assert_eq!(ctx.task_max_idle_count(), 1);
+
+        let idle_count = |protocol: Protocol| {
+            let mut ctx = ctx.clone();
+            ctx.set_protocol_idle_timeout(protocol);
+            ctx.task_max_idle_count()
+        };
+        assert_eq!(idle_count(Protocol::Ssh), 24);
+        assert_eq!(idle_count(Protocol::SshLegacy), 24);
+        assert_eq!(idle_count(Protocol::Http1), 1);
+        assert_eq!(idle_count(Protocol::Http2), 1);
+        assert_eq!(idle_count(Protocol::Websocket), 3);
+        assert_eq!(idle_count(Protocol::Smtp), 2);
+        assert_eq!(idle_count(Protocol::Imap), 6);
+        assert_eq!(idle_count(Protocol::Pop3), 3);
+        // fallback to the server value
+        assert_eq!(idle_count(Protocol::FtpControl), 1);
+        assert_eq!(ctx.idle_checker().protocol_max_idle_count, None);
+
+        let yaml =
+            YamlLoader::load_from_str("{name: test, protocol_idle_timeout: {ssh: 1h}}").unwrap();
+        let mut auditor_config = AuditorConfig::new(None);
+        auditor_config.parse(yaml[0].as_hash().unwrap()).unwrap();
+        let mut ctx = new_ctx_with_auditor(auditor_config);
+        ctx.set_protocol_idle_timeout(Protocol::Websocket);
+        assert_eq!(ctx.task_max_idle_count(), 1);
+        ctx.set_protocol_idle_timeout(Protocol::Ssh);
+        assert_eq!(ctx.task_max_idle_count(), 12);
+        let idle_checker = ctx.idle_checker();
+        assert_eq!(idle_checker.protocol_max_idle_count, Some(12));
+        assert!(!idle_checker.check_quit(12));
+        assert!(idle_checker.check_quit(13));
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::str;
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub(super) enum CommandLineError {
+    #[error("no trailing sequence")]
+    NoTrailingSequence,
+    #[error("invalid utf-8 command line")]
+    InvalidUtf8Command,
+    #[error("no keyword found")]
+    NoKeyword,
+    #[error("missing parameter")]
+    MissingParameter,
+    #[error("invalid message number")]
+    InvalidMessageNumber,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Command {
+    User(String),
+    /// the password is not kept, so it won't be leaked to logs
+    Pass,
+    Apop(String),
+    /// the bool value indicates whether the SASL mechanism is set
+    Auth(bool),
+    StartTls,
+    Capability,
+    Stat,
+    /// the bool value indicates whether the message number is set
+    List(bool),
+    /// the bool value indicates whether the message number is set
+    Uidl(bool),
+    Retrieve,
+    Top,
+    Delete,
+    Noop,
+    Reset,
+    Quit,
+    Unknown,
+}
+
+impl Command {
+    /// RFC 2449 limits the command line to 255 octets, be lenient here
+    pub(super) const MAX_LINE_SIZE: usize = 512;
+
+    pub(super) fn parse_line(line: &[u8]) -> Result<Self, CommandLineError> {
+        let line = line
+            .strip_suffix(b"\r\n")
+            .or_else(|| line.strip_suffix(b"\n"))
+            .ok_or(CommandLineError::NoTrailingSequence)?;
+        let line = str::from_utf8(line).map_err(|_| CommandLineError::InvalidUtf8Command)?;
+
+        let mut iter = line.split_ascii_whitespace();
+        let keyword = iter.next().ok_or(CommandLineError::NoKeyword)?;
+        let mut next_param = || iter.next().ok_or(CommandLineError::MissingParameter);
+
+        let cmd = match keyword.to_uppercase().as_str() {
+            "USER" => Command::User(next_param()?.to_string()),
+            "PASS" => Command::Pass,
+            "APOP" => Command::Apop(next_param()?.to_string()),
+            "AUTH" => Command::Auth(next_param().is_ok()),
+            "STLS" => Command::StartTls,
+            "CAPA" => Command::Capability,
+            "STAT" => Command::Stat,
+            "LIST" => Command::List(next_param().is_ok()),
+            "UIDL" => Command::Uidl(next_param().is_ok()),
+            "RETR" => {
+                check_msg_number(next_param()?)?;
+                Command::Retrieve
+            }
+            "TOP" => {
+                check_msg_number(next_param()?)?;
+                Command::Top
+            }
+            "DELE" => {
+                check_msg_number(next_param()?)?;
+                Command::Delete
+            }
+            "NOOP" => Command::Noop,
+            "RSET" => Command::Reset,
+            "QUIT" => Command::Quit,
+            _ => Command::Unknown,
+        };
+        Ok(cmd)
+    }
+
+    /// Check if the positive response to this command will be a multi-line one
+    pub(super) fn has_multi_line_response(&self) -> bool {
+        match self {
+            Command::Capability | Command::Retrieve | Command::Top => true,
+            Command::List(has_arg) | Command::Uidl(has_arg) => !has_arg,
+            // list the supported SASL mechanisms
+            Command::Auth(has_mechanism) => !has_mechanism,
+            _ => false,
+        }
+    }
+}
+
+fn check_msg_number(s: &str) -> Result<(), CommandLineError> {
+    match s.parse::<u32>() {
+        Ok(0) | Err(_) => Err(CommandLineError::InvalidMessageNumber),
+        Ok(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Command::parse_line(b"USER alice\r\n").unwrap(),
+            Command::User("alice".to_string())
+        );
+        assert_eq!(
+            Command::parse_line(b"pass secret\r\n").unwrap(),
+            Command::Pass
+        );
+        assert_eq!(Command::parse_line(b"STLS\r\n").unwrap(), Command::StartTls);
+        assert_eq!(
+            Command::parse_line(b"RETR 1\r\n").unwrap(),
+            Command::Retrieve
+        );
+        assert_eq!(Command::parse_line(b"DELE 2\r\n").unwrap(), Command::Delete);
+        assert_eq!(Command::parse_line(b"TOP 1 10\r\n").unwrap(), Command::Top);
+        assert_eq!(
+            Command::parse_line(b"XTND XMIT\r\n").unwrap(),
+            Command::Unknown
+        );
+
+        assert_eq!(
+            Command::parse_line(b"RETR\r\n").unwrap_err(),
+            CommandLineError::MissingParameter
+        );
+        assert_eq!(
+            Command::parse_line(b"DELE 0\r\n").unwrap_err(),
+            CommandLineError::InvalidMessageNumber
+        );
+        assert_eq!(
+            Command::parse_line(b"QUIT").unwrap_err(),
+            CommandLineError::NoTrailingSequence
+        );
+    }
+
+    #[test]
+    fn multi_line_response() {
+        assert!(Command::Capability.has_multi_line_response());
+        assert!(Command::Retrieve.has_multi_line_response());
+        assert!(Command::List(false).has_multi_line_response());
+        assert!(!Command::List(true).has_multi_line_response());
+        assert!(!Command::Auth(true).has_multi_line_response());
+        assert!(!Command::Delete.has_multi_line_response());
+        assert!(!Command::Pass.has_multi_line_response());
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+
+use g3_io_ext::{LimitedCopy, LimitedCopyError, LimitedWriteExt, OnceBufReader, RecvLineError};
+use g3_smtp_proto::io::TextDataReader;
+
+use super::{
+    Command, CommandLineError, Pop3InterceptObject, Pop3RelayBuf, ResponseEncoder, ResponseStatus,
+};
+use crate::config::server::ServerConfig;
+use crate::serve::{ServerTaskError, ServerTaskResult};
+
+pub(super) enum ForwardNextAction {
+    Quit,
+    StartTls,
+}
+
+impl<SC> Pop3InterceptObject<SC>
+where
+    SC: ServerConfig + Send + Sync + 'static,
+{
+    pub(super) async fn relay_forward<CR, CW, UR, UW>(
+        &mut self,
+        buf: &mut Pop3RelayBuf,
+        clt_r: &mut CR,
+        clt_w: &mut CW,
+        ups_r: &mut UR,
+        ups_w: &mut UW,
+    ) -> ServerTaskResult<ForwardNextAction>
+    where
+        CR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+        UR: AsyncRead + Unpin,
+        UW: AsyncWrite + Unpin,
+    {
+        let config = self.ctx.pop3_interception();
+        let command_wait_timeout = config.command_wait_timeout;
+        let response_wait_timeout = config.response_wait_timeout;
+        let quit_wait_timeout = config.quit_wait_timeout;
+
+        loop {
+            buf.cmd_recv_buf.consume_line();
+            let line = buf
+                .cmd_recv_buf
+                .read_line_with_timeout(clt_r, command_wait_timeout)
+                .await
+                .map_err(command_recv_error)?;
+            let cmd = match Command::parse_line(line) {
+                Ok(cmd) => cmd,
+                Err(CommandLineError::NoTrailingSequence) => {
+                    return Err(ServerTaskError::ClientAppError(anyhow!(
+                        "invalid POP3 command line: {}",
+                        CommandLineError::NoTrailingSequence
+                    )));
+                }
+                Err(_) => {
+                    ResponseEncoder::INVALID_COMMAND
+                        .write(clt_w)
+                        .await
+                        .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+                    continue;
+                }
+            };
+            ups_w
+                .write_all_flush(line)
+                .await
+                .map_err(ServerTaskError::UpstreamWriteFailed)?;
+
+            if cmd == Command::Quit {
+                self.client_quit = true;
+                self.relay_response(buf, ups_r, clt_w, quit_wait_timeout, false)
+                    .await?;
+                return Ok(ForwardNextAction::Quit);
+            }
+
+            let status = if matches!(cmd, Command::Auth(true)) {
+                self.relay_sasl_exchange(buf, clt_r, clt_w, ups_r, ups_w)
+                    .await?
+            } else {
+                self.relay_response(
+                    buf,
+                    ups_r,
+                    clt_w,
+                    response_wait_timeout,
+                    cmd.has_multi_line_response(),
+                )
+                .await?
+            };
+            if status != ResponseStatus::Positive {
+                continue;
+            }
+
+            match cmd {
+                Command::User(name) => self.user = Some(name),
+                Command::Apop(name) => {
+                    self.user = Some(name);
+                    self.authenticated = true;
+                }
+                Command::Pass | Command::Auth(true) => self.authenticated = true,
+                Command::Retrieve => self.retr_count += 1,
+                Command::Delete => self.dele_count += 1,
+                Command::StartTls => return Ok(ForwardNextAction::StartTls),
+                _ => {}
+            }
+        }
+    }
+
+    async fn relay_sasl_exchange<CR, CW, UR, UW>(
+        &mut self,
+        buf: &mut Pop3RelayBuf,
+        clt_r: &mut CR,
+        clt_w: &mut CW,
+        ups_r: &mut UR,
+        ups_w: &mut UW,
+    ) -> ServerTaskResult<ResponseStatus>
+    where
+        CR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+        UR: AsyncRead + Unpin,
+        UW: AsyncWrite + Unpin,
+    {
+        let config = self.ctx.pop3_interception();
+        let command_wait_timeout = config.command_wait_timeout;
+        let response_wait_timeout = config.response_wait_timeout;
+
+        loop {
+            let status = self
+                .relay_response(buf, ups_r, clt_w, response_wait_timeout, false)
+                .await?;
+            if status != ResponseStatus::Continuation {
+                return Ok(status);
+            }
+
+            buf.cmd_recv_buf.consume_line();
+            let line = buf
+                .cmd_recv_buf
+                .read_line_with_timeout(clt_r, command_wait_timeout)
+                .await
+                .map_err(command_recv_error)?;
+            ups_w
+                .write_all_flush(line)
+                .await
+                .map_err(ServerTaskError::UpstreamWriteFailed)?;
+        }
+    }
+
+    async fn relay_response<UR, CW>(
+        &mut self,
+        buf: &mut Pop3RelayBuf,
+        ups_r: &mut UR,
+        clt_w: &mut CW,
+        timeout: Duration,
+        multi_line: bool,
+    ) -> ServerTaskResult<ResponseStatus>
+    where
+        UR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+    {
+        buf.rsp_recv_buf.consume_line();
+        let line = buf
+            .rsp_recv_buf
+            .read_line_with_timeout(ups_r, timeout)
+            .await
+            .map_err(response_recv_error)?;
+        let status = ResponseStatus::parse_line(line).map_err(|e| {
+            ServerTaskError::UpstreamAppError(anyhow!("invalid POP3 response line: {e}"))
+        })?;
+        clt_w
+            .write_all_flush(line)
+            .await
+            .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+
+        if multi_line && status == ResponseStatus::Positive {
+            buf.rsp_recv_buf.consume_line();
+            let cached = buf.rsp_recv_buf.consume_left(ResponseStatus::MAX_LINE_SIZE);
+            let mut ups_r = OnceBufReader::with_bytes(ups_r, Bytes::copy_from_slice(cached));
+            let mut reader = TextDataReader::new(&mut ups_r);
+            self.transfer_data(&mut reader, clt_w).await?;
+            if !reader.finished() {
+                return Err(ServerTaskError::ClosedByUpstream);
+            }
+        }
+
+        Ok(status)
+    }
+
+    async fn transfer_data<UR, CW>(
+        &mut self,
+        ups_r: &mut UR,
+        clt_w: &mut CW,
+    ) -> ServerTaskResult<()>
+    where
+        UR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+    {
+        let mut ups_to_clt =
+            LimitedCopy::new(ups_r, clt_w, &self.ctx.server_config.limited_copy_config());
+
+        let idle_duration = self.ctx.server_config.task_idle_check_duration();
+        let mut idle_interval =
+            tokio::time::interval_at(Instant::now() + idle_duration, idle_duration);
+        let mut idle_count = 0;
+        let max_idle_count = self.ctx.pop3_interception().transfer_max_idle_count;
+
+        loop {
+            tokio::select! {
+                biased;
+
+                r = &mut ups_to_clt => {
+                    return match r {
+                        Ok(_) => Ok(()),
+                        Err(LimitedCopyError::ReadFailed(e)) => {
+                            let _ = ups_to_clt.write_flush().await;
+                            Err(ServerTaskError::UpstreamReadFailed(e))
+                        }
+                        Err(LimitedCopyError::WriteFailed(e)) => Err(ServerTaskError::ClientTcpWriteFailed(e)),
+                    };
+                }
+                _ = idle_interval.tick() => {
+                    if ups_to_clt.is_idle() {
+                        idle_count += 1;
+                        if idle_count >= max_idle_count {
+                            return if ups_to_clt.no_cached_data() {
+                                Err(ServerTaskError::UpstreamAppTimeout("idle while reading POP3 multi-line response"))
+                            } else {
+                                Err(ServerTaskError::ClientAppTimeout("idle while sending POP3 multi-line response"))
+                            };
+                        }
+                    } else {
+                        idle_count = 0;
+                        ups_to_clt.reset_active();
+                    }
+
+                    if self.ctx.belongs_to_blocked_user() {
+                        let _ = ups_to_clt.write_flush().await;
+                        return Err(ServerTaskError::CanceledAsUserBlocked);
+                    }
+
+                    if self.ctx.server_force_quit() {
+                        let _ = ups_to_clt.write_flush().await;
+                        return Err(ServerTaskError::CanceledAsServerQuit)
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn command_recv_error(e: RecvLineError) -> ServerTaskError {
+    match e {
+        RecvLineError::IoError(e) => ServerTaskError::ClientTcpReadFailed(e),
+        RecvLineError::IoClosed => ServerTaskError::ClosedByClient,
+        RecvLineError::Timeout => ServerTaskError::ClientAppTimeout("timeout to get POP3 command"),
+        RecvLineError::LineTooLong => {
+            ServerTaskError::ClientAppError(anyhow!("POP3 command line too long"))
+        }
+    }
+}
+
+fn response_recv_error(e: RecvLineError) -> ServerTaskError {
+    match e {
+        RecvLineError::IoError(e) => ServerTaskError::UpstreamReadFailed(e),
+        RecvLineError::IoClosed => ServerTaskError::ClosedByUpstream,
+        RecvLineError::Timeout => {
+            ServerTaskError::UpstreamAppTimeout("timeout to get POP3 response")
+        }
+        RecvLineError::LineTooLong => {
+            ServerTaskError::UpstreamAppError(anyhow!("POP3 response line too long"))
+        }
+    }
+}
@@ -0,0 +1,194 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use g3_io_ext::{OnceBufReader, RecvLineError};
+
+use super::{ResponseEncoder, ResponseLineError, ResponseStatus};
+use crate::inspect::line_protocol::{LineProtocolParser, LineProtocolRelay, LineRelayAction};
+use crate::serve::ServerTaskError;
+
+#[derive(Default)]
+struct GreetingParser {
+    close_service: bool,
+}
+
+impl LineProtocolParser for GreetingParser {
+    type Error = GreetingError;
+
+    fn feed_line(&mut self, line: &[u8]) -> Result<LineRelayAction<GreetingError>, GreetingError> {
+        match ResponseStatus::parse_line(line)? {
+            ResponseStatus::Positive => Ok(LineRelayAction::Finish),
+            ResponseStatus::Negative => {
+                self.close_service = true;
+                Ok(LineRelayAction::Finish)
+            }
+            ResponseStatus::Continuation => Err(GreetingError::InvalidResponseLine(
+                ResponseLineError::InvalidStatus,
+            )),
+        }
+    }
+
+    fn client_write_error(e: io::Error) -> GreetingError {
+        GreetingError::ClientWriteFailed(e)
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Greeting {
+    parser: GreetingParser,
+    relay: LineProtocolRelay<{ ResponseStatus::MAX_LINE_SIZE }>,
+}
+
+impl Greeting {
+    /// The upstream sent a negative greeting and will close the connection
+    #[inline]
+    pub(super) fn close_service(&self) -> bool {
+        self.parser.close_service
+    }
+
+    pub(super) async fn relay<UR, CW>(
+        &mut self,
+        mut ups_r: OnceBufReader<UR>,
+        clt_w: &mut CW,
+        timeout: Duration,
+    ) -> Result<OnceBufReader<UR>, GreetingError>
+    where
+        UR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+    {
+        self.relay
+            .relay(&mut self.parser, &mut ups_r, clt_w, timeout)
+            .await?;
+        Ok(self.relay.keep_left_data(ups_r))
+    }
+
+    pub(super) async fn reply_no_service<CW>(&self, e: &GreetingError, clt_w: &mut CW)
+    where
+        CW: AsyncWrite + Unpin,
+    {
+        if self.relay.total_to_write() > 0 {
+            return;
+        }
+        let rsp = match e {
+            GreetingError::Timeout => ResponseEncoder::UPSTREAM_TIMEOUT,
+            GreetingError::InvalidResponseLine(_)
+            | GreetingError::TooLongResponseLine
+            | GreetingError::UpstreamClosed => ResponseEncoder::UPSTREAM_NOT_READY,
+            GreetingError::UpstreamReadFailed(_) => ResponseEncoder::UPSTREAM_ERROR,
+            GreetingError::ClientWriteFailed(_) => return,
+        };
+        let _ = rsp.write_and_close(clt_w).await;
+    }
+}
+
+#[derive(Debug, Error)]
+pub(super) enum GreetingError {
+    #[error("greeting timeout")]
+    Timeout,
+    #[error("invalid greeting response line: {0}")]
+    InvalidResponseLine(#[from] ResponseLineError),
+    #[error("response line too long")]
+    TooLongResponseLine,
+    #[error("write to client failed: {0:?}")]
+    ClientWriteFailed(io::Error),
+    #[error("read from upstream failed: {0:?}")]
+    UpstreamReadFailed(io::Error),
+    #[error("upstream closed connection")]
+    UpstreamClosed,
+}
+
+impl From<RecvLineError> for GreetingError {
+    fn from(value: RecvLineError) -> Self {
+        match value {
+            RecvLineError::IoError(e) => GreetingError::UpstreamReadFailed(e),
+            RecvLineError::IoClosed => GreetingError::UpstreamClosed,
+            RecvLineError::Timeout => GreetingError::Timeout,
+            RecvLineError::LineTooLong => GreetingError::TooLongResponseLine,
+        }
+    }
+}
+
+impl From<GreetingError> for ServerTaskError {
+    fn from(value: GreetingError) -> Self {
+        match value {
+            GreetingError::Timeout => ServerTaskError::UpstreamAppTimeout("pop3 greeting timeout"),
+            GreetingError::InvalidResponseLine(e) => {
+                ServerTaskError::UpstreamAppError(anyhow!("invalid greeting response line: {e}"))
+            }
+            GreetingError::TooLongResponseLine => {
+                ServerTaskError::UpstreamAppError(anyhow!("response line too long"))
+            }
+            GreetingError::ClientWriteFailed(e) => ServerTaskError::ClientTcpWriteFailed(e),
+            GreetingError::UpstreamReadFailed(e) => ServerTaskError::UpstreamReadFailed(e),
+            GreetingError::UpstreamClosed => ServerTaskError::ClosedByUpstream,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn relay_greeting() {
+        const BANNER: &[u8] = b"+OK POP3 server ready <1896.697170952@dbc.mtview.ca.us>\r\n";
+
+        let ups_r = OnceBufReader::with_bytes(tokio::io::empty(), Bytes::from_static(BANNER));
+        let mut clt_w = Vec::new();
+        let mut greeting = Greeting::default();
+        greeting
+            .relay(ups_r, &mut clt_w, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(!greeting.close_service());
+        assert_eq!(clt_w.as_slice(), BANNER);
+
+        let ups_r = OnceBufReader::with_bytes(
+            tokio::io::empty(),
+            Bytes::from_static(b"-ERR too many connections\r\n"),
+        );
+        let mut clt_w = Vec::new();
+        let mut greeting = Greeting::default();
+        greeting
+            .relay(ups_r, &mut clt_w, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(greeting.close_service());
+
+        let ups_r = OnceBufReader::with_bytes(
+            tokio::io::empty(),
+            Bytes::from_static(b"220 smtp.example.net\r\n"),
+        );
+        let mut clt_w = Vec::new();
+        let mut greeting = Greeting::default();
+        let Err(e) = greeting
+            .relay(ups_r, &mut clt_w, Duration::from_secs(1))
+            .await
+        else {
+            panic!("the greeting should be invalid");
+        };
+        assert!(matches!(e, GreetingError::InvalidResponseLine(_)));
+        assert!(clt_w.is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use slog::slog_info;
+use tokio::io::AsyncWriteExt;
+
+use g3_dpi::{Protocol, ProtocolInspectAction};
+use g3_io_ext::{LineRecvBuf, OnceBufReader};
+use g3_slog_types::{LtUpstreamAddr, LtUuid};
+use g3_types::net::UpstreamAddr;
+
+use super::StartTlsProtocol;
+#[cfg(feature = "quic")]
+use crate::audit::{DetourAction, StreamDetourFallback, StreamDetourFallbackAction};
+use crate::config::server::ServerConfig;
+use crate::inspect::{BoxAsyncRead, BoxAsyncWrite, StreamInspectContext, StreamInspection};
+use crate::serve::{ServerTaskError, ServerTaskResult};
+
+mod command;
+use command::{Command, CommandLineError};
+
+mod response;
+use response::{ResponseEncoder, ResponseLineError, ResponseStatus};
+
+mod greeting;
+use greeting::Greeting;
+
+mod forward;
+use forward::ForwardNextAction;
+
+#[derive(Default)]
+struct Pop3RelayBuf {
+    cmd_recv_buf: LineRecvBuf<{ Command::MAX_LINE_SIZE }>,
+    rsp_recv_buf: LineRecvBuf<{ ResponseStatus::MAX_LINE_SIZE }>,
+}
+
+macro_rules! intercept_log {
+    ($obj:tt, $($args:tt)+) => {
+        slog_info!($obj.ctx.intercept_logger(), $($args)+;
+            "intercept_type" => "Pop3Connection",
+            "task_id" => LtUuid($obj.ctx.server_task_id()),
+            "depth" => $obj.ctx.inspection_depth,
+            "upstream" => LtUpstreamAddr(&$obj.upstream),
+            "user" => $obj.user.as_deref(),
+            "authenticated" => $obj.authenticated,
+            "retr_count" => $obj.retr_count,
+            "dele_count" => $obj.dele_count,
+            "server_close" => $obj.server_close,
+            "client_quit" => $obj.client_quit,
+        )
+    };
+}
+
+struct Pop3Io {
+    pub(crate) clt_r: BoxAsyncRead,
+    pub(crate) clt_w: BoxAsyncWrite,
+    pub(crate) ups_r: OnceBufReader<BoxAsyncRead>,
+    pub(crate) ups_w: BoxAsyncWrite,
+}
+
+pub(crate) struct Pop3InterceptObject<SC: ServerConfig> {
+    io: Option<Pop3Io>,
+    ctx: StreamInspectContext<SC>,
+    upstream: UpstreamAddr,
+    from_starttls: bool,
+    user: Option<String>,
+    authenticated: bool,
+    retr_count: usize,
+    dele_count: usize,
+    server_close: bool,
+    client_quit: bool,
+}
+
+impl<SC> Pop3InterceptObject<SC>
+where
+    SC: ServerConfig + Send + Sync + 'static,
+{
+    pub(crate) fn new(mut ctx: StreamInspectContext<SC>, upstream: UpstreamAddr) -> Self {
+        ctx.set_protocol_idle_timeout(Protocol::Pop3);
+        Pop3InterceptObject {
+            io: None,
+            ctx,
+            upstream,
+            from_starttls: false,
+            user: None,
+            authenticated: false,
+            retr_count: 0,
+            dele_count: 0,
+            server_close: false,
+            client_quit: false,
+        }
+    }
+
+    pub(crate) fn set_from_starttls(&mut self) {
+        self.from_starttls = true;
+    }
+
+    pub(crate) fn set_io(
+        &mut self,
+        clt_r: BoxAsyncRead,
+        clt_w: BoxAsyncWrite,
+        ups_r: OnceBufReader<BoxAsyncRead>,
+        ups_w: BoxAsyncWrite,
+    ) {
+        let io = Pop3Io {
+            clt_r,
+            clt_w,
+            ups_r,
+            ups_w,
+        };
+        self.io = Some(io);
+    }
+
+    pub(crate) async fn intercept(mut self) -> ServerTaskResult<Option<StreamInspection<SC>>> {
+        let r = match self.ctx.pop3_inspect_action(self.upstream.host()) {
+            ProtocolInspectAction::Intercept => self.do_intercept().await,
+            #[cfg(feature = "quic")]
+            ProtocolInspectAction::Detour => self.do_detour().await.map(|_| None),
+            ProtocolInspectAction::Bypass => self.do_bypass().await.map(|_| None),
+            ProtocolInspectAction::Block | ProtocolInspectAction::BlockWithResponse => {
+                self.do_block().await.map(|_| None)
+            }
+        };
+        match r {
+            Ok(obj) => {
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished");
+                }
+                Ok(obj)
+            }
+            Err(e) => {
+                intercept_log!(self, "{e}");
+                Err(e)
+            }
+        }
+    }
+
+    /// Handle the stream as decided by [StreamDetourFallback] if the stream detour is unavailable
+    #[cfg(feature = "quic")]
+    async fn do_detour_fallback(&mut self, e: Option<anyhow::Error>) -> ServerTaskResult<()> {
+        let fallback = StreamDetourFallback::new(self.ctx.audit_handle.stream_detour_on_error(), e);
+        intercept_log!(self, "{fallback}");
+        match fallback.action {
+            StreamDetourFallbackAction::Block => self.do_block().await,
+            StreamDetourFallbackAction::Bypass => self.do_bypass().await,
+            StreamDetourFallbackAction::Close(e) => {
+                self.close_on_detour_error().await;
+                Err(ServerTaskError::InternalAdapterError(e))
+            }
+        }
+    }
+
+    #[cfg(feature = "quic")]
+    async fn do_detour(&mut self) -> ServerTaskResult<()> {
+        let Some(client) = self.ctx.audit_handle.stream_detour_client() else {
+            return self.do_detour_fallback(None).await;
+        };
+
+        let mut detour_stream = match client.open_detour_stream().await {
+            Ok(s) => s,
+            Err(e) => return self.do_detour_fallback(Some(e)).await,
+        };
+
+        let detour_ctx = client.build_context(
+            &self.ctx.server_config,
+            &self.ctx.server_quit_policy,
+            &self.ctx.task_notes,
+            &self.upstream,
+            Protocol::Pop3,
+        );
+
+        match detour_ctx.check_detour_action(&mut detour_stream).await {
+            Ok(DetourAction::Continue) => {
+                let Pop3Io {
+                    clt_r,
+                    clt_w,
+                    ups_r,
+                    ups_w,
+                } = self.io.take().unwrap();
+
+                detour_ctx
+                    .relay(clt_r, clt_w, ups_r, ups_w, detour_stream)
+                    .await
+            }
+            Ok(DetourAction::Bypass) => {
+                detour_stream.finish();
+                self.do_bypass().await
+            }
+            Ok(DetourAction::Block) => {
+                detour_stream.finish();
+                self.do_block().await
+            }
+            Err(e) => {
+                detour_stream.finish();
+                self.close_on_detour_error().await;
+                Err(ServerTaskError::InternalAdapterError(e))
+            }
+        }
+    }
+
+    #[cfg(feature = "quic")]
+    async fn close_on_detour_error(&mut self) {
+        let Pop3Io {
+            clt_r: _,
+            mut clt_w,
+            ups_r: _,
+            mut ups_w,
+        } = self.io.take().unwrap();
+
+        tokio::spawn(async move {
+            let _ = ups_w.shutdown().await;
+        });
+
+        let _ = ResponseEncoder::INTERNAL_ERROR
+            .write_and_close(&mut clt_w)
+            .await;
+    }
+
+    async fn do_bypass(&mut self) -> ServerTaskResult<()> {
+        let Pop3Io {
+            clt_r,
+            clt_w,
+            ups_r,
+            ups_w,
+        } = self.io.take().unwrap();
+
+        self.ctx
+            .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+            .await
+    }
+
+    async fn do_block(&mut self) -> ServerTaskResult<()> {
+        let Pop3Io {
+            clt_r: _,
+            mut clt_w,
+            ups_r: _,
+            mut ups_w,
+        } = self.io.take().unwrap();
+
+        tokio::spawn(async move {
+            let _ = ups_w.shutdown().await;
+        });
+
+        ResponseEncoder::BLOCKED
+            .write(&mut clt_w)
+            .await
+            .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+        self.ctx.tarpit_blocked(&mut clt_w).await;
+        clt_w
+            .shutdown()
+            .await
+            .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+        Err(ServerTaskError::InternalAdapterError(anyhow!(
+            "pop3 blocked by inspection policy"
+        )))
+    }
+
+    async fn do_intercept(&mut self) -> ServerTaskResult<Option<StreamInspection<SC>>> {
+        let Pop3Io {
+            clt_r,
+            mut clt_w,
+            ups_r,
+            ups_w,
+        } = self.io.take().unwrap();
+
+        if self.from_starttls {
+            // the capabilities may be changed after STLS, so the client should send CAPA again
+            return self.start_forward(clt_r, clt_w, ups_r, ups_w).await;
+        }
+
+        let mut greeting = Greeting::default();
+        let ups_r = match greeting
+            .relay(
+                ups_r,
+                &mut clt_w,
+                self.ctx.pop3_interception().greeting_timeout,
+            )
+            .await
+        {
+            Ok(ups_r) => ups_r,
+            Err(e) => {
+                greeting.reply_no_service(&e, &mut clt_w).await;
+                return Err(e.into());
+            }
+        };
+        if greeting.close_service() {
+            self.server_close = true;
+            return Ok(None);
+        }
+
+        self.start_forward(clt_r, clt_w, ups_r, ups_w).await
+    }
+
+    async fn start_forward(
+        &mut self,
+        mut clt_r: BoxAsyncRead,
+        mut clt_w: BoxAsyncWrite,
+        mut ups_r: OnceBufReader<BoxAsyncRead>,
+        mut ups_w: BoxAsyncWrite,
+    ) -> ServerTaskResult<Option<StreamInspection<SC>>> {
+        let mut relay_buf = Pop3RelayBuf::default();
+        match self
+            .relay_forward(
+                &mut relay_buf,
+                &mut clt_r,
+                &mut clt_w,
+                &mut ups_r,
+                &mut ups_w,
+            )
+            .await?
+        {
+            ForwardNextAction::Quit => Ok(None),
+            ForwardNextAction::StartTls => {
+                // keep the data pipelined after the STLS command and response
+                relay_buf.cmd_recv_buf.consume_line();
+                relay_buf.rsp_recv_buf.consume_line();
+                let clt_r = carry_buffered(clt_r, relay_buf.cmd_recv_buf.consume_left(usize::MAX));
+                let ups_r: BoxAsyncRead = if ups_r.buf().is_some() {
+                    Box::new(ups_r)
+                } else {
+                    ups_r.into_inner()
+                };
+                let ups_r = carry_buffered(ups_r, relay_buf.rsp_recv_buf.consume_left(usize::MAX));
+                if let Some(tls_interception) = self.ctx.tls_interception() {
+                    let mut start_tls_obj = crate::inspect::start_tls::StartTlsInterceptObject::new(
+                        self.ctx.clone(),
+                        self.upstream.clone(),
+                        tls_interception,
+                        StartTlsProtocol::Pop3,
+                    );
+                    start_tls_obj.set_io(clt_r, clt_w, ups_r, ups_w);
+                    Ok(Some(StreamInspection::StartTls(start_tls_obj)))
+                } else {
+                    self.ctx
+                        .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+                        .await
+                        .map(|_| None)
+                }
+            }
+        }
+    }
+}
+
+/// Keep the data that has been read in but not handled yet
+fn carry_buffered(reader: BoxAsyncRead, buffered: &[u8]) -> BoxAsyncRead {
+    if buffered.is_empty() {
+        reader
+    } else {
+        Box::new(OnceBufReader::with_bytes(
+            reader,
+            Bytes::copy_from_slice(buffered),
+        ))
+    }
+}
@@ -0,0 +1,131 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+
+use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use g3_io_ext::LimitedWriteExt;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub(super) enum ResponseLineError {
+    #[error("no trailing sequence")]
+    NoTrailingSequence,
+    #[error("invalid status indicator")]
+    InvalidStatus,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ResponseStatus {
+    Positive,
+    Negative,
+    /// SASL continuation response
+    Continuation,
+}
+
+impl ResponseStatus {
+    /// RFC 2449 limits the response line to 512 octets
+    pub(super) const MAX_LINE_SIZE: usize = 512;
+
+    pub(super) fn parse_line(line: &[u8]) -> Result<Self, ResponseLineError> {
+        let line = line
+            .strip_suffix(b"\r\n")
+            .ok_or(ResponseLineError::NoTrailingSequence)?;
+        let (indicator, left) = match memchr::memchr(b' ', line) {
+            Some(p) => (&line[..p], &line[p..]),
+            None => (line, &line[line.len()..]),
+        };
+        if indicator.eq_ignore_ascii_case(b"+OK") {
+            Ok(ResponseStatus::Positive)
+        } else if indicator.eq_ignore_ascii_case(b"-ERR") {
+            Ok(ResponseStatus::Negative)
+        } else if indicator == b"+" && (left.is_empty() || left[0] == b' ') {
+            Ok(ResponseStatus::Continuation)
+        } else {
+            Err(ResponseLineError::InvalidStatus)
+        }
+    }
+}
+
+pub(super) struct ResponseEncoder(&'static str);
+
+impl ResponseEncoder {
+    pub(super) const BLOCKED: ResponseEncoder = ResponseEncoder("-ERR blocked by policy\r\n");
+    #[cfg(feature = "quic")]
+    pub(super) const INTERNAL_ERROR: ResponseEncoder =
+        ResponseEncoder("-ERR internal server error\r\n");
+    pub(super) const INVALID_COMMAND: ResponseEncoder =
+        ResponseEncoder("-ERR invalid command line\r\n");
+    pub(super) const UPSTREAM_NOT_READY: ResponseEncoder =
+        ResponseEncoder("-ERR upstream service not ready\r\n");
+    pub(super) const UPSTREAM_TIMEOUT: ResponseEncoder =
+        ResponseEncoder("-ERR upstream response timeout\r\n");
+    pub(super) const UPSTREAM_ERROR: ResponseEncoder =
+        ResponseEncoder("-ERR upstream read failed\r\n");
+
+    pub(super) async fn write<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        writer.write_all_flush(self.0.as_bytes()).await
+    }
+
+    pub(super) async fn write_and_close<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.write(writer).await?;
+        writer.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            ResponseStatus::parse_line(b"+OK POP3 server ready\r\n").unwrap(),
+            ResponseStatus::Positive
+        );
+        assert_eq!(
+            ResponseStatus::parse_line(b"+OK\r\n").unwrap(),
+            ResponseStatus::Positive
+        );
+        assert_eq!(
+            ResponseStatus::parse_line(b"-ERR no such message\r\n").unwrap(),
+            ResponseStatus::Negative
+        );
+        assert_eq!(
+            ResponseStatus::parse_line(b"+ \r\n").unwrap(),
+            ResponseStatus::Continuation
+        );
+        assert_eq!(
+            ResponseStatus::parse_line(b"+ dXNlcm5hbWU6\r\n").unwrap(),
+            ResponseStatus::Continuation
+        );
+        assert_eq!(
+            ResponseStatus::parse_line(b"+OKAY\r\n").unwrap_err(),
+            ResponseLineError::InvalidStatus
+        );
+        assert_eq!(
+            ResponseStatus::parse_line(b"+OK").unwrap_err(),
+            ResponseLineError::NoTrailingSequence
+        );
+    }
+}
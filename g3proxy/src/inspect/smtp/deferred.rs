@@ -0,0 +1,363 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use g3_dpi::{MaybeProtocol, ProtocolInspectAction};
+use g3_io_ext::{LimitedWriteExt, LineRecvBuf};
+use g3_smtp_proto::command::Command;
+use g3_smtp_proto::response::ResponseEncoder;
+use g3_types::net::UpstreamAddr;
+
+use super::ext::CommandLineRecvExt;
+use crate::audit::{AuditContext, AuditHandle};
+use crate::serve::{ServerTaskError, ServerTaskResult};
+
+/// The local greeting stage of an intercepted SMTP session, which runs before the upstream
+/// connection is established.
+///
+/// The local greeting banner is sent to the client at once, and the client EHLO / HELO command
+/// will be held and replayed to the upstream MTA after the connection is established.
+pub(crate) struct SmtpDeferredGreeting {
+    audit_handle: Arc<AuditHandle>,
+    local_ip: IpAddr,
+    banner: String,
+    held: Bytes,
+}
+
+impl SmtpDeferredGreeting {
+    /// Take the audit handle if the upstream connection should be deferred for this task.
+    pub(crate) fn check(
+        audit_ctx: &mut AuditContext,
+        local_ip: IpAddr,
+        upstream: &UpstreamAddr,
+    ) -> Option<Self> {
+        let handle = audit_ctx.handle()?;
+        let banner = handle.smtp_interception().greeting_banner.as_ref()?;
+        if !banner.defer_upstream_connection {
+            return None;
+        }
+        if !handle
+            .server_tcp_portmap()
+            .contains(upstream.port(), MaybeProtocol::Smtp)
+        {
+            return None;
+        }
+        let (_, action) = handle.smtp_inspect_policy.check_rule(upstream.host());
+        if action != ProtocolInspectAction::Intercept {
+            return None;
+        }
+        let banner = banner.encode(local_ip, upstream.host());
+
+        let audit_handle = audit_ctx.check_take_handle()?;
+        Some(SmtpDeferredGreeting {
+            audit_handle,
+            local_ip,
+            banner,
+            held: Bytes::new(),
+        })
+    }
+
+    /// Send the local greeting banner and wait for the client EHLO / HELO command.
+    ///
+    /// Commands other than EHLO / HELO / QUIT will be rejected with a 503 reply.
+    pub(crate) async fn greet<CR, CW>(
+        &mut self,
+        clt_r: &mut CR,
+        clt_w: &mut CW,
+    ) -> ServerTaskResult<()>
+    where
+        CR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+    {
+        let config = self.audit_handle.smtp_interception();
+
+        clt_w
+            .write_all_flush(self.banner.as_bytes())
+            .await
+            .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+
+        let mut recv_buf = LineRecvBuf::<{ Command::MAX_LINE_SIZE }>::default();
+        loop {
+            recv_buf.consume_line();
+            recv_buf
+                .wait_cmd(config.command_idle_timeout, clt_r, clt_w, self.local_ip)
+                .await?;
+            let (cmd, _) = recv_buf
+                .recv_cmd(config.command_wait_timeout, clt_r, clt_w)
+                .await?;
+
+            match cmd {
+                Command::ExtendHello(_) | Command::Hello(_) => {
+                    // keep the command line and all the pipelined data after it
+                    self.held = Bytes::copy_from_slice(recv_buf.consume_left(usize::MAX));
+                    return Ok(());
+                }
+                Command::Quit => {
+                    let _ = ResponseEncoder::local_service_closing(self.local_ip)
+                        .write(clt_w)
+                        .await;
+                    let _ = clt_w.shutdown().await;
+                    return Err(ServerTaskError::ClosedByClient);
+                }
+                _ => {
+                    ResponseEncoder::BAD_SEQUENCE_OF_COMMANDS
+                        .write(clt_w)
+                        .await
+                        .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+                }
+            }
+        }
+    }
+
+    /// Tell the client that the service is not available as we failed to connect to upstream.
+    pub(crate) async fn reply_connect_failed<CW>(&self, clt_w: &mut CW)
+    where
+        CW: AsyncWrite + Unpin,
+    {
+        let _ = ResponseEncoder::local_service_not_available(self.local_ip)
+            .write(clt_w)
+            .await;
+        let _ = clt_w.shutdown().await;
+    }
+
+    pub(crate) fn into_parts(self) -> (Arc<AuditHandle>, Bytes) {
+        (self.audit_handle, self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+    use yaml_rust::YamlLoader;
+
+    use g3_daemon::server::{ClientConnectionInfo, ServerQuitPolicy};
+    use g3_types::metrics::MetricsName;
+
+    use crate::audit::Auditor;
+    use crate::config::audit::AuditorConfig;
+    use crate::config::server::dummy_close::DummyCloseServerConfig;
+    use crate::inspect::StreamInspectContext;
+    use crate::module::tcp_connect::TcpConnectTaskNotes;
+    use crate::serve::{ServerTaskNotes, TcpStreamServerStats};
+
+    const LOCAL_IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const DEFERRED: &str = "smtp_interception: {greeting_banner: \
+        {hostname: mx.proxy.net, message: ESMTP ready, defer_upstream_connection: true}}";
+
+    fn new_audit_ctx(auditor: &str) -> AuditContext {
+        let yaml = YamlLoader::load_from_str(&format!("{{name: test, {auditor}}}")).unwrap();
+        let mut auditor_config = AuditorConfig::new(None);
+        auditor_config.parse(yaml[0].as_hash().unwrap()).unwrap();
+        AuditContext::new(Some(Auditor::build_test_handle(auditor_config)))
+    }
+
+    async fn read_until<R: AsyncRead + Unpin>(r: &mut R, end: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !data.ends_with(end) {
+            let n = r.read(&mut buf).await.unwrap();
+            assert_ne!(n, 0, "unexpected eof");
+            data.extend_from_slice(&buf[..n]);
+        }
+        data
+    }
+
+    #[test]
+    fn check() {
+        let upstream = UpstreamAddr::from_str("example.net:25").unwrap();
+
+        let mut audit_ctx = new_audit_ctx(DEFERRED);
+        assert!(SmtpDeferredGreeting::check(&mut audit_ctx, LOCAL_IP, &upstream).is_some());
+        assert!(audit_ctx.handle().is_none());
+
+        // not a SMTP port
+        let mut audit_ctx = new_audit_ctx(DEFERRED);
+        let https_upstream = UpstreamAddr::from_str("example.net:443").unwrap();
+        assert!(SmtpDeferredGreeting::check(&mut audit_ctx, LOCAL_IP, &https_upstream).is_none());
+        assert!(audit_ctx.handle().is_some());
+
+        // not deferred
+        let mut audit_ctx = new_audit_ctx("smtp_interception: {greeting_banner: ESMTP ready}");
+        assert!(SmtpDeferredGreeting::check(&mut audit_ctx, LOCAL_IP, &upstream).is_none());
+        assert!(audit_ctx.handle().is_some());
+
+        // not intercepted
+        let mut audit_ctx = new_audit_ctx(&format!("{DEFERRED}, smtp_inspect_policy: bypass"));
+        assert!(SmtpDeferredGreeting::check(&mut audit_ctx, LOCAL_IP, &upstream).is_none());
+        assert!(audit_ctx.handle().is_some());
+    }
+
+    #[tokio::test]
+    async fn greet() {
+        let upstream = UpstreamAddr::from_str("example.net:25").unwrap();
+        let mut audit_ctx = new_audit_ctx(DEFERRED);
+        let mut greeting =
+            SmtpDeferredGreeting::check(&mut audit_ctx, LOCAL_IP, &upstream).unwrap();
+
+        let (clt_io, mut clt_peer) = tokio::io::duplex(4096);
+        let (mut clt_r, mut clt_w) = tokio::io::split(clt_io);
+
+        let client = async move {
+            let banner = read_until(&mut clt_peer, b"\r\n").await;
+            clt_peer
+                .write_all(b"MAIL FROM:<s@example.net>\r\n")
+                .await
+                .unwrap();
+            let reply = read_until(&mut clt_peer, b"\r\n").await;
+            clt_peer
+                .write_all(b"EHLO client.example.net\r\nNOOP\r\n")
+                .await
+                .unwrap();
+            (banner, reply, clt_peer)
+        };
+        let (r, (banner, reply, _clt_peer)) =
+            tokio::join!(greeting.greet(&mut clt_r, &mut clt_w), client);
+        r.unwrap();
+        assert_eq!(banner.as_slice(), b"220 mx.proxy.net ESMTP ready\r\n");
+        assert_eq!(reply.as_slice(), b"503 Bad sequence of commands\r\n");
+
+        let (_, held) = greeting.into_parts();
+        assert_eq!(held.as_ref(), b"EHLO client.example.net\r\nNOOP\r\n");
+    }
+
+    #[tokio::test]
+    async fn greet_quit() {
+        let upstream = UpstreamAddr::from_str("example.net:25").unwrap();
+        let mut audit_ctx = new_audit_ctx(DEFERRED);
+        let mut greeting =
+            SmtpDeferredGreeting::check(&mut audit_ctx, LOCAL_IP, &upstream).unwrap();
+
+        let (clt_io, mut clt_peer) = tokio::io::duplex(4096);
+        let (mut clt_r, mut clt_w) = tokio::io::split(clt_io);
+
+        let client = async move {
+            read_until(&mut clt_peer, b"\r\n").await;
+            clt_peer.write_all(b"QUIT\r\n").await.unwrap();
+            let mut data = Vec::new();
+            clt_peer.read_to_end(&mut data).await.unwrap();
+            data
+        };
+        let (r, reply) = tokio::join!(greeting.greet(&mut clt_r, &mut clt_w), client);
+        assert!(matches!(r, Err(ServerTaskError::ClosedByClient)));
+        assert!(reply.starts_with(b"221 "));
+    }
+
+    #[tokio::test]
+    async fn replay_to_upstream() {
+        let upstream = UpstreamAddr::from_str("example.net:25").unwrap();
+        let mut audit_ctx = new_audit_ctx(DEFERRED);
+        let mut greeting =
+            SmtpDeferredGreeting::check(&mut audit_ctx, LOCAL_IP, &upstream).unwrap();
+
+        let (clt_io, mut clt_peer) = tokio::io::duplex(4096);
+        let (mut clt_r, mut clt_w) = tokio::io::split(clt_io);
+
+        // the local greeting stage, before the upstream connection
+        let client = async move {
+            let banner = read_until(&mut clt_peer, b"\r\n").await;
+            clt_peer
+                .write_all(b"EHLO client.example.net\r\n")
+                .await
+                .unwrap();
+            (banner, clt_peer)
+        };
+        let (r, (banner, mut clt_peer)) =
+            tokio::join!(greeting.greet(&mut clt_r, &mut clt_w), client);
+        r.unwrap();
+        assert_eq!(banner.as_slice(), b"220 mx.proxy.net ESMTP ready\r\n");
+
+        // the upstream is connected now
+        let (audit_handle, client_hello) = greeting.into_parts();
+        let name = MetricsName::from_str("test").unwrap();
+        let addr = SocketAddr::new(LOCAL_IP, 25);
+        let task_notes =
+            ServerTaskNotes::new(ClientConnectionInfo::new(addr, addr), None, Duration::ZERO);
+        let server_stats = Arc::new(TcpStreamServerStats::new(&name));
+        server_stats.set_online();
+        let ctx = StreamInspectContext::new(
+            audit_handle,
+            Arc::new(DummyCloseServerConfig::new(&name, None)),
+            server_stats,
+            Arc::new(ServerQuitPolicy::default()),
+            &task_notes,
+            &TcpConnectTaskNotes::new(upstream.clone()),
+        );
+
+        let (ups_io, ups_peer) = tokio::io::duplex(4096);
+        let (ups_r, ups_w) = tokio::io::split(ups_io);
+
+        let mock_upstream = async move {
+            let (ups_r, mut ups_w) = tokio::io::split(ups_peer);
+            let mut ups_r = BufReader::new(ups_r);
+            ups_w
+                .write_all(b"220 mx.example.net ESMTP\r\n")
+                .await
+                .unwrap();
+            let mut cmds = Vec::new();
+            loop {
+                let mut line = Vec::new();
+                if ups_r.read_until(b'\n', &mut line).await.unwrap() == 0 {
+                    return cmds;
+                }
+                cmds.extend_from_slice(&line);
+                let reply: &[u8] = if line.starts_with(b"EHLO ") {
+                    b"250-mx.example.net\r\n250 PIPELINING\r\n"
+                } else if line.starts_with(b"QUIT") {
+                    b"221 bye\r\n"
+                } else {
+                    b"500 unexpected command\r\n"
+                };
+                ups_w.write_all(reply).await.unwrap();
+            }
+        };
+        let client = async move {
+            let ehlo_reply = read_until(&mut clt_peer, b"250 PIPELINING\r\n").await;
+            clt_peer.write_all(b"QUIT\r\n").await.unwrap();
+            let mut data = Vec::new();
+            clt_peer.read_to_end(&mut data).await.unwrap();
+            (ehlo_reply, data)
+        };
+        let relay = crate::inspect::stream::transit_with_smtp_greeting(
+            clt_r,
+            clt_w,
+            ups_r,
+            ups_w,
+            ctx,
+            upstream,
+            client_hello,
+        );
+
+        let (r, cmds, (ehlo_reply, quit_reply)) = tokio::join!(relay, mock_upstream, client);
+        r.unwrap();
+        assert_eq!(cmds.as_slice(), b"EHLO client.example.net\r\nQUIT\r\n");
+        // the upstream greeting is not sent to the client, and the local hostname is used
+        assert_eq!(
+            ehlo_reply.as_slice(),
+            b"250-mx.proxy.net\r\n250 PIPELINING\r\n"
+        );
+        assert_eq!(quit_reply.as_slice(), b"221 bye\r\n");
+    }
+}
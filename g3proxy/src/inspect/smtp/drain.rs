@@ -0,0 +1,381 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+
+use g3_daemon::server::ServerQuitPolicy;
+use g3_io_ext::{LimitedCopy, LimitedCopyError, LineRecvBuf};
+use g3_smtp_proto::command::Command;
+
+use super::CommandLineRecvExt;
+use crate::config::server::ServerConfig;
+use crate::inspect::StreamInspectContext;
+use crate::serve::{ArcServerStats, ServerTaskError, ServerTaskResult};
+
+/// Check if the session should be closed at the next command boundary as the server is going
+/// offline, so no mail transaction will be cut in the middle of the message data
+pub(super) struct DrainChecker {
+    server_stats: ArcServerStats,
+    server_quit_policy: Arc<ServerQuitPolicy>,
+    check_interval: Duration,
+}
+
+impl DrainChecker {
+    pub(super) fn new<SC: ServerConfig>(ctx: &StreamInspectContext<SC>) -> Self {
+        DrainChecker {
+            server_stats: ctx.server_stats.clone(),
+            server_quit_policy: ctx.server_quit_policy.clone(),
+            check_interval: ctx.server_config.task_idle_check_duration(),
+        }
+    }
+
+    pub(super) fn draining(&self) -> bool {
+        !self.server_stats.is_online() || self.server_quit_policy.force_quit()
+    }
+
+    /// Wait for the client to start sending the next command.
+    ///
+    /// Return false if the session should be drained.
+    pub(super) async fn wait_cmd<CR, CW>(
+        &self,
+        recv_buf: &mut LineRecvBuf<{ Command::MAX_LINE_SIZE }>,
+        idle_timeout: Option<Duration>,
+        wait_timeout: Duration,
+        clt_r: &mut CR,
+        clt_w: &mut CW,
+        local_ip: IpAddr,
+    ) -> ServerTaskResult<bool>
+    where
+        CR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+    {
+        if self.draining() {
+            return Ok(false);
+        }
+
+        let wait_cmd = async {
+            if idle_timeout.is_some() {
+                return recv_buf
+                    .wait_cmd(idle_timeout, clt_r, clt_w, local_ip)
+                    .await;
+            }
+            match recv_buf.wait_data_with_timeout(clt_r, wait_timeout).await {
+                Ok(_) => Ok(()),
+                Err(e) => Err(
+                    LineRecvBuf::<{ Command::MAX_LINE_SIZE }>::handle_line_error(e, clt_w).await,
+                ),
+            }
+        };
+        tokio::pin!(wait_cmd);
+
+        let mut check_interval =
+            tokio::time::interval_at(Instant::now() + self.check_interval, self.check_interval);
+        loop {
+            tokio::select! {
+                r = &mut wait_cmd => return r.map(|_| true),
+                _ = check_interval.tick() => {
+                    if self.draining() {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Transfer the message data to upstream.
+    ///
+    /// If the server is force quit in the middle, the transfer will be given `drain_timeout`
+    /// to finish before being canceled.
+    pub(super) async fn transfer_data<R, W, F>(
+        &self,
+        mut clt_to_ups: LimitedCopy<'_, R, W>,
+        max_idle_count: i32,
+        drain_timeout: Duration,
+        user_blocked: F,
+    ) -> ServerTaskResult<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+        F: Fn() -> bool,
+    {
+        let mut idle_interval =
+            tokio::time::interval_at(Instant::now() + self.check_interval, self.check_interval);
+        let mut idle_count = 0;
+        let mut drain_deadline: Option<Instant> = None;
+
+        loop {
+            tokio::select! {
+                biased;
+
+                r = &mut clt_to_ups => {
+                    return match r {
+                        Ok(_) => {
+                            // ups_w is already flushed
+                            Ok(())
+                        }
+                        Err(LimitedCopyError::ReadFailed(e)) => {
+                            let _ = clt_to_ups.write_flush().await;
+                            Err(ServerTaskError::ClientTcpReadFailed(e))
+                        }
+                        Err(LimitedCopyError::WriteFailed(e)) => Err(ServerTaskError::UpstreamWriteFailed(e)),
+                    };
+                }
+                _ = idle_interval.tick() => {
+                    if clt_to_ups.is_idle() {
+                        idle_count += 1;
+                        if idle_count >= max_idle_count {
+                            return if clt_to_ups.no_cached_data() {
+                                Err(ServerTaskError::ClientAppTimeout("idle while reading BDAT data"))
+                            } else {
+                                Err(ServerTaskError::UpstreamAppTimeout("idle while sending BDAT data"))
+                            };
+                        }
+                    } else {
+                        idle_count = 0;
+                        clt_to_ups.reset_active();
+                    }
+
+                    if user_blocked() {
+                        let _ = clt_to_ups.write_flush().await;
+                        return Err(ServerTaskError::CanceledAsUserBlocked);
+                    }
+
+                    if self.server_quit_policy.force_quit() && drain_deadline.is_none() {
+                        // let the message data transfer finish in the drain timeout
+                        drain_deadline = Some(Instant::now() + drain_timeout);
+                    }
+                }
+                _ = wait_deadline(drain_deadline) => {
+                    let _ = clt_to_ups.write_flush().await;
+                    return Err(ServerTaskError::CanceledAsServerQuit)
+                }
+            }
+        }
+    }
+}
+
+/// Wait until the drain deadline is reached, or forever if it is not set
+async fn wait_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use arc_swap::ArcSwapOption;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use g3_types::metrics::{MetricsName, StaticMetricsTags};
+    use g3_types::stats::StatId;
+
+    use crate::serve::{ServerForbiddenSnapshot, ServerStats};
+
+    struct MockServerStats {
+        name: MetricsName,
+        id: StatId,
+        extra_metrics_tags: Arc<ArcSwapOption<StaticMetricsTags>>,
+        online: AtomicBool,
+    }
+
+    impl MockServerStats {
+        fn set_offline(&self) {
+            self.online.store(false, Ordering::Relaxed);
+        }
+    }
+
+    impl ServerStats for MockServerStats {
+        fn name(&self) -> &MetricsName {
+            &self.name
+        }
+
+        fn stat_id(&self) -> StatId {
+            self.id
+        }
+
+        fn load_extra_tags(&self) -> Option<Arc<StaticMetricsTags>> {
+            self.extra_metrics_tags.load_full()
+        }
+
+        fn share_extra_tags(&self) -> &Arc<ArcSwapOption<StaticMetricsTags>> {
+            &self.extra_metrics_tags
+        }
+
+        fn is_online(&self) -> bool {
+            self.online.load(Ordering::Relaxed)
+        }
+
+        fn get_conn_total(&self) -> u64 {
+            0
+        }
+
+        fn get_task_total(&self) -> u64 {
+            0
+        }
+
+        fn get_alive_count(&self) -> i32 {
+            0
+        }
+
+        fn forbidden_stats(&self) -> ServerForbiddenSnapshot {
+            ServerForbiddenSnapshot::default()
+        }
+    }
+
+    fn new_checker() -> (DrainChecker, Arc<MockServerStats>) {
+        let stats = Arc::new(MockServerStats {
+            name: MetricsName::from_str("test").unwrap(),
+            id: StatId::new(),
+            extra_metrics_tags: Arc::new(ArcSwapOption::new(None)),
+            online: AtomicBool::new(true),
+        });
+        let checker = DrainChecker {
+            server_stats: stats.clone(),
+            server_quit_policy: Arc::new(ServerQuitPolicy::default()),
+            check_interval: Duration::from_millis(10),
+        };
+        (checker, stats)
+    }
+
+    #[tokio::test]
+    async fn offline_between_transactions() {
+        let (checker, stats) = new_checker();
+        let (mut clt_r, _clt_peer) = tokio::io::duplex(1024);
+        let mut recv_buf = LineRecvBuf::<{ Command::MAX_LINE_SIZE }>::default();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stats.set_offline();
+        });
+        let has_cmd = checker
+            .wait_cmd(
+                &mut recv_buf,
+                None,
+                Duration::from_secs(10),
+                &mut clt_r,
+                &mut tokio::io::sink(),
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+            )
+            .await
+            .unwrap();
+        assert!(!has_cmd);
+        assert!(checker.draining());
+    }
+
+    #[tokio::test]
+    async fn command_received_before_offline() {
+        let (checker, stats) = new_checker();
+        let (mut clt_r, mut clt_peer) = tokio::io::duplex(1024);
+        let mut recv_buf = LineRecvBuf::<{ Command::MAX_LINE_SIZE }>::default();
+
+        clt_peer
+            .write_all(b"MAIL FROM:<a@example.net>\r\n")
+            .await
+            .unwrap();
+        let has_cmd = checker
+            .wait_cmd(
+                &mut recv_buf,
+                None,
+                Duration::from_secs(10),
+                &mut clt_r,
+                &mut tokio::io::sink(),
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+            )
+            .await
+            .unwrap();
+        assert!(has_cmd);
+
+        stats.set_offline();
+        recv_buf.consume_line();
+        let has_cmd = checker
+            .wait_cmd(
+                &mut recv_buf,
+                None,
+                Duration::from_secs(10),
+                &mut clt_r,
+                &mut tokio::io::sink(),
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+            )
+            .await
+            .unwrap();
+        assert!(!has_cmd);
+    }
+
+    #[tokio::test]
+    async fn force_quit_mid_data() {
+        let (checker, _stats) = new_checker();
+        let (mut clt_r, mut clt_peer) = tokio::io::duplex(1024);
+        let (mut ups_w, mut ups_peer) = tokio::io::duplex(1024);
+        let quit_policy = checker.server_quit_policy.clone();
+
+        let client = tokio::spawn(async move {
+            clt_peer.write_all(b"Subject: test\r\n").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            quit_policy.set_force_quit();
+            // the remaining data is sent in the drain timeout
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            clt_peer.write_all(b"\r\nbody\r\n.\r\n").await.unwrap();
+        });
+
+        let clt_to_ups = LimitedCopy::new(&mut clt_r, &mut ups_w, &Default::default());
+        checker
+            .transfer_data(clt_to_ups, 100, Duration::from_secs(1), || false)
+            .await
+            .unwrap();
+        client.await.unwrap();
+        assert!(checker.draining());
+
+        drop(ups_w);
+        let mut buf = Vec::new();
+        ups_peer.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), b"Subject: test\r\n\r\nbody\r\n.\r\n");
+    }
+
+    #[tokio::test]
+    async fn force_quit_mid_data_drain_timeout() {
+        let (checker, _stats) = new_checker();
+        let (mut clt_r, mut clt_peer) = tokio::io::duplex(1024);
+        let (mut ups_w, mut ups_peer) = tokio::io::duplex(1024);
+
+        clt_peer.write_all(b"Subject: test\r\n").await.unwrap();
+        checker.server_quit_policy.set_force_quit();
+
+        let time_start = Instant::now();
+        let clt_to_ups = LimitedCopy::new(&mut clt_r, &mut ups_w, &Default::default());
+        let r = checker
+            .transfer_data(clt_to_ups, 100, Duration::from_millis(100), || false)
+            .await;
+        assert!(matches!(r, Err(ServerTaskError::CanceledAsServerQuit)));
+        assert!(time_start.elapsed() >= Duration::from_millis(100));
+
+        // the data received before the drain timeout should be flushed to upstream
+        drop(ups_w);
+        let mut buf = Vec::new();
+        ups_peer.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), b"Subject: test\r\n");
+    }
+}
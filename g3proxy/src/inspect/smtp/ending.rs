@@ -26,6 +26,89 @@ use g3_smtp_proto::response::{ReplyCode, ResponseEncoder, ResponseParser};
 
 use crate::serve::{ServerTaskError, ServerTaskResult};
 
+/// Relay the reply of the client QUIT command, a local closing reply will be sent to the client
+/// if the upstream failed to reply in time.
+pub(super) struct QuitReplyRelay {
+    local_ip: IpAddr,
+}
+
+impl QuitReplyRelay {
+    pub(super) fn new(local_ip: IpAddr) -> Self {
+        QuitReplyRelay { local_ip }
+    }
+
+    pub(super) async fn relay<UR, CW>(
+        self,
+        recv_buf: &mut LineRecvBuf<{ ResponseParser::MAX_LINE_SIZE }>,
+        ups_r: &mut UR,
+        clt_w: &mut CW,
+        timeout: Duration,
+    ) -> ServerTaskResult<()>
+    where
+        UR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+    {
+        // the reply will be sent to client only after it has been fully received,
+        // so we can safely send the local one if the upstream failed
+        let r = match tokio::time::timeout(timeout, Self::recv_reply(recv_buf, ups_r)).await {
+            Ok(r) => r,
+            Err(_) => Err(ServerTaskError::UpstreamAppTimeout(
+                "timeout to wait SMTP QUIT response",
+            )),
+        };
+        match r {
+            Ok(reply) => clt_w
+                .write_all_flush(&reply)
+                .await
+                .map_err(ServerTaskError::ClientTcpWriteFailed),
+            Err(e) => {
+                let _ = ResponseEncoder::local_service_closing(self.local_ip)
+                    .write(clt_w)
+                    .await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn recv_reply<UR>(
+        recv_buf: &mut LineRecvBuf<{ ResponseParser::MAX_LINE_SIZE }>,
+        ups_r: &mut UR,
+    ) -> ServerTaskResult<Vec<u8>>
+    where
+        UR: AsyncRead + Unpin,
+    {
+        let mut rsp = ResponseParser::default();
+        let mut reply = Vec::with_capacity(64);
+        loop {
+            recv_buf.consume_line();
+            let line = recv_buf
+                .read_line(ups_r)
+                .await
+                .map_err(quit_reply_recv_error)?;
+            rsp.feed_line(line).map_err(|e| {
+                ServerTaskError::UpstreamAppError(anyhow!("invalid SMTP QUIT response line: {e}"))
+            })?;
+            reply.extend_from_slice(line);
+            if rsp.finished() {
+                return Ok(reply);
+            }
+        }
+    }
+}
+
+fn quit_reply_recv_error(e: RecvLineError) -> ServerTaskError {
+    match e {
+        RecvLineError::IoError(e) => ServerTaskError::UpstreamReadFailed(e),
+        RecvLineError::IoClosed => ServerTaskError::ClosedByUpstream,
+        RecvLineError::Timeout => {
+            ServerTaskError::UpstreamAppTimeout("timeout to get upstream response")
+        }
+        RecvLineError::LineTooLong => {
+            ServerTaskError::UpstreamAppError(anyhow!("SMTP response line too long"))
+        }
+    }
+}
+
 pub(super) struct EndQuitServer {}
 
 impl EndQuitServer {
@@ -58,16 +141,10 @@ impl EndQuitServer {
 
         let mut rsp = ResponseParser::default();
         loop {
-            let line = recv_buf.read_line(&mut ups_r).await.map_err(|e| match e {
-                RecvLineError::IoError(e) => ServerTaskError::UpstreamReadFailed(e),
-                RecvLineError::IoClosed => ServerTaskError::ClosedByUpstream,
-                RecvLineError::Timeout => {
-                    ServerTaskError::UpstreamAppTimeout("timeout to get upstream response")
-                }
-                RecvLineError::LineTooLong => {
-                    ServerTaskError::UpstreamAppError(anyhow!("SMTP response line too long"))
-                }
-            })?;
+            let line = recv_buf
+                .read_line(&mut ups_r)
+                .await
+                .map_err(quit_reply_recv_error)?;
 
             rsp.feed_line(line).map_err(|e| {
                 ServerTaskError::UpstreamAppError(anyhow!("invalid SMTP QUIT response line: {e}"))
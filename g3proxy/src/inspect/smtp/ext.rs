@@ -123,6 +123,20 @@ impl ResponseParseExt for ResponseParser {
 }
 
 pub(super) trait CommandLineRecvExt {
+    /// Wait for the client to start sending the next command.
+    ///
+    /// A 421 reply will be sent to the client if it has been idle for too long.
+    async fn wait_cmd<CR, CW>(
+        &mut self,
+        idle_timeout: Option<Duration>,
+        clt_r: &mut CR,
+        clt_w: &mut CW,
+        local_ip: IpAddr,
+    ) -> ServerTaskResult<()>
+    where
+        CR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin;
+
     async fn recv_cmd<CR, CW>(
         &mut self,
         recv_timeout: Duration,
@@ -152,6 +166,34 @@ pub(super) trait CommandLineRecvExt {
 }
 
 impl<const MAX_LINE_SIZE: usize> CommandLineRecvExt for LineRecvBuf<MAX_LINE_SIZE> {
+    async fn wait_cmd<CR, CW>(
+        &mut self,
+        idle_timeout: Option<Duration>,
+        clt_r: &mut CR,
+        clt_w: &mut CW,
+        local_ip: IpAddr,
+    ) -> ServerTaskResult<()>
+    where
+        CR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+    {
+        let Some(idle_timeout) = idle_timeout else {
+            return Ok(());
+        };
+        match self.wait_data_with_timeout(clt_r, idle_timeout).await {
+            Ok(_) => Ok(()),
+            Err(RecvLineError::Timeout) => {
+                let _ = ResponseEncoder::local_idle_timeout(local_ip)
+                    .write(clt_w)
+                    .await;
+                Err(ServerTaskError::ClientAppTimeout(
+                    "idle timeout while waiting client command",
+                ))
+            }
+            Err(e) => Err(Self::handle_line_error(e, clt_w).await),
+        }
+    }
+
     async fn recv_cmd<CR, CW>(
         &mut self,
         recv_timeout: Duration,
@@ -18,14 +18,14 @@ use std::net::IpAddr;
 
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use g3_dpi::SmtpInterceptionConfig;
+use g3_dpi::{SmtpInterceptionConfig, SmtpMailParamRewrite};
 use g3_io_ext::{LimitedWriteExt, LineRecvBuf};
 use g3_smtp_proto::command::{Command, MailParam};
 use g3_smtp_proto::response::{ReplyCode, ResponseEncoder, ResponseParser};
 
 use super::{
-    CommandLineRecvExt, InitializedExtensions, Initiation, ResponseLineRecvExt, ResponseParseExt,
-    SmtpRelayBuf,
+    CommandLineRecvExt, DrainChecker, InitializedExtensions, Initiation, QuitReplyRelay,
+    ReplyRemapper, ResponseLineRecvExt, ResponseParseExt, SmtpRelayBuf,
 };
 use crate::serve::{ServerTaskError, ServerTaskResult};
 
@@ -34,30 +34,42 @@ pub(super) enum ForwardNextAction {
     StartTls,
     ReverseConnection,
     SetExtensions(InitializedExtensions),
-    MailTransport(MailParam),
+    /// the bool value indicates whether the MAIL reply is still pending as the client is pipelining
+    MailTransport(MailParam, bool),
+    /// the server is going offline, and a 421 reply has been sent to the client
+    Drain,
 }
 
 pub(super) struct Forward<'a> {
     config: &'a SmtpInterceptionConfig,
+    drain: &'a DrainChecker,
     local_ip: IpAddr,
     allow_odmr: bool,
     allow_starttls: bool,
+    require_starttls: bool,
     auth_end: bool,
+    log_remap: &'a (dyn Fn(&str) + Sync),
 }
 
 impl<'a> Forward<'a> {
     pub(super) fn new(
         config: &'a SmtpInterceptionConfig,
+        drain: &'a DrainChecker,
         local_ip: IpAddr,
         allow_odmr: bool,
         allow_starttls: bool,
+        require_starttls: bool,
+        log_remap: &'a (dyn Fn(&str) + Sync),
     ) -> Self {
         Forward {
             config,
+            drain,
             local_ip,
             allow_odmr,
             allow_starttls,
+            require_starttls,
             auth_end: false,
+            log_remap,
         }
     }
 
@@ -77,6 +89,25 @@ impl<'a> Forward<'a> {
     {
         loop {
             buf.cmd_recv_buf.consume_line();
+            let has_cmd = self
+                .drain
+                .wait_cmd(
+                    &mut buf.cmd_recv_buf,
+                    self.config.command_idle_timeout,
+                    self.config.command_wait_timeout,
+                    clt_r,
+                    clt_w,
+                    self.local_ip,
+                )
+                .await?;
+            if !has_cmd {
+                self.send_error_to_client(
+                    clt_w,
+                    ResponseEncoder::local_service_shutting_down(self.local_ip),
+                )
+                .await?;
+                return Ok(ForwardNextAction::Drain);
+            }
             let (cmd, cmd_line) = buf
                 .cmd_recv_buf
                 .recv_cmd(self.config.command_wait_timeout, clt_r, clt_w)
@@ -95,13 +126,21 @@ impl<'a> Forward<'a> {
                 }
                 Command::Quit => {
                     self.send_cmd(ups_w, clt_w, cmd_line).await?;
-                    let _ = self.recv_relay_rsp(buf, ups_r, clt_w).await?;
+                    QuitReplyRelay::new(self.local_ip)
+                        .relay(
+                            &mut buf.rsp_recv_buf,
+                            ups_r,
+                            clt_w,
+                            self.config.quit_wait_timeout,
+                        )
+                        .await?;
                     return Ok(ForwardNextAction::Quit);
                 }
                 Command::StartTls => {
                     if !self.allow_starttls {
                         self.send_error_to_client(clt_w, ResponseEncoder::COMMAND_NOT_IMPLEMENTED)
                             .await?;
+                        continue;
                     }
                     self.send_cmd(ups_w, clt_w, cmd_line).await?;
                     let rsp = self.recv_relay_rsp(buf, ups_r, clt_w).await?;
@@ -113,6 +152,7 @@ impl<'a> Forward<'a> {
                     if self.auth_end {
                         self.send_error_to_client(clt_w, ResponseEncoder::BAD_SEQUENCE_OF_COMMANDS)
                             .await?;
+                        continue;
                     }
                     self.send_cmd(ups_w, clt_w, cmd_line).await?;
                     self.recv_relay_auth(buf, clt_r, clt_w, ups_r, ups_w)
@@ -122,10 +162,12 @@ impl<'a> Forward<'a> {
                     if !self.allow_odmr {
                         self.send_error_to_client(clt_w, ResponseEncoder::COMMAND_NOT_IMPLEMENTED)
                             .await?;
+                        continue;
                     }
                     if !self.auth_end {
                         self.send_error_to_client(clt_w, ResponseEncoder::AUTHENTICATION_REQUIRED)
                             .await?;
+                        continue;
                     }
                     self.send_cmd(ups_w, clt_w, cmd_line).await?;
                     // a max 10min timeout according to RFC2645
@@ -146,11 +188,26 @@ impl<'a> Forward<'a> {
                         return Ok(ForwardNextAction::SetExtensions(extensions));
                     }
                 }
-                Command::Mail(param) => {
-                    self.send_cmd(ups_w, clt_w, cmd_line).await?;
+                Command::Mail(mut param) => {
+                    if self.require_starttls {
+                        self.send_error_to_client(clt_w, ResponseEncoder::STARTTLS_REQUIRED)
+                            .await?;
+                        continue;
+                    }
+                    if self.config.mail_param_rewrite.is_empty() {
+                        self.send_cmd(ups_w, clt_w, cmd_line).await?;
+                    } else {
+                        self.rewrite_mail_param(&mut param);
+                        let cmd_line = param.encode_line();
+                        self.send_cmd(ups_w, clt_w, &cmd_line).await?;
+                    }
+                    if buf.cmd_recv_buf.has_next_line() {
+                        // the client is pipelining, the reply will be handled in the transaction
+                        return Ok(ForwardNextAction::MailTransport(param, true));
+                    }
                     let rsp = self.recv_relay_rsp(buf, ups_r, clt_w).await?;
                     if rsp == ReplyCode::OK {
-                        return Ok(ForwardNextAction::MailTransport(param));
+                        return Ok(ForwardNextAction::MailTransport(param, false));
                     }
                 }
                 _ => {
@@ -161,6 +218,19 @@ impl<'a> Forward<'a> {
         }
     }
 
+    fn rewrite_mail_param(&self, param: &mut MailParam) {
+        for rewrite in &self.config.mail_param_rewrite {
+            match rewrite {
+                SmtpMailParamRewrite::Remove(keyword) => {
+                    param.remove_esmtp_param(keyword);
+                }
+                SmtpMailParamRewrite::Set(keyword, value) => {
+                    param.set_esmtp_param(keyword, value.as_deref());
+                }
+            }
+        }
+    }
+
     async fn send_cmd<UW, CW>(
         &self,
         ups_w: &mut UW,
@@ -207,6 +277,7 @@ impl<'a> Forward<'a> {
         UR: AsyncRead + Unpin,
     {
         let mut rsp = ResponseParser::default();
+        let mut remapper = ReplyRemapper::new(self.config);
         loop {
             buf.rsp_recv_buf.consume_line();
             let line = buf
@@ -218,16 +289,21 @@ impl<'a> Forward<'a> {
                     self.local_ip,
                 )
                 .await?;
-            let _msg = rsp
+            let msg = rsp
                 .feed_line_with_feedback(line, clt_w, self.local_ip)
                 .await?;
+            let line = remapper.rewrite(&rsp, line, msg);
 
             clt_w
-                .write_all_flush(line)
+                .write_all_flush(&line)
                 .await
                 .map_err(ServerTaskError::ClientTcpWriteFailed)?;
 
             if rsp.finished() {
+                // log the remapped reply for audit
+                if let Some(record) = remapper.take_record() {
+                    (self.log_remap)(&record);
+                }
                 let code = rsp.code();
                 return if code == ReplyCode::SERVICE_NOT_AVAILABLE {
                     Err(ServerTaskError::UpstreamAppUnavailable)
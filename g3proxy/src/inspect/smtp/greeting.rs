@@ -15,125 +15,143 @@
  */
 
 use std::io;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
 use anyhow::anyhow;
 use thiserror::Error;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 
-use g3_io_ext::{LimitedWriteExt, LineRecvBuf, OnceBufReader, RecvLineError};
+use g3_io_ext::{LimitedWriteExt, OnceBufReader, RecvLineError};
 use g3_smtp_proto::response::{ReplyCode, ResponseEncoder, ResponseLineError, ResponseParser};
-use g3_types::net::Host;
+use g3_types::net::{Host, ProxyProtocolEncoder, ProxyProtocolVersion};
 
+use crate::inspect::line_protocol::{LineProtocolParser, LineProtocolRelay, LineRelayAction};
 use crate::serve::ServerTaskError;
 
-pub(super) struct Greeting {
-    local_ip: IpAddr,
+struct GreetingParser {
     upstream_host: Host,
+    lenient_host: bool,
     rsp: ResponseParser,
-    total_to_write: usize,
+}
+
+impl LineProtocolParser for GreetingParser {
+    type Error = GreetingError;
+
+    fn feed_line(&mut self, line: &[u8]) -> Result<LineRelayAction<GreetingError>, GreetingError> {
+        let msg = self.rsp.feed_line(line)?;
+
+        match self.rsp.code() {
+            ReplyCode::SERVICE_READY => {
+                if self.upstream_host.is_empty() {
+                    let host_d = match memchr::memchr(b' ', msg) {
+                        Some(d) => &msg[..d],
+                        None => msg,
+                    };
+                    if host_d.is_empty() {
+                        return Ok(LineRelayAction::Abort(GreetingError::NoHostField));
+                    }
+                    let host = if self.lenient_host {
+                        Host::parse_smtp_host_address_lenient(host_d)
+                    } else {
+                        Host::parse_smtp_host_address(host_d)
+                    };
+                    match host {
+                        Some(host) => self.upstream_host = host,
+                        None => {
+                            return Ok(LineRelayAction::Abort(GreetingError::UnsupportedHostFormat))
+                        }
+                    }
+                }
+            }
+            ReplyCode::NO_SERVICE => {}
+            c => {
+                return Ok(LineRelayAction::Abort(GreetingError::UnexpectedReplyCode(
+                    c,
+                )))
+            }
+        }
+
+        if self.rsp.finished() {
+            Ok(LineRelayAction::Finish)
+        } else {
+            Ok(LineRelayAction::Continue)
+        }
+    }
+
+    fn client_write_error(e: io::Error) -> GreetingError {
+        GreetingError::ClientWriteFailed(e)
+    }
+}
+
+pub(super) struct Greeting {
+    local_ip: IpAddr,
+    parser: GreetingParser,
+    relay: LineProtocolRelay<{ ResponseParser::MAX_LINE_SIZE }>,
 }
 
 impl Greeting {
-    pub(super) fn new(local_ip: IpAddr) -> Self {
+    pub(super) fn new(local_ip: IpAddr, lenient_host: bool) -> Self {
         Greeting {
             local_ip,
-            upstream_host: Host::empty(),
-            rsp: ResponseParser::default(),
-            total_to_write: 0,
+            parser: GreetingParser {
+                upstream_host: Host::empty(),
+                lenient_host,
+                rsp: ResponseParser::default(),
+            },
+            relay: LineProtocolRelay::default(),
         }
     }
 
+    pub(super) fn set_truncate_long_line(&mut self) {
+        self.relay.set_truncate_long_line();
+    }
+
+    /// Get the number of too long lines that have been truncated
+    pub(super) fn take_truncated_count(&mut self) -> usize {
+        self.relay.take_truncated_count()
+    }
+
     pub(super) fn into_parts(self) -> (ReplyCode, Host) {
-        (self.rsp.code(), self.upstream_host)
+        (self.parser.rsp.code(), self.parser.upstream_host)
     }
 
-    async fn do_relay<UR, CW>(
-        &mut self,
-        mut ups_r: OnceBufReader<UR>,
-        clt_w: &mut CW,
-    ) -> Result<UR, GreetingError>
-    where
-        UR: AsyncRead + Unpin,
-        CW: AsyncWrite + Unpin,
-    {
-        let mut recv_buf = LineRecvBuf::<{ ResponseParser::MAX_LINE_SIZE }>::default();
-
-        loop {
-            recv_buf.consume_line();
-            let line = recv_buf.read_line(&mut ups_r).await?;
-
-            let msg = self.rsp.feed_line(line)?;
-            self.total_to_write += line.len();
-            clt_w
-                .write_all_flush(line)
-                .await
-                .map_err(GreetingError::ClientWriteFailed)?;
-
-            match self.rsp.code() {
-                ReplyCode::SERVICE_READY => {
-                    if self.upstream_host.is_empty() {
-                        let host_d = match memchr::memchr(b' ', msg) {
-                            Some(d) => &msg[..d],
-                            None => msg,
-                        };
-                        if host_d.is_empty() {
-                            return Err(GreetingError::NoHostField);
-                        }
-                        self.upstream_host = Host::parse_smtp_host_address(host_d)
-                            .ok_or(GreetingError::UnsupportedHostFormat)?;
-                    }
-                    if self.rsp.finished() {
-                        return Ok(ups_r.into_inner());
-                    }
-                }
-                ReplyCode::NO_SERVICE => {
-                    if self.rsp.finished() {
-                        return Ok(ups_r.into_inner());
-                    }
-                }
-                c => return Err(GreetingError::UnexpectedReplyCode(c)),
-            }
-        }
+    /// Time to receive the first line of the greeting response
+    pub(super) fn ttfb(&self) -> Option<Duration> {
+        self.relay.ttfb()
     }
 
+    /// Time to receive the whole greeting response
+    pub(super) fn total_time(&self) -> Option<Duration> {
+        self.relay.total_time()
+    }
+
+    /// Relay the greeting response.
+    ///
+    /// The bytes pre-buffered in `ups_r` will be consumed before any read on the inner reader,
+    /// so the banner sent early by the upstream won't be lost. Data received after the greeting
+    /// will be kept in the returned reader.
     pub(super) async fn relay<UR, CW>(
         &mut self,
-        ups_r: OnceBufReader<UR>,
+        mut ups_r: OnceBufReader<UR>,
         clt_w: &mut CW,
         timeout: Duration,
-    ) -> Result<UR, GreetingError>
+    ) -> Result<OnceBufReader<UR>, GreetingError>
     where
         UR: AsyncRead + Unpin,
         CW: AsyncWrite + Unpin,
     {
-        let mut buf_writer = BufWriter::with_capacity(1024, clt_w);
-        match tokio::time::timeout(timeout, self.do_relay(ups_r, &mut buf_writer)).await {
-            Ok(Ok(ups_r)) => {
-                let _ = buf_writer.flush().await;
-                Ok(ups_r)
-            }
-            Ok(Err(e)) => {
-                if let GreetingError::ClientWriteFailed(e) = e {
-                    Err(GreetingError::ClientWriteFailed(e))
-                } else {
-                    let _ = buf_writer.flush().await;
-                    Err(e)
-                }
-            }
-            Err(_) => {
-                let _ = buf_writer.flush().await;
-                Err(GreetingError::Timeout)
-            }
-        }
+        self.relay
+            .relay(&mut self.parser, &mut ups_r, clt_w, timeout)
+            .await?;
+        Ok(self.relay.keep_left_data(ups_r))
     }
 
     pub(super) async fn reply_no_service<CW>(self, e: &GreetingError, clt_w: &mut CW)
     where
         CW: AsyncWrite + Unpin,
     {
-        if self.total_to_write > 0 {
+        if self.relay.total_to_write() > 0 {
             return;
         }
         let reason = match e {
@@ -150,6 +168,43 @@ impl Greeting {
     }
 }
 
+/// Send the PROXY protocol header to the upstream MTA, which will send the greeting only after it
+/// has been received.
+///
+/// The IPv4 address will be converted to an IPv4-mapped IPv6 address if the address families of
+/// the client and the server are different.
+pub(super) async fn send_upstream_proxy_protocol<UW>(
+    version: ProxyProtocolVersion,
+    client_addr: SocketAddr,
+    server_addr: SocketAddr,
+    ups_w: &mut UW,
+) -> Result<(), ServerTaskError>
+where
+    UW: AsyncWrite + Unpin,
+{
+    let (client_addr, server_addr) = match (client_addr, server_addr) {
+        (SocketAddr::V4(c), SocketAddr::V6(_)) => (
+            SocketAddr::new(IpAddr::V6(c.ip().to_ipv6_mapped()), c.port()),
+            server_addr,
+        ),
+        (SocketAddr::V6(_), SocketAddr::V4(s)) => (
+            client_addr,
+            SocketAddr::new(IpAddr::V6(s.ip().to_ipv6_mapped()), s.port()),
+        ),
+        _ => (client_addr, server_addr),
+    };
+    let mut encoder = ProxyProtocolEncoder::new(version);
+    let bytes = encoder.encode_tcp(client_addr, server_addr).map_err(|e| {
+        ServerTaskError::InternalAdapterError(anyhow!(
+            "failed to encode smtp upstream proxy protocol header: {e}"
+        ))
+    })?;
+    ups_w
+        .write_all_flush(bytes)
+        .await
+        .map_err(ServerTaskError::UpstreamWriteFailed)
+}
+
 #[derive(Debug, Error)]
 pub(super) enum GreetingError {
     #[error("greeting timeout")]
@@ -208,3 +263,193 @@ impl From<GreetingError> for ServerTaskError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::net::Ipv4Addr;
+    use std::pin::Pin;
+    use std::str::FromStr;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader, ReadBuf};
+
+    struct NoReadReader;
+
+    impl AsyncRead for NoReadReader {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Ready(Err(io::Error::other("unexpected read on the socket")))
+        }
+    }
+
+    #[tokio::test]
+    async fn pre_buffered_banner() {
+        const BANNER: &[u8] = b"220 mx.example.net ESMTP\r\n";
+
+        let ups_r = OnceBufReader::with_bytes(NoReadReader, Bytes::from_static(BANNER));
+        let mut clt_w = Vec::new();
+        let mut greeting = Greeting::new(IpAddr::V4(Ipv4Addr::LOCALHOST), false);
+        let ups_r = greeting
+            .relay(ups_r, &mut clt_w, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(ups_r.buf().is_none());
+        assert_eq!(greeting.relay.total_to_write(), BANNER.len());
+        assert_eq!(clt_w.as_slice(), BANNER);
+        let (code, host) = greeting.into_parts();
+        assert_eq!(code, ReplyCode::SERVICE_READY);
+        assert_eq!(host, Host::Domain("mx.example.net".into()));
+    }
+
+    #[tokio::test]
+    async fn ip_literal_banner() {
+        const BANNER: &[u8] = b"220 192.0.2.1 ESMTP\r\n";
+
+        let ups_r = OnceBufReader::with_bytes(NoReadReader, Bytes::from_static(BANNER));
+        let mut greeting = Greeting::new(IpAddr::V4(Ipv4Addr::LOCALHOST), true);
+        greeting
+            .relay(ups_r, &mut Vec::new(), Duration::from_secs(1))
+            .await
+            .unwrap();
+        let (code, host) = greeting.into_parts();
+        assert_eq!(code, ReplyCode::SERVICE_READY);
+        assert_eq!(host, Host::Ip(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))));
+
+        const BANNER_V6: &[u8] = b"220 [IPv6:2001:db8::1] ESMTP\r\n";
+
+        let ups_r = OnceBufReader::with_bytes(NoReadReader, Bytes::from_static(BANNER_V6));
+        let mut greeting = Greeting::new(IpAddr::V4(Ipv4Addr::LOCALHOST), false);
+        let r = greeting
+            .relay(ups_r, &mut Vec::new(), Duration::from_secs(1))
+            .await;
+        assert!(matches!(r, Err(GreetingError::UnsupportedHostFormat)));
+    }
+
+    #[tokio::test]
+    async fn pre_buffered_extra_data() {
+        let data = b"220-mx.example.net ESMTP\r\n220 ready\r\n421 closing\r\n";
+
+        let ups_r = OnceBufReader::with_bytes(NoReadReader, Bytes::from_static(data));
+        let mut clt_w = Vec::new();
+        let mut greeting = Greeting::new(IpAddr::V4(Ipv4Addr::LOCALHOST), false);
+        let mut ups_r = greeting
+            .relay(ups_r, &mut clt_w, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(greeting.relay.total_to_write(), data.len() - 13);
+
+        let mut left = [0u8; 13];
+        ups_r.read_exact(&mut left).await.unwrap();
+        assert_eq!(&left, b"421 closing\r\n");
+    }
+
+    #[tokio::test]
+    async fn too_long_banner_line() {
+        let mut data = b"220-mx.example.net ".to_vec();
+        data.extend_from_slice(&[b'a'; 600]);
+        data.extend_from_slice(b"\r\n220 ready\r\n");
+        let data = Bytes::from(data);
+
+        let ups_r = OnceBufReader::with_bytes(NoReadReader, data.clone());
+        let mut greeting = Greeting::new(IpAddr::V4(Ipv4Addr::LOCALHOST), false);
+        let r = greeting
+            .relay(ups_r, &mut Vec::new(), Duration::from_secs(1))
+            .await;
+        assert!(matches!(r, Err(GreetingError::TooLongResponseLine)));
+
+        let ups_r = OnceBufReader::with_bytes(NoReadReader, data);
+        let mut clt_w = Vec::new();
+        let mut greeting = Greeting::new(IpAddr::V4(Ipv4Addr::LOCALHOST), false);
+        greeting.set_truncate_long_line();
+        let ups_r = greeting
+            .relay(ups_r, &mut clt_w, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(ups_r.buf().is_none());
+        assert_eq!(clt_w.len(), ResponseParser::MAX_LINE_SIZE + 11);
+        assert_eq!(greeting.relay.total_to_write(), clt_w.len());
+        assert!(clt_w.ends_with(b"aa\r\n220 ready\r\n"));
+        assert_eq!(greeting.take_truncated_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn banner_after_proxy_protocol() {
+        const BANNER: &[u8] = b"220 mx.example.net ESMTP Postfix\r\n";
+
+        let (ups_s, mta_s) = tokio::io::duplex(1024);
+        let (ups_r, mut ups_w) = tokio::io::split(ups_s);
+        let (mta_r, mut mta_w) = tokio::io::split(mta_s);
+
+        let mta = tokio::spawn(async move {
+            // the MTA only sends the greeting after the PROXY header is received
+            let mut mta_r = BufReader::new(mta_r);
+            let mut line = String::new();
+            mta_r.read_line(&mut line).await.unwrap();
+            mta_w.write_all(BANNER).await.unwrap();
+            line
+        });
+
+        send_upstream_proxy_protocol(
+            ProxyProtocolVersion::V1,
+            SocketAddr::from_str("192.168.1.1:50000").unwrap(),
+            SocketAddr::from_str("192.168.1.2:25").unwrap(),
+            &mut ups_w,
+        )
+        .await
+        .unwrap();
+
+        let mut clt_w = Vec::new();
+        let mut greeting = Greeting::new(IpAddr::V4(Ipv4Addr::LOCALHOST), false);
+        let ups_r = greeting
+            .relay(
+                OnceBufReader::with_no_buf(ups_r),
+                &mut clt_w,
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap();
+        assert!(ups_r.buf().is_none());
+        assert_eq!(clt_w.as_slice(), BANNER);
+        let (code, host) = greeting.into_parts();
+        assert_eq!(code, ReplyCode::SERVICE_READY);
+        assert_eq!(host, Host::Domain("mx.example.net".into()));
+
+        let line = mta.await.unwrap();
+        assert_eq!(line, "PROXY TCP4 192.168.1.1 192.168.1.2 50000 25\r\n");
+    }
+
+    #[tokio::test]
+    async fn proxy_protocol_mixed_family() {
+        let mut ups_w = Vec::new();
+        send_upstream_proxy_protocol(
+            ProxyProtocolVersion::V1,
+            SocketAddr::from_str("192.168.1.1:50000").unwrap(),
+            SocketAddr::from_str("[2001:db8::2]:25").unwrap(),
+            &mut ups_w,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            ups_w.as_slice(),
+            b"PROXY TCP6 ::ffff:192.168.1.1 2001:db8::2 50000 25\r\n"
+        );
+
+        let mut ups_w = Vec::new();
+        send_upstream_proxy_protocol(
+            ProxyProtocolVersion::V1,
+            SocketAddr::from_str("[2001:db8::1]:50000").unwrap(),
+            SocketAddr::from_str("192.168.1.2:25").unwrap(),
+            &mut ups_w,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            ups_w.as_slice(),
+            b"PROXY TCP6 2001:db8::1 ::ffff:192.168.1.2 50000 25\r\n"
+        );
+    }
+}
@@ -14,9 +14,11 @@
  * limitations under the License.
  */
 
+use std::borrow::Cow;
 use std::net::IpAddr;
 use std::str;
 
+use anyhow::anyhow;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 
 use g3_dpi::SmtpInterceptionConfig;
@@ -25,7 +27,7 @@ use g3_smtp_proto::command::Command;
 use g3_smtp_proto::response::{ReplyCode, ResponseEncoder, ResponseParser};
 use g3_types::net::Host;
 
-use super::{CommandLineRecvExt, ResponseLineRecvExt, ResponseParseExt};
+use super::{CommandLineRecvExt, ResponseLineRecvExt, ResponseParseExt, SmtpRelayBuf};
 use crate::serve::{ServerTaskError, ServerTaskResult};
 
 #[derive(Default)]
@@ -58,6 +60,7 @@ pub(super) struct Initiation<'a> {
     config: &'a SmtpInterceptionConfig,
     local_ip: IpAddr,
     from_starttls: bool,
+    require_starttls: bool,
     client_host: Host,
     server_ext: InitializedExtensions,
 }
@@ -72,17 +75,23 @@ impl<'a> Initiation<'a> {
             config,
             local_ip,
             from_starttls,
+            require_starttls: false,
             client_host: Host::empty(),
             server_ext: InitializedExtensions::default(),
         }
     }
 
+    pub(super) fn set_require_starttls(&mut self) {
+        self.require_starttls = true;
+    }
+
     pub(super) fn into_parts(self) -> (Host, InitializedExtensions) {
         (self.client_host, self.server_ext)
     }
 
     pub(super) async fn relay<CR, CW, UR, UW>(
         &mut self,
+        buf: &mut SmtpRelayBuf,
         clt_r: &mut CR,
         clt_w: &mut CW,
         ups_r: &mut UR,
@@ -94,12 +103,18 @@ impl<'a> Initiation<'a> {
         UR: AsyncRead + Unpin,
         UW: AsyncWrite + Unpin,
     {
-        let mut cmd_recv_buf = LineRecvBuf::<{ Command::MAX_LINE_SIZE }>::default();
-        let mut rsp_recv_buf = LineRecvBuf::<{ ResponseParser::MAX_LINE_SIZE }>::default();
-
         loop {
-            cmd_recv_buf.consume_line();
-            let (cmd, cmd_line) = cmd_recv_buf
+            buf.cmd_recv_buf.consume_line();
+            buf.cmd_recv_buf
+                .wait_cmd(
+                    self.config.command_idle_timeout,
+                    clt_r,
+                    clt_w,
+                    self.local_ip,
+                )
+                .await?;
+            let (cmd, cmd_line) = buf
+                .cmd_recv_buf
                 .recv_cmd(self.config.command_wait_timeout, clt_r, clt_w)
                 .await?;
 
@@ -119,7 +134,7 @@ impl<'a> Initiation<'a> {
             }
 
             if self
-                .recv_relay_check_rsp(&mut rsp_recv_buf, ups_r, clt_w)
+                .recv_relay_check_rsp(&mut buf.rsp_recv_buf, ups_r, clt_w)
                 .await?
                 .is_some()
             {
@@ -174,6 +189,8 @@ impl<'a> Initiation<'a> {
         UR: AsyncRead + Unpin,
     {
         let mut rsp = ResponseParser::default();
+        // hold the OK response until we know if STARTTLS is offered
+        let mut held_rsp = Vec::new();
         loop {
             rsp_recv_buf.consume_line();
             let line = rsp_recv_buf
@@ -190,14 +207,40 @@ impl<'a> Initiation<'a> {
 
             match rsp.code() {
                 ReplyCode::OK => {
-                    if rsp.is_first_line() || self.allow_extension(msg) {
-                        clt_w
-                            .write_all(line)
-                            .await
-                            .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+                    let line = if rsp.is_first_line() {
+                        Some(self.local_greeting_line(line, msg))
+                    } else if self.allow_extension(msg) {
+                        Some(Cow::Borrowed(line))
+                    } else {
+                        None
+                    };
+                    if let Some(line) = line {
+                        if self.require_starttls {
+                            held_rsp.extend_from_slice(&line);
+                        } else {
+                            clt_w
+                                .write_all(&line)
+                                .await
+                                .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+                        }
                     }
 
                     if rsp.finished() {
+                        if self.require_starttls {
+                            if !self.server_ext.starttls {
+                                let _ =
+                                    ResponseEncoder::upstream_starttls_not_offered(self.local_ip)
+                                        .write(clt_w)
+                                        .await;
+                                return Err(ServerTaskError::UpstreamAppError(anyhow!(
+                                    "STARTTLS is required but not offered by upstream"
+                                )));
+                            }
+                            clt_w
+                                .write_all(&held_rsp)
+                                .await
+                                .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+                        }
                         clt_w
                             .flush()
                             .await
@@ -234,6 +277,31 @@ impl<'a> Initiation<'a> {
         }
     }
 
+    /// Use the local hostname in the first line of the EHLO / HELO response if the greeting
+    /// banner is sent by us, so the client will see the same server host.
+    fn local_greeting_line<'b>(&self, line: &'b [u8], msg: &[u8]) -> Cow<'b, [u8]> {
+        let Some(banner) = &self.config.greeting_banner else {
+            return Cow::Borrowed(line);
+        };
+
+        let hostname = banner.hostname(self.local_ip);
+        // the code and the delimiter
+        let prefix = &line[..line.len() - msg.len() - 2];
+        let left: &[u8] = match memchr::memchr(b' ', msg) {
+            Some(p) => &msg[p..],
+            None => b"",
+        };
+        let mut buf = Vec::with_capacity(4 + hostname.len() + left.len() + 2);
+        buf.extend_from_slice(prefix);
+        if prefix.len() < 4 {
+            buf.push(b' ');
+        }
+        buf.extend_from_slice(hostname.as_bytes());
+        buf.extend_from_slice(left);
+        buf.extend_from_slice(b"\r\n");
+        Cow::Owned(buf)
+    }
+
     fn allow_extension(&mut self, msg: &[u8]) -> bool {
         if let Some(p) = memchr::memchr(b' ', msg) {
             let Ok(keyword) = str::from_utf8(&msg[..p]) else {
@@ -14,20 +14,25 @@
  * limitations under the License.
  */
 
+use std::net::IpAddr;
+use std::time::Duration;
+
 use anyhow::anyhow;
 use slog::slog_info;
 use tokio::io::AsyncWriteExt;
 
-use g3_dpi::ProtocolInspectAction;
-use g3_io_ext::{LineRecvBuf, OnceBufReader};
-use g3_slog_types::{LtHost, LtUpstreamAddr, LtUuid};
+use g3_dpi::{
+    Protocol, ProtocolInspectAction, SmtpInterceptionConfig, SmtpLongLineAction, SmtpStartTlsPolicy,
+};
+use g3_io_ext::{LimitedWriteExt, LineRecvBuf, OnceBufReader};
+use g3_slog_types::{LtDuration, LtHost, LtUpstreamAddr, LtUuid};
 use g3_smtp_proto::command::Command;
 use g3_smtp_proto::response::{ReplyCode, ResponseEncoder, ResponseParser};
-use g3_types::net::{Host, UpstreamAddr};
+use g3_types::net::{Host, ProxyProtocolVersion, UpstreamAddr};
 
 use super::StartTlsProtocol;
 #[cfg(feature = "quic")]
-use crate::audit::DetourAction;
+use crate::audit::{DetourAction, StreamDetourFallback, StreamDetourFallbackAction};
 use crate::config::server::ServerConfig;
 use crate::inspect::{BoxAsyncRead, BoxAsyncWrite, StreamInspectContext, StreamInspection};
 use crate::serve::{ServerTaskError, ServerTaskResult};
@@ -36,10 +41,13 @@ mod ext;
 use ext::{CommandLineRecvExt, ResponseLineRecvExt, ResponseParseExt};
 
 mod greeting;
-use greeting::Greeting;
+use greeting::{send_upstream_proxy_protocol, Greeting};
 
 mod ending;
-use ending::{EndQuitServer, EndWaitClient};
+use ending::{EndQuitServer, EndWaitClient, QuitReplyRelay};
+
+mod drain;
+use drain::DrainChecker;
 
 mod initiation;
 use initiation::{InitializedExtensions, Initiation};
@@ -50,12 +58,28 @@ use forward::{Forward, ForwardNextAction};
 mod transaction;
 use transaction::Transaction;
 
+mod remap;
+use remap::ReplyRemapper;
+
+mod deferred;
+pub(crate) use deferred::SmtpDeferredGreeting;
+
 #[derive(Default)]
 struct SmtpRelayBuf {
     cmd_recv_buf: LineRecvBuf<{ Command::MAX_LINE_SIZE }>,
     rsp_recv_buf: LineRecvBuf<{ ResponseParser::MAX_LINE_SIZE }>,
 }
 
+impl SmtpRelayBuf {
+    fn new(config: &SmtpInterceptionConfig) -> Self {
+        let mut buf = SmtpRelayBuf::default();
+        if config.long_response_line == SmtpLongLineAction::Truncate {
+            buf.rsp_recv_buf.set_truncate_long_line(true);
+        }
+        buf
+    }
+}
+
 macro_rules! intercept_log {
     ($obj:tt, $($args:tt)+) => {
         slog_info!($obj.ctx.intercept_logger(), $($args)+;
@@ -65,6 +89,9 @@ macro_rules! intercept_log {
             "upstream" => LtUpstreamAddr(&$obj.upstream),
             "client_host" => $obj.client_host.as_ref().map(LtHost),
             "transaction_count" => $obj.transaction_count,
+            "client_quit" => $obj.client_quit,
+            "greeting_ttfb" => $obj.greeting_ttfb.map(LtDuration),
+            "greeting_time" => $obj.greeting_time.map(LtDuration),
         )
     };
 }
@@ -81,22 +108,33 @@ pub(crate) struct SmtpInterceptObject<SC: ServerConfig> {
     ctx: StreamInspectContext<SC>,
     upstream: UpstreamAddr,
     from_starttls: bool,
+    upstream_proxy_protocol: Option<ProxyProtocolVersion>,
+    greeting_sent: bool,
     client_host: Option<Host>,
     transaction_count: usize,
+    client_quit: bool,
+    greeting_ttfb: Option<Duration>,
+    greeting_time: Option<Duration>,
 }
 
 impl<SC> SmtpInterceptObject<SC>
 where
     SC: ServerConfig + Send + Sync + 'static,
 {
-    pub(crate) fn new(ctx: StreamInspectContext<SC>, upstream: UpstreamAddr) -> Self {
+    pub(crate) fn new(mut ctx: StreamInspectContext<SC>, upstream: UpstreamAddr) -> Self {
+        ctx.set_protocol_idle_timeout(Protocol::Smtp);
         SmtpInterceptObject {
             io: None,
             ctx,
             upstream,
             from_starttls: false,
+            upstream_proxy_protocol: None,
+            greeting_sent: false,
             client_host: None,
             transaction_count: 0,
+            client_quit: false,
+            greeting_ttfb: None,
+            greeting_time: None,
         }
     }
 
@@ -104,6 +142,16 @@ where
         self.from_starttls = true;
     }
 
+    /// Send the PROXY protocol header to the upstream MTA if the session is intercepted.
+    pub(crate) fn set_upstream_proxy_protocol(&mut self, version: ProxyProtocolVersion) {
+        self.upstream_proxy_protocol = Some(version);
+    }
+
+    /// The local greeting banner has been sent to the client before the upstream connection.
+    pub(crate) fn set_greeting_sent(&mut self) {
+        self.greeting_sent = true;
+    }
+
     pub(crate) fn set_io(
         &mut self,
         clt_r: BoxAsyncRead,
@@ -126,11 +174,15 @@ where
             #[cfg(feature = "quic")]
             ProtocolInspectAction::Detour => self.do_detour().await.map(|_| None),
             ProtocolInspectAction::Bypass => self.do_bypass().await.map(|_| None),
-            ProtocolInspectAction::Block => self.do_block().await.map(|_| None),
+            ProtocolInspectAction::Block | ProtocolInspectAction::BlockWithResponse => {
+                self.do_block().await.map(|_| None)
+            }
         };
         match r {
             Ok(obj) => {
-                intercept_log!(self, "finished");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished");
+                }
                 Ok(obj)
             }
             Err(e) => {
@@ -140,18 +192,30 @@ where
         }
     }
 
+    /// Handle the stream as decided by [StreamDetourFallback] if the stream detour is unavailable
+    #[cfg(feature = "quic")]
+    async fn do_detour_fallback(&mut self, e: Option<anyhow::Error>) -> ServerTaskResult<()> {
+        let fallback = StreamDetourFallback::new(self.ctx.audit_handle.stream_detour_on_error(), e);
+        intercept_log!(self, "{fallback}");
+        match fallback.action {
+            StreamDetourFallbackAction::Block => self.do_block().await,
+            StreamDetourFallbackAction::Bypass => self.do_bypass().await,
+            StreamDetourFallbackAction::Close(e) => {
+                self.close_on_detour_error().await;
+                Err(ServerTaskError::InternalAdapterError(e))
+            }
+        }
+    }
+
     #[cfg(feature = "quic")]
     async fn do_detour(&mut self) -> ServerTaskResult<()> {
         let Some(client) = self.ctx.audit_handle.stream_detour_client() else {
-            return self.do_bypass().await;
+            return self.do_detour_fallback(None).await;
         };
 
         let mut detour_stream = match client.open_detour_stream().await {
             Ok(s) => s,
-            Err(e) => {
-                self.close_on_detour_error().await;
-                return Err(ServerTaskError::InternalAdapterError(e));
-            }
+            Err(e) => return self.do_detour_fallback(Some(e)).await,
         };
 
         let detour_ctx = client.build_context(
@@ -159,7 +223,7 @@ where
             &self.ctx.server_quit_policy,
             &self.ctx.task_notes,
             &self.upstream,
-            g3_dpi::Protocol::Smtp,
+            Protocol::Smtp,
         );
 
         match detour_ctx.check_detour_action(&mut detour_stream).await {
@@ -229,16 +293,9 @@ where
             ups_w,
         } = self.io.take().unwrap();
 
-        crate::inspect::stream::transit_transparent(
-            clt_r,
-            clt_w,
-            ups_r,
-            ups_w,
-            &self.ctx.server_config,
-            &self.ctx.server_quit_policy,
-            self.ctx.user(),
-        )
-        .await
+        self.ctx
+            .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+            .await
     }
 
     async fn do_block(&mut self) -> ServerTaskResult<()> {
@@ -259,6 +316,7 @@ where
             .write(&mut clt_w)
             .await
             .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+        self.ctx.tarpit_blocked(&mut clt_w).await;
         EndWaitClient::new(local_ip)
             .run_to_end(
                 clt_r,
@@ -276,24 +334,95 @@ where
             clt_r,
             mut clt_w,
             ups_r,
-            ups_w,
+            mut ups_w,
         } = self.io.take().unwrap();
 
         if self.from_starttls {
             return self
-                .start_initiation(clt_r, clt_w, ups_r.into_inner(), ups_w)
+                .start_initiation(
+                    clt_r,
+                    clt_w,
+                    ups_r.into_inner(),
+                    ups_w,
+                    SmtpStartTlsPolicy::Ignore,
+                )
                 .await;
         }
 
+        if let Some(version) = self.upstream_proxy_protocol {
+            send_upstream_proxy_protocol(
+                version,
+                self.ctx.task_notes.client_addr,
+                self.ctx.task_notes.server_addr,
+                &mut ups_w,
+            )
+            .await?;
+        }
+
         let interception_config = self.ctx.smtp_interception();
         let local_ip = self.ctx.task_notes.server_addr.ip();
+        let starttls_policy = interception_config.starttls_policy(self.upstream.host());
+
+        if let Some(banner) = &interception_config.greeting_banner {
+            if !self.greeting_sent {
+                let banner = banner.encode(local_ip, self.upstream.host());
+                clt_w
+                    .write_all_flush(banner.as_bytes())
+                    .await
+                    .map_err(ServerTaskError::ClientTcpWriteFailed)?;
+            }
 
-        let mut greeting = Greeting::new(local_ip);
-        let ups_r = match greeting
+            // the client commands will be kept in the socket buffer until the upstream greeting
+            // is received, and the capabilities will be reconciled in the initiation stage
+            let mut greeting = self.new_greeting(local_ip);
+            let ups_r = greeting
+                .relay(
+                    ups_r,
+                    &mut tokio::io::sink(),
+                    interception_config.greeting_timeout,
+                )
+                .await;
+            self.log_truncated_lines(greeting.take_truncated_count());
+            self.greeting_ttfb = greeting.ttfb();
+            self.greeting_time = greeting.total_time();
+            let ups_r = match ups_r {
+                Ok(ups_r) => box_greeting_reader(ups_r),
+                Err(e) => {
+                    let _ = ResponseEncoder::local_service_not_available(local_ip)
+                        .write(&mut clt_w)
+                        .await;
+                    let _ = clt_w.shutdown().await;
+                    return Err(e.into());
+                }
+            };
+            let (code, host) = greeting.into_parts();
+            self.upstream.set_host(host);
+            if code == ReplyCode::NO_SERVICE {
+                let quit_wait_timeout = interception_config.quit_wait_timeout;
+                tokio::spawn(async move {
+                    let _ = EndQuitServer::run_to_end(ups_r, ups_w, quit_wait_timeout).await;
+                });
+                let _ = ResponseEncoder::local_service_not_available(local_ip)
+                    .write(&mut clt_w)
+                    .await;
+                let _ = clt_w.shutdown().await;
+                return Err(ServerTaskError::UpstreamAppUnavailable);
+            }
+
+            return self
+                .start_initiation(clt_r, clt_w, ups_r, ups_w, starttls_policy)
+                .await;
+        }
+
+        let mut greeting = self.new_greeting(local_ip);
+        let ups_r = greeting
             .relay(ups_r, &mut clt_w, interception_config.greeting_timeout)
-            .await
-        {
-            Ok(ups_r) => ups_r,
+            .await;
+        self.log_truncated_lines(greeting.take_truncated_count());
+        self.greeting_ttfb = greeting.ttfb();
+        self.greeting_time = greeting.total_time();
+        let ups_r = match ups_r {
+            Ok(ups_r) => box_greeting_reader(ups_r),
             Err(e) => {
                 greeting.reply_no_service(&e, &mut clt_w).await;
                 return Err(e.into());
@@ -312,7 +441,54 @@ where
                 .map(|_| None);
         }
 
-        self.start_initiation(clt_r, clt_w, ups_r, ups_w).await
+        self.start_initiation(clt_r, clt_w, ups_r, ups_w, starttls_policy)
+            .await
+    }
+
+    fn new_greeting(&self, local_ip: IpAddr) -> Greeting {
+        let interception_config = self.ctx.smtp_interception();
+        let mut greeting = Greeting::new(local_ip, interception_config.lenient_greeting_host);
+        if interception_config.long_response_line == SmtpLongLineAction::Truncate {
+            greeting.set_truncate_long_line();
+        }
+        greeting
+    }
+
+    fn log_truncated_lines(&self, count: usize) {
+        if count > 0 {
+            intercept_log!(self, "truncated {count} too long response lines");
+        }
+    }
+
+    /// Close both sides after the QUIT reply has been relayed to the client
+    async fn close_on_quit(
+        &mut self,
+        mut clt_w: BoxAsyncWrite,
+        mut ups_w: BoxAsyncWrite,
+    ) -> ServerTaskResult<Option<StreamInspection<SC>>> {
+        self.client_quit = true;
+        let quit_wait_timeout = self.ctx.smtp_interception().quit_wait_timeout;
+        let _ = tokio::time::timeout(quit_wait_timeout, async {
+            let _ = ups_w.shutdown().await;
+            let _ = clt_w.shutdown().await;
+        })
+        .await;
+        Ok(None)
+    }
+
+    /// Close both sides after the 421 reply has been sent to the client as the server is going offline
+    async fn close_on_drain(
+        &mut self,
+        mut clt_w: BoxAsyncWrite,
+        ups_r: BoxAsyncRead,
+        ups_w: BoxAsyncWrite,
+    ) -> ServerTaskResult<Option<StreamInspection<SC>>> {
+        let quit_wait_timeout = self.ctx.smtp_interception().quit_wait_timeout;
+        tokio::spawn(async move {
+            let _ = EndQuitServer::run_to_end(ups_r, ups_w, quit_wait_timeout).await;
+        });
+        let _ = tokio::time::timeout(quit_wait_timeout, clt_w.shutdown()).await;
+        Err(ServerTaskError::CanceledAsServerQuit)
     }
 
     async fn start_initiation(
@@ -321,25 +497,52 @@ where
         mut clt_w: BoxAsyncWrite,
         mut ups_r: BoxAsyncRead,
         mut ups_w: BoxAsyncWrite,
+        starttls_policy: SmtpStartTlsPolicy,
     ) -> ServerTaskResult<Option<StreamInspection<SC>>> {
         let local_ip = self.ctx.task_notes.server_addr.ip();
         let interception_config = self.ctx.smtp_interception();
 
+        let require_starttls = starttls_policy == SmtpStartTlsPolicy::Require;
+        let mut relay_buf = SmtpRelayBuf::new(interception_config);
         let mut initiation = Initiation::new(interception_config, local_ip, self.from_starttls);
-        initiation
-            .relay(&mut clt_r, &mut clt_w, &mut ups_r, &mut ups_w)
-            .await?;
+        if require_starttls {
+            initiation.set_require_starttls();
+        }
+        let r = initiation
+            .relay(
+                &mut relay_buf,
+                &mut clt_r,
+                &mut clt_w,
+                &mut ups_r,
+                &mut ups_w,
+            )
+            .await;
+        self.log_truncated_lines(relay_buf.rsp_recv_buf.take_truncated_count());
+        r?;
         let (client_host, mut server_ext) = initiation.into_parts();
         self.client_host = Some(client_host);
+        if starttls_policy == SmtpStartTlsPolicy::Prefer
+            && !server_ext.allow_starttls(self.from_starttls)
+        {
+            intercept_log!(self, "STARTTLS is not offered by upstream");
+        }
 
-        let mut relay_buf = SmtpRelayBuf::default();
+        let drain = DrainChecker::new(&self.ctx);
 
         loop {
             let allow_odmr = server_ext.allow_odmr(interception_config);
             let allow_starttls = server_ext.allow_starttls(self.from_starttls);
-            let mut forward =
-                Forward::new(interception_config, local_ip, allow_odmr, allow_starttls);
-            let next_action = forward
+            let log_remap = |record: &str| intercept_log!(self, "reply remapped: {record}");
+            let mut forward = Forward::new(
+                interception_config,
+                &drain,
+                local_ip,
+                allow_odmr,
+                allow_starttls,
+                require_starttls,
+                &log_remap,
+            );
+            let r = forward
                 .relay(
                     &mut relay_buf,
                     &mut clt_r,
@@ -347,9 +550,14 @@ where
                     &mut ups_r,
                     &mut ups_w,
                 )
-                .await?;
+                .await;
+            self.log_truncated_lines(relay_buf.rsp_recv_buf.take_truncated_count());
+            let next_action = r?;
             match next_action {
-                ForwardNextAction::Quit => return Ok(None),
+                ForwardNextAction::Quit => return self.close_on_quit(clt_w, ups_w).await,
+                ForwardNextAction::Drain => {
+                    return self.close_on_drain(clt_w, ups_r, ups_w).await;
+                }
                 ForwardNextAction::StartTls => {
                     return if let Some(tls_interception) = self.ctx.tls_interception() {
                         let mut start_tls_obj =
@@ -362,34 +570,21 @@ where
                         start_tls_obj.set_io(clt_r, clt_w, ups_r, ups_w);
                         Ok(Some(StreamInspection::StartTls(start_tls_obj)))
                     } else {
-                        crate::inspect::stream::transit_transparent(
-                            clt_r,
-                            clt_w,
-                            ups_r,
-                            ups_w,
-                            &self.ctx.server_config,
-                            &self.ctx.server_quit_policy,
-                            self.ctx.user(),
-                        )
-                        .await
-                        .map(|_| None)
+                        self.ctx
+                            .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+                            .await
+                            .map(|_| None)
                     }
                 }
                 ForwardNextAction::ReverseConnection => {
-                    return crate::inspect::stream::transit_transparent(
-                        clt_r,
-                        clt_w,
-                        ups_r,
-                        ups_w,
-                        &self.ctx.server_config,
-                        &self.ctx.server_quit_policy,
-                        self.ctx.user(),
-                    )
-                    .await
-                    .map(|_| None);
+                    return self
+                        .ctx
+                        .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+                        .await
+                        .map(|_| None);
                 }
                 ForwardNextAction::SetExtensions(ext) => server_ext = ext,
-                ForwardNextAction::MailTransport(param) => {
+                ForwardNextAction::MailTransport(param, mail_reply_pending) => {
                     let allow_chunking = server_ext.allow_chunking(interception_config);
                     let allow_burl = server_ext.allow_burl(interception_config);
 
@@ -397,13 +592,17 @@ where
                     self.transaction_count += 1;
                     let mut transaction = Transaction::new(
                         &self.ctx,
+                        &drain,
                         transaction_id,
                         local_ip,
                         allow_chunking,
                         allow_burl,
                         param,
                     );
-                    transaction
+                    if mail_reply_pending {
+                        transaction.set_mail_reply_pending();
+                    }
+                    let r = transaction
                         .relay(
                             &mut relay_buf,
                             &mut clt_r,
@@ -411,12 +610,27 @@ where
                             &mut ups_r,
                             &mut ups_w,
                         )
-                        .await?;
+                        .await;
+                    self.log_truncated_lines(relay_buf.rsp_recv_buf.take_truncated_count());
+                    r?;
                     if transaction.quit() {
-                        return Ok(None);
+                        return self.close_on_quit(clt_w, ups_w).await;
+                    }
+                    if transaction.drained() {
+                        return self.close_on_drain(clt_w, ups_r, ups_w).await;
                     }
                 }
             }
         }
     }
 }
+
+/// Keep the data received after the greeting, which should be rare as the upstream should
+/// wait for the client command before sending more data
+fn box_greeting_reader(ups_r: OnceBufReader<BoxAsyncRead>) -> BoxAsyncRead {
+    if ups_r.buf().is_some() {
+        Box::new(ups_r)
+    } else {
+        ups_r.into_inner()
+    }
+}
@@ -0,0 +1,219 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+
+use g3_dpi::{SmtpInterceptionConfig, SmtpReplyRemap};
+use g3_smtp_proto::response::ResponseParser;
+
+/// Split the enhanced status code (RFC 3463) from the reply text
+fn split_enhanced_status(msg: &[u8]) -> Option<(&str, &[u8])> {
+    let (status, left) = match memchr::memchr(b' ', msg) {
+        Some(p) => (&msg[..p], &msg[p + 1..]),
+        None => (msg, &msg[msg.len()..]),
+    };
+    let mut parts = status.split(|c| *c == b'.');
+    let class = parts.next()?;
+    if !matches!(class, b"2" | b"4" | b"5") {
+        return None;
+    }
+    for _ in 0..2 {
+        let v = parts.next()?;
+        if v.is_empty() || v.len() > 3 || !v.iter().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    let status = std::str::from_utf8(status).ok()?;
+    Some((status, left))
+}
+
+/// Rewrite the lines of an upstream reply according to the reply remap config.
+///
+/// A new one should be created for each reply.
+pub(super) struct ReplyRemapper<'a> {
+    config: &'a SmtpInterceptionConfig,
+    started: bool,
+    remap: Option<&'a SmtpReplyRemap>,
+    record: Option<String>,
+}
+
+impl<'a> ReplyRemapper<'a> {
+    pub(super) fn new(config: &'a SmtpInterceptionConfig) -> Self {
+        ReplyRemapper {
+            config,
+            started: false,
+            remap: None,
+            record: None,
+        }
+    }
+
+    /// Rewrite the line that has been fed to the response parser, `msg` is what returned by it
+    pub(super) fn rewrite<'b>(
+        &mut self,
+        rsp: &ResponseParser,
+        line: &'b [u8],
+        msg: &[u8],
+    ) -> Cow<'b, [u8]> {
+        let enhanced_status = split_enhanced_status(msg);
+        if !self.started {
+            self.started = true;
+            if self.config.reply_remap.is_empty() {
+                return Cow::Borrowed(line);
+            }
+            let code = rsp.code().as_u16();
+            let status = enhanced_status.map(|(s, _)| s);
+            self.remap = self.config.find_reply_remap(code, status);
+            if let Some(remap) = self.remap {
+                let new_status = status.map(|s| remap.enhanced_status.as_deref().unwrap_or(s));
+                self.record = Some(match (status, new_status) {
+                    (Some(s1), Some(s2)) => format!("{code} {s1} -> {} {s2}", remap.code),
+                    _ => format!("{code} -> {}", remap.code),
+                });
+            }
+        }
+        let Some(remap) = self.remap else {
+            return Cow::Borrowed(line);
+        };
+
+        let (status, text) = match enhanced_status {
+            Some((s, left)) => (Some(remap.enhanced_status.as_deref().unwrap_or(s)), left),
+            None => (None, msg),
+        };
+        let text = remap.text.as_ref().map(|s| s.as_bytes()).unwrap_or(text);
+
+        let mut buf = Vec::with_capacity(line.len() + 32);
+        buf.extend_from_slice(remap.code.to_string().as_bytes());
+        let delimiter = if rsp.finished() { b' ' } else { b'-' };
+        match status {
+            Some(status) => {
+                buf.push(delimiter);
+                buf.extend_from_slice(status.as_bytes());
+                if !text.is_empty() {
+                    buf.push(b' ');
+                    buf.extend_from_slice(text);
+                }
+            }
+            None => {
+                if !text.is_empty() || delimiter == b'-' {
+                    buf.push(delimiter);
+                    buf.extend_from_slice(text);
+                }
+            }
+        }
+        buf.extend_from_slice(b"\r\n");
+        Cow::Owned(buf)
+    }
+
+    /// Get the `original -> rewritten` record if the reply has been remapped
+    pub(super) fn take_record(&mut self) -> Option<String> {
+        self.record.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remap_all(config: &SmtpInterceptionConfig, lines: &[&[u8]]) -> (Vec<u8>, Option<String>) {
+        let mut rsp = ResponseParser::default();
+        let mut remapper = ReplyRemapper::new(config);
+        let mut output = Vec::new();
+        for line in lines {
+            let msg = rsp.feed_line(line).unwrap();
+            output.extend_from_slice(&remapper.rewrite(&rsp, line, msg));
+        }
+        (output, remapper.take_record())
+    }
+
+    fn new_config() -> SmtpInterceptionConfig {
+        let mut config = SmtpInterceptionConfig::default();
+        config.reply_remap.push(SmtpReplyRemap {
+            match_code: 550,
+            match_enhanced_status: Some("5.7.1".to_string()),
+            code: 554,
+            enhanced_status: None,
+            text: None,
+        });
+        config.reply_remap.push(SmtpReplyRemap {
+            match_code: 552,
+            match_enhanced_status: None,
+            code: 554,
+            enhanced_status: Some("5.3.4".to_string()),
+            text: Some("Message rejected".to_string()),
+        });
+        config
+    }
+
+    #[test]
+    fn split_status() {
+        assert_eq!(
+            split_enhanced_status(b"5.7.1 Denied"),
+            Some(("5.7.1", b"Denied".as_slice()))
+        );
+        assert_eq!(
+            split_enhanced_status(b"2.0.0"),
+            Some(("2.0.0", b"".as_slice()))
+        );
+        assert!(split_enhanced_status(b"Denied").is_none());
+        assert!(split_enhanced_status(b"3.0.0 Denied").is_none());
+        assert!(split_enhanced_status(b"5.7.1.1 Denied").is_none());
+        assert!(split_enhanced_status(b"5.7 Denied").is_none());
+    }
+
+    #[test]
+    fn not_matched() {
+        let config = new_config();
+        let lines: [&[u8]; 1] = [b"550 5.1.1 No such user\r\n"];
+        let (output, record) = remap_all(&config, &lines);
+        assert_eq!(output.as_slice(), lines[0]);
+        assert!(record.is_none());
+    }
+
+    #[test]
+    fn code_only() {
+        let config = new_config();
+        let lines: [&[u8]; 2] = [
+            b"550-5.7.1 Message rejected by vendor policy\r\n",
+            b"550 5.7.1 See https://vendor.example.net\r\n",
+        ];
+        let (output, record) = remap_all(&config, &lines);
+        assert_eq!(
+            output.as_slice(),
+            b"554-5.7.1 Message rejected by vendor policy\r\n554 5.7.1 See https://vendor.example.net\r\n"
+        );
+        assert_eq!(record.as_deref(), Some("550 5.7.1 -> 554 5.7.1"));
+    }
+
+    #[test]
+    fn with_status_and_text() {
+        let config = new_config();
+        let lines: [&[u8]; 2] = [b"552-5.3.4 Too big\r\n", b"552 Limit 10MB\r\n"];
+        let (output, record) = remap_all(&config, &lines);
+        assert_eq!(
+            output.as_slice(),
+            b"554-5.3.4 Message rejected\r\n554 Message rejected\r\n"
+        );
+        assert_eq!(record.as_deref(), Some("552 5.3.4 -> 554 5.3.4"));
+
+        let lines: [&[u8]; 1] = [b"552\r\n"];
+        let (output, record) = remap_all(&config, &lines);
+        assert_eq!(output.as_slice(), b"554 Message rejected\r\n");
+        assert_eq!(record.as_deref(), Some("552 -> 554"));
+    }
+}
@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::collections::VecDeque;
 use std::net::IpAddr;
 use std::time::Duration;
 
@@ -25,13 +26,16 @@ use tokio::time::Instant;
 use g3_dpi::SmtpInterceptionConfig;
 use g3_icap_client::reqmod::mail::{ReqmodAdaptationEndState, ReqmodAdaptationRunState};
 use g3_icap_client::reqmod::smtp::SmtpMessageAdapter;
-use g3_io_ext::{LimitedCopy, LimitedCopyError, LimitedWriteExt};
+use g3_io_ext::{LimitedCopy, LimitedWriteExt};
 use g3_slog_types::LtUuid;
 use g3_smtp_proto::command::{Command, MailParam, RecipientParam};
 use g3_smtp_proto::io::TextDataReader;
 use g3_smtp_proto::response::{ReplyCode, ResponseEncoder, ResponseParser};
 
-use super::{CommandLineRecvExt, ResponseLineRecvExt, ResponseParseExt, SmtpRelayBuf};
+use super::{
+    CommandLineRecvExt, DrainChecker, QuitReplyRelay, ReplyRemapper, ResponseLineRecvExt,
+    ResponseParseExt, SmtpRelayBuf,
+};
 use crate::config::server::ServerConfig;
 use crate::inspect::StreamInspectContext;
 use crate::serve::{ServerIdleChecker, ServerTaskError, ServerTaskResult};
@@ -44,25 +48,41 @@ macro_rules! intercept_log {
             "depth" => $obj.ctx.inspection_depth,
             "transaction_id" => $obj.transaction_id,
             "mail_from" => $obj.mail_from.reverse_path(),
+            "message_size" => $obj.message_size,
         )
     };
 }
 
+/// Commands that have been sent to upstream but whose replies haven't been received yet
+enum PendingReply {
+    Mail,
+    Recipient(RecipientParam),
+}
+
 pub(super) struct Transaction<'a, SC: ServerConfig> {
     config: &'a SmtpInterceptionConfig,
     ctx: &'a StreamInspectContext<SC>,
+    drain: &'a DrainChecker,
     transaction_id: usize,
     local_ip: IpAddr,
     allow_chunking: bool,
     allow_burl: bool,
     mail_from: MailParam,
     mail_to: Vec<RecipientParam>,
+    message_size: Option<usize>,
+    size_exceeded: bool,
+    max_recipients: Option<usize>,
+    rejected_recipients: usize,
+    pending_replies: VecDeque<PendingReply>,
+    mail_rejected: bool,
     quit: bool,
+    drained: bool,
 }
 
 impl<'a, SC: ServerConfig> Transaction<'a, SC> {
     pub(super) fn new(
         ctx: &'a StreamInspectContext<SC>,
+        drain: &'a DrainChecker,
         transaction_id: usize,
         local_ip: IpAddr,
         allow_chunking: bool,
@@ -72,21 +92,38 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
         Transaction {
             config: ctx.smtp_interception(),
             ctx,
+            drain,
             transaction_id,
             local_ip,
             allow_chunking,
             allow_burl,
             mail_from: from,
             mail_to: Vec::with_capacity(4),
+            message_size: None,
+            size_exceeded: false,
+            max_recipients: ctx.smtp_max_recipients(),
+            rejected_recipients: 0,
+            pending_replies: VecDeque::new(),
+            mail_rejected: false,
             quit: false,
+            drained: false,
         }
     }
 
+    pub(super) fn set_mail_reply_pending(&mut self) {
+        self.pending_replies.push_back(PendingReply::Mail);
+    }
+
     #[inline]
     pub(super) fn quit(&self) -> bool {
         self.quit
     }
 
+    #[inline]
+    pub(super) fn drained(&self) -> bool {
+        self.drained
+    }
+
     pub(super) async fn relay<CR, CW, UR, UW>(
         &mut self,
         buf: &mut SmtpRelayBuf,
@@ -103,7 +140,22 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
     {
         match self.do_relay(buf, clt_r, clt_w, ups_r, ups_w).await {
             Ok(_) => {
-                intercept_log!(self, "finished");
+                if self.size_exceeded {
+                    intercept_log!(self, "message size exceeds limit");
+                } else if self.mail_rejected {
+                    intercept_log!(self, "MAIL command rejected by upstream");
+                } else if self.rejected_recipients > 0 {
+                    intercept_log!(
+                        self,
+                        "too many recipients, {} rejected by limit {}",
+                        self.rejected_recipients,
+                        self.max_recipients.unwrap_or_default()
+                    );
+                } else if self.drained {
+                    intercept_log!(self, "drained as the server is going offline");
+                } else if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished");
+                }
                 Ok(())
             }
             Err(e) => {
@@ -129,7 +181,46 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
     {
         let mut in_chunking = false;
         loop {
+            if self.mail_rejected {
+                // all pipelined commands have been replied, let the caller handle the following ones
+                return Ok(());
+            }
+
             buf.cmd_recv_buf.consume_line();
+            if in_chunking {
+                // never cut the message data in the middle of the BDAT chunks
+                buf.cmd_recv_buf
+                    .wait_cmd(
+                        self.config.command_idle_timeout,
+                        clt_r,
+                        clt_w,
+                        self.local_ip,
+                    )
+                    .await?;
+            } else {
+                let has_cmd = self
+                    .drain
+                    .wait_cmd(
+                        &mut buf.cmd_recv_buf,
+                        self.config.command_idle_timeout,
+                        self.config.command_wait_timeout,
+                        clt_r,
+                        clt_w,
+                        self.local_ip,
+                    )
+                    .await?;
+                if !has_cmd {
+                    self.reply_error_to_client(
+                        buf,
+                        ups_r,
+                        clt_w,
+                        ResponseEncoder::local_service_shutting_down(self.local_ip),
+                    )
+                    .await?;
+                    self.drained = true;
+                    return Ok(());
+                }
+            }
             let (cmd, cmd_line) = buf
                 .cmd_recv_buf
                 .recv_cmd(self.config.command_wait_timeout, clt_r, clt_w)
@@ -138,20 +229,43 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
             match cmd {
                 Command::Recipient(p) => {
                     if in_chunking {
-                        self.send_error_to_client(clt_w, ResponseEncoder::BAD_SEQUENCE_OF_COMMANDS)
-                            .await?;
+                        self.reply_error_to_client(
+                            buf,
+                            ups_r,
+                            clt_w,
+                            ResponseEncoder::BAD_SEQUENCE_OF_COMMANDS,
+                        )
+                        .await?;
                         continue;
                     }
-                    self.send_cmd(ups_w, clt_w, cmd_line).await?;
-                    let _ = self
-                        .recv_relay_rsp(self.config.response_wait_timeout, buf, ups_r, clt_w)
+                    // the pending replies may need to be drained before the limit check
+                    let cmd_line = cmd_line.to_vec();
+                    if self.recipient_limit_reached(buf, ups_r, clt_w).await? {
+                        self.rejected_recipients += 1;
+                        self.reply_error_to_client(
+                            buf,
+                            ups_r,
+                            clt_w,
+                            ResponseEncoder::TOO_MANY_RECIPIENTS,
+                        )
                         .await?;
-                    self.mail_to.push(p);
+                        continue;
+                    }
+                    self.send_cmd(ups_w, clt_w, &cmd_line).await?;
+                    self.pending_replies.push_back(PendingReply::Recipient(p));
+                    if !buf.cmd_recv_buf.has_next_line() {
+                        self.recv_relay_pending_rsp(buf, ups_r, clt_w).await?;
+                    }
                 }
                 Command::Data => {
                     if in_chunking {
-                        self.send_error_to_client(clt_w, ResponseEncoder::BAD_SEQUENCE_OF_COMMANDS)
-                            .await?;
+                        self.reply_error_to_client(
+                            buf,
+                            ups_r,
+                            clt_w,
+                            ResponseEncoder::BAD_SEQUENCE_OF_COMMANDS,
+                        )
+                        .await?;
                         continue;
                     }
                     self.send_data_cmd(ups_w, clt_w, cmd_line).await?;
@@ -169,8 +283,19 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
                 }
                 Command::BinaryData(size) => {
                     if !self.allow_chunking {
-                        self.send_error_to_client(clt_w, ResponseEncoder::COMMAND_NOT_IMPLEMENTED)
+                        self.reply_error_to_client(
+                            buf,
+                            ups_r,
+                            clt_w,
+                            ResponseEncoder::COMMAND_NOT_IMPLEMENTED,
+                        )
+                        .await?;
+                        continue;
+                    }
+                    if self.size_exceeded || !self.add_chunk_size(size) {
+                        self.reject_bin_data(buf, clt_r, clt_w, ups_r, ups_w, size)
                             .await?;
+                        in_chunking = true;
                         continue;
                     }
                     self.send_bdat_cmd(ups_w, clt_w, cmd_line, size).await?;
@@ -182,10 +307,20 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
                 }
                 Command::LastBinaryData(size) => {
                     if !self.allow_chunking {
-                        self.send_error_to_client(clt_w, ResponseEncoder::COMMAND_NOT_IMPLEMENTED)
-                            .await?;
+                        self.reply_error_to_client(
+                            buf,
+                            ups_r,
+                            clt_w,
+                            ResponseEncoder::COMMAND_NOT_IMPLEMENTED,
+                        )
+                        .await?;
                         continue;
                     }
+                    if self.size_exceeded || !self.add_chunk_size(size) {
+                        return self
+                            .reject_bin_data(buf, clt_r, clt_w, ups_r, ups_w, size)
+                            .await;
+                    }
                     self.send_bdat_cmd(ups_w, clt_w, cmd_line, size).await?;
                     self.send_bin_data(buf, clt_r, ups_w, size).await?;
                     let _ = self
@@ -195,8 +330,13 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
                 }
                 Command::DataByUrl(url) => {
                     if !self.allow_burl || !self.allow_chunking {
-                        self.send_error_to_client(clt_w, ResponseEncoder::COMMAND_NOT_IMPLEMENTED)
-                            .await?;
+                        self.reply_error_to_client(
+                            buf,
+                            ups_r,
+                            clt_w,
+                            ResponseEncoder::COMMAND_NOT_IMPLEMENTED,
+                        )
+                        .await?;
                         continue;
                     }
                     self.send_burl_cmd(ups_w, clt_w, cmd_line, url).await?;
@@ -207,8 +347,13 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
                 }
                 Command::LastDataByUrl(url) => {
                     if !self.allow_burl {
-                        self.send_error_to_client(clt_w, ResponseEncoder::COMMAND_NOT_IMPLEMENTED)
-                            .await?;
+                        self.reply_error_to_client(
+                            buf,
+                            ups_r,
+                            clt_w,
+                            ResponseEncoder::COMMAND_NOT_IMPLEMENTED,
+                        )
+                        .await?;
                         continue;
                     }
                     self.send_burl_cmd(ups_w, clt_w, cmd_line, url).await?;
@@ -243,20 +388,33 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
                 }
                 Command::Quit => {
                     self.send_cmd(ups_w, clt_w, cmd_line).await?;
-                    let _ = self
-                        .recv_relay_rsp(self.config.response_wait_timeout, buf, ups_r, clt_w)
+                    self.recv_relay_pending_rsp(buf, ups_r, clt_w).await?;
+                    QuitReplyRelay::new(self.local_ip)
+                        .relay(
+                            &mut buf.rsp_recv_buf,
+                            ups_r,
+                            clt_w,
+                            self.config.quit_wait_timeout,
+                        )
                         .await?;
                     self.quit = true;
                     return Ok(());
                 }
                 _ => {
-                    self.send_error_to_client(clt_w, ResponseEncoder::BAD_SEQUENCE_OF_COMMANDS)
-                        .await?;
+                    self.reply_error_to_client(
+                        buf,
+                        ups_r,
+                        clt_w,
+                        ResponseEncoder::BAD_SEQUENCE_OF_COMMANDS,
+                    )
+                    .await?;
                 }
             }
         }
     }
 
+    /// Receive and relay the reply of the last sent command,
+    /// the replies of the previous pipelined commands will be relayed first
     async fn recv_relay_rsp<CW, UR>(
         &mut self,
         recv_timeout: Duration,
@@ -264,27 +422,114 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
         ups_r: &mut UR,
         clt_w: &mut CW,
     ) -> ServerTaskResult<ReplyCode>
+    where
+        CW: AsyncWrite + Unpin,
+        UR: AsyncRead + Unpin,
+    {
+        self.recv_relay_pending_rsp(buf, ups_r, clt_w).await?;
+        self.recv_relay_single_rsp(recv_timeout, buf, ups_r, clt_w)
+            .await
+    }
+
+    /// Check if the recipient limit has been reached, which is always false in dry-run mode.
+    ///
+    /// The pending RCPT replies will be relayed first if they may make the limit reached,
+    /// so only the recipients accepted by upstream will be counted.
+    async fn recipient_limit_reached<CW, UR>(
+        &mut self,
+        buf: &mut SmtpRelayBuf,
+        ups_r: &mut UR,
+        clt_w: &mut CW,
+    ) -> ServerTaskResult<bool>
+    where
+        CW: AsyncWrite + Unpin,
+        UR: AsyncRead + Unpin,
+    {
+        let Some(max) = self.max_recipients else {
+            return Ok(false);
+        };
+        let pending = self
+            .pending_replies
+            .iter()
+            .filter(|p| matches!(p, PendingReply::Recipient(_)))
+            .count();
+        if self.mail_to.len() + pending < max {
+            return Ok(false);
+        }
+        if pending > 0 {
+            self.recv_relay_pending_rsp(buf, ups_r, clt_w).await?;
+        }
+        if self.mail_to.len() < max {
+            return Ok(false);
+        }
+        let reason = format!("recipient limit {max} reached");
+        Ok(!self.ctx.skip_block_in_dry_run("smtp", &reason))
+    }
+
+    /// Receive and relay the replies of the pipelined commands, in the order they were sent
+    async fn recv_relay_pending_rsp<CW, UR>(
+        &mut self,
+        buf: &mut SmtpRelayBuf,
+        ups_r: &mut UR,
+        clt_w: &mut CW,
+    ) -> ServerTaskResult<()>
+    where
+        CW: AsyncWrite + Unpin,
+        UR: AsyncRead + Unpin,
+    {
+        while let Some(pending) = self.pending_replies.pop_front() {
+            let rsp = self
+                .recv_relay_single_rsp(self.config.response_wait_timeout, buf, ups_r, clt_w)
+                .await?;
+            match pending {
+                PendingReply::Mail => {
+                    if rsp != ReplyCode::OK {
+                        self.mail_rejected = true;
+                    }
+                }
+                PendingReply::Recipient(p) => {
+                    if rsp.is_positive_completion() {
+                        self.mail_to.push(p);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn recv_relay_single_rsp<CW, UR>(
+        &mut self,
+        recv_timeout: Duration,
+        buf: &mut SmtpRelayBuf,
+        ups_r: &mut UR,
+        clt_w: &mut CW,
+    ) -> ServerTaskResult<ReplyCode>
     where
         CW: AsyncWrite + Unpin,
         UR: AsyncRead + Unpin,
     {
         let mut rsp = ResponseParser::default();
+        let mut remapper = ReplyRemapper::new(self.config);
         loop {
             buf.rsp_recv_buf.consume_line();
             let line = buf
                 .rsp_recv_buf
                 .read_rsp_line_with_feedback(recv_timeout, ups_r, clt_w, self.local_ip)
                 .await?;
-            let _msg = rsp
+            let msg = rsp
                 .feed_line_with_feedback(line, clt_w, self.local_ip)
                 .await?;
+            let line = remapper.rewrite(&rsp, line, msg);
 
             clt_w
-                .write_all_flush(line)
+                .write_all_flush(&line)
                 .await
                 .map_err(ServerTaskError::ClientTcpWriteFailed)?;
 
             if rsp.finished() {
+                if let Some(record) = remapper.take_record() {
+                    intercept_log!(self, "reply remapped: {record}");
+                }
                 let code = rsp.code();
                 return if code == ReplyCode::SERVICE_NOT_AVAILABLE {
                     Err(ServerTaskError::UpstreamAppUnavailable)
@@ -306,6 +551,11 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
         CW: AsyncWrite + Unpin,
         UW: AsyncWrite + Unpin,
     {
+        let max_size = self
+            .config
+            .max_message_size
+            .map(|v| v as u64)
+            .unwrap_or(u64::MAX);
         if let Some(client) = self.ctx.audit_handle.icap_reqmod_client() {
             match client
                 .smtp_message_adaptor(
@@ -315,9 +565,15 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
                 .await
             {
                 Ok(adapter) => {
-                    return self
-                        .send_txt_data_with_adaptation(clt_r, clt_w, ups_w, adapter)
+                    // the message decoder in the adapter will fail if the data is cut by the limit
+                    let mut limited_r = clt_r.take(max_size);
+                    let r = self
+                        .send_txt_data_with_adaptation(&mut limited_r, clt_w, ups_w, adapter)
                         .await;
+                    if r.is_err() && limited_r.limit() == 0 {
+                        return self.reply_size_exceeded(clt_w).await;
+                    }
+                    return r;
                 }
                 Err(e) => {
                     if !client.bypass() {
@@ -327,8 +583,57 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
             }
         }
 
-        let mut reader = TextDataReader::new(clt_r);
-        self.transfer_data(&mut reader, ups_w).await
+        let mut reader = TextDataReader::new(clt_r).take(max_size);
+        self.transfer_data(&mut reader, ups_w).await?;
+        if reader.limit() == 0 && !reader.get_ref().finished() {
+            self.discard_txt_data(reader.get_mut()).await?;
+            return self.reply_size_exceeded(clt_w).await;
+        }
+        Ok(())
+    }
+
+    /// Read and drop the left text data until the end-of-data line
+    async fn discard_txt_data<CR>(
+        &self,
+        reader: &mut TextDataReader<'_, CR>,
+    ) -> ServerTaskResult<()>
+    where
+        CR: AsyncRead + Unpin,
+    {
+        let mut sinker = tokio::io::sink();
+        match tokio::time::timeout(
+            self.config.data_termination_timeout,
+            tokio::io::copy(reader, &mut sinker),
+        )
+        .await
+        {
+            Ok(Ok(_)) => {
+                if reader.finished() {
+                    Ok(())
+                } else {
+                    Err(ServerTaskError::ClosedByClient)
+                }
+            }
+            Ok(Err(e)) => Err(ServerTaskError::ClientTcpReadFailed(e)),
+            Err(_) => Err(ServerTaskError::ClientAppTimeout(
+                "timeout to read DATA data",
+            )),
+        }
+    }
+
+    /// The max message size has been exceeded in the middle of the DATA message,
+    /// the partial message sent to upstream can only be dropped by closing the connection
+    async fn reply_size_exceeded<CW>(&self, clt_w: &mut CW) -> ServerTaskResult<()>
+    where
+        CW: AsyncWrite + Unpin,
+    {
+        let _ = self
+            .send_error_to_client(clt_w, ResponseEncoder::MESSAGE_SIZE_EXCEEDED)
+            .await;
+        Err(ServerTaskError::ClientAppError(anyhow!(
+            "message size exceeds limit {}",
+            self.config.max_message_size.unwrap_or_default()
+        )))
     }
 
     async fn send_txt_data_with_adaptation<CR, CW, UW>(
@@ -396,6 +701,9 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
         CR: AsyncRead + Unpin,
         UW: AsyncWrite + Unpin,
     {
+        // the BDAT command line is still in the buffer, skip it before taking the chunk data
+        buf.cmd_recv_buf.consume_line();
+
         let mut copy_size = size;
         let cache = buf.cmd_recv_buf.consume_left(size);
         if !cache.is_empty() {
@@ -418,64 +726,147 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
         self.transfer_data(&mut reader, ups_w).await
     }
 
-    async fn transfer_data<CR, UW>(&self, clt_r: &mut CR, ups_w: &mut UW) -> ServerTaskResult<()>
+    /// Add the chunk size to the message size, return false if the max message size is exceeded
+    fn add_chunk_size(&mut self, size: usize) -> bool {
+        let message_size = self.message_size.unwrap_or_default().saturating_add(size);
+        self.message_size = Some(message_size);
+        match self.config.max_message_size {
+            Some(max_size) => message_size <= max_size,
+            None => true,
+        }
+    }
+
+    async fn discard_bin_data<CR>(
+        &self,
+        buf: &mut SmtpRelayBuf,
+        clt_r: &mut CR,
+        size: usize,
+    ) -> ServerTaskResult<()>
     where
         CR: AsyncRead + Unpin,
-        UW: AsyncWrite + Unpin,
     {
-        let mut clt_to_ups =
-            LimitedCopy::new(clt_r, ups_w, &self.ctx.server_config.limited_copy_config());
+        buf.cmd_recv_buf.consume_line();
 
-        let idle_duration = self.ctx.server_config.task_idle_check_duration();
-        let mut idle_interval =
-            tokio::time::interval_at(Instant::now() + idle_duration, idle_duration);
-        let mut idle_count = 0;
-        let max_idle_count = self.ctx.task_max_idle_count();
+        let cache = buf.cmd_recv_buf.consume_left(size);
+        let copy_size = size - cache.len();
+        if copy_size == 0 {
+            return Ok(());
+        }
 
-        loop {
-            tokio::select! {
-                biased;
-
-                r = &mut clt_to_ups => {
-                    return match r {
-                        Ok(_) => {
-                            // ups_w is already flushed
-                            Ok(())
-                        }
-                        Err(LimitedCopyError::ReadFailed(e)) => {
-                            let _ = clt_to_ups.write_flush().await;
-                            Err(ServerTaskError::ClientTcpReadFailed(e))
-                        }
-                        Err(LimitedCopyError::WriteFailed(e)) => Err(ServerTaskError::UpstreamWriteFailed(e)),
-                    };
+        let mut reader = clt_r.take(copy_size as u64);
+        let mut sinker = tokio::io::sink();
+        match tokio::time::timeout(
+            self.config.data_termination_timeout,
+            tokio::io::copy(&mut reader, &mut sinker),
+        )
+        .await
+        {
+            Ok(Ok(n)) => {
+                if n < copy_size as u64 {
+                    Err(ServerTaskError::ClosedByClient)
+                } else {
+                    Ok(())
                 }
-                _ = idle_interval.tick() => {
-                    if clt_to_ups.is_idle() {
-                        idle_count += 1;
-                        if idle_count >= max_idle_count {
-                            return if clt_to_ups.no_cached_data() {
-                                Err(ServerTaskError::ClientAppTimeout("idle while reading BDAT data"))
-                            } else {
-                                Err(ServerTaskError::UpstreamAppTimeout("idle while sending BDAT data"))
-                            };
-                        }
-                    } else {
-                        idle_count = 0;
-                        clt_to_ups.reset_active();
-                    }
+            }
+            Ok(Err(e)) => Err(ServerTaskError::ClientTcpReadFailed(e)),
+            Err(_) => Err(ServerTaskError::ClientAppTimeout(
+                "timeout to read BDAT data",
+            )),
+        }
+    }
 
-                    if self.ctx.belongs_to_blocked_user() {
-                        let _ = clt_to_ups.write_flush().await;
-                        return Err(ServerTaskError::CanceledAsUserBlocked);
-                    }
+    /// Reject the BDAT chunk as the max message size has been exceeded.
+    ///
+    /// The upstream transaction will be reset at the first rejected chunk. The data of each
+    /// rejected chunk will still be read and discarded after the 552 reply, as required by
+    /// rfc3030, so the following pipelined commands won't be taken from the chunk data.
+    async fn reject_bin_data<CR, CW, UR, UW>(
+        &mut self,
+        buf: &mut SmtpRelayBuf,
+        clt_r: &mut CR,
+        clt_w: &mut CW,
+        ups_r: &mut UR,
+        ups_w: &mut UW,
+        size: usize,
+    ) -> ServerTaskResult<()>
+    where
+        CR: AsyncRead + Unpin,
+        CW: AsyncWrite + Unpin,
+        UR: AsyncRead + Unpin,
+        UW: AsyncWrite + Unpin,
+    {
+        if self.size_exceeded {
+            self.reply_error_to_client(buf, ups_r, clt_w, ResponseEncoder::MESSAGE_SIZE_EXCEEDED)
+                .await?;
+        } else {
+            self.abort_on_size_exceeded(buf, clt_w, ups_r, ups_w)
+                .await?;
+        }
+        self.discard_bin_data(buf, clt_r, size).await
+    }
 
-                    if self.ctx.server_force_quit() {
-                        let _ = clt_to_ups.write_flush().await;
-                        return Err(ServerTaskError::CanceledAsServerQuit)
-                    }
-                }
+    async fn abort_on_size_exceeded<CW, UR, UW>(
+        &mut self,
+        buf: &mut SmtpRelayBuf,
+        clt_w: &mut CW,
+        ups_r: &mut UR,
+        ups_w: &mut UW,
+    ) -> ServerTaskResult<()>
+    where
+        CW: AsyncWrite + Unpin,
+        UR: AsyncRead + Unpin,
+        UW: AsyncWrite + Unpin,
+    {
+        self.size_exceeded = true;
+        self.recv_relay_pending_rsp(buf, ups_r, clt_w).await?;
+
+        // drop the pending transaction on the upstream side without notifying the client
+        self.send_cmd(ups_w, clt_w, b"RSET\r\n").await?;
+        let mut rsp = ResponseParser::default();
+        loop {
+            buf.rsp_recv_buf.consume_line();
+            let line = buf
+                .rsp_recv_buf
+                .read_rsp_line_with_feedback(
+                    self.config.response_wait_timeout,
+                    ups_r,
+                    clt_w,
+                    self.local_ip,
+                )
+                .await?;
+            let _msg = rsp
+                .feed_line_with_feedback(line, clt_w, self.local_ip)
+                .await?;
+            if rsp.finished() {
+                break;
             }
         }
+        if rsp.code() != ReplyCode::OK {
+            return Err(ServerTaskError::UpstreamAppError(anyhow!(
+                "unexpected RESET reply code {}",
+                rsp.code()
+            )));
+        }
+
+        self.send_error_to_client(clt_w, ResponseEncoder::MESSAGE_SIZE_EXCEEDED)
+            .await
+    }
+
+    async fn transfer_data<CR, UW>(&self, clt_r: &mut CR, ups_w: &mut UW) -> ServerTaskResult<()>
+    where
+        CR: AsyncRead + Unpin,
+        UW: AsyncWrite + Unpin,
+    {
+        let clt_to_ups =
+            LimitedCopy::new(clt_r, ups_w, &self.ctx.server_config.limited_copy_config());
+        self.drain
+            .transfer_data(
+                clt_to_ups,
+                self.ctx.task_max_idle_count(),
+                self.config.drain_timeout,
+                || self.ctx.belongs_to_blocked_user(),
+            )
+            .await
     }
 
     async fn send_data_cmd<UW, CW>(
@@ -540,6 +931,22 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
         }
     }
 
+    /// Reply a local error to the client, after all pending replies have been relayed
+    async fn reply_error_to_client<CW, UR>(
+        &mut self,
+        buf: &mut SmtpRelayBuf,
+        ups_r: &mut UR,
+        clt_w: &mut CW,
+        rsp_encoder: ResponseEncoder,
+    ) -> ServerTaskResult<()>
+    where
+        CW: AsyncWrite + Unpin,
+        UR: AsyncRead + Unpin,
+    {
+        self.recv_relay_pending_rsp(buf, ups_r, clt_w).await?;
+        self.send_error_to_client(clt_w, rsp_encoder).await
+    }
+
     async fn send_error_to_client<W>(
         &self,
         clt_w: &mut W,
@@ -554,3 +961,434 @@ impl<'a, SC: ServerConfig> Transaction<'a, SC> {
             .map_err(ServerTaskError::ClientTcpWriteFailed)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncBufReadExt, BufReader, DuplexStream};
+    use yaml_rust::YamlLoader;
+
+    use g3_daemon::server::{ClientConnectionInfo, ServerQuitPolicy};
+    use g3_io_ext::OnceBufReader;
+    use g3_types::metrics::MetricsName;
+    use g3_types::net::UpstreamAddr;
+
+    use crate::audit::Auditor;
+    use crate::config::audit::AuditorConfig;
+    use crate::config::server::dummy_close::DummyCloseServerConfig;
+    use crate::inspect::smtp::SmtpInterceptObject;
+    use crate::module::tcp_connect::TcpConnectTaskNotes;
+    use crate::serve::{ServerTaskNotes, TcpStreamServerStats};
+
+    fn new_ctx(smtp_interception: &str) -> StreamInspectContext<DummyCloseServerConfig> {
+        let yaml = YamlLoader::load_from_str(&format!(
+            "{{name: test, smtp_interception: {smtp_interception}}}"
+        ))
+        .unwrap();
+        let mut auditor_config = AuditorConfig::new(None);
+        auditor_config.parse(yaml[0].as_hash().unwrap()).unwrap();
+
+        let name = MetricsName::from_str("test").unwrap();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 25);
+        let upstream = UpstreamAddr::from_str("example.net:25").unwrap();
+        let task_notes =
+            ServerTaskNotes::new(ClientConnectionInfo::new(addr, addr), None, Duration::ZERO);
+        let server_stats = Arc::new(TcpStreamServerStats::new(&name));
+        server_stats.set_online();
+        StreamInspectContext::new(
+            Auditor::build_test_handle(auditor_config),
+            Arc::new(DummyCloseServerConfig::new(&name, None)),
+            server_stats,
+            Arc::new(ServerQuitPolicy::default()),
+            &task_notes,
+            &TcpConnectTaskNotes::new(upstream),
+        )
+    }
+
+    fn mail_from(line: &[u8]) -> MailParam {
+        match Command::parse_line(line).unwrap() {
+            Command::Mail(p) => p,
+            _ => unreachable!(),
+        }
+    }
+
+    async fn read_until<R: AsyncRead + Unpin>(r: &mut R, end: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !data.ends_with(end) {
+            let n = r.read(&mut buf).await.unwrap();
+            assert_ne!(n, 0, "unexpected eof");
+            data.extend_from_slice(&buf[..n]);
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn pipelined_replies_in_order() {
+        let ctx = new_ctx("{}");
+        let drain = DrainChecker::new(&ctx);
+        let mut buf = SmtpRelayBuf::new(ctx.smtp_interception());
+
+        let (clt_io, mut clt_peer) = tokio::io::duplex(4096);
+        let (ups_io, mut ups_peer) = tokio::io::duplex(4096);
+        let (mut clt_r, mut clt_w) = tokio::io::split(clt_io);
+        let (mut ups_r, mut ups_w) = tokio::io::split(ups_io);
+
+        // the MAIL command has been sent by the caller, with all the following commands pipelined
+        let mut transaction = Transaction::new(
+            &ctx,
+            &drain,
+            1,
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            false,
+            false,
+            mail_from(b"MAIL FROM:<s@example.net>\r\n"),
+        );
+        transaction.set_mail_reply_pending();
+
+        let upstream = async move {
+            let cmds = read_until(&mut ups_peer, b"DATA\r\n").await;
+            ups_peer
+                .write_all(
+                    b"250 sender ok\r\n250 a ok\r\n550 b unknown\r\n250 c ok\r\n354 go ahead\r\n",
+                )
+                .await
+                .unwrap();
+            let data = read_until(&mut ups_peer, b"\r\n.\r\n").await;
+            ups_peer.write_all(b"250 queued\r\n").await.unwrap();
+            (cmds, data)
+        };
+        let client = async move {
+            clt_peer
+                .write_all(
+                    b"RCPT TO:<a@example.net>\r\nRCPT TO:<b@example.net>\r\n\
+                      RCPT TO:<c@example.net>\r\nDATA\r\n",
+                )
+                .await
+                .unwrap();
+            let replies = read_until(&mut clt_peer, b"354 go ahead\r\n").await;
+            clt_peer
+                .write_all(b"Subject: test\r\n\r\nhello\r\n.\r\n")
+                .await
+                .unwrap();
+            let end = read_until(&mut clt_peer, b"250 queued\r\n").await;
+            (replies, end)
+        };
+        let relay = transaction.relay(&mut buf, &mut clt_r, &mut clt_w, &mut ups_r, &mut ups_w);
+
+        let (r, (cmds, data), (replies, end)) = tokio::join!(relay, upstream, client);
+        r.unwrap();
+        assert_eq!(
+            cmds.as_slice(),
+            b"RCPT TO:<a@example.net>\r\nRCPT TO:<b@example.net>\r\n\
+              RCPT TO:<c@example.net>\r\nDATA\r\n"
+        );
+        assert_eq!(
+            replies.as_slice(),
+            b"250 sender ok\r\n250 a ok\r\n550 b unknown\r\n250 c ok\r\n354 go ahead\r\n"
+        );
+        assert_eq!(data.as_slice(), b"Subject: test\r\n\r\nhello\r\n.\r\n");
+        assert_eq!(end.as_slice(), b"250 queued\r\n");
+
+        assert!(!transaction.mail_rejected);
+        let recipients: Vec<&str> = transaction
+            .mail_to
+            .iter()
+            .map(|p| p.forward_path())
+            .collect();
+        assert_eq!(recipients, ["<a@example.net>", "<c@example.net>"]);
+    }
+
+    #[tokio::test]
+    async fn bdat_size_exceeded() {
+        let ctx = new_ctx("{max_message_size: 10}");
+        let drain = DrainChecker::new(&ctx);
+        let mut buf = SmtpRelayBuf::new(ctx.smtp_interception());
+
+        let (clt_io, mut clt_peer) = tokio::io::duplex(4096);
+        let (ups_io, mut ups_peer) = tokio::io::duplex(4096);
+        let (mut clt_r, mut clt_w) = tokio::io::split(clt_io);
+        let (ups_r, ups_w) = tokio::io::split(ups_io);
+
+        let mut transaction = Transaction::new(
+            &ctx,
+            &drain,
+            1,
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            true,
+            false,
+            mail_from(b"MAIL FROM:<s@example.net>\r\n"),
+        );
+
+        let upstream = async move {
+            let _ = read_until(&mut ups_peer, b"12345678").await;
+            ups_peer.write_all(b"250 chunk ok\r\n").await.unwrap();
+            let _ = read_until(&mut ups_peer, b"RSET\r\n").await;
+            ups_peer.write_all(b"250 reset\r\n").await.unwrap();
+            let mut left = Vec::new();
+            ups_peer.read_to_end(&mut left).await.unwrap();
+            left
+        };
+        let client = async move {
+            // the data of the rejected chunks should not be taken as commands
+            clt_peer
+                .write_all(b"BDAT 8\r\n12345678BDAT 8\r\nQUIT\r\nabBDAT 6 LAST\r\nQUIT\r\nNOOP\r\n")
+                .await
+                .unwrap();
+            read_until(
+                &mut clt_peer,
+                b"552 Message size exceeds fixed maximum message size\r\n\
+                  552 Message size exceeds fixed maximum message size\r\n",
+            )
+            .await
+        };
+        let relay = async {
+            let (mut ups_r, mut ups_w) = (ups_r, ups_w);
+            transaction
+                .relay(&mut buf, &mut clt_r, &mut clt_w, &mut ups_r, &mut ups_w)
+                .await
+        };
+
+        let (r, left, replies) = tokio::join!(relay, upstream, client);
+        r.unwrap();
+        assert!(left.is_empty());
+        assert_eq!(
+            replies.as_slice(),
+            b"250 chunk ok\r\n\
+              552 Message size exceeds fixed maximum message size\r\n\
+              552 Message size exceeds fixed maximum message size\r\n"
+        );
+        assert!(transaction.size_exceeded);
+        assert_eq!(transaction.message_size, Some(16));
+
+        buf.cmd_recv_buf.consume_line();
+        let line = buf.cmd_recv_buf.read_line(&mut clt_r).await.unwrap();
+        assert_eq!(line, b"NOOP\r\n");
+    }
+
+    #[tokio::test]
+    async fn data_size_exceeded() {
+        let ctx = new_ctx("{max_message_size: 16}");
+        let drain = DrainChecker::new(&ctx);
+        let mut buf = SmtpRelayBuf::new(ctx.smtp_interception());
+
+        let (clt_io, mut clt_peer) = tokio::io::duplex(4096);
+        let (ups_io, mut ups_peer) = tokio::io::duplex(4096);
+        let (mut clt_r, mut clt_w) = tokio::io::split(clt_io);
+        let (ups_r, ups_w) = tokio::io::split(ups_io);
+
+        let mut transaction = Transaction::new(
+            &ctx,
+            &drain,
+            1,
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            false,
+            false,
+            mail_from(b"MAIL FROM:<s@example.net>\r\n"),
+        );
+
+        let upstream = async move {
+            let _ = read_until(&mut ups_peer, b"DATA\r\n").await;
+            ups_peer.write_all(b"354 go ahead\r\n").await.unwrap();
+            let mut left = Vec::new();
+            ups_peer.read_to_end(&mut left).await.unwrap();
+            left
+        };
+        let client = async move {
+            clt_peer.write_all(b"DATA\r\n").await.unwrap();
+            let _ = read_until(&mut clt_peer, b"354 go ahead\r\n").await;
+            clt_peer
+                .write_all(b"Subject: test\r\n\r\nhello world\r\n.\r\n")
+                .await
+                .unwrap();
+            read_until(&mut clt_peer, b"\r\n").await
+        };
+        let relay = async {
+            let (mut ups_r, mut ups_w) = (ups_r, ups_w);
+            transaction
+                .relay(&mut buf, &mut clt_r, &mut clt_w, &mut ups_r, &mut ups_w)
+                .await
+        };
+
+        let (r, left, reply) = tokio::join!(relay, upstream, client);
+        assert!(matches!(r, Err(ServerTaskError::ClientAppError(_))));
+        // only the data within the limit is forwarded, then the upstream connection is dropped
+        assert_eq!(left.as_slice(), b"Subject: test\r\n\r");
+        assert_eq!(
+            reply.as_slice(),
+            b"552 Message size exceeds fixed maximum message size\r\n"
+        );
+    }
+
+    fn new_smtp_obj(
+        smtp_interception: &str,
+    ) -> (
+        SmtpInterceptObject<DummyCloseServerConfig>,
+        DuplexStream,
+        DuplexStream,
+    ) {
+        let ctx = new_ctx(smtp_interception);
+        let upstream = UpstreamAddr::from_str("example.net:25").unwrap();
+        let mut obj = SmtpInterceptObject::new(ctx, upstream);
+
+        let (clt_io, clt_peer) = tokio::io::duplex(4096);
+        let (ups_io, ups_peer) = tokio::io::duplex(4096);
+        let (clt_r, clt_w) = tokio::io::split(clt_io);
+        let (ups_r, ups_w) = tokio::io::split(ups_io);
+        obj.set_io(
+            Box::new(clt_r),
+            Box::new(clt_w),
+            OnceBufReader::with_no_buf(Box::new(ups_r)),
+            Box::new(ups_w),
+        );
+        (obj, clt_peer, ups_peer)
+    }
+
+    /// Run a mock upstream MTA, and return all the commands it received
+    async fn mock_upstream(ups_peer: DuplexStream, ehlo_reply: &[u8]) -> Vec<u8> {
+        let (ups_r, mut ups_w) = tokio::io::split(ups_peer);
+        let mut ups_r = BufReader::new(ups_r);
+        ups_w
+            .write_all(b"220 mx.example.net ESMTP\r\n")
+            .await
+            .unwrap();
+
+        let mut cmds = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            if ups_r.read_until(b'\n', &mut line).await.unwrap() == 0 {
+                return cmds;
+            }
+            cmds.extend_from_slice(&line);
+            let reply: &[u8] = if line.starts_with(b"EHLO ") {
+                ehlo_reply
+            } else if line.starts_with(b"MAIL ") {
+                b"250 sender ok\r\n"
+            } else if line.starts_with(b"QUIT") {
+                b"221 bye\r\n"
+            } else {
+                b"500 unexpected command\r\n"
+            };
+            ups_w.write_all(reply).await.unwrap();
+        }
+    }
+
+    /// Send EHLO to a session whose upstream doesn't offer STARTTLS and expect it to be aborted
+    async fn starttls_aborted(smtp_interception: &str) {
+        let (obj, mut clt_peer, ups_peer) = new_smtp_obj(smtp_interception);
+
+        let upstream = mock_upstream(ups_peer, b"250-mx.example.net\r\n250 PIPELINING\r\n");
+        let client = async move {
+            clt_peer
+                .write_all(b"EHLO client.example.net\r\nMAIL FROM:<s@example.net>\r\n")
+                .await
+                .unwrap();
+            let mut data = Vec::new();
+            clt_peer.read_to_end(&mut data).await.unwrap();
+            data
+        };
+
+        let (r, cmds, replies) = tokio::join!(obj.intercept(), upstream, client);
+        let e = r.err().unwrap();
+        assert!(matches!(e, ServerTaskError::UpstreamAppError(_)));
+        assert!(e.to_string().contains("STARTTLS is required"));
+        assert_eq!(cmds.as_slice(), b"EHLO client.example.net\r\n");
+        assert!(replies.starts_with(b"220 "));
+        assert!(replies.ends_with(b"Upstream service doesn't offer STARTTLS\r\n"));
+        assert!(!replies.windows(4).any(|w| w == b"250-"));
+    }
+
+    /// Run a session whose upstream doesn't offer STARTTLS and expect the MAIL to be relayed
+    async fn starttls_passed(smtp_interception: &str) {
+        let (obj, mut clt_peer, ups_peer) = new_smtp_obj(smtp_interception);
+
+        let upstream = mock_upstream(ups_peer, b"250-mx.example.net\r\n250 PIPELINING\r\n");
+        let client = async move {
+            clt_peer
+                .write_all(b"EHLO client.example.net\r\n")
+                .await
+                .unwrap();
+            read_until(&mut clt_peer, b"250 PIPELINING\r\n").await;
+            clt_peer
+                .write_all(b"MAIL FROM:<s@example.net>\r\n")
+                .await
+                .unwrap();
+            let mail_reply = read_until(&mut clt_peer, b"\r\n").await;
+            clt_peer.write_all(b"QUIT\r\n").await.unwrap();
+            let mut data = Vec::new();
+            clt_peer.read_to_end(&mut data).await.unwrap();
+            (mail_reply, data)
+        };
+
+        let (r, cmds, (mail_reply, quit_reply)) = tokio::join!(obj.intercept(), upstream, client);
+        assert!(r.unwrap().is_none());
+        assert_eq!(
+            cmds.as_slice(),
+            b"EHLO client.example.net\r\nMAIL FROM:<s@example.net>\r\nQUIT\r\n"
+        );
+        assert_eq!(mail_reply.as_slice(), b"250 sender ok\r\n");
+        assert_eq!(quit_reply.as_slice(), b"221 bye\r\n");
+    }
+
+    #[tokio::test]
+    async fn starttls_require_not_offered() {
+        starttls_aborted("{starttls_policy: require}").await;
+    }
+
+    #[tokio::test]
+    async fn starttls_require_mail_before_starttls() {
+        let (obj, mut clt_peer, ups_peer) = new_smtp_obj("{starttls_policy: require}");
+
+        let upstream = mock_upstream(ups_peer, b"250-mx.example.net\r\n250 STARTTLS\r\n");
+        let client = async move {
+            clt_peer
+                .write_all(b"EHLO client.example.net\r\n")
+                .await
+                .unwrap();
+            let ehlo_reply = read_until(&mut clt_peer, b"250 STARTTLS\r\n").await;
+            clt_peer
+                .write_all(b"MAIL FROM:<s@example.net>\r\n")
+                .await
+                .unwrap();
+            let mail_reply = read_until(&mut clt_peer, b"\r\n").await;
+            clt_peer.write_all(b"QUIT\r\n").await.unwrap();
+            let mut data = Vec::new();
+            clt_peer.read_to_end(&mut data).await.unwrap();
+            (ehlo_reply, mail_reply)
+        };
+
+        let (r, cmds, (ehlo_reply, mail_reply)) = tokio::join!(obj.intercept(), upstream, client);
+        assert!(r.unwrap().is_none());
+        assert_eq!(
+            cmds.as_slice(),
+            b"EHLO client.example.net\r\nQUIT\r\n"
+        );
+        assert!(ehlo_reply.starts_with(b"220 "));
+        assert_eq!(
+            mail_reply.as_slice(),
+            ResponseEncoder::STARTTLS_REQUIRED.as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn starttls_prefer_not_offered() {
+        starttls_passed("{starttls_policy: prefer}").await;
+    }
+
+    #[tokio::test]
+    async fn starttls_ignore_not_offered() {
+        starttls_passed("{starttls_policy: ignore}").await;
+    }
+
+    #[tokio::test]
+    async fn starttls_host_policy() {
+        starttls_aborted("{starttls_policy: {default: ignore, require: example.net}}").await;
+        starttls_passed("{starttls_policy: {default: require, prefer: example.net}}").await;
+        starttls_passed("{starttls_policy: {default: require, ignore: [example.org, example.net]}}")
+            .await;
+    }
+}
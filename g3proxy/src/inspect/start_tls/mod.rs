@@ -33,7 +33,7 @@ use super::{
     TlsInterceptionContext,
 };
 use crate::config::server::ServerConfig;
-use crate::inspect::tls::TlsInterceptionError;
+use crate::inspect::tls::{TlsHandshakeFailureReason, TlsInterceptionError};
 use crate::log::inspect::stream::StreamInspectLog;
 use crate::log::inspect::InspectSource;
 use crate::serve::ServerTaskResult;
@@ -60,6 +60,7 @@ pub(crate) enum StartTlsProtocol {
     Smtp,
     #[allow(unused)]
     Imap,
+    Pop3,
 }
 
 impl From<StartTlsProtocol> for Protocol {
@@ -67,6 +68,7 @@ impl From<StartTlsProtocol> for Protocol {
         match value {
             StartTlsProtocol::Smtp => Protocol::Smtp,
             StartTlsProtocol::Imap => Protocol::Imap,
+            StartTlsProtocol::Pop3 => Protocol::Pop3,
         }
     }
 }
@@ -76,6 +78,7 @@ impl From<StartTlsProtocol> for TlsServiceType {
         match value {
             StartTlsProtocol::Smtp => TlsServiceType::Smtp,
             StartTlsProtocol::Imap => TlsServiceType::Imap,
+            StartTlsProtocol::Pop3 => TlsServiceType::Pop3,
         }
     }
 }
@@ -133,11 +136,14 @@ where
     pub(crate) async fn intercept(mut self) -> ServerTaskResult<StreamInspection<SC>> {
         match self.do_intercept().await {
             Ok(obj) => {
-                intercept_log!(self, "ok");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "ok");
+                }
                 Ok(obj)
             }
             Err(e) => {
                 intercept_log!(self, "{e}");
+                self.tls_interception.stats.add_client_aborted(&e);
                 Err(InterceptionError::StartTls(e).into_server_task_error(Protocol::TlsModern))
             }
         }
@@ -151,6 +157,9 @@ where
             ups_w,
         } = self.io.take().unwrap();
 
+        let stats = self.tls_interception.stats.clone();
+        stats.client.add_attempt();
+
         let ssl = Ssl::new(&self.tls_interception.server_config.ssl_context).map_err(|e| {
             TlsInterceptionError::InternalOpensslServerError(anyhow!(
                 "failed to get new SSL state: {e}"
@@ -168,13 +177,21 @@ where
 
         tokio::time::timeout(accept_timeout, lazy_acceptor.accept())
             .await
-            .map_err(|_| TlsInterceptionError::ClientHandshakeTimeout)?
+            .map_err(|_| {
+                stats.client.add_failed(TlsHandshakeFailureReason::Timeout);
+                TlsInterceptionError::ClientHandshakeTimeout
+            })?
             .map_err(|e| {
+                stats.client.add_failed(TlsHandshakeFailureReason::from(&e));
                 TlsInterceptionError::ClientHandshakeFailed(anyhow!(
                     "read client hello msg failed: {e:?}"
                 ))
             })?;
 
+        // only acquired after the ClientHello has been received, so idle clients won't hold it,
+        // and it will be held until both handshakes are finished
+        let _handshake_permit = self.tls_interception.acquire_handshake_permit().await?;
+
         // build to server ssl context based on client hello
         let sni_hostname = self
             .tls_interception
@@ -183,12 +200,13 @@ where
         if let Some(domain) = sni_hostname {
             // TODO also fetch user-site config here?
             self.upstream.set_host(Host::from(domain));
+            self.ctx.set_tls_server_name(domain.as_ref());
         }
         let alpn_ext = self
             .tls_interception
             .server_config
             .fetch_alpn_extension(lazy_acceptor.ssl());
-        let ups_ssl = match self.ctx.user_site_tls_client() {
+        let mut ups_ssl = match self.ctx.user_site_tls_client() {
             Some(c) => c
                 .build_mimic_ssl(sni_hostname, &self.upstream, alpn_ext)
                 .map_err(|e| {
@@ -206,6 +224,13 @@ where
                     ))
                 })?,
         };
+        self.ctx
+            .set_escaper_tls_client_cert(&mut ups_ssl)
+            .map_err(|e| {
+                TlsInterceptionError::UpstreamPrepareFailed(anyhow!(
+                    "failed to set escaper client cert: {e}"
+                ))
+            })?;
 
         // handshake with upstream server
         let ups_tls_connector =
@@ -214,14 +239,24 @@ where
                     "failed to get ssl stream: {e}"
                 ))
             })?;
+        stats.upstream.add_attempt();
         let ups_tls_stream = tokio::time::timeout(accept_timeout, ups_tls_connector.connect())
             .await
-            .map_err(|_| TlsInterceptionError::UpstreamHandshakeTimeout)?
+            .map_err(|_| {
+                stats
+                    .upstream
+                    .add_failed(TlsHandshakeFailureReason::Timeout);
+                TlsInterceptionError::UpstreamHandshakeTimeout
+            })?
             .map_err(|e| {
+                stats
+                    .upstream
+                    .add_failed(TlsHandshakeFailureReason::from(&e));
                 TlsInterceptionError::UpstreamHandshakeFailed(anyhow!(
                     "upstream handshake error: {e}"
                 ))
             })?;
+        stats.upstream.add_success();
 
         let upstream_cert = ups_tls_stream.ssl().peer_certificate().ok_or_else(|| {
             TlsInterceptionError::NoFakeCertGenerated(anyhow!("failed to get upstream certificate"))
@@ -264,17 +299,44 @@ where
         })?;
         let clt_tls_stream = tokio::time::timeout(accept_timeout, clt_acceptor.accept())
             .await
-            .map_err(|_| TlsInterceptionError::ClientHandshakeTimeout)?
+            .map_err(|_| {
+                stats.client.add_failed(TlsHandshakeFailureReason::Timeout);
+                TlsInterceptionError::ClientHandshakeTimeout
+            })?
             .map_err(|e| {
+                stats.client.add_failed(TlsHandshakeFailureReason::from(&e));
                 TlsInterceptionError::ClientHandshakeFailed(anyhow!(
                     "client handshake error: {e:?}"
                 ))
             })?;
+        stats.client.add_success();
 
         let (clt_r, clt_w) = clt_tls_stream.into_split();
         let (ups_r, ups_w) = ups_tls_stream.into_split();
 
         let protocol = Protocol::from(self.protocol);
+        if let Some(pcap) = self.tls_interception.new_pcap_session(&self.ctx.task_notes) {
+            let (clt_r, clt_w, ups_r, ups_w) = pcap.wrap_io(clt_r, clt_w, ups_r, ups_w);
+            Ok(self.transfer_plaintext(protocol, clt_r, clt_w, ups_r, ups_w))
+        } else {
+            Ok(self.transfer_plaintext(protocol, clt_r, clt_w, ups_r, ups_w))
+        }
+    }
+
+    fn transfer_plaintext<CR, CW, UR, UW>(
+        &self,
+        protocol: Protocol,
+        clt_r: CR,
+        clt_w: CW,
+        ups_r: UR,
+        ups_w: UW,
+    ) -> StreamInspection<SC>
+    where
+        CR: AsyncRead + Send + Unpin + 'static,
+        CW: AsyncWrite + Send + Unpin + 'static,
+        UR: AsyncRead + Send + Unpin + 'static,
+        UW: AsyncWrite + Send + Unpin + 'static,
+    {
         if let Some(stream_dumper) = self
             .tls_interception
             .get_stream_dumper(self.ctx.task_notes.worker_id)
@@ -292,7 +354,7 @@ where
                     clt_r,
                     clt_w,
                 );
-                Ok(self.inspect_inner(protocol, clt_r, clt_w, ups_r, ups_w))
+                self.inspect_inner(protocol, clt_r, clt_w, ups_r, ups_w)
             } else {
                 let (ups_r, ups_w) = stream_dumper.wrap_remote_io(
                     self.ctx.task_notes.client_addr,
@@ -301,10 +363,10 @@ where
                     ups_r,
                     ups_w,
                 );
-                Ok(self.inspect_inner(protocol, clt_r, clt_w, ups_r, ups_w))
+                self.inspect_inner(protocol, clt_r, clt_w, ups_r, ups_w)
             }
         } else {
-            Ok(self.inspect_inner(protocol, clt_r, clt_w, ups_r, ups_w))
+            self.inspect_inner(protocol, clt_r, clt_w, ups_r, ups_w)
         }
     }
 
@@ -349,6 +411,18 @@ where
                     Box::new(ups_w),
                 );
                 StreamInspection::Imap(imap_obj)
+            }
+            StartTlsProtocol::Pop3 => {
+                let mut pop3_obj =
+                    crate::inspect::pop3::Pop3InterceptObject::new(ctx, self.upstream.clone());
+                pop3_obj.set_from_starttls();
+                pop3_obj.set_io(
+                    Box::new(clt_r),
+                    Box::new(clt_w),
+                    OnceBufReader::with_no_buf(Box::new(ups_r)),
+                    Box::new(ups_w),
+                );
+                StreamInspection::Pop3(pop3_obj)
             } /*
               _ => {
                   let mut stream_obj =
@@ -0,0 +1,197 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ahash::AHashMap;
+
+use g3_dpi::ProtocolInspectAction;
+use g3_types::metrics::MetricsName;
+
+static INSPECT_POLICY_STATS_REGISTRY: LazyLock<
+    Mutex<AHashMap<MetricsName, Arc<InspectPolicyStats>>>,
+> = LazyLock::new(|| Mutex::new(AHashMap::new()));
+
+/// The protocols that have an inspect policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InspectPolicyProtocol {
+    H2,
+    Websocket,
+    Smtp,
+    Imap,
+    Pop3,
+}
+
+impl InspectPolicyProtocol {
+    pub(crate) const ALL: [InspectPolicyProtocol; 5] = [
+        InspectPolicyProtocol::H2,
+        InspectPolicyProtocol::Websocket,
+        InspectPolicyProtocol::Smtp,
+        InspectPolicyProtocol::Imap,
+        InspectPolicyProtocol::Pop3,
+    ];
+
+    pub(crate) const fn as_str(&self) -> &'static str {
+        match self {
+            InspectPolicyProtocol::H2 => "h2",
+            InspectPolicyProtocol::Websocket => "websocket",
+            InspectPolicyProtocol::Smtp => "smtp",
+            InspectPolicyProtocol::Imap => "imap",
+            InspectPolicyProtocol::Pop3 => "pop3",
+        }
+    }
+}
+
+#[cfg(feature = "quic")]
+pub(crate) const ALL_INSPECT_DECISIONS: [ProtocolInspectAction; 5] = [
+    ProtocolInspectAction::Block,
+    ProtocolInspectAction::BlockWithResponse,
+    ProtocolInspectAction::Intercept,
+    ProtocolInspectAction::Bypass,
+    ProtocolInspectAction::Detour,
+];
+#[cfg(not(feature = "quic"))]
+pub(crate) const ALL_INSPECT_DECISIONS: [ProtocolInspectAction; 4] = [
+    ProtocolInspectAction::Block,
+    ProtocolInspectAction::BlockWithResponse,
+    ProtocolInspectAction::Intercept,
+    ProtocolInspectAction::Bypass,
+];
+
+const fn decision_index(action: ProtocolInspectAction) -> usize {
+    match action {
+        ProtocolInspectAction::Block => 0,
+        ProtocolInspectAction::BlockWithResponse => 1,
+        ProtocolInspectAction::Intercept => 2,
+        ProtocolInspectAction::Bypass => 3,
+        #[cfg(feature = "quic")]
+        ProtocolInspectAction::Detour => 4,
+    }
+}
+
+/// The inspection depths greater than this will be counted as this one
+const INSPECT_DEPTH_MAX_TAG: usize = 3;
+const INSPECT_DEPTH_SLOTS: usize = INSPECT_DEPTH_MAX_TAG + 1;
+pub(crate) const INSPECT_DEPTH_TAGS: [&str; INSPECT_DEPTH_SLOTS] = ["0", "1", "2", "3"];
+const INSPECT_DECISION_COUNT: usize = ALL_INSPECT_DECISIONS.len();
+const INSPECT_PROTOCOL_COUNT: usize = InspectPolicyProtocol::ALL.len();
+
+pub(crate) type InspectPolicySnapshot =
+    [[[u64; INSPECT_DEPTH_SLOTS]; INSPECT_DECISION_COUNT]; INSPECT_PROTOCOL_COUNT];
+
+/// Inspect policy decision stats of an auditor, which will be kept across reload
+pub(crate) struct InspectPolicyStats {
+    auditor: MetricsName,
+    decisions: [[[AtomicU64; INSPECT_DEPTH_SLOTS]; INSPECT_DECISION_COUNT]; INSPECT_PROTOCOL_COUNT],
+}
+
+impl InspectPolicyStats {
+    pub(crate) fn get_or_insert(auditor: &MetricsName) -> Arc<Self> {
+        let mut ht = INSPECT_POLICY_STATS_REGISTRY.lock().unwrap();
+        ht.entry(auditor.clone())
+            .or_insert_with(|| {
+                Arc::new(InspectPolicyStats {
+                    auditor: auditor.clone(),
+                    decisions: Default::default(),
+                })
+            })
+            .clone()
+    }
+
+    #[inline]
+    pub(crate) fn auditor(&self) -> &MetricsName {
+        &self.auditor
+    }
+
+    pub(crate) fn add_decision(
+        &self,
+        protocol: InspectPolicyProtocol,
+        action: ProtocolInspectAction,
+        depth: usize,
+    ) {
+        let depth = depth.min(INSPECT_DEPTH_MAX_TAG);
+        self.decisions[protocol as usize][decision_index(action)][depth]
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> InspectPolicySnapshot {
+        let mut snap = InspectPolicySnapshot::default();
+        for (p, protocol_stats) in self.decisions.iter().enumerate() {
+            for (d, decision_stats) in protocol_stats.iter().enumerate() {
+                for (depth, v) in decision_stats.iter().enumerate() {
+                    snap[p][d][depth] = v.load(Ordering::Relaxed);
+                }
+            }
+        }
+        snap
+    }
+}
+
+pub(crate) fn foreach_stats<F>(f: F)
+where
+    F: FnMut(&Arc<InspectPolicyStats>),
+{
+    let ht = INSPECT_POLICY_STATS_REGISTRY.lock().unwrap();
+    ht.values().for_each(f);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn decision_index_order() {
+        for (i, action) in ALL_INSPECT_DECISIONS.iter().enumerate() {
+            assert_eq!(decision_index(*action), i);
+        }
+        for (i, protocol) in InspectPolicyProtocol::ALL.iter().enumerate() {
+            assert_eq!(*protocol as usize, i);
+        }
+    }
+
+    #[test]
+    fn add_decision() {
+        let stats =
+            InspectPolicyStats::get_or_insert(&MetricsName::from_str("policy-test").unwrap());
+        stats.add_decision(
+            InspectPolicyProtocol::Smtp,
+            ProtocolInspectAction::Bypass,
+            0,
+        );
+        stats.add_decision(
+            InspectPolicyProtocol::Websocket,
+            ProtocolInspectAction::Block,
+            1,
+        );
+        stats.add_decision(
+            InspectPolicyProtocol::Websocket,
+            ProtocolInspectAction::Block,
+            8,
+        );
+
+        let snap = stats.snapshot();
+        let smtp = InspectPolicyProtocol::Smtp as usize;
+        let websocket = InspectPolicyProtocol::Websocket as usize;
+        let block = decision_index(ProtocolInspectAction::Block);
+        let bypass = decision_index(ProtocolInspectAction::Bypass);
+        assert_eq!(snap[smtp][bypass][0], 1);
+        assert_eq!(snap[websocket][block][1], 1);
+        assert_eq!(snap[websocket][block][INSPECT_DEPTH_MAX_TAG], 1);
+        assert_eq!(snap.iter().flatten().flatten().sum::<u64>(), 3);
+    }
+}
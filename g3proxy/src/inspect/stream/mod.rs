@@ -15,8 +15,10 @@
  */
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use tokio::io::{AsyncRead, AsyncWrite};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::time::Instant;
 
 use g3_daemon::server::ServerQuitPolicy;
@@ -32,6 +34,11 @@ use crate::serve::{ServerTaskError, ServerTaskForbiddenError, ServerTaskResult};
 mod object;
 pub(crate) use object::StreamInspectObject;
 
+/// the max time to flush and close the streams when the task lifetime is exceeded,
+/// as the peers may have stopped reading
+const LIFETIME_CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn transit_transparent<CR, CW, UR, UW, SC>(
     mut clt_r: CR,
     mut clt_w: CW,
@@ -39,6 +46,7 @@ pub(crate) async fn transit_transparent<CR, CW, UR, UW, SC>(
     mut ups_w: UW,
     server_config: &Arc<SC>,
     server_quit_policy: &Arc<ServerQuitPolicy>,
+    task_deadline: Option<Instant>,
     user: Option<&Arc<User>>,
 ) -> ServerTaskResult<()>
 where
@@ -48,18 +56,33 @@ where
     UW: AsyncWrite + Unpin,
     SC: ServerConfig,
 {
+    let max_idle_count = user
+        .map(|user| user.task_max_idle_count())
+        .unwrap_or_else(|| server_config.task_max_idle_count());
+
     let copy_config = server_config.limited_copy_config();
     let clt_to_ups = LimitedCopy::new(&mut clt_r, &mut ups_w, &copy_config);
     let ups_to_clt = LimitedCopy::new(&mut ups_r, &mut clt_w, &copy_config);
 
-    transit_transparent2(
+    let r = transit_transparent2(
         clt_to_ups,
         ups_to_clt,
         server_config,
         server_quit_policy,
+        task_deadline,
         user,
+        max_idle_count,
     )
-    .await
+    .await;
+    if matches!(r, Err(ServerTaskError::CanceledAsLifetimeExceeded)) {
+        // send FIN to both sides so the peers know that the stream has been finished
+        let _ = tokio::time::timeout(LIFETIME_CLOSE_TIMEOUT, async {
+            let _ = ups_w.shutdown().await;
+            let _ = clt_w.shutdown().await;
+        })
+        .await;
+    }
+    r
 }
 
 pub(crate) async fn transit_transparent2<'a, CR, CW, UR, UW, SC>(
@@ -67,7 +90,9 @@ pub(crate) async fn transit_transparent2<'a, CR, CW, UR, UW, SC>(
     mut ups_to_clt: LimitedCopy<'a, UR, CW>,
     server_config: &'a Arc<SC>,
     server_quit_policy: &'a Arc<ServerQuitPolicy>,
+    task_deadline: Option<Instant>,
     user: Option<&'a Arc<User>>,
+    max_idle_count: i32,
 ) -> ServerTaskResult<()>
 where
     CR: AsyncRead + Unpin,
@@ -79,40 +104,106 @@ where
     let idle_duration = server_config.task_idle_check_duration();
     let mut idle_interval = tokio::time::interval_at(Instant::now() + idle_duration, idle_duration);
     let mut idle_count = 0;
+    // the branch will be disabled if no deadline is set, so the fallback value is never used
+    let lifetime_sleep = tokio::time::sleep_until(task_deadline.unwrap_or_else(Instant::now));
+    tokio::pin!(lifetime_sleep);
+
+    let half_close_linger_timeout = server_config.tcp_half_close_linger_timeout();
+    // the error to return when the relay finished, set when the first direction is half-closed
+    let mut half_closed: Option<ServerTaskError> = None;
+    let mut clt_to_ups_done = false;
+    let mut ups_to_clt_done = false;
+    // will be reset when the first direction is half-closed
+    let linger_sleep = tokio::time::sleep(Duration::ZERO);
+    tokio::pin!(linger_sleep);
     loop {
         tokio::select! {
             biased;
 
-            r = &mut clt_to_ups => {
-                let _ = ups_to_clt.write_flush().await;
-                return match r {
-                    Ok(_) => Err(ServerTaskError::ClosedByClient),
-                    Err(LimitedCopyError::ReadFailed(e)) => Err(ServerTaskError::ClientTcpReadFailed(e)),
-                    Err(LimitedCopyError::WriteFailed(e)) => Err(ServerTaskError::UpstreamWriteFailed(e)),
-                };
+            r = &mut clt_to_ups, if !clt_to_ups_done => {
+                match r {
+                    Ok(_) => {
+                        clt_to_ups_done = true;
+                        if let Some(e) = half_closed.take() {
+                            let _ = clt_to_ups.writer().shutdown().await;
+                            return Err(e);
+                        }
+                        let Some(timeout) = half_close_linger_timeout else {
+                            let _ = ups_to_clt.write_flush().await;
+                            return Err(ServerTaskError::ClosedByClient);
+                        };
+                        // propagate the FIN to upstream and keep relaying the other direction
+                        if let Err(e) = clt_to_ups.writer().shutdown().await {
+                            let _ = ups_to_clt.write_flush().await;
+                            return Err(ServerTaskError::UpstreamWriteFailed(e));
+                        }
+                        half_closed = Some(ServerTaskError::ClosedByClient);
+                        linger_sleep.as_mut().reset(Instant::now() + timeout);
+                    }
+                    Err(LimitedCopyError::ReadFailed(e)) => {
+                        let _ = ups_to_clt.write_flush().await;
+                        return Err(ServerTaskError::ClientTcpReadFailed(e));
+                    }
+                    Err(LimitedCopyError::WriteFailed(e)) => {
+                        let _ = ups_to_clt.write_flush().await;
+                        return Err(ServerTaskError::UpstreamWriteFailed(e));
+                    }
+                }
             }
-            r = &mut ups_to_clt => {
+            r = &mut ups_to_clt, if !ups_to_clt_done => {
+                match r {
+                    Ok(_) => {
+                        ups_to_clt_done = true;
+                        if let Some(e) = half_closed.take() {
+                            let _ = ups_to_clt.writer().shutdown().await;
+                            return Err(e);
+                        }
+                        let Some(timeout) = half_close_linger_timeout else {
+                            let _ = clt_to_ups.write_flush().await;
+                            return Err(ServerTaskError::ClosedByUpstream);
+                        };
+                        // propagate the FIN to client and keep relaying the other direction
+                        if let Err(e) = ups_to_clt.writer().shutdown().await {
+                            let _ = clt_to_ups.write_flush().await;
+                            return Err(ServerTaskError::ClientTcpWriteFailed(e));
+                        }
+                        half_closed = Some(ServerTaskError::ClosedByUpstream);
+                        linger_sleep.as_mut().reset(Instant::now() + timeout);
+                    }
+                    Err(LimitedCopyError::ReadFailed(e)) => {
+                        let _ = clt_to_ups.write_flush().await;
+                        return Err(ServerTaskError::UpstreamReadFailed(e));
+                    }
+                    Err(LimitedCopyError::WriteFailed(e)) => {
+                        let _ = clt_to_ups.write_flush().await;
+                        return Err(ServerTaskError::ClientTcpWriteFailed(e));
+                    }
+                }
+            }
+            _ = &mut linger_sleep, if half_closed.is_some() => {
                 let _ = clt_to_ups.write_flush().await;
-                return match r {
-                    Ok(_) => Err(ServerTaskError::ClosedByUpstream),
-                    Err(LimitedCopyError::ReadFailed(e)) => Err(ServerTaskError::UpstreamReadFailed(e)),
-                    Err(LimitedCopyError::WriteFailed(e)) => Err(ServerTaskError::ClientTcpWriteFailed(e)),
-                };
+                let _ = ups_to_clt.write_flush().await;
+                return Err(half_closed.take().unwrap());
+            }
+            _ = &mut lifetime_sleep, if task_deadline.is_some() => {
+                let _ = tokio::time::timeout(LIFETIME_CLOSE_TIMEOUT, async {
+                    let _ = clt_to_ups.write_flush().await;
+                    let _ = ups_to_clt.write_flush().await;
+                })
+                .await;
+                return Err(ServerTaskError::CanceledAsLifetimeExceeded);
             }
             _ = idle_interval.tick() => {
                 if clt_to_ups.is_idle() && ups_to_clt.is_idle() {
                     idle_count += 1;
 
-                    let quit = if let Some(user) = user {
+                    if let Some(user) = user {
                         if user.is_blocked() {
                             return Err(ServerTaskError::CanceledAsUserBlocked);
                         }
-                        idle_count >= user.task_max_idle_count()
-                    } else {
-                        idle_count >= server_config.task_max_idle_count()
-                    };
+                    }
 
-                    if quit {
+                    if idle_count >= max_idle_count {
                         return Err(ServerTaskError::Idle(idle_duration, idle_count));
                     }
                 } else {
@@ -166,6 +257,41 @@ where
         .await
 }
 
+/// Inspect a SMTP session whose local greeting has been done before the upstream connection.
+///
+/// The `client_hello` data, which starts with the client EHLO / HELO command, will be replayed to
+/// the upstream MTA.
+pub(crate) async fn transit_with_smtp_greeting<CR, CW, UR, UW, SC>(
+    clt_r: CR,
+    clt_w: CW,
+    ups_r: UR,
+    ups_w: UW,
+    ctx: StreamInspectContext<SC>,
+    upstream: UpstreamAddr,
+    client_hello: Bytes,
+) -> ServerTaskResult<()>
+where
+    CR: AsyncRead + Send + Sync + Unpin + 'static,
+    CW: AsyncWrite + Send + Sync + Unpin + 'static,
+    UR: AsyncRead + Send + Sync + Unpin + 'static,
+    UW: AsyncWrite + Send + Sync + Unpin + 'static,
+    SC: ServerConfig + Send + Sync + 'static,
+{
+    let inspector = ctx.protocol_inspector(None);
+
+    let mut obj = StreamInspectObject::new(ctx, upstream);
+    obj.set_io(
+        Box::new(clt_r),
+        Box::new(clt_w),
+        Box::new(ups_r),
+        Box::new(ups_w),
+    );
+    obj.set_smtp_client_hello(client_hello);
+    StreamInspection::StreamInspect(obj)
+        .into_loop_inspection(inspector)
+        .await
+}
+
 impl<SC> StreamInspectContext<SC>
 where
     SC: ServerConfig + Send + Sync + 'static,
@@ -229,13 +355,16 @@ where
 
         self.transit_transparent(clt_r, clt_w, ups_r, ups_w).await
     }
+}
 
+impl<SC: ServerConfig> StreamInspectContext<SC> {
+    /// Transit the stream transparently, with the idle timeout set for the identified protocol
     pub(super) async fn transit_transparent<CR, CW, UR, UW>(
         &self,
-        clt_r: CR,
-        clt_w: CW,
-        ups_r: UR,
-        ups_w: UW,
+        mut clt_r: CR,
+        mut clt_w: CW,
+        mut ups_r: UR,
+        mut ups_w: UW,
     ) -> ServerTaskResult<()>
     where
         CR: AsyncRead + Unpin,
@@ -243,16 +372,26 @@ where
         UR: AsyncRead + Unpin,
         UW: AsyncWrite + Unpin,
     {
-        transit_transparent(
-            clt_r,
-            clt_w,
-            ups_r,
-            ups_w,
+        let copy_config = self.server_config.limited_copy_config();
+        let clt_to_ups = LimitedCopy::new(&mut clt_r, &mut ups_w, &copy_config);
+        let ups_to_clt = LimitedCopy::new(&mut ups_r, &mut clt_w, &copy_config);
+
+        let r = transit_transparent2(
+            clt_to_ups,
+            ups_to_clt,
             &self.server_config,
             &self.server_quit_policy,
+            self.task_deadline(),
             self.user(),
+            self.task_max_idle_count(),
         )
-        .await
+        .await;
+        if matches!(r, Err(ServerTaskError::CanceledAsLifetimeExceeded)) {
+            // send FIN to both sides so the peers know that the stream has been finished
+            let _ = ups_w.shutdown().await;
+            let _ = clt_w.shutdown().await;
+        }
+        r
     }
 }
 
@@ -273,7 +412,10 @@ where
                 }
                 StreamInspection::StreamInspect(stream) => {
                     if stream.ctx.skip_next_inspection() {
-                        return stream.transit_unknown().await;
+                        return match stream.ctx.protocol_inspection().max_depth_action() {
+                            Some(action) => stream.stop_on_depth_limit(action).await,
+                            None => stream.transit_unknown().await,
+                        };
                     }
 
                     obj = stream.transit_with_inspection(&mut inspector).await?;
@@ -322,6 +464,13 @@ where
                     }
                     None => break,
                 },
+                StreamInspection::Pop3(pop3) => match pop3.intercept().await? {
+                    Some(new_obj) => {
+                        obj = new_obj;
+                        // no need to reset inspector state as the protocol should be known
+                    }
+                    None => break,
+                },
                 StreamInspection::End => break,
             }
         }
@@ -329,3 +478,250 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use tokio::io::AsyncReadExt;
+    use yaml_rust::YamlLoader;
+
+    use g3_daemon::server::ClientConnectionInfo;
+    use g3_types::metrics::MetricsName;
+
+    use crate::audit::Auditor;
+    use crate::config::audit::AuditorConfig;
+    use crate::config::server::dummy_close::DummyCloseServerConfig;
+    use crate::config::server::tcp_stream::TcpStreamServerConfig;
+    use crate::module::tcp_connect::TcpConnectTaskNotes;
+    use crate::serve::{ServerTaskNotes, TcpStreamServerStats};
+
+    fn spawn_inspect(
+        protocol_inspection: &str,
+        clt_io: tokio::io::DuplexStream,
+        ups_io: tokio::io::DuplexStream,
+    ) -> tokio::task::JoinHandle<ServerTaskResult<()>> {
+        let yaml = YamlLoader::load_from_str(&format!(
+            "{{name: test, protocol_inspection: {protocol_inspection}}}"
+        ))
+        .unwrap();
+        let mut auditor_config = AuditorConfig::new(None);
+        auditor_config.parse(yaml[0].as_hash().unwrap()).unwrap();
+
+        let name = MetricsName::from_str("test").unwrap();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080);
+        let upstream = UpstreamAddr::from_str("example.net:8080").unwrap();
+        let task_notes =
+            ServerTaskNotes::new(ClientConnectionInfo::new(addr, addr), None, Duration::ZERO);
+        let ctx = StreamInspectContext::new(
+            Auditor::build_test_handle(auditor_config),
+            Arc::new(DummyCloseServerConfig::new(&name, None)),
+            Arc::new(TcpStreamServerStats::new(&name)),
+            Arc::new(ServerQuitPolicy::default()),
+            &task_notes,
+            &TcpConnectTaskNotes::new(upstream.clone()),
+        );
+
+        let (clt_r, clt_w) = tokio::io::split(clt_io);
+        let (ups_r, ups_w) = tokio::io::split(ups_io);
+        tokio::spawn(transit_with_inspection(
+            clt_r, clt_w, ups_r, ups_w, ctx, upstream, None,
+        ))
+    }
+
+    #[tokio::test]
+    async fn max_depth_block() {
+        let (_clt_peer, clt_io) = tokio::io::duplex(1024);
+        let (ups_io, _ups_peer) = tokio::io::duplex(1024);
+
+        let task = spawn_inspect(
+            "{inspect_max_depth: 0, inspect_max_depth_action: block}",
+            clt_io,
+            ups_io,
+        );
+        let r = task.await.unwrap();
+        assert!(matches!(
+            r,
+            Err(ServerTaskError::InspectionDepthExceeded(0))
+        ));
+    }
+
+    #[tokio::test]
+    async fn max_depth_bypass() {
+        let (mut clt_peer, clt_io) = tokio::io::duplex(1024);
+        let (ups_io, mut ups_peer) = tokio::io::duplex(1024);
+
+        let task = spawn_inspect(
+            "{inspect_max_depth: 0, inspect_max_depth_action: bypass}",
+            clt_io,
+            ups_io,
+        );
+
+        // no protocol inspection on the data, which looks like HTTP
+        clt_peer.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        clt_peer.shutdown().await.unwrap();
+
+        let r = task.await.unwrap();
+        assert!(matches!(r, Err(ServerTaskError::ClosedByClient)));
+
+        let mut buf = Vec::new();
+        ups_peer.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), b"GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn max_depth_invalid_action() {
+        let yaml = YamlLoader::load_from_str(
+            "{name: test, protocol_inspection: {inspect_max_depth_action: intercept}}",
+        )
+        .unwrap();
+        let mut auditor_config = AuditorConfig::new(None);
+        assert!(auditor_config.parse(yaml[0].as_hash().unwrap()).is_err());
+    }
+
+    fn spawn_relay<SC>(
+        server_config: SC,
+        clt_io: tokio::io::DuplexStream,
+        ups_io: tokio::io::DuplexStream,
+        task_deadline: Option<Instant>,
+    ) -> tokio::task::JoinHandle<ServerTaskResult<()>>
+    where
+        SC: ServerConfig + Send + Sync + 'static,
+    {
+        tokio::spawn(async move {
+            let server_config = Arc::new(server_config);
+            let server_quit_policy = Arc::new(ServerQuitPolicy::default());
+            let (clt_r, clt_w) = tokio::io::split(clt_io);
+            let (ups_r, ups_w) = tokio::io::split(ups_io);
+            transit_transparent(
+                clt_r,
+                clt_w,
+                ups_r,
+                ups_w,
+                &server_config,
+                &server_quit_policy,
+                task_deadline,
+                None,
+            )
+            .await
+        })
+    }
+
+    #[tokio::test]
+    async fn client_half_close() {
+        let (mut clt_peer, clt_io) = tokio::io::duplex(1024);
+        let (ups_io, mut ups_peer) = tokio::io::duplex(1024);
+
+        let yaml = YamlLoader::load_from_str(
+            "{name: test, escaper: default, upstream: '127.0.0.1:80', tcp_half_close_linger_timeout: 60s}",
+        )
+        .unwrap();
+        let server_config = TcpStreamServerConfig::parse(yaml[0].as_hash().unwrap(), None).unwrap();
+        let relay = spawn_relay(server_config, clt_io, ups_io, None);
+
+        clt_peer.write_all(b"ping").await.unwrap();
+        clt_peer.shutdown().await.unwrap();
+
+        // the FIN should be propagated to upstream
+        let mut buf = Vec::new();
+        ups_peer.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), b"ping");
+
+        // the other direction should be kept
+        ups_peer.write_all(b"pong").await.unwrap();
+        ups_peer.shutdown().await.unwrap();
+
+        let mut buf = Vec::new();
+        clt_peer.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), b"pong");
+
+        let r = relay.await.unwrap();
+        assert!(matches!(r, Err(ServerTaskError::ClosedByClient)));
+    }
+
+    #[tokio::test]
+    async fn client_close_without_linger() {
+        let (mut clt_peer, clt_io) = tokio::io::duplex(1024);
+        let (ups_io, mut ups_peer) = tokio::io::duplex(1024);
+
+        // half close is disabled by default
+        let server_config =
+            DummyCloseServerConfig::new(&MetricsName::from_str("test").unwrap(), None);
+        let relay = spawn_relay(server_config, clt_io, ups_io, None);
+
+        clt_peer.write_all(b"ping").await.unwrap();
+        clt_peer.shutdown().await.unwrap();
+
+        let r = relay.await.unwrap();
+        assert!(matches!(r, Err(ServerTaskError::ClosedByClient)));
+
+        let mut buf = Vec::new();
+        ups_peer.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), b"ping");
+    }
+
+    #[tokio::test]
+    async fn cancel_at_deadline() {
+        let (mut clt_peer, clt_io) = tokio::io::duplex(1024);
+        let (ups_io, mut ups_peer) = tokio::io::duplex(1024);
+
+        let server_config =
+            DummyCloseServerConfig::new(&MetricsName::from_str("test").unwrap(), None);
+        let lifetime = Duration::from_millis(200);
+        let start = Instant::now();
+        let relay = spawn_relay(server_config, clt_io, ups_io, Some(start + lifetime));
+
+        // the task is active but should still be canceled
+        clt_peer.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        ups_peer.read_exact(&mut buf).await.unwrap();
+
+        let r = tokio::time::timeout(Duration::from_secs(2), relay)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            r,
+            Err(ServerTaskError::CanceledAsLifetimeExceeded)
+        ));
+        assert!(start.elapsed() >= lifetime);
+
+        // FIN should be sent to both sides
+        let mut buf = Vec::new();
+        ups_peer.read_to_end(&mut buf).await.unwrap();
+        assert!(buf.is_empty());
+        clt_peer.read_to_end(&mut buf).await.unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancel_at_deadline_with_stalled_peer() {
+        let (mut clt_peer, clt_io) = tokio::io::duplex(1024);
+        // the upstream peer never reads, so the pending data can never be flushed
+        let (ups_io, _ups_peer) = tokio::io::duplex(16);
+
+        let server_config =
+            DummyCloseServerConfig::new(&MetricsName::from_str("test").unwrap(), None);
+        let lifetime = Duration::from_millis(100);
+        let relay = spawn_relay(
+            server_config,
+            clt_io,
+            ups_io,
+            Some(Instant::now() + lifetime),
+        );
+
+        clt_peer.write_all(&[b'a'; 256]).await.unwrap();
+
+        let r = tokio::time::timeout(lifetime + LIFETIME_CLOSE_TIMEOUT * 3, relay)
+            .await
+            .expect("the relay should not be blocked by the stalled peer")
+            .unwrap();
+        assert!(matches!(
+            r,
+            Err(ServerTaskError::CanceledAsLifetimeExceeded)
+        ));
+    }
+}
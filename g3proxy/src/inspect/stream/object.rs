@@ -14,18 +14,21 @@
  * limitations under the License.
  */
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
+use slog::slog_info;
 use tokio::io::{AsyncRead, AsyncReadExt};
 
-use g3_dpi::{Protocol, ProtocolInspectError, ProtocolInspector};
+use g3_dpi::parser::tls::{ClientHelloPeek, ClientHelloPeekError, ClientHelloPeeker};
+use g3_dpi::{Protocol, ProtocolInspectAction, ProtocolInspectError, ProtocolInspector};
 use g3_io_ext::{FlexBufReader, OnceBufReader};
+use g3_slog_types::{LtUpstreamAddr, LtUuid};
 use g3_types::net::UpstreamAddr;
 
 use crate::config::server::ServerConfig;
 use crate::inspect::{BoxAsyncRead, BoxAsyncWrite, StreamInspectContext, StreamInspection};
 use crate::log::inspect::stream::StreamInspectLog;
 use crate::log::inspect::InspectSource;
-use crate::serve::{ServerTaskError, ServerTaskResult};
+use crate::serve::{ServerTaskError, ServerTaskForbiddenError, ServerTaskResult};
 
 enum InitialDataSource {
     Client,
@@ -43,6 +46,7 @@ pub(crate) struct StreamInspectObject<SC: ServerConfig> {
     io: Option<StreamInspectIo>,
     pub(super) ctx: StreamInspectContext<SC>,
     upstream: UpstreamAddr,
+    smtp_client_hello: Option<Bytes>,
 }
 
 impl<SC> StreamInspectObject<SC>
@@ -54,9 +58,16 @@ where
             io: None,
             ctx,
             upstream,
+            smtp_client_hello: None,
         }
     }
 
+    /// The local SMTP greeting has been done before the upstream connection, and the held client
+    /// data, which starts with the EHLO / HELO command, should be replayed to upstream.
+    pub(super) fn set_smtp_client_hello(&mut self, data: Bytes) {
+        self.smtp_client_hello = Some(data);
+    }
+
     pub(crate) fn set_io(
         &mut self,
         clt_r: BoxAsyncRead,
@@ -84,17 +95,73 @@ where
         self.ctx.transit_unknown(clt_r, clt_w, ups_r, ups_w).await
     }
 
+    pub(super) async fn stop_on_depth_limit(
+        mut self,
+        action: ProtocolInspectAction,
+    ) -> ServerTaskResult<()> {
+        let max_depth = self.ctx.protocol_inspection().max_depth();
+        let dry_run = action.is_block() && self.ctx.audit_handle.inspect_dry_run();
+        slog_info!(self.ctx.intercept_logger(), "inspection depth limit reached";
+            "intercept_type" => "StreamInspect",
+            "task_id" => LtUuid(self.ctx.server_task_id()),
+            "depth" => self.ctx.inspection_depth,
+            "upstream" => LtUpstreamAddr(&self.upstream),
+            "max_depth" => max_depth,
+            "action" => action.as_str(),
+            "dry_run" => dry_run,
+        );
+
+        if action.is_block() && !dry_run {
+            return Err(ServerTaskError::InspectionDepthExceeded(max_depth));
+        }
+
+        let StreamInspectIo {
+            clt_r,
+            clt_w,
+            ups_r,
+            ups_w,
+        } = self.io.take().unwrap();
+        self.ctx
+            .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+            .await
+    }
+
     pub(super) async fn transit_with_inspection(
         mut self,
         inspector: &mut ProtocolInspector,
     ) -> ServerTaskResult<StreamInspection<SC>> {
         let StreamInspectIo {
             mut clt_r,
-            clt_w,
+            mut clt_w,
             mut ups_r,
             ups_w,
         } = self.io.take().unwrap();
 
+        let smtp_client_hello = self.smtp_client_hello.take();
+        let smtp_upstream_proxy_protocol =
+            self.ctx.smtp_upstream_proxy_protocol(self.upstream.port());
+        if smtp_client_hello.is_some() || smtp_upstream_proxy_protocol.is_some() {
+            // the upstream MTA won't send the greeting before receiving the PROXY protocol header,
+            // which will be sent only if the SMTP session is going to be intercepted
+            self.ctx.increase_inspection_depth();
+            StreamInspectLog::new(&self.ctx).log(InspectSource::StreamInspection, Protocol::Smtp);
+            self.ctx.set_protocol_tcp_no_delay(Protocol::Smtp);
+            let mut smtp_obj =
+                crate::inspect::smtp::SmtpInterceptObject::new(self.ctx, self.upstream.clone());
+            if let Some(version) = smtp_upstream_proxy_protocol {
+                smtp_obj.set_upstream_proxy_protocol(version);
+            }
+            let clt_r = match smtp_client_hello {
+                Some(data) => {
+                    smtp_obj.set_greeting_sent();
+                    Box::new(OnceBufReader::with_bytes(clt_r, data))
+                }
+                None => clt_r,
+            };
+            smtp_obj.set_io(clt_r, clt_w, OnceBufReader::with_no_buf(ups_r), ups_w);
+            return Ok(StreamInspection::Smtp(smtp_obj));
+        }
+
         let inspect_buffer_size = self.ctx.protocol_inspection().data0_buffer_size();
         let mut clt_r_buf = BytesMut::with_capacity(inspect_buffer_size);
         let mut ups_r_buf = BytesMut::with_capacity(inspect_buffer_size);
@@ -134,6 +201,7 @@ where
 
         self.ctx.increase_inspection_depth();
         StreamInspectLog::new(&self.ctx).log(InspectSource::StreamInspection, protocol);
+        self.ctx.set_protocol_tcp_no_delay(protocol);
         match protocol {
             Protocol::Unknown => {
                 self.ctx
@@ -167,6 +235,17 @@ where
                     tls_obj.set_io(OnceBufReader::new(clt_r, clt_r_buf), clt_w, ups_r, ups_w);
                     return Ok(StreamInspection::TlsModern(tls_obj));
                 }
+                if let Some(max_size) = self.ctx.tls_sni_peek() {
+                    peek_tls_client_hello(
+                        &mut self.ctx,
+                        &self.upstream,
+                        &mut clt_r,
+                        &mut clt_w,
+                        &mut clt_r_buf,
+                        max_size,
+                    )
+                    .await?;
+                }
             }
             #[cfg(feature = "vendored-tongsuo")]
             Protocol::TlsTlcp => {
@@ -179,6 +258,17 @@ where
                     tls_obj.set_io(OnceBufReader::new(clt_r, clt_r_buf), clt_w, ups_r, ups_w);
                     return Ok(StreamInspection::TlsTlcp(tls_obj));
                 }
+                if let Some(max_size) = self.ctx.tls_sni_peek() {
+                    peek_tls_client_hello(
+                        &mut self.ctx,
+                        &self.upstream,
+                        &mut clt_r,
+                        &mut clt_w,
+                        &mut clt_r_buf,
+                        max_size,
+                    )
+                    .await?;
+                }
             }
             Protocol::Http1 => {
                 let mut h1_obj = crate::inspect::http::H1InterceptObject::new(self.ctx);
@@ -208,9 +298,16 @@ where
                 imap_obj.set_io(clt_r, clt_w, OnceBufReader::new(ups_r, ups_r_buf), ups_w);
                 return Ok(StreamInspection::Imap(imap_obj));
             }
+            Protocol::Pop3 => {
+                let mut pop3_obj =
+                    crate::inspect::pop3::Pop3InterceptObject::new(self.ctx, self.upstream.clone());
+                pop3_obj.set_io(clt_r, clt_w, OnceBufReader::new(ups_r, ups_r_buf), ups_w);
+                return Ok(StreamInspection::Pop3(pop3_obj));
+            }
             _ => {}
         }
 
+        self.ctx.set_protocol_idle_timeout(protocol);
         self.ctx
             .transit_transparent(
                 OnceBufReader::new(clt_r, clt_r_buf),
@@ -326,3 +423,97 @@ where
         }
     }
 }
+
+/// Peek the ClientHello message of a TLS stream that won't be intercepted.
+///
+/// The SNI will be saved in the task notes and checked against the SNI filter if set.
+async fn peek_tls_client_hello<SC>(
+    ctx: &mut StreamInspectContext<SC>,
+    upstream: &UpstreamAddr,
+    clt_r: &mut BoxAsyncRead,
+    clt_w: &mut BoxAsyncWrite,
+    clt_r_buf: &mut BytesMut,
+    max_size: u32,
+) -> ServerTaskResult<()>
+where
+    SC: ServerConfig + Send + Sync + 'static,
+{
+    let (peek, peek_error) = match tokio::time::timeout(
+        ctx.protocol_inspection().data0_read_timeout(),
+        read_tls_client_hello(clt_r, clt_r_buf, max_size),
+    )
+    .await
+    {
+        Ok(Ok(Ok(peek))) => (Some(peek), None),
+        Ok(Ok(Err(e))) => (None, Some(e.to_string())),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => (None, Some("timeout".to_string())),
+    };
+
+    let sni = peek.as_ref().and_then(|p| p.server_name());
+    let alpn = peek.as_ref().and_then(|p| p.alpn()).map(|alpn| {
+        alpn.protocol_names()
+            .map(String::from_utf8_lossy)
+            .collect::<Vec<_>>()
+            .join(",")
+    });
+    let sni_filter = ctx.tls_sni_filter().cloned();
+    let sni_denied = match (&sni_filter, &peek) {
+        (Some(filter), Some(_)) => !filter.allow(sni),
+        _ => false,
+    };
+    slog_info!(ctx.intercept_logger(), "tls client hello peeked";
+        "intercept_type" => "TlsPeek",
+        "task_id" => LtUuid(ctx.server_task_id()),
+        "depth" => ctx.inspection_depth,
+        "upstream" => LtUpstreamAddr(upstream),
+        "sni" => sni.map(|v| v.as_ref()),
+        "alpn" => alpn,
+        "sni_denied" => sni_denied,
+        "peek_error" => peek_error,
+    );
+
+    if let Some(sni) = sni {
+        ctx.set_tls_server_name(sni.as_ref());
+    }
+    if sni_denied {
+        let reason = match sni {
+            Some(sni) => format!("tls server name {sni} not allowed"),
+            None => "tls connection without server name not allowed".to_string(),
+        };
+        if ctx.skip_block_in_dry_run("tls", &reason) {
+            return Ok(());
+        }
+        if let Some(filter) = sni_filter {
+            filter.deny_by_alert(&mut *clt_w).await;
+        }
+        ctx.tarpit_blocked(clt_w).await;
+        return Err(ServerTaskError::ForbiddenByRule(
+            ServerTaskForbiddenError::DestDenied,
+        ));
+    }
+    Ok(())
+}
+
+/// Read until the full ClientHello message is buffered, the buffered data won't be consumed
+async fn read_tls_client_hello<R>(
+    clt_r: &mut R,
+    clt_r_buf: &mut BytesMut,
+    max_size: u32,
+) -> ServerTaskResult<Result<ClientHelloPeek, ClientHelloPeekError>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut peeker = ClientHelloPeeker::new(max_size);
+    loop {
+        match peeker.peek(clt_r_buf.chunk()) {
+            Ok(Some(peek)) => return Ok(Ok(peek)),
+            Ok(None) => match clt_r.read_buf(clt_r_buf).await {
+                Ok(0) => return Err(ServerTaskError::ClosedByClient),
+                Ok(_) => {}
+                Err(e) => return Err(ServerTaskError::ClientTcpReadFailed(e)),
+            },
+            Err(e) => return Ok(Err(e)),
+        }
+    }
+}
@@ -32,4 +32,14 @@ pub(crate) enum TlsInterceptionError {
     UpstreamHandshakeFailed(anyhow::Error),
     #[error("no fake cert generated: {0:?}")]
     NoFakeCertGenerated(anyhow::Error),
+    #[error("handshake queue full")]
+    HandshakeQueueFull,
+    #[error("handshake queue timeout")]
+    HandshakeQueueTimeout,
+    #[error("tls server name {0} is not allowed")]
+    SniNotAllowed(String),
+    #[error("tls connection without server name is not allowed")]
+    NoSniNotAllowed,
+    #[error("tls client cert subject {0} is not allowed")]
+    ClientSubjectNotAllowed(String),
 }
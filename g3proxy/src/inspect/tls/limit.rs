@@ -0,0 +1,128 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{TlsHandshakeLimitStats, TlsInterceptionError};
+use crate::config::audit::TlsHandshakeLimitConfig;
+
+/// Limit the concurrent in-progress TLS interception handshakes of an auditor.
+///
+/// New handshakes will wait in the queue if the concurrency limit is reached,
+/// and will be shed if the queue is full or the queue timeout is reached.
+pub(crate) struct TlsHandshakeLimiter {
+    config: TlsHandshakeLimitConfig,
+    semaphore: Arc<Semaphore>,
+    queued: AtomicUsize,
+}
+
+struct QueuedGuard<'a> {
+    queued: &'a AtomicUsize,
+    stats: &'a TlsHandshakeLimitStats,
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        self.stats.del_queued();
+    }
+}
+
+impl TlsHandshakeLimiter {
+    pub(crate) fn new(config: TlsHandshakeLimitConfig) -> Self {
+        TlsHandshakeLimiter {
+            config,
+            semaphore: Arc::new(Semaphore::new(config.max_concurrent)),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn config(&self) -> &TlsHandshakeLimitConfig {
+        &self.config
+    }
+
+    pub(super) async fn acquire(
+        &self,
+        stats: &TlsHandshakeLimitStats,
+    ) -> Result<OwnedSemaphorePermit, TlsInterceptionError> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        if self.queued.fetch_add(1, Ordering::Relaxed) >= self.config.max_queued {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            stats.add_shed_queue_full();
+            return Err(TlsInterceptionError::HandshakeQueueFull);
+        }
+        stats.add_queued();
+        let _guard = QueuedGuard {
+            queued: &self.queued,
+            stats,
+        };
+
+        match tokio::time::timeout(
+            self.config.queue_timeout,
+            self.semaphore.clone().acquire_owned(),
+        )
+        .await
+        {
+            Ok(Ok(permit)) => Ok(permit),
+            // the semaphore is never closed
+            Ok(Err(_)) | Err(_) => {
+                stats.add_shed_queue_timeout();
+                Err(TlsInterceptionError::HandshakeQueueTimeout)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn acquire() {
+        let limiter = TlsHandshakeLimiter::new(TlsHandshakeLimitConfig {
+            max_concurrent: 1,
+            max_queued: 1,
+            queue_timeout: Duration::from_millis(100),
+        });
+        let stats = TlsHandshakeLimitStats::default();
+
+        let permit = limiter.acquire(&stats).await.unwrap();
+        let (r1, r2) = tokio::join!(limiter.acquire(&stats), async {
+            tokio::task::yield_now().await;
+            limiter.acquire(&stats).await
+        });
+        assert!(matches!(
+            r1,
+            Err(TlsInterceptionError::HandshakeQueueTimeout)
+        ));
+        assert!(matches!(r2, Err(TlsInterceptionError::HandshakeQueueFull)));
+        let snap = stats.snapshot();
+        assert_eq!(snap.queued, 0);
+        assert_eq!(snap.shed_queue_full, 1);
+        assert_eq!(snap.shed_queue_timeout, 1);
+
+        drop(permit);
+        let _permit = limiter.acquire(&stats).await.unwrap();
+    }
+}
@@ -17,44 +17,75 @@
 use std::sync::Arc;
 
 use anyhow::anyhow;
+use openssl::x509::X509NameRef;
 use slog::slog_info;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::runtime::Handle;
+use tokio::sync::OwnedSemaphorePermit;
 
 use g3_cert_agent::CertAgentHandle;
-use g3_dpi::Protocol;
+use g3_dpi::{Protocol, ProtocolInspectAction};
 use g3_io_ext::{AsyncStream, FlexBufReader, OnceBufReader};
 use g3_slog_types::{LtUpstreamAddr, LtUuid};
+use g3_types::acl::AclRegexSetRule;
+use g3_types::metrics::MetricsName;
 use g3_types::net::{
     AlpnProtocol, OpensslInterceptionClientConfig, OpensslInterceptionServerConfig, UpstreamAddr,
 };
 use g3_udpdump::{ExportedPduDissectorHint, StreamDumpConfig, StreamDumper};
 
-use super::{BoxAsyncRead, BoxAsyncWrite, StreamInspectContext, StreamInspection};
+use super::{
+    BoxAsyncRead, BoxAsyncWrite, StreamInspectContext, StreamInspectTaskNotes, StreamInspection,
+};
+use crate::config::audit::AuditStreamPcapConfig;
 use crate::config::server::ServerConfig;
 use crate::log::inspect::{stream::StreamInspectLog, InspectSource};
 
 mod error;
 pub(crate) use error::TlsInterceptionError;
 
+mod stats;
+pub(crate) use stats::{
+    foreach_stats as foreach_tls_interception_stats, TlsHandshakeFailureReason,
+    TlsHandshakeLimitSnapshot, TlsHandshakeLimitStats, TlsHandshakeSnapshot, TlsHandshakeStats,
+    TlsInterceptionStats,
+};
+
+mod limit;
+pub(crate) use limit::TlsHandshakeLimiter;
+
+mod sni_filter;
+pub(crate) use sni_filter::TlsSniFilter;
+
 mod modern;
 #[cfg(feature = "vendored-tongsuo")]
 mod tlcp;
 
+mod pcap;
+use pcap::{TlsStreamPcap, TlsStreamPcapSession};
+
 #[derive(Clone)]
 pub(crate) struct TlsInterceptionContext {
     pub(super) cert_agent: Arc<CertAgentHandle>,
     pub(super) client_config: Arc<OpensslInterceptionClientConfig>,
     pub(super) server_config: Arc<OpensslInterceptionServerConfig>,
     stream_dumper: Arc<Vec<StreamDumper>>,
+    stream_pcap: Option<Arc<TlsStreamPcap>>,
+    handshake_limiter: Option<Arc<TlsHandshakeLimiter>>,
+    sni_filter: Option<Arc<TlsSniFilter>>,
+    client_subject_acl: Option<Arc<AclRegexSetRule>>,
+    pub(super) stats: Arc<TlsInterceptionStats>,
 }
 
 impl TlsInterceptionContext {
     pub(crate) fn new(
+        auditor: &MetricsName,
         cert_agent: CertAgentHandle,
         client_config: OpensslInterceptionClientConfig,
         server_config: OpensslInterceptionServerConfig,
         dump_config: Option<StreamDumpConfig>,
+        pcap_config: Option<Arc<AuditStreamPcapConfig>>,
+        handshake_limiter: Option<Arc<TlsHandshakeLimiter>>,
     ) -> anyhow::Result<Self> {
         let mut stream_dumper = Vec::new();
         if let Some(dump) = dump_config {
@@ -79,14 +110,47 @@ impl TlsInterceptionContext {
             }
         }
 
+        let stream_pcap = match pcap_config {
+            Some(config) => Some(TlsStreamPcap::get_or_create(auditor, config)?),
+            None => None,
+        };
+
         Ok(TlsInterceptionContext {
             cert_agent: Arc::new(cert_agent),
             client_config: Arc::new(client_config),
             server_config: Arc::new(server_config),
             stream_dumper: Arc::new(stream_dumper),
+            stream_pcap,
+            handshake_limiter,
+            sni_filter: None,
+            client_subject_acl: None,
+            stats: TlsInterceptionStats::get_or_insert(auditor),
         })
     }
 
+    pub(crate) fn set_sni_filter(&mut self, filter: Arc<TlsSniFilter>) {
+        self.sni_filter = Some(filter);
+    }
+
+    pub(crate) fn set_client_subject_acl(&mut self, acl: Arc<AclRegexSetRule>) {
+        self.client_subject_acl = Some(acl);
+    }
+
+    /// Wait for a handshake permit if the concurrency of handshakes is limited.
+    ///
+    /// The permit should be held until both the client and the upstream handshakes are finished.
+    pub(super) async fn acquire_handshake_permit(
+        &self,
+    ) -> Result<Option<OwnedSemaphorePermit>, TlsInterceptionError> {
+        match &self.handshake_limiter {
+            Some(limiter) => {
+                let permit = limiter.acquire(&self.stats.limit).await?;
+                Ok(Some(permit))
+            }
+            None => Ok(None),
+        }
+    }
+
     pub(super) fn get_stream_dumper(&self, worker_id: Option<usize>) -> Option<&StreamDumper> {
         if self.stream_dumper.is_empty() {
             return None;
@@ -100,6 +164,15 @@ impl TlsInterceptionContext {
 
         fastrand::choice(self.stream_dumper.iter())
     }
+
+    pub(super) fn new_pcap_session(
+        &self,
+        task_notes: &StreamInspectTaskNotes,
+    ) -> Option<TlsStreamPcapSession> {
+        self.stream_pcap
+            .as_ref()
+            .map(|pcap| pcap.new_session(task_notes))
+    }
 }
 
 struct TlsInterceptIo {
@@ -114,6 +187,9 @@ pub(crate) struct TlsInterceptObject<SC: ServerConfig> {
     ctx: StreamInspectContext<SC>,
     upstream: UpstreamAddr,
     tls_interception: TlsInterceptionContext,
+    client_cert_subject: Option<String>,
+    client_tls_version: Option<&'static str>,
+    upstream_tls_version: Option<&'static str>,
 }
 
 macro_rules! intercept_log {
@@ -123,6 +199,9 @@ macro_rules! intercept_log {
             "task_id" => LtUuid($obj.ctx.server_task_id()),
             "depth" => $obj.ctx.inspection_depth,
             "upstream" => LtUpstreamAddr(&$obj.upstream),
+            "client_cert_subject" => $obj.client_cert_subject.as_deref(),
+            "client_tls_version" => $obj.client_tls_version,
+            "upstream_tls_version" => $obj.upstream_tls_version,
         )
     };
 }
@@ -138,6 +217,9 @@ impl<SC: ServerConfig> TlsInterceptObject<SC> {
             ctx,
             upstream,
             tls_interception: tls,
+            client_cert_subject: None,
+            client_tls_version: None,
+            upstream_tls_version: None,
         }
     }
 
@@ -158,25 +240,88 @@ impl<SC: ServerConfig> TlsInterceptObject<SC> {
     }
 
     fn log_ok(&self) {
-        intercept_log!(self, "ok");
+        if self.ctx.intercept_log_sampled() {
+            intercept_log!(self, "ok");
+        }
     }
 
     fn log_err(&self, e: &TlsInterceptionError) {
         intercept_log!(self, "{e}");
     }
 
+    fn set_client_cert_subject(&mut self, name: &X509NameRef) {
+        let mut subject = String::new();
+        for entry in name.entries() {
+            let Ok(short_name) = entry.object().nid().short_name() else {
+                continue;
+            };
+            let Ok(value) = entry.data().as_utf8() else {
+                continue;
+            };
+            if !subject.is_empty() {
+                subject.push_str(", ");
+            }
+            subject.push_str(short_name);
+            subject.push('=');
+            subject.push_str(&value);
+        }
+        self.ctx.set_tls_client_subject(&subject);
+        self.client_cert_subject = Some(subject);
+    }
+
+    /// Check the verified client cert subject against the client subject acl of the auditor
+    fn check_client_cert_subject(&self) -> Result<(), TlsInterceptionError> {
+        let (Some(acl), Some(subject)) = (
+            &self.tls_interception.client_subject_acl,
+            &self.client_cert_subject,
+        ) else {
+            return Ok(());
+        };
+        let (_, action) = acl.check(subject);
+        if action.forbid_early() {
+            let reason = format!("tls client cert subject {subject} not allowed");
+            if !self.ctx.skip_block_in_dry_run("tls", &reason) {
+                return Err(TlsInterceptionError::ClientSubjectNotAllowed(
+                    subject.clone(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
     fn retain_alpn_protocol(&self, p: &[u8]) -> bool {
         if p == AlpnProtocol::Http2.identification_sequence() {
-            return !self.ctx.h2_inspect_action(self.upstream.host()).is_block();
+            // keep h2 for block_with_response, so the custom response can be sent to the client
+            return !matches!(
+                self.ctx.effective_inspect_action(
+                    &self.ctx.audit_handle.h2_inspect_policy,
+                    self.upstream.host()
+                ),
+                ProtocolInspectAction::Block
+            );
         } else if p == AlpnProtocol::Smtp.identification_sequence() {
             return !self
                 .ctx
-                .smtp_inspect_action(self.upstream.host())
+                .effective_inspect_action(
+                    &self.ctx.audit_handle.smtp_inspect_policy,
+                    self.upstream.host(),
+                )
                 .is_block();
         } else if p == AlpnProtocol::Imap.identification_sequence() {
             return !self
                 .ctx
-                .imap_inspect_action(self.upstream.host())
+                .effective_inspect_action(
+                    &self.ctx.audit_handle.imap_inspect_policy,
+                    self.upstream.host(),
+                )
+                .is_block();
+        } else if p == AlpnProtocol::Pop3.identification_sequence() {
+            return !self
+                .ctx
+                .effective_inspect_action(
+                    &self.ctx.audit_handle.pop3_inspect_policy,
+                    self.upstream.host(),
+                )
                 .is_block();
         }
         true
@@ -205,6 +350,29 @@ where
         let (clt_r, clt_w) = clt_s.into_split();
         let (ups_r, ups_w) = ups_s.into_split();
 
+        if let Some(pcap) = self.tls_interception.new_pcap_session(&self.ctx.task_notes) {
+            let (clt_r, clt_w, ups_r, ups_w) = pcap.wrap_io(clt_r, clt_w, ups_r, ups_w);
+            self.transfer_plaintext(protocol, has_alpn, clt_r, clt_w, ups_r, ups_w)
+        } else {
+            self.transfer_plaintext(protocol, has_alpn, clt_r, clt_w, ups_r, ups_w)
+        }
+    }
+
+    fn transfer_plaintext<CR, CW, UR, UW>(
+        &self,
+        protocol: Protocol,
+        has_alpn: bool,
+        clt_r: CR,
+        clt_w: CW,
+        ups_r: UR,
+        ups_w: UW,
+    ) -> StreamInspection<SC>
+    where
+        CR: AsyncRead + Send + Unpin + 'static,
+        CW: AsyncWrite + Send + Unpin + 'static,
+        UR: AsyncRead + Send + Unpin + 'static,
+        UW: AsyncWrite + Send + Unpin + 'static,
+    {
         if let Some(stream_dumper) = self
             .tls_interception
             .get_stream_dumper(self.ctx.task_notes.worker_id)
@@ -300,6 +468,17 @@ where
                 );
                 StreamInspection::Imap(imap_obj)
             }
+            Protocol::Pop3 => {
+                let mut pop3_obj =
+                    crate::inspect::pop3::Pop3InterceptObject::new(ctx, self.upstream.clone());
+                pop3_obj.set_io(
+                    Box::new(clt_r),
+                    Box::new(clt_w),
+                    OnceBufReader::with_no_buf(Box::new(ups_r)),
+                    Box::new(ups_w),
+                );
+                StreamInspection::Pop3(pop3_obj)
+            }
             _ => {
                 let mut stream_obj =
                     crate::inspect::stream::StreamInspectObject::new(ctx, self.upstream.clone());
@@ -23,7 +23,7 @@ use g3_dpi::{Protocol, ProtocolInspector};
 use g3_openssl::{SslConnector, SslLazyAcceptor};
 use g3_types::net::{AlpnProtocol, Host, TlsCertUsage, TlsServiceType};
 
-use super::{TlsInterceptIo, TlsInterceptObject, TlsInterceptionError};
+use super::{TlsHandshakeFailureReason, TlsInterceptIo, TlsInterceptObject, TlsInterceptionError};
 use crate::config::server::ServerConfig;
 use crate::inspect::{InterceptionError, StreamInspection};
 use crate::serve::ServerTaskResult;
@@ -43,11 +43,14 @@ where
     ) -> ServerTaskResult<StreamInspection<SC>> {
         match self.do_intercept_modern(inspector).await {
             Ok(obj) => {
+                self.ctx.record_tls_interception(true);
                 self.log_ok();
                 Ok(obj)
             }
             Err(e) => {
+                self.ctx.record_tls_interception(false);
                 self.log_err(&e);
+                self.tls_interception.stats.add_client_aborted(&e);
                 Err(InterceptionError::Tls(e).into_server_task_error(Protocol::TlsModern))
             }
         }
@@ -64,6 +67,9 @@ where
             ups_w,
         } = self.io.take().unwrap();
 
+        let stats = self.tls_interception.stats.clone();
+        stats.client.add_attempt();
+
         let ssl = Ssl::new(&self.tls_interception.server_config.ssl_context).map_err(|e| {
             TlsInterceptionError::InternalOpensslServerError(anyhow!(
                 "failed to get new SSL state: {e}"
@@ -81,13 +87,22 @@ where
 
         tokio::time::timeout(accept_timeout, lazy_acceptor.accept())
             .await
-            .map_err(|_| TlsInterceptionError::ClientHandshakeTimeout)?
+            .map_err(|_| {
+                stats.client.add_failed(TlsHandshakeFailureReason::Timeout);
+                TlsInterceptionError::ClientHandshakeTimeout
+            })?
             .map_err(|e| {
+                stats.client.add_failed(TlsHandshakeFailureReason::from(&e));
                 TlsInterceptionError::ClientHandshakeFailed(anyhow!(
                     "read client hello msg failed: {e:?}"
                 ))
             })?;
 
+        // only acquired after the ClientHello has been received, so idle clients won't hold it,
+        // and it will be held until both handshakes are finished
+        let _handshake_permit = self.tls_interception.acquire_handshake_permit().await?;
+        let mut lazy_acceptor = self.check_sni_modern(lazy_acceptor, CERT_USAGE).await?;
+
         // build to server ssl context based on client hello
         let sni_hostname = self
             .tls_interception
@@ -96,6 +111,7 @@ where
         if let Some(domain) = sni_hostname {
             // TODO also fetch user-site config here?
             self.upstream.set_host(Host::from(domain));
+            self.ctx.set_tls_server_name(domain.as_ref());
         }
         let alpn_ext = self
             .tls_interception
@@ -111,7 +127,7 @@ where
                     new_ext
                 }
             });
-        let ups_ssl = match self.ctx.user_site_tls_client() {
+        let mut ups_ssl = match self.ctx.user_site_tls_client() {
             Some(c) => c
                 .build_mimic_ssl(sni_hostname, &self.upstream, alpn_ext.as_ref())
                 .map_err(|e| {
@@ -129,6 +145,13 @@ where
                     ))
                 })?,
         };
+        self.ctx
+            .set_escaper_tls_client_cert(&mut ups_ssl)
+            .map_err(|e| {
+                TlsInterceptionError::UpstreamPrepareFailed(anyhow!(
+                    "failed to set escaper client cert: {e}"
+                ))
+            })?;
 
         // fetch fake server cert early in the background
         let cert_domain = sni_hostname
@@ -150,14 +173,25 @@ where
                     "failed to get ssl stream: {e}"
                 ))
             })?;
+        stats.upstream.add_attempt();
         let ups_tls_stream = tokio::time::timeout(accept_timeout, ups_tls_connector.connect())
             .await
-            .map_err(|_| TlsInterceptionError::UpstreamHandshakeTimeout)?
+            .map_err(|_| {
+                stats
+                    .upstream
+                    .add_failed(TlsHandshakeFailureReason::Timeout);
+                TlsInterceptionError::UpstreamHandshakeTimeout
+            })?
             .map_err(|e| {
+                stats
+                    .upstream
+                    .add_failed(TlsHandshakeFailureReason::from(&e));
                 TlsInterceptionError::UpstreamHandshakeFailed(anyhow!(
                     "upstream handshake error: {e}"
                 ))
             })?;
+        stats.upstream.add_success();
+        self.upstream_tls_version = Some(ups_tls_stream.ssl().version_str());
 
         let pre_fetch_pair = pre_fetch_handle.await.map_err(|e| {
             TlsInterceptionError::NoFakeCertGenerated(anyhow!(
@@ -204,12 +238,23 @@ where
         })?;
         let clt_tls_stream = tokio::time::timeout(accept_timeout, clt_acceptor.accept())
             .await
-            .map_err(|_| TlsInterceptionError::ClientHandshakeTimeout)?
+            .map_err(|_| {
+                stats.client.add_failed(TlsHandshakeFailureReason::Timeout);
+                TlsInterceptionError::ClientHandshakeTimeout
+            })?
             .map_err(|e| {
+                stats.client.add_failed(TlsHandshakeFailureReason::from(&e));
                 TlsInterceptionError::ClientHandshakeFailed(anyhow!(
                     "client handshake error: {e:?}"
                 ))
             })?;
+        stats.client.add_success();
+        self.client_tls_version = Some(clt_tls_stream.ssl().version_str());
+        if let Some(client_cert) = clt_tls_stream.ssl().peer_certificate() {
+            // only set if client auth is enabled, and it has already been verified
+            self.set_client_cert_subject(client_cert.subject_name());
+        }
+        self.check_client_cert_subject()?;
 
         let mut protocol = Protocol::Unknown;
         let has_alpn = if let Some(alpn_protocol) = clt_tls_stream.ssl().selected_alpn_protocol() {
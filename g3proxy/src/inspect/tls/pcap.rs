@@ -0,0 +1,136 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex, Weak};
+
+use anyhow::anyhow;
+use arc_swap::ArcSwap;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use g3_types::metrics::MetricsName;
+use g3_udpdump::{PcapDumpSession, PcapDumper};
+
+use crate::config::audit::AuditStreamPcapConfig;
+use crate::inspect::{BoxAsyncRead, BoxAsyncWrite, StreamInspectTaskNotes};
+
+/// The running pcap dumpers, keyed by the file path, so a reloaded auditor will reuse the one
+/// that is still in use by the existing tasks, instead of appending to the same file.
+static PCAP_DUMPERS: LazyLock<Mutex<HashMap<PathBuf, Weak<TlsStreamPcap>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub(super) struct TlsStreamPcap {
+    auditor: MetricsName,
+    config: ArcSwap<AuditStreamPcapConfig>,
+    dumper: PcapDumper,
+}
+
+impl TlsStreamPcap {
+    pub(super) fn get_or_create(
+        auditor: &MetricsName,
+        config: Arc<AuditStreamPcapConfig>,
+    ) -> anyhow::Result<Arc<Self>> {
+        let mut dumpers = PCAP_DUMPERS.lock().unwrap();
+        dumpers.retain(|_, v| v.strong_count() > 0);
+
+        if let Some(pcap) = dumpers.get(&config.dump.file).and_then(Weak::upgrade) {
+            if pcap.auditor != *auditor {
+                return Err(anyhow!(
+                    "pcap file {} is already in use by auditor {}",
+                    config.dump.file.display(),
+                    pcap.auditor
+                ));
+            }
+            pcap.dumper.update_config(config.dump.clone());
+            pcap.config.store(config);
+            return Ok(pcap);
+        }
+
+        let dumper = PcapDumper::new(config.dump.clone()).map_err(|e| {
+            anyhow!(
+                "failed to create tls stream pcap dumper for file {}: {e}",
+                config.dump.file.display()
+            )
+        })?;
+        let file = config.dump.file.clone();
+        let pcap = Arc::new(TlsStreamPcap {
+            auditor: auditor.clone(),
+            config: ArcSwap::new(config),
+            dumper,
+        });
+        dumpers.insert(file, Arc::downgrade(&pcap));
+        Ok(pcap)
+    }
+
+    /// Create a capture session for the task.
+    ///
+    /// The selectors will be checked against the latest config each time the data is dumped,
+    /// so the capture of a running task can be started by adding its task id and reload.
+    pub(super) fn new_session(
+        self: &Arc<Self>,
+        task_notes: &StreamInspectTaskNotes,
+    ) -> TlsStreamPcapSession {
+        let pcap = self.clone();
+        let client_ip = task_notes.client_addr.ip();
+        let task_id = *task_notes.task_id();
+        let session =
+            self.dumper
+                .new_session(task_notes.client_addr, task_notes.server_addr, move || {
+                    pcap.config.load().matches(client_ip, &task_id)
+                });
+        TlsStreamPcapSession { session }
+    }
+}
+
+/// A pcap capture session of the plaintext streams of an intercepted connection
+pub(crate) struct TlsStreamPcapSession {
+    session: PcapDumpSession,
+}
+
+impl TlsStreamPcapSession {
+    pub(crate) fn wrap_io<CR, CW, UR, UW>(
+        self,
+        clt_r: CR,
+        clt_w: CW,
+        ups_r: UR,
+        ups_w: UW,
+    ) -> (BoxAsyncRead, BoxAsyncWrite, BoxAsyncRead, BoxAsyncWrite)
+    where
+        CR: AsyncRead + Send + Unpin + 'static,
+        CW: AsyncWrite + Send + Unpin + 'static,
+        UR: AsyncRead + Send + Unpin + 'static,
+        UW: AsyncWrite + Send + Unpin + 'static,
+    {
+        if self.session.client_side() {
+            let (clt_r, clt_w) = self.session.wrap_client_io(clt_r, clt_w);
+            (
+                Box::new(clt_r),
+                Box::new(clt_w),
+                Box::new(ups_r),
+                Box::new(ups_w),
+            )
+        } else {
+            let (ups_r, ups_w) = self.session.wrap_remote_io(ups_r, ups_w);
+            (
+                Box::new(clt_r),
+                Box::new(clt_w),
+                Box::new(ups_r),
+                Box::new(ups_w),
+            )
+        }
+    }
+}
@@ -0,0 +1,380 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use http::Version;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use g3_openssl::SslLazyAcceptor;
+use g3_types::acl_set::AclDstHostRuleSet;
+use g3_types::net::{
+    AlpnProtocol, Host, HttpBlockResponse, TlsCertUsage, TlsServerName, TlsServiceType,
+};
+
+use super::{TlsHandshakeFailureReason, TlsInterceptObject, TlsInterceptionError};
+use crate::config::audit::{TlsSniDenyAction, TlsSniFilterConfig};
+use crate::config::server::ServerConfig;
+
+/// unrecognized_name(112) fatal alert
+const ALERT_UNRECOGNIZED_NAME: [u8; 2] = [0x02, 0x70];
+const TLS_RECORD_VERSION: [u8; 2] = [0x03, 0x03];
+#[cfg(feature = "vendored-tongsuo")]
+const TLCP_RECORD_VERSION: [u8; 2] = [0x01, 0x01];
+
+const DENY_PAGE_MAX_REQUEST_HEAD_SIZE: usize = 8192;
+
+pub(crate) struct TlsSniFilter {
+    allowed: AclDstHostRuleSet,
+    deny_no_sni: bool,
+    deny_action: TlsSniDenyAction,
+    deny_response: Arc<HttpBlockResponse>,
+}
+
+impl TlsSniFilter {
+    pub(crate) fn new(config: &TlsSniFilterConfig) -> Self {
+        TlsSniFilter {
+            allowed: config.allowed.build(),
+            deny_no_sni: config.deny_no_sni,
+            deny_action: config.deny_action,
+            deny_response: config.deny_response.clone(),
+        }
+    }
+
+    pub(crate) fn allow(&self, sni: Option<&TlsServerName>) -> bool {
+        match sni {
+            Some(sni) => {
+                let (_, action) = self.allowed.check(&Host::from(sni));
+                !action.forbid_early()
+            }
+            None => !self.deny_no_sni,
+        }
+    }
+
+    /// Deny the connection by an alert, used when the handshake is relayed without interception
+    pub(crate) async fn deny_by_alert<W>(&self, clt_w: &mut W)
+    where
+        W: AsyncWrite + Unpin,
+    {
+        write_unrecognized_name_alert(clt_w, TLS_RECORD_VERSION).await;
+    }
+}
+
+fn sni_not_allowed(sni: Option<TlsServerName>) -> TlsInterceptionError {
+    match sni {
+        Some(sni) => TlsInterceptionError::SniNotAllowed(sni.to_string()),
+        None => TlsInterceptionError::NoSniNotAllowed,
+    }
+}
+
+async fn send_unrecognized_name_alert<S>(
+    mut lazy_acceptor: SslLazyAcceptor<S>,
+    record_version: [u8; 2],
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    write_unrecognized_name_alert(lazy_acceptor.get_mut(), record_version).await;
+}
+
+async fn write_unrecognized_name_alert<W>(io: &mut W, record_version: [u8; 2])
+where
+    W: AsyncWrite + Unpin,
+{
+    let record = [
+        0x15, // alert
+        record_version[0],
+        record_version[1],
+        0x00,
+        0x02,
+        ALERT_UNRECOGNIZED_NAME[0],
+        ALERT_UNRECOGNIZED_NAME[1],
+    ];
+    let _ = io.write_all(&record).await;
+    let _ = io.shutdown().await;
+}
+
+impl<SC: ServerConfig> TlsInterceptObject<SC> {
+    /// Check the client sent server name against the sni filter.
+    ///
+    /// The connection is denied and an error is returned if the server name is not allowed.
+    /// The alert action is always used for connections without server name.
+    pub(super) async fn check_sni_modern<S>(
+        &mut self,
+        lazy_acceptor: SslLazyAcceptor<S>,
+        cert_usage: TlsCertUsage,
+    ) -> Result<SslLazyAcceptor<S>, TlsInterceptionError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let Some(filter) = self.tls_interception.sni_filter.clone() else {
+            return Ok(lazy_acceptor);
+        };
+        let sni = self
+            .tls_interception
+            .server_config
+            .fetch_server_name(lazy_acceptor.ssl())
+            .cloned();
+        if filter.allow(sni.as_ref()) || self.skip_sni_deny_in_dry_run(sni.as_ref()) {
+            return Ok(lazy_acceptor);
+        }
+
+        match (filter.deny_action, &sni) {
+            (TlsSniDenyAction::DenyPage, Some(sni)) => {
+                self.serve_deny_page(lazy_acceptor, sni, cert_usage, &filter.deny_response)
+                    .await?;
+            }
+            _ => {
+                self.tls_interception
+                    .stats
+                    .client
+                    .add_failed(TlsHandshakeFailureReason::Aborted);
+                send_unrecognized_name_alert(lazy_acceptor, TLS_RECORD_VERSION).await;
+            }
+        }
+        Err(sni_not_allowed(sni))
+    }
+
+    /// Check the client sent server name against the sni filter.
+    ///
+    /// Only the alert action is supported for TLCP.
+    #[cfg(feature = "vendored-tongsuo")]
+    pub(super) async fn check_sni_tlcp<S>(
+        &mut self,
+        lazy_acceptor: SslLazyAcceptor<S>,
+    ) -> Result<SslLazyAcceptor<S>, TlsInterceptionError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let Some(filter) = self.tls_interception.sni_filter.clone() else {
+            return Ok(lazy_acceptor);
+        };
+        let sni = self
+            .tls_interception
+            .server_config
+            .fetch_server_name(lazy_acceptor.ssl())
+            .cloned();
+        if filter.allow(sni.as_ref()) || self.skip_sni_deny_in_dry_run(sni.as_ref()) {
+            return Ok(lazy_acceptor);
+        }
+
+        self.tls_interception
+            .stats
+            .client
+            .add_failed(TlsHandshakeFailureReason::Aborted);
+        send_unrecognized_name_alert(lazy_acceptor, TLCP_RECORD_VERSION).await;
+        Err(sni_not_allowed(sni))
+    }
+
+    fn skip_sni_deny_in_dry_run(&self, sni: Option<&TlsServerName>) -> bool {
+        let reason = match sni {
+            Some(sni) => format!("tls server name {sni} not allowed"),
+            None => "tls connection without server name not allowed".to_string(),
+        };
+        self.ctx.skip_block_in_dry_run("tls", &reason)
+    }
+
+    async fn serve_deny_page<S>(
+        &mut self,
+        mut lazy_acceptor: SslLazyAcceptor<S>,
+        sni: &TlsServerName,
+        cert_usage: TlsCertUsage,
+        response: &HttpBlockResponse,
+    ) -> Result<(), TlsInterceptionError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let stats = &self.tls_interception.stats;
+        let server_config = &self.tls_interception.server_config;
+
+        // no upstream cert to mimic, as we won't connect to the upstream
+        let cert_pair = self
+            .tls_interception
+            .cert_agent
+            .fetch_without_mimic(TlsServiceType::Http, cert_usage, Arc::from(sni.as_ref()))
+            .await
+            .ok_or_else(|| {
+                TlsInterceptionError::NoFakeCertGenerated(anyhow!(
+                    "failed to get fake certificate for the deny page"
+                ))
+            })?;
+
+        let has_http11 = server_config
+            .fetch_alpn_extension(lazy_acceptor.ssl())
+            .map(|ext| {
+                ext.protocol_names()
+                    .any(|p| p == AlpnProtocol::Http11.identification_sequence())
+            })
+            .unwrap_or(false);
+        let clt_ssl = lazy_acceptor.ssl_mut();
+        cert_pair
+            .add_to_ssl(clt_ssl)
+            .map_err(TlsInterceptionError::InternalOpensslServerError)?;
+        if has_http11 {
+            server_config.set_selected_alpn(
+                clt_ssl,
+                AlpnProtocol::Http11.identification_sequence().to_vec(),
+            );
+        }
+
+        let clt_acceptor = lazy_acceptor.into_acceptor(None).map_err(|e| {
+            TlsInterceptionError::InternalOpensslServerError(anyhow!(
+                "failed to convert acceptor: {e}"
+            ))
+        })?;
+        let accept_timeout = server_config.accept_timeout;
+        let mut clt_tls_stream = tokio::time::timeout(accept_timeout, clt_acceptor.accept())
+            .await
+            .map_err(|_| {
+                stats.client.add_failed(TlsHandshakeFailureReason::Timeout);
+                TlsInterceptionError::ClientHandshakeTimeout
+            })?
+            .map_err(|e| {
+                stats.client.add_failed(TlsHandshakeFailureReason::from(&e));
+                TlsInterceptionError::ClientHandshakeFailed(anyhow!(
+                    "client handshake error: {e:?}"
+                ))
+            })?;
+        stats.client.add_success();
+
+        reply_deny_page(&mut clt_tls_stream, response, accept_timeout).await;
+        Ok(())
+    }
+}
+
+/// Reply the deny response to the first request
+async fn reply_deny_page<S>(stream: &mut S, response: &HttpBlockResponse, read_timeout: Duration)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // read the request head before sending the response, or the client may see a reset
+    let mut buf = Vec::with_capacity(1024);
+    let _ = tokio::time::timeout(read_timeout, async {
+        let mut tmp = [0u8; 1024];
+        while buf.len() < DENY_PAGE_MAX_REQUEST_HEAD_SIZE {
+            let nr = stream.read(&mut tmp).await?;
+            if nr == 0 {
+                break;
+            }
+            buf.extend_from_slice(&tmp[..nr]);
+            if memchr::memmem::find(&buf, b"\r\n\r\n").is_some() {
+                break;
+            }
+        }
+        Ok::<(), std::io::Error>(())
+    })
+    .await;
+
+    let rsp = response.serialize_h1(Version::HTTP_11);
+    let _ = stream.write_all(&rsp).await;
+    let _ = stream.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::StatusCode;
+    use yaml_rust::YamlLoader;
+
+    fn new_filter(deny_no_sni: bool) -> TlsSniFilter {
+        let yaml = YamlLoader::load_from_str("child_match: example.net").unwrap();
+        let allowed = g3_yaml::value::acl_set::as_dst_host_rule_set_builder(&yaml[0]).unwrap();
+        TlsSniFilter {
+            allowed: allowed.build(),
+            deny_no_sni,
+            deny_action: TlsSniDenyAction::Alert,
+            deny_response: Arc::new(HttpBlockResponse::default()),
+        }
+    }
+
+    fn server_name(name: &str) -> TlsServerName {
+        let mut buf = Vec::with_capacity(name.len() + 5);
+        buf.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes());
+        buf.push(0x00);
+        buf.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        buf.extend_from_slice(name.as_bytes());
+        TlsServerName::from_extension_value(&buf).unwrap()
+    }
+
+    #[test]
+    fn allow() {
+        let filter = new_filter(true);
+        assert!(filter.allow(Some(&server_name("www.example.net"))));
+        assert!(!filter.allow(Some(&server_name("www.example.com"))));
+        assert!(!filter.allow(None));
+
+        let filter = new_filter(false);
+        assert!(!filter.allow(Some(&server_name("www.example.com"))));
+        assert!(filter.allow(None));
+    }
+
+    #[tokio::test]
+    async fn deny_by_alert() {
+        let filter = new_filter(true);
+        let (mut clt_peer, mut clt_w) = tokio::io::duplex(1024);
+
+        filter.deny_by_alert(&mut clt_w).await;
+
+        let mut buf = Vec::new();
+        clt_peer.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), &[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x70]);
+    }
+
+    #[tokio::test]
+    async fn deny_page() {
+        let (mut clt_peer, mut clt_stream) = tokio::io::duplex(1024);
+        let response = HttpBlockResponse::new(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS);
+
+        let server = tokio::spawn(async move {
+            reply_deny_page(&mut clt_stream, &response, Duration::from_secs(4)).await;
+        });
+
+        clt_peer
+            .write_all(b"GET / HTTP/1.1\r\nHost: www.example.com\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        clt_peer.read_to_end(&mut buf).await.unwrap();
+        server.await.unwrap();
+        assert!(buf.starts_with(b"HTTP/1.1 451 "));
+    }
+
+    #[tokio::test]
+    async fn deny_page_wait_request_head() {
+        let (mut clt_peer, mut clt_stream) = tokio::io::duplex(1024);
+        let response = HttpBlockResponse::default();
+
+        let server = tokio::spawn(async move {
+            reply_deny_page(&mut clt_stream, &response, Duration::from_secs(4)).await;
+        });
+
+        // no response should be sent before the end of the request head
+        clt_peer.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let mut buf = [0u8; 16];
+        let r = tokio::time::timeout(Duration::from_millis(100), clt_peer.read(&mut buf)).await;
+        assert!(r.is_err());
+
+        clt_peer
+            .write_all(b"Host: www.example.com\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        clt_peer.read_to_end(&mut buf).await.unwrap();
+        server.await.unwrap();
+        assert!(buf.starts_with(b"HTTP/1.1 403 "));
+    }
+}
@@ -0,0 +1,287 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ahash::AHashMap;
+
+use g3_types::metrics::MetricsName;
+
+use super::TlsInterceptionError;
+
+static TLS_INTERCEPTION_STATS_REGISTRY: LazyLock<
+    Mutex<AHashMap<MetricsName, Arc<TlsInterceptionStats>>>,
+> = LazyLock::new(|| Mutex::new(AHashMap::new()));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TlsHandshakeFailureReason {
+    Timeout,
+    CertError,
+    ProtocolVersion,
+    AlertReceived,
+    IoError,
+    /// aborted because of failure at the other side or internal error
+    Aborted,
+    Other,
+}
+
+impl TlsHandshakeFailureReason {
+    pub(crate) const ALL: [TlsHandshakeFailureReason; 7] = [
+        TlsHandshakeFailureReason::Timeout,
+        TlsHandshakeFailureReason::CertError,
+        TlsHandshakeFailureReason::ProtocolVersion,
+        TlsHandshakeFailureReason::AlertReceived,
+        TlsHandshakeFailureReason::IoError,
+        TlsHandshakeFailureReason::Aborted,
+        TlsHandshakeFailureReason::Other,
+    ];
+
+    pub(crate) const fn as_str(&self) -> &'static str {
+        match self {
+            TlsHandshakeFailureReason::Timeout => "timeout",
+            TlsHandshakeFailureReason::CertError => "cert_error",
+            TlsHandshakeFailureReason::ProtocolVersion => "protocol_version",
+            TlsHandshakeFailureReason::AlertReceived => "alert_received",
+            TlsHandshakeFailureReason::IoError => "io_error",
+            TlsHandshakeFailureReason::Aborted => "aborted",
+            TlsHandshakeFailureReason::Other => "other",
+        }
+    }
+
+    const fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn from_ssl_reason(reason: &str) -> Option<Self> {
+        // the reason strings are in different case and format for OpenSSL and BoringSSL
+        let reason = reason.to_lowercase().replace('_', " ");
+        if reason.contains("alert") {
+            Some(TlsHandshakeFailureReason::AlertReceived)
+        } else if reason.contains("certificate") {
+            Some(TlsHandshakeFailureReason::CertError)
+        } else if reason.contains("version") || reason.contains("unsupported protocol") {
+            Some(TlsHandshakeFailureReason::ProtocolVersion)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<&io::Error> for TlsHandshakeFailureReason {
+    fn from(e: &io::Error) -> Self {
+        let Some(ssl_e) = e
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<openssl::ssl::Error>())
+        else {
+            return TlsHandshakeFailureReason::IoError;
+        };
+        if let Some(stack) = ssl_e.ssl_error() {
+            for e in stack.errors() {
+                if let Some(reason) = e.reason().and_then(Self::from_ssl_reason) {
+                    return reason;
+                }
+            }
+        }
+        if ssl_e.io_error().is_some() {
+            TlsHandshakeFailureReason::IoError
+        } else {
+            TlsHandshakeFailureReason::Other
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct TlsHandshakeSnapshot {
+    pub(crate) attempt: u64,
+    pub(crate) success: u64,
+    pub(crate) failed: [u64; TlsHandshakeFailureReason::ALL.len()],
+}
+
+#[derive(Default)]
+pub(crate) struct TlsHandshakeStats {
+    attempt: AtomicU64,
+    success: AtomicU64,
+    failed: [AtomicU64; TlsHandshakeFailureReason::ALL.len()],
+}
+
+impl TlsHandshakeStats {
+    pub(crate) fn add_attempt(&self) {
+        self.attempt.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_success(&self) {
+        self.success.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_failed(&self, reason: TlsHandshakeFailureReason) {
+        self.failed[reason.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> TlsHandshakeSnapshot {
+        let mut snap = TlsHandshakeSnapshot {
+            attempt: self.attempt.load(Ordering::Relaxed),
+            success: self.success.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        for (v, s) in snap.failed.iter_mut().zip(self.failed.iter()) {
+            *v = s.load(Ordering::Relaxed);
+        }
+        snap
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct TlsHandshakeLimitSnapshot {
+    pub(crate) queued: u64,
+    pub(crate) shed_queue_full: u64,
+    pub(crate) shed_queue_timeout: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct TlsHandshakeLimitStats {
+    queued: AtomicU64,
+    shed_queue_full: AtomicU64,
+    shed_queue_timeout: AtomicU64,
+}
+
+impl TlsHandshakeLimitStats {
+    pub(super) fn add_queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn del_queued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_shed_queue_full(&self) {
+        self.shed_queue_full.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_shed_queue_timeout(&self) {
+        self.shed_queue_timeout.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> TlsHandshakeLimitSnapshot {
+        TlsHandshakeLimitSnapshot {
+            queued: self.queued.load(Ordering::Relaxed),
+            shed_queue_full: self.shed_queue_full.load(Ordering::Relaxed),
+            shed_queue_timeout: self.shed_queue_timeout.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// TLS handshake stats of an auditor, which will be kept across reload
+pub(crate) struct TlsInterceptionStats {
+    auditor: MetricsName,
+    pub(crate) client: TlsHandshakeStats,
+    pub(crate) upstream: TlsHandshakeStats,
+    pub(crate) limit: TlsHandshakeLimitStats,
+}
+
+impl TlsInterceptionStats {
+    pub(super) fn get_or_insert(auditor: &MetricsName) -> Arc<Self> {
+        let mut ht = TLS_INTERCEPTION_STATS_REGISTRY.lock().unwrap();
+        ht.entry(auditor.clone())
+            .or_insert_with(|| {
+                Arc::new(TlsInterceptionStats {
+                    auditor: auditor.clone(),
+                    client: TlsHandshakeStats::default(),
+                    upstream: TlsHandshakeStats::default(),
+                    limit: TlsHandshakeLimitStats::default(),
+                })
+            })
+            .clone()
+    }
+
+    #[inline]
+    pub(crate) fn auditor(&self) -> &MetricsName {
+        &self.auditor
+    }
+
+    /// Add client side failure for errors that not happen in client handshake
+    pub(crate) fn add_client_aborted(&self, e: &TlsInterceptionError) {
+        let reason = match e {
+            TlsInterceptionError::ClientHandshakeTimeout
+            | TlsInterceptionError::ClientHandshakeFailed(_)
+            | TlsInterceptionError::HandshakeQueueFull
+            | TlsInterceptionError::HandshakeQueueTimeout
+            | TlsInterceptionError::SniNotAllowed(_)
+            | TlsInterceptionError::NoSniNotAllowed
+            | TlsInterceptionError::ClientSubjectNotAllowed(_) => return,
+            TlsInterceptionError::UpstreamPrepareFailed(_)
+            | TlsInterceptionError::UpstreamHandshakeTimeout
+            | TlsInterceptionError::UpstreamHandshakeFailed(_) => {
+                TlsHandshakeFailureReason::Aborted
+            }
+            TlsInterceptionError::NoFakeCertGenerated(_) => TlsHandshakeFailureReason::CertError,
+            TlsInterceptionError::InternalOpensslServerError(_) => TlsHandshakeFailureReason::Other,
+        };
+        self.client.add_failed(reason);
+    }
+}
+
+pub(crate) fn foreach_stats<F>(f: F)
+where
+    F: FnMut(&Arc<TlsInterceptionStats>),
+{
+    let ht = TLS_INTERCEPTION_STATS_REGISTRY.lock().unwrap();
+    ht.values().for_each(f);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssl_reason() {
+        assert_eq!(
+            TlsHandshakeFailureReason::from_ssl_reason("certificate verify failed"),
+            Some(TlsHandshakeFailureReason::CertError)
+        );
+        assert_eq!(
+            TlsHandshakeFailureReason::from_ssl_reason("CERTIFICATE_VERIFY_FAILED"),
+            Some(TlsHandshakeFailureReason::CertError)
+        );
+        assert_eq!(
+            TlsHandshakeFailureReason::from_ssl_reason("sslv3 alert bad certificate"),
+            Some(TlsHandshakeFailureReason::AlertReceived)
+        );
+        assert_eq!(
+            TlsHandshakeFailureReason::from_ssl_reason("TLSV1_ALERT_PROTOCOL_VERSION"),
+            Some(TlsHandshakeFailureReason::AlertReceived)
+        );
+        assert_eq!(
+            TlsHandshakeFailureReason::from_ssl_reason("unsupported protocol"),
+            Some(TlsHandshakeFailureReason::ProtocolVersion)
+        );
+        assert_eq!(
+            TlsHandshakeFailureReason::from_ssl_reason("wrong version number"),
+            Some(TlsHandshakeFailureReason::ProtocolVersion)
+        );
+        assert_eq!(
+            TlsHandshakeFailureReason::from_ssl_reason("decryption failed"),
+            None
+        );
+
+        let e = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+        assert_eq!(
+            TlsHandshakeFailureReason::from(&e),
+            TlsHandshakeFailureReason::IoError
+        );
+    }
+}
@@ -14,6 +14,11 @@
  * limitations under the License.
  */
 
+use tokio::time::Instant;
+
+/// The max payload size of control frames
+pub(super) const MAX_CLOSE_PAYLOAD_SIZE: usize = 125;
+
 pub struct ServerCloseFrame {}
 
 impl ServerCloseFrame {
@@ -21,6 +26,15 @@ impl ServerCloseFrame {
         let code = status_code.to_be_bytes();
         [0x88, 0x02, code[0], code[1]]
     }
+
+    pub(super) fn encode_with_reason(status_code: u16, reason: &str) -> Vec<u8> {
+        let payload = close_payload(status_code, reason);
+        let mut buf = Vec::with_capacity(2 + payload.len());
+        buf.push(0x88);
+        buf.push(payload.len() as u8);
+        buf.extend_from_slice(&payload);
+        buf
+    }
 }
 
 pub struct ClientCloseFrame {}
@@ -30,4 +44,157 @@ impl ClientCloseFrame {
         let code = status_code.to_be_bytes();
         [0x88, 0x82, 0x00, 0x00, 0x00, 0x00, code[0], code[1]]
     }
+
+    /// Encode with an all zero masking key, so the payload can be used as is
+    pub(super) fn encode_with_reason(status_code: u16, reason: &str) -> Vec<u8> {
+        let payload = close_payload(status_code, reason);
+        let mut buf = Vec::with_capacity(6 + payload.len());
+        buf.push(0x88);
+        buf.push(0x80 | payload.len() as u8);
+        buf.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        buf.extend_from_slice(&payload);
+        buf
+    }
+}
+
+/// Build the close frame payload, the reason will be truncated at char boundary if too long
+fn close_payload(status_code: u16, reason: &str) -> Vec<u8> {
+    let max_reason_len = MAX_CLOSE_PAYLOAD_SIZE - 2;
+    let reason = if reason.len() > max_reason_len {
+        let mut end = max_reason_len;
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        &reason[..end]
+    } else {
+        reason
+    };
+
+    let mut payload = Vec::with_capacity(2 + reason.len());
+    payload.extend_from_slice(&status_code.to_be_bytes());
+    payload.extend_from_slice(reason.as_bytes());
+    payload
+}
+
+/// The status code and reason text of a received close frame
+pub(super) struct CloseFrameInfo {
+    code: Option<u16>,
+    reason: Option<String>,
+    time: Instant,
+}
+
+impl CloseFrameInfo {
+    /// Parse the unmasked close frame payload, which should have been bounded to the max size
+    pub(super) fn parse(payload: &[u8]) -> Self {
+        let (code, reason) = if payload.len() < 2 {
+            (None, None)
+        } else {
+            let code = u16::from_be_bytes([payload[0], payload[1]]);
+            let reason = &payload[2..];
+            let reason = (!reason.is_empty()).then(|| String::from_utf8_lossy(reason).into_owned());
+            (Some(code), reason)
+        };
+        CloseFrameInfo {
+            code,
+            reason,
+            time: Instant::now(),
+        }
+    }
+}
+
+/// The first close frame received in each direction
+#[derive(Default)]
+pub(super) struct WebSocketCloseNotes {
+    pub(super) client: Option<CloseFrameInfo>,
+    pub(super) server: Option<CloseFrameInfo>,
+}
+
+impl WebSocketCloseNotes {
+    fn first(&self) -> Option<(&'static str, &CloseFrameInfo)> {
+        match (&self.client, &self.server) {
+            (Some(c), Some(s)) => {
+                if s.time < c.time {
+                    Some(("server", s))
+                } else {
+                    Some(("client", c))
+                }
+            }
+            (Some(c), None) => Some(("client", c)),
+            (None, Some(s)) => Some(("server", s)),
+            (None, None) => None,
+        }
+    }
+
+    pub(super) fn closed_by(&self) -> Option<&'static str> {
+        self.first().map(|(side, _)| side)
+    }
+
+    pub(super) fn close_code(&self) -> Option<u16> {
+        self.first().and_then(|(_, f)| f.code)
+    }
+
+    pub(super) fn close_reason(&self) -> Option<&str> {
+        self.first().and_then(|(_, f)| f.reason.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_close() {
+        let f = CloseFrameInfo::parse(&[0x03, 0xE8, b'b', b'y', b'e']);
+        assert_eq!(f.code, Some(1000));
+        assert_eq!(f.reason.as_deref(), Some("bye"));
+
+        let f = CloseFrameInfo::parse(&[0x03, 0xE9, 0xFF]);
+        assert_eq!(f.code, Some(1001));
+        assert_eq!(f.reason.as_deref(), Some("\u{FFFD}"));
+
+        let f = CloseFrameInfo::parse(&[]);
+        assert!(f.code.is_none());
+        assert!(f.reason.is_none());
+    }
+
+    #[test]
+    fn encode_close() {
+        assert_eq!(
+            ServerCloseFrame::encode_with_reason(1001, ""),
+            ServerCloseFrame::encode_with_status_code(1001)
+        );
+        assert_eq!(
+            ClientCloseFrame::encode_with_reason(1001, ""),
+            ClientCloseFrame::encode_with_status_code(1001)
+        );
+
+        let buf = ServerCloseFrame::encode_with_reason(1008, "blocked");
+        assert_eq!(buf[1] as usize, 9);
+        let f = CloseFrameInfo::parse(&buf[2..]);
+        assert_eq!(f.code, Some(1008));
+        assert_eq!(f.reason.as_deref(), Some("blocked"));
+
+        let buf = ClientCloseFrame::encode_with_reason(1008, "blocked");
+        assert_eq!(buf[1], 0x80 | 9);
+        let f = CloseFrameInfo::parse(&buf[6..]);
+        assert_eq!(f.reason.as_deref(), Some("blocked"));
+
+        let reason = "\u{4E2D}".repeat(50);
+        let buf = ServerCloseFrame::encode_with_reason(1008, &reason);
+        assert_eq!(buf.len(), 2 + 2 + 123);
+        let f = CloseFrameInfo::parse(&buf[2..]);
+        assert_eq!(f.reason.unwrap(), "\u{4E2D}".repeat(41));
+    }
+
+    #[test]
+    fn closed_by() {
+        let mut notes = WebSocketCloseNotes::default();
+        assert!(notes.closed_by().is_none());
+
+        notes.server = Some(CloseFrameInfo::parse(&[0x03, 0xE9]));
+        notes.client = Some(CloseFrameInfo::parse(&[0x03, 0xE8]));
+        assert_eq!(notes.closed_by(), Some("server"));
+        assert_eq!(notes.close_code(), Some(1001));
+        assert!(notes.close_reason().is_none());
+    }
 }
@@ -0,0 +1,343 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::close::{CloseFrameInfo, MAX_CLOSE_PAYLOAD_SIZE};
+
+const MAX_FRAME_HEADER_SIZE: usize = 14;
+const MAX_CONTROL_PAYLOAD_SIZE: usize = 125;
+
+pub(super) const OPCODE_CLOSE: u8 = 0x08;
+pub(super) const OPCODE_PING: u8 = 0x09;
+pub(super) const OPCODE_PONG: u8 = 0x0A;
+
+/// Track the frame boundaries of a websocket byte stream without modifying it
+#[derive(Default)]
+pub(super) struct FrameTracker {
+    header: [u8; MAX_FRAME_HEADER_SIZE],
+    header_len: usize,
+    payload_left: u64,
+    close_mask: Option<[u8; 4]>,
+    close_payload: Option<Vec<u8>>,
+    close_frame: Option<CloseFrameInfo>,
+    track_ping_pong: bool,
+    ping_mask: Option<[u8; 4]>,
+    ping_payload: Option<Vec<u8>>,
+    ping_is_pong: bool,
+    ping_frames: Vec<Vec<u8>>,
+    pong_frames: Vec<Vec<u8>>,
+}
+
+impl FrameTracker {
+    fn header_size(&self) -> usize {
+        let mut size = 2;
+        match self.header[1] & 0x7F {
+            126 => size += 2,
+            127 => size += 8,
+            _ => {}
+        }
+        if self.header[1] & 0x80 != 0 {
+            size += 4;
+        }
+        size
+    }
+
+    fn payload_len(&self) -> u64 {
+        match self.header[1] & 0x7F {
+            126 => u16::from_be_bytes([self.header[2], self.header[3]]) as u64,
+            127 => {
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&self.header[2..10]);
+                u64::from_be_bytes(len)
+            }
+            n => n as u64,
+        }
+    }
+
+    fn mask_key(&self) -> Option<[u8; 4]> {
+        if self.header[1] & 0x80 != 0 {
+            let end = self.header_size();
+            let mut key = [0u8; 4];
+            key.copy_from_slice(&self.header[end - 4..end]);
+            Some(key)
+        } else {
+            None
+        }
+    }
+
+    /// Get the first close frame seen in this stream
+    pub(super) fn take_close_frame(&mut self) -> Option<CloseFrameInfo> {
+        self.close_frame.take()
+    }
+
+    /// Collect the (unmasked) payload of ping and pong frames
+    pub(super) fn track_ping_pong(&mut self) {
+        self.track_ping_pong = true;
+    }
+
+    /// Get the payloads of the ping frames seen since the last call
+    pub(super) fn take_ping_frames(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.ping_frames)
+    }
+
+    /// Get the payloads of the pong frames seen since the last call
+    pub(super) fn take_pong_frames(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.pong_frames)
+    }
+
+    fn collect_ping_payload(&mut self, data: &[u8]) {
+        let Some(payload) = &mut self.ping_payload else {
+            return;
+        };
+        match self.ping_mask {
+            Some(key) => {
+                let offset = payload.len();
+                payload.extend(
+                    data.iter()
+                        .enumerate()
+                        .map(|(i, b)| b ^ key[(offset + i) % 4]),
+                );
+            }
+            None => payload.extend_from_slice(data),
+        }
+    }
+
+    fn finish_ping_payload(&mut self) {
+        if let Some(payload) = self.ping_payload.take() {
+            if self.ping_is_pong {
+                self.pong_frames.push(payload);
+            } else {
+                self.ping_frames.push(payload);
+            }
+        }
+    }
+
+    fn start_ping_pong(&mut self, opcode: u8) {
+        match opcode {
+            OPCODE_PING | OPCODE_PONG => {
+                // control frames with too large payload are invalid, just ignore them
+                if self.payload_left > MAX_CONTROL_PAYLOAD_SIZE as u64 {
+                    return;
+                }
+                self.ping_is_pong = opcode == OPCODE_PONG;
+                self.ping_mask = self.mask_key();
+                self.ping_payload = Some(Vec::with_capacity(self.payload_left as usize));
+                if self.payload_left == 0 {
+                    self.finish_ping_payload();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn collect_close_payload(&mut self, data: &[u8]) {
+        let Some(payload) = &mut self.close_payload else {
+            return;
+        };
+        let room = MAX_CLOSE_PAYLOAD_SIZE.saturating_sub(payload.len());
+        let data = &data[..data.len().min(room)];
+        match self.close_mask {
+            Some(key) => {
+                let offset = payload.len();
+                payload.extend(
+                    data.iter()
+                        .enumerate()
+                        .map(|(i, b)| b ^ key[(offset + i) % 4]),
+                );
+            }
+            None => payload.extend_from_slice(data),
+        }
+    }
+
+    fn finish_close_payload(&mut self) {
+        if let Some(payload) = self.close_payload.take() {
+            self.close_frame = Some(CloseFrameInfo::parse(&payload));
+        }
+    }
+
+    /// Check if all data fed so far consists of complete frames
+    pub(super) fn at_frame_boundary(&self) -> bool {
+        self.header_len == 0 && self.payload_left == 0
+    }
+
+    /// Feed the next chunk of data in the stream.
+    ///
+    /// `check` will be called with the opcode of each frame once its header is complete.
+    /// If it returns false, the tracking stops and the offset of the header start of that frame
+    /// in this chunk will be returned, it will be 0 if the header started in a previous chunk.
+    pub(super) fn feed<F>(&mut self, data: &[u8], mut check: F) -> Option<usize>
+    where
+        F: FnMut(u8) -> bool,
+    {
+        let mut offset = 0;
+        let mut header_start = 0;
+        while offset < data.len() {
+            if self.payload_left > 0 {
+                let left = (data.len() - offset) as u64;
+                let n = left.min(self.payload_left) as usize;
+                self.collect_close_payload(&data[offset..offset + n]);
+                self.collect_ping_payload(&data[offset..offset + n]);
+                self.payload_left -= n as u64;
+                offset += n;
+                if self.payload_left == 0 {
+                    self.finish_close_payload();
+                    self.finish_ping_payload();
+                }
+                continue;
+            }
+
+            if self.header_len == 0 {
+                header_start = offset;
+            }
+            self.header[self.header_len] = data[offset];
+            self.header_len += 1;
+            offset += 1;
+            if self.header_len < 2 || self.header_len < self.header_size() {
+                continue;
+            }
+
+            self.payload_left = self.payload_len();
+            let opcode = self.header[0] & 0x0F;
+            if opcode == OPCODE_CLOSE && self.close_frame.is_none() && self.close_payload.is_none()
+            {
+                self.close_mask = self.mask_key();
+                self.close_payload = Some(Vec::with_capacity(MAX_CLOSE_PAYLOAD_SIZE));
+                if self.payload_left == 0 {
+                    self.finish_close_payload();
+                }
+            }
+            if self.track_ping_pong {
+                self.start_ping_pong(opcode);
+            }
+            self.header_len = 0;
+            if !check(opcode) {
+                return Some(header_start);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inspect::websocket::close::WebSocketCloseNotes;
+
+    #[test]
+    fn split_frames() {
+        let mut tracker = FrameTracker::default();
+        let mut opcodes = Vec::new();
+
+        // unmasked text frame with 5 bytes payload, and the start of a masked ping frame
+        let data = [0x81, 0x05, b'h', b'e', b'l', b'l', b'o', 0x89];
+        assert!(tracker
+            .feed(&data, |op| {
+                opcodes.push(op);
+                true
+            })
+            .is_none());
+        // the rest of the ping frame with 1 byte payload, and a binary frame with 16 bit length
+        let data = [0x81, 0x02, 0x03, 0x04, 0x05, b'x', 0x82, 0x7E, 0x00, 0x80];
+        assert!(tracker
+            .feed(&data, |op| {
+                opcodes.push(op);
+                true
+            })
+            .is_none());
+        assert_eq!(opcodes, [0x01, OPCODE_PING, 0x02]);
+
+        let data = [0u8; 0x80];
+        assert!(tracker.feed(&data, |_| false).is_none());
+        let data = [0x88, 0x00];
+        assert_eq!(tracker.feed(&data, |op| op != OPCODE_CLOSE), Some(0));
+    }
+
+    #[test]
+    fn reject_offset() {
+        let mut tracker = FrameTracker::default();
+        let data = [0x81, 0x01, b'a', 0x81, 0x01, b'b'];
+        let mut count = 0;
+        let r = tracker.feed(&data, |_| {
+            count += 1;
+            count < 2
+        });
+        assert_eq!(r, Some(3));
+
+        let mut tracker = FrameTracker::default();
+        assert!(tracker.feed(&[0x81], |_| true).is_none());
+        assert_eq!(tracker.feed(&[0x01, b'a'], |_| false), Some(0));
+    }
+
+    #[test]
+    fn close_frame() {
+        let mut tracker = FrameTracker::default();
+        // masked close frame with status code 1001 and reason "away", split in two chunks
+        let key = [0x01, 0x02, 0x03, 0x04];
+        let payload = [0x03, 0xE9, b'a', b'w', b'a', b'y'];
+        let mut data = vec![0x88, 0x86];
+        data.extend_from_slice(&key);
+        data.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+        assert!(tracker.feed(&data[..8], |_| true).is_none());
+        assert!(tracker.take_close_frame().is_none());
+        assert!(tracker.feed(&data[8..], |_| true).is_none());
+        let close = tracker.take_close_frame().unwrap();
+        let notes = WebSocketCloseNotes {
+            client: Some(close),
+            ..Default::default()
+        };
+        assert_eq!(notes.close_code(), Some(1001));
+        assert_eq!(notes.close_reason(), Some("away"));
+
+        // empty close frame after some data frame
+        let mut tracker = FrameTracker::default();
+        assert!(tracker
+            .feed(&[0x81, 0x01, b'a', 0x88, 0x00], |_| true)
+            .is_none());
+        assert!(tracker.take_close_frame().is_some());
+    }
+
+    #[test]
+    fn ping_pong() {
+        let mut tracker = FrameTracker::default();
+        tracker.track_ping_pong();
+        // masked ping with payload "hi", split in the middle, then an empty ping and a pong
+        let key = [0x01, 0x02, 0x03, 0x04];
+        let data = [0x89, 0x82, 0x01, 0x02, 0x03, 0x04, b'h' ^ key[0]];
+        assert!(tracker.feed(&data, |_| true).is_none());
+        assert!(tracker.take_ping_frames().is_empty());
+        let data = [b'i' ^ key[1], 0x89, 0x00, 0x8A, 0x00];
+        assert!(tracker.feed(&data, |_| true).is_none());
+        assert_eq!(tracker.take_ping_frames(), vec![b"hi".to_vec(), Vec::new()]);
+        assert_eq!(tracker.take_pong_frames(), vec![Vec::<u8>::new()]);
+        assert!(tracker.take_pong_frames().is_empty());
+
+        // unmasked pong with payload "hi"
+        assert!(tracker.feed(&[0x8A, 0x02, b'h', b'i'], |_| true).is_none());
+        assert!(tracker.take_ping_frames().is_empty());
+        assert_eq!(tracker.take_pong_frames(), vec![b"hi".to_vec()]);
+    }
+
+    #[test]
+    fn frame_boundary() {
+        let mut tracker = FrameTracker::default();
+        assert!(tracker.at_frame_boundary());
+        assert!(tracker.feed(&[0x81], |_| true).is_none());
+        assert!(!tracker.at_frame_boundary());
+        assert!(tracker.feed(&[0x02, b'a'], |_| true).is_none());
+        assert!(!tracker.at_frame_boundary());
+        assert!(tracker.feed(&[b'b', 0x89, 0x00], |_| true).is_none());
+        assert!(tracker.at_frame_boundary());
+    }
+}
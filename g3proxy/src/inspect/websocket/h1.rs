@@ -18,14 +18,16 @@ use anyhow::anyhow;
 use slog::slog_info;
 use tokio::io::AsyncWriteExt;
 
-use g3_dpi::ProtocolInspectAction;
+use g3_dpi::{Protocol, ProtocolInspectAction};
 use g3_io_ext::LimitedWriteExt;
 use g3_slog_types::{LtHttpHeaderValue, LtUpstreamAddr, LtUuid};
 use g3_types::net::{UpstreamAddr, WebSocketNotes};
 
-use super::{ClientCloseFrame, ServerCloseFrame};
+use super::{ClientCloseFrame, ServerCloseFrame, WebSocketCloseNotes};
 #[cfg(feature = "quic")]
-use crate::audit::DetourAction;
+use crate::audit::{
+    DetourAction, StreamDetourFallback, StreamDetourFallbackAction, StreamDetourPayload,
+};
 use crate::config::server::ServerConfig;
 use crate::inspect::{BoxAsyncRead, BoxAsyncWrite, StreamInspectContext};
 use crate::serve::{ServerTaskError, ServerTaskResult};
@@ -41,6 +43,9 @@ macro_rules! intercept_log {
             "ws_origin" => $obj.ws_notes.origin().map(LtHttpHeaderValue),
             "ws_sub_protocol" => $obj.ws_notes.sub_protocol().map(LtHttpHeaderValue),
             "ws_version" => $obj.ws_notes.version().map(LtHttpHeaderValue),
+            "ws_closed_by" => $obj.ws_close.closed_by(),
+            "ws_close_code" => $obj.ws_close.close_code(),
+            "ws_close_reason" => $obj.ws_close.close_reason(),
         )
     };
 }
@@ -57,19 +62,22 @@ pub(crate) struct H1WebsocketInterceptObject<SC: ServerConfig> {
     pub(crate) ctx: StreamInspectContext<SC>,
     upstream: UpstreamAddr,
     ws_notes: WebSocketNotes,
+    ws_close: WebSocketCloseNotes,
 }
 
 impl<SC: ServerConfig> H1WebsocketInterceptObject<SC> {
     pub(crate) fn new(
-        ctx: StreamInspectContext<SC>,
+        mut ctx: StreamInspectContext<SC>,
         upstream: UpstreamAddr,
         ws_notes: WebSocketNotes,
     ) -> Self {
+        ctx.set_protocol_idle_timeout(Protocol::Websocket);
         H1WebsocketInterceptObject {
             io: None,
             ctx,
             upstream,
             ws_notes,
+            ws_close: WebSocketCloseNotes::default(),
         }
     }
 
@@ -95,11 +103,15 @@ impl<SC: ServerConfig> H1WebsocketInterceptObject<SC> {
             #[cfg(feature = "quic")]
             ProtocolInspectAction::Detour => self.do_detour().await,
             ProtocolInspectAction::Bypass => self.do_bypass().await,
-            ProtocolInspectAction::Block => self.do_block().await,
+            ProtocolInspectAction::Block | ProtocolInspectAction::BlockWithResponse => {
+                self.do_block().await
+            }
         };
         match r {
             Ok(_) => {
-                intercept_log!(self, "finished");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished");
+                }
                 Ok(())
             }
             Err(e) => {
@@ -109,18 +121,30 @@ impl<SC: ServerConfig> H1WebsocketInterceptObject<SC> {
         }
     }
 
+    /// Handle the stream as decided by [StreamDetourFallback] if the stream detour is unavailable
+    #[cfg(feature = "quic")]
+    async fn do_detour_fallback(&mut self, e: Option<anyhow::Error>) -> ServerTaskResult<()> {
+        let fallback = StreamDetourFallback::new(self.ctx.audit_handle.stream_detour_on_error(), e);
+        intercept_log!(self, "{fallback}");
+        match fallback.action {
+            StreamDetourFallbackAction::Block => self.do_block().await,
+            StreamDetourFallbackAction::Bypass => self.do_bypass().await,
+            StreamDetourFallbackAction::Close(e) => {
+                self.close_on_detour_error().await;
+                Err(ServerTaskError::InternalAdapterError(e))
+            }
+        }
+    }
+
     #[cfg(feature = "quic")]
     async fn do_detour(&mut self) -> ServerTaskResult<()> {
         let Some(client) = self.ctx.audit_handle.stream_detour_client() else {
-            return self.do_bypass().await;
+            return self.do_detour_fallback(None).await;
         };
 
         let mut detour_stream = match client.open_detour_stream().await {
             Ok(s) => s,
-            Err(e) => {
-                self.close_on_detour_error().await;
-                return Err(ServerTaskError::InternalAdapterError(e));
-            }
+            Err(e) => return self.do_detour_fallback(Some(e)).await,
         };
 
         let mut detour_ctx = client.build_context(
@@ -128,9 +152,9 @@ impl<SC: ServerConfig> H1WebsocketInterceptObject<SC> {
             &self.ctx.server_quit_policy,
             &self.ctx.task_notes,
             &self.upstream,
-            g3_dpi::Protocol::Websocket,
+            Protocol::Websocket,
         );
-        detour_ctx.set_payload(self.ws_notes.serialize());
+        detour_ctx.set_payload(StreamDetourPayload::WebSocket(&self.ws_notes));
 
         match detour_ctx.check_detour_action(&mut detour_stream).await {
             Ok(DetourAction::Continue) => {
@@ -192,21 +216,16 @@ impl<SC: ServerConfig> H1WebsocketInterceptObject<SC> {
             ups_w,
         } = self.io.take().unwrap();
 
-        crate::inspect::stream::transit_transparent(
-            clt_r,
-            clt_w,
-            ups_r,
-            ups_w,
-            &self.ctx.server_config,
-            &self.ctx.server_quit_policy,
-            self.ctx.user(),
-        )
-        .await
+        self.ctx
+            .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+            .await
     }
 
     async fn do_block(&mut self) -> ServerTaskResult<()> {
-        const SERVER_CLOSE_BYTES: [u8; 4] = ServerCloseFrame::encode_with_status_code(1001);
-        const CLIENT_CLOSE_BYTES: [u8; 8] = ClientCloseFrame::encode_with_status_code(1001);
+        let ws_config = self.ctx.websocket_interception();
+        let reason = ws_config.block_close_reason.as_str();
+        let server_close_bytes = ServerCloseFrame::encode_with_reason(1001, reason);
+        let client_close_bytes = ClientCloseFrame::encode_with_reason(1001, reason);
 
         let H1WebsocketIo {
             clt_r: _,
@@ -216,17 +235,24 @@ impl<SC: ServerConfig> H1WebsocketInterceptObject<SC> {
         } = self.io.take().unwrap();
 
         tokio::spawn(async move {
-            if ups_w.write_all_flush(&CLIENT_CLOSE_BYTES).await.is_ok() {
+            if ups_w.write_all_flush(&client_close_bytes).await.is_ok() {
                 let _ = ups_w.shutdown().await;
             }
         });
 
-        if clt_w.write_all_flush(&SERVER_CLOSE_BYTES).await.is_ok() {
+        if clt_w.write_all_flush(&server_close_bytes).await.is_ok() {
+            self.ctx.tarpit_blocked(&mut clt_w).await;
             let _ = clt_w.shutdown().await;
         }
-        Err(ServerTaskError::InternalAdapterError(anyhow!(
-            "websocket blocked by inspection policy"
-        )))
+        if reason.is_empty() {
+            Err(ServerTaskError::InternalAdapterError(anyhow!(
+                "websocket blocked by inspection policy"
+            )))
+        } else {
+            Err(ServerTaskError::InternalAdapterError(anyhow!(
+                "websocket blocked by inspection policy: {reason}"
+            )))
+        }
     }
 
     async fn do_intercept(&mut self) -> ServerTaskResult<()> {
@@ -237,14 +263,13 @@ impl<SC: ServerConfig> H1WebsocketInterceptObject<SC> {
             ups_w,
         } = self.io.take().unwrap();
 
-        crate::inspect::stream::transit_transparent(
+        super::limit::transit_with_frame_rate_limit(
             clt_r,
             clt_w,
             ups_r,
             ups_w,
-            &self.ctx.server_config,
-            &self.ctx.server_quit_policy,
-            self.ctx.user(),
+            &self.ctx,
+            &mut self.ws_close,
         )
         .await
     }
@@ -19,14 +19,16 @@ use bytes::Bytes;
 use h2::{RecvStream, SendStream};
 use slog::slog_info;
 
-use g3_dpi::ProtocolInspectAction;
+use g3_dpi::{Protocol, ProtocolInspectAction};
 use g3_h2::{H2StreamReader, H2StreamWriter};
 use g3_slog_types::{LtHttpHeaderValue, LtUpstreamAddr, LtUuid};
 use g3_types::net::{UpstreamAddr, WebSocketNotes};
 
-use super::{ClientCloseFrame, ServerCloseFrame};
+use super::{ClientCloseFrame, ServerCloseFrame, WebSocketCloseNotes};
 #[cfg(feature = "quic")]
-use crate::audit::DetourAction;
+use crate::audit::{
+    DetourAction, StreamDetourFallback, StreamDetourFallbackAction, StreamDetourPayload,
+};
 use crate::config::server::ServerConfig;
 use crate::inspect::StreamInspectContext;
 use crate::serve::{ServerTaskError, ServerTaskResult};
@@ -42,6 +44,9 @@ macro_rules! intercept_log {
             "ws_origin" => $obj.ws_notes.origin().map(LtHttpHeaderValue),
             "ws_sub_protocol" => $obj.ws_notes.sub_protocol().map(LtHttpHeaderValue),
             "ws_version" => $obj.ws_notes.version().map(LtHttpHeaderValue),
+            "ws_closed_by" => $obj.ws_close.closed_by(),
+            "ws_close_code" => $obj.ws_close.close_code(),
+            "ws_close_reason" => $obj.ws_close.close_reason(),
         )
     };
 }
@@ -50,18 +55,21 @@ pub(crate) struct H2WebsocketInterceptObject<SC: ServerConfig> {
     ctx: StreamInspectContext<SC>,
     upstream: UpstreamAddr,
     ws_notes: WebSocketNotes,
+    ws_close: WebSocketCloseNotes,
 }
 
 impl<SC: ServerConfig> H2WebsocketInterceptObject<SC> {
     pub(crate) fn new(
-        ctx: StreamInspectContext<SC>,
+        mut ctx: StreamInspectContext<SC>,
         upstream: UpstreamAddr,
         ws_notes: WebSocketNotes,
     ) -> Self {
+        ctx.set_protocol_idle_timeout(Protocol::Websocket);
         H2WebsocketInterceptObject {
             ctx,
             upstream,
             ws_notes,
+            ws_close: WebSocketCloseNotes::default(),
         }
     }
 }
@@ -79,11 +87,15 @@ impl<SC: ServerConfig> H2WebsocketInterceptObject<SC> {
             #[cfg(feature = "quic")]
             ProtocolInspectAction::Detour => self.do_detour(clt_r, clt_w, ups_r, ups_w).await,
             ProtocolInspectAction::Bypass => self.do_bypass(clt_r, clt_w, ups_r, ups_w).await,
-            ProtocolInspectAction::Block => self.do_block(clt_w, ups_w).await,
+            ProtocolInspectAction::Block | ProtocolInspectAction::BlockWithResponse => {
+                self.do_block(clt_w, ups_w).await
+            }
         };
         match r {
             Ok(_) => {
-                intercept_log!(self, "finished");
+                if self.ctx.intercept_log_sampled() {
+                    intercept_log!(self, "finished");
+                }
             }
             Err(e) => {
                 intercept_log!(self, "{e}");
@@ -91,6 +103,28 @@ impl<SC: ServerConfig> H2WebsocketInterceptObject<SC> {
         }
     }
 
+    /// Handle the stream as decided by [StreamDetourFallback] if the stream detour is unavailable
+    #[cfg(feature = "quic")]
+    async fn do_detour_fallback(
+        &mut self,
+        e: Option<anyhow::Error>,
+        clt_r: RecvStream,
+        clt_w: SendStream<Bytes>,
+        ups_r: RecvStream,
+        ups_w: SendStream<Bytes>,
+    ) -> ServerTaskResult<()> {
+        let fallback = StreamDetourFallback::new(self.ctx.audit_handle.stream_detour_on_error(), e);
+        intercept_log!(self, "{fallback}");
+        match fallback.action {
+            StreamDetourFallbackAction::Block => self.do_block(clt_w, ups_w).await,
+            StreamDetourFallbackAction::Bypass => self.do_bypass(clt_r, clt_w, ups_r, ups_w).await,
+            StreamDetourFallbackAction::Close(e) => {
+                self.close_on_detour_error(clt_w, ups_w);
+                Err(ServerTaskError::InternalAdapterError(e))
+            }
+        }
+    }
+
     #[cfg(feature = "quic")]
     async fn do_detour(
         &mut self,
@@ -100,14 +134,17 @@ impl<SC: ServerConfig> H2WebsocketInterceptObject<SC> {
         ups_w: SendStream<Bytes>,
     ) -> ServerTaskResult<()> {
         let Some(client) = self.ctx.audit_handle.stream_detour_client() else {
-            return self.do_bypass(clt_r, clt_w, ups_r, ups_w).await;
+            return self
+                .do_detour_fallback(None, clt_r, clt_w, ups_r, ups_w)
+                .await;
         };
 
         let mut detour_stream = match client.open_detour_stream().await {
             Ok(s) => s,
             Err(e) => {
-                self.close_on_detour_error(clt_w, ups_w);
-                return Err(ServerTaskError::InternalAdapterError(e));
+                return self
+                    .do_detour_fallback(Some(e), clt_r, clt_w, ups_r, ups_w)
+                    .await;
             }
         };
 
@@ -116,9 +153,9 @@ impl<SC: ServerConfig> H2WebsocketInterceptObject<SC> {
             &self.ctx.server_quit_policy,
             &self.ctx.task_notes,
             &self.upstream,
-            g3_dpi::Protocol::Websocket,
+            Protocol::Websocket,
         );
-        detour_ctx.set_payload(self.ws_notes.serialize());
+        detour_ctx.set_payload(StreamDetourPayload::WebSocket(&self.ws_notes));
 
         match detour_ctx.check_detour_action(&mut detour_stream).await {
             Ok(DetourAction::Continue) => {
@@ -172,16 +209,9 @@ impl<SC: ServerConfig> H2WebsocketInterceptObject<SC> {
         let ups_r = H2StreamReader::new(ups_r);
         let ups_w = H2StreamWriter::new(ups_w);
 
-        crate::inspect::stream::transit_transparent(
-            clt_r,
-            clt_w,
-            ups_r,
-            ups_w,
-            &self.ctx.server_config,
-            &self.ctx.server_quit_policy,
-            self.ctx.user(),
-        )
-        .await
+        self.ctx
+            .transit_transparent(clt_r, clt_w, ups_r, ups_w)
+            .await
     }
 
     async fn do_block(
@@ -189,14 +219,22 @@ impl<SC: ServerConfig> H2WebsocketInterceptObject<SC> {
         mut clt_w: SendStream<Bytes>,
         mut ups_w: SendStream<Bytes>,
     ) -> ServerTaskResult<()> {
-        const SERVER_CLOSE_BYTES: [u8; 4] = ServerCloseFrame::encode_with_status_code(1001);
-        const CLIENT_CLOSE_BYTES: [u8; 8] = ClientCloseFrame::encode_with_status_code(1001);
+        let ws_config = self.ctx.websocket_interception();
+        let reason = ws_config.block_close_reason.as_str();
+        let server_close_bytes = ServerCloseFrame::encode_with_reason(1001, reason);
+        let client_close_bytes = ClientCloseFrame::encode_with_reason(1001, reason);
 
-        let _ = ups_w.send_data(Bytes::from_static(&CLIENT_CLOSE_BYTES), true);
-        let _ = clt_w.send_data(Bytes::from_static(&SERVER_CLOSE_BYTES), true);
-        Err(ServerTaskError::InternalAdapterError(anyhow!(
-            "websocket blocked by inspection policy"
-        )))
+        let _ = ups_w.send_data(Bytes::from(client_close_bytes), true);
+        let _ = clt_w.send_data(Bytes::from(server_close_bytes), true);
+        if reason.is_empty() {
+            Err(ServerTaskError::InternalAdapterError(anyhow!(
+                "websocket blocked by inspection policy"
+            )))
+        } else {
+            Err(ServerTaskError::InternalAdapterError(anyhow!(
+                "websocket blocked by inspection policy: {reason}"
+            )))
+        }
     }
 
     async fn do_intercept(
@@ -211,14 +249,13 @@ impl<SC: ServerConfig> H2WebsocketInterceptObject<SC> {
         let ups_r = H2StreamReader::new(ups_r);
         let ups_w = H2StreamWriter::new(ups_w);
 
-        crate::inspect::stream::transit_transparent(
+        super::limit::transit_with_frame_rate_limit(
             clt_r,
             clt_w,
             ups_r,
             ups_w,
-            &self.ctx.server_config,
-            &self.ctx.server_quit_policy,
-            self.ctx.user(),
+            &self.ctx,
+            &mut self.ws_close,
         )
         .await
     }
@@ -0,0 +1,384 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use anyhow::anyhow;
+use governor::{clock::DefaultClock, state::InMemoryState, state::NotKeyed, RateLimiter};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use g3_io_ext::{LimitedCopy, LimitedWriteExt};
+use g3_types::limit::RateLimitQuotaConfig;
+
+use super::close::{CloseFrameInfo, WebSocketCloseNotes};
+use super::frame::{FrameTracker, OPCODE_CLOSE, OPCODE_PING, OPCODE_PONG};
+use super::{ClientCloseFrame, ServerCloseFrame};
+use crate::config::server::ServerConfig;
+use crate::inspect::{DryRunBlockLogger, StreamInspectContext};
+use crate::serve::{ServerTaskError, ServerTaskResult};
+
+#[derive(Debug, Error)]
+#[error("websocket frame rate limit exceeded")]
+pub(super) struct FrameRateLimitExceeded;
+
+impl FrameRateLimitExceeded {
+    pub(super) fn is_source_of(e: &io::Error) -> bool {
+        e.get_ref()
+            .map(|e| e.is::<FrameRateLimitExceeded>())
+            .unwrap_or(false)
+    }
+}
+
+struct FrameRateLimiter {
+    limiter: RateLimiter<NotKeyed, InMemoryState, DefaultClock>,
+    count_ping_pong: bool,
+    exceeded: bool,
+    /// only log the first exceeding event in dry-run mode
+    dry_run: Option<(DryRunBlockLogger, &'static str)>,
+}
+
+impl FrameRateLimiter {
+    fn new(
+        quota: &RateLimitQuotaConfig,
+        count_ping_pong: bool,
+        dry_run: Option<(DryRunBlockLogger, &'static str)>,
+    ) -> Self {
+        FrameRateLimiter {
+            limiter: RateLimiter::direct(quota.get_inner()),
+            count_ping_pong,
+            exceeded: false,
+            dry_run,
+        }
+    }
+
+    /// check the new data, and return the length of data that can be forwarded
+    fn check(&mut self, tracker: &mut FrameTracker, data: &[u8]) -> Option<usize> {
+        let limiter = &self.limiter;
+        let count_ping_pong = self.count_ping_pong;
+        if let Some((logger, reason)) = &self.dry_run {
+            // forward all data, and log the first exceeding event only
+            if self.exceeded {
+                tracker.feed(data, |_| true);
+            } else {
+                let mut exceeded = false;
+                tracker.feed(data, |opcode| {
+                    match opcode {
+                        OPCODE_CLOSE => {}
+                        OPCODE_PING | OPCODE_PONG if !count_ping_pong => {}
+                        _ => exceeded |= limiter.check().is_err(),
+                    }
+                    true
+                });
+                if exceeded {
+                    logger.log(reason);
+                    self.exceeded = true;
+                }
+            }
+            return None;
+        }
+        let offset = tracker.feed(data, |opcode| match opcode {
+            OPCODE_CLOSE => true,
+            OPCODE_PING | OPCODE_PONG if !count_ping_pong => true,
+            _ => limiter.check().is_ok(),
+        })?;
+        self.exceeded = true;
+        Some(offset)
+    }
+
+    #[inline]
+    fn blocked(&self) -> bool {
+        self.exceeded && self.dry_run.is_none()
+    }
+}
+
+/// A reader that tracks websocket frames and stops reading when the frame rate limit is exceeded
+pub(super) struct FrameRateLimitReader<R> {
+    inner: R,
+    tracker: FrameTracker,
+    limiter: Option<FrameRateLimiter>,
+}
+
+impl<R> FrameRateLimitReader<R> {
+    /// Create a new reader, the limit will only be logged if `dry_run` is set
+    pub(super) fn new(
+        inner: R,
+        quota: Option<&RateLimitQuotaConfig>,
+        count_ping_pong: bool,
+        dry_run: Option<(DryRunBlockLogger, &'static str)>,
+    ) -> Self {
+        FrameRateLimitReader {
+            inner,
+            tracker: FrameTracker::default(),
+            limiter: quota.map(|quota| FrameRateLimiter::new(quota, count_ping_pong, dry_run)),
+        }
+    }
+
+    /// Check if the data read so far ends at a frame boundary,
+    /// so it's safe to insert a new frame after it
+    pub(super) fn at_frame_boundary(&self) -> bool {
+        self.tracker.at_frame_boundary()
+    }
+
+    pub(super) fn take_close_frame(&mut self) -> Option<CloseFrameInfo> {
+        self.tracker.take_close_frame()
+    }
+
+    pub(super) fn track_ping_pong(&mut self) {
+        self.tracker.track_ping_pong();
+    }
+
+    pub(super) fn take_ping_frames(&mut self) -> Vec<Vec<u8>> {
+        self.tracker.take_ping_frames()
+    }
+
+    pub(super) fn take_pong_frames(&mut self) -> Vec<Vec<u8>> {
+        self.tracker.take_pong_frames()
+    }
+}
+
+impl<R> AsyncRead for FrameRateLimitReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let start = buf.filled().len();
+        let Some(limiter) = &mut this.limiter else {
+            ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+            this.tracker.feed(&buf.filled()[start..], |_| true);
+            return Poll::Ready(Ok(()));
+        };
+        if limiter.blocked() {
+            return Poll::Ready(Err(io::Error::other(FrameRateLimitExceeded)));
+        }
+
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        if let Some(offset) = limiter.check(&mut this.tracker, &buf.filled()[start..]) {
+            if offset == 0 {
+                buf.set_filled(start);
+                return Poll::Ready(Err(io::Error::other(FrameRateLimitExceeded)));
+            }
+            // forward the complete frames first, and return error in the next read
+            buf.set_filled(start + offset);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Relay the websocket frames with the frame rate limit set in the interception config.
+///
+/// The session will be closed with status code 1008 if the limit is exceeded in either direction,
+/// or with status code 1001 if the max task lifetime is reached.
+/// The frame rate limit will only be logged in dry-run mode.
+///
+/// Synthetic pong frames will be sent to the client if enabled in the interception config.
+///
+/// The first close frame received in each direction will be saved to `close_notes`.
+pub(super) async fn transit_with_frame_rate_limit<CR, CW, UR, UW, SC>(
+    clt_r: CR,
+    mut clt_w: CW,
+    ups_r: UR,
+    mut ups_w: UW,
+    ctx: &StreamInspectContext<SC>,
+    close_notes: &mut WebSocketCloseNotes,
+) -> ServerTaskResult<()>
+where
+    CR: AsyncRead + Unpin,
+    CW: AsyncWrite + Unpin,
+    UR: AsyncRead + Unpin,
+    UW: AsyncWrite + Unpin,
+    SC: ServerConfig,
+{
+    const SERVER_CLOSE_BYTES: [u8; 4] = ServerCloseFrame::encode_with_status_code(1008);
+    const CLIENT_CLOSE_BYTES: [u8; 8] = ClientCloseFrame::encode_with_status_code(1008);
+    const SERVER_GOING_AWAY_BYTES: [u8; 4] = ServerCloseFrame::encode_with_status_code(1001);
+    const CLIENT_GOING_AWAY_BYTES: [u8; 8] = ClientCloseFrame::encode_with_status_code(1001);
+
+    let config = ctx.websocket_interception();
+    let dry_run = ctx.dry_run_block_logger("websocket");
+    let clt_r = FrameRateLimitReader::new(
+        clt_r,
+        config.client_frame_rate_limit.as_ref(),
+        config.rate_limit_count_ping_pong,
+        dry_run
+            .clone()
+            .map(|logger| (logger, "client websocket frame rate limit exceeded")),
+    );
+    let ups_r = FrameRateLimitReader::new(
+        ups_r,
+        config.server_frame_rate_limit.as_ref(),
+        config.rate_limit_count_ping_pong,
+        dry_run.map(|logger| (logger, "server websocket frame rate limit exceeded")),
+    );
+    let (mut clt_r, mut ups_r) =
+        super::pong::ping_pong_readers(clt_r, ups_r, config.synthetic_pong_delay);
+
+    let copy_config = ctx.server_config.limited_copy_config();
+    let clt_to_ups = LimitedCopy::new(&mut clt_r, &mut ups_w, &copy_config);
+    let ups_to_clt = LimitedCopy::new(&mut ups_r, &mut clt_w, &copy_config);
+
+    let r = crate::inspect::stream::transit_transparent2(
+        clt_to_ups,
+        ups_to_clt,
+        &ctx.server_config,
+        &ctx.server_quit_policy,
+        ctx.task_deadline(),
+        ctx.user(),
+        ctx.task_max_idle_count(),
+    )
+    .await;
+    close_notes.client = clt_r.take_close_frame();
+    close_notes.server = ups_r.take_close_frame();
+    let (e, clt_close, ups_close) = match r {
+        Err(ServerTaskError::CanceledAsLifetimeExceeded) => (
+            ServerTaskError::CanceledAsLifetimeExceeded,
+            &SERVER_GOING_AWAY_BYTES[..],
+            &CLIENT_GOING_AWAY_BYTES[..],
+        ),
+        Err(ServerTaskError::ClientTcpReadFailed(e))
+            if FrameRateLimitExceeded::is_source_of(&e) =>
+        {
+            (
+                ServerTaskError::ClientAppError(anyhow!(
+                    "client websocket frame rate limit exceeded"
+                )),
+                &SERVER_CLOSE_BYTES[..],
+                &CLIENT_CLOSE_BYTES[..],
+            )
+        }
+        Err(ServerTaskError::UpstreamReadFailed(e)) if FrameRateLimitExceeded::is_source_of(&e) => {
+            (
+                ServerTaskError::UpstreamAppError(anyhow!(
+                    "server websocket frame rate limit exceeded"
+                )),
+                &SERVER_CLOSE_BYTES[..],
+                &CLIENT_CLOSE_BYTES[..],
+            )
+        }
+        r => return r,
+    };
+    // the frame in transit would be broken if we insert the close frame in the middle
+    let clt_close = ups_r.at_frame_boundary().then_some(clt_close);
+    let ups_close = clt_r.at_frame_boundary().then_some(ups_close);
+
+    match ups_close {
+        Some(close) => {
+            if ups_w.write_all_flush(close).await.is_ok() {
+                let _ = ups_w.shutdown().await;
+            }
+        }
+        None => {
+            let _ = ups_w.shutdown().await;
+        }
+    }
+    match clt_close {
+        Some(close) => {
+            if clt_w.write_all_flush(close).await.is_ok() {
+                let _ = clt_w.shutdown().await;
+            }
+        }
+        None => {
+            let _ = clt_w.shutdown().await;
+        }
+    }
+    Err(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU32;
+    use tokio::io::AsyncReadExt;
+
+    fn text_frames(count: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(count * 3);
+        for _ in 0..count {
+            data.extend_from_slice(&[0x81, 0x01, b'a']);
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn burst() {
+        let mut quota = RateLimitQuotaConfig::per_second(NonZeroU32::new(1).unwrap());
+        quota.allow_burst(NonZeroU32::new(4).unwrap());
+
+        let data = text_frames(4);
+        let mut reader = FrameRateLimitReader::new(data.as_slice(), Some(&quota), false, None);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, data);
+
+        let data = text_frames(6);
+        let mut reader = FrameRateLimitReader::new(data.as_slice(), Some(&quota), false, None);
+        let mut buf = [0u8; 64];
+        let len = reader.read(&mut buf).await.unwrap();
+        assert_eq!(len, 12);
+        let e = reader.read(&mut buf).await.unwrap_err();
+        assert!(FrameRateLimitExceeded::is_source_of(&e));
+    }
+
+    #[tokio::test]
+    async fn ping_pong() {
+        let quota = RateLimitQuotaConfig::per_second(NonZeroU32::new(1).unwrap());
+
+        let data = [0x89, 0x00, 0x8A, 0x00, 0x81, 0x01, b'a', 0x88, 0x00];
+        let mut reader = FrameRateLimitReader::new(data.as_slice(), Some(&quota), false, None);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, data);
+
+        let mut reader = FrameRateLimitReader::new(data.as_slice(), Some(&quota), true, None);
+        let mut buf = [0u8; 16];
+        let len = reader.read(&mut buf).await.unwrap();
+        assert_eq!(len, 2);
+        let e = reader.read(&mut buf).await.unwrap_err();
+        assert!(FrameRateLimitExceeded::is_source_of(&e));
+    }
+
+    #[tokio::test]
+    async fn dry_run() {
+        let quota = RateLimitQuotaConfig::per_second(NonZeroU32::new(1).unwrap());
+        let logger = DryRunBlockLogger::discard("websocket");
+
+        let data = text_frames(4);
+        let mut reader = FrameRateLimitReader::new(
+            data.as_slice(),
+            Some(&quota),
+            false,
+            Some((logger, "frame rate limit exceeded")),
+        );
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[tokio::test]
+    async fn no_limit() {
+        let data = text_frames(100);
+        let mut reader = FrameRateLimitReader::new(data.as_slice(), None, true, None);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, data);
+    }
+}
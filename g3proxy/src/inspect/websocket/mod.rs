@@ -15,7 +15,11 @@
  */
 
 mod close;
-use close::{ClientCloseFrame, ServerCloseFrame};
+use close::{ClientCloseFrame, ServerCloseFrame, WebSocketCloseNotes};
+
+mod frame;
+mod limit;
+mod pong;
 
 mod h1;
 pub(crate) use h1::H1WebsocketInterceptObject;
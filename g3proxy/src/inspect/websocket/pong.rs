@@ -0,0 +1,394 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use super::close::CloseFrameInfo;
+use super::frame::OPCODE_PONG;
+use super::limit::FrameRateLimitReader;
+
+/// Max number of client pings that are waiting for the server pong
+const MAX_OUTSTANDING_PINGS: usize = 16;
+
+struct OutstandingPing {
+    payload: Vec<u8>,
+    deadline: Instant,
+}
+
+#[derive(Default)]
+struct PingState {
+    /// the client pings that have not been answered yet
+    pings: VecDeque<OutstandingPing>,
+    /// the payloads of the pings answered by synthetic pongs, the server pongs may still come
+    answered: VecDeque<Vec<u8>>,
+    waker: Option<Waker>,
+}
+
+impl PingState {
+    fn answer_by_server(&mut self, payload: &[u8]) {
+        // the server may only reply to the most recent ping, so all older ones are answered
+        if let Some(i) = self.answered.iter().position(|p| p == payload) {
+            self.answered.drain(..=i);
+        } else if let Some(i) = self.pings.iter().position(|p| p.payload == payload) {
+            self.answered.clear();
+            self.pings.drain(..=i);
+        }
+        // unsolicited pongs are just ignored
+    }
+
+    fn answer_by_proxy(&mut self) -> Option<OutstandingPing> {
+        let ping = self.pings.pop_front()?;
+        if self.answered.len() >= MAX_OUTSTANDING_PINGS {
+            self.answered.pop_front();
+        }
+        self.answered.push_back(ping.payload.clone());
+        Some(ping)
+    }
+}
+
+/// Create the readers that track the client pings and reply synthetic pongs if the server is slow.
+///
+/// The server pongs are always relayed, and are matched to the client pings by the payload.
+/// At most one synthetic pong will be sent for each client ping, and the ping will be dropped from
+/// the queue after that. The server pong to an already answered ping will still be relayed if it
+/// comes late, so the client may see two pongs for one ping, which is allowed by RFC 6455.
+pub(super) fn ping_pong_readers<CR, UR>(
+    mut clt_r: FrameRateLimitReader<CR>,
+    mut ups_r: FrameRateLimitReader<UR>,
+    synthetic_pong_delay: Option<Duration>,
+) -> (ClientPingReader<CR>, ServerPongReader<UR>) {
+    let Some(delay) = synthetic_pong_delay else {
+        return (
+            ClientPingReader {
+                inner: clt_r,
+                state: None,
+            },
+            ServerPongReader {
+                inner: ups_r,
+                synthetic: None,
+            },
+        );
+    };
+
+    clt_r.track_ping_pong();
+    ups_r.track_ping_pong();
+    let state = Arc::new(Mutex::new(PingState::default()));
+    (
+        ClientPingReader {
+            inner: clt_r,
+            state: Some((state.clone(), delay)),
+        },
+        ServerPongReader {
+            inner: ups_r,
+            synthetic: Some(SyntheticPong {
+                state,
+                sleep: Box::pin(tokio::time::sleep(delay)),
+                frame: Vec::new(),
+                frame_offset: 0,
+            }),
+        },
+    )
+}
+
+pub(super) struct ClientPingReader<R> {
+    inner: FrameRateLimitReader<R>,
+    state: Option<(Arc<Mutex<PingState>>, Duration)>,
+}
+
+impl<R> ClientPingReader<R> {
+    pub(super) fn at_frame_boundary(&self) -> bool {
+        self.inner.at_frame_boundary()
+    }
+
+    pub(super) fn take_close_frame(&mut self) -> Option<CloseFrameInfo> {
+        self.inner.take_close_frame()
+    }
+}
+
+impl<R> AsyncRead for ClientPingReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let r = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Some((state, delay)) = &this.state {
+            let pings = this.inner.take_ping_frames();
+            if !pings.is_empty() {
+                let deadline = Instant::now() + *delay;
+                let mut state = state.lock().unwrap();
+                for payload in pings {
+                    if state.pings.len() >= MAX_OUTSTANDING_PINGS {
+                        break;
+                    }
+                    state.pings.push_back(OutstandingPing { payload, deadline });
+                }
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+        r
+    }
+}
+
+struct SyntheticPong {
+    state: Arc<Mutex<PingState>>,
+    sleep: Pin<Box<Sleep>>,
+    frame: Vec<u8>,
+    frame_offset: usize,
+}
+
+impl SyntheticPong {
+    fn in_progress(&self) -> bool {
+        self.frame_offset < self.frame.len()
+    }
+
+    fn write_frame(&mut self, buf: &mut ReadBuf<'_>) {
+        let left = &self.frame[self.frame_offset..];
+        let n = left.len().min(buf.remaining());
+        buf.put_slice(&left[..n]);
+        self.frame_offset += n;
+    }
+
+    fn answer_by_server(&mut self, pongs: Vec<Vec<u8>>) {
+        let mut state = self.state.lock().unwrap();
+        for payload in pongs {
+            state.answer_by_server(&payload);
+        }
+    }
+
+    /// Poll for the next synthetic pong frame when the server has nothing to send
+    fn poll_next_frame(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            let mut state = self.state.lock().unwrap();
+            state.waker = Some(cx.waker().clone());
+            let Some(ping) = state.pings.front() else {
+                return Poll::Pending;
+            };
+            if ping.deadline <= Instant::now() {
+                let ping = state.answer_by_proxy().unwrap();
+                self.frame.clear();
+                self.frame.push(0x80 | OPCODE_PONG);
+                self.frame.push(ping.payload.len() as u8);
+                self.frame.extend_from_slice(&ping.payload);
+                self.frame_offset = 0;
+                return Poll::Ready(());
+            }
+            let deadline = ping.deadline;
+            drop(state);
+
+            self.sleep.as_mut().reset(deadline);
+            if self.sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+pub(super) struct ServerPongReader<R> {
+    inner: FrameRateLimitReader<R>,
+    synthetic: Option<SyntheticPong>,
+}
+
+impl<R> ServerPongReader<R> {
+    pub(super) fn at_frame_boundary(&self) -> bool {
+        let in_progress = self
+            .synthetic
+            .as_ref()
+            .map(|s| s.in_progress())
+            .unwrap_or(false);
+        !in_progress && self.inner.at_frame_boundary()
+    }
+
+    pub(super) fn take_close_frame(&mut self) -> Option<CloseFrameInfo> {
+        self.inner.take_close_frame()
+    }
+}
+
+impl<R> AsyncRead for ServerPongReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let Some(synthetic) = &mut this.synthetic else {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        };
+        if synthetic.in_progress() {
+            synthetic.write_frame(buf);
+            return Poll::Ready(Ok(()));
+        }
+
+        if let Poll::Ready(r) = Pin::new(&mut this.inner).poll_read(cx, buf) {
+            let pongs = this.inner.take_pong_frames();
+            if !pongs.is_empty() {
+                synthetic.answer_by_server(pongs);
+            }
+            return Poll::Ready(r);
+        }
+
+        // the server is slow, the pong can only be inserted between server frames
+        if !this.inner.at_frame_boundary() {
+            return Poll::Pending;
+        }
+        if synthetic.poll_next_frame(cx).is_pending() {
+            return Poll::Pending;
+        }
+        synthetic.write_frame(buf);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const PING_HI: [u8; 8] = [0x89, 0x82, 0x01, 0x02, 0x03, 0x04, b'h' ^ 0x01, b'i' ^ 0x02];
+    const PONG_HI: [u8; 4] = [0x8A, 0x02, b'h', b'i'];
+
+    #[tokio::test]
+    async fn slow_server() {
+        let (_server, ups) = tokio::io::duplex(64);
+        let clt_r = FrameRateLimitReader::new(PING_HI.as_slice(), None, false, None);
+        let ups_r = FrameRateLimitReader::new(ups, None, false, None);
+        let (mut clt_r, mut ups_r) =
+            ping_pong_readers(clt_r, ups_r, Some(Duration::from_millis(10)));
+
+        let mut buf = Vec::new();
+        clt_r.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, PING_HI);
+
+        let mut buf = [0u8; 16];
+        let len = tokio::time::timeout(Duration::from_secs(1), ups_r.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..len], PONG_HI);
+        assert!(ups_r.at_frame_boundary());
+
+        // only one synthetic pong for each ping
+        let r = tokio::time::timeout(Duration::from_millis(50), ups_r.read(&mut buf)).await;
+        assert!(r.is_err());
+    }
+
+    #[tokio::test]
+    async fn server_pong_in_time() {
+        let (mut server, ups) = tokio::io::duplex(64);
+        let clt_r = FrameRateLimitReader::new(PING_HI.as_slice(), None, false, None);
+        let ups_r = FrameRateLimitReader::new(ups, None, false, None);
+        let (mut clt_r, mut ups_r) =
+            ping_pong_readers(clt_r, ups_r, Some(Duration::from_millis(20)));
+
+        let mut buf = Vec::new();
+        clt_r.read_to_end(&mut buf).await.unwrap();
+        server.write_all(&PONG_HI).await.unwrap();
+
+        let mut buf = [0u8; 16];
+        let len = ups_r.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], PONG_HI);
+
+        let r = tokio::time::timeout(Duration::from_millis(50), ups_r.read(&mut buf)).await;
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn ping_state() {
+        let mut state = PingState::default();
+        let deadline = Instant::now();
+        for payload in [b"a", b"b", b"c"] {
+            state.pings.push_back(OutstandingPing {
+                payload: payload.to_vec(),
+                deadline,
+            });
+        }
+
+        // the answered ping is dropped from the queue
+        let ping = state.answer_by_proxy().unwrap();
+        assert_eq!(ping.payload, b"a");
+        assert_eq!(state.pings.len(), 2);
+
+        // unsolicited pong
+        state.answer_by_server(b"x");
+        assert_eq!(state.pings.len(), 2);
+        assert_eq!(state.answered.len(), 1);
+
+        // late server pong for the answered ping
+        state.answer_by_server(b"a");
+        assert!(state.answered.is_empty());
+        assert_eq!(state.pings.len(), 2);
+
+        // reply to the most recent ping only
+        state.answer_by_server(b"c");
+        assert!(state.pings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stalled_server() {
+        let (_server, ups) = tokio::io::duplex(64);
+        let (mut client, clt) = tokio::io::duplex(1024);
+        let clt_r = FrameRateLimitReader::new(clt, None, false, None);
+        let ups_r = FrameRateLimitReader::new(ups, None, false, None);
+        let (mut clt_r, mut ups_r) =
+            ping_pong_readers(clt_r, ups_r, Some(Duration::from_millis(1)));
+
+        let mut buf = [0u8; 16];
+        for _ in 0..MAX_OUTSTANDING_PINGS * 2 {
+            client.write_all(&PING_HI).await.unwrap();
+            let len = clt_r.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..len], PING_HI);
+
+            let len = tokio::time::timeout(Duration::from_secs(1), ups_r.read(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(&buf[..len], PONG_HI);
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let (_server, ups) = tokio::io::duplex(64);
+        let clt_r = FrameRateLimitReader::new(PING_HI.as_slice(), None, false, None);
+        let ups_r = FrameRateLimitReader::new(ups, None, false, None);
+        let (mut clt_r, mut ups_r) = ping_pong_readers(clt_r, ups_r, None);
+
+        let mut buf = Vec::new();
+        clt_r.read_to_end(&mut buf).await.unwrap();
+
+        let mut buf = [0u8; 16];
+        let r = tokio::time::timeout(Duration::from_millis(50), ups_r.read(&mut buf)).await;
+        assert!(r.is_err());
+    }
+}
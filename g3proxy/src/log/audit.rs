@@ -14,10 +14,22 @@
  * limitations under the License.
  */
 
+use std::sync::LazyLock;
+
 use slog::{slog_o, Logger};
+use uuid::Uuid;
 
+use g3_types::log::LogSampler;
 use g3_types::metrics::MetricsName;
 
+static AUDIT_LOG_SAMPLER: LazyLock<LogSampler> =
+    LazyLock::new(|| LogSampler::new(crate::config::log::get_audit_default_config().sampling()));
+
+/// Decide whether the normal events of the task should be logged
+pub(crate) fn sample(task_id: &Uuid) -> bool {
+    AUDIT_LOG_SAMPLER.sample(task_id.as_bytes())
+}
+
 pub(super) fn get_logger(log_type: &'static str, auditor_name: &MetricsName) -> Logger {
     let config = crate::config::log::get_audit_default_config();
     let logger_name = format!("la-{auditor_name}");
@@ -28,6 +28,10 @@ pub(crate) struct EscapeLogForTcpConnect<'a> {
 
 impl EscapeLogForTcpConnect<'_> {
     pub(crate) fn log(&self, logger: &Logger, e: &TcpConnectError) {
+        let connect_error = match e {
+            TcpConnectError::ConnectFailed(e) => Some(e),
+            _ => None,
+        };
         slog_info!(logger, "{}", e;
             "escape_type" => "TcpConnect",
             "task_id" => LtUuid(self.task_id),
@@ -38,7 +42,10 @@ impl EscapeLogForTcpConnect<'_> {
             "next_expire" => self.tcp_notes.expire.as_ref().map(LtDateTime),
             "tcp_connect_tries" => self.tcp_notes.tries,
             "tcp_connect_spend" => LtDuration(self.tcp_notes.duration),
+            "tcp_connect_try_spend" => LtDuration(self.tcp_notes.try_duration),
             "reason" => e.brief(),
+            "connect_error" => connect_error.map(|e| e.brief()),
+            "os_error" => connect_error.and_then(|e| e.raw_os_error()),
         )
     }
 }
@@ -40,6 +40,9 @@ pub(crate) struct TaskLogForFtpOverHttp<'a> {
 
 impl TaskLogForFtpOverHttp<'_> {
     pub(crate) fn log(&self, logger: &Logger, e: &ServerTaskError) {
+        if e.is_normal_end() && !self.task_notes.log_sampled() {
+            return;
+        }
         if let Some(user_ctx) = self.task_notes.user_ctx() {
             if user_ctx.skip_log() {
                 return;
@@ -52,6 +55,7 @@ impl TaskLogForFtpOverHttp<'_> {
             "stage" => self.task_notes.stage.brief(),
             "start_at" => LtDateTime(&self.task_notes.start_at),
             "user" => self.task_notes.raw_user_name(),
+            "tenant" => self.task_notes.tenant(),
             "server_addr" => self.task_notes.server_addr(),
             "client_addr" => self.task_notes.client_addr(),
             "upstream" => LtUpstreamAddr(self.ftp_notes.upstream()),
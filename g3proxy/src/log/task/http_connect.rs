@@ -0,0 +1,77 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use slog::{slog_info, Logger};
+
+use g3_slog_types::{LtDateTime, LtDuration, LtUpstreamAddr, LtUuid};
+
+use crate::inspect::StreamInspectSummary;
+use crate::module::tcp_connect::TcpConnectTaskNotes;
+use crate::serve::{ServerTaskError, ServerTaskNotes};
+
+/// Log emitted once per http CONNECT tunnel, when the connect phase failed or the tunnel ends.
+pub(crate) struct TaskLogForHttpConnect<'a> {
+    pub(crate) task_notes: &'a ServerTaskNotes,
+    pub(crate) tcp_notes: &'a TcpConnectTaskNotes,
+    pub(crate) inspect_summary: Option<&'a StreamInspectSummary>,
+}
+
+impl TaskLogForHttpConnect<'_> {
+    pub(crate) fn log(&self, logger: &Logger, e: Option<&ServerTaskError>) {
+        if !self.task_notes.log_sampled() && e.map(|e| e.is_normal_end()).unwrap_or(true) {
+            return;
+        }
+        if let Some(user_ctx) = self.task_notes.user_ctx() {
+            if user_ctx.skip_log() {
+                return;
+            }
+        }
+
+        let msg = e.map(|e| e.to_string());
+        let reason = e.map(|e| e.brief()).unwrap_or("Connected");
+        let (depth, tls_interception, tls_intercepted) = self
+            .inspect_summary
+            .map(|s| {
+                (
+                    s.max_depth(),
+                    s.tls_interception_attempted(),
+                    s.tls_interception_succeeded(),
+                )
+            })
+            .unwrap_or_default();
+        slog_info!(logger, "{}", msg.as_deref().unwrap_or("ok");
+            "task_type" => "HttpConnect",
+            "task_id" => LtUuid(&self.task_notes.id),
+            "stage" => self.task_notes.stage.brief(),
+            "start_at" => LtDateTime(&self.task_notes.start_at),
+            "user" => self.task_notes.raw_user_name(),
+            "tenant" => self.task_notes.tenant(),
+            "server_addr" => self.task_notes.server_addr(),
+            "client_addr" => self.task_notes.client_addr(),
+            "upstream" => LtUpstreamAddr(&self.tcp_notes.upstream),
+            "depth" => depth,
+            "escaper" => self.tcp_notes.escaper.as_str(),
+            "next_peer_addr" => self.tcp_notes.next,
+            "tcp_connect_tries" => self.tcp_notes.tries,
+            "tcp_connect_spend" => LtDuration(self.tcp_notes.duration),
+            "tls_interception" => tls_interception,
+            "tls_intercepted" => tls_intercepted,
+            "reason" => reason,
+            "wait_time" => LtDuration(self.task_notes.wait_time),
+            "total_time" => LtDuration(self.task_notes.time_elapsed()),
+        )
+    }
+}
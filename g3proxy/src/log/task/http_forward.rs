@@ -40,6 +40,9 @@ pub(crate) struct TaskLogForHttpForward<'a> {
 
 impl TaskLogForHttpForward<'_> {
     pub(crate) fn log(&self, logger: &Logger, e: &ServerTaskError) {
+        if e.is_normal_end() && !self.task_notes.log_sampled() {
+            return;
+        }
         if let Some(user_ctx) = self.task_notes.user_ctx() {
             if user_ctx.skip_log() {
                 return;
@@ -52,6 +55,7 @@ impl TaskLogForHttpForward<'_> {
             "stage" => self.task_notes.stage.brief(),
             "start_at" => LtDateTime(&self.task_notes.start_at),
             "user" => self.task_notes.raw_user_name(),
+            "tenant" => self.task_notes.tenant(),
             "server_addr" => self.task_notes.server_addr(),
             "client_addr" => self.task_notes.client_addr(),
             "upstream" => LtUpstreamAddr(&self.tcp_notes.upstream),
@@ -65,6 +69,9 @@ impl TaskLogForHttpForward<'_> {
             "reason" => e.brief(),
             "pipeline_wait" => LtDuration(self.http_notes.pipeline_wait),
             "reuse_connection" => self.http_notes.reuse_connection,
+            "ups_conn_reused" => self.http_notes.ups_conn_reused,
+            "ups_conn_age_ms" => self.http_notes.ups_conn_age.as_millis() as u64,
+            "ups_conn_req_seq" => self.http_notes.ups_conn_req_seq,
             "method" => LtHttpMethod(&self.http_notes.method),
             "uri" => LtHttpUri::new(&self.http_notes.uri, self.http_notes.uri_log_max_chars),
             "user_agent" => self.http_user_agent,
@@ -14,11 +14,16 @@
  * limitations under the License.
  */
 
+use std::sync::LazyLock;
+
 use slog::{slog_o, Logger};
+use uuid::Uuid;
 
+use g3_types::log::LogSampler;
 use g3_types::metrics::MetricsName;
 
 pub(crate) mod ftp_over_http;
+pub(crate) mod http_connect;
 pub(crate) mod http_forward;
 pub(crate) mod tcp_connect;
 pub(crate) mod udp_associate;
@@ -26,6 +31,14 @@ pub(crate) mod udp_connect;
 
 use super::shared::SharedLoggerType;
 
+static TASK_LOG_SAMPLER: LazyLock<LogSampler> =
+    LazyLock::new(|| LogSampler::new(crate::config::log::get_task_default_config().sampling()));
+
+/// Decide whether the normal events of the task should be logged
+pub(crate) fn sample(task_id: &Uuid) -> bool {
+    TASK_LOG_SAMPLER.sample(task_id.as_bytes())
+}
+
 pub(crate) fn get_logger(server_type: &str, server_name: &MetricsName) -> Logger {
     let config = crate::config::log::get_task_default_config();
     let logger_name = format!("lt-{server_name}");
@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::fmt;
 use std::time::Duration;
 
 use slog::{slog_info, Logger};
@@ -35,18 +36,35 @@ pub(crate) struct TaskLogForTcpConnect<'a> {
 
 impl TaskLogForTcpConnect<'_> {
     pub(crate) fn log(&self, logger: &Logger, e: &ServerTaskError) {
+        if e.is_normal_end() && !self.task_notes.log_sampled() {
+            return;
+        }
+        self.log_event(logger, e, e.brief());
+    }
+
+    /// Emit a preliminary log when the relay starts, the final log will be
+    /// emitted by [`Self::log`] with the same task id when the task ends.
+    pub(crate) fn log_connected(&self, logger: &Logger) {
+        if !self.task_notes.log_sampled() {
+            return;
+        }
+        self.log_event(logger, "connected", "Connected");
+    }
+
+    fn log_event<M: fmt::Display>(&self, logger: &Logger, msg: M, reason: &str) {
         if let Some(user_ctx) = self.task_notes.user_ctx() {
             if user_ctx.skip_log() {
                 return;
             }
         }
 
-        slog_info!(logger, "{}", e;
+        slog_info!(logger, "{}", msg;
             "task_type" => "TcpConnect",
             "task_id" => LtUuid(&self.task_notes.id),
             "stage" => self.task_notes.stage.brief(),
             "start_at" => LtDateTime(&self.task_notes.start_at),
             "user" => self.task_notes.raw_user_name(),
+            "tenant" => self.task_notes.tenant(),
             "server_addr" => self.task_notes.server_addr(),
             "client_addr" => self.task_notes.client_addr(),
             "upstream" => LtUpstreamAddr(&self.tcp_notes.upstream),
@@ -57,7 +75,7 @@ impl TaskLogForTcpConnect<'_> {
             "next_expire" => self.tcp_notes.expire.as_ref().map(LtDateTime),
             "tcp_connect_tries" => self.tcp_notes.tries,
             "tcp_connect_spend" => LtDuration(self.tcp_notes.duration),
-            "reason" => e.brief(),
+            "reason" => reason,
             "wait_time" => LtDuration(self.task_notes.wait_time),
             "ready_time" => LtDuration(self.task_notes.ready_time),
             "total_time" => LtDuration(self.total_time),
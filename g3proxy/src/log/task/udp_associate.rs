@@ -40,10 +40,14 @@ pub(crate) struct TaskLogForUdpAssociate<'a> {
     pub(crate) remote_rd_packets: u64,
     pub(crate) remote_wr_bytes: u64,
     pub(crate) remote_wr_packets: u64,
+    pub(crate) dest_denied_packets: Option<u64>,
 }
 
 impl TaskLogForUdpAssociate<'_> {
     pub(crate) fn log(&self, logger: &Logger, e: &ServerTaskError) {
+        if e.is_normal_end() && !self.task_notes.log_sampled() {
+            return;
+        }
         if let Some(user_ctx) = self.task_notes.user_ctx() {
             if user_ctx.skip_log() {
                 return;
@@ -56,6 +60,7 @@ impl TaskLogForUdpAssociate<'_> {
             "stage" => self.task_notes.stage.brief(),
             "start_at" => LtDateTime(&self.task_notes.start_at),
             "user" => self.task_notes.raw_user_name(),
+            "tenant" => self.task_notes.tenant(),
             "tcp_server_addr" => self.tcp_server_addr,
             "tcp_client_addr" => self.tcp_client_addr,
             "udp_listen_addr" => self.udp_listen_addr,
@@ -74,6 +79,7 @@ impl TaskLogForUdpAssociate<'_> {
             "r_rd_packets" => self.remote_rd_packets,
             "r_wr_bytes" => self.remote_wr_bytes,
             "r_wr_packets" => self.remote_wr_packets,
+            "dest_denied_packets" => self.dest_denied_packets,
         )
     }
 }
@@ -44,6 +44,9 @@ pub(crate) struct TaskLogForUdpConnect<'a> {
 
 impl TaskLogForUdpConnect<'_> {
     pub(crate) fn log(&self, logger: &Logger, e: &ServerTaskError) {
+        if e.is_normal_end() && !self.task_notes.log_sampled() {
+            return;
+        }
         if let Some(user_ctx) = self.task_notes.user_ctx() {
             if user_ctx.skip_log() {
                 return;
@@ -56,6 +59,7 @@ impl TaskLogForUdpConnect<'_> {
             "stage" => self.task_notes.stage.brief(),
             "start_at" => LtDateTime(&self.task_notes.start_at),
             "user" => self.task_notes.raw_user_name(),
+            "tenant" => self.task_notes.tenant(),
             "tcp_server_addr" => self.tcp_server_addr,
             "tcp_client_addr" => self.tcp_client_addr,
             "udp_listen_addr" => self.udp_listen_addr,
@@ -184,5 +184,8 @@ async fn load_and_spawn() -> anyhow::Result<()> {
     g3proxy::serve::spawn_all()
         .await
         .context("failed to spawn all servers")?;
+    g3proxy::stat::spawn_status_page()
+        .await
+        .context("failed to spawn status page")?;
     Ok(())
 }
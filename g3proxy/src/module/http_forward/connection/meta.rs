@@ -0,0 +1,71 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use tokio::time::{Duration, Instant};
+
+/// Metadata of an upstream connection, which will be kept along with the connection when reused
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct HttpForwardConnectionMeta {
+    created: Instant,
+    req_count: u64,
+}
+
+impl HttpForwardConnectionMeta {
+    pub(crate) fn new() -> Self {
+        HttpForwardConnectionMeta {
+            created: Instant::now(),
+            req_count: 0,
+        }
+    }
+
+    /// Should be called each time a new request is going to be sent on this connection
+    pub(crate) fn mark_new_request(&mut self) {
+        self.req_count += 1;
+    }
+
+    #[inline]
+    pub(crate) fn reused(&self) -> bool {
+        self.req_count > 1
+    }
+
+    /// The sequence number of the current request on this connection, starting from 1
+    #[inline]
+    pub(crate) fn req_seq(&self) -> u64 {
+        self.req_count
+    }
+
+    #[inline]
+    pub(crate) fn age(&self) -> Duration {
+        self.created.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse() {
+        let mut meta = HttpForwardConnectionMeta::new();
+        meta.mark_new_request();
+        assert!(!meta.reused());
+        assert_eq!(meta.req_seq(), 1);
+
+        meta.mark_new_request();
+        assert!(meta.reused());
+        assert_eq!(meta.req_seq(), 2);
+    }
+}
@@ -38,13 +38,17 @@ pub(crate) use writer::{send_req_header_to_origin, send_req_header_via_proxy};
 mod eof_poller;
 pub(crate) use eof_poller::HttpConnectionEofPoller;
 
+mod meta;
+pub(crate) use meta::HttpForwardConnectionMeta;
+
 pub(crate) type BoxHttpForwardWriter = Box<dyn HttpForwardWrite + Send + Unpin>;
 pub(crate) type BoxHttpForwardReader = Box<dyn HttpForwardRead + Send + Unpin>;
 pub(crate) type BoxHttpForwardConnection = (BoxHttpForwardWriter, BoxHttpForwardReader);
 
 #[async_trait]
 pub(crate) trait HttpForwardWrite: AsyncWrite {
-    fn prepare_new(&mut self, task_notes: &ServerTaskNotes, upstream: &UpstreamAddr);
+    fn prepare_new(&mut self, task_notes: &ServerTaskNotes, upstream: &UpstreamAddr, reused: bool);
+    fn connection_meta(&self) -> &HttpForwardConnectionMeta;
     fn update_stats(
         &mut self,
         task_stats: &ArcHttpForwardTaskRemoteStats,
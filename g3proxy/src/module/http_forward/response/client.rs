@@ -275,6 +275,11 @@ impl HttpProxyClientResponse {
             TcpConnectError::TimeoutByRule => {
                 HttpProxyClientResponse::from_standard(StatusCode::GATEWAY_TIMEOUT, version, close)
             }
+            TcpConnectError::UpstreamConnLimitReached => HttpProxyClientResponse::from_standard(
+                StatusCode::SERVICE_UNAVAILABLE,
+                version,
+                close,
+            ),
             TcpConnectError::NoAddressConnected => {
                 HttpProxyClientResponse::from_standard(StatusCode::BAD_GATEWAY, version, close)
             }
@@ -373,6 +378,11 @@ impl HttpProxyClientResponse {
             ServerTaskError::UpstreamNotAvailable => {
                 HttpProxyClientResponse::from_standard(StatusCode::BAD_GATEWAY, version, close)
             }
+            ServerTaskError::UpstreamConnLimitReached => HttpProxyClientResponse::from_standard(
+                StatusCode::SERVICE_UNAVAILABLE,
+                version,
+                close,
+            ),
             ServerTaskError::InvalidUpstreamProtocol(_) => {
                 HttpProxyClientResponse::from_standard(StatusCode::BAD_GATEWAY, version, true)
             }
@@ -417,7 +427,9 @@ impl HttpProxyClientResponse {
             | ServerTaskError::ClosedByClient
             | ServerTaskError::ClosedEarlyByClient
             | ServerTaskError::Idle(_, _)
+            | ServerTaskError::CanceledAsLifetimeExceeded
             | ServerTaskError::InterceptionError(_, _)
+            | ServerTaskError::InspectionDepthExceeded(_)
             | ServerTaskError::Finished => return None,
         };
         Some(r)
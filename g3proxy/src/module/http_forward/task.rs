@@ -17,6 +17,8 @@
 use http::{Method, Uri};
 use tokio::time::{Duration, Instant};
 
+use super::HttpForwardConnectionMeta;
+
 pub(crate) struct HttpForwardTaskNotes {
     pub(crate) method: Method,
     pub(crate) uri: Uri,
@@ -25,6 +27,9 @@ pub(crate) struct HttpForwardTaskNotes {
     pub(crate) origin_status: u16,
     pub(crate) pipeline_wait: Duration,
     pub(crate) reuse_connection: bool,
+    pub(crate) ups_conn_reused: bool,
+    pub(crate) ups_conn_age: Duration,
+    pub(crate) ups_conn_req_seq: u64,
     create_ins: Instant,
     pub(crate) dur_req_send_hdr: Duration,
     pub(crate) dur_req_send_all: Duration,
@@ -49,6 +54,9 @@ impl HttpForwardTaskNotes {
             origin_status: 0,
             pipeline_wait: req_received.elapsed(),
             reuse_connection: false,
+            ups_conn_reused: false,
+            ups_conn_age: Duration::default(),
+            ups_conn_req_seq: 0,
             create_ins: task_created,
             dur_req_send_hdr: Duration::default(),
             dur_req_send_all: Duration::default(),
@@ -58,6 +66,12 @@ impl HttpForwardTaskNotes {
         }
     }
 
+    pub(crate) fn set_ups_conn_meta(&mut self, meta: &HttpForwardConnectionMeta) {
+        self.ups_conn_reused = meta.reused();
+        self.ups_conn_age = meta.age();
+        self.ups_conn_req_seq = meta.req_seq();
+    }
+
     pub(crate) fn mark_req_send_hdr(&mut self) {
         self.dur_req_send_hdr = self.create_ins.elapsed();
     }
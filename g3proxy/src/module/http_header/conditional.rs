@@ -0,0 +1,574 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use http::{HeaderName, Method};
+use serde_json::Value;
+use yaml_rust::Yaml;
+
+use g3_types::net::{
+    Host, HttpHeaderMap, HttpHeaderMergeMode, HttpHeaderStack, HttpHeaderValue, UpstreamAddr,
+};
+
+/// Extra headers that will only be appended to matched forward requests.
+#[derive(Clone, Default, PartialEq)]
+pub(crate) struct ConditionalAppendHeaders {
+    host: Option<String>,
+    path_prefix: Option<String>,
+    method: Option<Method>,
+    mode: HttpHeaderMergeMode,
+    headers: HttpHeaderMap,
+}
+
+impl ConditionalAppendHeaders {
+    pub(crate) fn parse_json(v: &Value) -> anyhow::Result<Self> {
+        let Value::Object(map) = v else {
+            return Err(anyhow!("invalid json object value"));
+        };
+
+        let mut headers = ConditionalAppendHeaders::default();
+        for (k, v) in map {
+            match g3_json::key::normalize(k).as_str() {
+                "host" => {
+                    let host = g3_json::value::as_string(v)
+                        .context(format!("invalid string value for key {k}"))?;
+                    headers.set_host(&host);
+                }
+                "path" | "path_prefix" => {
+                    let path = g3_json::value::as_string(v)
+                        .context(format!("invalid string value for key {k}"))?;
+                    headers.set_path_prefix(path)?;
+                }
+                "method" => {
+                    let method = g3_json::value::as_string(v)
+                        .context(format!("invalid string value for key {k}"))?;
+                    headers.set_method(&method)?;
+                }
+                "mode" => {
+                    let mode = g3_json::value::as_string(v)
+                        .context(format!("invalid string value for key {k}"))?;
+                    headers.set_mode(&mode)?;
+                }
+                "headers" => {
+                    let Value::Object(map) = v else {
+                        return Err(anyhow!("invalid map value for key {k}"));
+                    };
+                    for (name, value) in map {
+                        let value = g3_json::value::as_ascii(value).context(format!(
+                            "invalid ascii string value for extra header {name}"
+                        ))?;
+                        headers.add_header(name, value.as_str())?;
+                    }
+                }
+                _ => return Err(anyhow!("invalid key {k}")),
+            }
+        }
+
+        headers.check()?;
+        Ok(headers)
+    }
+
+    /// Parse a single map or a sequence of maps
+    pub(crate) fn parse_json_list(v: &Value) -> anyhow::Result<Vec<Self>> {
+        if let Value::Array(seq) = v {
+            let mut list = Vec::with_capacity(seq.len());
+            for (i, v) in seq.iter().enumerate() {
+                let headers = ConditionalAppendHeaders::parse_json(v)
+                    .context(format!("invalid conditional append headers value #{i}"))?;
+                list.push(headers);
+            }
+            Ok(list)
+        } else {
+            let headers = ConditionalAppendHeaders::parse_json(v)?;
+            Ok(vec![headers])
+        }
+    }
+
+    pub(crate) fn parse_yaml(v: &Yaml) -> anyhow::Result<Self> {
+        let Yaml::Hash(map) = v else {
+            return Err(anyhow!("invalid yaml map value"));
+        };
+
+        let mut headers = ConditionalAppendHeaders::default();
+        g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "host" => {
+                let host = g3_yaml::value::as_string(v)
+                    .context(format!("invalid string value for key {k}"))?;
+                headers.set_host(&host);
+                Ok(())
+            }
+            "path" | "path_prefix" => {
+                let path = g3_yaml::value::as_string(v)
+                    .context(format!("invalid string value for key {k}"))?;
+                headers.set_path_prefix(path)
+            }
+            "method" => {
+                let method = g3_yaml::value::as_string(v)
+                    .context(format!("invalid string value for key {k}"))?;
+                headers.set_method(&method)
+            }
+            "mode" => {
+                let mode = g3_yaml::value::as_string(v)
+                    .context(format!("invalid string value for key {k}"))?;
+                headers.set_mode(&mode)
+            }
+            "headers" => {
+                let Yaml::Hash(map) = v else {
+                    return Err(anyhow!("invalid map value for key {k}"));
+                };
+                g3_yaml::foreach_kv(map, |name, value| {
+                    let value = g3_yaml::value::as_ascii(value).context(format!(
+                        "invalid ascii string value for extra header {name}"
+                    ))?;
+                    headers.add_header(name, value.as_str())
+                })
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+
+        headers.check()?;
+        Ok(headers)
+    }
+
+    /// Parse a single map or a sequence of maps
+    pub(crate) fn parse_yaml_list(v: &Yaml) -> anyhow::Result<Vec<Self>> {
+        if let Yaml::Array(seq) = v {
+            let mut list = Vec::with_capacity(seq.len());
+            for (i, v) in seq.iter().enumerate() {
+                let headers = ConditionalAppendHeaders::parse_yaml(v)
+                    .context(format!("invalid conditional append headers value #{i}"))?;
+                list.push(headers);
+            }
+            Ok(list)
+        } else {
+            let headers = ConditionalAppendHeaders::parse_yaml(v)?;
+            Ok(vec![headers])
+        }
+    }
+
+    fn set_host(&mut self, host: &str) {
+        self.host = Some(host.to_ascii_lowercase());
+    }
+
+    fn set_path_prefix(&mut self, path: String) -> anyhow::Result<()> {
+        if !path.starts_with('/') {
+            return Err(anyhow!("the path prefix should start with '/'"));
+        }
+        self.path_prefix = Some(path);
+        Ok(())
+    }
+
+    fn set_method(&mut self, method: &str) -> anyhow::Result<()> {
+        let method = Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+            .map_err(|e| anyhow!("invalid http method {method}: {e}"))?;
+        self.method = Some(method);
+        Ok(())
+    }
+
+    fn set_mode(&mut self, mode: &str) -> anyhow::Result<()> {
+        self.mode = HttpHeaderMergeMode::from_str(mode)
+            .map_err(|_| anyhow!("invalid header merge mode {mode}"))?;
+        Ok(())
+    }
+
+    fn add_header(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        let header_name =
+            HeaderName::from_str(name).map_err(|e| anyhow!("invalid header name {name}: {e}"))?;
+        let mut header_value = HttpHeaderValue::from_str(value)
+            .map_err(|_| anyhow!("invalid value for extra header {name}"))?;
+        header_value.set_original_name(name);
+        self.headers.append(header_name, header_value);
+        Ok(())
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        if self.headers.is_empty() {
+            return Err(anyhow!("no headers set"));
+        }
+        Ok(())
+    }
+
+    fn match_host(&self, upstream: &UpstreamAddr) -> bool {
+        let Some(expected) = &self.host else {
+            return true;
+        };
+        match upstream.host() {
+            Host::Domain(domain) => {
+                let domain = domain.to_ascii_lowercase();
+                if let Some(suffix) = expected.strip_prefix('.') {
+                    domain
+                        .strip_suffix(suffix)
+                        .map(|prefix| prefix.ends_with('.'))
+                        .unwrap_or(false)
+                } else {
+                    domain.eq(expected)
+                }
+            }
+            Host::Ip(ip) => ip.to_string().eq(expected),
+        }
+    }
+
+    fn match_request(&self, method: &Method, path: &str) -> bool {
+        if let Some(expected) = &self.method {
+            if method.ne(expected) {
+                return false;
+            }
+        }
+        if let Some(prefix) = &self.path_prefix {
+            if !path.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Headers that will be appended to all requests sent to the next proxy.
+///
+/// The raw lines are used as is in CONNECT requests, and the map parsed at config load is
+/// used as the base layer when merging with conditional headers for forward requests.
+#[derive(Clone, Default, PartialEq)]
+pub(crate) struct StaticAppendHeaders {
+    lines: Vec<String>,
+    map: HttpHeaderMap,
+}
+
+impl StaticAppendHeaders {
+    #[inline]
+    pub(crate) fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Add a header line which should end with CRLF
+    pub(crate) fn push_line(&mut self, line: String) -> anyhow::Result<()> {
+        let (name, value) =
+            parse_header_line(&line).ok_or_else(|| anyhow!("invalid header line {line:?}"))?;
+        self.map.append(name, value);
+        self.lines.push(line);
+        Ok(())
+    }
+
+    pub(crate) fn push_header(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        self.push_line(format!("{name}: {value}\r\n"))
+    }
+}
+
+/// Merge the matched conditional headers in order on top of the static headers.
+///
+/// The layers should be given from the lowest precedence to the highest, which is the escaper
+/// layer then the user layer. The merge mode of each matched one decides whether its headers
+/// override or append to the headers set before it, including the static ones. The order of the
+/// headers will be kept.
+pub(crate) fn merge_header_lines<'a>(
+    base: &'a StaticAppendHeaders,
+    layers: &[&[ConditionalAppendHeaders]],
+    method: &Method,
+    path: &str,
+    upstream: &UpstreamAddr,
+) -> Cow<'a, [String]> {
+    let mut matched = layers
+        .iter()
+        .flat_map(|layer| layer.iter())
+        .filter(|headers| headers.match_host(upstream) && headers.match_request(method, path))
+        .peekable();
+    if matched.peek().is_none() {
+        return Cow::Borrowed(&base.lines);
+    }
+
+    let mut stack = HttpHeaderStack::default();
+    stack.push(HttpHeaderMergeMode::Append, &base.map);
+    for headers in matched {
+        stack.push(headers.mode, &headers.headers);
+    }
+    let merged = stack.merge();
+    let mut lines = Vec::with_capacity(merged.len());
+    for (name, value) in merged {
+        let name = value.original_name().unwrap_or(name.as_str());
+        lines.push(format!("{name}: {}\r\n", value.to_str()));
+    }
+    Cow::Owned(lines)
+}
+
+fn parse_header_line(line: &str) -> Option<(HeaderName, HttpHeaderValue)> {
+    let (name, value) = line.split_once(':')?;
+    let header_name = HeaderName::from_str(name).ok()?;
+    let mut header_value = HttpHeaderValue::from_str(value.trim()).ok()?;
+    header_value.set_original_name(name);
+    Some((header_name, header_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse() {
+        let v = json!({"host": ".Example.com", "method": "post", "path": "/api", "headers": {"X-Auth": "abc"}});
+        let headers = ConditionalAppendHeaders::parse_json(&v).unwrap();
+        assert_eq!(headers.host.as_deref(), Some(".example.com"));
+        assert_eq!(headers.method, Some(Method::POST));
+        assert_eq!(headers.path_prefix.as_deref(), Some("/api"));
+        assert_eq!(headers.mode, HttpHeaderMergeMode::Append);
+        let value = headers.headers.get("x-auth").unwrap();
+        assert_eq!(value.original_name(), Some("X-Auth"));
+        assert_eq!(value.to_str(), "abc");
+
+        let v = json!({"mode": "set", "headers": {"X-Auth": "abc"}});
+        let headers = ConditionalAppendHeaders::parse_json(&v).unwrap();
+        assert_eq!(headers.mode, HttpHeaderMergeMode::Set);
+
+        let v = json!({"mode": "remove", "headers": {"X-Auth": "abc"}});
+        assert!(ConditionalAppendHeaders::parse_json(&v).is_err());
+
+        let v = json!({"host": "example.com"});
+        assert!(ConditionalAppendHeaders::parse_json(&v).is_err());
+
+        let v = json!({"path": "api", "headers": {"X-Auth": "abc"}});
+        assert!(ConditionalAppendHeaders::parse_json(&v).is_err());
+    }
+
+    #[test]
+    fn match_host() {
+        let v = json!({"host": ".example.com", "headers": {"X-Auth": "abc"}});
+        let headers = ConditionalAppendHeaders::parse_json(&v).unwrap();
+        assert!(headers
+            .match_host(&UpstreamAddr::from_host_str_and_port("www.example.com", 80).unwrap()));
+        assert!(headers
+            .match_host(&UpstreamAddr::from_host_str_and_port("a.b.EXAMPLE.com", 80).unwrap()));
+        assert!(
+            !headers.match_host(&UpstreamAddr::from_host_str_and_port("example.com", 80).unwrap())
+        );
+        assert!(!headers
+            .match_host(&UpstreamAddr::from_host_str_and_port("wwwexample.com", 80).unwrap()));
+
+        let v = json!({"host": "example.com", "headers": {"X-Auth": "abc"}});
+        let headers = ConditionalAppendHeaders::parse_json(&v).unwrap();
+        assert!(
+            headers.match_host(&UpstreamAddr::from_host_str_and_port("example.com", 443).unwrap())
+        );
+        assert!(!headers
+            .match_host(&UpstreamAddr::from_host_str_and_port("www.example.com", 443).unwrap()));
+
+        let v = json!({"host": "192.168.1.1", "headers": {"X-Auth": "abc"}});
+        let headers = ConditionalAppendHeaders::parse_json(&v).unwrap();
+        assert!(
+            headers.match_host(&UpstreamAddr::from_host_str_and_port("192.168.1.1", 80).unwrap())
+        );
+        assert!(
+            !headers.match_host(&UpstreamAddr::from_host_str_and_port("192.168.1.2", 80).unwrap())
+        );
+    }
+
+    #[test]
+    fn match_request() {
+        let v = json!({"method": "GET", "path": "/api/", "headers": {"X-Auth": "abc"}});
+        let headers = ConditionalAppendHeaders::parse_json(&v).unwrap();
+        assert!(headers.match_request(&Method::GET, "/api/v1"));
+        assert!(!headers.match_request(&Method::POST, "/api/v1"));
+        assert!(!headers.match_request(&Method::GET, "/index.html"));
+
+        let v = json!({"host": "example.com", "headers": {"X-Auth": "abc"}});
+        let headers = ConditionalAppendHeaders::parse_json(&v).unwrap();
+        assert!(headers.match_request(&Method::PUT, "/"));
+    }
+
+    fn static_headers(lines: &[&str]) -> StaticAppendHeaders {
+        let mut headers = StaticAppendHeaders::default();
+        for line in lines {
+            headers.push_line(line.to_string()).unwrap();
+        }
+        headers
+    }
+
+    #[test]
+    fn static_lines() {
+        let mut headers = StaticAppendHeaders::default();
+        headers.push_header("X-Static", "1").unwrap();
+        headers
+            .push_line("Proxy-Authorization: Basic dTpw\r\n".to_string())
+            .unwrap();
+        assert_eq!(
+            headers.lines(),
+            &["X-Static: 1\r\n", "Proxy-Authorization: Basic dTpw\r\n"]
+        );
+        let value = headers.map.get("proxy-authorization").unwrap();
+        assert_eq!(value.to_str(), "Basic dTpw");
+
+        assert!(headers.push_line("no colon\r\n".to_string()).is_err());
+        assert!(headers.push_header("X Bad", "1").is_err());
+        assert_eq!(headers.lines().len(), 2);
+    }
+
+    #[test]
+    fn merge() {
+        let base = static_headers(&["Proxy-Authorization: Basic dTpw\r\n"]);
+        let conditional = vec![
+            ConditionalAppendHeaders::parse_json(
+                &json!({"host": ".example.com", "headers": {"X-Auth": "abc"}}),
+            )
+            .unwrap(),
+            ConditionalAppendHeaders::parse_json(
+                &json!({"method": "POST", "headers": {"X-Post": "1"}}),
+            )
+            .unwrap(),
+        ];
+
+        let upstream = UpstreamAddr::from_host_str_and_port("www.example.com", 80).unwrap();
+        let lines = merge_header_lines(&base, &[&conditional], &Method::GET, "/", &upstream);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], base.lines()[0]);
+        assert_eq!(lines[1], "X-Auth: abc\r\n");
+
+        let lines = merge_header_lines(&base, &[&conditional], &Method::POST, "/", &upstream);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2], "X-Post: 1\r\n");
+
+        let upstream = UpstreamAddr::from_host_str_and_port("www.example.net", 80).unwrap();
+        let lines = merge_header_lines(&base, &[&conditional, &[]], &Method::GET, "/", &upstream);
+        assert!(matches!(lines, Cow::Borrowed(_)));
+        assert_eq!(lines.as_ref(), base.lines());
+    }
+
+    #[test]
+    fn merge_mode() {
+        let base = StaticAppendHeaders::default();
+        let conditional = vec![
+            ConditionalAppendHeaders::parse_json(&json!({"headers": {"X-Tag": "a"}})).unwrap(),
+            ConditionalAppendHeaders::parse_json(
+                &json!({"host": ".example.com", "headers": {"X-Tag": "b"}}),
+            )
+            .unwrap(),
+            ConditionalAppendHeaders::parse_json(
+                &json!({"method": "POST", "mode": "set", "headers": {"X-Tag": "c"}}),
+            )
+            .unwrap(),
+        ];
+
+        let upstream = UpstreamAddr::from_host_str_and_port("www.example.com", 80).unwrap();
+        let lines = merge_header_lines(&base, &[&conditional], &Method::GET, "/", &upstream);
+        assert_eq!(lines.as_ref(), &["X-Tag: a\r\n", "X-Tag: b\r\n"]);
+
+        let lines = merge_header_lines(&base, &[&conditional], &Method::POST, "/", &upstream);
+        assert_eq!(lines.as_ref(), &["X-Tag: c\r\n"]);
+    }
+
+    #[test]
+    fn merge_override_base() {
+        let base = static_headers(&[
+            "Proxy-Authorization: Basic dTpw\r\n",
+            "X-Tag: base\r\n",
+            "X-Other: 1\r\n",
+        ]);
+        let conditional = vec![
+            ConditionalAppendHeaders::parse_json(
+                &json!({"mode": "set", "headers": {"X-Tag": "new"}}),
+            )
+            .unwrap(),
+            ConditionalAppendHeaders::parse_json(&json!({"headers": {"X-Extra": "e"}})).unwrap(),
+        ];
+
+        let upstream = UpstreamAddr::from_host_str_and_port("www.example.com", 80).unwrap();
+        let lines = merge_header_lines(&base, &[&conditional], &Method::GET, "/", &upstream);
+        assert_eq!(
+            lines.as_ref(),
+            &[
+                "Proxy-Authorization: Basic dTpw\r\n",
+                "X-Tag: new\r\n",
+                "X-Other: 1\r\n",
+                "X-Extra: e\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_user_layer() {
+        let base = static_headers(&["X-Tag: static\r\n"]);
+        let escaper = vec![ConditionalAppendHeaders::parse_json(
+            &json!({"host": ".example.com", "headers": {"X-Tag": "escaper"}}),
+        )
+        .unwrap()];
+        let user = vec![
+            ConditionalAppendHeaders::parse_json(&json!({"headers": {"X-Tag": "user"}})).unwrap(),
+            ConditionalAppendHeaders::parse_json(
+                &json!({"method": "POST", "mode": "set", "headers": {"X-Tag": "user-post"}}),
+            )
+            .unwrap(),
+        ];
+
+        let upstream = UpstreamAddr::from_host_str_and_port("www.example.com", 80).unwrap();
+        let lines = merge_header_lines(&base, &[&escaper, &user], &Method::GET, "/", &upstream);
+        assert_eq!(
+            lines.as_ref(),
+            &["X-Tag: static\r\n", "X-Tag: escaper\r\n", "X-Tag: user\r\n"]
+        );
+
+        // the user layer has the highest precedence
+        let lines = merge_header_lines(&base, &[&escaper, &user], &Method::POST, "/", &upstream);
+        assert_eq!(lines.as_ref(), &["X-Tag: user-post\r\n"]);
+
+        let upstream = UpstreamAddr::from_host_str_and_port("www.example.net", 80).unwrap();
+        let lines = merge_header_lines(&base, &[&escaper, &user], &Method::GET, "/", &upstream);
+        assert_eq!(lines.as_ref(), &["X-Tag: static\r\n", "X-Tag: user\r\n"]);
+    }
+
+    #[test]
+    fn parse_json_list() {
+        let v = json!([{"host": "example.com", "headers": {"X-Auth": "abc"}}, {"headers": {"X-Tag": "a"}}]);
+        let list = ConditionalAppendHeaders::parse_json_list(&v).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].host.as_deref(), Some("example.com"));
+
+        let v = json!({"headers": {"X-Tag": "a"}});
+        let list = ConditionalAppendHeaders::parse_json_list(&v).unwrap();
+        assert_eq!(list.len(), 1);
+
+        let v = json!([{"headers": {"X-Tag": "a"}}, {"host": "example.com"}]);
+        assert!(ConditionalAppendHeaders::parse_json_list(&v).is_err());
+    }
+
+    #[test]
+    fn parse_yaml() {
+        let v = yaml_rust::YamlLoader::load_from_str(
+            r#"
+            - host: .example.com
+              mode: set
+              headers:
+                X-Auth: abc
+            - method: POST
+              headers:
+                X-Post: "1"
+            "#,
+        )
+        .unwrap();
+        let list = ConditionalAppendHeaders::parse_yaml_list(&v[0]).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].host.as_deref(), Some(".example.com"));
+        assert_eq!(list[0].mode, HttpHeaderMergeMode::Set);
+        assert_eq!(list[1].method, Some(Method::POST));
+        assert_eq!(list[1].headers.get("x-post").unwrap().to_str(), "1");
+
+        let v = yaml_rust::YamlLoader::load_from_str("headers: {X-Auth: abc}").unwrap();
+        let list = ConditionalAppendHeaders::parse_yaml_list(&v[0]).unwrap();
+        assert_eq!(list.len(), 1);
+
+        let v = yaml_rust::YamlLoader::load_from_str("host: example.com").unwrap();
+        assert!(ConditionalAppendHeaders::parse_yaml_list(&v[0]).is_err());
+    }
+}
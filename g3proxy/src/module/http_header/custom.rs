@@ -21,6 +21,7 @@ use std::net::{IpAddr, SocketAddr};
 use base64::prelude::*;
 use chrono::{DateTime, Utc};
 use http::HeaderName;
+use uuid::Uuid;
 
 use g3_types::net::{EgressInfo, HttpHeaderMap, HttpHeaderValue, HttpServerId};
 
@@ -167,3 +168,11 @@ pub(crate) fn set_outgoing_ip(headers: &mut HttpHeaderMap, addr: SocketAddr) {
         });
     }
 }
+
+pub(crate) fn error_code(code: &str) -> String {
+    format!("X-BD-Error-Code: {code}\r\n")
+}
+
+pub(crate) fn task_id(id: &Uuid) -> String {
+    format!("X-BD-Task-Id: {id}\r\n")
+}
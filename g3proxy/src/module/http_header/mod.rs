@@ -14,11 +14,15 @@
  * limitations under the License.
  */
 
+mod conditional;
 mod custom;
 mod standard;
 
+pub(crate) use conditional::{merge_header_lines, ConditionalAppendHeaders, StaticAppendHeaders};
+
 pub(crate) use custom::{
-    dynamic_egress_info, outgoing_ip, remote_connection_info, set_dynamic_egress_info,
-    set_outgoing_ip, set_remote_connection_info, set_upstream_addr, set_upstream_id, upstream_addr,
+    dynamic_egress_info, error_code, outgoing_ip, remote_connection_info, set_dynamic_egress_info,
+    set_outgoing_ip, set_remote_connection_info, set_upstream_addr, set_upstream_id, task_id,
+    upstream_addr,
 };
 pub(crate) use standard::proxy_authorization_basic_pass;
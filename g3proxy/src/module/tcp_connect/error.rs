@@ -24,7 +24,7 @@ use g3_socks::v5::Socks5Reply;
 use g3_socks::SocksConnectError;
 use g3_types::net::{ConnectError, ProxyProtocolEncodeError};
 
-use crate::serve::{ServerTaskError, ServerTaskForbiddenError};
+use crate::serve::{ServerTaskError, ServerTaskErrorCode, ServerTaskForbiddenError};
 
 #[derive(Error, Debug)]
 pub(crate) enum TcpConnectError {
@@ -40,6 +40,8 @@ pub(crate) enum TcpConnectError {
     ConnectFailed(#[from] ConnectError),
     #[error("timeout by rule")]
     TimeoutByRule,
+    #[error("upstream connection limit reached")]
+    UpstreamConnLimitReached,
     #[error("no address connected")]
     NoAddressConnected,
     #[error("forbidden address family")]
@@ -83,6 +85,7 @@ impl TcpConnectError {
             TcpConnectError::SetupSocketFailed(_) => "SetupSocketFailed",
             TcpConnectError::ConnectFailed(_) => "ConnectFailed",
             TcpConnectError::TimeoutByRule => "TimeoutByRule",
+            TcpConnectError::UpstreamConnLimitReached => "UpstreamConnLimitReached",
             TcpConnectError::NoAddressConnected => "NoAddressConnected",
             TcpConnectError::ForbiddenAddressFamily => "ForbiddenAddressFamily",
             TcpConnectError::ForbiddenRemoteAddress => "ForbiddenRemoteAddress",
@@ -118,6 +121,7 @@ impl From<TcpConnectError> for ServerTaskError {
             TcpConnectError::TimeoutByRule => {
                 ServerTaskError::UpstreamNotConnected(ConnectError::TimedOut)
             }
+            TcpConnectError::UpstreamConnLimitReached => ServerTaskError::UpstreamConnLimitReached,
             TcpConnectError::NoAddressConnected => ServerTaskError::UpstreamNotAvailable,
             TcpConnectError::ForbiddenAddressFamily | TcpConnectError::ForbiddenRemoteAddress => {
                 ServerTaskError::ForbiddenByRule(ServerTaskForbiddenError::IpBlocked)
@@ -170,6 +174,10 @@ impl From<SocksConnectError> for TcpConnectError {
             SocksConnectError::InvalidProtocol(_) => TcpConnectError::NegotiationProtocolErr,
             SocksConnectError::PeerTimeout => TcpConnectError::NegotiationPeerTimeout,
             SocksConnectError::RequestFailed(s) => TcpConnectError::NegotiationRejected(s),
+            SocksConnectError::UdpAssociateUnsupported
+            | SocksConnectError::InvalidUdpRelayAddr(_) => {
+                TcpConnectError::NegotiationRejected(e.to_string())
+            }
         }
     }
 }
@@ -194,6 +202,7 @@ impl From<&TcpConnectError> for Socks5Reply {
             }
             TcpConnectError::TimeoutByRule => Socks5Reply::ConnectionTimedOut,
             TcpConnectError::EscaperNotUsable(_)
+            | TcpConnectError::UpstreamConnLimitReached
             | TcpConnectError::SetupSocketFailed(_)
             | TcpConnectError::ProxyProtocolEncodeError(_)
             | TcpConnectError::NegotiationProtocolErr => Socks5Reply::GeneralServerFailure,
@@ -227,6 +236,42 @@ impl From<HttpConnectError> for TcpConnectError {
                 ))
             }
             HttpConnectError::PeerTimeout(_) => TcpConnectError::NegotiationPeerTimeout,
+            HttpConnectError::ProxyAuthRequired(reason) => TcpConnectError::NegotiationRejected(
+                format!("auth failed with remote proxy: 407 {reason}"),
+            ),
+        }
+    }
+}
+
+impl From<&TcpConnectError> for ServerTaskErrorCode {
+    fn from(e: &TcpConnectError) -> Self {
+        match e {
+            TcpConnectError::MethodUnavailable
+            | TcpConnectError::ForbiddenAddressFamily
+            | TcpConnectError::ForbiddenRemoteAddress => ServerTaskErrorCode::Forbidden,
+            TcpConnectError::EscaperNotUsable(_) => ServerTaskErrorCode::ProxyUnavailable,
+            TcpConnectError::ResolveFailed(_) => ServerTaskErrorCode::UpstreamDnsFailed,
+            TcpConnectError::ConnectFailed(e) => ServerTaskErrorCode::from(e),
+            TcpConnectError::TimeoutByRule => ServerTaskErrorCode::UpstreamConnectTimeout,
+            TcpConnectError::UpstreamConnLimitReached => ServerTaskErrorCode::UpstreamConnLimited,
+            TcpConnectError::NoAddressConnected => ServerTaskErrorCode::UpstreamConnectFailed,
+            TcpConnectError::SetupSocketFailed(_)
+            | TcpConnectError::ProxyProtocolEncodeError(_)
+            | TcpConnectError::InternalServerError(_)
+            | TcpConnectError::InternalTlsClientError(_) => ServerTaskErrorCode::InternalError,
+            TcpConnectError::ProxyProtocolWriteFailed(_)
+            | TcpConnectError::NegotiationReadFailed(_)
+            | TcpConnectError::NegotiationWriteFailed(_) => ServerTaskErrorCode::UpstreamIoError,
+            TcpConnectError::NegotiationRejected(_) | TcpConnectError::NegotiationProtocolErr => {
+                ServerTaskErrorCode::UpstreamProtocolError
+            }
+            TcpConnectError::NegotiationPeerTimeout => ServerTaskErrorCode::UpstreamTimeout,
+            TcpConnectError::PeerTlsHandshakeTimeout
+            | TcpConnectError::PeerTlsHandshakeFailed(_)
+            | TcpConnectError::UpstreamTlsHandshakeTimeout
+            | TcpConnectError::UpstreamTlsHandshakeFailed(_) => {
+                ServerTaskErrorCode::UpstreamTlsFailed
+            }
         }
     }
 }
@@ -15,13 +15,16 @@
  */
 
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 
-use g3_socket::BindAddr;
+use g3_socket::{BindAddr, RawSocket};
 use g3_types::metrics::MetricsName;
-use g3_types::net::{EgressInfo, UpstreamAddr};
+use g3_types::net::{EgressInfo, OpensslCertificatePair, UpstreamAddr};
+
+use crate::config::audit::ProtocolTcpNoDelayConfig;
 
 /// This contains the final chained info about the client request
 #[derive(Debug, Clone, Default)]
@@ -49,6 +52,14 @@ pub(crate) struct TcpConnectTaskNotes {
     pub(crate) egress: Option<EgressInfo>,
     pub(crate) chained: TcpConnectChainedNotes,
     pub(crate) duration: Duration,
+    /// time spent in the last connection try
+    pub(crate) try_duration: Duration,
+    /// the upstream tcp socket, only set by escapers that connect to the upstream directly
+    pub(crate) raw_socket: Option<RawSocket>,
+    /// the TCP_NODELAY config for identified protocols set on the escaper
+    pub(crate) protocol_tcp_no_delay: Option<ProtocolTcpNoDelayConfig>,
+    /// the client cert to present to upstream in TLS interception set on the escaper
+    pub(crate) tls_interception_client_cert: Option<Arc<OpensslCertificatePair>>,
 }
 
 impl TcpConnectTaskNotes {
@@ -64,6 +75,10 @@ impl TcpConnectTaskNotes {
             egress: None,
             chained: Default::default(),
             duration: Duration::ZERO,
+            try_duration: Duration::ZERO,
+            raw_socket: None,
+            protocol_tcp_no_delay: None,
+            tls_interception_client_cert: None,
         }
     }
 
@@ -86,6 +101,9 @@ impl TcpConnectTaskNotes {
         self.egress = None;
         self.chained.reset();
         self.duration = Duration::ZERO;
+        self.raw_socket = None;
+        self.protocol_tcp_no_delay = None;
+        self.tls_interception_client_cert = None;
     }
 
     pub(crate) fn fill_generated(&mut self, other: &Self) {
@@ -98,5 +116,9 @@ impl TcpConnectTaskNotes {
         self.egress.clone_from(&other.egress);
         self.chained.clone_from(&other.chained);
         self.duration = other.duration;
+        self.raw_socket.clone_from(&other.raw_socket);
+        self.protocol_tcp_no_delay = other.protocol_tcp_no_delay;
+        self.tls_interception_client_cert
+            .clone_from(&other.tls_interception_client_cert);
     }
 }
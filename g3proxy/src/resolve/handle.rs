@@ -456,3 +456,133 @@ impl ArriveFirstResolveJob {
         Poll::Ready(Ok(ip))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::str::FromStr;
+
+    use g3_types::resolve::PickStrategy;
+
+    const IP4: &str = "192.0.2.1";
+    const IP6: &str = "2001:db8::1";
+
+    struct ReadyResolveJob {
+        addrs: Vec<IpAddr>,
+    }
+
+    impl LoggedResolveJob for ReadyResolveJob {
+        fn poll_query(&mut self, _cx: &mut Context<'_>) -> Poll<Result<Vec<IpAddr>, ResolveError>> {
+            Poll::Ready(Ok(self.addrs.clone()))
+        }
+    }
+
+    /// a resolver which has both A and AAAA records for all domains
+    struct DualStackResolverHandle {
+        name: MetricsName,
+    }
+
+    impl IntegratedResolverHandle for DualStackResolverHandle {
+        fn name(&self) -> &MetricsName {
+            &self.name
+        }
+
+        fn is_closed(&self) -> bool {
+            false
+        }
+
+        fn query_v4(&self, _domain: Arc<str>) -> Result<BoxLoggedResolveJob, ResolveError> {
+            Ok(Box::new(ReadyResolveJob {
+                addrs: vec![IpAddr::from_str(IP4).unwrap()],
+            }))
+        }
+
+        fn query_v6(&self, _domain: Arc<str>) -> Result<BoxLoggedResolveJob, ResolveError> {
+            Ok(Box::new(ReadyResolveJob {
+                addrs: vec![IpAddr::from_str(IP6).unwrap()],
+            }))
+        }
+
+        fn clone_inner(&self) -> Option<g3_resolver::ResolverHandle> {
+            None
+        }
+    }
+
+    fn dual_stack_handle() -> ArcIntegratedResolverHandle {
+        Arc::new(DualStackResolverHandle {
+            name: MetricsName::default(),
+        })
+    }
+
+    fn strategy(query: QueryStrategy) -> ResolveStrategy {
+        ResolveStrategy {
+            query,
+            pick: PickStrategy::Serial,
+        }
+    }
+
+    fn ips(s: &str) -> Vec<IpAddr> {
+        if s.is_empty() {
+            Vec::new()
+        } else {
+            vec![IpAddr::from_str(s).unwrap()]
+        }
+    }
+
+    async fn happy_eyeballs(query: QueryStrategy) -> (Vec<IpAddr>, Vec<IpAddr>) {
+        let mut job = HappyEyeballsResolveJob::new_dyn(
+            strategy(query),
+            &dual_stack_handle(),
+            Arc::from("example.net"),
+        )
+        .unwrap();
+        let r1 = job
+            .get_r1_or_first(Duration::from_millis(50), 4)
+            .await
+            .unwrap();
+        let r2 = tokio::time::timeout(Duration::from_millis(100), job.get_r2_or_never(4))
+            .await
+            .map(|r| r.unwrap())
+            .unwrap_or_default();
+        (r1, r2)
+    }
+
+    #[tokio::test]
+    async fn happy_eyeballs_query_strategy() {
+        assert_eq!(
+            happy_eyeballs(QueryStrategy::Ipv4Only).await,
+            (ips(IP4), ips(""))
+        );
+        assert_eq!(
+            happy_eyeballs(QueryStrategy::Ipv6Only).await,
+            (ips(IP6), ips(""))
+        );
+        assert_eq!(
+            happy_eyeballs(QueryStrategy::Ipv4First).await,
+            (ips(IP4), ips(IP6))
+        );
+        assert_eq!(
+            happy_eyeballs(QueryStrategy::Ipv6First).await,
+            (ips(IP6), ips(IP4))
+        );
+    }
+
+    fn arrive_first(query: QueryStrategy) -> impl Future<Output = Vec<IpAddr>> {
+        let mut job = ArriveFirstResolveJob::new(
+            &dual_stack_handle(),
+            strategy(query),
+            Arc::from("example.net"),
+        )
+        .unwrap();
+        async move { poll_fn(|cx| job.poll_all_addrs(cx)).await.unwrap() }
+    }
+
+    #[tokio::test]
+    async fn arrive_first_query_strategy() {
+        assert_eq!(arrive_first(QueryStrategy::Ipv4Only).await, ips(IP4));
+        assert_eq!(arrive_first(QueryStrategy::Ipv6Only).await, ips(IP6));
+        assert_eq!(arrive_first(QueryStrategy::Ipv4First).await, ips(IP4));
+        assert_eq!(arrive_first(QueryStrategy::Ipv6First).await, ips(IP6));
+    }
+}
@@ -45,6 +45,9 @@ mod hickory;
 mod deny_all;
 mod fail_over;
 
+mod warmup;
+pub(crate) use warmup::{get_status as get_warmup_status, start as start_warmup};
+
 mod ops;
 pub(crate) use ops::reload;
 pub use ops::spawn_all;
@@ -162,6 +162,7 @@ async fn delete_existed_unlocked(name: &MetricsName) {
     const STATUS: &str = "deleted";
 
     let old_resolver = registry::del(name);
+    super::warmup::del_status(name);
     update_dependency_to_resolver_unlocked(name, STATUS).await;
     crate::escape::update_dependency_to_resolver(name, STATUS).await;
     if let Some(mut resolver) = old_resolver {
@@ -175,6 +176,7 @@ async fn spawn_new_unlocked(config: AnyResolverConfig) -> anyhow::Result<()> {
     const STATUS: &str = "spawned";
 
     let name = config.name().clone();
+    let warmup = config.warmup();
     let resolver = match config {
         #[cfg(feature = "c-ares")]
         AnyResolverConfig::CAres(c) => CAresResolver::new_obj(c)?,
@@ -183,6 +185,7 @@ async fn spawn_new_unlocked(config: AnyResolverConfig) -> anyhow::Result<()> {
         AnyResolverConfig::DenyAll(c) => DenyAllResolver::new_obj(c)?,
         AnyResolverConfig::FailOver(c) => FailOverResolver::new_obj(c)?,
     };
+    let handle = resolver.get_handle();
     let old_resolver = registry::add(name.clone(), resolver);
     update_dependency_to_resolver_unlocked(&name, STATUS).await;
     crate::escape::update_dependency_to_resolver(&name, STATUS).await;
@@ -191,5 +194,10 @@ async fn spawn_new_unlocked(config: AnyResolverConfig) -> anyhow::Result<()> {
             resolver._shutdown().await;
         });
     }
+    if let Some(warmup) = warmup {
+        if let Err(e) = super::warmup::spawn(&name, handle, warmup) {
+            warn!("failed to start warmup for resolver {name}: {e}");
+        }
+    }
     Ok(())
 }
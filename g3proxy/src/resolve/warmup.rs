@@ -0,0 +1,195 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::future::poll_fn;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::{info, warn};
+
+use g3_resolver::ResolveError;
+use g3_types::metrics::MetricsName;
+use g3_types::resolve::QueryStrategy;
+
+use super::{registry, ArcIntegratedResolverHandle};
+use crate::config::resolver::ResolverWarmupConfig;
+
+static WARMUP_STATUS_REGISTRY: LazyLock<Mutex<HashMap<MetricsName, Arc<ResolverWarmupStatus>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Default)]
+struct WarmupState {
+    running: bool,
+    total: usize,
+    succeeded: usize,
+    failed: usize,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+/// The status of the latest warmup run of a resolver
+#[derive(Default)]
+pub(crate) struct ResolverWarmupStatus {
+    inner: Mutex<WarmupState>,
+}
+
+impl ResolverWarmupStatus {
+    fn start(&self, total: usize) -> bool {
+        let mut state = self.inner.lock().unwrap();
+        if state.running {
+            return false;
+        }
+        *state = WarmupState {
+            running: true,
+            total,
+            started_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        true
+    }
+
+    fn add_result(&self, success: bool) {
+        let mut state = self.inner.lock().unwrap();
+        if success {
+            state.succeeded += 1;
+        } else {
+            state.failed += 1;
+        }
+    }
+
+    fn finish(&self) -> (usize, usize) {
+        let mut state = self.inner.lock().unwrap();
+        state.running = false;
+        state.finished_at = Some(Utc::now());
+        (state.succeeded, state.failed)
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        let state = self.inner.lock().unwrap();
+        let status = if state.running {
+            "running"
+        } else if state.finished_at.is_some() {
+            "finished"
+        } else {
+            "not_started"
+        };
+        serde_json::json!({
+            "status": status,
+            "total": state.total,
+            "succeeded": state.succeeded,
+            "failed": state.failed,
+            "started_at": state.started_at.map(|v| v.to_rfc3339()),
+            "finished_at": state.finished_at.map(|v| v.to_rfc3339()),
+        })
+    }
+}
+
+pub(crate) fn get_status(name: &MetricsName) -> Arc<ResolverWarmupStatus> {
+    let mut ht = WARMUP_STATUS_REGISTRY.lock().unwrap();
+    ht.entry(name.clone()).or_default().clone()
+}
+
+pub(super) fn del_status(name: &MetricsName) {
+    let mut ht = WARMUP_STATUS_REGISTRY.lock().unwrap();
+    ht.remove(name);
+}
+
+/// Start a warmup run on demand, using the current warmup config of the resolver
+pub(crate) fn start(name: &MetricsName) -> anyhow::Result<()> {
+    let Some(config) = registry::get_config(name) else {
+        return Err(anyhow!("no resolver with name {name} found"));
+    };
+    let Some(warmup) = config.warmup() else {
+        return Err(anyhow!("no warmup config set for resolver {name}"));
+    };
+    let handle = registry::get_handle(name)?;
+    spawn(name, handle, warmup)
+}
+
+/// Start a background warmup run for the resolver, which won't block the caller.
+///
+/// An error will be returned if there is already a running one.
+pub(super) fn spawn(
+    name: &MetricsName,
+    handle: ArcIntegratedResolverHandle,
+    config: Arc<ResolverWarmupConfig>,
+) -> anyhow::Result<()> {
+    let status = get_status(name);
+    if !status.start(config.domains.len()) {
+        return Err(anyhow!("warmup of resolver {name} is already running"));
+    }
+
+    let name = name.clone();
+    let strategy = config.query_strategy;
+    tokio::spawn(async move {
+        futures_util::stream::iter(config.domains.iter().cloned())
+            .map(|domain| {
+                let handle = Arc::clone(&handle);
+                async move {
+                    let r = resolve(&handle, strategy, domain.clone()).await;
+                    (domain, r)
+                }
+            })
+            .buffer_unordered(config.parallelism)
+            .for_each(|(domain, r)| {
+                match r {
+                    Ok(_) => status.add_result(true),
+                    Err(e) => {
+                        warn!("resolver {name}: failed to warmup domain {domain}: {e}");
+                        status.add_result(false);
+                    }
+                }
+                async {}
+            })
+            .await;
+        let (succeeded, failed) = status.finish();
+        info!("resolver {name}: warmup finished, {succeeded} succeeded, {failed} failed");
+    });
+    Ok(())
+}
+
+/// Resolve the domain through the normal query path, so the same cache rules apply
+async fn resolve(
+    handle: &ArcIntegratedResolverHandle,
+    strategy: QueryStrategy,
+    domain: Arc<str>,
+) -> Result<(), ResolveError> {
+    let mut jobs = Vec::with_capacity(2);
+    match strategy {
+        QueryStrategy::Ipv4Only => jobs.push(handle.query_v4(domain)?),
+        QueryStrategy::Ipv6Only => jobs.push(handle.query_v6(domain)?),
+        QueryStrategy::Ipv4First | QueryStrategy::Ipv6First => {
+            jobs.push(handle.query_v4(domain.clone())?);
+            jobs.push(handle.query_v6(domain)?);
+        }
+    }
+
+    let mut last_err = None;
+    let mut resolved = false;
+    for mut job in jobs {
+        match poll_fn(|cx| job.poll_query(cx)).await {
+            Ok(_) => resolved = true,
+            Err(e) => last_err = Some(e),
+        }
+    }
+    match last_err {
+        Some(e) if !resolved => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -59,6 +59,8 @@ pub(crate) enum ServerTaskForbiddenError {
     UaBlocked,
     #[error("user blocked")]
     UserBlocked,
+    #[error("http body blocked")]
+    BodyBlocked,
 }
 
 #[derive(Error, Debug)]
@@ -99,6 +101,8 @@ pub(crate) enum ServerTaskError {
     UpstreamNotConnected(ConnectError),
     #[error("upstream not available")]
     UpstreamNotAvailable,
+    #[error("upstream connection limit reached")]
+    UpstreamConnLimitReached,
     #[error("invalid upstream protocol: {0}")]
     InvalidUpstreamProtocol(&'static str),
     #[error("read from upstream: {0:?}")]
@@ -127,10 +131,14 @@ pub(crate) enum ServerTaskError {
     CanceledAsUserBlocked,
     #[error("canceled as server quit")]
     CanceledAsServerQuit,
+    #[error("canceled as task lifetime exceeded")]
+    CanceledAsLifetimeExceeded,
     #[error("idle after {0:?} x {1}")]
     Idle(Duration, i32),
     #[error("{0} interception error: {1}")]
     InterceptionError(Protocol, InterceptionError),
+    #[error("inspection depth limit {0} exceeded")]
+    InspectionDepthExceeded(usize),
     #[error("finished")]
     Finished, // this isn't an error, for log only
     #[error("unclassified error: {0:?}")]
@@ -158,6 +166,7 @@ impl ServerTaskError {
             ServerTaskError::UpstreamNotResolved(_) => "UpstreamNotResolved",
             ServerTaskError::UpstreamNotConnected(_) => "UpstreamNotConnected",
             ServerTaskError::UpstreamNotAvailable => "UpstreamNotAvailable",
+            ServerTaskError::UpstreamConnLimitReached => "UpstreamConnLimitReached",
             ServerTaskError::InvalidUpstreamProtocol(_) => "InvalidUpstreamProtocol",
             ServerTaskError::UpstreamReadFailed(_) => "UpstreamReadFailed",
             ServerTaskError::UpstreamWriteFailed(_) => "UpstreamWriteFailed",
@@ -172,12 +181,171 @@ impl ServerTaskError {
             ServerTaskError::ClosedEarlyByClient => "ClosedEarlyByClient",
             ServerTaskError::CanceledAsUserBlocked => "CanceledAsUserBlocked",
             ServerTaskError::CanceledAsServerQuit => "CanceledAsServerQuit",
+            ServerTaskError::CanceledAsLifetimeExceeded => "CanceledAsLifetimeExceeded",
             ServerTaskError::Idle(_, _) => "Idle",
             ServerTaskError::InterceptionError(_, _) => "InterceptionError",
+            ServerTaskError::InspectionDepthExceeded(_) => "InspectionDepthExceeded",
             ServerTaskError::Finished => "Finished",
             ServerTaskError::UnclassifiedError(_) => "UnclassifiedError",
         }
     }
+
+    /// Whether this is a normal end of the task, which may be skipped by log sampling
+    pub(crate) fn is_normal_end(&self) -> bool {
+        matches!(
+            self,
+            ServerTaskError::Finished
+                | ServerTaskError::ClosedByClient
+                | ServerTaskError::ClosedByUpstream
+        )
+    }
+}
+
+/// A small and stable set of error codes that is safe to be sent to clients
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ServerTaskErrorCode {
+    InternalError,
+    ProxyUnavailable,
+    ServerBusy,
+    Forbidden,
+    RateLimited,
+    InvalidRequest,
+    Unimplemented,
+    AuthFailed,
+    ClientTimeout,
+    ClientIoError,
+    UpstreamDnsFailed,
+    UpstreamConnectRefused,
+    UpstreamConnectTimeout,
+    UpstreamUnreachable,
+    UpstreamConnectFailed,
+    UpstreamUnavailable,
+    UpstreamConnLimited,
+    UpstreamProtocolError,
+    UpstreamIoError,
+    UpstreamTlsFailed,
+    UpstreamTimeout,
+    InterceptionFailed,
+    Canceled,
+    Idle,
+    Closed,
+}
+
+impl ServerTaskErrorCode {
+    pub(crate) const fn as_str(&self) -> &'static str {
+        match self {
+            ServerTaskErrorCode::InternalError => "internal_error",
+            ServerTaskErrorCode::ProxyUnavailable => "proxy_unavailable",
+            ServerTaskErrorCode::ServerBusy => "server_busy",
+            ServerTaskErrorCode::Forbidden => "forbidden",
+            ServerTaskErrorCode::RateLimited => "rate_limited",
+            ServerTaskErrorCode::InvalidRequest => "invalid_request",
+            ServerTaskErrorCode::Unimplemented => "unimplemented",
+            ServerTaskErrorCode::AuthFailed => "auth_failed",
+            ServerTaskErrorCode::ClientTimeout => "client_timeout",
+            ServerTaskErrorCode::ClientIoError => "client_io_error",
+            ServerTaskErrorCode::UpstreamDnsFailed => "upstream_dns_failed",
+            ServerTaskErrorCode::UpstreamConnectRefused => "upstream_connect_refused",
+            ServerTaskErrorCode::UpstreamConnectTimeout => "upstream_connect_timeout",
+            ServerTaskErrorCode::UpstreamUnreachable => "upstream_unreachable",
+            ServerTaskErrorCode::UpstreamConnectFailed => "upstream_connect_failed",
+            ServerTaskErrorCode::UpstreamUnavailable => "upstream_unavailable",
+            ServerTaskErrorCode::UpstreamConnLimited => "upstream_conn_limited",
+            ServerTaskErrorCode::UpstreamProtocolError => "upstream_protocol_error",
+            ServerTaskErrorCode::UpstreamIoError => "upstream_io_error",
+            ServerTaskErrorCode::UpstreamTlsFailed => "upstream_tls_failed",
+            ServerTaskErrorCode::UpstreamTimeout => "upstream_timeout",
+            ServerTaskErrorCode::InterceptionFailed => "interception_failed",
+            ServerTaskErrorCode::Canceled => "canceled",
+            ServerTaskErrorCode::Idle => "idle",
+            ServerTaskErrorCode::Closed => "closed",
+        }
+    }
+}
+
+impl From<&ConnectError> for ServerTaskErrorCode {
+    fn from(e: &ConnectError) -> Self {
+        match e {
+            ConnectError::ConnectionRefused | ConnectError::ConnectionReset => {
+                ServerTaskErrorCode::UpstreamConnectRefused
+            }
+            ConnectError::NetworkUnreachable | ConnectError::HostUnreachable => {
+                ServerTaskErrorCode::UpstreamUnreachable
+            }
+            ConnectError::TimedOut => ServerTaskErrorCode::UpstreamConnectTimeout,
+            ConnectError::UnspecifiedError(_) => ServerTaskErrorCode::UpstreamConnectFailed,
+        }
+    }
+}
+
+impl From<&ServerTaskError> for ServerTaskErrorCode {
+    fn from(e: &ServerTaskError) -> Self {
+        match e {
+            ServerTaskError::InternalServerError(_)
+            | ServerTaskError::InternalAdapterError(_)
+            | ServerTaskError::InternalResolverError(_)
+            | ServerTaskError::InternalTlsClientError(_)
+            | ServerTaskError::UnclassifiedError(_) => ServerTaskErrorCode::InternalError,
+            ServerTaskError::EscaperNotUsable(_) => ServerTaskErrorCode::ProxyUnavailable,
+            ServerTaskError::ForbiddenByRule(e) => match e {
+                ServerTaskForbiddenError::RateLimited => ServerTaskErrorCode::RateLimited,
+                ServerTaskForbiddenError::FullyLoaded => ServerTaskErrorCode::ServerBusy,
+                _ => ServerTaskErrorCode::Forbidden,
+            },
+            ServerTaskError::InvalidClientProtocol(_) | ServerTaskError::ClientAppError(_) => {
+                ServerTaskErrorCode::InvalidRequest
+            }
+            ServerTaskError::UnimplementedProtocol => ServerTaskErrorCode::Unimplemented,
+            ServerTaskError::ClientTcpReadFailed(_)
+            | ServerTaskError::ClientTcpWriteFailed(_)
+            | ServerTaskError::ClientUdpRecvFailed(_)
+            | ServerTaskError::ClientUdpSendFailed(_) => ServerTaskErrorCode::ClientIoError,
+            ServerTaskError::ClientAuthFailed => ServerTaskErrorCode::AuthFailed,
+            ServerTaskError::ClientAppTimeout(_) => ServerTaskErrorCode::ClientTimeout,
+            ServerTaskError::UpstreamNotResolved(_) => ServerTaskErrorCode::UpstreamDnsFailed,
+            ServerTaskError::UpstreamNotConnected(e) => ServerTaskErrorCode::from(e),
+            ServerTaskError::UpstreamNotAvailable | ServerTaskError::UpstreamAppUnavailable => {
+                ServerTaskErrorCode::UpstreamUnavailable
+            }
+            ServerTaskError::UpstreamConnLimitReached => ServerTaskErrorCode::UpstreamConnLimited,
+            ServerTaskError::InvalidUpstreamProtocol(_)
+            | ServerTaskError::UpstreamNotNegotiated(_)
+            | ServerTaskError::UpstreamAppError(_) => ServerTaskErrorCode::UpstreamProtocolError,
+            ServerTaskError::UpstreamReadFailed(_)
+            | ServerTaskError::UpstreamWriteFailed(_)
+            | ServerTaskError::ClosedByUpstream => ServerTaskErrorCode::UpstreamIoError,
+            ServerTaskError::UpstreamTlsHandshakeTimeout
+            | ServerTaskError::UpstreamTlsHandshakeFailed(_) => {
+                ServerTaskErrorCode::UpstreamTlsFailed
+            }
+            ServerTaskError::UpstreamAppTimeout(_) => ServerTaskErrorCode::UpstreamTimeout,
+            ServerTaskError::InterceptionError(_, _)
+            | ServerTaskError::InspectionDepthExceeded(_) => {
+                ServerTaskErrorCode::InterceptionFailed
+            }
+            ServerTaskError::CanceledAsUserBlocked
+            | ServerTaskError::CanceledAsServerQuit
+            | ServerTaskError::CanceledAsLifetimeExceeded => ServerTaskErrorCode::Canceled,
+            ServerTaskError::Idle(_, _) => ServerTaskErrorCode::Idle,
+            ServerTaskError::ClosedByClient
+            | ServerTaskError::ClosedEarlyByClient
+            | ServerTaskError::Finished => ServerTaskErrorCode::Closed,
+        }
+    }
+}
+
+impl From<&FtpConnectError<TcpConnectError>> for ServerTaskErrorCode {
+    fn from(e: &FtpConnectError<TcpConnectError>) -> Self {
+        match e {
+            FtpConnectError::ConnectIoError(e) => ServerTaskErrorCode::from(e),
+            FtpConnectError::ConnectTimedOut => ServerTaskErrorCode::UpstreamConnectTimeout,
+            FtpConnectError::GreetingTimedOut => ServerTaskErrorCode::UpstreamTimeout,
+            FtpConnectError::GreetingFailed(_)
+            | FtpConnectError::NegotiationFailed(_)
+            | FtpConnectError::InvalidReplyCode(_) => ServerTaskErrorCode::UpstreamProtocolError,
+            FtpConnectError::ServiceNotAvailable => ServerTaskErrorCode::UpstreamUnavailable,
+        }
+    }
 }
 
 pub(crate) type ServerTaskResult<T> = Result<T, ServerTaskError>;
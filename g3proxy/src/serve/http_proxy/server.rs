@@ -34,11 +34,13 @@ use g3_daemon::listen::{AcceptQuicServer, AcceptTcpServer, ListenStats, ListenTc
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerReloadCommand};
 use g3_io_ext::AsyncStream;
 use g3_openssl::SslStream;
-use g3_types::acl::{AclAction, AclNetworkRule};
+use g3_types::acl::{AclAction, AclNetworkRule, AclOrderedNetworkRule};
 use g3_types::acl_set::AclDstHostRuleSet;
+use g3_types::limit::{ClientConnLimiter, ClientConnPermit};
 use g3_types::metrics::MetricsName;
 use g3_types::net::{
-    OpensslClientConfig, OpensslTicketKey, RollingTicketer, RustlsServerConnectionExt,
+    OpensslClientConfig, OpensslOcspStapler, OpensslTicketKey, RollingTicketer,
+    RustlsServerConnectionExt,
 };
 
 use super::task::{
@@ -49,10 +51,11 @@ use super::HttpProxyServerStats;
 use crate::audit::{AuditContext, AuditHandle};
 use crate::auth::UserGroup;
 use crate::config::server::http_proxy::HttpProxyServerConfig;
-use crate::config::server::{AnyServerConfig, ServerConfig};
+use crate::config::server::{AnyServerConfig, ServerConfig, TarpitTrigger};
 use crate::escape::ArcEscaper;
 use crate::serve::{
-    ArcServer, ArcServerStats, Server, ServerInternal, ServerQuitPolicy, ServerStats, WrapArcServer,
+    ArcServer, ArcServerStats, Server, ServerInternal, ServerQuitPolicy, ServerStats, ServerTarpit,
+    WrapArcServer,
 };
 
 pub(crate) struct HttpProxyServer {
@@ -62,8 +65,12 @@ pub(crate) struct HttpProxyServer {
     tls_rolling_ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
     tls_acceptor: Option<TlsAcceptor>,
     tls_accept_timeout: Duration,
+    tls_ocsp_stapler: Option<Arc<OpensslOcspStapler>>,
     tls_client_config: Arc<OpensslClientConfig>,
     ingress_net_filter: Option<AclNetworkRule>,
+    client_net_acl: Option<AclOrderedNetworkRule>,
+    client_conn_limiter: Option<ClientConnLimiter>,
+    tarpit: Option<Arc<ServerTarpit>>,
     dst_host_filter: Option<Arc<AclDstHostRuleSet>>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
     task_logger: Logger,
@@ -81,14 +88,31 @@ impl HttpProxyServer {
         server_stats: Arc<HttpProxyServerStats>,
         listen_stats: Arc<ListenStats>,
         tls_rolling_ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
+        old_ocsp_stapler: Option<&OpensslOcspStapler>,
+        old_conn_limiter: Option<&ClientConnLimiter>,
+        old_tarpit: Option<&ServerTarpit>,
         version: usize,
     ) -> anyhow::Result<HttpProxyServer> {
         let reload_sender = crate::serve::new_reload_notify_channel();
 
         let mut tls_accept_timeout = Duration::from_secs(10);
+        let mut tls_ocsp_stapler = None;
         let tls_acceptor = if let Some(tls_config_builder) = &config.server_tls_config {
+            if let Some(c) = &config.tls_ocsp_stapling {
+                let stapler = crate::serve::spawn_rustls_ocsp_stapler(
+                    config.name(),
+                    c,
+                    tls_config_builder,
+                    old_ocsp_stapler,
+                )?;
+                tls_ocsp_stapler = Some(stapler);
+            }
             let tls_server_config = tls_config_builder
-                .build_with_ticketer(tls_rolling_ticketer.clone())
+                .build_with_ocsp_stapler(
+                    None,
+                    tls_rolling_ticketer.clone(),
+                    tls_ocsp_stapler.clone(),
+                )
                 .context("failed to build tls server config")?;
             tls_accept_timeout = tls_server_config.accept_timeout;
             Some(TlsAcceptor::from(tls_server_config.driver))
@@ -106,6 +130,17 @@ impl HttpProxyServer {
             .as_ref()
             .map(|builder| builder.build());
 
+        let client_net_acl = config
+            .client_net_acl
+            .as_ref()
+            .map(|builder| builder.build());
+
+        let client_conn_limiter = crate::serve::reload_client_conn_limiter(
+            old_conn_limiter,
+            config.client_conn_limit.as_ref(),
+        );
+        let tarpit = ServerTarpit::new_updated(old_tarpit, config.tarpit.as_ref());
+
         let dst_host_filter = config
             .dst_host_filter
             .as_ref()
@@ -127,8 +162,12 @@ impl HttpProxyServer {
             tls_rolling_ticketer,
             tls_acceptor,
             tls_accept_timeout,
+            tls_ocsp_stapler,
             tls_client_config: Arc::new(tls_client_config),
             ingress_net_filter,
+            client_net_acl,
+            client_conn_limiter,
+            tarpit,
             dst_host_filter,
             reload_sender,
             task_logger,
@@ -156,8 +195,16 @@ impl HttpProxyServer {
             None
         };
 
-        let server =
-            HttpProxyServer::new(config, server_stats, listen_stats, tls_rolling_ticketer, 1)?;
+        let server = HttpProxyServer::new(
+            config,
+            server_stats,
+            listen_stats,
+            tls_rolling_ticketer,
+            None,
+            None,
+            None,
+            1,
+        )?;
         Ok(Arc::new(server))
     }
 
@@ -183,6 +230,9 @@ impl HttpProxyServer {
                 server_stats,
                 listen_stats,
                 tls_rolling_ticketer,
+                self.tls_ocsp_stapler.as_deref(),
+                self.client_conn_limiter.as_ref(),
+                self.tarpit.as_deref(),
                 self.reload_version + 1,
             )?;
             Ok(server)
@@ -205,6 +255,7 @@ impl HttpProxyServer {
             tls_client_config: self.tls_client_config.clone(),
             task_logger: self.task_logger.clone(),
             dst_host_filter: self.dst_host_filter.clone(),
+            tarpit: self.tarpit.clone(),
         })
     }
 
@@ -220,11 +271,58 @@ impl HttpProxyServer {
             }
         }
 
+        if let Some(client_net_acl) = &self.client_net_acl {
+            if crate::serve::forbid_by_client_net_acl(
+                client_net_acl,
+                self.config.name(),
+                client_addr,
+            ) {
+                self.listen_stats.add_dropped();
+                return true;
+            }
+        }
+
         // TODO add cps limit
 
         false
     }
 
+    /// Get the connection permit for the client, which should be held until the connection closed
+    fn acquire_conn_permit(&self, client_addr: SocketAddr) -> Result<Option<ClientConnPermit>, ()> {
+        let Some(limiter) = &self.client_conn_limiter else {
+            return Ok(None);
+        };
+        limiter.try_acquire(client_addr.ip()).map(Some).ok_or(())
+    }
+
+    async fn reject_by_conn_limit<W>(&self, client_addr: SocketAddr, writer: Option<W>)
+    where
+        W: AsyncWrite + Unpin,
+    {
+        if let Some(limiter) = &self.client_conn_limiter {
+            crate::serve::reject_by_client_conn_limit(
+                limiter,
+                self.tarpit.as_deref(),
+                self.config.name(),
+                &self.listen_stats,
+                client_addr,
+                writer,
+            )
+            .await;
+        }
+    }
+
+    async fn tarpit<W>(&self, trigger: TarpitTrigger, client_addr: SocketAddr, writer: W)
+    where
+        W: AsyncWrite + Unpin,
+    {
+        if let Some(tarpit) = &self.tarpit {
+            tarpit
+                .hold(trigger, self.config.name(), client_addr, writer)
+                .await;
+        }
+    }
+
     fn audit_context(&self) -> AuditContext {
         AuditContext::new(self.audit_handle.load_full())
     }
@@ -373,8 +471,14 @@ impl AcceptTcpServer for HttpProxyServer {
         let client_addr = cc_info.client_addr();
         self.server_stats.add_conn(client_addr);
         if self.drop_early(client_addr) {
+            self.tarpit(TarpitTrigger::NetworkAcl, client_addr, stream)
+                .await;
             return;
         }
+        let Ok(_conn_permit) = self.acquire_conn_permit(client_addr) else {
+            self.reject_by_conn_limit(client_addr, Some(stream)).await;
+            return;
+        };
 
         if let Some(tls_acceptor) = &self.tls_acceptor {
             match tokio::time::timeout(self.tls_accept_timeout, tls_acceptor.accept(stream)).await {
@@ -419,6 +523,11 @@ impl AcceptQuicServer for HttpProxyServer {
         if self.drop_early(client_addr) {
             return;
         }
+        let Ok(_conn_permit) = self.acquire_conn_permit(client_addr) else {
+            self.reject_by_conn_limit(client_addr, None::<TcpStream>)
+                .await;
+            return;
+        };
 
         loop {
             // TODO update ctx and quit gracefully
@@ -474,8 +583,14 @@ impl Server for HttpProxyServer {
         let client_addr = cc_info.client_addr();
         self.server_stats.add_conn(client_addr);
         if self.drop_early(client_addr) {
+            self.tarpit(TarpitTrigger::NetworkAcl, client_addr, stream)
+                .await;
             return;
         }
+        let Ok(_conn_permit) = self.acquire_conn_permit(client_addr) else {
+            self.reject_by_conn_limit(client_addr, Some(stream)).await;
+            return;
+        };
 
         self.spawn_stream_task(stream, cc_info).await;
     }
@@ -484,8 +599,14 @@ impl Server for HttpProxyServer {
         let client_addr = cc_info.client_addr();
         self.server_stats.add_conn(client_addr);
         if self.drop_early(client_addr) {
+            self.tarpit(TarpitTrigger::NetworkAcl, client_addr, stream)
+                .await;
             return;
         }
+        let Ok(_conn_permit) = self.acquire_conn_permit(client_addr) else {
+            self.reject_by_conn_limit(client_addr, Some(stream)).await;
+            return;
+        };
 
         self.spawn_stream_task(stream, cc_info).await;
     }
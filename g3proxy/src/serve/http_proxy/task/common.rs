@@ -18,6 +18,8 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use slog::Logger;
+use tokio::io::AsyncWrite;
+use uuid::Uuid;
 
 use g3_daemon::server::ClientConnectionInfo;
 use g3_icap_client::reqmod::h1::HttpAdapterErrorResponse;
@@ -26,11 +28,15 @@ use g3_types::acl_set::AclDstHostRuleSet;
 use g3_types::net::{OpensslClientConfig, UpstreamAddr};
 
 use super::{HttpProxyServerConfig, HttpProxyServerStats};
+use crate::config::server::ServerConfig;
 use crate::escape::ArcEscaper;
 use crate::module::http_forward::HttpProxyClientResponse;
 use crate::module::http_header;
 use crate::module::tcp_connect::TcpConnectTaskNotes;
-use crate::serve::{ServerIdleChecker, ServerQuitPolicy, ServerTaskNotes};
+use crate::serve::{
+    ServerIdleChecker, ServerQuitPolicy, ServerTarpit, ServerTaskError, ServerTaskErrorCode,
+    ServerTaskNotes,
+};
 
 #[derive(Clone)]
 pub(crate) struct CommonTaskContext {
@@ -43,6 +49,7 @@ pub(crate) struct CommonTaskContext {
     pub(crate) task_logger: Logger,
 
     pub(crate) dst_host_filter: Option<Arc<AclDstHostRuleSet>>,
+    pub(crate) tarpit: Option<Arc<ServerTarpit>>,
 }
 
 impl CommonTaskContext {
@@ -51,11 +58,25 @@ impl CommonTaskContext {
         self.cc_info.client_addr()
     }
 
+    /// Hold the client connection in the server tarpit if the task is blocked by a selected rule
+    pub(crate) async fn tarpit_on_error<W>(&self, e: &ServerTaskError, clt_w: &mut W) -> bool
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let Some(tarpit) = &self.tarpit else {
+            return false;
+        };
+        tarpit
+            .hold_on_error(e, self.server_config.name(), self.client_addr(), clt_w)
+            .await
+    }
+
     pub(crate) fn idle_checker(&self, task_notes: &ServerTaskNotes) -> ServerIdleChecker {
         ServerIdleChecker {
             idle_duration: self.server_config.task_idle_check_duration,
             user: task_notes.user_ctx().map(|ctx| ctx.user().clone()),
             task_max_idle_count: self.server_config.task_idle_max_count,
+            protocol_max_idle_count: None,
             server_quit_policy: self.server_quit_policy.clone(),
         }
     }
@@ -119,6 +140,19 @@ impl CommonTaskContext {
         }
     }
 
+    /// Set the error code and task id headers, which should only be used for error responses
+    pub(crate) fn set_error_header_for_local_reply(
+        &self,
+        task_id: &Uuid,
+        code: ServerTaskErrorCode,
+        rsp: &mut HttpProxyClientResponse,
+    ) {
+        if self.server_config.echo_error_code {
+            rsp.add_extra_header(http_header::error_code(code.as_str()));
+            rsp.add_extra_header(http_header::task_id(task_id));
+        }
+    }
+
     pub(crate) fn set_custom_header_for_adaptation_error_reply(
         &self,
         tcp_notes: &TcpConnectTaskNotes,
@@ -29,13 +29,14 @@ use super::protocol::{HttpClientWriter, HttpProxyRequest};
 use super::{CommonTaskContext, TcpConnectTaskCltWrapperStats};
 use crate::audit::AuditContext;
 use crate::config::server::ServerConfig;
-use crate::inspect::StreamInspectContext;
+use crate::inspect::{StreamInspectContext, StreamInspectSummary};
+use crate::log::task::http_connect::TaskLogForHttpConnect;
 use crate::log::task::tcp_connect::TaskLogForTcpConnect;
 use crate::module::http_forward::HttpProxyClientResponse;
 use crate::module::tcp_connect::{TcpConnectError, TcpConnectTaskNotes, TcpConnection};
 use crate::serve::{
-    ServerStats, ServerTaskError, ServerTaskForbiddenError, ServerTaskNotes, ServerTaskResult,
-    ServerTaskStage,
+    ServerStats, ServerTaskError, ServerTaskErrorCode, ServerTaskForbiddenError, ServerTaskNotes,
+    ServerTaskResult, ServerTaskStage,
 };
 
 pub(crate) struct HttpProxyConnectTask {
@@ -46,6 +47,8 @@ pub(crate) struct HttpProxyConnectTask {
     tcp_notes: TcpConnectTaskNotes,
     task_stats: Arc<TcpStreamTaskStats>,
     audit_ctx: AuditContext,
+    audit_task: bool,
+    inspect_summary: Option<Arc<StreamInspectSummary>>,
     http_version: Version,
 }
 
@@ -64,6 +67,8 @@ impl HttpProxyConnectTask {
             tcp_notes: TcpConnectTaskNotes::new(req.upstream.clone()),
             task_stats: Arc::new(TcpStreamTaskStats::default()),
             audit_ctx,
+            audit_task: false,
+            inspect_summary: None,
             http_version: req.inner.version,
         }
     }
@@ -119,6 +124,11 @@ impl HttpProxyConnectTask {
             HttpProxyClientResponse::from_tcp_connect_error(e, http::Version::HTTP_11, false);
         self.ctx
             .set_custom_header_for_local_reply(&self.tcp_notes, &mut rsp);
+        self.ctx.set_error_header_for_local_reply(
+            &self.task_notes.id,
+            ServerTaskErrorCode::from(e),
+            &mut rsp,
+        );
         let should_close = rsp.should_close();
         self.back_to_http = !should_close;
 
@@ -135,15 +145,38 @@ impl HttpProxyConnectTask {
         match self.run_connect(clt_w).await {
             Ok(()) => {
                 self.back_to_http = false;
-                // no pre_stop, as we will continue
+                self.audit_task = self.check_audit_task();
+                // no pre_stop, as we will continue, the connect log will be generated when the
+                // tunnel ends, so the inspection result can be logged
             }
             Err(e) => {
+                self.get_connect_log_context()
+                    .log(&self.ctx.task_logger, Some(&e));
                 self.get_log_context().log(&self.ctx.task_logger, &e);
                 self.pre_stop();
+                if self.ctx.tarpit_on_error(&e, clt_w).await {
+                    self.back_to_http = false;
+                }
             }
         }
     }
 
+    fn check_audit_task(&self) -> bool {
+        let Some(audit_handle) = self.audit_ctx.handle() else {
+            return false;
+        };
+        self.task_notes
+            .user_ctx()
+            .map(|ctx| {
+                let user_config = &ctx.user_config().audit;
+                user_config.enable_protocol_inspection
+                    && user_config
+                        .do_task_audit()
+                        .unwrap_or_else(|| audit_handle.do_task_audit())
+            })
+            .unwrap_or_else(|| audit_handle.do_task_audit())
+    }
+
     async fn handle_server_upstream_acl_action<W>(
         &mut self,
         action: AclAction,
@@ -289,6 +322,12 @@ impl HttpProxyConnectTask {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
 
         self.task_notes.stage = ServerTaskStage::Connecting;
         match self
@@ -351,7 +390,7 @@ impl HttpProxyConnectTask {
         }
     }
 
-    fn get_log_context(&self) -> TaskLogForTcpConnect {
+    fn get_log_context(&self) -> TaskLogForTcpConnect<'_> {
         TaskLogForTcpConnect {
             task_notes: &self.task_notes,
             tcp_notes: &self.tcp_notes,
@@ -363,6 +402,14 @@ impl HttpProxyConnectTask {
         }
     }
 
+    fn get_connect_log_context(&self) -> TaskLogForHttpConnect<'_> {
+        TaskLogForHttpConnect {
+            task_notes: &self.task_notes,
+            tcp_notes: &self.tcp_notes,
+            inspect_summary: self.inspect_summary.as_deref(),
+        }
+    }
+
     pub(crate) fn into_running<CDR, CDW>(mut self, clt_r: CDR, clt_w: HttpClientWriter<CDW>)
     where
         CDR: AsyncRead + Send + Sync + Unpin + 'static,
@@ -375,7 +422,10 @@ impl HttpProxyConnectTask {
         tokio::spawn(async move {
             match self.stream_ups.take() {
                 Some((ups_r, ups_w)) => {
-                    match self.run_connected(clt_r, clt_w, ups_r, ups_w).await {
+                    let r = self.run_connected(clt_r, clt_w, ups_r, ups_w).await;
+                    self.get_connect_log_context()
+                        .log(&self.ctx.task_logger, None);
+                    match r {
                         Ok(_) => self
                             .get_log_context()
                             .log(&self.ctx.task_logger, &ServerTaskError::Finished),
@@ -430,26 +480,19 @@ impl HttpProxyConnectTask {
         let (clt_r, clt_w) = self.update_clt(clt_r, clt_w);
 
         if let Some(audit_handle) = self.audit_ctx.handle() {
-            let audit_task = self
-                .task_notes
-                .user_ctx()
-                .map(|ctx| {
-                    let user_config = &ctx.user_config().audit;
-                    user_config.enable_protocol_inspection
-                        && user_config
-                            .do_task_audit()
-                            .unwrap_or_else(|| audit_handle.do_task_audit())
-                })
-                .unwrap_or_else(|| audit_handle.do_task_audit());
-
-            if audit_task {
-                let ctx = StreamInspectContext::new(
+            if self.audit_task {
+                let mut ctx = StreamInspectContext::new(
                     audit_handle.clone(),
                     self.ctx.server_config.clone(),
                     self.ctx.server_stats.clone(),
                     self.ctx.server_quit_policy.clone(),
                     &self.task_notes,
+                    &self.tcp_notes,
                 );
+                ctx.set_tarpit(self.ctx.tarpit.clone());
+                let summary = Arc::new(StreamInspectSummary::default());
+                ctx.set_summary(summary.clone());
+                self.inspect_summary = Some(summary);
                 return crate::inspect::stream::transit_with_inspection(
                     clt_r,
                     clt_w,
@@ -470,6 +513,8 @@ impl HttpProxyConnectTask {
             ups_w,
             &self.ctx.server_config,
             &self.ctx.server_quit_policy,
+            self.task_notes
+                .task_deadline(self.ctx.server_config.max_task_lifetime()),
             self.task_notes.user_ctx().map(|ctx| ctx.user()),
         )
         .await
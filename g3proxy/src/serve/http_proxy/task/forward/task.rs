@@ -54,8 +54,8 @@ use crate::module::http_forward::{
 use crate::module::http_header;
 use crate::module::tcp_connect::{TcpConnectError, TcpConnectTaskNotes};
 use crate::serve::{
-    ServerIdleChecker, ServerStats, ServerTaskError, ServerTaskForbiddenError, ServerTaskNotes,
-    ServerTaskResult, ServerTaskStage,
+    ServerIdleChecker, ServerStats, ServerTaskError, ServerTaskErrorCode, ServerTaskForbiddenError,
+    ServerTaskNotes, ServerTaskResult, ServerTaskStage,
 };
 
 pub(crate) struct HttpProxyForwardTask<'a> {
@@ -157,6 +157,11 @@ impl<'a> HttpProxyForwardTask<'a> {
 
         self.ctx
             .set_custom_header_for_local_reply(&self.tcp_notes, &mut rsp);
+        self.ctx.set_error_header_for_local_reply(
+            &self.task_notes.id,
+            ServerTaskErrorCode::from(e),
+            &mut rsp,
+        );
 
         if rsp.should_close() {
             self.should_close = true;
@@ -183,6 +188,11 @@ impl<'a> HttpProxyForwardTask<'a> {
         if let Some(mut rsp) = rsp {
             self.ctx
                 .set_custom_header_for_local_reply(&self.tcp_notes, &mut rsp);
+            self.ctx.set_error_header_for_local_reply(
+                &self.task_notes.id,
+                ServerTaskErrorCode::from(e),
+                &mut rsp,
+            );
 
             if rsp.should_close() {
                 self.should_close = true;
@@ -231,12 +241,16 @@ impl<'a> HttpProxyForwardTask<'a> {
             Ok(()) => {
                 self.get_log_context()
                     .log(&self.ctx.task_logger, &ServerTaskError::Finished);
+                self.pre_stop();
             }
             Err(e) => {
                 self.get_log_context().log(&self.ctx.task_logger, &e);
+                self.pre_stop();
+                if self.ctx.tarpit_on_error(&e, clt_w).await {
+                    self.should_close = true;
+                }
             }
         }
-        self.pre_stop();
     }
 
     fn pre_start(&self) {
@@ -584,6 +598,12 @@ impl<'a> HttpProxyForwardTask<'a> {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
 
         self.setup_clt_limit_and_stats(clt_r, clt_w);
 
@@ -730,9 +750,12 @@ impl<'a> HttpProxyForwardTask<'a> {
                 };
             }
         }
-        ups_c
-            .0
-            .prepare_new(&self.task_notes, &self.tcp_notes.upstream);
+        ups_c.0.prepare_new(
+            &self.task_notes,
+            &self.tcp_notes.upstream,
+            reused_connection,
+        );
+        self.http_notes.set_ups_conn_meta(ups_c.0.connection_meta());
 
         if audit_task {
             if let Some(audit_handle) = self.audit_ctx.handle() {
@@ -44,8 +44,8 @@ use crate::module::ftp_over_http::{BoxFtpRemoteConnection, FtpOverHttpTaskNotes,
 use crate::module::http_forward::HttpProxyClientResponse;
 use crate::module::tcp_connect::TcpConnectError;
 use crate::serve::{
-    ServerStats, ServerTaskError, ServerTaskForbiddenError, ServerTaskNotes, ServerTaskResult,
-    ServerTaskStage,
+    ServerStats, ServerTaskError, ServerTaskErrorCode, ServerTaskForbiddenError, ServerTaskNotes,
+    ServerTaskResult, ServerTaskStage,
 };
 
 type HttpProxyFtpClient = FtpClient<
@@ -123,12 +123,16 @@ impl<'a> FtpOverHttpTask<'a> {
             Ok(()) => {
                 self.get_log_context()
                     .log(&self.ctx.task_logger, &ServerTaskError::Finished);
+                self.pre_stop();
             }
             Err(e) => {
                 self.get_log_context().log(&self.ctx.task_logger, &e);
+                self.pre_stop();
+                if self.ctx.tarpit_on_error(&e, clt_w).await {
+                    self.should_close = true;
+                }
             }
         }
-        self.pre_stop();
     }
 
     fn pre_start(&self) {
@@ -167,6 +171,15 @@ impl<'a> FtpOverHttpTask<'a> {
             .set_custom_header_for_local_reply(&self.ftp_notes.control_tcp_notes, rsp);
     }
 
+    fn enable_error_header_for_local_reply(
+        &self,
+        code: ServerTaskErrorCode,
+        rsp: &mut HttpProxyClientResponse,
+    ) {
+        self.ctx
+            .set_error_header_for_local_reply(&self.task_notes.id, code, rsp);
+    }
+
     async fn reply_too_many_requests<W>(&mut self, clt_w: &mut W)
     where
         W: AsyncWrite + Unpin,
@@ -418,6 +431,12 @@ impl<'a> FtpOverHttpTask<'a> {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
 
         self.setup_clt_limit_and_stats(clt_r, clt_w);
 
@@ -645,6 +664,7 @@ impl<'a> FtpOverHttpTask<'a> {
                     self.should_close || body_pending,
                 );
                 self.enable_custom_header_for_local_reply(&mut rsp);
+                self.enable_error_header_for_local_reply(ServerTaskErrorCode::from(&e), &mut rsp);
                 if rsp.reply_err_to_request(clt_w).await.is_ok() {
                     self.ftp_notes.rsp_status = rsp.status();
                     self.should_close = rsp.should_close();
@@ -1326,6 +1346,10 @@ impl<'a> FtpOverHttpTask<'a> {
                             HttpProxyClientResponse::from_task_err(&e, self.req.version, true)
                         {
                             self.enable_custom_header_for_local_reply(&mut rsp);
+                            self.enable_error_header_for_local_reply(
+                                ServerTaskErrorCode::from(&e),
+                                &mut rsp,
+                            );
                             rsp.reply_err_to_request(clt_w)
                                 .await
                                 .map_err(ServerTaskError::ClientTcpWriteFailed)?;
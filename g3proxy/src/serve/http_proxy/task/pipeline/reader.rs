@@ -113,6 +113,7 @@ where
                         &mut reader,
                         stream_sender.clone(),
                         self.ctx.server_config.req_hdr_max_size,
+                        self.ctx.server_config.req_hdr_max_count,
                         self.ctx.server_config.steal_forwarded_for,
                         self.ctx.server_config.allow_custom_host,
                         &mut version,
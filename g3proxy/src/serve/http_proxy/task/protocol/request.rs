@@ -42,14 +42,19 @@ where
         reader: &mut HttpClientReader<CDR>,
         sender: mpsc::Sender<Option<HttpClientReader<CDR>>>,
         max_header_size: usize,
+        max_header_count: usize,
         steal_forwarded_for: bool,
         allow_custom_host: bool,
         version: &mut Version,
     ) -> Result<(Self, bool), HttpRequestParseError> {
         let time_accepted = Instant::now();
 
-        let req =
-            HttpProxyClientRequest::parse(reader, max_header_size, version, |req, name, header| {
+        let req = HttpProxyClientRequest::parse(
+            reader,
+            max_header_size,
+            max_header_count,
+            version,
+            |req, name, header| {
                 match name.as_str() {
                     "proxy-authorization" => return req.parse_header_authorization(header.value),
                     "proxy-connection" => {
@@ -65,8 +70,9 @@ where
                 }
                 req.append_header(name, header)?;
                 Ok(())
-            })
-            .await?;
+            },
+        )
+        .await?;
         let time_received = Instant::now();
 
         let (upstream, sub_protocol) = if matches!(&req.method, &Method::CONNECT) {
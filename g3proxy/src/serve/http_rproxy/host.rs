@@ -18,7 +18,11 @@ use std::sync::Arc;
 
 use anyhow::Context;
 
-use g3_types::net::{OpensslClientConfig, OpensslTicketKey, RollingTicketer, RustlsServerConfig};
+use g3_http::server::HttpProxyClientRequest;
+use g3_types::net::{
+    HttpPathRewriteError, OpensslClientConfig, OpensslTicketKey, RollingTicketer,
+    RustlsServerConfig,
+};
 
 use crate::config::server::http_rproxy::HttpHostConfig;
 
@@ -55,4 +59,21 @@ impl HttpHost {
             tls_client,
         })
     }
+
+    /// Rewrite the request path in place, return whether the path is changed
+    pub(super) fn rewrite_path(
+        &self,
+        req: &mut HttpProxyClientRequest,
+    ) -> Result<bool, HttpPathRewriteError> {
+        if self.config.path_rewrite.is_empty() {
+            return Ok(false);
+        }
+        match self.config.path_rewrite.rewrite_uri(&req.uri)? {
+            Some(uri) => {
+                req.uri = uri;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
 }
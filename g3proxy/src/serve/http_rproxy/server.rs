@@ -35,7 +35,7 @@ use g3_daemon::listen::{AcceptQuicServer, AcceptTcpServer, ListenStats, ListenTc
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerReloadCommand};
 use g3_io_ext::AsyncStream;
 use g3_openssl::SslStream;
-use g3_types::acl::{AclAction, AclNetworkRule};
+use g3_types::acl::{AclAction, AclNetworkRule, AclOrderedNetworkRule};
 use g3_types::metrics::MetricsName;
 use g3_types::net::{
     OpensslTicketKey, RollingTicketer, RustlsServerConfig, RustlsServerConnectionExt, UpstreamAddr,
@@ -62,6 +62,7 @@ pub(crate) struct HttpRProxyServer {
     tls_rolling_ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
     global_tls_server: Option<RustlsServerConfig>,
     ingress_net_filter: Option<AclNetworkRule>,
+    client_net_acl: Option<AclOrderedNetworkRule>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
     task_logger: Logger,
     hosts: HostMatch<Arc<HttpHost>>,
@@ -98,6 +99,11 @@ impl HttpRProxyServer {
             .as_ref()
             .map(|builder| builder.build());
 
+        let client_net_acl = config
+            .client_net_acl
+            .as_ref()
+            .map(|builder| builder.build());
+
         let task_logger = config.get_task_logger();
 
         // always update extra metrics tags
@@ -113,6 +119,7 @@ impl HttpRProxyServer {
             tls_rolling_ticketer,
             global_tls_server,
             ingress_net_filter,
+            client_net_acl,
             reload_sender,
             task_logger,
             hosts,
@@ -215,6 +222,17 @@ impl HttpRProxyServer {
             }
         }
 
+        if let Some(client_net_acl) = &self.client_net_acl {
+            if crate::serve::forbid_by_client_net_acl(
+                client_net_acl,
+                self.config.name(),
+                client_addr,
+            ) {
+                self.listen_stats.add_dropped();
+                return true;
+            }
+        }
+
         // TODO add cps limit
 
         false
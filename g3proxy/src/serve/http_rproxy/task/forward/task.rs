@@ -480,6 +480,12 @@ impl<'a> HttpRProxyForwardTask<'a> {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
 
         self.setup_clt_limit_and_stats(clt_r, clt_w);
 
@@ -603,9 +609,12 @@ impl<'a> HttpRProxyForwardTask<'a> {
         CDR: AsyncRead + Unpin,
         CDW: AsyncWrite + Unpin,
     {
-        ups_c
-            .0
-            .prepare_new(&self.task_notes, &self.tcp_notes.upstream);
+        ups_c.0.prepare_new(
+            &self.task_notes,
+            &self.tcp_notes.upstream,
+            reused_connection,
+        );
+        self.http_notes.set_ups_conn_meta(ups_c.0.connection_meta());
 
         if self.req.body_type().is_none() {
             self.mark_relaying();
@@ -131,6 +131,7 @@ where
                         &mut reader,
                         stream_sender.clone(),
                         self.ctx.server_config.req_hdr_max_size,
+                        self.ctx.server_config.req_hdr_max_count,
                         self.ctx.server_config.server_id.as_ref(),
                         &mut version,
                     ),
@@ -18,12 +18,12 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use ahash::AHashMap;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::sync::mpsc;
 
 use g3_io_ext::{ArcLimitedWriterStats, LimitedWriter};
 use g3_types::auth::UserAuthError;
-use g3_types::net::{HttpAuth, HttpBasicAuth};
+use g3_types::net::{HttpAuth, HttpBasicAuth, HttpBlockResponse};
 use g3_types::route::HostMatch;
 
 use super::protocol::{HttpClientWriter, HttpRProxyRequest};
@@ -204,13 +204,44 @@ where
     pub(crate) async fn into_running(mut self, hosts: &HostMatch<Arc<HttpHost>>) {
         loop {
             let res = match self.task_queue.recv().await {
-                Some(Ok(req)) => {
+                Some(Ok(mut req)) => {
+                    if let Some(rsp) = self
+                        .ctx
+                        .server_config
+                        .local_response
+                        .find(&req.inner.method, req.inner.uri.path())
+                        .cloned()
+                    {
+                        // the response will always be `Connection: Close`
+                        self.reply_local(req, &rsp).await;
+                        self.pipeline_stats.del_task();
+                        break;
+                    }
+
                     let res = match self.do_auth(&req) {
                         Ok(user_ctx) => {
                             self.req_count.consequent_auth_failed = 0;
 
                             match hosts.get(req.upstream.host()).cloned() {
-                                Some(host) => self.run(req, user_ctx, host).await,
+                                Some(host) => match host.rewrite_path(&mut req.inner) {
+                                    Ok(_) => self.run(req, user_ctx, host).await,
+                                    Err(_) => {
+                                        // close the connection if the rewritten path is invalid
+                                        self.req_count.invalid += 1;
+
+                                        if !self.ctx.server_config.no_early_error_reply {
+                                            if let Some(stream_w) = &mut self.stream_writer {
+                                                let rsp = HttpProxyClientResponse::bad_request(
+                                                    req.inner.version,
+                                                );
+                                                let _ = rsp.reply_err_to_request(stream_w).await;
+                                            }
+                                        }
+
+                                        self.notify_reader_to_close();
+                                        LoopAction::Break
+                                    }
+                                },
                                 None => {
                                     // close the connection if no host config found
                                     self.req_count.invalid += 1;
@@ -267,11 +298,12 @@ where
         user_ctx: Option<UserContext>,
         host: Arc<HttpHost>,
     ) -> LoopAction {
-        let task_notes = ServerTaskNotes::new(
+        let mut task_notes = ServerTaskNotes::new(
             self.ctx.cc_info.clone(),
             user_ctx,
             req.time_accepted.elapsed(),
         );
+        task_notes.tag_tenant(self.ctx.server_config.as_ref(), None);
 
         if let Some(mut stream_w) = self.stream_writer.take() {
             let mut audit_ctx = AuditContext::default();
@@ -443,6 +475,22 @@ where
         }
     }
 
+    /// send the configured local response directly, no upstream connection will be made
+    async fn reply_local(&mut self, mut req: HttpRProxyRequest<CDR>, rsp: &HttpBlockResponse) {
+        if let Some(clt_w) = &mut self.stream_writer {
+            let buf = rsp.serialize_h1(req.inner.version);
+            if clt_w.write_all(&buf).await.is_ok() {
+                let _ = clt_w.flush().await;
+            }
+        }
+
+        if req.body_reader.take().is_some() {
+            let _ = req.stream_sender.send(None).await;
+        } else {
+            self.notify_reader_to_close();
+        }
+    }
+
     /// notify reader to close while it's not closed and not in waiting writer status.
     /// always use the req.stream_sender.send(None) when possible.
     fn notify_reader_to_close(&mut self) {
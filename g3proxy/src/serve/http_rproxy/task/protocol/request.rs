@@ -43,20 +43,26 @@ where
         reader: &mut HttpClientReader<CDR>,
         sender: mpsc::Sender<Option<HttpClientReader<CDR>>>,
         max_header_size: usize,
+        max_header_count: usize,
         server_id: Option<&HttpServerId>,
         version: &mut Version,
     ) -> Result<(Self, bool), HttpRequestParseError> {
         let time_accepted = Instant::now();
 
-        let mut req =
-            HttpProxyClientRequest::parse(reader, max_header_size, version, |req, name, header| {
+        let mut req = HttpProxyClientRequest::parse(
+            reader,
+            max_header_size,
+            max_header_count,
+            version,
+            |req, name, header| {
                 if name.as_str() == "authorization" {
                     return req.parse_header_authorization(header.value);
                 }
                 req.append_header(name, header)?;
                 Ok(())
-            })
-            .await?;
+            },
+        )
+        .await?;
         let time_received = Instant::now();
 
         if matches!(&req.method, &Method::CONNECT) {
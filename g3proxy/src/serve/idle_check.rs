@@ -26,6 +26,8 @@ pub(crate) struct ServerIdleChecker {
     pub(crate) idle_duration: Duration,
     pub(crate) user: Option<Arc<User>>,
     pub(crate) task_max_idle_count: i32,
+    /// the max idle count of the identified protocol, which takes precedence over the user config
+    pub(crate) protocol_max_idle_count: Option<i32>,
     pub(crate) server_quit_policy: Arc<ServerQuitPolicy>,
 }
 
@@ -35,7 +37,9 @@ impl IdleCheck for ServerIdleChecker {
     }
 
     fn check_quit(&self, idle_count: i32) -> bool {
-        if let Some(user) = &self.user {
+        if let Some(max_count) = self.protocol_max_idle_count {
+            idle_count > max_count
+        } else if let Some(user) = &self.user {
             idle_count > user.task_max_idle_count()
         } else {
             idle_count > self.task_max_idle_count
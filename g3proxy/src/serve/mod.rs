@@ -14,11 +14,15 @@
  * limitations under the License.
  */
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use async_trait::async_trait;
+use log::{debug, info};
 #[cfg(feature = "quic")]
 use quinn::Connection;
+use tokio::io::AsyncWrite;
 use tokio::net::TcpStream;
 use tokio::sync::broadcast;
 use tokio_rustls::server::TlsStream;
@@ -28,9 +32,11 @@ use g3_daemon::listen::{
 };
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerQuitPolicy, ServerReloadCommand};
 use g3_openssl::SslStream;
+use g3_types::acl::{AclAction, AclOrderedNetworkRule};
+use g3_types::limit::{ClientConnLimitConfig, ClientConnLimiter};
 use g3_types::metrics::MetricsName;
 
-use crate::config::server::AnyServerConfig;
+use crate::config::server::{AnyServerConfig, TarpitTrigger};
 
 mod registry;
 pub(crate) use registry::{foreach_online as foreach_server, get_names, get_or_insert_default};
@@ -38,6 +44,20 @@ pub(crate) use registry::{foreach_online as foreach_server, get_names, get_or_in
 mod idle_check;
 pub(crate) use idle_check::ServerIdleChecker;
 
+mod tls_alpn;
+use tls_alpn::TlsAlpnNextServers;
+
+mod tarpit;
+pub(crate) use tarpit::ServerTarpit;
+
+mod tls_ocsp;
+pub(crate) use tls_ocsp::{
+    foreach_stats as foreach_ocsp_stapling_stats, OcspStaplingSnapshot, OcspStaplingStats,
+};
+use tls_ocsp::{
+    spawn_rustls_stapler as spawn_rustls_ocsp_stapler, OcspStapleReady, OcspStapleUpdater,
+};
+
 mod dummy_close;
 mod intelli_proxy;
 mod native_tls_port;
@@ -62,9 +82,18 @@ mod tls_stream;
 
 mod error;
 mod task;
+mod tenant;
 
-pub(crate) use error::{ServerTaskError, ServerTaskForbiddenError, ServerTaskResult};
+pub(crate) use error::{
+    ServerTaskError, ServerTaskErrorCode, ServerTaskForbiddenError, ServerTaskResult,
+};
 pub(crate) use task::{ServerTaskNotes, ServerTaskStage};
+#[cfg(test)]
+pub(crate) use tcp_stream::TcpStreamServerStats;
+pub(crate) use tenant::{
+    foreach_stats as foreach_tenant_stats, prune_idle_stats as prune_idle_tenant_stats,
+    TenantTaskStats,
+};
 
 mod ops;
 pub(crate) use ops::{
@@ -113,6 +142,11 @@ pub(crate) trait Server:
     fn alive_count(&self) -> i32;
     fn quit_policy(&self) -> &Arc<ServerQuitPolicy>;
 
+    /// reload the tls certificates for new handshakes, existing connections are not affected
+    fn reload_tls_cert(&self) -> anyhow::Result<()> {
+        Err(anyhow!("tls cert reload is not supported on this server"))
+    }
+
     async fn run_rustls_task(&self, stream: TlsStream<TcpStream>, cc_info: ClientConnectionInfo);
 
     async fn run_openssl_task(&self, stream: SslStream<TcpStream>, cc_info: ClientConnectionInfo);
@@ -167,3 +201,69 @@ impl ReloadQuicServer for WrapArcServer {
 fn new_reload_notify_channel() -> broadcast::Sender<ServerReloadCommand> {
     broadcast::Sender::new(16)
 }
+
+/// Check the client address against the client network acl, return true if it should be dropped
+fn forbid_by_client_net_acl(
+    acl: &AclOrderedNetworkRule,
+    server: &MetricsName,
+    client_addr: SocketAddr,
+) -> bool {
+    let (_, action, reason) = acl.check(client_addr.ip());
+    match action {
+        AclAction::Permit | AclAction::PermitAndLog => false,
+        AclAction::Forbid => true,
+        AclAction::ForbidAndLog => {
+            info!(
+                "server {server}: dropped connection from {client_addr}: {}",
+                reason.unwrap_or("forbidden by client network acl")
+            );
+            true
+        }
+    }
+}
+
+/// Build the client connection limiter, the alive connection counts will be kept on reload
+fn reload_client_conn_limiter(
+    old: Option<&ClientConnLimiter>,
+    config: Option<&ClientConnLimitConfig>,
+) -> Option<ClientConnLimiter> {
+    let config = config?.clone();
+    match old {
+        Some(limiter) => Some(limiter.new_updated(config)),
+        None => Some(ClientConnLimiter::new(config)),
+    }
+}
+
+/// Handle the connection that reached the client connection limit.
+///
+/// The connection should be closed after this returns. It will be held by the server tarpit if
+/// selected, or else the tarpit delay of the limiter will be applied if set, which is also the
+/// case when the server tarpit is full.
+async fn reject_by_client_conn_limit<W>(
+    limiter: &ClientConnLimiter,
+    tarpit: Option<&ServerTarpit>,
+    server: &MetricsName,
+    listen_stats: &ListenStats,
+    client_addr: SocketAddr,
+    writer: Option<W>,
+) where
+    W: AsyncWrite + Unpin,
+{
+    listen_stats.add_limited();
+    debug!(
+        "server {server}: rejected connection from {client_addr}: reached the connection limit {}",
+        limiter.config().max_per_ip()
+    );
+    if let (Some(tarpit), Some(writer)) = (tarpit, writer) {
+        if tarpit
+            .hold(TarpitTrigger::ClientConnLimit, server, client_addr, writer)
+            .await
+        {
+            return;
+        }
+    }
+    let tarpit_delay = limiter.config().tarpit_delay();
+    if !tarpit_delay.is_zero() {
+        tokio::time::sleep(tarpit_delay).await;
+    }
+}
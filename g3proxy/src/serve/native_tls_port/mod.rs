@@ -34,21 +34,30 @@ use g3_io_ext::haproxy::{ProxyProtocolV1Reader, ProxyProtocolV2Reader};
 use g3_openssl::{SslAcceptor, SslStream};
 use g3_types::acl::{AclAction, AclNetworkRule};
 use g3_types::metrics::MetricsName;
-use g3_types::net::{OpensslServerConfig, OpensslTicketKey, ProxyProtocolVersion, RollingTicketer};
+use g3_types::net::{
+    OpensslOcspStapler, OpensslServerConfig, OpensslTicketKey, ProxyProtocolVersion,
+    RollingTicketer,
+};
 
 use crate::config::server::native_tls_port::NativeTlsPortConfig;
 use crate::config::server::{AnyServerConfig, ServerConfig};
-use crate::serve::{ArcServer, Server, ServerInternal, ServerQuitPolicy, WrapArcServer};
+use crate::serve::{
+    ArcServer, OcspStapleReady, OcspStapleUpdater, Server, ServerInternal, ServerQuitPolicy,
+    TlsAlpnNextServers, WrapArcServer,
+};
 
 pub(crate) struct NativeTlsPort {
     config: NativeTlsPortConfig,
     listen_stats: Arc<ListenStats>,
     tls_rolling_ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
     tls_server_config: OpensslServerConfig,
+    tls_ocsp_stapler: Option<Arc<OpensslOcspStapler>>,
+    tls_ocsp_ready: Option<OcspStapleReady>,
     ingress_net_filter: Option<AclNetworkRule>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
 
     next_server: ArcSwap<ArcServer>,
+    alpn_servers: TlsAlpnNextServers,
     quit_policy: Arc<ServerQuitPolicy>,
     reload_version: usize,
 }
@@ -58,17 +67,43 @@ impl NativeTlsPort {
         config: NativeTlsPortConfig,
         listen_stats: Arc<ListenStats>,
         tls_rolling_ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
+        old_ocsp_stapler: Option<&OpensslOcspStapler>,
         reload_version: usize,
     ) -> anyhow::Result<Self> {
         let reload_sender = crate::serve::new_reload_notify_channel();
 
-        let tls_server_config = if let Some(builder) = &config.server_tls_config {
-            builder
-                .build_with_ticketer(tls_rolling_ticketer.clone())
-                .context("failed to build tls server config")?
-        } else {
+        let Some(builder) = &config.server_tls_config else {
             return Err(anyhow!("no tls server config set"));
         };
+        let mut tls_ocsp_ready = None;
+        let tls_ocsp_stapler = if let Some(c) = &config.tls_ocsp_stapling {
+            let stapler = builder
+                .new_ocsp_stapler(c.hard_fail)
+                .context("failed to create tls ocsp stapler")?;
+            if let Some(old) = old_ocsp_stapler {
+                stapler.inherit_staples(old);
+            }
+            let stapler = Arc::new(stapler);
+            let ready = OcspStapleUpdater::new(config.name(), c.clone(), &stapler).spawn_run();
+            if c.hard_fail {
+                tls_ocsp_ready = Some(ready);
+            }
+            Some(stapler)
+        } else {
+            None
+        };
+        let alpn_protocols = if config.alpn_servers.is_empty() {
+            None
+        } else {
+            Some(config.alpn_servers.protocols())
+        };
+        let tls_server_config = builder
+            .build_with_ocsp_stapler(
+                alpn_protocols,
+                tls_rolling_ticketer.clone(),
+                tls_ocsp_stapler.clone(),
+            )
+            .context("failed to build tls server config")?;
 
         let ingress_net_filter = config
             .ingress_net_filter
@@ -76,15 +111,19 @@ impl NativeTlsPort {
             .map(|builder| builder.build());
 
         let next_server = Arc::new(crate::serve::get_or_insert_default(&config.server));
+        let alpn_servers = TlsAlpnNextServers::new(&config.alpn_servers);
 
         Ok(NativeTlsPort {
             config,
             listen_stats,
             tls_rolling_ticketer,
             tls_server_config,
+            tls_ocsp_stapler,
+            tls_ocsp_ready,
             ingress_net_filter,
             reload_sender,
             next_server: ArcSwap::new(next_server),
+            alpn_servers,
             quit_policy: Arc::new(ServerQuitPolicy::default()),
             reload_version,
         })
@@ -102,7 +141,7 @@ impl NativeTlsPort {
             None
         };
 
-        let server = NativeTlsPort::new(config, listen_stats, tls_rolling_ticketer, 1)?;
+        let server = NativeTlsPort::new(config, listen_stats, tls_rolling_ticketer, None, 1)?;
         Ok(Arc::new(server))
     }
 
@@ -125,6 +164,7 @@ impl NativeTlsPort {
                 config,
                 listen_stats,
                 tls_rolling_ticketer,
+                self.tls_ocsp_stapler.as_deref(),
                 self.reload_version + 1,
             )
         } else {
@@ -187,6 +227,17 @@ impl NativeTlsPort {
             None => {}
         }
 
+        if let Some(ready) = &self.tls_ocsp_ready {
+            // the handshake will fail with hard fail before the first fetch of the staples
+            if tokio::time::timeout(self.tls_server_config.accept_timeout, ready.wait())
+                .await
+                .is_err()
+            {
+                self.listen_stats.add_timeout();
+                return;
+            }
+        }
+
         let Ok(ssl_acceptor) = SslAcceptor::new(ssl, stream) else {
             self.listen_stats.add_dropped();
             return;
@@ -199,7 +250,10 @@ impl NativeTlsPort {
                     // Quick ACK is needed with session resumption
                     cc_info.tcp_sock_try_quick_ack();
                 }
-                let next_server = self.next_server.load().as_ref().clone();
+                let next_server = self
+                    .alpn_servers
+                    .select(ssl_stream.ssl().selected_alpn_protocol())
+                    .unwrap_or_else(|| self.next_server.load().as_ref().clone());
                 next_server.run_openssl_task(ssl_stream, cc_info).await
             }
             Ok(Err(e)) => {
@@ -234,7 +288,7 @@ impl ServerInternal for NativeTlsPort {
     }
 
     fn _depend_on_server(&self, name: &MetricsName) -> bool {
-        self.config.server.eq(name)
+        self.config.server.eq(name) || self.config.alpn_servers.depend_on_server(name)
     }
 
     fn _reload_config_notify_runtime(&self) {
@@ -245,6 +299,7 @@ impl ServerInternal for NativeTlsPort {
     fn _update_next_servers_in_place(&self) {
         let next_server = crate::serve::get_or_insert_default(&self.config.server);
         self.next_server.store(Arc::new(next_server));
+        self.alpn_servers.update_in_place();
     }
 
     fn _update_escaper_in_place(&self) {}
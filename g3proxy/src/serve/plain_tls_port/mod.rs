@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::io;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -21,12 +22,12 @@ use std::time::Duration;
 use anyhow::{anyhow, Context};
 use arc_swap::ArcSwap;
 use async_trait::async_trait;
-use log::debug;
+use log::{debug, info, warn};
 #[cfg(feature = "quic")]
 use quinn::Connection;
 use tokio::net::TcpStream;
 use tokio::sync::broadcast;
-use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tokio_rustls::{server::TlsStream, LazyConfigAcceptor, TlsAcceptor};
 
 use g3_daemon::listen::{AcceptQuicServer, AcceptTcpServer, ListenStats, ListenTcpRuntime};
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerReloadCommand};
@@ -35,23 +36,79 @@ use g3_openssl::SslStream;
 use g3_types::acl::{AclAction, AclNetworkRule};
 use g3_types::metrics::MetricsName;
 use g3_types::net::{
-    OpensslTicketKey, ProxyProtocolVersion, RollingTicketer, RustlsServerConnectionExt,
+    OpensslOcspStapler, OpensslTicketKey, ProxyProtocolVersion, RollingTicketer,
+    RustlsServerConfigBuilder, RustlsServerConnectionExt,
 };
 
 use crate::config::server::plain_tls_port::PlainTlsPortConfig;
-use crate::config::server::{AnyServerConfig, ServerConfig};
-use crate::serve::{ArcServer, Server, ServerInternal, ServerQuitPolicy, WrapArcServer};
+use crate::config::server::{
+    AnyServerConfig, ServerConfig, TlsAlpnServersConfig, TlsOcspStaplingConfig,
+};
+use crate::serve::{
+    ArcServer, Server, ServerInternal, ServerQuitPolicy, TlsAlpnNextServers, WrapArcServer,
+};
+
+struct PlainTlsDrivers {
+    default: Arc<rustls::ServerConfig>,
+    alpn: Option<Arc<rustls::ServerConfig>>,
+    ocsp_stapler: Option<Arc<OpensslOcspStapler>>,
+}
+
+impl PlainTlsDrivers {
+    fn build(
+        server: &MetricsName,
+        builder: &RustlsServerConfigBuilder,
+        alpn_servers: &TlsAlpnServersConfig,
+        tls_rolling_ticketer: &Option<Arc<RollingTicketer<OpensslTicketKey>>>,
+        tls_ocsp_stapling: Option<&TlsOcspStaplingConfig>,
+        old_ocsp_stapler: Option<&OpensslOcspStapler>,
+    ) -> anyhow::Result<(Self, Duration)> {
+        let ocsp_stapler = match tls_ocsp_stapling {
+            Some(c) => Some(crate::serve::spawn_rustls_ocsp_stapler(
+                server,
+                c,
+                builder,
+                old_ocsp_stapler,
+            )?),
+            None => None,
+        };
+        let tls_server_config = builder
+            .build_with_ocsp_stapler(None, tls_rolling_ticketer.clone(), ocsp_stapler.clone())
+            .context("failed to build tls server config")?;
+        // rustls will abort the handshake if none of the client offered ALPN protocols matches,
+        // so the one with ALPN set will only be used if there is a match
+        let alpn = if alpn_servers.is_empty() {
+            None
+        } else {
+            let alpn_config = builder
+                .build_with_ocsp_stapler(
+                    Some(alpn_servers.protocols()),
+                    tls_rolling_ticketer.clone(),
+                    ocsp_stapler.clone(),
+                )
+                .context("failed to build tls server config with alpn protocols")?;
+            Some(alpn_config.driver)
+        };
+        let drivers = PlainTlsDrivers {
+            default: tls_server_config.driver,
+            alpn,
+            ocsp_stapler,
+        };
+        Ok((drivers, tls_server_config.accept_timeout))
+    }
+}
 
 pub(crate) struct PlainTlsPort {
     config: PlainTlsPortConfig,
     listen_stats: Arc<ListenStats>,
     tls_rolling_ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
-    tls_acceptor: TlsAcceptor,
+    tls_drivers: ArcSwap<PlainTlsDrivers>,
     tls_accept_timeout: Duration,
     ingress_net_filter: Option<AclNetworkRule>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
 
     next_server: ArcSwap<ArcServer>,
+    alpn_servers: TlsAlpnNextServers,
     quit_policy: Arc<ServerQuitPolicy>,
     reload_version: usize,
 }
@@ -61,17 +118,22 @@ impl PlainTlsPort {
         config: PlainTlsPortConfig,
         listen_stats: Arc<ListenStats>,
         tls_rolling_ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
+        old_ocsp_stapler: Option<&OpensslOcspStapler>,
         reload_version: usize,
     ) -> anyhow::Result<Self> {
         let reload_sender = crate::serve::new_reload_notify_channel();
 
-        let tls_server_config = if let Some(builder) = &config.server_tls_config {
-            builder
-                .build_with_ticketer(tls_rolling_ticketer.clone())
-                .context("failed to build tls server config")?
-        } else {
+        let Some(builder) = &config.server_tls_config else {
             return Err(anyhow!("no tls server config set"));
         };
+        let (tls_drivers, tls_accept_timeout) = PlainTlsDrivers::build(
+            config.name(),
+            builder,
+            &config.alpn_servers,
+            &tls_rolling_ticketer,
+            config.tls_ocsp_stapling.as_ref(),
+            old_ocsp_stapler,
+        )?;
 
         let ingress_net_filter = config
             .ingress_net_filter
@@ -79,16 +141,18 @@ impl PlainTlsPort {
             .map(|builder| builder.build());
 
         let next_server = Arc::new(crate::serve::get_or_insert_default(&config.server));
+        let alpn_servers = TlsAlpnNextServers::new(&config.alpn_servers);
 
         Ok(PlainTlsPort {
             config,
             listen_stats,
             tls_rolling_ticketer,
-            tls_acceptor: TlsAcceptor::from(tls_server_config.driver),
-            tls_accept_timeout: tls_server_config.accept_timeout,
+            tls_drivers: ArcSwap::from_pointee(tls_drivers),
+            tls_accept_timeout,
             ingress_net_filter,
             reload_sender,
             next_server: ArcSwap::new(next_server),
+            alpn_servers,
             quit_policy: Arc::new(ServerQuitPolicy::default()),
             reload_version,
         })
@@ -106,7 +170,7 @@ impl PlainTlsPort {
             None
         };
 
-        let server = PlainTlsPort::new(config, listen_stats, tls_rolling_ticketer, 1)?;
+        let server = PlainTlsPort::new(config, listen_stats, tls_rolling_ticketer, None, 1)?;
         Ok(Arc::new(server))
     }
 
@@ -125,10 +189,12 @@ impl PlainTlsPort {
                 None
             };
 
+            let tls_drivers = self.tls_drivers.load();
             PlainTlsPort::new(
                 config,
                 listen_stats,
                 tls_rolling_ticketer,
+                tls_drivers.ocsp_stapler.as_deref(),
                 self.reload_version + 1,
             )
         } else {
@@ -157,6 +223,55 @@ impl PlainTlsPort {
         false
     }
 
+    fn do_reload_tls_cert(&self) -> anyhow::Result<()> {
+        let Some(position) = self.config.position() else {
+            return Err(anyhow!("no config position found"));
+        };
+        let AnyServerConfig::PlainTlsPort(config) =
+            crate::config::server::parse_at_position(&position)?
+        else {
+            return Err(anyhow!(
+                "server type changed, a full server reload is required"
+            ));
+        };
+        let Some(builder) = &config.server_tls_config else {
+            return Err(anyhow!("no tls server config set"));
+        };
+        // the new cert and key pairs will be validated when building the drivers
+        let old_drivers = self.tls_drivers.load();
+        let (tls_drivers, _) = PlainTlsDrivers::build(
+            self.config.name(),
+            builder,
+            &self.config.alpn_servers,
+            &self.tls_rolling_ticketer,
+            self.config.tls_ocsp_stapling.as_ref(),
+            old_drivers.ocsp_stapler.as_deref(),
+        )?;
+        self.tls_drivers.store(Arc::new(tls_drivers));
+        Ok(())
+    }
+
+    async fn accept_tls(&self, stream: TcpStream) -> io::Result<TlsStream<TcpStream>> {
+        let tls_drivers = self.tls_drivers.load_full();
+        let Some(alpn_driver) = &tls_drivers.alpn else {
+            return TlsAcceptor::from(tls_drivers.default.clone())
+                .accept(stream)
+                .await;
+        };
+
+        let start = LazyConfigAcceptor::new(rustls::server::Acceptor::default(), stream).await?;
+        let alpn_matched = start
+            .client_hello()
+            .alpn()
+            .map(|mut offered| offered.any(|p| self.config.alpn_servers.contains(p)))
+            .unwrap_or(false);
+        if alpn_matched {
+            start.into_stream(alpn_driver.clone()).await
+        } else {
+            start.into_stream(tls_drivers.default.clone()).await
+        }
+    }
+
     async fn run_task(&self, mut stream: TcpStream, mut cc_info: ClientConnectionInfo) {
         match self.config.proxy_protocol {
             Some(ProxyProtocolVersion::V1) => {
@@ -186,14 +301,16 @@ impl PlainTlsPort {
             None => {}
         }
 
-        match tokio::time::timeout(self.tls_accept_timeout, self.tls_acceptor.accept(stream)).await
-        {
+        match tokio::time::timeout(self.tls_accept_timeout, self.accept_tls(stream)).await {
             Ok(Ok(tls_stream)) => {
                 if tls_stream.get_ref().1.session_reused() {
                     // Quick ACK is needed with session resumption
                     cc_info.tcp_sock_try_quick_ack();
                 }
-                let next_server = self.next_server.load().as_ref().clone();
+                let next_server = self
+                    .alpn_servers
+                    .select(tls_stream.get_ref().1.alpn_protocol())
+                    .unwrap_or_else(|| self.next_server.load().as_ref().clone());
                 next_server.run_rustls_task(tls_stream, cc_info).await
             }
             Ok(Err(e)) => {
@@ -228,7 +345,7 @@ impl ServerInternal for PlainTlsPort {
     }
 
     fn _depend_on_server(&self, name: &MetricsName) -> bool {
-        self.config.server.eq(name)
+        self.config.server.eq(name) || self.config.alpn_servers.depend_on_server(name)
     }
 
     fn _reload_config_notify_runtime(&self) {
@@ -239,6 +356,7 @@ impl ServerInternal for PlainTlsPort {
     fn _update_next_servers_in_place(&self) {
         let next_server = crate::serve::get_or_insert_default(&self.config.server);
         self.next_server.store(Arc::new(next_server));
+        self.alpn_servers.update_in_place();
     }
 
     fn _update_escaper_in_place(&self) {}
@@ -335,6 +453,22 @@ impl Server for PlainTlsPort {
         &self.quit_policy
     }
 
+    fn reload_tls_cert(&self) -> anyhow::Result<()> {
+        match self.do_reload_tls_cert() {
+            Ok(_) => {
+                info!("server {}: tls cert reloaded", self.name());
+                Ok(())
+            }
+            Err(e) => {
+                warn!(
+                    "server {}: failed to reload tls cert, keep using the old one: {e:?}",
+                    self.name()
+                );
+                Err(e)
+            }
+        }
+    }
+
     async fn run_rustls_task(&self, _stream: TlsStream<TcpStream>, _cc_info: ClientConnectionInfo) {
     }
 
@@ -31,7 +31,7 @@ use g3_daemon::listen::{AcceptQuicServer, AcceptTcpServer, ListenStats, ListenTc
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerReloadCommand};
 use g3_dpi::ProtocolPortMap;
 use g3_openssl::SslStream;
-use g3_types::acl::{AclAction, AclNetworkRule};
+use g3_types::acl::{AclAction, AclNetworkRule, AclOrderedNetworkRule};
 use g3_types::metrics::MetricsName;
 
 use super::{ClientHelloAcceptTask, CommonTaskContext, TcpStreamServerStats};
@@ -48,6 +48,7 @@ pub(crate) struct SniProxyServer {
     server_stats: Arc<TcpStreamServerStats>,
     listen_stats: Arc<ListenStats>,
     ingress_net_filter: Option<AclNetworkRule>,
+    client_net_acl: Option<AclOrderedNetworkRule>,
     server_tcp_portmap: Arc<ProtocolPortMap>,
     client_tcp_portmap: Arc<ProtocolPortMap>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
@@ -73,6 +74,11 @@ impl SniProxyServer {
             .as_ref()
             .map(|builder| builder.build());
 
+        let client_net_acl = config
+            .client_net_acl
+            .as_ref()
+            .map(|builder| builder.build());
+
         let server_tcp_portmap = Arc::new(config.server_tcp_portmap.clone());
         let client_tcp_portmap = Arc::new(config.client_tcp_portmap.clone());
 
@@ -88,6 +94,7 @@ impl SniProxyServer {
             server_stats,
             listen_stats,
             ingress_net_filter,
+            client_net_acl,
             server_tcp_portmap,
             client_tcp_portmap,
             reload_sender,
@@ -140,6 +147,17 @@ impl SniProxyServer {
             }
         }
 
+        if let Some(client_net_acl) = &self.client_net_acl {
+            if crate::serve::forbid_by_client_net_acl(
+                client_net_acl,
+                self.config.name(),
+                client_addr,
+            ) {
+                self.listen_stats.add_dropped();
+                return true;
+            }
+        }
+
         // TODO add cps limit
 
         false
@@ -24,7 +24,7 @@ use tokio::io::{AsyncRead, AsyncWrite};
 use g3_daemon::stat::task::{TcpStreamConnectionStats, TcpStreamTaskStats};
 use g3_dpi::Protocol;
 use g3_io_ext::{FlexBufReader, LimitedCopy, LimitedReader, LimitedWriter, OnceBufReader};
-use g3_types::net::UpstreamAddr;
+use g3_types::net::{Host, UpstreamAddr};
 
 use super::CommonTaskContext;
 use crate::audit::AuditContext;
@@ -53,7 +53,12 @@ impl TcpStreamTask {
         wait_time: Duration,
         pre_handshake_stats: TcpStreamConnectionStats,
     ) -> Self {
-        let task_notes = ServerTaskNotes::new(ctx.cc_info.clone(), None, wait_time);
+        let mut task_notes = ServerTaskNotes::new(ctx.cc_info.clone(), None, wait_time);
+        let sni = match upstream.host() {
+            Host::Domain(domain) => Some(domain.as_ref()),
+            Host::Ip(_) => None,
+        };
+        task_notes.tag_tenant(ctx.server_config.as_ref(), sni);
         TcpStreamTask {
             ctx,
             protocol,
@@ -130,6 +135,12 @@ impl TcpStreamTask {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
 
         self.task_notes.stage = ServerTaskStage::Connecting;
         let (ups_r, ups_w) = self
@@ -163,6 +174,9 @@ impl TcpStreamTask {
         UW: AsyncWrite + Send + Sync + Unpin + 'static,
     {
         self.task_notes.mark_relaying();
+        if self.ctx.server_config.task_log_on_connect {
+            self.get_log_context().log_connected(&self.ctx.task_logger);
+        }
         self.relay(clt_r, clt_r_buf, clt_w, ups_r, ups_w).await
     }
 
@@ -192,6 +206,7 @@ impl TcpStreamTask {
                 self.ctx.server_stats.clone(),
                 self.ctx.server_quit_policy.clone(),
                 &self.task_notes,
+                &self.tcp_notes,
             );
             let protocol_inspector = ctx.protocol_inspector(None);
             match self.protocol {
@@ -261,7 +276,10 @@ impl TcpStreamTask {
             ups_to_clt,
             &self.ctx.server_config,
             &self.ctx.server_quit_policy,
+            self.task_notes
+                .task_deadline(self.ctx.server_config.max_task_lifetime()),
             None,
+            self.ctx.server_config.task_max_idle_count(),
         )
         .await
     }
@@ -32,8 +32,9 @@ use g3_daemon::listen::{AcceptQuicServer, AcceptTcpServer, ListenStats, ListenTc
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerReloadCommand};
 use g3_io_ext::AsyncStream;
 use g3_openssl::SslStream;
-use g3_types::acl::{AclAction, AclNetworkRule};
+use g3_types::acl::{AclAction, AclNetworkRule, AclOrderedNetworkRule};
 use g3_types::acl_set::AclDstHostRuleSet;
+use g3_types::limit::{ClientConnLimiter, ClientConnPermit};
 use g3_types::metrics::MetricsName;
 
 use super::task::{CommonTaskContext, SocksProxyNegotiationTask};
@@ -41,10 +42,11 @@ use super::SocksProxyServerStats;
 use crate::audit::{AuditContext, AuditHandle};
 use crate::auth::UserGroup;
 use crate::config::server::socks_proxy::SocksProxyServerConfig;
-use crate::config::server::{AnyServerConfig, ServerConfig};
+use crate::config::server::{AnyServerConfig, ServerConfig, TarpitTrigger};
 use crate::escape::ArcEscaper;
 use crate::serve::{
-    ArcServer, ArcServerStats, Server, ServerInternal, ServerQuitPolicy, ServerStats, WrapArcServer,
+    ArcServer, ArcServerStats, Server, ServerInternal, ServerQuitPolicy, ServerStats, ServerTarpit,
+    WrapArcServer,
 };
 
 pub(crate) struct SocksProxyServer {
@@ -52,6 +54,9 @@ pub(crate) struct SocksProxyServer {
     server_stats: Arc<SocksProxyServerStats>,
     listen_stats: Arc<ListenStats>,
     ingress_net_filter: Option<Arc<AclNetworkRule>>,
+    client_net_acl: Option<AclOrderedNetworkRule>,
+    client_conn_limiter: Option<ClientConnLimiter>,
+    tarpit: Option<Arc<ServerTarpit>>,
     dst_host_filter: Option<Arc<AclDstHostRuleSet>>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
     task_logger: Logger,
@@ -68,6 +73,8 @@ impl SocksProxyServer {
         config: Arc<SocksProxyServerConfig>,
         server_stats: Arc<SocksProxyServerStats>,
         listen_stats: Arc<ListenStats>,
+        old_conn_limiter: Option<&ClientConnLimiter>,
+        old_tarpit: Option<&ServerTarpit>,
         version: usize,
     ) -> anyhow::Result<SocksProxyServer> {
         let reload_sender = crate::serve::new_reload_notify_channel();
@@ -77,6 +84,17 @@ impl SocksProxyServer {
             .as_ref()
             .map(|builder| Arc::new(builder.build()));
 
+        let client_net_acl = config
+            .client_net_acl
+            .as_ref()
+            .map(|builder| builder.build());
+
+        let client_conn_limiter = crate::serve::reload_client_conn_limiter(
+            old_conn_limiter,
+            config.client_conn_limit.as_ref(),
+        );
+        let tarpit = ServerTarpit::new_updated(old_tarpit, config.tarpit.as_ref());
+
         let dst_host_filter = config
             .dst_host_filter
             .as_ref()
@@ -95,6 +113,9 @@ impl SocksProxyServer {
             server_stats,
             listen_stats,
             ingress_net_filter,
+            client_net_acl,
+            client_conn_limiter,
+            tarpit,
             dst_host_filter,
             reload_sender,
             task_logger,
@@ -113,7 +134,7 @@ impl SocksProxyServer {
         let server_stats = Arc::new(SocksProxyServerStats::new(config.name()));
         let listen_stats = Arc::new(ListenStats::new(config.name()));
 
-        let server = SocksProxyServer::new(config, server_stats, listen_stats, 1)?;
+        let server = SocksProxyServer::new(config, server_stats, listen_stats, None, None, 1)?;
         Ok(Arc::new(server))
     }
 
@@ -123,8 +144,14 @@ impl SocksProxyServer {
             let server_stats = Arc::clone(&self.server_stats);
             let listen_stats = Arc::clone(&self.listen_stats);
 
-            let server =
-                SocksProxyServer::new(config, server_stats, listen_stats, self.reload_version + 1)?;
+            let server = SocksProxyServer::new(
+                config,
+                server_stats,
+                listen_stats,
+                self.client_conn_limiter.as_ref(),
+                self.tarpit.as_deref(),
+                self.reload_version + 1,
+            )?;
             Ok(server)
         } else {
             Err(anyhow!(
@@ -147,11 +174,60 @@ impl SocksProxyServer {
             }
         }
 
+        if let Some(client_net_acl) = &self.client_net_acl {
+            if crate::serve::forbid_by_client_net_acl(
+                client_net_acl,
+                self.config.name(),
+                client_addr,
+            ) {
+                self.listen_stats.add_dropped();
+                return true;
+            }
+        }
+
         // TODO add cps limit
 
         false
     }
 
+    /// Get the connection permit for the client, which should be held until the connection closed
+    fn acquire_conn_permit(&self, client_addr: SocketAddr) -> Result<Option<ClientConnPermit>, ()> {
+        let Some(limiter) = &self.client_conn_limiter else {
+            return Ok(None);
+        };
+        limiter.try_acquire(client_addr.ip()).map(Some).ok_or(())
+    }
+
+    async fn reject_by_conn_limit<W>(&self, client_addr: SocketAddr, writer: Option<W>)
+    where
+        W: AsyncWrite + Unpin,
+    {
+        if let Some(limiter) = &self.client_conn_limiter {
+            crate::serve::reject_by_client_conn_limit(
+                limiter,
+                self.tarpit.as_deref(),
+                self.config.name(),
+                &self.listen_stats,
+                client_addr,
+                writer,
+            )
+            .await;
+        }
+    }
+
+    async fn tarpit<S>(&self, trigger: TarpitTrigger, client_addr: SocketAddr, stream: S)
+    where
+        S: AsyncStream,
+        S::W: AsyncWrite + Unpin,
+    {
+        if let Some(tarpit) = &self.tarpit {
+            let (_clt_r, clt_w) = stream.into_split();
+            tarpit
+                .hold(trigger, self.config.name(), client_addr, clt_w)
+                .await;
+        }
+    }
+
     fn audit_context(&self) -> AuditContext {
         AuditContext::new(self.audit_handle.load_full())
     }
@@ -165,8 +241,15 @@ impl SocksProxyServer {
         let client_addr = cc_info.client_addr();
         self.server_stats.add_conn(client_addr);
         if self.drop_early(client_addr) {
+            self.tarpit(TarpitTrigger::NetworkAcl, client_addr, stream)
+                .await;
             return;
         }
+        let Ok(_conn_permit) = self.acquire_conn_permit(client_addr) else {
+            let (_clt_r, clt_w) = stream.into_split();
+            self.reject_by_conn_limit(client_addr, Some(clt_w)).await;
+            return;
+        };
 
         let ctx = CommonTaskContext {
             server_config: Arc::clone(&self.config),
@@ -177,6 +260,7 @@ impl SocksProxyServer {
             dst_host_filter: self.dst_host_filter.clone(),
             cc_info,
             task_logger: self.task_logger.clone(),
+            tarpit: self.tarpit.clone(),
         };
         SocksProxyNegotiationTask::new(ctx, self.audit_context(), self.user_group.load_full())
             .into_running(stream)
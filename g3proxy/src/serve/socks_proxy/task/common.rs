@@ -18,6 +18,7 @@ use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
 use slog::Logger;
+use tokio::io::AsyncWrite;
 use tokio::net::UdpSocket;
 
 use g3_daemon::server::ClientConnectionInfo;
@@ -26,8 +27,11 @@ use g3_types::acl_set::AclDstHostRuleSet;
 use g3_types::net::UpstreamAddr;
 
 use super::{SocksProxyServerConfig, SocksProxyServerStats};
+use crate::config::server::ServerConfig;
 use crate::escape::ArcEscaper;
-use crate::serve::{ServerQuitPolicy, ServerTaskError, ServerTaskNotes, ServerTaskResult};
+use crate::serve::{
+    ServerQuitPolicy, ServerTarpit, ServerTaskError, ServerTaskNotes, ServerTaskResult,
+};
 
 #[derive(Clone)]
 pub(crate) struct CommonTaskContext {
@@ -39,6 +43,7 @@ pub(crate) struct CommonTaskContext {
     pub(crate) dst_host_filter: Option<Arc<AclDstHostRuleSet>>,
     pub(crate) cc_info: ClientConnectionInfo,
     pub(crate) task_logger: Logger,
+    pub(crate) tarpit: Option<Arc<ServerTarpit>>,
 }
 
 impl CommonTaskContext {
@@ -47,6 +52,19 @@ impl CommonTaskContext {
         self.cc_info.client_addr()
     }
 
+    /// Hold the client connection in the server tarpit if the task is blocked by a selected rule
+    pub(crate) async fn tarpit_on_error<W>(&self, e: &ServerTaskError, clt_w: &mut W) -> bool
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let Some(tarpit) = &self.tarpit else {
+            return false;
+        };
+        tarpit
+            .hold_on_error(e, self.server_config.name(), self.client_addr(), clt_w)
+            .await
+    }
+
     #[inline]
     pub(super) fn server_addr(&self) -> SocketAddr {
         self.cc_info.server_addr()
@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::collections::BTreeSet;
 use std::sync::Arc;
 
 use log::debug;
@@ -29,6 +30,7 @@ use super::udp_connect::SocksProxyUdpConnectTask;
 use super::{CommonTaskContext, SocksProxyCltWrapperStats};
 use crate::audit::AuditContext;
 use crate::auth::{UserContext, UserGroup};
+use crate::config::server::socks_proxy::Socks5ServerAuthMethod;
 use crate::config::server::ServerConfig;
 use crate::serve::{
     ServerStats, ServerTaskError, ServerTaskForbiddenError, ServerTaskNotes, ServerTaskResult,
@@ -135,9 +137,19 @@ impl SocksProxyNegotiationTask {
         CDR: AsyncRead + Send + Sync + Unpin + 'static,
         CDW: AsyncWrite + Send + Sync + Unpin + 'static,
     {
+        if !self.ctx.server_config.enable_socks4 {
+            let _ = v4a::SocksV4Reply::RequestRejectedOrFailed
+                .send(&mut clt_w)
+                .await;
+            return Err(ServerTaskError::InvalidClientProtocol("socks4 is disabled"));
+        }
+
         if self.user_group.is_some() {
             // socks4(a) doesn't support auth
             self.ctx.server_stats.forbidden.add_auth_failed();
+            let _ = v4a::SocksV4Reply::RequestRejectedOrFailed
+                .send(&mut clt_w)
+                .await;
             return Err(ServerTaskError::InvalidClientProtocol(
                 "socks4 does not support auth",
             ));
@@ -145,8 +157,9 @@ impl SocksProxyNegotiationTask {
 
         let req = v4a::SocksV4aRequest::recv(&mut clt_r).await?;
 
-        let task_notes =
+        let mut task_notes =
             ServerTaskNotes::new(self.ctx.cc_info.clone(), None, self.time_accepted.elapsed());
+        task_notes.tag_tenant(self.ctx.server_config.as_ref(), None);
         match req.command {
             SocksCommand::TcpConnect => {
                 let task = SocksProxyTcpConnectTask::new(
@@ -171,6 +184,34 @@ impl SocksProxyNegotiationTask {
         }
     }
 
+    /// Select the first offered auth method that is also supported by the client
+    fn select_v5_auth_method(&self, client_methods: &BTreeSet<SocksAuthMethod>) -> SocksAuthMethod {
+        for method in &self.ctx.server_config.socks5_auth_methods {
+            match method {
+                Socks5ServerAuthMethod::User => {
+                    if self.user_group.is_some() && client_methods.contains(&SocksAuthMethod::User)
+                    {
+                        return SocksAuthMethod::User;
+                    }
+                }
+                Socks5ServerAuthMethod::None => {
+                    if !client_methods.contains(&SocksAuthMethod::None) {
+                        continue;
+                    }
+                    match &self.user_group {
+                        Some(user_group) => {
+                            if user_group.allow_anonymous(self.ctx.client_addr()) {
+                                return SocksAuthMethod::None;
+                            }
+                        }
+                        None => return SocksAuthMethod::None,
+                    }
+                }
+            }
+        }
+        SocksAuthMethod::NoAcceptable
+    }
+
     async fn run_v5<CDR, CDW>(
         self,
         mut clt_r: BufReader<LimitedReader<CDR>>,
@@ -181,20 +222,15 @@ impl SocksProxyNegotiationTask {
         CDW: AsyncWrite + Send + Sync + Unpin + 'static,
     {
         let client_methods = v5::auth::recv_methods_from_client(&mut clt_r).await?;
-        let auth_method = if let Some(user_group) = &self.user_group {
-            if client_methods.contains(&SocksAuthMethod::User) {
-                SocksAuthMethod::User
-            } else if user_group.allow_anonymous(self.ctx.client_addr()) {
-                SocksAuthMethod::None
-            } else {
-                SocksAuthMethod::User
+        let auth_method = self.select_v5_auth_method(&client_methods);
+        if auth_method == SocksAuthMethod::NoAcceptable {
+            if client_methods.contains(&SocksAuthMethod::GssApi) {
+                debug!(
+                    "declined socks5 gssapi auth method from client {}",
+                    self.ctx.client_addr()
+                );
             }
-        } else {
-            SocksAuthMethod::None
-        };
-        if !client_methods.contains(&auth_method) {
-            let _ =
-                v5::auth::send_method_to_client(&mut clt_w, &SocksAuthMethod::NoAcceptable).await;
+            let _ = v5::auth::send_method_to_client(&mut clt_w, &auth_method).await;
             self.ctx.server_stats.forbidden.add_auth_failed();
             return Err(ServerTaskError::ClientAuthFailed);
         }
@@ -274,11 +310,12 @@ impl SocksProxyNegotiationTask {
 
         let req = v5::Socks5Request::recv(&mut clt_r).await?;
 
-        let task_notes = ServerTaskNotes::new(
+        let mut task_notes = ServerTaskNotes::new(
             self.ctx.cc_info.clone(),
             user_ctx,
             self.time_accepted.elapsed(),
         );
+        task_notes.tag_tenant(self.ctx.server_config.as_ref(), None);
         match req.command {
             SocksCommand::TcpConnect => {
                 let task = SocksProxyTcpConnectTask::new(
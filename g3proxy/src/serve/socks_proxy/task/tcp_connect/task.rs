@@ -31,7 +31,7 @@ use crate::audit::AuditContext;
 use crate::config::server::ServerConfig;
 use crate::inspect::StreamInspectContext;
 use crate::log::task::tcp_connect::TaskLogForTcpConnect;
-use crate::module::tcp_connect::TcpConnectTaskNotes;
+use crate::module::tcp_connect::{TcpConnectTaskNotes, TcpConnection};
 use crate::serve::{
     ServerStats, ServerTaskError, ServerTaskForbiddenError, ServerTaskNotes, ServerTaskResult,
     ServerTaskStage,
@@ -93,13 +93,24 @@ impl SocksProxyTcpConnectTask {
     {
         tokio::spawn(async move {
             self.pre_start();
-            match self.run(clt_r, clt_w).await {
-                Ok(_) => self
-                    .get_log_context()
-                    .log(&self.ctx.task_logger, &ServerTaskError::Finished),
-                Err(e) => self.get_log_context().log(&self.ctx.task_logger, &e),
+            let mut clt_w = clt_w;
+            match self.connect_to_upstream(&mut clt_w).await {
+                Ok((ups_r, ups_w)) => {
+                    self.task_notes.stage = ServerTaskStage::Connected;
+                    match self.run_connected(clt_r, clt_w, ups_r, ups_w).await {
+                        Ok(_) => self
+                            .get_log_context()
+                            .log(&self.ctx.task_logger, &ServerTaskError::Finished),
+                        Err(e) => self.get_log_context().log(&self.ctx.task_logger, &e),
+                    }
+                    self.pre_stop();
+                }
+                Err(e) => {
+                    self.get_log_context().log(&self.ctx.task_logger, &e);
+                    self.pre_stop();
+                    self.ctx.tarpit_on_error(&e, &mut clt_w).await;
+                }
             }
-            self.pre_stop();
         });
     }
 
@@ -214,13 +225,11 @@ impl SocksProxyTcpConnectTask {
         }
     }
 
-    async fn run<R, W>(
+    async fn connect_to_upstream<W>(
         &mut self,
-        clt_r: LimitedReader<R>,
-        mut clt_w: LimitedWriter<W>,
-    ) -> ServerTaskResult<()>
+        clt_w: &mut LimitedWriter<W>,
+    ) -> ServerTaskResult<TcpConnection>
     where
-        R: AsyncRead + Send + Sync + Unpin + 'static,
         W: AsyncWrite + Send + Sync + Unpin + 'static,
     {
         let mut tcp_client_misc_opts = self.ctx.server_config.tcp_misc_opts;
@@ -229,7 +238,7 @@ impl SocksProxyTcpConnectTask {
             let user_ctx = user_ctx.clone();
 
             if user_ctx.check_rate_limit().is_err() {
-                self.reply_forbidden(&mut clt_w).await;
+                self.reply_forbidden(clt_w).await;
                 return Err(ServerTaskError::ForbiddenByRule(
                     ServerTaskForbiddenError::RateLimited,
                 ));
@@ -238,7 +247,7 @@ impl SocksProxyTcpConnectTask {
             match user_ctx.acquire_request_semaphore() {
                 Ok(permit) => self.task_notes.user_req_alive_permit = Some(permit),
                 Err(_) => {
-                    self.reply_forbidden(&mut clt_w).await;
+                    self.reply_forbidden(clt_w).await;
                     return Err(ServerTaskError::ForbiddenByRule(
                         ServerTaskForbiddenError::FullyLoaded,
                     ));
@@ -246,11 +255,11 @@ impl SocksProxyTcpConnectTask {
             }
 
             let action = user_ctx.check_proxy_request(ProxyRequestType::SocksTcpConnect);
-            self.handle_user_acl_action(action, &mut clt_w, ServerTaskForbiddenError::ProtoBanned)
+            self.handle_user_acl_action(action, clt_w, ServerTaskForbiddenError::ProtoBanned)
                 .await?;
 
             let action = user_ctx.check_upstream(&self.tcp_notes.upstream);
-            self.handle_user_acl_action(action, &mut clt_w, ServerTaskForbiddenError::DestDenied)
+            self.handle_user_acl_action(action, clt_w, ServerTaskForbiddenError::DestDenied)
                 .await?;
 
             tcp_client_misc_opts = user_ctx
@@ -260,7 +269,7 @@ impl SocksProxyTcpConnectTask {
 
         // server level dst host/port acl rules
         let action = self.ctx.check_upstream(&self.tcp_notes.upstream);
-        self.handle_server_upstream_acl_action(action, &mut clt_w)
+        self.handle_server_upstream_acl_action(action, clt_w)
             .await?;
 
         // set client side socket options
@@ -270,6 +279,12 @@ impl SocksProxyTcpConnectTask {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
 
         self.task_notes.stage = ServerTaskStage::Connecting;
         match self
@@ -283,19 +298,14 @@ impl SocksProxyTcpConnectTask {
             )
             .await
         {
-            Ok((ups_r, ups_w)) => {
-                self.task_notes.stage = ServerTaskStage::Connected;
-                self.run_connected(clt_r, clt_w, ups_r, ups_w).await
-            }
+            Ok(v) => Ok(v),
             Err(e) => {
                 match self.socks_version {
                     SocksVersion::V4a => {
-                        let _ = v4a::SocksV4Reply::RequestRejectedOrFailed
-                            .send(&mut clt_w)
-                            .await;
+                        let _ = v4a::SocksV4Reply::RequestRejectedOrFailed.send(clt_w).await;
                     }
                     SocksVersion::V5 => {
-                        let _ = v5::Socks5Reply::from(&e).send(&mut clt_w).await;
+                        let _ = v5::Socks5Reply::from(&e).send(clt_w).await;
                     }
                     SocksVersion::V6 => {} // TODO socks v6
                 }
@@ -347,6 +357,9 @@ impl SocksProxyTcpConnectTask {
             SocksVersion::V6 => return Err(ServerTaskError::UnimplementedProtocol),
         }
         self.task_notes.mark_relaying();
+        if self.ctx.server_config.task_log_on_connect {
+            self.get_log_context().log_connected(&self.ctx.task_logger);
+        }
         if let Some(user_ctx) = self.task_notes.user_ctx() {
             user_ctx.foreach_req_stats(|s| s.req_ready.add_socks_tcp_connect());
         }
@@ -382,13 +395,15 @@ impl SocksProxyTcpConnectTask {
                 .unwrap_or_else(|| audit_handle.do_task_audit());
 
             if audit_task {
-                let ctx = StreamInspectContext::new(
+                let mut ctx = StreamInspectContext::new(
                     audit_handle.clone(),
                     self.ctx.server_config.clone(),
                     self.ctx.server_stats.clone(),
                     self.ctx.server_quit_policy.clone(),
                     &self.task_notes,
+                    &self.tcp_notes,
                 );
+                ctx.set_tarpit(self.ctx.tarpit.clone());
                 return crate::inspect::stream::transit_with_inspection(
                     clt_r,
                     clt_w,
@@ -409,6 +424,8 @@ impl SocksProxyTcpConnectTask {
             ups_w,
             &self.ctx.server_config,
             &self.ctx.server_quit_policy,
+            self.task_notes
+                .task_deadline(self.ctx.server_config.max_task_lifetime()),
             self.task_notes.user_ctx().map(|ctx| ctx.user()),
         )
         .await
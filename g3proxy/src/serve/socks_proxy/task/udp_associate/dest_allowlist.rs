@@ -0,0 +1,97 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use log::warn;
+use uuid::Uuid;
+
+use g3_io_ext::UdpRelayClientError;
+use g3_types::net::UpstreamAddr;
+
+use crate::config::server::{UdpDestAllowlistConfig, UdpDestViolationAction};
+
+/// Enforce the udp dest allowlist for a single udp associate session
+pub(super) struct UdpDestAllowlistChecker {
+    config: Arc<UdpDestAllowlistConfig>,
+    task_id: Uuid,
+    client_addr: SocketAddr,
+    dropped_packets: AtomicU64,
+    logged_violations: AtomicUsize,
+}
+
+impl UdpDestAllowlistChecker {
+    pub(super) fn new(
+        config: Arc<UdpDestAllowlistConfig>,
+        task_id: Uuid,
+        client_addr: SocketAddr,
+    ) -> Self {
+        UdpDestAllowlistChecker {
+            config,
+            task_id,
+            client_addr,
+            dropped_packets: AtomicU64::new(0),
+            logged_violations: AtomicUsize::new(0),
+        }
+    }
+
+    pub(super) fn dropped_packets(&self) -> u64 {
+        self.dropped_packets.load(Ordering::Relaxed)
+    }
+
+    /// Check the target address of the packet received from the client.
+    ///
+    /// Return Ok(false) if the packet should be dropped.
+    pub(super) fn check_target(&self, ups: &UpstreamAddr) -> Result<bool, UdpRelayClientError> {
+        if self.config.allow_upstream(ups) {
+            return Ok(true);
+        }
+        self.handle_violation("to", ups)
+    }
+
+    /// Check the source address of the packet that will be sent to the client.
+    ///
+    /// Return Ok(false) if the packet should be dropped.
+    pub(super) fn check_source(&self, ups: &UpstreamAddr) -> Result<bool, UdpRelayClientError> {
+        if self.config.allow_upstream(ups) {
+            return Ok(true);
+        }
+        self.handle_violation("from", ups)
+    }
+
+    fn handle_violation(
+        &self,
+        direction: &str,
+        ups: &UpstreamAddr,
+    ) -> Result<bool, UdpRelayClientError> {
+        if self.logged_violations.fetch_add(1, Ordering::Relaxed) < self.config.log_max_violations()
+        {
+            warn!(
+                "SocksProxy/UdpAssociate: task {} from client {}: disallowed udp packet {direction} {ups}",
+                self.task_id, self.client_addr,
+            );
+        }
+        match self.config.on_violation() {
+            UdpDestViolationAction::Drop => {
+                self.dropped_packets.fetch_add(1, Ordering::Relaxed);
+                Ok(false)
+            }
+            UdpDestViolationAction::Close => Err(UdpRelayClientError::ForbiddenTargetAddress),
+        }
+    }
+}
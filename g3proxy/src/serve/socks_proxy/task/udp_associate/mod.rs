@@ -19,10 +19,12 @@ use super::{CommonTaskContext, SocksProxyServerStats};
 mod task;
 pub(super) use task::SocksProxyUdpAssociateTask;
 
+mod dest_allowlist;
 mod recv;
 mod send;
 mod stats;
 
+use dest_allowlist::UdpDestAllowlistChecker;
 use recv::Socks5UdpAssociateClientRecv;
 use send::Socks5UdpAssociateClientSend;
 use stats::{UdpAssociateTaskCltWrapperStats, UdpAssociateTaskStats};
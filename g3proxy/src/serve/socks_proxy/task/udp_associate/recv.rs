@@ -33,7 +33,7 @@ use g3_socks::v5::UdpInput;
 use g3_types::acl::{AclAction, AclNetworkRule};
 use g3_types::net::UpstreamAddr;
 
-use super::CommonTaskContext;
+use super::{CommonTaskContext, UdpDestAllowlistChecker};
 use crate::auth::UserContext;
 
 pub(super) struct Socks5UdpAssociateClientRecv<T> {
@@ -41,6 +41,7 @@ pub(super) struct Socks5UdpAssociateClientRecv<T> {
     client_addr: SocketAddr,
     ctx: Arc<CommonTaskContext>,
     user_ctx: Option<UserContext>,
+    dest_allowlist: Option<Arc<UdpDestAllowlistChecker>>,
 }
 
 impl<T> Socks5UdpAssociateClientRecv<T>
@@ -52,6 +53,7 @@ where
         client: Option<SocketAddr>,
         ctx: &Arc<CommonTaskContext>,
         user_ctx: Option<&UserContext>,
+        dest_allowlist: Option<Arc<UdpDestAllowlistChecker>>,
     ) -> Self {
         let client_addr =
             client.unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
@@ -60,6 +62,7 @@ where
             client_addr,
             ctx: Arc::clone(ctx),
             user_ctx: user_ctx.cloned(),
+            dest_allowlist,
         }
     }
 
@@ -135,17 +138,30 @@ where
         Ok(())
     }
 
+    /// Return Ok(false) if the packet should be dropped
+    fn check_dest_allowlist(&self, upstream: &UpstreamAddr) -> Result<bool, UdpRelayClientError> {
+        match &self.dest_allowlist {
+            Some(checker) => checker.check_target(upstream),
+            None => Ok(true),
+        }
+    }
+
     fn poll_recv(
         &mut self,
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<Result<(usize, usize, UpstreamAddr), UdpRelayClientError>> {
-        let nr = ready!(self.inner.poll_recv(cx, buf)).map_err(UdpRelayClientError::RecvFailed)?;
+        loop {
+            let nr =
+                ready!(self.inner.poll_recv(cx, buf)).map_err(UdpRelayClientError::RecvFailed)?;
 
-        let (off, upstream) = UdpInput::parse_header(buf)
-            .map_err(|e| UdpRelayClientError::InvalidPacket(e.to_string()))?;
-        self.check_upstream(&upstream)?;
-        Poll::Ready(Ok((off, nr, upstream)))
+            let (off, upstream) = UdpInput::parse_header(buf)
+                .map_err(|e| UdpRelayClientError::InvalidPacket(e.to_string()))?;
+            self.check_upstream(&upstream)?;
+            if self.check_dest_allowlist(&upstream)? {
+                return Poll::Ready(Ok((off, nr, upstream)));
+            }
+        }
     }
 
     fn poll_recv_first(
@@ -154,7 +170,7 @@ where
         buf: &mut [u8],
         ingress_net_filter: &Option<Arc<AclNetworkRule>>,
         initial_peer: &mut UpstreamAddr,
-    ) -> Poll<Result<(usize, usize), UdpRelayClientError>> {
+    ) -> Poll<Result<Option<(usize, usize)>, UdpRelayClientError>> {
         let expected_ip = self.client_addr.ip();
         let expected_port = self.client_addr.port();
         let set_client = expected_ip.is_unspecified() || expected_port == 0;
@@ -194,9 +210,12 @@ where
 
         let (off, upstream) = UdpInput::parse_header(buf)
             .map_err(|e| UdpRelayClientError::InvalidPacket(e.to_string()))?;
+        self.check_upstream(&upstream)?;
+        if !self.check_dest_allowlist(&upstream)? {
+            return Poll::Ready(Ok(None));
+        }
         *initial_peer = upstream;
-        self.check_upstream(initial_peer)?;
-        Poll::Ready(Ok((off, nr)))
+        Poll::Ready(Ok(Some((off, nr))))
     }
 
     pub async fn recv_first_packet(
@@ -210,7 +229,8 @@ where
             match poll_fn(|cx| self.poll_recv_first(cx, buf, ingress_net_filter, initial_peer))
                 .await
             {
-                Ok((off, nr)) => return Ok((off, nr, self.client_addr)),
+                Ok(Some((off, nr))) => return Ok((off, nr, self.client_addr)),
+                Ok(None) => {}
                 Err(UdpRelayClientError::MismatchedClientAddress) => {}
                 Err(e) => return Err(e),
             }
@@ -248,25 +268,38 @@ where
         cx: &mut Context<'_>,
         packets: &mut [UdpRelayPacket],
     ) -> Poll<Result<usize, UdpRelayClientError>> {
-        let mut hdr_v: Vec<RecvMsgHdr<1>> = packets
-            .iter_mut()
-            .map(|p| RecvMsgHdr::new([std::io::IoSliceMut::new(p.buf_mut())]))
-            .collect();
+        loop {
+            let mut hdr_v: Vec<RecvMsgHdr<1>> = packets
+                .iter_mut()
+                .map(|p| RecvMsgHdr::new([std::io::IoSliceMut::new(p.buf_mut())]))
+                .collect();
 
-        let count = ready!(self.inner.poll_batch_recvmsg(cx, &mut hdr_v))
-            .map_err(UdpRelayClientError::RecvFailed)?;
+            let count = ready!(self.inner.poll_batch_recvmsg(cx, &mut hdr_v))
+                .map_err(UdpRelayClientError::RecvFailed)?;
 
-        let mut r = Vec::with_capacity(count);
-        for h in hdr_v.into_iter().take(count) {
-            let iov = &h.iov[0];
-            let (off, ups) = UdpInput::parse_header(&iov[0..h.n_recv])
-                .map_err(|e| UdpRelayClientError::InvalidPacket(e.to_string()))?;
-            r.push(UdpRelayPacketMeta::new(iov, off, h.n_recv, ups))
-        }
-        for (m, p) in r.into_iter().zip(packets.iter_mut()) {
-            m.set_packet(p);
-        }
+            let mut r = Vec::with_capacity(count);
+            for (i, h) in hdr_v.into_iter().take(count).enumerate() {
+                let iov = &h.iov[0];
+                let (off, ups) = UdpInput::parse_header(&iov[0..h.n_recv])
+                    .map_err(|e| UdpRelayClientError::InvalidPacket(e.to_string()))?;
+                self.check_upstream(&ups)?;
+                if self.check_dest_allowlist(&ups)? {
+                    r.push((i, UdpRelayPacketMeta::new(iov, off, h.n_recv, ups)));
+                }
+            }
+            if r.is_empty() {
+                continue;
+            }
 
-        Poll::Ready(Ok(count))
+            let kept = r.len();
+            for (dst, (src, m)) in r.into_iter().enumerate() {
+                // the meta is bound to the buffer of the original packet, so set it before moving
+                m.set_packet(&mut packets[src]);
+                if dst != src {
+                    packets.swap(dst, src);
+                }
+            }
+            return Poll::Ready(Ok(kept));
+        }
     }
 }
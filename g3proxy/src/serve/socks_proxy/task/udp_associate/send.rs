@@ -16,6 +16,7 @@
 
 use std::io::{self, IoSlice};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::task::{ready, Context, Poll};
 
 use g3_io_ext::{AsyncUdpSend, UdpRelayClientError, UdpRelayClientSend};
@@ -31,23 +32,62 @@ use g3_io_ext::{SendMsgHdr, UdpRelayPacket};
 use g3_socks::v5::SocksUdpHeader;
 use g3_types::net::UpstreamAddr;
 
+use super::UdpDestAllowlistChecker;
+
 pub(super) struct Socks5UdpAssociateClientSend<T> {
     inner: T,
     client: SocketAddr,
     socks_headers: Vec<SocksUdpHeader>,
+    dest_allowlist: Option<Arc<UdpDestAllowlistChecker>>,
 }
 
 impl<T> Socks5UdpAssociateClientSend<T>
 where
     T: AsyncUdpSend,
 {
-    pub(super) fn new(inner: T, client: SocketAddr) -> Self {
+    pub(super) fn new(
+        inner: T,
+        client: SocketAddr,
+        dest_allowlist: Option<Arc<UdpDestAllowlistChecker>>,
+    ) -> Self {
         Socks5UdpAssociateClientSend {
             inner,
             client,
             socks_headers: vec![SocksUdpHeader::default(); 4],
+            dest_allowlist,
         }
     }
+
+    /// Return Ok(false) if the packet should be dropped
+    fn check_dest_allowlist(&self, from: &UpstreamAddr) -> Result<bool, UdpRelayClientError> {
+        match &self.dest_allowlist {
+            Some(checker) => checker.check_source(from),
+            None => Ok(true),
+        }
+    }
+
+    /// Get the number of leading packets that could be sent.
+    ///
+    /// Return Ok(None) if the first packet should be dropped.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "macos",
+    ))]
+    fn check_dest_allowlist_batch(
+        &self,
+        packets: &[UdpRelayPacket],
+    ) -> Result<Option<usize>, UdpRelayClientError> {
+        for (i, p) in packets.iter().enumerate() {
+            if !self.check_dest_allowlist(p.upstream())? {
+                return if i == 0 { Ok(None) } else { Ok(Some(i)) };
+            }
+        }
+        Ok(Some(packets.len()))
+    }
 }
 
 impl<T> UdpRelayClientSend for Socks5UdpAssociateClientSend<T>
@@ -60,6 +100,11 @@ where
         buf: &[u8],
         from: &UpstreamAddr,
     ) -> Poll<Result<usize, UdpRelayClientError>> {
+        if !self.check_dest_allowlist(from)? {
+            // treat it as sent, so the relay will go on with the next packet
+            return Poll::Ready(Ok(buf.len()));
+        }
+
         let socks_header = self.socks_headers.get_mut(0).unwrap();
         let nw = ready!(self.inner.poll_sendmsg(
             cx,
@@ -89,6 +134,11 @@ where
         cx: &mut Context<'_>,
         packets: &[UdpRelayPacket],
     ) -> Poll<Result<usize, UdpRelayClientError>> {
+        let Some(n) = self.check_dest_allowlist_batch(packets)? else {
+            // treat it as sent, so the relay will go on with the next packet
+            return Poll::Ready(Ok(1));
+        };
+        let packets = &packets[..n];
         if packets.len() > self.socks_headers.len() {
             self.socks_headers.resize(packets.len(), Default::default());
         }
@@ -121,6 +171,11 @@ where
         cx: &mut Context<'_>,
         packets: &[UdpRelayPacket],
     ) -> Poll<Result<usize, UdpRelayClientError>> {
+        let Some(n) = self.check_dest_allowlist_batch(packets)? else {
+            // treat it as sent, so the relay will go on with the next packet
+            return Poll::Ready(Ok(1));
+        };
+        let packets = &packets[..n];
         if packets.len() > self.socks_headers.len() {
             self.socks_headers.resize(packets.len(), Default::default());
         }
@@ -35,7 +35,7 @@ use g3_types::net::ProxyRequestType;
 
 use super::{
     CommonTaskContext, Socks5UdpAssociateClientRecv, Socks5UdpAssociateClientSend,
-    UdpAssociateTaskCltWrapperStats, UdpAssociateTaskStats,
+    UdpAssociateTaskCltWrapperStats, UdpAssociateTaskStats, UdpDestAllowlistChecker,
 };
 use crate::config::server::ServerConfig;
 use crate::log::escape::udp_sendto::EscapeLogForUdpRelaySendto;
@@ -53,6 +53,7 @@ pub(crate) struct SocksProxyUdpAssociateTask {
     task_stats: Arc<UdpAssociateTaskStats>,
     udp_listen_addr: Option<SocketAddr>,
     udp_client_addr: Option<SocketAddr>,
+    dest_allowlist: Option<Arc<UdpDestAllowlistChecker>>,
 }
 
 impl SocksProxyUdpAssociateTask {
@@ -62,6 +63,13 @@ impl SocksProxyUdpAssociateTask {
         udp_client_addr: Option<SocketAddr>,
     ) -> Self {
         let buf_conf = ctx.server_config.udp_socket_buffer;
+        let dest_allowlist = ctx.server_config.udp_dest_allowlist.as_ref().map(|config| {
+            Arc::new(UdpDestAllowlistChecker::new(
+                config.clone(),
+                notes.id,
+                ctx.client_addr(),
+            ))
+        });
         SocksProxyUdpAssociateTask {
             ctx: Arc::new(ctx),
             udp_notes: UdpRelayTaskNotes::empty(buf_conf),
@@ -69,6 +77,7 @@ impl SocksProxyUdpAssociateTask {
             task_stats: Arc::new(UdpAssociateTaskStats::default()),
             udp_listen_addr: None,
             udp_client_addr,
+            dest_allowlist,
         }
     }
 
@@ -89,6 +98,7 @@ impl SocksProxyUdpAssociateTask {
             remote_rd_packets: self.task_stats.ups.recv.get_packets(),
             remote_wr_bytes: self.task_stats.ups.send.get_bytes(),
             remote_wr_packets: self.task_stats.ups.send.get_packets(),
+            dest_denied_packets: self.dest_allowlist.as_ref().map(|c| c.dropped_packets()),
         }
     }
 
@@ -410,6 +420,7 @@ impl SocksProxyUdpAssociateTask {
             self.udp_client_addr,
             &self.ctx,
             self.task_notes.user_ctx(),
+            self.dest_allowlist.clone(),
         );
 
         let buf_len = self.ctx.server_config.udp_relay.packet_size();
@@ -492,7 +503,8 @@ impl SocksProxyUdpAssociateTask {
         })
         .await?;
 
-        let clt_w = Socks5UdpAssociateClientSend::new(clt_w, udp_client_addr);
+        let clt_w =
+            Socks5UdpAssociateClientSend::new(clt_w, udp_client_addr, self.dest_allowlist.clone());
 
         Ok((clt_r, clt_w, ups_r, ups_w, logger))
     }
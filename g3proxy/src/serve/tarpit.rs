@@ -0,0 +1,149 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use log::debug;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
+
+use g3_types::metrics::MetricsName;
+
+use super::{ServerTaskError, ServerTaskForbiddenError};
+use crate::config::server::{TarpitConfig, TarpitTrigger};
+
+/// Hold the blocked connections, the count of alive ones will be kept across reload
+pub(crate) struct ServerTarpit {
+    config: TarpitConfig,
+    alive_count: Arc<AtomicUsize>,
+}
+
+struct TarpitGuard {
+    alive_count: Arc<AtomicUsize>,
+}
+
+impl Drop for TarpitGuard {
+    fn drop(&mut self) {
+        self.alive_count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ServerTarpit {
+    pub(crate) fn new_updated(
+        old: Option<&ServerTarpit>,
+        config: Option<&TarpitConfig>,
+    ) -> Option<Arc<ServerTarpit>> {
+        let config = config?.clone();
+        let alive_count = old
+            .map(|t| t.alive_count.clone())
+            .unwrap_or_else(|| Arc::new(AtomicUsize::new(0)));
+        Some(Arc::new(ServerTarpit {
+            config,
+            alive_count,
+        }))
+    }
+
+    #[inline]
+    pub(crate) fn applies_to(&self, trigger: TarpitTrigger) -> bool {
+        self.config.applies_to(trigger)
+    }
+
+    fn try_acquire(&self) -> Option<TarpitGuard> {
+        self.alive_count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < self.config.max_concurrent).then_some(n + 1)
+            })
+            .ok()?;
+        Some(TarpitGuard {
+            alive_count: self.alive_count.clone(),
+        })
+    }
+
+    /// Hold the connection if the task error is a block decision selected by the tarpit
+    pub(crate) async fn hold_on_error<W>(
+        &self,
+        e: &ServerTaskError,
+        server: &MetricsName,
+        client_addr: SocketAddr,
+        writer: W,
+    ) -> bool
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let ServerTaskError::ForbiddenByRule(e) = e else {
+            return false;
+        };
+        let trigger = match e {
+            ServerTaskForbiddenError::RateLimited => TarpitTrigger::RateLimit,
+            ServerTaskForbiddenError::ProtoBanned => TarpitTrigger::ProxyRequestAcl,
+            ServerTaskForbiddenError::DestDenied => TarpitTrigger::DstHostAcl,
+            ServerTaskForbiddenError::IpBlocked => TarpitTrigger::DstIpAcl,
+            ServerTaskForbiddenError::UaBlocked => TarpitTrigger::UserAgentAcl,
+            ServerTaskForbiddenError::ClientIpBlocked => TarpitTrigger::NetworkAcl,
+            _ => return false,
+        };
+        self.hold(trigger, server, client_addr, writer).await
+    }
+
+    /// Hold the connection open for the configured duration.
+    ///
+    /// This returns false immediately if the trigger is not selected or too many connections are
+    /// held, the caller should fall back to its own reject policy then.
+    pub(crate) async fn hold<W>(
+        &self,
+        trigger: TarpitTrigger,
+        server: &MetricsName,
+        client_addr: SocketAddr,
+        mut writer: W,
+    ) -> bool
+    where
+        W: AsyncWrite + Unpin,
+    {
+        if !self.applies_to(trigger) {
+            return false;
+        }
+        let Some(_guard) = self.try_acquire() else {
+            return false;
+        };
+        debug!(
+            "server {server}: tarpit connection from {client_addr} on {}",
+            trigger.as_str()
+        );
+
+        let deadline = Instant::now() + self.config.duration;
+        let Some(interval) = self.config.keepalive_interval else {
+            tokio::time::sleep_until(deadline).await;
+            return true;
+        };
+
+        let mut interval = tokio::time::interval_at(Instant::now() + interval, interval);
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => return true,
+                _ = interval.tick() => {
+                    if writer.write_all(&self.config.keepalive_data).await.is_err() {
+                        return true;
+                    }
+                    if writer.flush().await.is_err() {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+}
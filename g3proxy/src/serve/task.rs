@@ -25,7 +25,9 @@ use uuid::Uuid;
 use g3_daemon::server::ClientConnectionInfo;
 use g3_types::limit::GaugeSemaphorePermit;
 
+use super::tenant::TenantTaskAliveGuard;
 use crate::auth::UserContext;
+use crate::config::server::ServerConfig;
 use crate::escape::EgressPathSelection;
 
 #[derive(Clone, Copy)]
@@ -68,8 +70,10 @@ pub(crate) struct ServerTaskNotes {
     pub(crate) wait_time: Duration,
     pub(crate) ready_time: Duration,
     pub(crate) egress_path_selection: Option<EgressPathSelection>,
+    log_sampled: bool,
     /// the following fields should not be cloned
     pub(crate) user_req_alive_permit: Option<GaugeSemaphorePermit>,
+    tenant: Option<TenantTaskAliveGuard>,
 }
 
 impl ServerTaskNotes {
@@ -99,10 +103,18 @@ impl ServerTaskNotes {
             wait_time,
             ready_time: Duration::default(),
             egress_path_selection,
+            log_sampled: crate::log::task::sample(&uuid),
             user_req_alive_permit: None,
+            tenant: None,
         }
     }
 
+    /// Whether the normal events of this task should be logged in task log.
+    /// Error and block events are always logged.
+    #[inline]
+    pub(crate) fn log_sampled(&self) -> bool {
+        self.log_sampled
+    }
     #[inline]
     pub(crate) fn client_addr(&self) -> SocketAddr {
         self.cc_info.client_addr()
@@ -123,6 +135,11 @@ impl ServerTaskNotes {
         self.cc_info.worker_id()
     }
 
+    #[inline]
+    pub(crate) fn cc_info(&self) -> &ClientConnectionInfo {
+        &self.cc_info
+    }
+
     #[inline]
     pub(crate) fn user_ctx(&self) -> Option<&UserContext> {
         self.user_ctx.as_ref()
@@ -137,6 +154,29 @@ impl ServerTaskNotes {
         self.user_ctx.as_ref().and_then(|c| c.raw_user_name())
     }
 
+    /// Tag the task with the tenant selected by the tenant tag config of the server
+    pub(crate) fn tag_tenant<SC>(&mut self, server_config: &SC, sni: Option<&str>)
+    where
+        SC: ServerConfig + ?Sized,
+    {
+        let Some(tenant_tag) = server_config.tenant_tag() else {
+            return;
+        };
+        let user_group = self.user_ctx.as_ref().map(|_| server_config.user_group());
+        let tenant = tenant_tag.select(
+            server_config.name(),
+            sni,
+            user_group,
+            self.raw_user_name().map(|s| s.as_ref()),
+        );
+        self.tenant = Some(TenantTaskAliveGuard::new(server_config.name(), tenant));
+    }
+
+    #[inline]
+    pub(crate) fn tenant(&self) -> Option<&str> {
+        self.tenant.as_ref().map(|v| v.tenant())
+    }
+
     pub(crate) fn egress_path(&self) -> Option<&EgressPathSelection> {
         self.user_ctx
             .as_ref()
@@ -149,6 +189,12 @@ impl ServerTaskNotes {
         self.create_ins
     }
 
+    /// Get the instant at which the task should be canceled, if a max lifetime is set
+    #[inline]
+    pub(crate) fn task_deadline(&self, max_lifetime: Option<Duration>) -> Option<Instant> {
+        max_lifetime.map(|d| self.create_ins + d)
+    }
+
     #[inline]
     pub(crate) fn time_elapsed(&self) -> Duration {
         self.create_ins.elapsed()
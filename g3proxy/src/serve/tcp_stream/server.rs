@@ -32,7 +32,7 @@ use g3_daemon::listen::{AcceptQuicServer, AcceptTcpServer, ListenStats, ListenTc
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerExt, ServerReloadCommand};
 use g3_io_ext::AsyncStream;
 use g3_openssl::SslStream;
-use g3_types::acl::{AclAction, AclNetworkRule};
+use g3_types::acl::{AclAction, AclNetworkRule, AclOrderedNetworkRule};
 use g3_types::collection::{SelectiveVec, SelectiveVecBuilder};
 use g3_types::metrics::MetricsName;
 use g3_types::net::{OpensslClientConfig, UpstreamAddr, WeightedUpstreamAddr};
@@ -55,6 +55,7 @@ pub(crate) struct TcpStreamServer {
     upstream: SelectiveVec<WeightedUpstreamAddr>,
     tls_client_config: Option<Arc<OpensslClientConfig>>,
     ingress_net_filter: Option<AclNetworkRule>,
+    client_net_acl: Option<AclOrderedNetworkRule>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
     task_logger: Logger,
 
@@ -95,6 +96,11 @@ impl TcpStreamServer {
             .as_ref()
             .map(|builder| builder.build());
 
+        let client_net_acl = config
+            .client_net_acl
+            .as_ref()
+            .map(|builder| builder.build());
+
         let task_logger = config.get_task_logger();
 
         server_stats.set_extra_tags(config.extra_metrics_tags.clone());
@@ -109,6 +115,7 @@ impl TcpStreamServer {
             upstream,
             tls_client_config,
             ingress_net_filter,
+            client_net_acl,
             reload_sender,
             task_logger,
             escaper: ArcSwap::new(escaper),
@@ -159,6 +166,17 @@ impl TcpStreamServer {
             }
         }
 
+        if let Some(client_net_acl) = &self.client_net_acl {
+            if crate::serve::forbid_by_client_net_acl(
+                client_net_acl,
+                self.config.name(),
+                client_addr,
+            ) {
+                self.listen_stats.add_dropped();
+                return true;
+            }
+        }
+
         // TODO add cps limit
 
         false
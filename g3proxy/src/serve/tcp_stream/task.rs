@@ -28,6 +28,7 @@ use super::common::CommonTaskContext;
 use super::stats::TcpStreamTaskCltWrapperStats;
 use crate::audit::AuditContext;
 use crate::config::server::ServerConfig;
+use crate::inspect::smtp::SmtpDeferredGreeting;
 use crate::inspect::StreamInspectContext;
 use crate::log::task::tcp_connect::TaskLogForTcpConnect;
 use crate::module::tcp_connect::TcpConnectTaskNotes;
@@ -48,7 +49,8 @@ impl TcpStreamTask {
         upstream: &UpstreamAddr,
         audit_ctx: AuditContext,
     ) -> Self {
-        let task_notes = ServerTaskNotes::new(ctx.cc_info.clone(), None, Duration::ZERO);
+        let mut task_notes = ServerTaskNotes::new(ctx.cc_info.clone(), None, Duration::ZERO);
+        task_notes.tag_tenant(ctx.server_config.as_ref(), None);
         TcpStreamTask {
             ctx,
             upstream: upstream.clone(),
@@ -103,7 +105,7 @@ impl TcpStreamTask {
         self.ctx.server_stats.dec_alive_task();
     }
 
-    async fn run<CR, CW>(&mut self, clt_r: CR, clt_w: CW) -> ServerTaskResult<()>
+    async fn run<CR, CW>(&mut self, mut clt_r: CR, mut clt_w: CW) -> ServerTaskResult<()>
     where
         CR: AsyncRead + Send + Sync + Unpin + 'static,
         CW: AsyncWrite + Send + Sync + Unpin + 'static,
@@ -115,9 +117,24 @@ impl TcpStreamTask {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
+
+        let mut smtp_greeting = SmtpDeferredGreeting::check(
+            &mut self.audit_ctx,
+            self.task_notes.server_addr().ip(),
+            &self.upstream,
+        );
+        if let Some(greeting) = &mut smtp_greeting {
+            greeting.greet(&mut clt_r, &mut clt_w).await?;
+        }
 
         self.task_notes.stage = ServerTaskStage::Connecting;
-        let (ups_r, ups_w) = if let Some(tls_client_config) = &self.ctx.tls_client_config {
+        let r = if let Some(tls_client_config) = &self.ctx.tls_client_config {
             let tls_name = self
                 .ctx
                 .server_config
@@ -134,7 +151,7 @@ impl TcpStreamTask {
                     tls_client_config,
                     tls_name,
                 )
-                .await?
+                .await
         } else {
             self.ctx
                 .escaper
@@ -144,11 +161,21 @@ impl TcpStreamTask {
                     self.task_stats.clone(),
                     &mut self.audit_ctx,
                 )
-                .await?
+                .await
+        };
+        let (ups_r, ups_w) = match r {
+            Ok(v) => v,
+            Err(e) => {
+                if let Some(greeting) = &smtp_greeting {
+                    greeting.reply_connect_failed(&mut clt_w).await;
+                }
+                return Err(e.into());
+            }
         };
 
         self.task_notes.stage = ServerTaskStage::Connected;
-        self.run_connected(clt_r, clt_w, ups_r, ups_w).await
+        self.run_connected(clt_r, clt_w, ups_r, ups_w, smtp_greeting)
+            .await
     }
 
     async fn run_connected<CR, CW, UR, UW>(
@@ -157,6 +184,7 @@ impl TcpStreamTask {
         clt_w: CW,
         ups_r: UR,
         ups_w: UW,
+        smtp_greeting: Option<SmtpDeferredGreeting>,
     ) -> ServerTaskResult<()>
     where
         CR: AsyncRead + Send + Sync + Unpin + 'static,
@@ -165,7 +193,10 @@ impl TcpStreamTask {
         UW: AsyncWrite + Send + Sync + Unpin + 'static,
     {
         self.task_notes.mark_relaying();
-        self.relay(clt_r, clt_w, ups_r, ups_w).await
+        if self.ctx.server_config.task_log_on_connect {
+            self.get_log_context().log_connected(&self.ctx.task_logger);
+        }
+        self.relay(clt_r, clt_w, ups_r, ups_w, smtp_greeting).await
     }
 
     async fn relay<CR, CW, UR, UW>(
@@ -174,6 +205,7 @@ impl TcpStreamTask {
         clt_w: CW,
         ups_r: UR,
         ups_w: UW,
+        smtp_greeting: Option<SmtpDeferredGreeting>,
     ) -> ServerTaskResult<()>
     where
         CR: AsyncRead + Send + Sync + Unpin + 'static,
@@ -181,13 +213,34 @@ impl TcpStreamTask {
         UR: AsyncRead + Send + Sync + Unpin + 'static,
         UW: AsyncWrite + Send + Sync + Unpin + 'static,
     {
-        if let Some(audit_handle) = self.audit_ctx.check_take_handle() {
+        if let Some(greeting) = smtp_greeting {
+            let (audit_handle, client_hello) = greeting.into_parts();
+            let ctx = StreamInspectContext::new(
+                audit_handle,
+                self.ctx.server_config.clone(),
+                self.ctx.server_stats.clone(),
+                self.ctx.server_quit_policy.clone(),
+                &self.task_notes,
+                &self.tcp_notes,
+            );
+            crate::inspect::stream::transit_with_smtp_greeting(
+                clt_r,
+                clt_w,
+                ups_r,
+                ups_w,
+                ctx,
+                self.tcp_notes.upstream.clone(),
+                client_hello,
+            )
+            .await
+        } else if let Some(audit_handle) = self.audit_ctx.check_take_handle() {
             let ctx = StreamInspectContext::new(
                 audit_handle,
                 self.ctx.server_config.clone(),
                 self.ctx.server_stats.clone(),
                 self.ctx.server_quit_policy.clone(),
                 &self.task_notes,
+                &self.tcp_notes,
             );
             crate::inspect::stream::transit_with_inspection(
                 clt_r,
@@ -207,6 +260,8 @@ impl TcpStreamTask {
                 ups_w,
                 &self.ctx.server_config,
                 &self.ctx.server_quit_policy,
+                self.task_notes
+                    .task_deadline(self.ctx.server_config.max_task_lifetime()),
                 None,
             )
             .await
@@ -30,7 +30,7 @@ use tokio_rustls::server::TlsStream;
 use g3_daemon::listen::{AcceptQuicServer, AcceptTcpServer, ListenStats, ListenTcpRuntime};
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerReloadCommand};
 use g3_openssl::SslStream;
-use g3_types::acl::{AclAction, AclNetworkRule};
+use g3_types::acl::{AclAction, AclNetworkRule, AclOrderedNetworkRule};
 use g3_types::metrics::MetricsName;
 
 use super::common::CommonTaskContext;
@@ -49,6 +49,7 @@ pub(crate) struct TcpTProxyServer {
     server_stats: Arc<TcpStreamServerStats>,
     listen_stats: Arc<ListenStats>,
     ingress_net_filter: Option<AclNetworkRule>,
+    client_net_acl: Option<AclOrderedNetworkRule>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
     task_logger: Logger,
 
@@ -72,6 +73,11 @@ impl TcpTProxyServer {
             .as_ref()
             .map(|builder| builder.build());
 
+        let client_net_acl = config
+            .client_net_acl
+            .as_ref()
+            .map(|builder| builder.build());
+
         let task_logger = config.get_task_logger();
 
         server_stats.set_extra_tags(config.extra_metrics_tags.clone());
@@ -84,6 +90,7 @@ impl TcpTProxyServer {
             server_stats,
             listen_stats,
             ingress_net_filter,
+            client_net_acl,
             reload_sender,
             task_logger,
             escaper: ArcSwap::new(escaper),
@@ -134,6 +141,17 @@ impl TcpTProxyServer {
             }
         }
 
+        if let Some(client_net_acl) = &self.client_net_acl {
+            if crate::serve::forbid_by_client_net_acl(
+                client_net_acl,
+                self.config.name(),
+                client_addr,
+            ) {
+                self.listen_stats.add_dropped();
+                return true;
+            }
+        }
+
         // TODO add cps limit
 
         false
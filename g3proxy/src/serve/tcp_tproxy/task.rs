@@ -28,6 +28,7 @@ use g3_types::net::UpstreamAddr;
 use super::common::CommonTaskContext;
 use crate::audit::AuditContext;
 use crate::config::server::ServerConfig;
+use crate::inspect::smtp::SmtpDeferredGreeting;
 use crate::inspect::StreamInspectContext;
 use crate::log::task::tcp_connect::TaskLogForTcpConnect;
 use crate::module::tcp_connect::TcpConnectTaskNotes;
@@ -45,7 +46,8 @@ pub(super) struct TProxyStreamTask {
 impl TProxyStreamTask {
     pub(super) fn new(ctx: CommonTaskContext, audit_ctx: AuditContext) -> Self {
         let target = ctx.target_addr();
-        let task_notes = ServerTaskNotes::new(ctx.cc_info.clone(), None, Duration::ZERO);
+        let mut task_notes = ServerTaskNotes::new(ctx.cc_info.clone(), None, Duration::ZERO);
+        task_notes.tag_tenant(ctx.server_config.as_ref(), None);
         TProxyStreamTask {
             ctx,
             tcp_notes: TcpConnectTaskNotes::new(UpstreamAddr::from(target)),
@@ -102,9 +104,26 @@ impl TProxyStreamTask {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
+
+        let (mut clt_r, mut clt_w) = self.split_clt(clt_stream);
+
+        let mut smtp_greeting = SmtpDeferredGreeting::check(
+            &mut self.audit_ctx,
+            self.task_notes.server_addr().ip(),
+            &self.tcp_notes.upstream,
+        );
+        if let Some(greeting) = &mut smtp_greeting {
+            greeting.greet(&mut clt_r, &mut clt_w).await?;
+        }
 
         self.task_notes.stage = ServerTaskStage::Connecting;
-        let (ups_r, ups_w) = self
+        let (ups_r, ups_w) = match self
             .ctx
             .escaper
             .tcp_setup_connection(
@@ -113,45 +132,85 @@ impl TProxyStreamTask {
                 self.task_stats.clone(),
                 &mut self.audit_ctx,
             )
-            .await?;
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                if let Some(greeting) = &smtp_greeting {
+                    greeting.reply_connect_failed(&mut clt_w).await;
+                }
+                return Err(e.into());
+            }
+        };
 
         self.task_notes.stage = ServerTaskStage::Connected;
-        self.run_connected(clt_stream, ups_r, ups_w).await
+        self.run_connected(clt_r, clt_w, ups_r, ups_w, smtp_greeting)
+            .await
     }
 
-    async fn run_connected<R, W>(
+    async fn run_connected<CR, CW, UR, UW>(
         &mut self,
-        clt_stream: TcpStream,
-        ups_r: R,
-        ups_w: W,
+        clt_r: CR,
+        clt_w: CW,
+        ups_r: UR,
+        ups_w: UW,
+        smtp_greeting: Option<SmtpDeferredGreeting>,
     ) -> ServerTaskResult<()>
     where
-        R: AsyncRead + Send + Sync + Unpin + 'static,
-        W: AsyncWrite + Send + Sync + Unpin + 'static,
+        CR: AsyncRead + Send + Sync + Unpin + 'static,
+        CW: AsyncWrite + Send + Sync + Unpin + 'static,
+        UR: AsyncRead + Send + Sync + Unpin + 'static,
+        UW: AsyncWrite + Send + Sync + Unpin + 'static,
     {
         self.task_notes.mark_relaying();
-        self.relay(clt_stream, ups_r, ups_w).await
+        if self.ctx.server_config.task_log_on_connect {
+            self.get_log_context().log_connected(&self.ctx.task_logger);
+        }
+        self.relay(clt_r, clt_w, ups_r, ups_w, smtp_greeting).await
     }
 
-    async fn relay<R, W>(
+    async fn relay<CR, CW, UR, UW>(
         &mut self,
-        clt_stream: TcpStream,
-        ups_r: R,
-        ups_w: W,
+        clt_r: CR,
+        clt_w: CW,
+        ups_r: UR,
+        ups_w: UW,
+        smtp_greeting: Option<SmtpDeferredGreeting>,
     ) -> ServerTaskResult<()>
     where
-        R: AsyncRead + Send + Sync + Unpin + 'static,
-        W: AsyncWrite + Send + Sync + Unpin + 'static,
+        CR: AsyncRead + Send + Sync + Unpin + 'static,
+        CW: AsyncWrite + Send + Sync + Unpin + 'static,
+        UR: AsyncRead + Send + Sync + Unpin + 'static,
+        UW: AsyncWrite + Send + Sync + Unpin + 'static,
     {
-        let (clt_r, clt_w) = self.split_clt(clt_stream);
-
-        if let Some(audit_handle) = self.audit_ctx.check_take_handle() {
+        if let Some(greeting) = smtp_greeting {
+            let (audit_handle, client_hello) = greeting.into_parts();
+            let ctx = StreamInspectContext::new(
+                audit_handle,
+                self.ctx.server_config.clone(),
+                self.ctx.server_stats.clone(),
+                self.ctx.server_quit_policy.clone(),
+                &self.task_notes,
+                &self.tcp_notes,
+            );
+            crate::inspect::stream::transit_with_smtp_greeting(
+                clt_r,
+                clt_w,
+                ups_r,
+                ups_w,
+                ctx,
+                self.tcp_notes.upstream.clone(),
+                client_hello,
+            )
+            .await
+        } else if let Some(audit_handle) = self.audit_ctx.check_take_handle() {
             let ctx = StreamInspectContext::new(
                 audit_handle,
                 self.ctx.server_config.clone(),
                 self.ctx.server_stats.clone(),
                 self.ctx.server_quit_policy.clone(),
                 &self.task_notes,
+                &self.tcp_notes,
             );
             crate::inspect::stream::transit_with_inspection(
                 clt_r,
@@ -171,6 +230,8 @@ impl TProxyStreamTask {
                 ups_w,
                 &self.ctx.server_config,
                 &self.ctx.server_quit_policy,
+                self.task_notes
+                    .task_deadline(self.ctx.server_config.max_task_lifetime()),
                 None,
             )
             .await
@@ -0,0 +1,120 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ahash::AHashMap;
+
+use g3_types::metrics::MetricsName;
+
+type TenantStatsKey = (MetricsName, Arc<str>);
+
+static TENANT_STATS_REGISTRY: LazyLock<Mutex<AHashMap<TenantStatsKey, Arc<TenantTaskStats>>>> =
+    LazyLock::new(|| Mutex::new(AHashMap::new()));
+
+/// Task stats of a tenant on a server.
+///
+/// The stats will be removed from the registry when there is no alive task of the tenant,
+/// see [`prune_idle_stats`].
+pub(crate) struct TenantTaskStats {
+    server: MetricsName,
+    tenant: Arc<str>,
+    task_total: AtomicU64,
+    task_alive: AtomicI32,
+}
+
+impl TenantTaskStats {
+    fn new(server: MetricsName, tenant: Arc<str>) -> Self {
+        TenantTaskStats {
+            server,
+            tenant,
+            task_total: AtomicU64::new(0),
+            task_alive: AtomicI32::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn server(&self) -> &MetricsName {
+        &self.server
+    }
+
+    #[inline]
+    pub(crate) fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    pub(crate) fn get_task_total(&self) -> u64 {
+        self.task_total.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn get_alive_count(&self) -> i32 {
+        self.task_alive.load(Ordering::Relaxed)
+    }
+}
+
+/// Keep the alive task count of the tenant, which will be decreased when dropped
+pub(crate) struct TenantTaskAliveGuard {
+    stats: Arc<TenantTaskStats>,
+}
+
+impl TenantTaskAliveGuard {
+    pub(crate) fn new(server: &MetricsName, tenant: Arc<str>) -> Self {
+        let stats = get_or_insert_alive(server, tenant);
+        TenantTaskAliveGuard { stats }
+    }
+
+    #[inline]
+    pub(crate) fn tenant(&self) -> &str {
+        self.stats.tenant()
+    }
+}
+
+impl Drop for TenantTaskAliveGuard {
+    fn drop(&mut self) {
+        self.stats.task_alive.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Get the stats and increase the task count, which should be done with the lock held,
+/// so the stats won't be pruned before the alive count is increased
+fn get_or_insert_alive(server: &MetricsName, tenant: Arc<str>) -> Arc<TenantTaskStats> {
+    let mut ht = TENANT_STATS_REGISTRY.lock().unwrap();
+    let key = (server.clone(), tenant);
+    let stats = ht
+        .entry(key.clone())
+        .or_insert_with(|| Arc::new(TenantTaskStats::new(key.0, key.1)));
+    stats.task_total.fetch_add(1, Ordering::Relaxed);
+    stats.task_alive.fetch_add(1, Ordering::Relaxed);
+    stats.clone()
+}
+
+/// Remove the stats of tenants that have no alive tasks.
+///
+/// The removed stats may still be held by the metrics emitter, which should emit the final values
+/// before dropping it. A new one will be created if there are new tasks of the tenant.
+pub(crate) fn prune_idle_stats() {
+    let mut ht = TENANT_STATS_REGISTRY.lock().unwrap();
+    ht.retain(|_, stats| stats.get_alive_count() > 0);
+}
+
+pub(crate) fn foreach_stats<F>(f: F)
+where
+    F: FnMut(&Arc<TenantTaskStats>),
+{
+    let ht = TENANT_STATS_REGISTRY.lock().unwrap();
+    ht.values().for_each(f);
+}
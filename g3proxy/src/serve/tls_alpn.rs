@@ -0,0 +1,62 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use g3_types::metrics::MetricsName;
+use g3_types::net::AlpnProtocol;
+
+use super::ArcServer;
+use crate::config::server::TlsAlpnServersConfig;
+
+/// The next servers to use for each negotiated ALPN protocol
+pub(crate) struct TlsAlpnNextServers {
+    servers: Vec<(AlpnProtocol, MetricsName, ArcSwap<ArcServer>)>,
+}
+
+impl TlsAlpnNextServers {
+    pub(crate) fn new(config: &TlsAlpnServersConfig) -> Self {
+        let servers = config
+            .iter()
+            .map(|(protocol, name)| {
+                let server = Arc::new(crate::serve::get_or_insert_default(name));
+                (*protocol, name.clone(), ArcSwap::new(server))
+            })
+            .collect();
+        TlsAlpnNextServers { servers }
+    }
+
+    pub(crate) fn update_in_place(&self) {
+        for (_, name, server) in &self.servers {
+            let next_server = crate::serve::get_or_insert_default(name);
+            server.store(Arc::new(next_server));
+        }
+    }
+
+    /// Get the next server for the negotiated ALPN protocol.
+    ///
+    /// None will be returned if no ALPN protocol is negotiated or if it is not configured,
+    /// and the default next server should be used then.
+    pub(crate) fn select(&self, alpn: Option<&[u8]>) -> Option<ArcServer> {
+        let protocol = AlpnProtocol::from_buf(alpn?)?;
+        self.servers
+            .iter()
+            .find(|(p, _, _)| *p == protocol)
+            .map(|(_, _, server)| server.load().as_ref().clone())
+    }
+}
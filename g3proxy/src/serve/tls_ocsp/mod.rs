@@ -0,0 +1,345 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::future::poll_fn;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context};
+use http::Method;
+use log::{debug, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use url::{Host, Url};
+
+use g3_http::client::HttpForwardRemoteResponse;
+use g3_http::HttpBodyReader;
+use g3_types::metrics::MetricsName;
+use g3_types::net::{
+    OpensslOcspStaple, OpensslOcspStapleEntry, OpensslOcspStapler, RustlsServerConfigBuilder,
+};
+use g3_types::resolve::ResolveStrategy;
+
+use crate::config::server::TlsOcspStaplingConfig;
+use crate::resolve::ArriveFirstResolveJob;
+
+mod stats;
+pub(crate) use stats::{foreach_stats, OcspStaplingSnapshot, OcspStaplingStats};
+
+#[cfg(not(any(feature = "vendored-boringssl", feature = "vendored-aws-lc")))]
+mod protocol;
+#[cfg(any(feature = "vendored-boringssl", feature = "vendored-aws-lc"))]
+mod protocol {
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+
+    use g3_types::net::{OpensslOcspStaple, OpensslOcspStapleEntry};
+
+    pub(super) fn new_request(_entry: &OpensslOcspStapleEntry) -> anyhow::Result<Vec<u8>> {
+        Err(anyhow!(
+            "ocsp is not supported with the current ssl library"
+        ))
+    }
+
+    pub(super) fn parse_response(
+        _entry: &OpensslOcspStapleEntry,
+        _rsp_der: Vec<u8>,
+        _default_lifetime: Duration,
+    ) -> anyhow::Result<OpensslOcspStaple> {
+        Err(anyhow!(
+            "ocsp is not supported with the current ssl library"
+        ))
+    }
+}
+
+/// Create the OCSP stapler for a rustls server config, and spawn the updater for it.
+///
+/// Hard fail is not supported with rustls, so there is no need to wait for the first fetch.
+pub(crate) fn spawn_rustls_stapler(
+    server: &MetricsName,
+    config: &TlsOcspStaplingConfig,
+    builder: &RustlsServerConfigBuilder,
+    old: Option<&OpensslOcspStapler>,
+) -> anyhow::Result<Arc<OpensslOcspStapler>> {
+    let stapler = builder
+        .new_ocsp_stapler()
+        .context("failed to create tls ocsp stapler")?;
+    if let Some(old) = old {
+        stapler.inherit_staples(old);
+    }
+    let stapler = Arc::new(stapler);
+    let _ = OcspStapleUpdater::new(server, config.clone(), &stapler).spawn_run();
+    Ok(stapler)
+}
+
+const RESPONSE_MAX_HEADER_SIZE: usize = 4096;
+const RESPONSE_MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/// Notify that the first refresh of all staples has been done
+#[derive(Clone)]
+pub(crate) struct OcspStapleReady(watch::Receiver<bool>);
+
+impl OcspStapleReady {
+    pub(crate) async fn wait(&self) {
+        let mut receiver = self.0.clone();
+        // the updater may quit early if the server is reloaded
+        let _ = receiver.wait_for(|ready| *ready).await;
+    }
+}
+
+/// Fetch and cache the OCSP responses for the certs of a server.
+///
+/// The task will quit after the stapler is dropped, which happens on server reload.
+pub(crate) struct OcspStapleUpdater {
+    server: MetricsName,
+    config: TlsOcspStaplingConfig,
+    stapler: Weak<OpensslOcspStapler>,
+    stats: Arc<OcspStaplingStats>,
+    refresh_at: Vec<Option<SystemTime>>,
+    ready: watch::Sender<bool>,
+}
+
+impl OcspStapleUpdater {
+    /// Create the updater, the staples already set in the stapler will be kept until refresh
+    pub(crate) fn new(
+        server: &MetricsName,
+        config: TlsOcspStaplingConfig,
+        stapler: &Arc<OpensslOcspStapler>,
+    ) -> Self {
+        let now = SystemTime::now();
+        let refresh_at = stapler
+            .entries()
+            .iter()
+            .map(|entry| {
+                entry
+                    .staple()
+                    .and_then(|s| s.expire())
+                    .map(|t| get_refresh_time(now, t, config.refresh_ahead))
+            })
+            .collect::<Vec<_>>();
+        let ready = refresh_at.iter().all(|t| t.is_some());
+        OcspStapleUpdater {
+            server: server.clone(),
+            config,
+            stapler: Arc::downgrade(stapler),
+            stats: OcspStaplingStats::get_or_insert(server),
+            refresh_at,
+            ready: watch::Sender::new(ready),
+        }
+    }
+
+    pub(crate) fn spawn_run(self) -> OcspStapleReady {
+        let ready = OcspStapleReady(self.ready.subscribe());
+        tokio::spawn(self.run());
+        ready
+    }
+
+    async fn run(mut self) {
+        let mut check_interval = tokio::time::interval(self.config.check_interval);
+
+        loop {
+            check_interval.tick().await;
+
+            let Some(stapler) = self.stapler.upgrade() else {
+                break;
+            };
+            self.refresh_all(&stapler).await;
+            self.ready
+                .send_if_modified(|ready| !std::mem::replace(ready, true));
+        }
+        debug!("server {}: ocsp staple updater quit", self.server);
+    }
+
+    async fn refresh_all(&mut self, stapler: &OpensslOcspStapler) {
+        let now = SystemTime::now();
+        let mut staple_valid = 0;
+        for (i, entry) in stapler.entries().iter().enumerate() {
+            let need_refresh = self.refresh_at[i].map(|t| t <= now).unwrap_or(true);
+            if need_refresh {
+                match self.fetch(entry).await {
+                    Ok(staple) => {
+                        self.stats.add_refresh_success();
+                        self.refresh_at[i] = staple
+                            .expire()
+                            .map(|t| get_refresh_time(now, t, self.config.refresh_ahead));
+                        entry.set_staple(Some(Arc::new(staple)));
+                    }
+                    Err(e) => {
+                        // retry at the next check, and keep the old staple until it expires
+                        self.stats.add_refresh_failed();
+                        self.refresh_at[i] = None;
+                        warn!(
+                            "server {}: failed to refresh ocsp staple for cert #{i}: {e:?}",
+                            self.server
+                        );
+                    }
+                }
+            }
+            if entry.staple().map(|s| !s.is_expired()).unwrap_or(false) {
+                staple_valid += 1;
+            }
+        }
+        self.stats
+            .set_staple_status(stapler.entries().len(), staple_valid);
+    }
+
+    fn responder(&self, entry: &OpensslOcspStapleEntry) -> anyhow::Result<Url> {
+        if let Some(url) = &self.config.responder {
+            return Ok(url.clone());
+        }
+        for s in entry.responders() {
+            let Ok(url) = Url::parse(s) else {
+                continue;
+            };
+            if url.scheme() == "http" {
+                return Ok(url);
+            }
+        }
+        Err(anyhow!("no usable http ocsp responder found in cert"))
+    }
+
+    async fn fetch(&self, entry: &OpensslOcspStapleEntry) -> anyhow::Result<OpensslOcspStaple> {
+        let url = self.responder(entry)?;
+        let req = protocol::new_request(entry)?;
+
+        let rsp = tokio::time::timeout(
+            self.config.request_timeout,
+            post_request(&url, &req, &self.config.resolver),
+        )
+        .await
+        .map_err(|_| anyhow!("timed out to get response from {url}"))?
+        .context(format!("failed to get response from {url}"))?;
+
+        protocol::parse_response(entry, rsp, self.config.default_lifetime)
+    }
+}
+
+/// Resolve the domain with the resolver configured, or the system resolver if not set
+async fn resolve_domain(domain: &str, resolver: &MetricsName) -> anyhow::Result<IpAddr> {
+    let handle = crate::resolve::get_handle(resolver)?;
+    let mut job = ArriveFirstResolveJob::new(&handle, ResolveStrategy::default(), domain.into())
+        .map_err(|e| anyhow!("failed to create resolve job: {e}"))?;
+    poll_fn(|cx| job.poll_best_addr(cx))
+        .await
+        .map_err(|e| anyhow!("failed to resolve {domain}: {e}"))
+}
+
+async fn post_request(url: &Url, req: &[u8], resolver: &MetricsName) -> anyhow::Result<Vec<u8>> {
+    let port = url.port_or_known_default().unwrap_or(80);
+    let stream = match url.host() {
+        Some(Host::Domain(domain)) if !resolver.is_empty() => {
+            let ip = resolve_domain(domain, resolver).await?;
+            TcpStream::connect(SocketAddr::new(ip, port)).await
+        }
+        Some(Host::Domain(domain)) => TcpStream::connect((domain, port)).await,
+        Some(Host::Ipv4(ip)) => TcpStream::connect(SocketAddr::new(ip.into(), port)).await,
+        Some(Host::Ipv6(ip)) => TcpStream::connect(SocketAddr::new(ip.into(), port)).await,
+        None => return Err(anyhow!("no host found in url")),
+    }
+    .map_err(|e| anyhow!("failed to connect: {e}"))?;
+    let (r, mut w) = stream.into_split();
+
+    let host = url.host_str().unwrap_or_default();
+    let host = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+    let mut buf = Vec::with_capacity(256 + req.len());
+    buf.extend_from_slice(
+        format!(
+            "POST {path} HTTP/1.1\r\n\
+             Host: {host}\r\n\
+             Content-Type: application/ocsp-request\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            req.len()
+        )
+        .as_bytes(),
+    );
+    buf.extend_from_slice(req);
+    w.write_all(&buf)
+        .await
+        .map_err(|e| anyhow!("failed to send request: {e}"))?;
+    w.flush()
+        .await
+        .map_err(|e| anyhow!("failed to send request: {e}"))?;
+
+    let mut reader = BufReader::new(r);
+    let rsp = HttpForwardRemoteResponse::parse(
+        &mut reader,
+        &Method::POST,
+        false,
+        RESPONSE_MAX_HEADER_SIZE,
+    )
+    .await
+    .map_err(|e| anyhow!("failed to read http response header: {e}"))?;
+    if rsp.code != 200 {
+        return Err(anyhow!("unexpected http response status {}", rsp.code));
+    }
+    let Some(body_type) = rsp.body_type(&Method::POST) else {
+        return Err(anyhow!("no body found in http response"));
+    };
+
+    let mut body_reader = HttpBodyReader::new(&mut reader, body_type, 1024);
+    let mut body = Vec::with_capacity(4096);
+    (&mut body_reader)
+        .take(RESPONSE_MAX_BODY_SIZE + 1)
+        .read_to_end(&mut body)
+        .await
+        .map_err(|e| anyhow!("failed to read http response body: {e}"))?;
+    if body.len() as u64 > RESPONSE_MAX_BODY_SIZE {
+        return Err(anyhow!("too large http response body"));
+    }
+    Ok(body)
+}
+
+/// Refresh ahead of the expire time, but no earlier than the middle of the lifetime
+fn get_refresh_time(now: SystemTime, expire: SystemTime, refresh_ahead: Duration) -> SystemTime {
+    let lifetime = expire.duration_since(now).unwrap_or_default();
+    let half_life = now + lifetime / 2;
+    let ahead = expire.checked_sub(refresh_ahead).unwrap_or(half_life);
+    ahead.max(half_life)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_time() {
+        let refresh_ahead = Duration::from_secs(3600);
+        let now = SystemTime::now();
+
+        let expire = now + Duration::from_secs(7 * 86400);
+        assert_eq!(
+            get_refresh_time(now, expire, refresh_ahead),
+            expire - refresh_ahead
+        );
+
+        let expire = now + Duration::from_secs(1800);
+        assert_eq!(
+            get_refresh_time(now, expire, refresh_ahead),
+            now + Duration::from_secs(900)
+        );
+    }
+}
@@ -0,0 +1,119 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::anyhow;
+use openssl::asn1::{Asn1GeneralizedTimeRef, Asn1Time, Asn1TimeRef};
+use openssl::foreign_types::ForeignTypeRef;
+use openssl::hash::MessageDigest;
+use openssl::ocsp::{
+    OcspCertId, OcspCertStatus, OcspFlag, OcspRequest, OcspResponse, OcspResponseStatus,
+};
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::verify::X509VerifyFlags;
+
+use g3_types::net::{OpensslOcspStaple, OpensslOcspStapleEntry};
+
+/// the clock skew allowed when checking the thisUpdate and nextUpdate time of the response
+const RESPONSE_VALIDITY_LEEWAY: u32 = 300;
+
+fn new_cert_id(entry: &OpensslOcspStapleEntry) -> anyhow::Result<OcspCertId> {
+    OcspCertId::from_cert(
+        MessageDigest::sha1(),
+        entry.leaf_cert(),
+        entry.issuer_cert(),
+    )
+    .map_err(|e| anyhow!("failed to create ocsp cert id: {e}"))
+}
+
+pub(super) fn new_request(entry: &OpensslOcspStapleEntry) -> anyhow::Result<Vec<u8>> {
+    let mut req = OcspRequest::new().map_err(|e| anyhow!("failed to create request: {e}"))?;
+    req.add_id(new_cert_id(entry)?)
+        .map_err(|e| anyhow!("failed to add cert id to request: {e}"))?;
+    req.to_der()
+        .map_err(|e| anyhow!("failed to encode request: {e}"))
+}
+
+/// Verify the response and create a staple which will expire at the nextUpdate time
+pub(super) fn parse_response(
+    entry: &OpensslOcspStapleEntry,
+    rsp_der: Vec<u8>,
+    default_lifetime: Duration,
+) -> anyhow::Result<OpensslOcspStaple> {
+    let rsp =
+        OcspResponse::from_der(&rsp_der).map_err(|e| anyhow!("invalid ocsp response: {e}"))?;
+    if rsp.status() != OcspResponseStatus::SUCCESSFUL {
+        return Err(anyhow!(
+            "unsuccessful ocsp response status {:?}",
+            rsp.status()
+        ));
+    }
+    let basic = rsp
+        .basic()
+        .map_err(|e| anyhow!("no basic ocsp response found: {e}"))?;
+
+    let mut certs = Stack::new().map_err(|e| anyhow!("failed to create cert stack: {e}"))?;
+    certs
+        .push(entry.issuer_cert().clone())
+        .map_err(|e| anyhow!("failed to push issuer cert: {e}"))?;
+    let mut store_builder =
+        X509StoreBuilder::new().map_err(|e| anyhow!("failed to create cert store: {e}"))?;
+    store_builder
+        .add_cert(entry.issuer_cert().clone())
+        .map_err(|e| anyhow!("failed to add issuer cert to store: {e}"))?;
+    store_builder
+        .set_flags(X509VerifyFlags::PARTIAL_CHAIN)
+        .map_err(|e| anyhow!("failed to set cert store flags: {e}"))?;
+    basic
+        .verify(&certs, &store_builder.build(), OcspFlag::TRUST_OTHER)
+        .map_err(|e| anyhow!("ocsp response verify failed: {e}"))?;
+
+    let cert_id = new_cert_id(entry)?;
+    let status = basic
+        .find_status(&cert_id)
+        .ok_or_else(|| anyhow!("no status for the cert found in ocsp response"))?;
+    if status.status != OcspCertStatus::GOOD {
+        return Err(anyhow!("unexpected cert status {:?}", status.status));
+    }
+    status
+        .check_validity(RESPONSE_VALIDITY_LEEWAY, None)
+        .map_err(|e| anyhow!("ocsp response is out of validity period: {e}"))?;
+
+    // ASN1_TIME_diff will use the current time if there is no nextUpdate in the response
+    let expire = match time_left(status.next_update)? {
+        Some(left) => SystemTime::now() + left,
+        None => SystemTime::now() + default_lifetime,
+    };
+    Ok(OpensslOcspStaple::new(rsp_der, Some(expire)))
+}
+
+/// Get the time left before the given time, or None if it's not in the future
+fn time_left(t: &Asn1GeneralizedTimeRef) -> anyhow::Result<Option<Duration>> {
+    // ASN1_GENERALIZEDTIME is just a typedef of ASN1_STRING, the same as ASN1_TIME
+    let t = unsafe { Asn1TimeRef::from_ptr(t.as_ptr().cast()) };
+    let now = Asn1Time::days_from_now(0).map_err(|e| anyhow!("failed to get current time: {e}"))?;
+    let diff = now
+        .diff(t)
+        .map_err(|e| anyhow!("failed to compare asn1 time: {e}"))?;
+    let secs = i64::from(diff.days) * 86400 + i64::from(diff.secs);
+    if secs > 0 {
+        Ok(Some(Duration::from_secs(secs as u64)))
+    } else {
+        Ok(None)
+    }
+}
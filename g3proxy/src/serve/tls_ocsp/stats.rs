@@ -0,0 +1,101 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ahash::AHashMap;
+
+use g3_types::metrics::MetricsName;
+
+static OCSP_STAPLING_STATS_REGISTRY: LazyLock<
+    Mutex<AHashMap<MetricsName, Arc<OcspStaplingStats>>>,
+> = LazyLock::new(|| Mutex::new(AHashMap::new()));
+
+#[derive(Default)]
+pub(crate) struct OcspStaplingSnapshot {
+    pub(crate) refresh_success: u64,
+    pub(crate) refresh_failed: u64,
+}
+
+/// OCSP staple refresh stats of a server, which will be kept across reload
+pub(crate) struct OcspStaplingStats {
+    server: MetricsName,
+    refresh_success: AtomicU64,
+    refresh_failed: AtomicU64,
+    cert_total: AtomicUsize,
+    staple_valid: AtomicUsize,
+}
+
+impl OcspStaplingStats {
+    pub(super) fn get_or_insert(server: &MetricsName) -> Arc<Self> {
+        let mut ht = OCSP_STAPLING_STATS_REGISTRY.lock().unwrap();
+        ht.entry(server.clone())
+            .or_insert_with(|| {
+                Arc::new(OcspStaplingStats {
+                    server: server.clone(),
+                    refresh_success: AtomicU64::new(0),
+                    refresh_failed: AtomicU64::new(0),
+                    cert_total: AtomicUsize::new(0),
+                    staple_valid: AtomicUsize::new(0),
+                })
+            })
+            .clone()
+    }
+
+    #[inline]
+    pub(crate) fn server(&self) -> &MetricsName {
+        &self.server
+    }
+
+    pub(super) fn add_refresh_success(&self) {
+        self.refresh_success.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_refresh_failed(&self) {
+        self.refresh_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn set_staple_status(&self, cert_total: usize, staple_valid: usize) {
+        self.cert_total.store(cert_total, Ordering::Relaxed);
+        self.staple_valid.store(staple_valid, Ordering::Relaxed);
+    }
+
+    /// The number of certs that need OCSP stapling
+    pub(crate) fn cert_total(&self) -> usize {
+        self.cert_total.load(Ordering::Relaxed)
+    }
+
+    /// The number of certs that have an unexpired staple
+    pub(crate) fn staple_valid(&self) -> usize {
+        self.staple_valid.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn snapshot(&self) -> OcspStaplingSnapshot {
+        OcspStaplingSnapshot {
+            refresh_success: self.refresh_success.load(Ordering::Relaxed),
+            refresh_failed: self.refresh_failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) fn foreach_stats<F>(f: F)
+where
+    F: FnMut(&Arc<OcspStaplingStats>),
+{
+    let ht = OCSP_STAPLING_STATS_REGISTRY.lock().unwrap();
+    ht.values().for_each(f);
+}
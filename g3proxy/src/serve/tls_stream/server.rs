@@ -32,7 +32,7 @@ use tokio_rustls::{server::TlsStream, TlsAcceptor};
 use g3_daemon::listen::{AcceptQuicServer, AcceptTcpServer, ListenStats, ListenTcpRuntime};
 use g3_daemon::server::{BaseServer, ClientConnectionInfo, ServerExt, ServerReloadCommand};
 use g3_openssl::SslStream;
-use g3_types::acl::{AclAction, AclNetworkRule};
+use g3_types::acl::{AclAction, AclNetworkRule, AclOrderedNetworkRule};
 use g3_types::collection::{SelectiveVec, SelectiveVecBuilder};
 use g3_types::metrics::MetricsName;
 use g3_types::net::{
@@ -61,6 +61,7 @@ pub(crate) struct TlsStreamServer {
     tls_accept_timeout: Duration,
     tls_client_config: Option<Arc<OpensslClientConfig>>,
     ingress_net_filter: Option<AclNetworkRule>,
+    client_net_acl: Option<AclOrderedNetworkRule>,
     reload_sender: broadcast::Sender<ServerReloadCommand>,
     task_logger: Logger,
 
@@ -108,6 +109,11 @@ impl TlsStreamServer {
             .as_ref()
             .map(|builder| builder.build());
 
+        let client_net_acl = config
+            .client_net_acl
+            .as_ref()
+            .map(|builder| builder.build());
+
         let task_logger = config.get_task_logger();
 
         server_stats.set_extra_tags(config.extra_metrics_tags.clone());
@@ -125,6 +131,7 @@ impl TlsStreamServer {
             tls_accept_timeout: tls_server_config.accept_timeout,
             tls_client_config,
             ingress_net_filter,
+            client_net_acl,
             reload_sender,
             task_logger,
             escaper: ArcSwap::new(escaper),
@@ -201,6 +208,17 @@ impl TlsStreamServer {
             }
         }
 
+        if let Some(client_net_acl) = &self.client_net_acl {
+            if crate::serve::forbid_by_client_net_acl(
+                client_net_acl,
+                self.config.name(),
+                client_addr,
+            ) {
+                self.listen_stats.add_dropped();
+                return true;
+            }
+        }
+
         // TODO add cps limit
 
         false
@@ -50,7 +50,8 @@ impl TlsStreamTask {
         upstream: &UpstreamAddr,
         audit_ctx: AuditContext,
     ) -> Self {
-        let task_notes = ServerTaskNotes::new(ctx.cc_info.clone(), None, Duration::ZERO);
+        let mut task_notes = ServerTaskNotes::new(ctx.cc_info.clone(), None, Duration::ZERO);
+        task_notes.tag_tenant(ctx.server_config.as_ref(), None);
         TlsStreamTask {
             ctx,
             upstream: upstream.clone(),
@@ -108,6 +109,12 @@ impl TlsStreamTask {
             .map_err(|_| {
                 ServerTaskError::InternalServerError("failed to set client socket options")
             })?;
+        self.ctx
+            .cc_info
+            .tcp_sock_set_keepalive(&self.ctx.server_config.tcp_keepalive)
+            .map_err(|_| {
+                ServerTaskError::InternalServerError("failed to set client socket keepalive")
+            })?;
 
         self.task_notes.stage = ServerTaskStage::Connecting;
         let (ups_r, ups_w) = if let Some(tls_client_config) = &self.ctx.tls_client_config {
@@ -155,6 +162,9 @@ impl TlsStreamTask {
         W: AsyncWrite + Send + Sync + Unpin + 'static,
     {
         self.task_notes.mark_relaying();
+        if self.ctx.server_config.task_log_on_connect {
+            self.get_log_context().log_connected(&self.ctx.task_logger);
+        }
         self.relay(clt_stream, ups_r, ups_w).await
     }
 
@@ -177,6 +187,7 @@ impl TlsStreamTask {
                 self.ctx.server_stats.clone(),
                 self.ctx.server_quit_policy.clone(),
                 &self.task_notes,
+                &self.tcp_notes,
             );
             crate::inspect::stream::transit_with_inspection(
                 clt_r,
@@ -196,6 +207,8 @@ impl TlsStreamTask {
                 ups_w,
                 &self.ctx.server_config,
                 &self.ctx.server_quit_policy,
+                self.task_notes
+                    .task_deadline(self.ctx.server_config.max_task_lifetime()),
                 None,
             )
             .await
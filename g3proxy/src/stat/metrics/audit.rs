@@ -0,0 +1,115 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ahash::AHashMap;
+
+use g3_statsd_client::{StatsdClient, StatsdTagGroup};
+use g3_types::metrics::MetricsName;
+use g3_types::stats::ConnectionPoolStats;
+
+use crate::audit::IcapPoolStats;
+
+const TAG_KEY_AUDITOR: &str = "auditor";
+const TAG_KEY_SERVICE: &str = "service";
+
+const SERVICE_REQMOD: &str = "reqmod";
+const SERVICE_RESPMOD: &str = "respmod";
+
+const METRIC_NAME_ICAP_POOL_CONNECTION_REAPED: &str = "audit.icap.pool.connection.reaped";
+const METRIC_NAME_ICAP_POOL_CONNECTION_VALIDATE_FAILED: &str =
+    "audit.icap.pool.connection.validate_failed";
+
+#[derive(Default)]
+struct ConnectionPoolSnapshot {
+    reaped: u64,
+    validate_failed: u64,
+}
+
+#[derive(Default)]
+struct IcapPoolSnapshot {
+    reqmod: ConnectionPoolSnapshot,
+    respmod: ConnectionPoolSnapshot,
+}
+
+type IcapPoolStatsValue = (Arc<IcapPoolStats>, IcapPoolSnapshot);
+
+static ICAP_POOL_STATS_MAP: LazyLock<Mutex<AHashMap<MetricsName, IcapPoolStatsValue>>> =
+    LazyLock::new(|| Mutex::new(AHashMap::new()));
+
+pub(in crate::stat) fn sync_stats() {
+    let mut stats_map = ICAP_POOL_STATS_MAP.lock().unwrap();
+    crate::audit::foreach_icap_pool_stats(|stats| {
+        stats_map
+            .entry(stats.auditor().clone())
+            .or_insert_with(|| (stats.clone(), IcapPoolSnapshot::default()));
+    });
+}
+
+pub(in crate::stat) fn emit_stats(client: &mut StatsdClient) {
+    let mut stats_map = ICAP_POOL_STATS_MAP.lock().unwrap();
+    for (stats, snap) in stats_map.values_mut() {
+        let mut common_tags = StatsdTagGroup::default();
+        common_tags.add_tag(TAG_KEY_AUDITOR, stats.auditor());
+
+        emit_pool_stats(
+            client,
+            &stats.reqmod,
+            &mut snap.reqmod,
+            &common_tags,
+            SERVICE_REQMOD,
+        );
+        emit_pool_stats(
+            client,
+            &stats.respmod,
+            &mut snap.respmod,
+            &common_tags,
+            SERVICE_RESPMOD,
+        );
+    }
+}
+
+fn emit_pool_stats(
+    client: &mut StatsdClient,
+    stats: &ConnectionPoolStats,
+    snap: &mut ConnectionPoolSnapshot,
+    common_tags: &StatsdTagGroup,
+    service: &str,
+) {
+    let new_snap = ConnectionPoolSnapshot {
+        reaped: stats.reaped_count(),
+        validate_failed: stats.validate_failed_count(),
+    };
+
+    macro_rules! emit_field {
+        ($field:ident, $name:expr) => {
+            let diff_value = new_snap.$field.wrapping_sub(snap.$field);
+            client
+                .count_with_tags($name, diff_value, common_tags)
+                .with_tag(TAG_KEY_SERVICE, service)
+                .send();
+        };
+    }
+
+    emit_field!(reaped, METRIC_NAME_ICAP_POOL_CONNECTION_REAPED);
+    emit_field!(
+        validate_failed,
+        METRIC_NAME_ICAP_POOL_CONNECTION_VALIDATE_FAILED
+    );
+
+    *snap = new_snap;
+}
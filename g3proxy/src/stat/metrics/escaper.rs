@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::net::IpAddr;
 use std::sync::{Arc, LazyLock, Mutex};
 
 use ahash::AHashMap;
@@ -27,7 +28,8 @@ use g3_types::stats::{StatId, TcpIoSnapshot, UdpIoSnapshot};
 
 use super::TAG_KEY_ESCAPER;
 use crate::escape::{
-    ArcEscaperStats, EscaperForbiddenSnapshot, RouteEscaperSnapshot, RouteEscaperStats,
+    ArcEscaperStats, EscaperConnLimitSnapshot, EscaperForbiddenSnapshot, RouteEscaperSnapshot,
+    RouteEscaperStats,
 };
 
 const METRIC_NAME_ESCAPER_TASK_TOTAL: &str = "escaper.task.total";
@@ -38,6 +40,14 @@ const METRIC_NAME_ESCAPER_IO_IN_PACKETS: &str = "escaper.traffic.in.packets";
 const METRIC_NAME_ESCAPER_IO_OUT_BYTES: &str = "escaper.traffic.out.bytes";
 const METRIC_NAME_ESCAPER_IO_OUT_PACKETS: &str = "escaper.traffic.out.packets";
 const METRIC_NAME_ESCAPER_FORBIDDEN_IP_BLOCKED: &str = "escaper.forbidden.ip_blocked";
+const METRIC_NAME_ESCAPER_UDP_OVERSIZED_DROPPED: &str = "escaper.udp.oversized_dropped";
+const METRIC_NAME_ESCAPER_UDP_UNEXPECTED_SOURCE_DROPPED: &str =
+    "escaper.udp.unexpected_source_dropped";
+const METRIC_NAME_ESCAPER_UPSTREAM_CONN_IN_USE: &str = "escaper.upstream_connection.in_use";
+const METRIC_NAME_ESCAPER_UPSTREAM_CONN_REJECTED: &str = "escaper.upstream_connection.rejected";
+const METRIC_NAME_ESCAPER_BIND_IP_SELECTED: &str = "escaper.bind_ip.selected";
+
+const TAG_KEY_BIND_IP: &str = "bind_ip";
 
 const METRIC_NAME_ROUTE_REQUEST_PASSED: &str = "route.request.passed";
 const METRIC_NAME_ROUTE_REQUEST_FAILED: &str = "route.request.failed";
@@ -70,7 +80,11 @@ struct EscaperSnapshotStats {
     conn_establish: u64,
     tcp: TcpIoSnapshot,
     udp: UdpIoSnapshot,
+    udp_oversized_dropped: u64,
+    udp_unexpected_source_dropped: u64,
     forbidden: EscaperForbiddenSnapshot,
+    conn_limit: EscaperConnLimitSnapshot,
+    bind_ip: AHashMap<IpAddr, u64>,
 }
 
 pub(in crate::stat) fn sync_stats() {
@@ -151,6 +165,14 @@ fn emit_escaper_stats(
         emit_forbidden_stats(client, forbidden_stats, &mut snap.forbidden, &common_tags);
     }
 
+    if let Some(conn_limit_stats) = stats.conn_limit_snapshot() {
+        emit_conn_limit_stats(client, conn_limit_stats, &mut snap.conn_limit, &common_tags);
+    }
+
+    if let Some(bind_ip_stats) = stats.bind_ip_snapshot() {
+        emit_bind_ip_stats(client, bind_ip_stats, &mut snap.bind_ip, &common_tags);
+    }
+
     if let Some(tcp_io_stats) = stats.tcp_io_snapshot() {
         emit_tcp_io_to_statsd(client, tcp_io_stats, &mut snap.tcp, &common_tags);
     }
@@ -158,6 +180,32 @@ fn emit_escaper_stats(
     if let Some(udp_io_stats) = stats.udp_io_snapshot() {
         emit_udp_io_to_statsd(client, udp_io_stats, &mut snap.udp, &common_tags);
     }
+
+    let new_value = stats.get_udp_oversized_packet_dropped();
+    if new_value != 0 || snap.udp_oversized_dropped != 0 {
+        let diff_value = new_value.wrapping_sub(snap.udp_oversized_dropped);
+        client
+            .count_with_tags(
+                METRIC_NAME_ESCAPER_UDP_OVERSIZED_DROPPED,
+                diff_value,
+                &common_tags,
+            )
+            .send();
+        snap.udp_oversized_dropped = new_value;
+    }
+
+    let new_value = stats.get_udp_unexpected_source_dropped();
+    if new_value != 0 || snap.udp_unexpected_source_dropped != 0 {
+        let diff_value = new_value.wrapping_sub(snap.udp_unexpected_source_dropped);
+        client
+            .count_with_tags(
+                METRIC_NAME_ESCAPER_UDP_UNEXPECTED_SOURCE_DROPPED,
+                diff_value,
+                &common_tags,
+            )
+            .send();
+        snap.udp_unexpected_source_dropped = new_value;
+    }
 }
 
 fn emit_forbidden_stats(
@@ -180,6 +228,59 @@ fn emit_forbidden_stats(
     }
 }
 
+fn emit_conn_limit_stats(
+    client: &mut StatsdClient,
+    stats: EscaperConnLimitSnapshot,
+    snap: &mut EscaperConnLimitSnapshot,
+    common_tags: &StatsdTagGroup,
+) {
+    // only emit if the limit is ever used
+    if stats.in_use != 0 || snap.in_use != 0 {
+        client
+            .gauge_with_tags(
+                METRIC_NAME_ESCAPER_UPSTREAM_CONN_IN_USE,
+                stats.in_use,
+                common_tags,
+            )
+            .send();
+        snap.in_use = stats.in_use;
+    }
+
+    let new_value = stats.rejected;
+    if new_value != 0 || snap.rejected != 0 {
+        let diff_value = new_value.wrapping_sub(snap.rejected);
+        client
+            .count_with_tags(
+                METRIC_NAME_ESCAPER_UPSTREAM_CONN_REJECTED,
+                diff_value,
+                common_tags,
+            )
+            .send();
+        snap.rejected = new_value;
+    }
+}
+
+fn emit_bind_ip_stats(
+    client: &mut StatsdClient,
+    stats: Vec<(IpAddr, u64)>,
+    snap: &mut AHashMap<IpAddr, u64>,
+    common_tags: &StatsdTagGroup,
+) {
+    for (ip, new_value) in stats {
+        let old_value = snap.entry(ip).or_default();
+        let diff_value = new_value.wrapping_sub(*old_value);
+        client
+            .count_with_tags(
+                METRIC_NAME_ESCAPER_BIND_IP_SELECTED,
+                diff_value,
+                common_tags,
+            )
+            .with_tag(TAG_KEY_BIND_IP, ip.to_string())
+            .send();
+        *old_value = new_value;
+    }
+}
+
 fn emit_tcp_io_to_statsd(
     client: &mut StatsdClient,
     stats: TcpIoSnapshot,
@@ -0,0 +1,221 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ahash::AHashMap;
+
+use g3_statsd_client::{StatsdClient, StatsdTagGroup};
+use g3_types::metrics::MetricsName;
+
+use crate::inspect::tls::{
+    TlsHandshakeFailureReason, TlsHandshakeLimitSnapshot, TlsHandshakeLimitStats,
+    TlsHandshakeSnapshot, TlsHandshakeStats, TlsInterceptionStats,
+};
+use crate::inspect::{
+    InspectPolicyProtocol, InspectPolicySnapshot, InspectPolicyStats, ALL_INSPECT_DECISIONS,
+    INSPECT_DEPTH_TAGS,
+};
+
+const TAG_KEY_AUDITOR: &str = "auditor";
+const TAG_KEY_DIRECTION: &str = "direction";
+const TAG_KEY_REASON: &str = "reason";
+const TAG_KEY_PROTOCOL: &str = "protocol";
+const TAG_KEY_DECISION: &str = "decision";
+const TAG_KEY_DEPTH: &str = "depth";
+
+const DIRECTION_CLIENT: &str = "client";
+const DIRECTION_UPSTREAM: &str = "upstream";
+
+const METRIC_NAME_TLS_HANDSHAKE_ATTEMPT: &str = "inspect.tls.handshake.attempt";
+const METRIC_NAME_TLS_HANDSHAKE_SUCCESS: &str = "inspect.tls.handshake.success";
+const METRIC_NAME_TLS_HANDSHAKE_FAILED: &str = "inspect.tls.handshake.failed";
+const METRIC_NAME_TLS_HANDSHAKE_QUEUED: &str = "inspect.tls.handshake.queued";
+const METRIC_NAME_TLS_HANDSHAKE_SHED: &str = "inspect.tls.handshake.shed";
+const METRIC_NAME_POLICY_DECISION: &str = "inspect.policy.decision";
+
+const SHED_REASON_QUEUE_FULL: &str = "queue_full";
+const SHED_REASON_QUEUE_TIMEOUT: &str = "queue_timeout";
+
+#[derive(Default)]
+struct TlsInterceptionSnapshot {
+    client: TlsHandshakeSnapshot,
+    upstream: TlsHandshakeSnapshot,
+    limit: TlsHandshakeLimitSnapshot,
+}
+
+type TlsInterceptionStatsValue = (Arc<TlsInterceptionStats>, TlsInterceptionSnapshot);
+
+static TLS_INTERCEPTION_STATS_MAP: LazyLock<
+    Mutex<AHashMap<MetricsName, TlsInterceptionStatsValue>>,
+> = LazyLock::new(|| Mutex::new(AHashMap::new()));
+
+type InspectPolicyStatsValue = (Arc<InspectPolicyStats>, InspectPolicySnapshot);
+
+static INSPECT_POLICY_STATS_MAP: LazyLock<Mutex<AHashMap<MetricsName, InspectPolicyStatsValue>>> =
+    LazyLock::new(|| Mutex::new(AHashMap::new()));
+
+pub(in crate::stat) fn sync_stats() {
+    let mut stats_map = TLS_INTERCEPTION_STATS_MAP.lock().unwrap();
+    crate::inspect::tls::foreach_tls_interception_stats(|stats| {
+        stats_map
+            .entry(stats.auditor().clone())
+            .or_insert_with(|| (stats.clone(), TlsInterceptionSnapshot::default()));
+    });
+    drop(stats_map);
+
+    let mut stats_map = INSPECT_POLICY_STATS_MAP.lock().unwrap();
+    crate::inspect::foreach_inspect_policy_stats(|stats| {
+        stats_map
+            .entry(stats.auditor().clone())
+            .or_insert_with(|| (stats.clone(), InspectPolicySnapshot::default()));
+    });
+}
+
+pub(in crate::stat) fn emit_stats(client: &mut StatsdClient) {
+    let mut stats_map = TLS_INTERCEPTION_STATS_MAP.lock().unwrap();
+    for (stats, snap) in stats_map.values_mut() {
+        let mut common_tags = StatsdTagGroup::default();
+        common_tags.add_tag(TAG_KEY_AUDITOR, stats.auditor());
+
+        emit_handshake_stats(
+            client,
+            &stats.client,
+            &mut snap.client,
+            &common_tags,
+            DIRECTION_CLIENT,
+        );
+        emit_handshake_stats(
+            client,
+            &stats.upstream,
+            &mut snap.upstream,
+            &common_tags,
+            DIRECTION_UPSTREAM,
+        );
+        emit_handshake_limit_stats(client, &stats.limit, &mut snap.limit, &common_tags);
+    }
+    drop(stats_map);
+
+    let mut stats_map = INSPECT_POLICY_STATS_MAP.lock().unwrap();
+    for (stats, snap) in stats_map.values_mut() {
+        let mut common_tags = StatsdTagGroup::default();
+        common_tags.add_tag(TAG_KEY_AUDITOR, stats.auditor());
+
+        emit_policy_stats(client, stats, snap, &common_tags);
+    }
+}
+
+fn emit_policy_stats(
+    client: &mut StatsdClient,
+    stats: &InspectPolicyStats,
+    snap: &mut InspectPolicySnapshot,
+    common_tags: &StatsdTagGroup,
+) {
+    let new_snap = stats.snapshot();
+
+    for (p, protocol) in InspectPolicyProtocol::ALL.iter().enumerate() {
+        for (d, decision) in ALL_INSPECT_DECISIONS.iter().enumerate() {
+            for (depth, depth_tag) in INSPECT_DEPTH_TAGS.iter().enumerate() {
+                let new_value = new_snap[p][d][depth];
+                let old_value = snap[p][d][depth];
+                if new_value == 0 && old_value == 0 {
+                    continue;
+                }
+                let diff_value = new_value.wrapping_sub(old_value);
+                client
+                    .count_with_tags(METRIC_NAME_POLICY_DECISION, diff_value, common_tags)
+                    .with_tag(TAG_KEY_PROTOCOL, protocol.as_str())
+                    .with_tag(TAG_KEY_DECISION, decision.as_str())
+                    .with_tag(TAG_KEY_DEPTH, depth_tag)
+                    .send();
+            }
+        }
+    }
+
+    *snap = new_snap;
+}
+
+fn emit_handshake_limit_stats(
+    client: &mut StatsdClient,
+    stats: &TlsHandshakeLimitStats,
+    snap: &mut TlsHandshakeLimitSnapshot,
+    common_tags: &StatsdTagGroup,
+) {
+    let new_snap = stats.snapshot();
+
+    client
+        .gauge_with_tags(
+            METRIC_NAME_TLS_HANDSHAKE_QUEUED,
+            new_snap.queued,
+            common_tags,
+        )
+        .send();
+
+    macro_rules! emit_shed {
+        ($field:ident, $reason:expr) => {
+            if new_snap.$field != 0 || snap.$field != 0 {
+                let diff_value = new_snap.$field.wrapping_sub(snap.$field);
+                client
+                    .count_with_tags(METRIC_NAME_TLS_HANDSHAKE_SHED, diff_value, common_tags)
+                    .with_tag(TAG_KEY_REASON, $reason)
+                    .send();
+            }
+        };
+    }
+
+    emit_shed!(shed_queue_full, SHED_REASON_QUEUE_FULL);
+    emit_shed!(shed_queue_timeout, SHED_REASON_QUEUE_TIMEOUT);
+
+    *snap = new_snap;
+}
+
+fn emit_handshake_stats(
+    client: &mut StatsdClient,
+    stats: &TlsHandshakeStats,
+    snap: &mut TlsHandshakeSnapshot,
+    common_tags: &StatsdTagGroup,
+    direction: &str,
+) {
+    let new_snap = stats.snapshot();
+
+    macro_rules! emit_field {
+        ($field:ident, $name:expr) => {
+            let diff_value = new_snap.$field.wrapping_sub(snap.$field);
+            client
+                .count_with_tags($name, diff_value, common_tags)
+                .with_tag(TAG_KEY_DIRECTION, direction)
+                .send();
+        };
+    }
+
+    emit_field!(attempt, METRIC_NAME_TLS_HANDSHAKE_ATTEMPT);
+    emit_field!(success, METRIC_NAME_TLS_HANDSHAKE_SUCCESS);
+
+    for (i, reason) in TlsHandshakeFailureReason::ALL.iter().enumerate() {
+        let new_value = new_snap.failed[i];
+        if new_value == 0 && snap.failed[i] == 0 {
+            continue;
+        }
+        let diff_value = new_value.wrapping_sub(snap.failed[i]);
+        client
+            .count_with_tags(METRIC_NAME_TLS_HANDSHAKE_FAILED, diff_value, common_tags)
+            .with_tag(TAG_KEY_DIRECTION, direction)
+            .with_tag(TAG_KEY_REASON, reason.as_str())
+            .send();
+    }
+
+    *snap = new_snap;
+}
@@ -14,7 +14,9 @@
  * limitations under the License.
  */
 
+pub(super) mod audit;
 pub(super) mod escaper;
+pub(super) mod inspect;
 pub(super) mod resolver;
 pub(super) mod server;
 
@@ -20,12 +20,16 @@ use ahash::AHashMap;
 
 use g3_daemon::listen::{ListenSnapshot, ListenStats};
 use g3_daemon::metrics::{
-    ServerMetricExt, TAG_KEY_TRANSPORT, TRANSPORT_TYPE_TCP, TRANSPORT_TYPE_UDP,
+    ServerMetricExt, TAG_KEY_SERVER, TAG_KEY_TRANSPORT, TRANSPORT_TYPE_TCP, TRANSPORT_TYPE_UDP,
 };
 use g3_statsd_client::{StatsdClient, StatsdTagGroup};
+use g3_types::metrics::MetricsName;
 use g3_types::stats::{StatId, TcpIoSnapshot, UdpIoSnapshot};
 
-use crate::serve::{ArcServerStats, ServerForbiddenSnapshot};
+use crate::serve::{
+    ArcServerStats, OcspStaplingSnapshot, OcspStaplingStats, ServerForbiddenSnapshot,
+    TenantTaskStats,
+};
 use crate::stat::types::UntrustedTaskStatsSnapshot;
 
 const METRIC_NAME_SERVER_CONN_TOTAL: &str = "server.connection.total";
@@ -41,14 +45,28 @@ const METRIC_NAME_SERVER_IO_OUT_PACKETS: &str = "server.traffic.out.packets";
 const METRIC_NAME_SERVER_UNTRUSTED_TASK_TOTAL: &str = "server.task.untrusted_total";
 const METRIC_NAME_SERVER_UNTRUSTED_TASK_ALIVE: &str = "server.task.untrusted_alive";
 const METRIC_NAME_SERVER_IO_UNTRUSTED_IN_BYTES: &str = "server.traffic.untrusted_in.bytes";
+const METRIC_NAME_SERVER_TENANT_TASK_TOTAL: &str = "server.tenant.task.total";
+const METRIC_NAME_SERVER_TENANT_TASK_ALIVE: &str = "server.tenant.task.alive";
+const METRIC_NAME_SERVER_OCSP_REFRESH_SUCCESS: &str = "server.tls.ocsp.refresh.success";
+const METRIC_NAME_SERVER_OCSP_REFRESH_FAILED: &str = "server.tls.ocsp.refresh.failed";
+const METRIC_NAME_SERVER_OCSP_CERT_TOTAL: &str = "server.tls.ocsp.cert.total";
+const METRIC_NAME_SERVER_OCSP_STAPLE_VALID: &str = "server.tls.ocsp.staple.valid";
+
+const TAG_KEY_TENANT: &str = "tenant";
 
 type ServerStatsValue = (ArcServerStats, ServerSnapshot);
 type ListenStatsValue = (Arc<ListenStats>, ListenSnapshot);
+type TenantStatsValue = (Arc<TenantTaskStats>, u64);
+type OcspStaplingStatsValue = (Arc<OcspStaplingStats>, OcspStaplingSnapshot);
 
 static SERVER_STATS_MAP: LazyLock<Mutex<AHashMap<StatId, ServerStatsValue>>> =
     LazyLock::new(|| Mutex::new(AHashMap::new()));
 static LISTEN_STATS_MAP: LazyLock<Mutex<AHashMap<StatId, ListenStatsValue>>> =
     LazyLock::new(|| Mutex::new(AHashMap::new()));
+static TENANT_STATS_MAP: LazyLock<Mutex<AHashMap<(MetricsName, String), TenantStatsValue>>> =
+    LazyLock::new(|| Mutex::new(AHashMap::new()));
+static OCSP_STAPLING_STATS_MAP: LazyLock<Mutex<AHashMap<MetricsName, OcspStaplingStatsValue>>> =
+    LazyLock::new(|| Mutex::new(AHashMap::new()));
 
 #[derive(Default)]
 struct ServerSnapshot {
@@ -81,6 +99,22 @@ pub(in crate::stat) fn sync_stats() {
             .or_insert_with(|| (stats, ListenSnapshot::default()));
     });
     drop(listen_stats_map);
+
+    crate::serve::prune_idle_tenant_stats();
+    let mut tenant_stats_map = TENANT_STATS_MAP.lock().unwrap();
+    crate::serve::foreach_tenant_stats(|stats| {
+        tenant_stats_map
+            .entry((stats.server().clone(), stats.tenant().to_string()))
+            .or_insert_with(|| (stats.clone(), 0));
+    });
+    drop(tenant_stats_map);
+
+    let mut ocsp_stats_map = OCSP_STAPLING_STATS_MAP.lock().unwrap();
+    crate::serve::foreach_ocsp_stapling_stats(|stats| {
+        ocsp_stats_map
+            .entry(stats.server().clone())
+            .or_insert_with(|| (stats.clone(), OcspStaplingSnapshot::default()));
+    });
 }
 
 pub(in crate::stat) fn emit_stats(client: &mut StatsdClient) {
@@ -98,6 +132,84 @@ pub(in crate::stat) fn emit_stats(client: &mut StatsdClient) {
         // use Arc instead of Weak here, as we should emit the final metrics before drop it
         Arc::strong_count(stats) > 1
     });
+    drop(listen_stats_map);
+
+    let mut tenant_stats_map = TENANT_STATS_MAP.lock().unwrap();
+    tenant_stats_map.retain(|_, (stats, snap)| {
+        emit_tenant_stats(client, stats, snap);
+        // the idle stats will be pruned from the registry, emit the final metrics before drop it
+        Arc::strong_count(stats) > 1
+    });
+    drop(tenant_stats_map);
+
+    let mut ocsp_stats_map = OCSP_STAPLING_STATS_MAP.lock().unwrap();
+    ocsp_stats_map
+        .values_mut()
+        .for_each(|(stats, snap)| emit_ocsp_stapling_stats(client, stats, snap));
+}
+
+fn emit_ocsp_stapling_stats(
+    client: &mut StatsdClient,
+    stats: &OcspStaplingStats,
+    snap: &mut OcspStaplingSnapshot,
+) {
+    let mut common_tags = StatsdTagGroup::default();
+    common_tags.add_tag(TAG_KEY_SERVER, stats.server());
+
+    let new_snap = stats.snapshot();
+
+    macro_rules! emit_field {
+        ($field:ident, $name:expr) => {
+            let diff_value = new_snap.$field.wrapping_sub(snap.$field);
+            client
+                .count_with_tags($name, diff_value, &common_tags)
+                .send();
+        };
+    }
+
+    emit_field!(refresh_success, METRIC_NAME_SERVER_OCSP_REFRESH_SUCCESS);
+    emit_field!(refresh_failed, METRIC_NAME_SERVER_OCSP_REFRESH_FAILED);
+    *snap = new_snap;
+
+    client
+        .gauge_with_tags(
+            METRIC_NAME_SERVER_OCSP_CERT_TOTAL,
+            stats.cert_total(),
+            &common_tags,
+        )
+        .send();
+    client
+        .gauge_with_tags(
+            METRIC_NAME_SERVER_OCSP_STAPLE_VALID,
+            stats.staple_valid(),
+            &common_tags,
+        )
+        .send();
+}
+
+fn emit_tenant_stats(client: &mut StatsdClient, stats: &TenantTaskStats, snap: &mut u64) {
+    let mut common_tags = StatsdTagGroup::default();
+    common_tags.add_tag(TAG_KEY_SERVER, stats.server());
+    common_tags.add_tag(TAG_KEY_TENANT, stats.tenant());
+
+    let new_value = stats.get_task_total();
+    let diff_value = new_value.wrapping_sub(*snap);
+    client
+        .count_with_tags(
+            METRIC_NAME_SERVER_TENANT_TASK_TOTAL,
+            diff_value,
+            &common_tags,
+        )
+        .send();
+    *snap = new_value;
+
+    client
+        .gauge_with_tags(
+            METRIC_NAME_SERVER_TENANT_TASK_ALIVE,
+            stats.get_alive_count(),
+            &common_tags,
+        )
+        .send();
 }
 
 fn emit_server_stats(client: &mut StatsdClient, stats: &ArcServerStats, snap: &mut ServerSnapshot) {
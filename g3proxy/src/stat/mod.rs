@@ -27,6 +27,9 @@ pub(crate) mod types;
 mod metrics;
 pub(crate) use metrics::user_site;
 
+mod status_page;
+pub use status_page::{spawn_status_page, stop_status_page};
+
 static QUIT_STAT_THREAD: AtomicBool = AtomicBool::new(false);
 
 fn build_statsd_client(config: &StatsdClientConfig) -> anyhow::Result<StatsdClient> {
@@ -52,12 +55,16 @@ fn spawn_main_thread(config: &StatsdClientConfig) -> anyhow::Result<JoinHandle<(
             metrics::escaper::sync_stats();
             metrics::resolver::sync_stats();
             metrics::user::sync_stats();
+            metrics::inspect::sync_stats();
+            metrics::audit::sync_stats();
             g3_daemon::log::metrics::sync_stats();
 
             metrics::server::emit_stats(&mut client);
             metrics::escaper::emit_stats(&mut client);
             metrics::resolver::emit_stats(&mut client);
             metrics::user::emit_stats(&mut client);
+            metrics::inspect::emit_stats(&mut client);
+            metrics::audit::emit_stats(&mut client);
             g3_daemon::runtime::metrics::emit_stats(&mut client);
             g3_daemon::log::metrics::emit_stats(&mut client);
 
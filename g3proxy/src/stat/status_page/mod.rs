@@ -0,0 +1,216 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use log::{debug, info, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::sync::{watch, Semaphore};
+use tokio::time::Instant;
+
+use crate::config::status_page::StatusPageConfig;
+
+mod render;
+use render::StatusSnapshot;
+
+const MAX_REQUEST_HEADER_SIZE: usize = 4096;
+const BIND_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+static QUIT_SENDER: Mutex<Option<watch::Sender<()>>> = Mutex::new(None);
+
+/// Spawn the read-only status page listener if it is configured.
+pub async fn spawn_status_page() -> anyhow::Result<()> {
+    let Some(config) = crate::config::status_page::get_global_config() else {
+        return Ok(());
+    };
+
+    let listener = match bind(config.listen) {
+        Ok(listener) => Some(listener),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            // the old process may still be running during hot upgrade
+            warn!(
+                "status page listen address {} is in use, will retry in background",
+                config.listen
+            );
+            None
+        }
+        Err(e) => return Err(anyhow!("failed to listen on {}: {e}", config.listen)),
+    };
+
+    let (quit_sender, quit_receiver) = watch::channel(());
+    *QUIT_SENDER.lock().unwrap() = Some(quit_sender);
+    let start_instant = Instant::now();
+    tokio::spawn(run(listener, config, start_instant, quit_receiver));
+    Ok(())
+}
+
+/// Stop the status page listener, the running requests won't be affected.
+pub fn stop_status_page() {
+    let _ = QUIT_SENDER.lock().unwrap().take();
+}
+
+async fn run(
+    listener: Option<TcpListener>,
+    config: &'static StatusPageConfig,
+    start_instant: Instant,
+    mut quit_receiver: watch::Receiver<()>,
+) {
+    let listener = match listener {
+        Some(listener) => listener,
+        None => loop {
+            tokio::select! {
+                biased;
+
+                _ = quit_receiver.changed() => return,
+                _ = tokio::time::sleep(BIND_RETRY_INTERVAL) => {
+                    match bind(config.listen) {
+                        Ok(listener) => {
+                            info!("status page started listening on {}", config.listen);
+                            break listener;
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {}
+                        Err(e) => {
+                            warn!("status page failed to listen on {}: {e}", config.listen);
+                            return;
+                        }
+                    }
+                }
+            }
+        },
+    };
+
+    let semaphore = Arc::new(Semaphore::new(config.max_connections));
+    loop {
+        tokio::select! {
+            biased;
+
+            _ = quit_receiver.changed() => break,
+            r = listener.accept() => match r {
+                Ok((stream, peer_addr)) => {
+                    let Ok(permit) = semaphore.clone().try_acquire_owned() else {
+                        debug!("status page dropped connection from {peer_addr} as too many connections");
+                        continue;
+                    };
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(stream, config, start_instant).await {
+                            debug!("status page request from {peer_addr} failed: {e}");
+                        }
+                        drop(permit);
+                    });
+                }
+                Err(e) => {
+                    warn!("status page failed to accept: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    }
+    debug!("status page stopped listening on {}", config.listen);
+}
+
+fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(16)
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    config: &StatusPageConfig,
+    start_instant: Instant,
+) -> anyhow::Result<()> {
+    let (method, path) =
+        match tokio::time::timeout(config.request_timeout, read_request(&mut stream)).await {
+            Ok(Ok(v)) => v,
+            Ok(Err(e)) => {
+                let _ = write_response(&mut stream, "400 Bad Request", "text/plain", "").await;
+                return Err(e);
+            }
+            Err(_) => return Err(anyhow!("timeout to read request")),
+        };
+
+    if method != "GET" {
+        write_response(&mut stream, "405 Method Not Allowed", "text/plain", "").await?;
+        return Ok(());
+    }
+    let path = path.split_once('?').map(|(p, _)| p).unwrap_or(&path);
+
+    let snapshot = || StatusSnapshot::collect(start_instant.elapsed());
+    let (content_type, body) = match path {
+        "/" | "/status" => ("text/html; charset=utf-8", snapshot().to_html()),
+        "/status.txt" => ("text/plain; charset=utf-8", snapshot().to_text()),
+        _ => {
+            write_response(&mut stream, "404 Not Found", "text/plain", "").await?;
+            return Ok(());
+        }
+    };
+    write_response(&mut stream, "200 OK", content_type, &body).await?;
+    Ok(())
+}
+
+async fn read_request(stream: &mut TcpStream) -> anyhow::Result<(String, String)> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut tmp = [0u8; 1024];
+    loop {
+        let nr = stream.read(&mut tmp).await?;
+        if nr == 0 {
+            return Err(anyhow!(
+                "connection closed before the end of request header"
+            ));
+        }
+        buf.extend_from_slice(&tmp[..nr]);
+        if memchr::memmem::find(&buf, b"\r\n\r\n").is_some() {
+            break;
+        }
+        if buf.len() > MAX_REQUEST_HEADER_SIZE {
+            return Err(anyhow!("too large request header"));
+        }
+    }
+
+    let line_end = memchr::memchr(b'\n', &buf).unwrap_or(buf.len());
+    let line = std::str::from_utf8(&buf[..line_end])
+        .map_err(|_| anyhow!("invalid request line"))?
+        .trim_end();
+    let mut iter = line.split_ascii_whitespace();
+    let method = iter.next().ok_or_else(|| anyhow!("no method found"))?;
+    let path = iter.next().ok_or_else(|| anyhow!("no path found"))?;
+    Ok((method.to_string(), path.to_string()))
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -0,0 +1,208 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::Write;
+use std::time::Duration;
+
+struct ServerRow {
+    name: String,
+    server_type: &'static str,
+    conn_total: u64,
+    task_total: u64,
+    task_alive: i32,
+}
+
+struct EscaperRow {
+    name: String,
+    escaper_type: String,
+    task_total: u64,
+    conn_attempted: u64,
+    conn_established: u64,
+    upstream_health: Option<String>,
+}
+
+pub(super) struct StatusSnapshot {
+    daemon_group: &'static str,
+    version: &'static str,
+    uptime: Duration,
+    servers: Vec<ServerRow>,
+    escapers: Vec<EscaperRow>,
+}
+
+impl StatusSnapshot {
+    pub(super) fn collect(uptime: Duration) -> Self {
+        let mut servers = Vec::new();
+        crate::serve::foreach_server(|name, server| {
+            let (conn_total, task_total) = match server.get_server_stats() {
+                Some(stats) => (stats.get_conn_total(), stats.get_task_total()),
+                None => (0, 0),
+            };
+            servers.push(ServerRow {
+                name: name.to_string(),
+                server_type: server.server_type(),
+                conn_total,
+                task_total,
+                task_alive: server.alive_count(),
+            });
+        });
+        servers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut escapers = Vec::new();
+        crate::escape::foreach_escaper(|name, escaper| {
+            let (task_total, conn_attempted, conn_established) = match escaper.get_escape_stats() {
+                Some(stats) => (
+                    stats.get_task_total(),
+                    stats.get_conn_attempted(),
+                    stats.get_conn_established(),
+                ),
+                None => (0, 0, 0),
+            };
+            escapers.push(EscaperRow {
+                name: name.to_string(),
+                escaper_type: escaper.escaper_type().to_string(),
+                task_total,
+                conn_attempted,
+                conn_established,
+                upstream_health: escaper.upstream_health().map(|v| v.to_string()),
+            });
+        });
+        escapers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        StatusSnapshot {
+            daemon_group: crate::opts::daemon_group(),
+            version: crate::build::VERSION,
+            uptime,
+            servers,
+            escapers,
+        }
+    }
+
+    pub(super) fn to_text(&self) -> String {
+        let mut s = String::with_capacity(4096);
+        let _ = writeln!(s, "version: {}", self.version);
+        let _ = writeln!(s, "daemon_group: {}", self.daemon_group);
+        let _ = writeln!(s, "uptime: {}s", self.uptime.as_secs());
+
+        let _ = writeln!(s, "\n[servers]");
+        let _ = writeln!(
+            s,
+            "{:<32} {:<16} {:>12} {:>12} {:>10}",
+            "name", "type", "conn_total", "task_total", "task_alive"
+        );
+        for row in &self.servers {
+            let _ = writeln!(
+                s,
+                "{:<32} {:<16} {:>12} {:>12} {:>10}",
+                row.name, row.server_type, row.conn_total, row.task_total, row.task_alive
+            );
+        }
+
+        let _ = writeln!(s, "\n[escapers]");
+        let _ = writeln!(
+            s,
+            "{:<32} {:<16} {:>12} {:>14} {:>16}  upstream_health",
+            "name", "type", "task_total", "conn_attempted", "conn_established"
+        );
+        for row in &self.escapers {
+            let _ = writeln!(
+                s,
+                "{:<32} {:<16} {:>12} {:>14} {:>16}  {}",
+                row.name,
+                row.escaper_type,
+                row.task_total,
+                row.conn_attempted,
+                row.conn_established,
+                row.upstream_health.as_deref().unwrap_or("-")
+            );
+        }
+        s
+    }
+
+    pub(super) fn to_html(&self) -> String {
+        let mut s = String::with_capacity(8192);
+        s.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        let _ = writeln!(s, "<title>g3proxy status</title>");
+        s.push_str("<style>table{border-collapse:collapse}th,td{border:1px solid #999;padding:2px 8px}td.n{text-align:right}</style>\n");
+        s.push_str("</head>\n<body>\n");
+
+        let _ = writeln!(s, "<h1>g3proxy status</h1>");
+        let _ = writeln!(
+            s,
+            "<p>version: {}<br>daemon group: {}<br>uptime: {}s</p>",
+            html_escape(self.version),
+            html_escape(self.daemon_group),
+            self.uptime.as_secs()
+        );
+
+        s.push_str("<h2>Servers</h2>\n<table>\n");
+        s.push_str("<tr><th>name</th><th>type</th><th>conn total</th><th>task total</th><th>task alive</th></tr>\n");
+        for row in &self.servers {
+            let _ = writeln!(
+                s,
+                "<tr><td>{}</td><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>",
+                html_escape(&row.name),
+                row.server_type,
+                row.conn_total,
+                row.task_total,
+                row.task_alive
+            );
+        }
+        s.push_str("</table>\n");
+
+        s.push_str("<h2>Escapers</h2>\n<table>\n");
+        s.push_str("<tr><th>name</th><th>type</th><th>task total</th><th>conn attempted</th><th>conn established</th><th>upstream health</th></tr>\n");
+        for row in &self.escapers {
+            let _ = writeln!(
+                s,
+                "<tr><td>{}</td><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td><code>{}</code></td></tr>",
+                html_escape(&row.name),
+                html_escape(&row.escaper_type),
+                row.task_total,
+                row.conn_attempted,
+                row.conn_established,
+                html_escape(row.upstream_health.as_deref().unwrap_or("-"))
+            );
+        }
+        s.push_str("</table>\n</body>\n</html>\n");
+        s
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        assert_eq!(html_escape("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&#39;");
+        assert_eq!(html_escape("plain-name_1"), "plain-name_1");
+    }
+}
@@ -35,6 +35,7 @@ const COMMAND_ARG_NAME: &str = "name";
 const SUBCOMMAND_PUBLISH: &str = "publish";
 const SUBCOMMAND_PUBLISH_ARG_FILE: &str = "file";
 const SUBCOMMAND_PUBLISH_ARG_DATA: &str = "data";
+const SUBCOMMAND_UPSTREAM_HEALTH: &str = "upstream-health";
 
 pub fn command() -> Command {
     Command::new(COMMAND)
@@ -61,6 +62,7 @@ pub fn command() -> Command {
                         .conflicts_with(SUBCOMMAND_PUBLISH_ARG_FILE),
                 ),
         )
+        .subcommand(Command::new(SUBCOMMAND_UPSTREAM_HEALTH))
 }
 
 async fn publish(client: &escaper_control::Client, args: &ArgMatches) -> CommandResult<()> {
@@ -89,6 +91,12 @@ async fn publish(client: &escaper_control::Client, args: &ArgMatches) -> Command
     parse_operation_result(rsp.get()?.get_result()?)
 }
 
+async fn upstream_health(client: &escaper_control::Client) -> CommandResult<()> {
+    let req = client.upstream_health_request();
+    let rsp = req.send().promise.await?;
+    g3_ctl::print_text("health", rsp.get()?.get_health()?)
+}
+
 pub async fn run(client: &proc_control::Client, args: &ArgMatches) -> CommandResult<()> {
     let name = args.get_one::<String>(COMMAND_ARG_NAME).unwrap();
 
@@ -99,6 +107,11 @@ pub async fn run(client: &proc_control::Client, args: &ArgMatches) -> CommandRes
                 .and_then(|escaper| async move { publish(&escaper, args).await })
                 .await
         }
+        SUBCOMMAND_UPSTREAM_HEALTH => {
+            super::proc::get_escaper(client, name)
+                .and_then(|escaper| async move { upstream_health(&escaper).await })
+                .await
+        }
         _ => unreachable!(),
     }
 }
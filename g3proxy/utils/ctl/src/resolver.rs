@@ -28,6 +28,8 @@ use g3proxy_proto::resolver_capnp::{
     query_result, resolver_control, QueryStrategy as RpcQueryStrategy,
 };
 
+use crate::common::parse_operation_result;
+
 pub const COMMAND: &str = "resolver";
 
 const COMMAND_ARG_NAME: &str = "name";
@@ -36,6 +38,8 @@ const SUBCOMMAND_QUERY: &str = "query";
 const SUBCOMMAND_QUERY_ARG_DOMAIN: &str = "domain";
 const SUBCOMMAND_QUERY_ARG_STRATEGY: &str = "strategy";
 const SUBCOMMAND_QUERY_ARG_RESOLUTION_DELAY: &str = "resolution-delay";
+const SUBCOMMAND_WARMUP: &str = "warmup";
+const SUBCOMMAND_WARMUP_STATUS: &str = "warmup-status";
 
 pub fn command() -> Command {
     Command::new(COMMAND)
@@ -59,6 +63,8 @@ pub fn command() -> Command {
                         .default_value("50"),
                 ),
         )
+        .subcommand(Command::new(SUBCOMMAND_WARMUP))
+        .subcommand(Command::new(SUBCOMMAND_WARMUP_STATUS))
 }
 
 async fn query_domain(client: &resolver_control::Client, args: &ArgMatches) -> CommandResult<()> {
@@ -94,6 +100,18 @@ async fn query_domain(client: &resolver_control::Client, args: &ArgMatches) -> C
     }
 }
 
+async fn warmup(client: &resolver_control::Client) -> CommandResult<()> {
+    let req = client.warmup_request();
+    let rsp = req.send().promise.await?;
+    parse_operation_result(rsp.get()?.get_result()?)
+}
+
+async fn warmup_status(client: &resolver_control::Client) -> CommandResult<()> {
+    let req = client.warmup_status_request();
+    let rsp = req.send().promise.await?;
+    g3_ctl::print_text("status", rsp.get()?.get_status()?)
+}
+
 pub async fn run(client: &proc_control::Client, args: &ArgMatches) -> CommandResult<()> {
     let name = args.get_one::<String>(COMMAND_ARG_NAME).unwrap();
 
@@ -104,6 +122,16 @@ pub async fn run(client: &proc_control::Client, args: &ArgMatches) -> CommandRes
                 .and_then(|resolver| async move { query_domain(&resolver, args).await })
                 .await
         }
+        SUBCOMMAND_WARMUP => {
+            super::proc::get_resolver(client, name)
+                .and_then(|resolver| async move { warmup(&resolver).await })
+                .await
+        }
+        SUBCOMMAND_WARMUP_STATUS => {
+            super::proc::get_resolver(client, name)
+                .and_then(|resolver| async move { warmup_status(&resolver).await })
+                .await
+        }
         _ => unreachable!(),
     }
 }
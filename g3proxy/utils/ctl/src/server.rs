@@ -22,17 +22,21 @@ use g3_ctl::CommandResult;
 use g3proxy_proto::proc_capnp::proc_control;
 use g3proxy_proto::server_capnp::server_control;
 
+use crate::common::parse_operation_result;
+
 pub const COMMAND: &str = "server";
 
 const COMMAND_ARG_NAME: &str = "name";
 
 const SUBCOMMAND_STATUS: &str = "status";
+const SUBCOMMAND_RELOAD_TLS_CERT: &str = "reload-tls-cert";
 
 pub fn command() -> Command {
     Command::new(COMMAND)
         .arg(Arg::new(COMMAND_ARG_NAME).required(true).num_args(1))
         .subcommand_required(true)
         .subcommand(Command::new(SUBCOMMAND_STATUS))
+        .subcommand(Command::new(SUBCOMMAND_RELOAD_TLS_CERT))
 }
 
 async fn status(client: &server_control::Client) -> CommandResult<()> {
@@ -46,6 +50,12 @@ async fn status(client: &server_control::Client) -> CommandResult<()> {
     Ok(())
 }
 
+async fn reload_tls_cert(client: &server_control::Client) -> CommandResult<()> {
+    let req = client.reload_tls_cert_request();
+    let rsp = req.send().promise.await?;
+    parse_operation_result(rsp.get()?.get_result()?)
+}
+
 pub async fn run(client: &proc_control::Client, args: &ArgMatches) -> CommandResult<()> {
     let name = args.get_one::<String>(COMMAND_ARG_NAME).unwrap();
 
@@ -56,6 +66,11 @@ pub async fn run(client: &proc_control::Client, args: &ArgMatches) -> CommandRes
                 .and_then(|server| async move { status(&server).await })
                 .await
         }
+        SUBCOMMAND_RELOAD_TLS_CERT => {
+            super::proc::get_server(client, name)
+                .and_then(|server| async move { reload_tls_cert(&server).await })
+                .await
+        }
         _ => unreachable!(),
     }
 }
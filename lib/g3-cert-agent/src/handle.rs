@@ -67,4 +67,18 @@ impl CertAgentHandle {
             .await
             .and_then(|r| r.inner().cloned())
     }
+
+    /// Fetch a cert without the mimic upstream cert, the generator will use the default params
+    pub async fn fetch_without_mimic(
+        &self,
+        service: TlsServiceType,
+        usage: TlsCertUsage,
+        host: Arc<str>,
+    ) -> Option<FakeCertPair> {
+        let query_key = CacheQueryKey::new(service, usage, host);
+        self.inner
+            .fetch(Arc::new(query_key), self.request_timeout)
+            .await
+            .and_then(|r| r.inner().cloned())
+    }
 }
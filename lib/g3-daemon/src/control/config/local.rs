@@ -14,7 +14,7 @@
  * limitations under the License.
  */
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use yaml_rust::Yaml;
 
 use g3_types::sync::GlobalInit;
@@ -23,18 +23,46 @@ use super::GeneralControllerConfig;
 
 pub(crate) struct LocalControllerConfig {
     general: GeneralControllerConfig,
+    allowed_peer_uid: Vec<u32>,
+    allowed_peer_gid: Vec<u32>,
 }
 
 static LOCAL_CONTROLLER_CONFIG: GlobalInit<LocalControllerConfig> =
-    GlobalInit::new(LocalControllerConfig {
-        general: GeneralControllerConfig::new(),
-    });
+    GlobalInit::new(LocalControllerConfig::new());
 
 impl LocalControllerConfig {
+    const fn new() -> Self {
+        LocalControllerConfig {
+            general: GeneralControllerConfig::new(),
+            allowed_peer_uid: Vec::new(),
+            allowed_peer_gid: Vec::new(),
+        }
+    }
+
     pub(crate) fn get_general() -> GeneralControllerConfig {
         LOCAL_CONTROLLER_CONFIG.as_ref().general.clone()
     }
 
+    #[cfg_attr(not(unix), allow(unused))]
+    pub(crate) fn peer_cred_check_enabled() -> bool {
+        let config = LOCAL_CONTROLLER_CONFIG.as_ref();
+        !config.allowed_peer_uid.is_empty() || !config.allowed_peer_gid.is_empty()
+    }
+
+    /// Check the peer credential of the local control client,
+    /// all peers are allowed if no uid / gid is configured
+    #[cfg_attr(not(unix), allow(unused))]
+    pub(crate) fn check_peer_cred(uid: u32, gid: u32) -> bool {
+        LOCAL_CONTROLLER_CONFIG.as_ref().allow_peer(uid, gid)
+    }
+
+    fn allow_peer(&self, uid: u32, gid: u32) -> bool {
+        if self.allowed_peer_uid.is_empty() && self.allowed_peer_gid.is_empty() {
+            return true;
+        }
+        self.allowed_peer_uid.contains(&uid) || self.allowed_peer_gid.contains(&gid)
+    }
+
     pub(crate) fn set_default(v: &Yaml) -> anyhow::Result<()> {
         match v {
             Yaml::Hash(map) => {
@@ -51,7 +79,36 @@ impl LocalControllerConfig {
     fn set(&mut self, k: &str, v: &Yaml) -> anyhow::Result<()> {
         match g3_yaml::key::normalize(k).as_str() {
             "recv_timeout" | "send_timeout" => self.general.set(k, v),
+            "allowed_peer_uid" | "allowed_uid" => {
+                self.allowed_peer_uid = g3_yaml::value::as_list(v, g3_yaml::value::as_u32)
+                    .context(format!("invalid u32 list value for key {k}"))?;
+                Ok(())
+            }
+            "allowed_peer_gid" | "allowed_gid" => {
+                self.allowed_peer_gid = g3_yaml::value::as_list(v, g3_yaml::value::as_u32)
+                    .context(format!("invalid u32 list value for key {k}"))?;
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_peer() {
+        let mut config = LocalControllerConfig::new();
+        assert!(config.allow_peer(1000, 1000));
+
+        config.allowed_peer_uid.push(1001);
+        assert!(config.allow_peer(1001, 1000));
+        assert!(!config.allow_peer(1000, 1000));
+
+        config.allowed_peer_gid.push(1000);
+        assert!(config.allow_peer(1000, 1000));
+        assert!(!config.allow_peer(1000, 1001));
+    }
+}
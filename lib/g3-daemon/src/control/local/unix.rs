@@ -16,25 +16,38 @@
 
 use std::fs::DirBuilder;
 use std::io;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::anyhow;
 use log::{debug, warn};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::net::UnixListener;
+use tokio::net::unix::SocketAddr;
+use tokio::net::{UnixListener, UnixStream};
 use tokio::sync::oneshot;
 
+use g3_io_ext::LimitedWriteExt;
+
+use super::LocalControllerConfig;
+
 pub(super) struct LocalControllerImpl {
     listen_path: PathBuf,
     listener: UnixListener,
+    owner_uid: u32,
+    socket_ino: u64,
 }
 
 impl LocalControllerImpl {
     fn new(listen_path: PathBuf) -> io::Result<Self> {
         let listener = UnixListener::bind(&listen_path)?;
+        // the socket file is owned by the effective uid of the daemon process
+        let meta = std::fs::metadata(&listen_path)?;
         Ok(LocalControllerImpl {
             listen_path,
             listener,
+            owner_uid: meta.uid(),
+            socket_ino: meta.ino(),
         })
     }
 
@@ -98,6 +111,53 @@ impl LocalControllerImpl {
             })
     }
 
+    fn check_peer(&self, stream: &UnixStream, addr: &SocketAddr) -> bool {
+        let ucred = match stream.peer_cred() {
+            Ok(ucred) => ucred,
+            Err(e) => {
+                if LocalControllerConfig::peer_cred_check_enabled() {
+                    warn!(
+                        "controller {}: failed to get peer credential: {e}",
+                        self.listen_path.display()
+                    );
+                    return false;
+                }
+                debug!("new ctl local control client");
+                return true;
+            }
+        };
+
+        if let Some(addr) = addr.as_pathname() {
+            debug!(
+                "new ctl client from {} uid {} gid {}",
+                addr.display(),
+                ucred.uid(),
+                ucred.gid(),
+            );
+        } else {
+            debug!(
+                "new ctl client from uid {} gid {}",
+                ucred.uid(),
+                ucred.gid()
+            );
+        }
+
+        // always allow the owner of the daemon process
+        if ucred.uid() == self.owner_uid
+            || LocalControllerConfig::check_peer_cred(ucred.uid(), ucred.gid())
+        {
+            return true;
+        }
+        warn!(
+            "controller {}: rejected client uid {} gid {} pid {:?}",
+            self.listen_path.display(),
+            ucred.uid(),
+            ucred.gid(),
+            ucred.pid(),
+        );
+        false
+    }
+
     pub(super) async fn into_running(
         self,
         mut quit_receiver: oneshot::Receiver<oneshot::Sender<Self>>,
@@ -109,23 +169,9 @@ impl LocalControllerImpl {
                 r = self.listener.accept() => {
                     match r {
                         Ok((stream, addr)) => {
-                            if let Ok(ucred) = stream.peer_cred() {
-                                if let Some(addr) = addr.as_pathname() {
-                                    debug!(
-                                        "new ctl client from {} uid {} pid {}",
-                                        addr.display(),
-                                        ucred.uid(),
-                                        ucred.gid(),
-                                    );
-                                } else {
-                                    debug!(
-                                        "new ctl client from uid {} pid {}",
-                                        ucred.uid(),
-                                        ucred.gid()
-                                    );
-                                }
-                            } else {
-                                debug!("new ctl local control client");
+                            if !self.check_peer(&stream, &addr) {
+                                reject(stream);
+                                continue;
                             }
 
                             let (r, w) = stream.into_split();
@@ -150,13 +196,28 @@ impl LocalControllerImpl {
 
 impl Drop for LocalControllerImpl {
     fn drop(&mut self) {
-        if self.listen_path.exists() {
+        // the file may have been replaced by a new daemon process
+        let Ok(meta) = std::fs::metadata(&self.listen_path) else {
+            return;
+        };
+        if meta.ino() == self.socket_ino {
             debug!("unlink socket file {}", self.listen_path.display());
             let _ = std::fs::remove_file(&self.listen_path);
         }
     }
 }
 
+fn reject(mut stream: UnixStream) {
+    let send_timeout = LocalControllerConfig::get_general().send_timeout;
+    tokio::spawn(async move {
+        let _ = tokio::time::timeout(
+            Duration::from_secs(send_timeout),
+            stream.write_all_flush(b"Error: permission denied\n"),
+        )
+        .await;
+    });
+}
+
 fn check_then_finalize_path(path: &Path) -> anyhow::Result<()> {
     if !path.has_root() {
         return Err(anyhow!(
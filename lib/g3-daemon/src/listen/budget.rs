@@ -0,0 +1,240 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use anyhow::anyhow;
+use log::{info, warn};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionBudgetConfig {
+    high_water: usize,
+    low_water: usize,
+}
+
+impl ConnectionBudgetConfig {
+    pub const fn new() -> Self {
+        ConnectionBudgetConfig {
+            high_water: 0,
+            low_water: 0,
+        }
+    }
+
+    pub fn set_high_water(&mut self, value: usize) {
+        self.high_water = value;
+    }
+
+    pub fn set_low_water(&mut self, value: usize) {
+        self.low_water = value;
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.high_water > 0
+    }
+
+    #[inline]
+    pub fn high_water(&self) -> usize {
+        self.high_water
+    }
+
+    /// The low water mark, default to 90% of the high water mark if not set
+    pub fn low_water(&self) -> usize {
+        if self.low_water > 0 {
+            self.low_water
+        } else {
+            self.high_water - self.high_water / 10
+        }
+    }
+
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.high_water == 0 {
+            return Ok(());
+        }
+        if self.low_water >= self.high_water {
+            return Err(anyhow!(
+                "low water {} should be less than high water {}",
+                self.low_water,
+                self.high_water
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Global budget of alive accepted tcp connections among all servers.
+///
+/// New connections will be shed once the high water mark is reached,
+/// and will be accepted again only after the alive count drops to the low water mark.
+pub struct ConnectionBudget {
+    alive: AtomicUsize,
+    overloaded: AtomicBool,
+    overload_count: AtomicU64,
+    shed: AtomicU64,
+}
+
+static GLOBAL_CONNECTION_BUDGET: ConnectionBudget = ConnectionBudget::new();
+
+impl ConnectionBudget {
+    const fn new() -> Self {
+        ConnectionBudget {
+            alive: AtomicUsize::new(0),
+            overloaded: AtomicBool::new(false),
+            overload_count: AtomicU64::new(0),
+            shed: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn global() -> &'static Self {
+        &GLOBAL_CONNECTION_BUDGET
+    }
+
+    /// Try to take a slot for a new accepted connection.
+    ///
+    /// Returns None if the connection should be shed.
+    pub fn try_acquire(&'static self) -> Option<ConnectionBudgetGuard> {
+        let config = crate::runtime::config::get_connection_budget_config();
+        if config.is_enabled() && !self.admit(config) {
+            self.shed.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        self.alive.fetch_add(1, Ordering::Relaxed);
+        Some(ConnectionBudgetGuard { budget: self })
+    }
+
+    fn admit(&self, config: &ConnectionBudgetConfig) -> bool {
+        let alive = self.alive.load(Ordering::Relaxed);
+        if self.overloaded.load(Ordering::Relaxed) {
+            if alive > config.low_water() {
+                return false;
+            }
+            self.leave_overload(alive, config);
+            true
+        } else if alive >= config.high_water() {
+            if self
+                .overloaded
+                .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                self.overload_count.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "entering overload state: {alive} alive connections reached the high water mark {}, \
+                     new connections will be shed",
+                    config.high_water()
+                );
+            }
+            false
+        } else {
+            true
+        }
+    }
+
+    fn leave_overload(&self, alive: usize, config: &ConnectionBudgetConfig) {
+        if self
+            .overloaded
+            .compare_exchange(true, false, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            info!(
+                "leaving overload state: {alive} alive connections dropped to the low water mark {}",
+                config.low_water()
+            );
+        }
+    }
+
+    fn release(&self) {
+        let alive = self.alive.fetch_sub(1, Ordering::Relaxed) - 1;
+        if self.overloaded.load(Ordering::Relaxed) {
+            let config = crate::runtime::config::get_connection_budget_config();
+            if !config.is_enabled() || alive <= config.low_water() {
+                self.leave_overload(alive, config);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn alive_count(&self) -> usize {
+        self.alive.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn is_overloaded(&self) -> bool {
+        self.overloaded.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn overload_count(&self) -> u64 {
+        self.overload_count.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn shed_count(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+}
+
+pub struct ConnectionBudgetGuard {
+    budget: &'static ConnectionBudget,
+}
+
+impl Drop for ConnectionBudgetGuard {
+    fn drop(&mut self) {
+        self.budget.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hysteresis() {
+        let config = ConnectionBudgetConfig {
+            high_water: 4,
+            low_water: 2,
+        };
+        let budget = ConnectionBudget::new();
+
+        for _ in 0..4 {
+            assert!(budget.admit(&config));
+            budget.alive.fetch_add(1, Ordering::Relaxed);
+        }
+        assert!(!budget.admit(&config));
+        assert!(budget.is_overloaded());
+        assert_eq!(budget.overload_count(), 1);
+
+        budget.alive.fetch_sub(1, Ordering::Relaxed);
+        assert!(!budget.admit(&config));
+        assert!(budget.is_overloaded());
+
+        budget.alive.fetch_sub(1, Ordering::Relaxed);
+        assert!(budget.admit(&config));
+        assert!(!budget.is_overloaded());
+        assert_eq!(budget.overload_count(), 1);
+    }
+
+    #[test]
+    fn default_low_water() {
+        let mut config = ConnectionBudgetConfig::new();
+        assert!(!config.is_enabled());
+        config.set_high_water(1000);
+        assert_eq!(config.low_water(), 900);
+        config.check().unwrap();
+        config.set_low_water(1000);
+        assert!(config.check().is_err());
+    }
+}
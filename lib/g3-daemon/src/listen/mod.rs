@@ -14,6 +14,9 @@
  * limitations under the License.
  */
 
+mod budget;
+pub use budget::{ConnectionBudget, ConnectionBudgetConfig, ConnectionBudgetGuard};
+
 mod stats;
 pub use stats::{ListenSnapshot, ListenStats};
 
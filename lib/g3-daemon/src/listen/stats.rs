@@ -24,6 +24,8 @@ use g3_types::stats::StatId;
 pub struct ListenSnapshot {
     pub accepted: u64,
     pub dropped: u64,
+    pub limited: u64,
+    pub shed: u64,
     pub timeout: u64,
     pub failed: u64,
 }
@@ -36,6 +38,8 @@ pub struct ListenStats {
     runtime_count: AtomicIsize,
     accepted: AtomicU64,
     dropped: AtomicU64,
+    limited: AtomicU64,
+    shed: AtomicU64,
     timeout: AtomicU64,
     failed: AtomicU64,
 }
@@ -48,6 +52,8 @@ impl ListenStats {
             runtime_count: AtomicIsize::new(0),
             accepted: AtomicU64::new(0),
             dropped: AtomicU64::new(0),
+            limited: AtomicU64::new(0),
+            shed: AtomicU64::new(0),
             timeout: AtomicU64::new(0),
             failed: AtomicU64::new(0),
         }
@@ -91,6 +97,20 @@ impl ListenStats {
         self.dropped.load(Ordering::Relaxed)
     }
 
+    pub fn add_limited(&self) {
+        self.limited.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn limited(&self) -> u64 {
+        self.limited.load(Ordering::Relaxed)
+    }
+
+    pub fn add_shed(&self) {
+        self.shed.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn shed(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+
     pub fn add_timeout(&self) {
         self.timeout.fetch_add(1, Ordering::Relaxed);
     }
@@ -18,7 +18,7 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use log::{info, warn};
+use log::{debug, info, warn};
 use tokio::net::TcpStream;
 use tokio::runtime::Handle;
 use tokio::sync::broadcast;
@@ -28,7 +28,7 @@ use g3_socket::util::native_socket_addr;
 use g3_socket::RawSocket;
 use g3_types::net::TcpListenConfig;
 
-use crate::listen::ListenStats;
+use crate::listen::{ConnectionBudget, ConnectionBudgetGuard, ListenStats};
 use crate::server::{BaseServer, ClientConnectionInfo, ServerReloadCommand};
 
 #[async_trait]
@@ -146,10 +146,17 @@ where
                         match result {
                             Ok(Some((stream, peer_addr, local_addr))) => {
                                 self.listen_stats.add_accepted();
+                                let Some(budget_guard) = ConnectionBudget::global().try_acquire() else {
+                                    self.listen_stats.add_shed();
+                                    debug!("SRT[{}_v{}#{}] shed connection from {peer_addr}: overloaded",
+                                        self.server.name(), self.server_version, self.instance_id);
+                                    return Ok(());
+                                };
                                 self.run_task(
                                     stream,
                                     native_socket_addr(peer_addr),
                                     native_socket_addr(local_addr),
+                                    budget_guard,
                                 );
                                 Ok(())
                             }
@@ -174,7 +181,13 @@ where
         self.post_stop();
     }
 
-    fn run_task(&self, stream: TcpStream, peer_addr: SocketAddr, local_addr: SocketAddr) {
+    fn run_task(
+        &self,
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        local_addr: SocketAddr,
+        budget_guard: ConnectionBudgetGuard,
+    ) {
         let server = self.server.clone();
 
         let mut cc_info = ClientConnectionInfo::new(peer_addr, local_addr);
@@ -183,15 +196,18 @@ where
             cc_info.set_worker_id(Some(worker_id));
             tokio::spawn(async move {
                 server.run_tcp_task(stream, cc_info).await;
+                drop(budget_guard);
             });
         } else if let Some(rt) = crate::runtime::worker::select_handle() {
             cc_info.set_worker_id(Some(rt.id));
             rt.handle.spawn(async move {
                 server.run_tcp_task(stream, cc_info).await;
+                drop(budget_guard);
             });
         } else {
             tokio::spawn(async move {
                 server.run_tcp_task(stream, cc_info).await;
+                drop(budget_guard);
             });
         }
     }
@@ -25,7 +25,7 @@ use g3_fluentd::FluentdClientConfig;
 #[cfg(target_os = "linux")]
 use g3_journal::JournalConfig;
 use g3_syslog::SyslogBuilder;
-use g3_types::log::AsyncLogConfig;
+use g3_types::log::{AsyncLogConfig, LogSamplingConfig};
 
 use super::{LoggerStats, ReportLogIoError};
 
@@ -49,6 +49,7 @@ pub struct LogConfig {
     pub(crate) async_channel_size: usize,
     pub(crate) async_thread_number: usize,
     pub(crate) io_err_sampling_mask: usize,
+    pub(crate) sampling: LogSamplingConfig,
     pub(crate) program_name: &'static str,
 }
 
@@ -59,6 +60,7 @@ impl LogConfig {
             async_channel_size: DEFAULT_CHANNEL_SIZE,
             async_thread_number: 1,
             io_err_sampling_mask: (1 << IO_ERROR_SAMPLING_OFFSET_DEFAULT) - 1,
+            sampling: LogSamplingConfig::default(),
             program_name,
         }
     }
@@ -165,6 +167,11 @@ impl LogConfig {
                             Ok(())
                         }
                     }
+                    "sampling" => {
+                        config.sampling = g3_yaml::value::as_log_sampling_config(v)
+                            .context(format!("invalid log sampling config value for key {k}"))?;
+                        Ok(())
+                    }
                     _ => Err(anyhow!("invalid key {k}")),
                 })?;
                 Ok(config)
@@ -173,6 +180,11 @@ impl LogConfig {
         }
     }
 
+    #[inline]
+    pub fn sampling(&self) -> LogSamplingConfig {
+        self.sampling
+    }
+
     pub fn build_shared_logger(
         self,
         logger_name: String,
@@ -24,6 +24,8 @@ use crate::listen::{ListenSnapshot, ListenStats};
 const METRIC_NAME_LISTEN_INSTANCE_COUNT: &str = "listen.instance.count";
 const METRIC_NAME_LISTEN_ACCEPTED: &str = "listen.accepted";
 const METRIC_NAME_LISTEN_DROPPED: &str = "listen.dropped";
+const METRIC_NAME_LISTEN_LIMITED: &str = "listen.limited";
+const METRIC_NAME_LISTEN_SHED: &str = "listen.shed";
 const METRIC_NAME_LISTEN_TIMEOUT: &str = "listen.timeout";
 const METRIC_NAME_LISTEN_FAILED: &str = "listen.failed";
 
@@ -58,6 +60,8 @@ pub fn emit_listen_stats(
 
     emit_field!(accepted, METRIC_NAME_LISTEN_ACCEPTED);
     emit_field!(dropped, METRIC_NAME_LISTEN_DROPPED);
+    emit_field!(limited, METRIC_NAME_LISTEN_LIMITED);
+    emit_field!(shed, METRIC_NAME_LISTEN_SHED);
     emit_field!(timeout, METRIC_NAME_LISTEN_TIMEOUT);
     emit_field!(failed, METRIC_NAME_LISTEN_FAILED);
 }
@@ -23,11 +23,15 @@ use g3_runtime::blended::BlendedRuntimeConfig;
 use g3_runtime::unaided::UnaidedRuntimeConfig;
 use g3_types::sync::GlobalInit;
 
+use crate::listen::ConnectionBudgetConfig;
+
 static RUNTIME_CONFIG: GlobalInit<BlendedRuntimeConfig> =
     GlobalInit::new(BlendedRuntimeConfig::new());
 static WORKER_CONFIG: GlobalInit<Option<UnaidedRuntimeConfig>> = GlobalInit::new(None);
 static GRACEFUL_WAIT_CONFIG: GlobalInit<GracefulWaitConfig> =
     GlobalInit::new(GracefulWaitConfig::new());
+static CONNECTION_BUDGET_CONFIG: GlobalInit<ConnectionBudgetConfig> =
+    GlobalInit::new(ConnectionBudgetConfig::new());
 
 struct GracefulWaitConfig {
     server_offline_delay: Duration,
@@ -77,6 +81,10 @@ pub fn get_task_quit_timeout() -> Duration {
     GRACEFUL_WAIT_CONFIG.as_ref().task_quit_timeout
 }
 
+pub fn get_connection_budget_config() -> &'static ConnectionBudgetConfig {
+    CONNECTION_BUDGET_CONFIG.as_ref()
+}
+
 pub fn load(v: &Yaml) -> anyhow::Result<()> {
     match v {
         Yaml::Hash(map) => g3_yaml::foreach_kv(map, set_global_config),
@@ -121,6 +129,13 @@ fn set_global_config(k: &str, v: &Yaml) -> anyhow::Result<()> {
             GRACEFUL_WAIT_CONFIG.with_mut(|config| config.task_quit_timeout = value);
             Ok(())
         }
+        "connection_budget" => {
+            let config = as_connection_budget_config(v).context(format!(
+                "invalid connection budget config value for key {k}"
+            ))?;
+            CONNECTION_BUDGET_CONFIG.with_mut(|c| *c = config);
+            Ok(())
+        }
         "thread_number" => {
             let value = g3_yaml::value::as_usize(v)?;
             RUNTIME_CONFIG.with_mut(|config| config.set_thread_number(value));
@@ -146,3 +161,32 @@ fn set_global_config(k: &str, v: &Yaml) -> anyhow::Result<()> {
         _ => Err(anyhow!("invalid key {k}")),
     }
 }
+
+fn as_connection_budget_config(v: &Yaml) -> anyhow::Result<ConnectionBudgetConfig> {
+    let mut config = ConnectionBudgetConfig::new();
+    match v {
+        Yaml::Hash(map) => {
+            g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+                "high_water" | "max_alive_connections" => {
+                    let value = g3_yaml::value::as_usize(v)
+                        .context(format!("invalid usize value for key {k}"))?;
+                    config.set_high_water(value);
+                    Ok(())
+                }
+                "low_water" => {
+                    let value = g3_yaml::value::as_usize(v)
+                        .context(format!("invalid usize value for key {k}"))?;
+                    config.set_low_water(value);
+                    Ok(())
+                }
+                _ => Err(anyhow!("invalid key {k}")),
+            })?;
+        }
+        _ => {
+            let value = g3_yaml::value::as_usize(v)?;
+            config.set_high_water(value);
+        }
+    }
+    config.check()?;
+    Ok(config)
+}
@@ -21,13 +21,27 @@ use tokio::runtime::RuntimeMetrics;
 use g3_statsd_client::{StatsdClient, StatsdTagGroup};
 use g3_types::stats::StatId;
 
+use crate::listen::ConnectionBudget;
 use crate::metrics::TAG_KEY_STAT_ID;
 
 const TAG_KEY_RUNTIME_ID: &str = "runtime_id";
 
 const METRIC_NAME_RUNTIME_TOKIO_ALIVE_TASKS: &str = "runtime.tokio.alive_tasks";
+const METRIC_NAME_RUNTIME_BUDGET_ALIVE_CONNECTIONS: &str = "runtime.budget.alive_connections";
+const METRIC_NAME_RUNTIME_BUDGET_OVERLOADED: &str = "runtime.budget.overloaded";
+const METRIC_NAME_RUNTIME_BUDGET_OVERLOAD: &str = "runtime.budget.overload";
+const METRIC_NAME_RUNTIME_BUDGET_SHED: &str = "runtime.budget.shed";
 
 static TOKIO_STATS_VEC: Mutex<Vec<TokioStatsValue>> = Mutex::new(Vec::new());
+static BUDGET_SNAPSHOT: Mutex<BudgetSnapshot> = Mutex::new(BudgetSnapshot {
+    overload: 0,
+    shed: 0,
+});
+
+struct BudgetSnapshot {
+    overload: u64,
+    shed: u64,
+}
 
 struct TokioStatsValue {
     stat_id: StatId,
@@ -50,6 +64,51 @@ pub fn emit_stats(client: &mut StatsdClient) {
     for v in tokio_stats_vec.iter_mut() {
         emit_tokio_stats(client, v);
     }
+    drop(tokio_stats_vec);
+
+    if crate::runtime::config::get_connection_budget_config().is_enabled() {
+        emit_budget_stats(client);
+    }
+}
+
+fn emit_budget_stats(client: &mut StatsdClient) {
+    let budget = ConnectionBudget::global();
+    let common_tags = StatsdTagGroup::default();
+
+    client
+        .gauge_with_tags(
+            METRIC_NAME_RUNTIME_BUDGET_ALIVE_CONNECTIONS,
+            budget.alive_count(),
+            &common_tags,
+        )
+        .send();
+    client
+        .gauge_with_tags(
+            METRIC_NAME_RUNTIME_BUDGET_OVERLOADED,
+            u8::from(budget.is_overloaded()),
+            &common_tags,
+        )
+        .send();
+
+    let mut snap = BUDGET_SNAPSHOT.lock().unwrap();
+    let overload = budget.overload_count();
+    client
+        .count_with_tags(
+            METRIC_NAME_RUNTIME_BUDGET_OVERLOAD,
+            overload.wrapping_sub(snap.overload),
+            &common_tags,
+        )
+        .send();
+    snap.overload = overload;
+    let shed = budget.shed_count();
+    client
+        .count_with_tags(
+            METRIC_NAME_RUNTIME_BUDGET_SHED,
+            shed.wrapping_sub(snap.shed),
+            &common_tags,
+        )
+        .send();
+    snap.shed = shed;
 }
 
 fn emit_tokio_stats(client: &mut StatsdClient, v: &mut TokioStatsValue) {
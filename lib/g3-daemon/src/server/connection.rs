@@ -19,7 +19,7 @@ use std::net::{IpAddr, SocketAddr};
 
 use g3_io_ext::haproxy::ProxyAddr;
 use g3_socket::RawSocket;
-use g3_types::net::TcpMiscSockOpts;
+use g3_types::net::{TcpKeepAliveConfig, TcpMiscSockOpts};
 
 #[derive(Clone, Debug)]
 pub struct ClientConnectionInfo {
@@ -112,6 +112,22 @@ impl ClientConnectionInfo {
         }
     }
 
+    pub fn tcp_sock_set_nodelay(&self, no_delay: bool) -> io::Result<()> {
+        let opts = TcpMiscSockOpts {
+            no_delay: Some(no_delay),
+            ..Default::default()
+        };
+        self.tcp_sock_set_raw_opts(&opts, false)
+    }
+
+    pub fn tcp_sock_set_keepalive(&self, keepalive: &TcpKeepAliveConfig) -> io::Result<()> {
+        if let Some(raw_socket) = &self.tcp_raw_socket {
+            raw_socket.set_tcp_keepalive(keepalive)
+        } else {
+            Ok(())
+        }
+    }
+
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn tcp_sock_try_quick_ack(&self) {
         if let Some(raw_socket) = &self.tcp_raw_socket {
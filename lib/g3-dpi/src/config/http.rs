@@ -14,8 +14,223 @@
  * limitations under the License.
  */
 
+use std::fmt;
+use std::str::FromStr;
 use std::time::Duration;
 
+use g3_types::net::{HttpHeaderMap, HttpHeaderMergeMode};
+
+use super::ProtocolInspectAction;
+
+/// The action to take if the response body size limit is exceeded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HttpBodySizeLimitPolicy {
+    /// Close the connection without sending the remaining data
+    #[default]
+    Abort,
+    /// Send the data up to the limit and then close the connection
+    Truncate,
+}
+
+impl HttpBodySizeLimitPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            HttpBodySizeLimitPolicy::Abort => "abort",
+            HttpBodySizeLimitPolicy::Truncate => "truncate",
+        }
+    }
+}
+
+impl fmt::Display for HttpBodySizeLimitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HttpBodySizeLimitPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(HttpBodySizeLimitPolicy::Abort),
+            "truncate" => Ok(HttpBodySizeLimitPolicy::Truncate),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Which bytes of the http body should be hashed for audit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpBodyHashMode {
+    /// Hash the on-the-wire body data
+    Raw,
+    /// Hash the body data after content decoding, if it's decoded
+    Decoded,
+}
+
+impl HttpBodyHashMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            HttpBodyHashMode::Raw => "raw",
+            HttpBodyHashMode::Decoded => "decoded",
+        }
+    }
+}
+
+impl fmt::Display for HttpBodyHashMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HttpBodyHashMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "raw" => Ok(HttpBodyHashMode::Raw),
+            "decoded" => Ok(HttpBodyHashMode::Decoded),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Check if the media type matches any of the expected ones.
+/// The `type/*` form can be used to match all subtypes
+fn media_type_match_any(expected: &[String], media_type: &str) -> bool {
+    expected
+        .iter()
+        .any(|expected| match expected.strip_suffix("/*") {
+            Some(main_type) => media_type
+                .split_once('/')
+                .map(|(t, _)| t.eq_ignore_ascii_case(main_type))
+                .unwrap_or(false),
+            None => media_type.eq_ignore_ascii_case(expected),
+        })
+}
+
+/// The policy to check each part of the multipart request body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpMultipartInspectionConfig {
+    pub part_head_max_size: usize,
+    /// block the parts with these filename extensions, in lowercase and without the leading dot
+    pub block_filename_ext: Vec<String>,
+    /// block the parts with these media types. The `type/*` form can be used to match all subtypes
+    pub block_content_type: Vec<String>,
+    /// the max size of each part, 0 means no limit
+    pub part_max_size: u64,
+    /// block the request if the body is not a valid multipart one
+    pub block_on_parse_error: bool,
+}
+
+impl Default for HttpMultipartInspectionConfig {
+    fn default() -> Self {
+        HttpMultipartInspectionConfig {
+            part_head_max_size: 8192,
+            block_filename_ext: Vec::new(),
+            block_content_type: Vec::new(),
+            part_max_size: 0,
+            block_on_parse_error: false,
+        }
+    }
+}
+
+impl HttpMultipartInspectionConfig {
+    /// Check the metadata of the part, return the reason if it should be blocked
+    pub fn check_part_head(
+        &self,
+        filename: Option<&str>,
+        content_type: Option<&str>,
+    ) -> Option<&'static str> {
+        if let Some(filename) = filename {
+            // the client may send the full path
+            let basename = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+            // trailing dots and spaces are stripped by windows
+            let basename = basename.trim_end_matches([' ', '.']);
+            if let Some((_, ext)) = basename.rsplit_once('.') {
+                if self
+                    .block_filename_ext
+                    .iter()
+                    .any(|e| e.eq_ignore_ascii_case(ext))
+                {
+                    return Some("filename extension blocked");
+                }
+            }
+        }
+        if let Some(content_type) = content_type {
+            if media_type_match_any(&self.block_content_type, content_type) {
+                return Some("content type blocked");
+            }
+        }
+        None
+    }
+
+    /// Check the size of the part, return the reason if it should be blocked
+    pub fn check_part_size(&self, size: u64) -> Option<&'static str> {
+        if self.part_max_size > 0 && size > self.part_max_size {
+            Some("part size limit exceeded")
+        } else {
+            None
+        }
+    }
+}
+
+/// Extra headers to inject into the http responses sent to the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponseHeaderInjection {
+    pub headers: HttpHeaderMap,
+    /// replace the headers with the same name set by upstream, or append to them
+    pub mode: HttpHeaderMergeMode,
+    /// only inject into responses with these status codes, match all if empty
+    pub match_status: Vec<u16>,
+    /// only inject into responses with these media types, match all if empty.
+    /// The `type/*` form can be used to match all subtypes
+    pub match_content_type: Vec<String>,
+}
+
+impl Default for HttpResponseHeaderInjection {
+    fn default() -> Self {
+        HttpResponseHeaderInjection {
+            headers: HttpHeaderMap::default(),
+            mode: HttpHeaderMergeMode::Set,
+            match_status: Vec::new(),
+            match_content_type: Vec::new(),
+        }
+    }
+}
+
+impl HttpResponseHeaderInjection {
+    fn match_content_type(&self, headers: &HttpHeaderMap) -> bool {
+        if self.match_content_type.is_empty() {
+            return true;
+        }
+        let Some(value) = headers.get("content-type") else {
+            return false;
+        };
+        let media_type = value.to_str().split(';').next().unwrap_or_default().trim();
+        media_type_match_any(&self.match_content_type, media_type)
+    }
+
+    pub fn matches(&self, status: u16, headers: &HttpHeaderMap) -> bool {
+        if !self.match_status.is_empty() && !self.match_status.contains(&status) {
+            return false;
+        }
+        self.match_content_type(headers)
+    }
+
+    /// Inject the headers if matched, return true if injected
+    pub fn inject(&self, status: u16, headers: &mut HttpHeaderMap) -> bool {
+        if !self.matches(status, headers) {
+            return false;
+        }
+        match self.mode {
+            HttpHeaderMergeMode::Set => headers.set_all(&self.headers),
+            HttpHeaderMergeMode::Append => headers.extend(&self.headers),
+        }
+        true
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct H1InterceptionConfig {
     pub pipeline_size: usize,
@@ -23,9 +238,24 @@ pub struct H1InterceptionConfig {
     pub req_head_recv_timeout: Duration,
     pub rsp_head_recv_timeout: Duration,
     pub req_head_max_size: usize,
+    pub req_head_max_count: usize,
     pub rsp_head_max_size: usize,
     pub body_line_max_len: usize,
     pub steal_forwarded_for: bool,
+    pub rsp_body_decompress: bool,
+    pub rsp_body_decompress_max_size: usize,
+    pub rsp_body_decompress_max_ratio: u32,
+    /// the action to take if any decompression limit is exceeded, only block and bypass are valid
+    pub rsp_body_decompress_exceed_action: ProtocolInspectAction,
+    /// the max size of the on-the-wire response body, 0 means no limit
+    pub rsp_body_max_size: u64,
+    pub rsp_body_exceed_policy: HttpBodySizeLimitPolicy,
+    /// calculate the sha256 digest of the request and response body, disabled if not set
+    pub body_sha256: Option<HttpBodyHashMode>,
+    /// headers to inject into the responses, all matched ones will be applied in order
+    pub rsp_header_inject: Vec<HttpResponseHeaderInjection>,
+    /// inspect each part of the multipart request body, disabled if not set
+    pub req_body_multipart: Option<HttpMultipartInspectionConfig>,
 }
 
 impl Default for H1InterceptionConfig {
@@ -36,9 +266,19 @@ impl Default for H1InterceptionConfig {
             req_head_recv_timeout: Duration::from_secs(30),
             rsp_head_recv_timeout: Duration::from_secs(60),
             req_head_max_size: 65536,
+            req_head_max_count: 128,
             rsp_head_max_size: 65536,
             body_line_max_len: 8192,
             steal_forwarded_for: false,
+            rsp_body_decompress: false,
+            rsp_body_decompress_max_size: 16 * 1024 * 1024, // 16MB
+            rsp_body_decompress_max_ratio: 100,
+            rsp_body_decompress_exceed_action: ProtocolInspectAction::Bypass,
+            rsp_body_max_size: 0,
+            rsp_body_exceed_policy: HttpBodySizeLimitPolicy::Abort,
+            body_sha256: None,
+            rsp_header_inject: Vec::new(),
+            req_body_multipart: None,
         }
     }
 }
@@ -47,6 +287,7 @@ impl Default for H1InterceptionConfig {
 pub struct H2InterceptionConfig {
     pub max_header_list_size: u32,
     pub max_concurrent_streams: u32,
+    pub max_upstream_concurrent_streams: u32,
     pub max_frame_size: u32,
     pub max_send_buffer_size: usize,
     pub disable_upstream_push: bool,
@@ -55,6 +296,8 @@ pub struct H2InterceptionConfig {
     pub client_handshake_timeout: Duration,
     pub rsp_head_recv_timeout: Duration,
     pub silent_drop_expect_header: bool,
+    pub rsp_body_max_size: u64,
+    pub rsp_body_exceed_policy: HttpBodySizeLimitPolicy,
 }
 
 impl Default for H2InterceptionConfig {
@@ -62,6 +305,7 @@ impl Default for H2InterceptionConfig {
         H2InterceptionConfig {
             max_header_list_size: 64 * 1024, // 64KB
             max_concurrent_streams: 16,
+            max_upstream_concurrent_streams: 0,
             max_frame_size: 1024 * 1024,            // 1MB
             max_send_buffer_size: 16 * 1024 * 1024, // 16MB
             disable_upstream_push: false,
@@ -70,6 +314,97 @@ impl Default for H2InterceptionConfig {
             client_handshake_timeout: Duration::from_secs(4),
             rsp_head_recv_timeout: Duration::from_secs(60),
             silent_drop_expect_header: false,
+            rsp_body_max_size: 0,
+            rsp_body_exceed_policy: HttpBodySizeLimitPolicy::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::http::HeaderName;
+    use g3_types::net::HttpHeaderValue;
+
+    fn header_map(headers: &[(&'static str, &'static str)]) -> HttpHeaderMap {
+        let mut map = HttpHeaderMap::default();
+        for (name, value) in headers {
+            map.append(
+                HeaderName::from_static(name),
+                HttpHeaderValue::from_static(value),
+            );
         }
+        map
+    }
+
+    fn values(map: &HttpHeaderMap, name: &'static str) -> Vec<String> {
+        map.get_all(name)
+            .iter()
+            .map(|v| v.to_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn inject_response_header() {
+        let hsts = HttpResponseHeaderInjection {
+            headers: header_map(&[("strict-transport-security", "max-age=31536000")]),
+            mode: HttpHeaderMergeMode::Set,
+            ..Default::default()
+        };
+        let cache = HttpResponseHeaderInjection {
+            headers: header_map(&[("x-cache", "MISS")]),
+            mode: HttpHeaderMergeMode::Append,
+            match_status: vec![200],
+            match_content_type: vec!["text/*".to_string()],
+        };
+
+        let mut rsp_200 = header_map(&[
+            ("content-type", "text/html; charset=utf-8"),
+            ("strict-transport-security", "max-age=600"),
+            ("x-cache", "HIT"),
+        ]);
+        assert!(hsts.inject(200, &mut rsp_200));
+        assert!(cache.inject(200, &mut rsp_200));
+        assert_eq!(
+            values(&rsp_200, "strict-transport-security"),
+            vec!["max-age=31536000"]
+        );
+        assert_eq!(values(&rsp_200, "x-cache"), vec!["HIT", "MISS"]);
+
+        let mut rsp_304 = header_map(&[("etag", "\"abc\"")]);
+        assert!(hsts.inject(304, &mut rsp_304));
+        assert!(!cache.inject(304, &mut rsp_304));
+        assert_eq!(
+            values(&rsp_304, "strict-transport-security"),
+            vec!["max-age=31536000"]
+        );
+        assert!(!rsp_304.contains_key("x-cache"));
+
+        let mut rsp_json = header_map(&[("content-type", "application/json")]);
+        assert!(!cache.inject(200, &mut rsp_json));
+    }
+
+    #[test]
+    fn check_multipart_part() {
+        let config = HttpMultipartInspectionConfig {
+            block_filename_ext: vec!["exe".to_string()],
+            block_content_type: vec![
+                "application/x-msdownload".to_string(),
+                "video/*".to_string(),
+            ],
+            part_max_size: 1024,
+            ..Default::default()
+        };
+        assert!(config
+            .check_part_head(Some("C:\\Users\\a\\setup.EXE"), None)
+            .is_some());
+        assert!(config.check_part_head(Some("setup.exe. "), None).is_some());
+        assert!(config
+            .check_part_head(Some("exe.txt"), Some("text/plain"))
+            .is_none());
+        assert!(config.check_part_head(None, Some("video/mp4")).is_some());
+        assert!(config.check_part_head(None, None).is_none());
+        assert!(config.check_part_size(1024).is_none());
+        assert!(config.check_part_size(1025).is_some());
     }
 }
@@ -16,33 +16,52 @@
 
 use std::fmt;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
 use g3_types::acl::{
     AclChildDomainRule, AclChildDomainRuleBuilder, AclExactHostRule, AclNetworkRule,
     AclNetworkRuleBuilder, ActionContract,
 };
-use g3_types::net::Host;
+use g3_types::net::{Host, HttpBlockResponse};
 
 mod size_limit;
 
 pub use size_limit::ProtocolInspectionSizeLimit;
 
 mod http;
-pub use http::{H1InterceptionConfig, H2InterceptionConfig};
+pub use http::{
+    H1InterceptionConfig, H2InterceptionConfig, HttpBodyHashMode, HttpBodySizeLimitPolicy,
+    HttpMultipartInspectionConfig, HttpResponseHeaderInjection,
+};
 
 mod smtp;
-pub use smtp::SmtpInterceptionConfig;
+pub use smtp::{
+    SmtpGreetingBanner, SmtpInterceptionConfig, SmtpLongLineAction, SmtpMailParamRewrite,
+    SmtpReplyRemap, SmtpStartTlsPolicy,
+};
 
 mod imap;
 pub use imap::ImapInterceptionConfig;
 
-#[derive(Clone)]
+mod pop3;
+pub use pop3::Pop3InterceptionConfig;
+
+mod websocket;
+pub use websocket::WebSocketInterceptionConfig;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ProtocolInspectPolicyBuilder {
     missed_action: ProtocolInspectAction,
     pub exact: Option<AclExactHostRule<ProtocolInspectAction>>,
     pub child: Option<AclChildDomainRuleBuilder<ProtocolInspectAction>>,
     pub subnet: Option<AclNetworkRuleBuilder<ProtocolInspectAction>>,
+    pub block_response: Option<Arc<HttpBlockResponse>>,
+    pub exact_block_response: Option<Arc<HttpBlockResponse>>,
+    pub child_block_response: Option<Arc<HttpBlockResponse>>,
+    pub subnet_block_response: Option<Arc<HttpBlockResponse>>,
+    /// log the block decisions but let the traffic through
+    pub dry_run: bool,
 }
 
 impl Default for ProtocolInspectPolicyBuilder {
@@ -58,6 +77,11 @@ impl ProtocolInspectPolicyBuilder {
             exact: None,
             child: None,
             subnet: None,
+            block_response: None,
+            exact_block_response: None,
+            child_block_response: None,
+            subnet_block_response: None,
+            dry_run: false,
         }
     }
 
@@ -71,6 +95,31 @@ impl ProtocolInspectPolicyBuilder {
             child: self.child.as_ref().map(|b| b.build()),
             subnet: self.subnet.as_ref().map(|b| b.build()),
             missed_action: self.missed_action,
+            block_response: self.block_response.clone(),
+            exact_block_response: self.exact_block_response.clone(),
+            child_block_response: self.child_block_response.clone(),
+            subnet_block_response: self.subnet_block_response.clone(),
+            dry_run: self.dry_run,
+        }
+    }
+}
+
+/// The rule in a [ProtocolInspectPolicy] that decided the action
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolInspectPolicyRule {
+    ExactMatch,
+    ChildMatch,
+    SubnetMatch,
+    Default,
+}
+
+impl ProtocolInspectPolicyRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProtocolInspectPolicyRule::ExactMatch => "exact_match",
+            ProtocolInspectPolicyRule::ChildMatch => "child_match",
+            ProtocolInspectPolicyRule::SubnetMatch => "subnet_match",
+            ProtocolInspectPolicyRule::Default => "default",
         }
     }
 }
@@ -80,23 +129,57 @@ pub struct ProtocolInspectPolicy {
     child: Option<AclChildDomainRule<ProtocolInspectAction>>,
     subnet: Option<AclNetworkRule<ProtocolInspectAction>>,
     missed_action: ProtocolInspectAction,
+    block_response: Option<Arc<HttpBlockResponse>>,
+    exact_block_response: Option<Arc<HttpBlockResponse>>,
+    child_block_response: Option<Arc<HttpBlockResponse>>,
+    subnet_block_response: Option<Arc<HttpBlockResponse>>,
+    dry_run: bool,
 }
 
 impl ProtocolInspectPolicy {
+    /// Get the custom HTTP response to use for the block_with_response action.
+    ///
+    /// The one set in the matched rule will be used first, then the policy level one.
+    pub fn block_response(&self, upstream: &Host) -> Option<&Arc<HttpBlockResponse>> {
+        let (rule, _) = self.check_rule(upstream);
+        let rule_response = match rule {
+            ProtocolInspectPolicyRule::ExactMatch => self.exact_block_response.as_ref(),
+            ProtocolInspectPolicyRule::ChildMatch => self.child_block_response.as_ref(),
+            ProtocolInspectPolicyRule::SubnetMatch => self.subnet_block_response.as_ref(),
+            ProtocolInspectPolicyRule::Default => None,
+        };
+        rule_response.or(self.block_response.as_ref())
+    }
+
+    /// Whether the block actions should only be logged
+    #[inline]
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
     pub fn check(&self, upstream: &Host) -> (bool, ProtocolInspectAction) {
+        let (rule, action) = self.check_rule(upstream);
+        (rule != ProtocolInspectPolicyRule::Default, action)
+    }
+
+    /// Check the upstream host and also return the rule that matched
+    pub fn check_rule(
+        &self,
+        upstream: &Host,
+    ) -> (ProtocolInspectPolicyRule, ProtocolInspectAction) {
         match upstream {
             Host::Ip(ip) => {
                 if let Some(rule) = &self.exact {
                     let (found, action) = rule.check_ip(ip);
                     if found {
-                        return (true, action);
+                        return (ProtocolInspectPolicyRule::ExactMatch, action);
                     }
                 }
 
                 if let Some(rule) = &self.subnet {
                     let (found, action) = rule.check(*ip);
                     if found {
-                        return (true, action);
+                        return (ProtocolInspectPolicyRule::SubnetMatch, action);
                     }
                 }
             }
@@ -104,26 +187,27 @@ impl ProtocolInspectPolicy {
                 if let Some(rule) = &self.exact {
                     let (found, action) = rule.check_domain(domain);
                     if found {
-                        return (true, action);
+                        return (ProtocolInspectPolicyRule::ExactMatch, action);
                     }
                 }
 
                 if let Some(rule) = &self.child {
                     let (found, action) = rule.check(domain);
                     if found {
-                        return (true, action);
+                        return (ProtocolInspectPolicyRule::ChildMatch, action);
                     }
                 }
             }
         }
 
-        (false, self.missed_action)
+        (ProtocolInspectPolicyRule::Default, self.missed_action)
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ProtocolInspectAction {
     Block,
+    BlockWithResponse,
     #[default]
     Intercept,
     Bypass,
@@ -132,9 +216,10 @@ pub enum ProtocolInspectAction {
 }
 
 impl ProtocolInspectAction {
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             Self::Block => "block",
+            Self::BlockWithResponse => "block_with_response",
             Self::Intercept => "intercept",
             Self::Bypass => "bypass",
             #[cfg(feature = "quic")]
@@ -143,7 +228,10 @@ impl ProtocolInspectAction {
     }
 
     pub fn is_block(&self) -> bool {
-        matches!(self, ProtocolInspectAction::Block)
+        matches!(
+            self,
+            ProtocolInspectAction::Block | ProtocolInspectAction::BlockWithResponse
+        )
     }
 }
 
@@ -159,6 +247,7 @@ impl FromStr for ProtocolInspectAction {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
             "block" => Ok(ProtocolInspectAction::Block),
+            "block_with_response" | "respond" => Ok(ProtocolInspectAction::BlockWithResponse),
             "intercept" => Ok(ProtocolInspectAction::Intercept),
             "bypass" => Ok(ProtocolInspectAction::Bypass),
             #[cfg(feature = "quic")]
@@ -173,6 +262,7 @@ impl ActionContract for ProtocolInspectAction {}
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ProtocolInspectionConfig {
     inspect_max_depth: usize,
+    inspect_max_depth_action: Option<ProtocolInspectAction>,
     data0_buffer_size: usize,
     data0_wait_timeout: Duration,
     data0_read_timeout: Duration,
@@ -183,6 +273,7 @@ impl Default for ProtocolInspectionConfig {
     fn default() -> Self {
         ProtocolInspectionConfig {
             inspect_max_depth: 4,
+            inspect_max_depth_action: None,
             data0_buffer_size: 4096,
             data0_wait_timeout: Duration::from_secs(60),
             data0_read_timeout: Duration::from_secs(4),
@@ -201,6 +292,17 @@ impl ProtocolInspectionConfig {
         self.inspect_max_depth
     }
 
+    pub fn set_max_depth_action(&mut self, action: ProtocolInspectAction) {
+        self.inspect_max_depth_action = Some(action);
+    }
+
+    /// The action to take if the max depth is reached,
+    /// or None if the stream should be treated as unknown protocol
+    #[inline]
+    pub fn max_depth_action(&self) -> Option<ProtocolInspectAction> {
+        self.inspect_max_depth_action
+    }
+
     pub fn set_data0_buffer_size(&mut self, size: usize) {
         self.data0_buffer_size = size;
     }
@@ -240,3 +342,72 @@ impl ProtocolInspectionConfig {
         &mut self.data0_size_limit
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_rule() {
+        let mut exact = AclExactHostRule::new(ProtocolInspectAction::Intercept);
+        exact.add_domain(
+            Arc::from("blocked.example.net"),
+            ProtocolInspectAction::Block,
+        );
+        let mut builder = ProtocolInspectPolicyBuilder::new(ProtocolInspectAction::Bypass);
+        builder.exact = Some(exact);
+        builder.dry_run = true;
+        let policy = builder.build();
+        assert!(policy.dry_run());
+
+        let host = Host::Domain(Arc::from("blocked.example.net"));
+        assert_eq!(
+            policy.check_rule(&host),
+            (
+                ProtocolInspectPolicyRule::ExactMatch,
+                ProtocolInspectAction::Block
+            )
+        );
+        assert_eq!(policy.check(&host), (true, ProtocolInspectAction::Block));
+
+        let host = Host::Domain(Arc::from("www.example.net"));
+        assert_eq!(
+            policy.check_rule(&host),
+            (
+                ProtocolInspectPolicyRule::Default,
+                ProtocolInspectAction::Bypass
+            )
+        );
+    }
+
+    #[test]
+    fn rule_block_response() {
+        let mut exact = AclExactHostRule::new(ProtocolInspectAction::Intercept);
+        exact.add_domain(
+            Arc::from("blocked.example.net"),
+            ProtocolInspectAction::BlockWithResponse,
+        );
+        let mut builder =
+            ProtocolInspectPolicyBuilder::new(ProtocolInspectAction::BlockWithResponse);
+        builder.exact = Some(exact);
+        builder.exact_block_response = Some(Arc::new(HttpBlockResponse::new(
+            ::http::StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+        )));
+        let policy = builder.build();
+
+        let host = Host::Domain(Arc::from("blocked.example.net"));
+        let rsp = policy.block_response(&host).unwrap();
+        assert_eq!(
+            rsp.status(),
+            ::http::StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS
+        );
+
+        let host = Host::Domain(Arc::from("www.example.net"));
+        assert!(policy.block_response(&host).is_none());
+
+        builder.block_response = Some(Arc::new(HttpBlockResponse::default()));
+        let policy = builder.build();
+        let rsp = policy.block_response(&host).unwrap();
+        assert_eq!(rsp.status(), ::http::StatusCode::FORBIDDEN);
+    }
+}
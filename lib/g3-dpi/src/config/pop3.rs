@@ -0,0 +1,38 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pop3InterceptionConfig {
+    pub greeting_timeout: Duration,
+    pub quit_wait_timeout: Duration,
+    pub command_wait_timeout: Duration,
+    pub response_wait_timeout: Duration,
+    pub transfer_max_idle_count: i32,
+}
+
+impl Default for Pop3InterceptionConfig {
+    fn default() -> Self {
+        Pop3InterceptionConfig {
+            greeting_timeout: Duration::from_secs(300),
+            quit_wait_timeout: Duration::from_secs(60),
+            command_wait_timeout: Duration::from_secs(600),
+            response_wait_timeout: Duration::from_secs(300),
+            transfer_max_idle_count: 1,
+        }
+    }
+}
@@ -14,19 +14,184 @@
  * limitations under the License.
  */
 
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
 use std::time::Duration;
 
+use g3_types::net::{Host, ProxyProtocolVersion};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SmtpStartTlsPolicy {
+    Require,
+    Prefer,
+    #[default]
+    Ignore,
+}
+
+impl SmtpStartTlsPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SmtpStartTlsPolicy::Require => "require",
+            SmtpStartTlsPolicy::Prefer => "prefer",
+            SmtpStartTlsPolicy::Ignore => "ignore",
+        }
+    }
+}
+
+impl fmt::Display for SmtpStartTlsPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SmtpStartTlsPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "require" | "required" => Ok(SmtpStartTlsPolicy::Require),
+            "prefer" | "preferred" => Ok(SmtpStartTlsPolicy::Prefer),
+            "ignore" | "ignored" => Ok(SmtpStartTlsPolicy::Ignore),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What to do if the upstream sends a response line longer than the max allowed size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SmtpLongLineAction {
+    #[default]
+    Abort,
+    Truncate,
+}
+
+impl SmtpLongLineAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SmtpLongLineAction::Abort => "abort",
+            SmtpLongLineAction::Truncate => "truncate",
+        }
+    }
+}
+
+impl fmt::Display for SmtpLongLineAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SmtpLongLineAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(SmtpLongLineAction::Abort),
+            "truncate" => Ok(SmtpLongLineAction::Truncate),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SmtpGreetingBanner {
+    /// the host field in the greeting message, the local ip address literal will be used if not set
+    pub hostname: Option<String>,
+    /// the textstring in the greeting message, `{hostname}` and `{upstream}` will be replaced
+    pub message: String,
+    /// connect to the upstream only after the client EHLO / HELO has been received
+    pub defer_upstream_connection: bool,
+}
+
+impl Default for SmtpGreetingBanner {
+    fn default() -> Self {
+        SmtpGreetingBanner {
+            hostname: None,
+            message: "ESMTP Service ready".to_string(),
+            defer_upstream_connection: false,
+        }
+    }
+}
+
+impl SmtpGreetingBanner {
+    pub fn hostname(&self, local_ip: IpAddr) -> Cow<'_, str> {
+        match &self.hostname {
+            Some(s) => Cow::Borrowed(s),
+            None => match local_ip {
+                IpAddr::V4(v4) => Cow::Owned(format!("[{v4}]")),
+                IpAddr::V6(v6) => Cow::Owned(format!("[IPv6:{v6}]")),
+            },
+        }
+    }
+
+    pub fn encode(&self, local_ip: IpAddr, upstream: &Host) -> String {
+        let hostname = self.hostname(local_ip);
+        let message = self
+            .message
+            .replace("{hostname}", &hostname)
+            .replace("{upstream}", &upstream.to_string());
+        if message.is_empty() {
+            format!("220 {hostname}\r\n")
+        } else {
+            format!("220 {hostname} {message}\r\n")
+        }
+    }
+}
+
+/// Rewrite of the upstream replies with the matched reply code and enhanced status code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SmtpReplyRemap {
+    pub match_code: u16,
+    /// match all enhanced status codes if not set
+    pub match_enhanced_status: Option<String>,
+    /// the new reply code, which should be in the same class as the matched one
+    pub code: u16,
+    /// the new enhanced status code, only used if there is one in the original reply
+    pub enhanced_status: Option<String>,
+    /// the new text for all lines of the reply
+    pub text: Option<String>,
+}
+
+/// Rewrite of the ESMTP parameters in the MAIL FROM command before forwarding it to the upstream
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SmtpMailParamRewrite {
+    /// remove the parameter with the keyword
+    Remove(String),
+    /// set the value of the parameter, which will be appended if not present
+    Set(String, Option<String>),
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SmtpInterceptionConfig {
     pub greeting_timeout: Duration,
     pub quit_wait_timeout: Duration,
+    /// max time to wait for the in-progress message data transfer to finish when the server is force quit
+    pub drain_timeout: Duration,
     pub command_wait_timeout: Duration,
+    /// close the session if the client sends nothing between commands, the timer resets on each command
+    pub command_idle_timeout: Option<Duration>,
     pub response_wait_timeout: Duration,
     pub data_initiation_timeout: Duration,
     pub data_termination_timeout: Duration,
     pub allow_on_demand_mail_relay: bool,
     pub allow_data_chunking: bool,
     pub allow_burl_data: bool,
+    pub max_message_size: Option<usize>,
+    /// max number of recipients accepted in a single mail transaction
+    pub max_recipients: Option<usize>,
+    pub starttls_policy: SmtpStartTlsPolicy,
+    pub starttls_host_policy: HashMap<Host, SmtpStartTlsPolicy>,
+    pub greeting_banner: Option<SmtpGreetingBanner>,
+    pub reply_remap: Vec<SmtpReplyRemap>,
+    /// rules applied in order to the ESMTP parameters of each MAIL FROM command
+    pub mail_param_rewrite: Vec<SmtpMailParamRewrite>,
+    /// send PROXY protocol header to the upstream MTA before waiting for its greeting
+    pub upstream_proxy_protocol: Option<ProxyProtocolVersion>,
+    /// also accept bare IP addresses and `[IPv6:...]` literals as the host in upstream greeting
+    pub lenient_greeting_host: bool,
+    pub long_response_line: SmtpLongLineAction,
 }
 
 impl Default for SmtpInterceptionConfig {
@@ -34,13 +199,112 @@ impl Default for SmtpInterceptionConfig {
         SmtpInterceptionConfig {
             greeting_timeout: Duration::from_secs(300),
             quit_wait_timeout: Duration::from_secs(60),
+            drain_timeout: Duration::from_secs(30),
             command_wait_timeout: Duration::from_secs(300),
+            command_idle_timeout: None,
             response_wait_timeout: Duration::from_secs(300),
             data_initiation_timeout: Duration::from_secs(120),
             data_termination_timeout: Duration::from_secs(600),
             allow_on_demand_mail_relay: false,
             allow_data_chunking: false,
             allow_burl_data: false,
+            max_message_size: None,
+            max_recipients: None,
+            starttls_policy: SmtpStartTlsPolicy::default(),
+            starttls_host_policy: HashMap::new(),
+            greeting_banner: None,
+            reply_remap: Vec::new(),
+            mail_param_rewrite: Vec::new(),
+            upstream_proxy_protocol: None,
+            lenient_greeting_host: false,
+            long_response_line: SmtpLongLineAction::default(),
         }
     }
 }
+
+impl SmtpInterceptionConfig {
+    pub fn starttls_policy(&self, upstream: &Host) -> SmtpStartTlsPolicy {
+        self.starttls_host_policy
+            .get(upstream)
+            .copied()
+            .unwrap_or(self.starttls_policy)
+    }
+
+    /// Find the reply remap rule, the ones with matched enhanced status code take precedence
+    pub fn find_reply_remap(
+        &self,
+        code: u16,
+        enhanced_status: Option<&str>,
+    ) -> Option<&SmtpReplyRemap> {
+        if let Some(status) = enhanced_status {
+            let found = self.reply_remap.iter().find(|r| {
+                r.match_code == code && r.match_enhanced_status.as_deref() == Some(status)
+            });
+            if found.is_some() {
+                return found;
+            }
+        }
+        self.reply_remap
+            .iter()
+            .find(|r| r.match_code == code && r.match_enhanced_status.is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_greeting_banner() {
+        let upstream = Host::from_str("mx.example.net").unwrap();
+
+        let banner = SmtpGreetingBanner::default();
+        assert_eq!(
+            banner.encode(IpAddr::from([192, 168, 1, 1]), &upstream),
+            "220 [192.168.1.1] ESMTP Service ready\r\n"
+        );
+        assert_eq!(
+            banner.encode("2001:db8::1".parse().unwrap(), &upstream),
+            "220 [IPv6:2001:db8::1] ESMTP Service ready\r\n"
+        );
+
+        let banner = SmtpGreetingBanner {
+            hostname: Some("smtp.example.com".to_string()),
+            message: "{hostname} relay for {upstream}".to_string(),
+            defer_upstream_connection: false,
+        };
+        assert_eq!(
+            banner.encode(IpAddr::from([192, 168, 1, 1]), &upstream),
+            "220 smtp.example.com smtp.example.com relay for mx.example.net\r\n"
+        );
+    }
+
+    #[test]
+    fn find_reply_remap() {
+        let mut config = SmtpInterceptionConfig::default();
+        assert!(config.find_reply_remap(550, Some("5.7.1")).is_none());
+
+        config.reply_remap.push(SmtpReplyRemap {
+            match_code: 550,
+            match_enhanced_status: None,
+            code: 554,
+            enhanced_status: None,
+            text: None,
+        });
+        config.reply_remap.push(SmtpReplyRemap {
+            match_code: 550,
+            match_enhanced_status: Some("5.7.1".to_string()),
+            code: 554,
+            enhanced_status: Some("5.7.0".to_string()),
+            text: Some("Rejected".to_string()),
+        });
+
+        let remap = config.find_reply_remap(550, Some("5.7.1")).unwrap();
+        assert_eq!(remap.enhanced_status.as_deref(), Some("5.7.0"));
+        let remap = config.find_reply_remap(550, Some("5.1.1")).unwrap();
+        assert!(remap.enhanced_status.is_none());
+        let remap = config.find_reply_remap(550, None).unwrap();
+        assert!(remap.enhanced_status.is_none());
+        assert!(config.find_reply_remap(552, None).is_none());
+    }
+}
@@ -0,0 +1,48 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use g3_types::limit::RateLimitQuotaConfig;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WebSocketInterceptionConfig {
+    /// max rate of frames sent from client to server
+    pub client_frame_rate_limit: Option<RateLimitQuotaConfig>,
+    /// max rate of frames sent from server to client
+    pub server_frame_rate_limit: Option<RateLimitQuotaConfig>,
+    /// whether ping / pong frames should be counted in the rate limit
+    pub rate_limit_count_ping_pong: bool,
+    /// the reason text to send in close frames when the session is blocked
+    pub block_close_reason: String,
+    /// whether to validate and sanitize the upgrade handshake headers strictly
+    pub strict_handshake: bool,
+    /// reply pong to client pings if the server has not replied within this delay
+    pub synthetic_pong_delay: Option<Duration>,
+}
+
+impl Default for WebSocketInterceptionConfig {
+    fn default() -> Self {
+        WebSocketInterceptionConfig {
+            client_frame_rate_limit: None,
+            server_frame_rate_limit: None,
+            rate_limit_count_ping_pong: false,
+            block_close_reason: String::new(),
+            strict_handshake: true,
+            synthetic_pong_delay: None,
+        }
+    }
+}
@@ -25,9 +25,12 @@ pub use protocol::{
 
 mod config;
 pub use config::{
-    H1InterceptionConfig, H2InterceptionConfig, ImapInterceptionConfig, ProtocolInspectAction,
-    ProtocolInspectPolicy, ProtocolInspectPolicyBuilder, ProtocolInspectionConfig,
-    ProtocolInspectionSizeLimit, SmtpInterceptionConfig,
+    H1InterceptionConfig, H2InterceptionConfig, HttpBodyHashMode, HttpBodySizeLimitPolicy,
+    HttpMultipartInspectionConfig, HttpResponseHeaderInjection, ImapInterceptionConfig,
+    Pop3InterceptionConfig, ProtocolInspectAction, ProtocolInspectPolicy,
+    ProtocolInspectPolicyBuilder, ProtocolInspectPolicyRule, ProtocolInspectionConfig,
+    ProtocolInspectionSizeLimit, SmtpGreetingBanner, SmtpInterceptionConfig, SmtpLongLineAction,
+    SmtpMailParamRewrite, SmtpReplyRemap, SmtpStartTlsPolicy, WebSocketInterceptionConfig,
 };
 
 pub mod parser;
@@ -33,5 +33,8 @@ pub use handshake::{ClientHello, ClientHelloParseError, HandshakeCoalescer, Hand
 mod extension;
 pub use extension::{ExtensionList, ExtensionParseError, ExtensionType};
 
+mod peek;
+pub use peek::{ClientHelloPeek, ClientHelloPeekError, ClientHelloPeeker};
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,172 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use thiserror::Error;
+
+use g3_types::net::{TlsAlpn, TlsServerName};
+
+use super::{ClientHello, ExtensionType, HandshakeCoalescer, Record, RecordParseError};
+
+#[derive(Debug, Error)]
+pub enum ClientHelloPeekError {
+    #[error("invalid tls record: {0}")]
+    InvalidRecord(RecordParseError),
+    #[error("invalid tls handshake message")]
+    InvalidHandshake,
+    #[error("invalid tls client hello message")]
+    InvalidClientHello,
+    #[error("partial fragmented tls client hello message")]
+    PartialFragment,
+    #[error("invalid extension in tls client hello message")]
+    InvalidExtension,
+    #[error("invalid server name extension")]
+    InvalidServerName,
+    #[error("invalid alpn extension")]
+    InvalidAlpn,
+}
+
+/// The server name and alpn info peeked from a TLS ClientHello message
+pub struct ClientHelloPeek {
+    server_name: Option<TlsServerName>,
+    alpn: Option<TlsAlpn>,
+}
+
+/// Incremental parser for the ClientHello message at the start of a stream.
+///
+/// Complete records are parsed only once, so the same growing buffer can be fed again
+/// after more data has been read.
+pub struct ClientHelloPeeker {
+    handshake_coalescer: HandshakeCoalescer,
+    record_offset: usize,
+}
+
+impl ClientHelloPeeker {
+    pub fn new(max_size: u32) -> Self {
+        ClientHelloPeeker {
+            handshake_coalescer: HandshakeCoalescer::new(max_size),
+            record_offset: 0,
+        }
+    }
+
+    /// Parse the ClientHello message at the start of the data, which may span multiple records.
+    ///
+    /// The data won't be consumed, and `Ok(None)` will be returned if more data is needed.
+    /// The caller should only append new data to the buffer between calls.
+    pub fn peek(&mut self, data: &[u8]) -> Result<Option<ClientHelloPeek>, ClientHelloPeekError> {
+        loop {
+            let Some(left) = data.get(self.record_offset..) else {
+                return Ok(None);
+            };
+            let mut record = match Record::parse(left) {
+                Ok(r) => r,
+                Err(RecordParseError::NeedMoreData(_)) => return Ok(None),
+                Err(e) => return Err(ClientHelloPeekError::InvalidRecord(e)),
+            };
+            self.record_offset += record.encoded_len();
+
+            // The Client Hello Message MUST be the first Handshake message
+            match record.consume_handshake(&mut self.handshake_coalescer) {
+                Ok(Some(handshake_msg)) => {
+                    let ch = handshake_msg
+                        .parse_client_hello()
+                        .map_err(|_| ClientHelloPeekError::InvalidClientHello)?;
+                    return ClientHelloPeek::from_client_hello(ch).map(Some);
+                }
+                Ok(None) => match self.handshake_coalescer.parse_client_hello() {
+                    Ok(Some(ch)) => return ClientHelloPeek::from_client_hello(ch).map(Some),
+                    Ok(None) => {
+                        if !record.consume_done() {
+                            return Err(ClientHelloPeekError::PartialFragment);
+                        }
+                    }
+                    Err(_) => return Err(ClientHelloPeekError::InvalidClientHello),
+                },
+                Err(_) => return Err(ClientHelloPeekError::InvalidHandshake),
+            }
+        }
+    }
+}
+
+impl ClientHelloPeek {
+    /// Parse the ClientHello message at the start of the data in one shot
+    pub fn parse(data: &[u8], max_size: u32) -> Result<Option<Self>, ClientHelloPeekError> {
+        ClientHelloPeeker::new(max_size).peek(data)
+    }
+
+    fn from_client_hello(ch: ClientHello) -> Result<Self, ClientHelloPeekError> {
+        let server_name = match ch.get_ext(ExtensionType::ServerName) {
+            Ok(Some(data)) => Some(
+                TlsServerName::from_extension_value(data)
+                    .map_err(|_| ClientHelloPeekError::InvalidServerName)?,
+            ),
+            Ok(None) => None,
+            Err(_) => return Err(ClientHelloPeekError::InvalidExtension),
+        };
+        let alpn = match ch.get_ext(ExtensionType::ApplicationLayerProtocolNegotiation) {
+            Ok(Some(data)) => Some(
+                TlsAlpn::from_extension_value(data)
+                    .map_err(|_| ClientHelloPeekError::InvalidAlpn)?,
+            ),
+            Ok(None) => None,
+            Err(_) => return Err(ClientHelloPeekError::InvalidExtension),
+        };
+        Ok(ClientHelloPeek { server_name, alpn })
+    }
+
+    #[inline]
+    pub fn server_name(&self) -> Option<&TlsServerName> {
+        self.server_name.as_ref()
+    }
+
+    #[inline]
+    pub fn alpn(&self) -> Option<&TlsAlpn> {
+        self.alpn.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_sni() {
+        let data: &[u8] = &[
+            0x16, //
+            0x03, 0x01, // TLS 1.0
+            0x00, 0x2f, // Fragment Length, 47
+            0x01, // Handshake Type - ClientHello
+            0x00, 0x00, 0x2b, // Message Length, 43
+            0x03, 0x03, // TLS 1.2
+            0x74, 0x90, 0x65, 0xea, 0xbb, 0x00, 0x5d, 0xf8, 0xdf, 0xd6, 0xde, 0x04, 0xf8, 0xd3,
+            0x69, 0x02, 0xf5, 0x8c, 0x82, 0x50, 0x7a, 0x40, 0xf6, 0xf3, 0xbb, 0x18, 0xc0, 0xac,
+            0x4f, 0x55, 0x9a, 0xda, // Random data, 32 bytes
+            0x00, // Session ID Length
+            0x00, 0x02, // Cipher Suites Length
+            0x13, 0x01, // Cipher Suites
+            0x01, // Compression Methods Length
+            0x00, // Compression Methods
+            0x00, 0x00, // Extensions Length, 0
+        ];
+
+        let peek = ClientHelloPeek::parse(data, 1 << 14).unwrap().unwrap();
+        assert!(peek.server_name().is_none());
+        assert!(peek.alpn().is_none());
+
+        assert!(ClientHelloPeek::parse(&data[..20], 1 << 14)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -16,7 +16,9 @@
 
 use g3_types::net::TlsServerName;
 
-use crate::parser::tls::{ExtensionType, HandshakeCoalescer, Record};
+use crate::parser::tls::{
+    ClientHelloPeek, ClientHelloPeeker, ExtensionType, HandshakeCoalescer, Record,
+};
 
 const RECORD_1_BYTES: &[u8] = &[
     0x16, 0x03, 0x01, 0x00, 0x64, 0x01, 0x00, 0x01, 0x8a, 0x03, 0x03, 0x02, 0x86, 0x70, 0x33, 0x46,
@@ -93,3 +95,38 @@ fn sni() {
     let sni = TlsServerName::from_extension_value(sni_bytes).unwrap();
     assert_eq!(sni.as_ref(), "www.google.com");
 }
+
+#[test]
+fn peek() {
+    let mut data = Vec::new();
+    for record in [RECORD_1_BYTES, RECORD_2_BYTES, RECORD_3_BYTES] {
+        data.extend_from_slice(record);
+        assert!(ClientHelloPeek::parse(&data, 1 << 14).unwrap().is_none());
+    }
+    data.extend_from_slice(&RECORD_4_BYTES[..10]);
+    assert!(ClientHelloPeek::parse(&data, 1 << 14).unwrap().is_none());
+    data.extend_from_slice(&RECORD_4_BYTES[10..]);
+
+    let peek = ClientHelloPeek::parse(&data, 1 << 14).unwrap().unwrap();
+    assert_eq!(peek.server_name().unwrap().as_ref(), "www.google.com");
+    let alpn: Vec<&[u8]> = peek.alpn().unwrap().protocol_names().collect();
+    assert_eq!(alpn, vec![b"h2".as_slice(), b"http/1.1".as_slice()]);
+
+    assert!(ClientHelloPeek::parse(&data, 128).is_err());
+}
+
+#[test]
+fn peek_incremental() {
+    let mut peeker = ClientHelloPeeker::new(1 << 14);
+    let mut data = Vec::new();
+    for record in [RECORD_1_BYTES, RECORD_2_BYTES, RECORD_3_BYTES] {
+        data.extend_from_slice(&record[..3]);
+        assert!(peeker.peek(&data).unwrap().is_none());
+        data.extend_from_slice(&record[3..]);
+        assert!(peeker.peek(&data).unwrap().is_none());
+    }
+    data.extend_from_slice(RECORD_4_BYTES);
+
+    let peek = peeker.peek(&data).unwrap().unwrap();
+    assert_eq!(peek.server_name().unwrap().as_ref(), "www.google.com");
+}
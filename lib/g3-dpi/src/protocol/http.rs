@@ -293,12 +293,17 @@ impl ProtocolInspectState {
 
         let data_len = data.len();
         if data_len < H2_CONNECTION_PREFACE_LEN {
+            if !H2_CONNECTION_PREFACE.starts_with(data) {
+                self.exclude_current();
+                return Ok(None);
+            }
             return Err(ProtocolInspectError::NeedMoreData(
                 H2_CONNECTION_PREFACE_LEN - data_len,
             ));
         }
 
         if !data.starts_with(H2_CONNECTION_PREFACE) {
+            self.exclude_current();
             return Ok(None);
         }
 
@@ -160,4 +160,12 @@ impl ProtocolPortMap {
     pub fn get(&self, port: u16) -> Option<&ProtocolPortMapValue> {
         self.inner.get(&port)
     }
+
+    /// Check if the protocol is expected on this port
+    pub fn contains(&self, port: u16, protocol: MaybeProtocol) -> bool {
+        self.inner
+            .get(&port)
+            .map(|v| v.protocols.contains(&protocol))
+            .unwrap_or(false)
+    }
 }
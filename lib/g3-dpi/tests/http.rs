@@ -29,3 +29,56 @@ fn port80_small() {
         .unwrap();
     assert_eq!(protocol, Protocol::Http1);
 }
+
+const H2_PRIOR_KNOWLEDGE_DATA: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\
+    \x00\x00\x12\x04\x00\x00\x00\x00\x00\x00\x03\x00\x00\x00\x64\x00\x04\x00\x10\x00\x00\x00\x06\x00\x00\x40\x00";
+
+#[test]
+fn port80_h2_prior_knowledge() {
+    let mut inspector = ProtocolInspector::default();
+    let config = ProtocolInspectionConfig::default();
+
+    let protocol = inspector
+        .check_client_initial_data(&config, 80, H2_PRIOR_KNOWLEDGE_DATA)
+        .unwrap();
+    assert_eq!(protocol, Protocol::Http2);
+}
+
+#[test]
+fn guess_h2_prior_knowledge() {
+    let mut inspector = ProtocolInspector::default();
+    let config = ProtocolInspectionConfig::default();
+
+    let protocol = inspector
+        .check_client_initial_data(&config, 50051, H2_PRIOR_KNOWLEDGE_DATA)
+        .unwrap();
+    assert_eq!(protocol, Protocol::Http2);
+}
+
+#[test]
+fn port80_h2_prior_knowledge_split() {
+    let mut inspector = ProtocolInspector::default();
+    let config = ProtocolInspectionConfig::default();
+
+    assert!(inspector
+        .check_client_initial_data(&config, 80, &H2_PRIOR_KNOWLEDGE_DATA[..20])
+        .is_err());
+    let protocol = inspector
+        .check_client_initial_data(&config, 80, H2_PRIOR_KNOWLEDGE_DATA)
+        .unwrap();
+    assert_eq!(protocol, Protocol::Http2);
+}
+
+#[test]
+fn port80_invalid_h2_preface() {
+    let mut inspector = ProtocolInspector::default();
+    let config = ProtocolInspectionConfig::default();
+
+    let mut data = b"PRI * HTTP/1.1\r\n\r\nSM\r\n\r\n".to_vec();
+    data.resize(80, 0);
+
+    let protocol = inspector
+        .check_client_initial_data(&config, 80, &data)
+        .unwrap();
+    assert_eq!(protocol, Protocol::Unknown);
+}
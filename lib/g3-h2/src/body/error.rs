@@ -34,4 +34,6 @@ pub enum H2StreamBodyTransferError {
     SendTrailersFailed(h2::Error),
     #[error("error while set graceful end of stream: {0}")]
     GracefulCloseError(h2::Error),
+    #[error("body size limit {0} exceeded")]
+    SizeLimitExceeded(u64),
 }
@@ -23,7 +23,10 @@ use h2::{FlowControl, RecvStream, SendStream};
 
 use super::H2StreamBodyTransferError;
 
-pub struct H2BodyTransfer {
+/// The observer that will be called with the body data before it's sent
+pub type H2BodyDataObserver<'a> = &'a mut (dyn FnMut(&[u8]) + Send);
+
+pub struct H2BodyTransfer<'a> {
     yield_size: usize,
     recv_stream: RecvStream,
     recv_flow_control: FlowControl,
@@ -31,9 +34,14 @@ pub struct H2BodyTransfer {
     send_chunk: Option<Bytes>,
     handle_trailers: bool,
     active: bool,
+    recv_size: u64,
+    max_size: u64,
+    send_partial: bool,
+    size_exceeded: bool,
+    data_observer: Option<H2BodyDataObserver<'a>>,
 }
 
-impl H2BodyTransfer {
+impl<'a> H2BodyTransfer<'a> {
     pub fn new(
         mut recv_stream: RecvStream,
         send_stream: SendStream<Bytes>,
@@ -48,9 +56,33 @@ impl H2BodyTransfer {
             send_chunk: None,
             handle_trailers: false,
             active: false,
+            recv_size: 0,
+            max_size: 0,
+            send_partial: false,
+            size_exceeded: false,
+            data_observer: None,
         }
     }
 
+    /// Set the observer that will be called with all the body data that will be sent
+    pub fn set_data_observer(&mut self, observer: H2BodyDataObserver<'a>) {
+        self.data_observer = Some(observer);
+    }
+
+    /// Set the max size of the body data, 0 means no limit.
+    ///
+    /// If exceeded, a SizeLimitExceeded error will be returned. The data up to the limit will be
+    /// sent before that if `send_partial` is set.
+    pub fn set_size_limit(&mut self, max_size: u64, send_partial: bool) {
+        self.max_size = max_size;
+        self.send_partial = send_partial;
+    }
+
+    #[inline]
+    pub fn recv_size(&self) -> u64 {
+        self.recv_size
+    }
+
     #[inline]
     pub fn is_idle(&self) -> bool {
         !self.active
@@ -132,15 +164,34 @@ impl H2BodyTransfer {
                         return Poll::Pending;
                     }
                 }
+            } else if self.size_exceeded {
+                return Poll::Ready(Err(H2StreamBodyTransferError::SizeLimitExceeded(
+                    self.max_size,
+                )));
             } else {
                 match ready!(self.recv_stream.poll_data(cx)) {
-                    Some(Ok(chunk)) => {
+                    Some(Ok(mut chunk)) => {
                         self.active = true;
+                        self.recv_size += chunk.len() as u64;
+                        if self.max_size > 0 && self.recv_size > self.max_size {
+                            self.size_exceeded = true;
+                            if !self.send_partial {
+                                continue;
+                            }
+                            let exceeded = (self.recv_size - self.max_size) as usize;
+                            chunk.truncate(chunk.len() - exceeded);
+                        }
                         if chunk.has_remaining() {
+                            if let Some(observer) = &mut self.data_observer {
+                                observer(&chunk);
+                            }
                             self.send_stream.reserve_capacity(chunk.len());
                             self.send_chunk = Some(chunk);
                             continue;
                         }
+                        if self.size_exceeded {
+                            continue;
+                        }
                     }
                     Some(Err(e)) => {
                         return Poll::Ready(Err(H2StreamBodyTransferError::RecvDataFailed(e)));
@@ -162,7 +213,7 @@ impl H2BodyTransfer {
     }
 }
 
-impl Future for H2BodyTransfer {
+impl Future for H2BodyTransfer<'_> {
     type Output = Result<(), H2StreamBodyTransferError>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
@@ -0,0 +1,400 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::{self, Write};
+
+use flate2::write::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use thiserror::Error;
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+/// the ratio limit will only be checked if the decoded size is larger than this value
+const RATIO_CHECK_MIN_SIZE: u64 = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HttpContentCoding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+impl HttpContentCoding {
+    /// Get the coding from the value of the Content-Encoding header.
+    ///
+    /// None will be returned for `identity`, unknown codings and multiple codings.
+    pub fn from_header_value(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Some(HttpContentCoding::Gzip)
+        } else if value.eq_ignore_ascii_case("deflate") {
+            Some(HttpContentCoding::Deflate)
+        } else if value.eq_ignore_ascii_case("br") {
+            Some(HttpContentCoding::Brotli)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpContentCoding::Gzip => "gzip",
+            HttpContentCoding::Deflate => "deflate",
+            HttpContentCoding::Brotli => "br",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum HttpContentDecodeError {
+    #[error("invalid {0} encoded data: {1:?}")]
+    InvalidData(&'static str, io::Error),
+    #[error("decoded size exceeds the limit {0}")]
+    SizeLimitExceeded(u64),
+    #[error("decompression ratio exceeds the limit {0}")]
+    RatioLimitExceeded(u32),
+}
+
+#[derive(Clone, Copy, Debug, Error)]
+enum LimitExceeded {
+    #[error("size limit exceeded")]
+    Size,
+    #[error("ratio limit exceeded")]
+    Ratio,
+}
+
+struct LimitedSink {
+    buf: Vec<u8>,
+    decoded: u64,
+    encoded: u64,
+    max_size: u64,
+    max_ratio: u32,
+}
+
+impl LimitedSink {
+    fn check(&self, decoded: u64) -> Result<(), LimitExceeded> {
+        if self.max_size > 0 && decoded > self.max_size {
+            return Err(LimitExceeded::Size);
+        }
+        if self.max_ratio > 0
+            && decoded > RATIO_CHECK_MIN_SIZE
+            && decoded / self.max_ratio as u64 > self.encoded
+        {
+            return Err(LimitExceeded::Ratio);
+        }
+        Ok(())
+    }
+}
+
+impl Write for LimitedSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let decoded = self.decoded + buf.len() as u64;
+        self.check(decoded).map_err(io::Error::other)?;
+        self.decoded = decoded;
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum DecoderInner {
+    Gzip(MultiGzDecoder<LimitedSink>),
+    /// waiting for the first 2 bytes to detect the zlib header
+    DeflatePending(LimitedSink, Vec<u8>),
+    Zlib(ZlibDecoder<LimitedSink>),
+    RawDeflate(DeflateDecoder<LimitedSink>),
+    Brotli(Box<brotli::DecompressorWriter<LimitedSink>>),
+}
+
+impl DecoderInner {
+    fn sink_mut(&mut self) -> &mut LimitedSink {
+        match self {
+            DecoderInner::Gzip(d) => d.get_mut(),
+            DecoderInner::DeflatePending(s, _) => s,
+            DecoderInner::Zlib(d) => d.get_mut(),
+            DecoderInner::RawDeflate(d) => d.get_mut(),
+            DecoderInner::Brotli(d) => d.get_mut(),
+        }
+    }
+}
+
+/// A streaming decoder for HTTP content codings, with limits on the decoded size
+/// and the decompression ratio to defend against decompression bombs.
+pub struct HttpContentDecoder {
+    coding: HttpContentCoding,
+    inner: Option<DecoderInner>,
+}
+
+impl HttpContentDecoder {
+    /// Create a new decoder, a zero value for `max_size` or `max_ratio` means no limit.
+    pub fn new(coding: HttpContentCoding, max_size: u64, max_ratio: u32) -> Self {
+        let sink = LimitedSink {
+            buf: Vec::new(),
+            decoded: 0,
+            encoded: 0,
+            max_size,
+            max_ratio,
+        };
+        let inner = match coding {
+            HttpContentCoding::Gzip => DecoderInner::Gzip(MultiGzDecoder::new(sink)),
+            HttpContentCoding::Deflate => DecoderInner::DeflatePending(sink, Vec::with_capacity(2)),
+            HttpContentCoding::Brotli => DecoderInner::Brotli(Box::new(
+                brotli::DecompressorWriter::new(sink, BROTLI_BUFFER_SIZE),
+            )),
+        };
+        HttpContentDecoder {
+            coding,
+            inner: Some(inner),
+        }
+    }
+
+    #[inline]
+    pub fn coding(&self) -> HttpContentCoding {
+        self.coding
+    }
+
+    /// Get the total size of the encoded data fed so far
+    pub fn encoded_size(&self) -> u64 {
+        self.inner_sink_ref().map(|s| s.encoded).unwrap_or(0)
+    }
+
+    /// Get the total size of the decoded data so far
+    pub fn decoded_size(&self) -> u64 {
+        self.inner_sink_ref().map(|s| s.decoded).unwrap_or(0)
+    }
+
+    fn map_err(&self, e: io::Error) -> HttpContentDecodeError {
+        if let Some(limit) = e.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()) {
+            return match limit {
+                LimitExceeded::Size => HttpContentDecodeError::SizeLimitExceeded(
+                    self.inner_sink_ref()
+                        .map(|s| s.max_size)
+                        .unwrap_or_default(),
+                ),
+                LimitExceeded::Ratio => HttpContentDecodeError::RatioLimitExceeded(
+                    self.inner_sink_ref()
+                        .map(|s| s.max_ratio)
+                        .unwrap_or_default(),
+                ),
+            };
+        }
+        HttpContentDecodeError::InvalidData(self.coding.as_str(), e)
+    }
+
+    fn inner_sink_ref(&self) -> Option<&LimitedSink> {
+        self.inner.as_ref().map(|inner| match inner {
+            DecoderInner::Gzip(d) => d.get_ref(),
+            DecoderInner::DeflatePending(s, _) => s,
+            DecoderInner::Zlib(d) => d.get_ref(),
+            DecoderInner::RawDeflate(d) => d.get_ref(),
+            DecoderInner::Brotli(d) => d.get_ref(),
+        })
+    }
+
+    /// Decode the next chunk of encoded data, and return the decoded data in this call
+    pub fn decode(&mut self, data: &[u8]) -> Result<&[u8], HttpContentDecodeError> {
+        let Some(mut inner) = self.inner.take() else {
+            return Ok(&[]);
+        };
+        {
+            let sink = inner.sink_mut();
+            sink.buf.clear();
+            sink.encoded += data.len() as u64;
+        }
+
+        let r = match &mut inner {
+            DecoderInner::Gzip(d) => d.write_all(data),
+            DecoderInner::DeflatePending(_, pending) => {
+                pending.extend_from_slice(data);
+                if pending.len() < 2 {
+                    Ok(())
+                } else {
+                    let DecoderInner::DeflatePending(sink, pending) = inner else {
+                        unreachable!()
+                    };
+                    // RFC 9110 says deflate is zlib format, but some servers send raw deflate data
+                    let zlib_header = pending[0] & 0x0F == 0x08
+                        && (pending[0] >> 4) <= 7
+                        && u16::from_be_bytes([pending[0], pending[1]]) % 31 == 0;
+                    let (new_inner, r) = if zlib_header {
+                        let mut d = ZlibDecoder::new(sink);
+                        let r = d.write_all(&pending);
+                        (DecoderInner::Zlib(d), r)
+                    } else {
+                        let mut d = DeflateDecoder::new(sink);
+                        let r = d.write_all(&pending);
+                        (DecoderInner::RawDeflate(d), r)
+                    };
+                    inner = new_inner;
+                    r
+                }
+            }
+            DecoderInner::Zlib(d) => d.write_all(data),
+            DecoderInner::RawDeflate(d) => d.write_all(data),
+            DecoderInner::Brotli(d) => d.write_all(data),
+        };
+        self.inner = Some(inner);
+        if let Err(e) = r {
+            let e = self.map_err(e);
+            // no more data will be decoded after error
+            self.inner = None;
+            return Err(e);
+        }
+        Ok(self.inner_buf())
+    }
+
+    /// Finish the decoding after all encoded data has been fed,
+    /// and return the remaining decoded data
+    pub fn finish(&mut self) -> Result<&[u8], HttpContentDecodeError> {
+        let Some(mut inner) = self.inner.take() else {
+            return Ok(&[]);
+        };
+        inner.sink_mut().buf.clear();
+
+        let r = match &mut inner {
+            DecoderInner::Gzip(d) => d.try_finish(),
+            DecoderInner::DeflatePending(_, pending) => {
+                if pending.is_empty() {
+                    Ok(())
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "incomplete deflate data",
+                    ))
+                }
+            }
+            DecoderInner::Zlib(d) => d.try_finish(),
+            DecoderInner::RawDeflate(d) => d.try_finish(),
+            DecoderInner::Brotli(d) => d.close(),
+        };
+        self.inner = Some(inner);
+        if let Err(e) = r {
+            let e = self.map_err(e);
+            self.inner = None;
+            return Err(e);
+        }
+        Ok(self.inner_buf())
+    }
+
+    fn inner_buf(&self) -> &[u8] {
+        self.inner_sink_ref()
+            .map(|s| s.buf.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    fn decode_all(decoder: &mut HttpContentDecoder, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for chunk in data.chunks(7) {
+            out.extend_from_slice(decoder.decode(chunk).unwrap());
+        }
+        out.extend_from_slice(decoder.finish().unwrap());
+        out
+    }
+
+    #[test]
+    fn coding() {
+        assert_eq!(
+            HttpContentCoding::from_header_value(" GZip "),
+            Some(HttpContentCoding::Gzip)
+        );
+        assert_eq!(
+            HttpContentCoding::from_header_value("br"),
+            Some(HttpContentCoding::Brotli)
+        );
+        assert_eq!(HttpContentCoding::from_header_value("identity"), None);
+        assert_eq!(HttpContentCoding::from_header_value("gzip, br"), None);
+        assert_eq!(HttpContentCoding::from_header_value("zstd"), None);
+    }
+
+    #[test]
+    fn gzip() {
+        let body = b"hello world, hello world, hello world";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        let data = encoder.finish().unwrap();
+
+        let mut decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 0, 0);
+        assert_eq!(decode_all(&mut decoder, &data), body);
+        assert_eq!(decoder.encoded_size(), data.len() as u64);
+        assert_eq!(decoder.decoded_size(), body.len() as u64);
+    }
+
+    #[test]
+    fn deflate() {
+        let body = b"hello world, hello world, hello world";
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        let data = encoder.finish().unwrap();
+        let mut decoder = HttpContentDecoder::new(HttpContentCoding::Deflate, 0, 0);
+        assert_eq!(decode_all(&mut decoder, &data), body);
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        let data = encoder.finish().unwrap();
+        let mut decoder = HttpContentDecoder::new(HttpContentCoding::Deflate, 0, 0);
+        assert_eq!(decode_all(&mut decoder, &data), body);
+    }
+
+    #[test]
+    fn brotli() {
+        let body = b"hello world, hello world, hello world";
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(body).unwrap();
+        let data = encoder.into_inner();
+
+        let mut decoder = HttpContentDecoder::new(HttpContentCoding::Brotli, 0, 0);
+        assert_eq!(decode_all(&mut decoder, &data), body);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 0, 0);
+        let e = decoder.decode(b"not gzip data").unwrap_err();
+        assert!(matches!(e, HttpContentDecodeError::InvalidData("gzip", _)));
+        assert!(decoder.decode(b"more").unwrap().is_empty());
+    }
+
+    #[test]
+    fn bomb() {
+        let body = vec![0u8; 1024 * 1024];
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&body).unwrap();
+        let data = encoder.finish().unwrap();
+
+        let mut decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 256 * 1024, 0);
+        let e = decoder.decode(&data).unwrap_err();
+        assert!(matches!(
+            e,
+            HttpContentDecodeError::SizeLimitExceeded(262144)
+        ));
+
+        let mut decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 0, 100);
+        let e = decoder.decode(&data).unwrap_err();
+        assert!(matches!(e, HttpContentDecodeError::RatioLimitExceeded(100)));
+
+        let mut decoder = HttpContentDecoder::new(HttpContentCoding::Gzip, 0, 2000);
+        assert_eq!(decode_all(&mut decoder, &data).len(), body.len());
+    }
+}
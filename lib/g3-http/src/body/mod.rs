@@ -41,3 +41,12 @@ pub use chunked_decoder::ChunkedDataDecodeReader;
 
 mod trailer_reader;
 pub use trailer_reader::{TrailerReadError, TrailerReader};
+
+mod content_decoder;
+pub use content_decoder::{HttpContentCoding, HttpContentDecodeError, HttpContentDecoder};
+
+mod multipart;
+pub use multipart::{
+    multipart_boundary, HttpMultipartEvent, HttpMultipartParseError, HttpMultipartParser,
+    HttpMultipartPart,
+};
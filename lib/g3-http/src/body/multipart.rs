@@ -0,0 +1,495 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::str::FromStr;
+
+use memchr::memmem;
+use mime::Mime;
+use thiserror::Error;
+
+const MULTIPART_BOUNDARY_MAX_LEN: usize = 70;
+const MULTIPART_MAX_NESTED_DEPTH: usize = 4;
+const DELIMITER_LINE_MAX_PADDING: usize = 256;
+
+#[derive(Debug, Error)]
+pub enum HttpMultipartParseError {
+    #[error("invalid delimiter line")]
+    InvalidDelimiter,
+    #[error("part header too large")]
+    PartHeaderTooLarge,
+    #[error("invalid part header")]
+    InvalidPartHeader,
+    #[error("nested multipart too deep")]
+    NestedTooDeep,
+    #[error("unexpected end of body")]
+    UnexpectedEnd,
+}
+
+/// Get the boundary from the value of the Content-Type header if it's a multipart media type
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    let mime = Mime::from_str(content_type).ok()?;
+    if mime.type_() != mime::MULTIPART {
+        return None;
+    }
+    let boundary = mime.get_param(mime::BOUNDARY)?.as_str();
+    if boundary.is_empty() || boundary.len() > MULTIPART_BOUNDARY_MAX_LEN {
+        return None;
+    }
+    Some(boundary.to_string())
+}
+
+/// The metadata of a part in the multipart body
+#[derive(Debug, Default)]
+pub struct HttpMultipartPart {
+    /// the nested depth, 0 for top level parts
+    pub depth: usize,
+    pub name: Option<String>,
+    pub filename: Option<String>,
+    /// the media type without parameters, in lowercase
+    pub content_type: Option<String>,
+    /// the size of the part body received so far, the nested parts are not included
+    pub size: u64,
+}
+
+impl HttpMultipartPart {
+    fn parse_head(&mut self, head: &[u8]) -> Result<Option<String>, HttpMultipartParseError> {
+        let head = String::from_utf8_lossy(head);
+        let mut nested_boundary = None;
+        for line in head.split("\r\n") {
+            if line.starts_with([' ', '\t']) {
+                // obsolete line folding, not used by the headers we care about
+                continue;
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(HttpMultipartParseError::InvalidPartHeader);
+            };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-disposition") {
+                self.parse_content_disposition(value);
+            } else if name.eq_ignore_ascii_case("content-type") {
+                let media_type = value.split(';').next().unwrap_or_default().trim();
+                self.content_type = Some(media_type.to_ascii_lowercase());
+                nested_boundary = multipart_boundary(value);
+            }
+        }
+        Ok(nested_boundary)
+    }
+
+    fn parse_content_disposition(&mut self, value: &str) {
+        let Some((_, params)) = value.split_once(';') else {
+            return;
+        };
+        let mut ext_filename = None;
+        for (key, value) in parse_params(params) {
+            match key.as_str() {
+                "name" => self.name = Some(value),
+                "filename" => self.filename = Some(value),
+                "filename*" => ext_filename = decode_ext_value(&value),
+                _ => {}
+            }
+        }
+        if ext_filename.is_some() {
+            self.filename = ext_filename;
+        }
+    }
+}
+
+fn parse_params(s: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = s;
+    loop {
+        rest = rest.trim_start_matches([';', ' ', '\t']);
+        let Some(p) = rest.find(['=', ';']) else {
+            break;
+        };
+        if rest.as_bytes()[p] == b';' {
+            // no value for this param
+            rest = &rest[p + 1..];
+            continue;
+        }
+        let key = rest[..p].trim().to_ascii_lowercase();
+        let value_s = rest[p + 1..].trim_start();
+        let value = if let Some(quoted) = value_s.strip_prefix('"') {
+            let mut value = String::with_capacity(quoted.len());
+            let mut escaped = false;
+            let mut end = quoted.len();
+            for (i, c) in quoted.char_indices() {
+                if escaped {
+                    value.push(c);
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    end = i + 1;
+                    break;
+                } else {
+                    value.push(c);
+                }
+            }
+            rest = &quoted[end..];
+            value
+        } else {
+            let (value, left) = value_s.split_once(';').unwrap_or((value_s, ""));
+            rest = left;
+            value.trim().to_string()
+        };
+        params.push((key, value));
+    }
+    params
+}
+
+/// Decode the RFC 8187 extended parameter value
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut iter = value.splitn(3, '\'');
+    let charset = iter.next()?;
+    let _language = iter.next()?;
+    let encoded = iter.next()?.as_bytes();
+
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let b = encoded[i];
+        if b == b'%' && i + 2 < encoded.len() {
+            let hex = std::str::from_utf8(&encoded[i + 1..i + 3]).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(b);
+            i += 1;
+        }
+    }
+    if charset.eq_ignore_ascii_case("utf-8") {
+        Some(String::from_utf8_lossy(&decoded).into_owned())
+    } else {
+        // ISO-8859-1
+        Some(decoded.into_iter().map(char::from).collect())
+    }
+}
+
+pub enum HttpMultipartEvent<'a> {
+    /// the header of a new part has been received
+    PartStart(&'a HttpMultipartPart),
+    /// more body data of the part has been received
+    PartData(&'a HttpMultipartPart),
+    /// all body data of the part has been received
+    PartEnd(&'a HttpMultipartPart),
+}
+
+enum ParseState {
+    Preamble,
+    Delimiter,
+    PartHead,
+    PartBody,
+    End,
+}
+
+/// Streaming parser of the multipart body.
+///
+/// Only the part headers and the trailing bytes that may be the start of a delimiter
+/// will be buffered, so large parts can be parsed without buffering the whole body.
+pub struct HttpMultipartParser {
+    delimiters: Vec<Vec<u8>>,
+    parts: Vec<HttpMultipartPart>,
+    state: ParseState,
+    buf: Vec<u8>,
+    part_head_max_size: usize,
+}
+
+fn delimiter(boundary: &str) -> Vec<u8> {
+    let mut delimiter = Vec::with_capacity(boundary.len() + 4);
+    delimiter.extend_from_slice(b"\r\n--");
+    delimiter.extend_from_slice(boundary.as_bytes());
+    delimiter
+}
+
+impl HttpMultipartParser {
+    pub fn new(boundary: &str, part_head_max_size: usize) -> Self {
+        HttpMultipartParser {
+            delimiters: vec![delimiter(boundary)],
+            parts: Vec::new(),
+            state: ParseState::Preamble,
+            // the first delimiter may be at the very beginning, without the leading CRLF
+            buf: b"\r\n".to_vec(),
+            part_head_max_size,
+        }
+    }
+
+    /// Check if the close delimiter of the top level multipart body has been received
+    pub fn finished(&self) -> bool {
+        matches!(self.state, ParseState::End)
+    }
+
+    /// Check if the whole body has been parsed, should be called after all data has been fed
+    pub fn finish(&self) -> Result<(), HttpMultipartParseError> {
+        if self.finished() {
+            Ok(())
+        } else {
+            Err(HttpMultipartParseError::UnexpectedEnd)
+        }
+    }
+
+    /// Feed the body data to the parser, the events will be passed to `on_event`.
+    /// The parse will be stopped if `on_event` returns error.
+    pub fn feed<F, E>(&mut self, data: &[u8], mut on_event: F) -> Result<(), E>
+    where
+        F: FnMut(HttpMultipartEvent<'_>) -> Result<(), E>,
+        E: From<HttpMultipartParseError>,
+    {
+        if self.finished() {
+            return Ok(());
+        }
+        self.buf.extend_from_slice(data);
+        let mut offset = 0;
+        let r = self.parse(&mut offset, &mut on_event);
+        self.buf.drain(..offset);
+        r
+    }
+
+    fn parse<F, E>(&mut self, offset: &mut usize, on_event: &mut F) -> Result<(), E>
+    where
+        F: FnMut(HttpMultipartEvent<'_>) -> Result<(), E>,
+        E: From<HttpMultipartParseError>,
+    {
+        loop {
+            let left = &self.buf[*offset..];
+            match self.state {
+                ParseState::Preamble | ParseState::PartBody => {
+                    let delimiter = self.delimiters.last().unwrap();
+                    let (data_len, found) = match memmem::find(left, delimiter) {
+                        Some(p) => (p, true),
+                        None => (left.len().saturating_sub(delimiter.len() - 1), false),
+                    };
+                    *offset += data_len;
+                    if matches!(self.state, ParseState::PartBody) {
+                        // the part of nested multipart body will also be checked here
+                        if let Some(part) = self.parts.last_mut() {
+                            if data_len > 0 {
+                                part.size += data_len as u64;
+                                on_event(HttpMultipartEvent::PartData(part))?;
+                            }
+                        }
+                        if found {
+                            if let Some(part) = self.parts.pop() {
+                                on_event(HttpMultipartEvent::PartEnd(&part))?;
+                            }
+                        }
+                    }
+                    if !found {
+                        return Ok(());
+                    }
+                    *offset += delimiter.len();
+                    self.state = ParseState::Delimiter;
+                }
+                ParseState::Delimiter => {
+                    if left.len() < 2 {
+                        return Ok(());
+                    }
+                    if left.starts_with(b"--") {
+                        *offset += 2;
+                        if self.delimiters.len() > 1 {
+                            // end of the nested multipart body, continue with the outer part
+                            self.delimiters.pop();
+                            self.state = ParseState::PartBody;
+                        } else {
+                            // skip the epilogue
+                            *offset = self.buf.len();
+                            self.state = ParseState::End;
+                            return Ok(());
+                        }
+                    } else {
+                        let Some(p) = memmem::find(left, b"\r\n") else {
+                            if left.len() > DELIMITER_LINE_MAX_PADDING {
+                                return Err(HttpMultipartParseError::InvalidDelimiter.into());
+                            }
+                            return Ok(());
+                        };
+                        if !left[..p].iter().all(|c| matches!(c, b' ' | b'\t')) {
+                            return Err(HttpMultipartParseError::InvalidDelimiter.into());
+                        }
+                        *offset += p + 2;
+                        self.state = ParseState::PartHead;
+                    }
+                }
+                ParseState::PartHead => {
+                    let mut part = HttpMultipartPart {
+                        depth: self.delimiters.len() - 1,
+                        ..Default::default()
+                    };
+                    let nested_boundary = if left.starts_with(b"\r\n") {
+                        *offset += 2;
+                        None
+                    } else {
+                        let Some(p) = memmem::find(left, b"\r\n\r\n") else {
+                            if left.len() > self.part_head_max_size {
+                                return Err(HttpMultipartParseError::PartHeaderTooLarge.into());
+                            }
+                            return Ok(());
+                        };
+                        if p > self.part_head_max_size {
+                            return Err(HttpMultipartParseError::PartHeaderTooLarge.into());
+                        }
+                        let nested_boundary = part.parse_head(&left[..p])?;
+                        if nested_boundary.is_some() {
+                            // keep the CRLF, as the first delimiter of the nested body may follow
+                            *offset += p + 2;
+                        } else {
+                            *offset += p + 4;
+                        }
+                        nested_boundary
+                    };
+                    self.parts.push(part);
+                    on_event(HttpMultipartEvent::PartStart(self.parts.last().unwrap()))?;
+                    match nested_boundary {
+                        Some(boundary) => {
+                            if self.delimiters.len() > MULTIPART_MAX_NESTED_DEPTH {
+                                return Err(HttpMultipartParseError::NestedTooDeep.into());
+                            }
+                            self.delimiters.push(delimiter(&boundary));
+                            self.state = ParseState::Preamble;
+                        }
+                        None => self.state = ParseState::PartBody,
+                    }
+                }
+                ParseState::End => {
+                    *offset = self.buf.len();
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORM_BODY: &[u8] = b"--AaB03x\r\n\
+        Content-Disposition: form-data; name=\"submit-name\"\r\n\
+        \r\n\
+        Larry\r\n\
+        --AaB03x\r\n\
+        Content-Disposition: form-data; name=\"files\"\r\n\
+        Content-Type: multipart/mixed; boundary=BbC04y\r\n\
+        \r\n\
+        --BbC04y\r\n\
+        Content-Disposition: file; filename=\"file1.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        ... contents of file1.txt ...\r\n\
+        --BbC04y\r\n\
+        Content-Disposition: file; filename*=UTF-8''file%202.gif\r\n\
+        Content-Type: image/gif\r\n\
+        Content-Transfer-Encoding: binary\r\n\
+        \r\n\
+        GIF89a\r\n--BbC\r\n\
+        --BbC04y--\r\n\
+        --AaB03x--\r\n\
+        epilogue";
+
+    #[derive(Debug, PartialEq)]
+    enum Record {
+        Start(usize, Option<String>, Option<String>, Option<String>),
+        End(usize, u64),
+    }
+
+    fn parse_split(body: &[u8], split: usize) -> Result<Vec<Record>, HttpMultipartParseError> {
+        let mut parser = HttpMultipartParser::new("AaB03x", 1024);
+        let mut records = Vec::new();
+        let mut on_event = |event: HttpMultipartEvent<'_>| {
+            match event {
+                HttpMultipartEvent::PartStart(p) => records.push(Record::Start(
+                    p.depth,
+                    p.name.clone(),
+                    p.filename.clone(),
+                    p.content_type.clone(),
+                )),
+                HttpMultipartEvent::PartData(_) => {}
+                HttpMultipartEvent::PartEnd(p) => records.push(Record::End(p.depth, p.size)),
+            }
+            Ok::<(), HttpMultipartParseError>(())
+        };
+        for chunk in body.chunks(split) {
+            parser.feed(chunk, &mut on_event)?;
+        }
+        parser.finish()?;
+        Ok(records)
+    }
+
+    #[test]
+    fn boundary() {
+        assert_eq!(
+            multipart_boundary("multipart/form-data; boundary=\"AaB03x\"").unwrap(),
+            "AaB03x"
+        );
+        assert!(multipart_boundary("multipart/form-data").is_none());
+        assert!(multipart_boundary("text/plain; boundary=AaB03x").is_none());
+    }
+
+    #[test]
+    fn parse_nested() {
+        let expected = vec![
+            Record::Start(0, Some("submit-name".to_string()), None, None),
+            Record::End(0, 5),
+            Record::Start(
+                0,
+                Some("files".to_string()),
+                None,
+                Some("multipart/mixed".to_string()),
+            ),
+            Record::Start(
+                1,
+                None,
+                Some("file1.txt".to_string()),
+                Some("text/plain".to_string()),
+            ),
+            Record::End(1, 29),
+            Record::Start(
+                1,
+                None,
+                Some("file 2.gif".to_string()),
+                Some("image/gif".to_string()),
+            ),
+            Record::End(1, 13),
+            Record::End(0, 0),
+        ];
+        for split in 1..=FORM_BODY.len() {
+            let records = parse_split(FORM_BODY, split).unwrap();
+            assert_eq!(records, expected, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        let body = b"--AaB03x\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue";
+        assert!(matches!(
+            parse_split(body, 8),
+            Err(HttpMultipartParseError::UnexpectedEnd)
+        ));
+
+        let body = b"--AaB03x\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue\r\n--AaB03xyz\r\n";
+        assert!(matches!(
+            parse_split(body, 8),
+            Err(HttpMultipartParseError::InvalidDelimiter)
+        ));
+
+        let mut body = b"--AaB03x\r\nX-Long: ".to_vec();
+        body.resize(body.len() + 2048, b'a');
+        assert!(matches!(
+            parse_split(&body, 8),
+            Err(HttpMultipartParseError::PartHeaderTooLarge)
+        ));
+    }
+}
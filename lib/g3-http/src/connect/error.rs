@@ -46,6 +46,8 @@ pub enum HttpConnectError {
     InvalidResponse(#[from] HttpConnectResponseError),
     #[error("unexpected status code {0} {1}")]
     UnexpectedStatusCode(u16, String),
+    #[error("proxy authentication required: {0}")]
+    ProxyAuthRequired(String),
     #[error("peer timeout with status code {0}")]
     PeerTimeout(u16),
 }
@@ -188,6 +188,9 @@ impl HttpConnectResponse {
         } else if self.code == 504 || self.code == 522 || self.code == 524 {
             // Peer tells us it timeout
             Err(HttpConnectError::PeerTimeout(self.code))
+        } else if self.code == 407 {
+            // no or wrong Proxy-Authorization header sent
+            Err(HttpConnectError::ProxyAuthRequired(self.reason.to_string()))
         } else {
             Err(HttpConnectError::UnexpectedStatusCode(
                 self.code,
@@ -216,3 +219,48 @@ impl HttpConnectResponse {
         Ok(rsp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use tokio::io::{BufReader, Result};
+    use tokio_util::io::StreamReader;
+
+    #[tokio::test]
+    async fn squid_responses() {
+        let content = b"HTTP/1.1 200 Connection established\r\n\r\n";
+        let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
+        let stream = StreamReader::new(stream);
+        let mut buf_stream = BufReader::new(stream);
+        let rsp = HttpConnectResponse::recv(&mut buf_stream, 4096)
+            .await
+            .unwrap();
+        assert_eq!(rsp.code, 200);
+
+        let content = b"HTTP/1.1 407 Proxy Authentication Required\r\n\
+            Server: squid\r\n\
+            Proxy-Authenticate: Basic realm=\"proxy\"\r\n\
+            Content-Type: text/html\r\n\
+            Content-Length: 4\r\n\r\n\
+            test";
+        let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
+        let stream = StreamReader::new(stream);
+        let mut buf_stream = BufReader::new(stream);
+        let Err(e) = HttpConnectResponse::recv(&mut buf_stream, 4096).await else {
+            panic!("the response should be an error");
+        };
+        assert!(matches!(e, HttpConnectError::ProxyAuthRequired(_)));
+
+        let content = b"HTTP/1.1 403 Forbidden\r\n\
+            Server: squid\r\n\
+            Content-Length: 0\r\n\r\n";
+        let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
+        let stream = StreamReader::new(stream);
+        let mut buf_stream = BufReader::new(stream);
+        let Err(e) = HttpConnectResponse::recv(&mut buf_stream, 4096).await else {
+            panic!("the response should be an error");
+        };
+        assert!(matches!(e, HttpConnectError::UnexpectedStatusCode(403, _)));
+    }
+}
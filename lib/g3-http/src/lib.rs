@@ -21,9 +21,11 @@ pub use parse::{
 
 mod body;
 pub use body::{
-    ChunkedDataDecodeReader, H1BodyToChunkedTransfer, HttpBodyDecodeReader, HttpBodyReader,
-    HttpBodyType, PreviewData, PreviewDataState, PreviewError, StreamToChunkedTransfer,
-    TrailerReadError, TrailerReader,
+    multipart_boundary, ChunkedDataDecodeReader, H1BodyToChunkedTransfer, HttpBodyDecodeReader,
+    HttpBodyReader, HttpBodyType, HttpContentCoding, HttpContentDecodeError, HttpContentDecoder,
+    HttpMultipartEvent, HttpMultipartParseError, HttpMultipartParser, HttpMultipartPart,
+    PreviewData, PreviewDataState, PreviewError, StreamToChunkedTransfer, TrailerReadError,
+    TrailerReader,
 };
 
 pub mod client;
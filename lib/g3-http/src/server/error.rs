@@ -27,6 +27,8 @@ pub enum HttpRequestParseError {
     ClientClosed,
     #[error("too large header, should be less than {0}")]
     TooLargeHeader(usize),
+    #[error("too many header lines, should be no more than {0}")]
+    TooManyHeaders(usize),
     #[error("invalid method line: {0}")]
     InvalidMethodLine(HttpLineParseError),
     #[error("unsupported method: {0}")]
@@ -63,7 +65,7 @@ impl HttpRequestParseError {
     pub fn status_code(&self) -> Option<StatusCode> {
         match self {
             HttpRequestParseError::IoFailed(_) | HttpRequestParseError::ClientClosed => None,
-            HttpRequestParseError::TooLargeHeader(_) => {
+            HttpRequestParseError::TooLargeHeader(_) | HttpRequestParseError::TooManyHeaders(_) => {
                 Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
             }
             HttpRequestParseError::UpgradeIsNotSupported
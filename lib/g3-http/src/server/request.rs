@@ -134,20 +134,26 @@ impl HttpProxyClientRequest {
     pub async fn parse_basic<R>(
         reader: &mut R,
         max_header_size: usize,
+        max_header_count: usize,
         version: &mut Version,
     ) -> Result<Self, HttpRequestParseError>
     where
         R: AsyncBufRead + Unpin,
     {
-        Self::parse(reader, max_header_size, version, |req, name, value| {
-            req.append_header(name, value)
-        })
+        Self::parse(
+            reader,
+            max_header_size,
+            max_header_count,
+            version,
+            |req, name, value| req.append_header(name, value),
+        )
         .await
     }
 
     pub async fn parse<R, F>(
         reader: &mut R,
         max_header_size: usize,
+        max_header_count: usize,
         version: &mut Version,
         parse_more_header: F,
     ) -> Result<Self, HttpRequestParseError>
@@ -157,6 +163,7 @@ impl HttpProxyClientRequest {
     {
         let mut line_buf = Vec::<u8>::with_capacity(1024);
         let mut header_size: usize = 0;
+        let mut header_count: usize = 0;
 
         let (found, nr) = reader
             .limited_read_until(b'\n', max_header_size, &mut line_buf)
@@ -208,6 +215,10 @@ impl HttpProxyClientRequest {
                 break;
             }
 
+            header_count += 1;
+            if header_count > max_header_count {
+                return Err(HttpRequestParseError::TooManyHeaders(max_header_count));
+            }
             req.parse_header_line(line_buf.as_ref(), &parse_more_header)?;
         }
         req.origin_header_size = header_size;
@@ -499,17 +510,27 @@ mod tests {
         let stream = StreamReader::new(stream);
         let mut buf_stream = BufReader::new(stream);
         let mut version = Version::HTTP_11;
-        let request =
-            HttpProxyClientRequest::parse(&mut buf_stream, 4096, &mut version, parse_more_header)
-                .await
-                .unwrap();
+        let request = HttpProxyClientRequest::parse(
+            &mut buf_stream,
+            4096,
+            64,
+            &mut version,
+            parse_more_header,
+        )
+        .await
+        .unwrap();
         assert_eq!(request.method, &Method::GET);
         assert!(request.keep_alive());
         assert!(request.body_type().is_none());
 
-        let result =
-            HttpProxyClientRequest::parse(&mut buf_stream, 4096, &mut version, parse_more_header)
-                .await;
+        let result = HttpProxyClientRequest::parse(
+            &mut buf_stream,
+            4096,
+            64,
+            &mut version,
+            parse_more_header,
+        )
+        .await;
         assert!(result.is_err());
     }
 
@@ -524,10 +545,59 @@ mod tests {
         let stream = StreamReader::new(stream);
         let mut buf_stream = BufReader::new(stream);
         let mut version = Version::HTTP_11;
-        let request =
-            HttpProxyClientRequest::parse(&mut buf_stream, 4096, &mut version, parse_more_header)
-                .await
-                .unwrap();
+        let request = HttpProxyClientRequest::parse(
+            &mut buf_stream,
+            4096,
+            64,
+            &mut version,
+            parse_more_header,
+        )
+        .await
+        .unwrap();
         assert!(!request.keep_alive());
     }
+
+    #[tokio::test]
+    async fn too_many_headers() {
+        let content = b"GET http://example.com/ HTTP/1.1\r\n\
+            Host: example.com\r\n\
+            X-Test-1: a\r\n\
+            X-Test-2: b\r\n\
+            X-Test-2: c\r\n\r\n";
+        let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
+        let stream = StreamReader::new(stream);
+        let mut buf_stream = BufReader::new(stream);
+        let mut version = Version::HTTP_11;
+        let Err(e) = HttpProxyClientRequest::parse(
+            &mut buf_stream,
+            4096,
+            3,
+            &mut version,
+            parse_more_header,
+        )
+        .await
+        else {
+            panic!("the request should be an error");
+        };
+        assert!(matches!(e, HttpRequestParseError::TooManyHeaders(3)));
+        assert_eq!(
+            e.status_code(),
+            Some(http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+
+        let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
+        let stream = StreamReader::new(stream);
+        let mut buf_stream = BufReader::new(stream);
+        let request = HttpProxyClientRequest::parse(
+            &mut buf_stream,
+            4096,
+            4,
+            &mut version,
+            parse_more_header,
+        )
+        .await
+        .unwrap();
+        assert_eq!(request.end_to_end_headers.len(), 3);
+        assert_eq!(request.end_to_end_headers.values_len(), 4);
+    }
 }
@@ -48,6 +48,7 @@ pub struct HttpTransparentRequest {
     chunked_transfer: bool,
     has_transfer_encoding: bool,
     has_content_length: bool,
+    expect_continue: bool,
 }
 
 impl HttpTransparentRequest {
@@ -70,6 +71,7 @@ impl HttpTransparentRequest {
             chunked_transfer: false,
             has_transfer_encoding: false,
             has_content_length: false,
+            expect_continue: false,
         }
     }
 
@@ -92,6 +94,7 @@ impl HttpTransparentRequest {
             chunked_transfer: true,
             has_transfer_encoding: false,
             has_content_length: false,
+            expect_continue: self.expect_continue,
         }
     }
 
@@ -105,6 +108,13 @@ impl HttpTransparentRequest {
         self.keep_alive
     }
 
+    /// Whether the client will wait for a 100 (Continue) interim response before sending the body
+    pub fn expect_continue(&self) -> bool {
+        self.expect_continue
+            && !matches!(self.version, Version::HTTP_09 | Version::HTTP_10)
+            && self.body_type().is_some()
+    }
+
     pub fn body_type(&self) -> Option<HttpBodyType> {
         if self.chunked_transfer {
             Some(HttpBodyType::Chunked)
@@ -135,6 +145,7 @@ impl HttpTransparentRequest {
     pub async fn parse<R>(
         reader: &mut R,
         max_header_size: usize,
+        max_header_count: usize,
         steal_forwarded_for: bool,
     ) -> Result<(Self, Bytes), HttpRequestParseError>
     where
@@ -164,6 +175,7 @@ impl HttpTransparentRequest {
         }
         req.steal_forwarded_for = steal_forwarded_for;
 
+        let mut header_count: usize = 0;
         loop {
             let header_size = head_bytes.len();
             if header_size >= max_header_size {
@@ -191,6 +203,10 @@ impl HttpTransparentRequest {
                 // header end line
                 break;
             }
+            header_count += 1;
+            if header_count > max_header_count {
+                return Err(HttpRequestParseError::TooManyHeaders(max_header_count));
+            }
             req.parse_header_line(line_buf)?;
         }
 
@@ -393,7 +409,11 @@ impl HttpTransparentRequest {
                     return Ok(());
                 }
             }
-            // ignore "expect"
+            "expect" => {
+                if header.value.eq_ignore_ascii_case("100-continue") {
+                    self.expect_continue = true;
+                }
+            }
             _ => {}
         }
 
@@ -542,7 +562,7 @@ mod tests {
         let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
         let stream = StreamReader::new(stream);
         let mut buf_stream = BufReader::new(stream);
-        let (request, data) = HttpTransparentRequest::parse(&mut buf_stream, 4096, false)
+        let (request, data) = HttpTransparentRequest::parse(&mut buf_stream, 4096, 64, false)
             .await
             .unwrap();
         assert_eq!(data.as_ref(), content.as_slice());
@@ -550,7 +570,7 @@ mod tests {
         assert!(request.keep_alive());
         assert!(request.body_type().is_none());
 
-        let result = HttpTransparentRequest::parse(&mut buf_stream, 4096, false).await;
+        let result = HttpTransparentRequest::parse(&mut buf_stream, 4096, 64, false).await;
         assert!(result.is_err());
     }
 
@@ -564,7 +584,7 @@ mod tests {
         let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
         let stream = StreamReader::new(stream);
         let mut buf_stream = BufReader::new(stream);
-        let (request, data) = HttpTransparentRequest::parse(&mut buf_stream, 4096, false)
+        let (request, data) = HttpTransparentRequest::parse(&mut buf_stream, 4096, 64, false)
             .await
             .unwrap();
         assert_eq!(data.as_ref(), content.as_slice());
@@ -581,7 +601,7 @@ mod tests {
         let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
         let stream = StreamReader::new(stream);
         let mut buf_stream = BufReader::new(stream);
-        let (mut request, _) = HttpTransparentRequest::parse(&mut buf_stream, 4096, false)
+        let (mut request, _) = HttpTransparentRequest::parse(&mut buf_stream, 4096, 64, false)
             .await
             .unwrap();
         let left_tokens = request
@@ -591,4 +611,34 @@ mod tests {
         let token = request.hop_by_hop_headers.get(header::UPGRADE).unwrap();
         assert_eq!(token.to_str(), "HTTP/2.0");
     }
+
+    #[tokio::test]
+    async fn expect_continue() {
+        let content = b"PUT /upload HTTP/1.1\r\n\
+            Host: www.example.com\r\n\
+            Content-Length: 1024\r\n\
+            Expect: 100-Continue\r\n\
+            \r\n";
+        let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
+        let stream = StreamReader::new(stream);
+        let mut buf_stream = BufReader::new(stream);
+        let (request, _) = HttpTransparentRequest::parse(&mut buf_stream, 4096, 64, false)
+            .await
+            .unwrap();
+        assert!(request.expect_continue());
+        assert!(request.end_to_end_headers.contains_key(header::EXPECT));
+
+        let content = b"PUT /upload HTTP/1.0\r\n\
+            Host: www.example.com\r\n\
+            Content-Length: 1024\r\n\
+            Expect: 100-continue\r\n\
+            \r\n";
+        let stream = tokio_stream::iter(vec![Result::Ok(Bytes::from_static(content))]);
+        let stream = StreamReader::new(stream);
+        let mut buf_stream = BufReader::new(stream);
+        let (request, _) = HttpTransparentRequest::parse(&mut buf_stream, 4096, 64, false)
+            .await
+            .unwrap();
+        assert!(!request.expect_continue());
+    }
 }
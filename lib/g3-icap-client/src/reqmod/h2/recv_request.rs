@@ -125,6 +125,9 @@ impl<I: IdleCheck> H2RequestAdapter<I> {
                 H2StreamBodyTransferError::SenderNotInSendState => {
                     H2ReqmodAdaptationError::HttpUpstreamNotInSendState
                 }
+                H2StreamBodyTransferError::SizeLimitExceeded(_) => {
+                    H2ReqmodAdaptationError::InternalServerError("unexpected body size limit")
+                }
             }
         }
 
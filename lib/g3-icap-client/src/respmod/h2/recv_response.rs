@@ -131,6 +131,9 @@ impl<I: IdleCheck> H2ResponseAdapter<I> {
                 H2StreamBodyTransferError::SenderNotInSendState => {
                     H2RespmodAdaptationError::HttpClientNotInSendState
                 }
+                H2StreamBodyTransferError::SizeLimitExceeded(_) => {
+                    H2RespmodAdaptationError::InternalServerError("unexpected body size limit")
+                }
             }
         }
 
@@ -19,6 +19,8 @@ use std::sync::Arc;
 use anyhow::anyhow;
 use tokio::sync::oneshot;
 
+use g3_types::stats::ConnectionPoolStats;
+
 use super::{
     IcapClientConnection, IcapConnector, IcapServiceClientCommand, IcapServiceConfig,
     IcapServicePool,
@@ -30,14 +32,28 @@ pub struct IcapServiceClient {
     pub(crate) partial_request_header: Vec<u8>,
     cmd_sender: flume::Sender<IcapServiceClientCommand>,
     conn_creator: Arc<IcapConnector>,
+    pool_stats: Arc<ConnectionPoolStats>,
 }
 
 impl IcapServiceClient {
     pub fn new(config: Arc<IcapServiceConfig>) -> anyhow::Result<Self> {
+        IcapServiceClient::with_pool_stats(config, Arc::new(ConnectionPoolStats::default()))
+    }
+
+    /// Create a new client with the given connection pool stats, which may be kept across reload
+    pub fn with_pool_stats(
+        config: Arc<IcapServiceConfig>,
+        pool_stats: Arc<ConnectionPoolStats>,
+    ) -> anyhow::Result<Self> {
         let (cmd_sender, cmd_receiver) = flume::unbounded();
         let conn_creator = IcapConnector::new(config.clone())?;
         let conn_creator = Arc::new(conn_creator);
-        let pool = IcapServicePool::new(config.clone(), cmd_receiver, conn_creator.clone());
+        let pool = IcapServicePool::new(
+            config.clone(),
+            cmd_receiver,
+            conn_creator.clone(),
+            pool_stats.clone(),
+        );
         tokio::spawn(pool.into_running());
         let partial_request_header = config.build_request_header();
         Ok(IcapServiceClient {
@@ -45,9 +61,15 @@ impl IcapServiceClient {
             partial_request_header,
             cmd_sender,
             conn_creator,
+            pool_stats,
         })
     }
 
+    #[inline]
+    pub fn pool_stats(&self) -> &Arc<ConnectionPoolStats> {
+        &self.pool_stats
+    }
+
     async fn fetch_from_pool(&self) -> Option<(IcapClientConnection, Arc<IcapServiceOptions>)> {
         let (rsp_sender, rsp_receiver) = oneshot::channel();
         let cmd = IcapServiceClientCommand::FetchConnection(rsp_sender);
@@ -17,6 +17,7 @@
 use std::io;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
 use tokio::io::BufReader;
@@ -27,6 +28,7 @@ use tokio_rustls::TlsConnector;
 use g3_io_ext::rustls::{MaybeTlsStreamReadHalf, MaybeTlsStreamWriteHalf};
 use g3_io_ext::{AsyncStream, LimitedBufReadExt};
 use g3_types::net::{Host, RustlsClientConfig};
+use g3_types::stats::ConnectionPoolStats;
 
 use super::IcapServiceConfig;
 use crate::IcapServiceOptions;
@@ -129,14 +131,35 @@ impl IcapConnectionPollRequest {
 pub(super) struct IcapConnectionEofPoller {
     conn: IcapClientConnection,
     req_receiver: flume::Receiver<IcapConnectionPollRequest>,
+    idle_timeout: Duration,
+    pool_stats: Arc<ConnectionPoolStats>,
 }
 
 impl IcapConnectionEofPoller {
     pub(super) fn new(
         conn: IcapClientConnection,
         req_receiver: flume::Receiver<IcapConnectionPollRequest>,
+        idle_timeout: Duration,
+        pool_stats: Arc<ConnectionPoolStats>,
     ) -> Self {
-        IcapConnectionEofPoller { conn, req_receiver }
+        IcapConnectionEofPoller {
+            conn,
+            req_receiver,
+            idle_timeout,
+            pool_stats,
+        }
+    }
+
+    /// Check if the connection is still usable without waiting.
+    ///
+    /// The connection is dead if it's closed by the server, or if there are unexpected data.
+    async fn validate(&mut self) -> bool {
+        tokio::select! {
+            biased;
+
+            _ = self.conn.1.fill_wait_data() => false,
+            _ = std::future::ready(()) => true,
+        }
     }
 
     pub(super) async fn into_running(mut self) {
@@ -144,6 +167,11 @@ impl IcapConnectionEofPoller {
             _ = self.conn.1.fill_wait_eof() => {}
             r = self.req_receiver.recv_async() => {
                 if let Ok(req) = r {
+                    if !self.validate().await {
+                        // drop the request, so the client will fallback to use a new connection
+                        self.pool_stats.add_validate_failed();
+                        return;
+                    }
                     let IcapConnectionPollRequest {
                         client_sender,
                         options,
@@ -151,6 +179,9 @@ impl IcapConnectionEofPoller {
                     let _ = client_sender.send((self.conn, options));
                 }
             }
+            _ = tokio::time::sleep(self.idle_timeout), if !self.idle_timeout.is_zero() => {
+                self.pool_stats.add_reaped();
+            }
         }
     }
 }
@@ -20,6 +20,8 @@ use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 use tokio::time::Interval;
 
+use g3_types::stats::ConnectionPoolStats;
+
 use super::{
     IcapClientConnection, IcapConnectionEofPoller, IcapConnectionPollRequest, IcapConnector,
     IcapServiceConfig,
@@ -47,6 +49,7 @@ pub(super) struct IcapServicePool {
     conn_req_sender: flume::Sender<IcapConnectionPollRequest>,
     conn_req_receiver: flume::Receiver<IcapConnectionPollRequest>,
     idle_conn_count: Arc<AtomicUsize>,
+    stats: Arc<ConnectionPoolStats>,
 }
 
 impl IcapServicePool {
@@ -54,6 +57,7 @@ impl IcapServicePool {
         config: Arc<IcapServiceConfig>,
         client_cmd_receiver: flume::Receiver<IcapServiceClientCommand>,
         connector: Arc<IcapConnector>,
+        stats: Arc<ConnectionPoolStats>,
     ) -> Self {
         let options = Arc::new(IcapServiceOptions::new_expired(config.method));
         let check_interval = tokio::time::interval(config.connection_pool.check_interval());
@@ -72,6 +76,7 @@ impl IcapServicePool {
             conn_req_sender,
             conn_req_receiver,
             idle_conn_count: Arc::new(AtomicUsize::new(0)),
+            stats,
         }
     }
 
@@ -185,7 +190,12 @@ impl IcapServicePool {
             let idle_count = self.idle_conn_count.clone();
             // relaxed is fine as we only increase it here in the same future context
             idle_count.fetch_add(1, Ordering::Relaxed);
-            let eof_poller = IcapConnectionEofPoller::new(conn, self.conn_req_receiver.clone());
+            let eof_poller = IcapConnectionEofPoller::new(
+                conn,
+                self.conn_req_receiver.clone(),
+                self.config.connection_pool.idle_timeout(),
+                self.stats.clone(),
+            );
             tokio::spawn(async move {
                 eof_poller.into_running().await;
                 idle_count.fetch_sub(1, Ordering::Relaxed);
@@ -0,0 +1,114 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![feature(test)]
+
+extern crate test;
+use test::Bencher;
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use tokio::io::AsyncWrite;
+use tokio::runtime::Runtime;
+use tokio::time::Sleep;
+
+use g3_io_ext::{LimitedCopy, LimitedCopyConfig};
+
+const DATA_SIZE: usize = 1024 * 1024;
+
+/// A writer that accept one buffer for each round trip, to simulate a high latency path
+struct LatencyWriter {
+    delay: Duration,
+    sleep: Option<Pin<Box<Sleep>>>,
+    total: usize,
+}
+
+impl LatencyWriter {
+    fn new(delay: Duration) -> Self {
+        LatencyWriter {
+            delay,
+            sleep: None,
+            total: 0,
+        }
+    }
+}
+
+impl AsyncWrite for LatencyWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if let Some(sleep) = &mut self.sleep {
+            ready!(sleep.as_mut().poll(cx));
+        }
+        self.sleep = Some(Box::pin(tokio::time::sleep(self.delay)));
+        self.total += buf.len();
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn bench_copy(b: &mut Bencher, config: LimitedCopyConfig) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let data = vec![0u8; DATA_SIZE];
+    b.iter(|| run_copy(&rt, &data, &config));
+}
+
+fn run_copy(rt: &Runtime, data: &[u8], config: &LimitedCopyConfig) {
+    rt.block_on(async {
+        let mut reader = data;
+        let mut writer = LatencyWriter::new(Duration::from_millis(1));
+        let copied = LimitedCopy::new(&mut reader, &mut writer, config)
+            .await
+            .unwrap();
+        assert_eq!(copied as usize, DATA_SIZE);
+        assert_eq!(writer.total, DATA_SIZE);
+    })
+}
+
+#[bench]
+fn default_buffer(b: &mut Bencher) {
+    bench_copy(b, LimitedCopyConfig::default());
+}
+
+#[bench]
+fn large_buffer(b: &mut Bencher) {
+    let mut config = LimitedCopyConfig::default();
+    config.set_buffer_size(256 * 1024);
+    bench_copy(b, config);
+}
+
+#[bench]
+fn adaptive_buffer(b: &mut Bencher) {
+    let mut config = LimitedCopyConfig::default();
+    config.set_max_buffer_size(256 * 1024);
+    bench_copy(b, config);
+}
@@ -30,6 +30,7 @@ const MINIMAL_COPY_YIELD_SIZE: usize = 256 * 1024; // 256KB
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct LimitedCopyConfig {
     buffer_size: usize,
+    max_buffer_size: usize,
     yield_size: usize,
 }
 
@@ -37,6 +38,7 @@ impl Default for LimitedCopyConfig {
     fn default() -> Self {
         LimitedCopyConfig {
             buffer_size: DEFAULT_COPY_BUFFER_SIZE,
+            max_buffer_size: 0,
             yield_size: DEFAULT_COPY_YIELD_SIZE,
         }
     }
@@ -52,6 +54,17 @@ impl LimitedCopyConfig {
         self.buffer_size
     }
 
+    /// Set the max size the buffer can grow to for high throughput streams.
+    /// The buffer won't grow if this is not larger than the initial buffer size.
+    pub fn set_max_buffer_size(&mut self, max_buffer_size: usize) {
+        self.max_buffer_size = max_buffer_size;
+    }
+
+    #[inline]
+    pub fn max_buffer_size(&self) -> usize {
+        self.max_buffer_size.max(self.buffer_size)
+    }
+
     pub fn set_yield_size(&mut self, yield_size: usize) {
         self.yield_size = yield_size.max(MINIMAL_COPY_YIELD_SIZE);
     }
@@ -74,6 +87,9 @@ pub enum LimitedCopyError {
 struct LimitedCopyBuffer {
     read_done: bool,
     buf: Box<[u8]>,
+    init_size: usize,
+    max_size: usize,
+    want_grow: bool,
     yield_size: usize,
     r_off: usize,
     w_off: usize,
@@ -87,6 +103,9 @@ impl LimitedCopyBuffer {
         LimitedCopyBuffer {
             read_done: false,
             buf: vec![0; config.buffer_size].into_boxed_slice(),
+            init_size: config.buffer_size,
+            max_size: config.max_buffer_size(),
+            want_grow: false,
             yield_size: config.yield_size,
             r_off: 0,
             w_off: 0,
@@ -106,6 +125,9 @@ impl LimitedCopyBuffer {
         LimitedCopyBuffer {
             read_done: false,
             buf: buf.into_boxed_slice(),
+            init_size: config.buffer_size,
+            max_size: config.max_buffer_size(),
+            want_grow: false,
             yield_size: config.yield_size,
             r_off,
             w_off: 0,
@@ -132,6 +154,10 @@ impl LimitedCopyBuffer {
             if self.r_off == filled_len {
                 self.read_done = true;
             } else {
+                if self.r_off == 0 && filled_len == self.buf.len() {
+                    // the whole buffer is filled in one read, more data may be pending
+                    self.want_grow = true;
+                }
                 self.r_off = filled_len;
                 self.active = true;
             }
@@ -201,6 +227,9 @@ impl LimitedCopyBuffer {
                     // if empty, reset
                     self.w_off = 0;
                     self.r_off = 0;
+                    if self.want_grow {
+                        self.grow();
+                    }
                 }
 
                 if self.r_off < self.buf.len() {
@@ -251,6 +280,25 @@ impl LimitedCopyBuffer {
         }
     }
 
+    /// Double the buffer size, up to the max size. Should only be called if the buffer is empty.
+    fn grow(&mut self) {
+        self.want_grow = false;
+        if self.buf.len() < self.max_size {
+            let size = (self.buf.len() * 2).min(self.max_size);
+            self.buf = vec![0; size].into_boxed_slice();
+        }
+    }
+
+    fn reset_active(&mut self) {
+        if !self.active && self.r_off == self.w_off && self.buf.len() > self.init_size {
+            // idle for a whole check interval, release the grown buffer
+            self.buf = vec![0; self.init_size].into_boxed_slice();
+            self.r_off = 0;
+            self.w_off = 0;
+        }
+        self.active = false;
+    }
+
     pub async fn write_flush<W>(&mut self, writer: &mut W) -> Result<(), LimitedCopyError>
     where
         W: AsyncWrite + Unpin + ?Sized,
@@ -335,7 +383,7 @@ where
 
     #[inline]
     pub fn reset_active(&mut self) {
-        self.buf.active = false;
+        self.buf.reset_active();
     }
 
     pub async fn write_flush(&mut self) -> Result<(), LimitedCopyError> {
@@ -405,7 +453,7 @@ where
 
     #[inline]
     pub fn reset_active(&mut self) {
-        self.buf.active = false;
+        self.buf.reset_active();
     }
 
     pub async fn write_flush(&mut self) -> Result<(), LimitedCopyError> {
@@ -431,3 +479,41 @@ where
             .poll_copy(cx, Pin::new(&mut me.reader), Pin::new(&mut *me.writer))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fixed_buffer() {
+        let data = vec![1u8; 1024 * 1024];
+        let mut reader = data.as_slice();
+        let mut writer = Vec::new();
+
+        let config = LimitedCopyConfig::default();
+        let mut copy = LimitedCopy::new(&mut reader, &mut writer, &config);
+        assert_eq!((&mut copy).await.unwrap(), data.len() as u64);
+        assert_eq!(copy.buf.buf.len(), DEFAULT_COPY_BUFFER_SIZE);
+    }
+
+    #[tokio::test]
+    async fn adaptive_buffer() {
+        let data = vec![1u8; 1024 * 1024];
+        let mut reader = data.as_slice();
+        let mut writer = Vec::new();
+
+        let mut config = LimitedCopyConfig::default();
+        config.set_buffer_size(4096);
+        config.set_max_buffer_size(65536);
+        let mut copy = LimitedCopy::new(&mut reader, &mut writer, &config);
+        assert_eq!((&mut copy).await.unwrap(), data.len() as u64);
+        assert_eq!(copy.buf.buf.len(), 65536);
+
+        copy.reset_active();
+        assert_eq!(copy.buf.buf.len(), 65536);
+        copy.reset_active();
+        assert_eq!(copy.buf.buf.len(), 4096);
+        drop(copy);
+        assert_eq!(writer, data);
+    }
+}
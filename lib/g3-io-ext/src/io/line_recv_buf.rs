@@ -36,6 +36,9 @@ pub struct LineRecvBuf<const MAX_LINE_SIZE: usize> {
     length: usize,
     line_start: usize,
     line_end: usize,
+    truncate_long_line: bool,
+    skip_truncated: bool,
+    truncated_count: usize,
     buf: [u8; MAX_LINE_SIZE],
 }
 
@@ -45,12 +48,28 @@ impl<const MAX_LINE_SIZE: usize> Default for LineRecvBuf<MAX_LINE_SIZE> {
             length: 0,
             line_start: 0,
             line_end: 0,
+            truncate_long_line: false,
+            skip_truncated: false,
+            truncated_count: 0,
             buf: [0u8; MAX_LINE_SIZE],
         }
     }
 }
 
 impl<const MAX_LINE_SIZE: usize> LineRecvBuf<MAX_LINE_SIZE> {
+    /// Truncate the too long lines instead of returning [RecvLineError::LineTooLong].
+    ///
+    /// The truncated line will end with CRLF and no UTF-8 multibyte character will be split,
+    /// the left data of the line will be dropped when reading the next line.
+    pub fn set_truncate_long_line(&mut self, enable: bool) {
+        self.truncate_long_line = enable;
+    }
+
+    /// Get and reset the number of lines that have been truncated
+    pub fn take_truncated_count(&mut self) -> usize {
+        std::mem::take(&mut self.truncated_count)
+    }
+
     pub async fn read_line_with_timeout<R>(
         &mut self,
         reader: &mut R,
@@ -64,6 +83,41 @@ impl<const MAX_LINE_SIZE: usize> LineRecvBuf<MAX_LINE_SIZE> {
             .map_err(|_| RecvLineError::Timeout)?
     }
 
+    /// Wait until some data of the next line has been received.
+    ///
+    /// This is useful to detect idle peers, as the timeout will not be reset by the data received.
+    pub async fn wait_data_with_timeout<R>(
+        &mut self,
+        reader: &mut R,
+        timeout: Duration,
+    ) -> Result<(), RecvLineError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        if self.line_start < self.length {
+            return Ok(());
+        }
+        tokio::time::timeout(timeout, self.fill_more(reader))
+            .await
+            .map_err(|_| RecvLineError::Timeout)?
+    }
+
+    async fn fill_more<R>(&mut self, reader: &mut R) -> Result<(), RecvLineError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut unfilled = &mut self.buf[self.length..];
+        if unfilled.is_empty() {
+            return Err(RecvLineError::LineTooLong);
+        }
+        let nr = reader.read_buf(&mut unfilled).await?;
+        if nr == 0 {
+            return Err(RecvLineError::IoClosed);
+        }
+        self.length += nr;
+        Ok(())
+    }
+
     pub async fn read_line<R>(&mut self, reader: &mut R) -> Result<&[u8], RecvLineError>
     where
         R: AsyncRead + Unpin,
@@ -76,13 +130,30 @@ impl<const MAX_LINE_SIZE: usize> LineRecvBuf<MAX_LINE_SIZE> {
     where
         R: AsyncRead + Unpin,
     {
+        if self.line_end <= self.line_start {
+            self.skip_truncated_left(reader).await?;
+        }
         if let Some(end) = self.get_line() {
             return Ok(end);
         }
         loop {
             let mut unfilled = &mut self.buf[self.length..];
             if unfilled.is_empty() {
-                return self.get_line().ok_or(RecvLineError::LineTooLong);
+                if let Some(end) = self.get_line() {
+                    return Ok(end);
+                }
+                if !self.truncate_long_line {
+                    return Err(RecvLineError::LineTooLong);
+                }
+                if self.line_start > 0 {
+                    self.buf.copy_within(self.line_start..self.length, 0);
+                    self.length -= self.line_start;
+                    self.line_start = 0;
+                    self.line_end = 0;
+                    continue;
+                }
+                self.truncate_line();
+                return Ok(());
             }
             let nr = reader.read_buf(&mut unfilled).await?;
             if nr == 0 {
@@ -95,6 +166,58 @@ impl<const MAX_LINE_SIZE: usize> LineRecvBuf<MAX_LINE_SIZE> {
         }
     }
 
+    /// Truncate the line that fills up the whole buffer
+    fn truncate_line(&mut self) {
+        let mut end = self.length.saturating_sub(2);
+        // do not split a UTF-8 multibyte character, which has at most 3 continuation bytes
+        for _ in 0..3 {
+            if end > 0 && (self.buf[end] & 0xC0) == 0x80 {
+                end -= 1;
+            } else {
+                break;
+            }
+        }
+        if end > 0 && self.buf[end - 1] == b'\r' {
+            end -= 1;
+        }
+        self.buf[end] = b'\r';
+        self.buf[end + 1] = b'\n';
+        self.length = end + 2;
+        self.line_end = self.length;
+        self.skip_truncated = true;
+        self.truncated_count += 1;
+    }
+
+    /// Consume the truncated line and drop the left data of it.
+    ///
+    /// This is a no-op if the last line is not truncated.
+    pub async fn skip_truncated_left<R>(&mut self, reader: &mut R) -> Result<(), RecvLineError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        if !self.skip_truncated {
+            return Ok(());
+        }
+        // the truncated line is always the last one in the buffer
+        self.line_start = self.line_end;
+        loop {
+            if let Some(p) = memchr::memchr(b'\n', &self.buf[self.line_start..self.length]) {
+                self.line_start += p + 1;
+                self.line_end = self.line_start;
+                self.skip_truncated = false;
+                return Ok(());
+            }
+            self.length = 0;
+            self.line_start = 0;
+            self.line_end = 0;
+            let nr = reader.read_buf(&mut self.buf.as_mut_slice()).await?;
+            if nr == 0 {
+                return Err(RecvLineError::IoClosed);
+            }
+            self.length = nr;
+        }
+    }
+
     fn get_line(&mut self) -> Option<()> {
         if self.line_end > self.line_start {
             return Some(());
@@ -116,6 +239,12 @@ impl<const MAX_LINE_SIZE: usize> LineRecvBuf<MAX_LINE_SIZE> {
         Some(&self.buf[self.line_start..self.line_end])
     }
 
+    /// Check if there is another complete line buffered after the current one
+    pub fn has_next_line(&self) -> bool {
+        let start = self.line_end.max(self.line_start);
+        start < self.length && memchr::memchr(b'\n', &self.buf[start..self.length]).is_some()
+    }
+
     pub fn consume_line(&mut self) {
         self.line_start = self.line_end;
         if self.get_line().is_some() {
@@ -158,6 +287,7 @@ impl<const MAX_LINE_SIZE: usize> LineRecvBuf<MAX_LINE_SIZE> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tokio::io::AsyncWriteExt;
     use tokio_util::io::StreamReader;
 
     #[tokio::test]
@@ -227,6 +357,81 @@ mod tests {
         assert!(matches!(r, Err(RecvLineError::IoClosed)));
     }
 
+    #[tokio::test]
+    async fn pipelined_lines() {
+        let data = b"RCPT TO:<a@example.net>\r\nRCPT TO:<b@example.net>\r\nDAT";
+
+        let stream = tokio_stream::iter(vec![io::Result::Ok(data.as_slice())]);
+        let mut reader = StreamReader::new(stream);
+
+        let mut b: LineRecvBuf<512> = LineRecvBuf::default();
+        assert!(!b.has_next_line());
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"RCPT TO:<a@example.net>\r\n");
+        assert!(b.has_next_line());
+        b.consume_line();
+
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"RCPT TO:<b@example.net>\r\n");
+        assert!(!b.has_next_line());
+        b.consume_line();
+        assert!(!b.has_next_line());
+    }
+
+    #[tokio::test]
+    async fn line_then_left_data() {
+        let data1 = b"BDAT 10\r\n0123";
+        let data2 = b"456789QUIT\r\n";
+
+        let stream = tokio_stream::iter(vec![
+            io::Result::Ok(data1.as_slice()),
+            io::Result::Ok(data2.as_slice()),
+        ]);
+        let mut reader = StreamReader::new(stream);
+
+        let mut b: LineRecvBuf<512> = LineRecvBuf::default();
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"BDAT 10\r\n");
+        b.consume_line();
+
+        let left = b.consume_left(10);
+        assert_eq!(left, b"0123");
+        assert!(b.is_empty());
+
+        let mut data = [0u8; 6];
+        reader.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"456789");
+
+        b.consume_line();
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"QUIT\r\n");
+    }
+
+    #[tokio::test]
+    async fn line_then_partial_left_data() {
+        let data = b"BDAT 4\r\n0123BDAT 2 LAST\r\n45";
+
+        let stream = tokio_stream::iter(vec![io::Result::Ok(data.as_slice())]);
+        let mut reader = StreamReader::new(stream);
+
+        let mut b: LineRecvBuf<512> = LineRecvBuf::default();
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"BDAT 4\r\n");
+        b.consume_line();
+
+        let left = b.consume_left(4);
+        assert_eq!(left, b"0123");
+
+        b.consume_line();
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"BDAT 2 LAST\r\n");
+        b.consume_line();
+
+        let left = b.consume_left(2);
+        assert_eq!(left, b"45");
+        assert!(b.is_empty());
+    }
+
     #[tokio::test]
     async fn too_long_line() {
         let data = b"123 test line\r\n";
@@ -238,4 +443,67 @@ mod tests {
         let r = b.read_line(&mut reader).await;
         assert!(matches!(r, Err(RecvLineError::LineTooLong)));
     }
+
+    #[tokio::test]
+    async fn truncate_long_line() {
+        let data = b"250-0123456789\r\n250 ok\r\n";
+
+        let stream =
+            tokio_stream::iter(vec![io::Result::Ok(&data[..8]), io::Result::Ok(&data[8..])]);
+        let mut reader = StreamReader::new(stream);
+
+        let mut b: LineRecvBuf<12> = LineRecvBuf::default();
+        b.set_truncate_long_line(true);
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"250-012345\r\n");
+        b.consume_line();
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"250 ok\r\n");
+        b.consume_line();
+        assert!(b.is_empty());
+        assert_eq!(b.take_truncated_count(), 1);
+        assert_eq!(b.take_truncated_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn truncate_utf8_line() {
+        // the 4 bytes character should not be split
+        let data = "250 12345\u{1F600}abcdefg\r\n".as_bytes();
+
+        let stream = tokio_stream::iter(vec![io::Result::Ok(data)]);
+        let mut reader = StreamReader::new(stream);
+
+        let mut b: LineRecvBuf<14> = LineRecvBuf::default();
+        b.set_truncate_long_line(true);
+        let line = b.read_line(&mut reader).await.unwrap();
+        assert_eq!(line, b"250 12345\r\n");
+        b.consume_line();
+        let r = b.read_line(&mut reader).await;
+        assert!(matches!(r, Err(RecvLineError::IoClosed)));
+    }
+
+    #[tokio::test]
+    async fn wait_data() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        let mut b: LineRecvBuf<512> = LineRecvBuf::default();
+        let r = b
+            .wait_data_with_timeout(&mut server, Duration::from_millis(10))
+            .await;
+        assert!(matches!(r, Err(RecvLineError::Timeout)));
+
+        client.write_all(b"NOOP\r\n").await.unwrap();
+        b.wait_data_with_timeout(&mut server, Duration::from_millis(10))
+            .await
+            .unwrap();
+        let line = b.read_line(&mut server).await.unwrap();
+        assert_eq!(line, b"NOOP\r\n");
+        b.consume_line();
+
+        drop(client);
+        let r = b
+            .wait_data_with_timeout(&mut server, Duration::from_millis(10))
+            .await;
+        assert!(matches!(r, Err(RecvLineError::IoClosed)));
+    }
 }
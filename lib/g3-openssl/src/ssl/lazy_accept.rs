@@ -92,4 +92,9 @@ impl<S: AsyncRead + AsyncWrite + Unpin> SslLazyAcceptor<S> {
     pub fn ssl_mut(&mut self) -> &mut SslRef {
         self.inner.ssl_mut()
     }
+
+    /// Get the underlying stream, which can be used to send raw records before the handshake
+    pub fn get_mut(&mut self) -> &mut S {
+        self.inner.get_mut().get_mut()
+    }
 }
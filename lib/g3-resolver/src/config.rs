@@ -14,9 +14,10 @@
  * limitations under the License.
  */
 
+use std::sync::Arc;
 use std::time::Duration;
 
-use super::AnyResolveDriverConfig;
+use super::{AnyResolveDriverConfig, ResolverStaticHosts};
 
 pub(crate) const RESOLVER_MINIMUM_CACHE_TTL: u32 = 30;
 #[cfg(any(feature = "c-ares", feature = "hickory"))]
@@ -33,6 +34,7 @@ pub struct ResolverRuntimeConfig {
     pub batch_request_count: usize,
     pub protective_query_timeout: Duration,
     pub graceful_stop_wait: Duration,
+    pub static_hosts: Option<Arc<ResolverStaticHosts>>,
 }
 
 impl Default for ResolverRuntimeConfig {
@@ -42,6 +44,7 @@ impl Default for ResolverRuntimeConfig {
             batch_request_count: RESOLVER_BATCH_REQUEST_COUNT,
             protective_query_timeout: RESOLVER_PROTECTIVE_QUERY_TIMEOUT,
             graceful_stop_wait: RESOLVER_GRACEFUL_STOP_WAIT,
+            static_hosts: None,
         }
     }
 }
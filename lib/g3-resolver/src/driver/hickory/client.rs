@@ -109,8 +109,13 @@ impl HickoryClient {
                     };
                     let async_client = self.client.clone();
                     tokio::spawn(async move {
-                        let r = client_job.run(async_client, req).await;
-                        let _ = rsp_sender.send(r).await;
+                        tokio::select! {
+                            r = client_job.run(async_client, req) => {
+                                let _ = rsp_sender.send(r).await;
+                            }
+                            // cancel the query if no one is waiting for the answer
+                            _ = rsp_sender.closed() => {}
+                        }
                     });
                 }
                 _ = check_interval.tick() => {
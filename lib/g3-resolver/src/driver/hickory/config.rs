@@ -15,6 +15,7 @@
  */
 
 use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use std::time::Duration;
 
 use anyhow::anyhow;
@@ -24,6 +25,33 @@ use g3_types::net::DnsEncryptionConfigBuilder;
 use super::{HickoryClient, HickoryClientConfig, HickoryResolver};
 use crate::driver::BoxResolverDriver;
 
+/// How to dispatch a query if there are multiple servers
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HickoryServerStrategy {
+    /// Query the next server every retry interval until an answer arrives
+    #[default]
+    Staggered,
+    /// Query the next server only if the current one failed or timed out
+    SequentialFailover,
+    /// Query all servers at the same time and take the first answer
+    ParallelRace,
+}
+
+impl FromStr for HickoryServerStrategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "staggered" => Ok(HickoryServerStrategy::Staggered),
+            "sequential_failover" | "sequential" | "failover" => {
+                Ok(HickoryServerStrategy::SequentialFailover)
+            }
+            "parallel_race" | "parallel" | "race" => Ok(HickoryServerStrategy::ParallelRace),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct HickoryDriverConfig {
     connect_timeout: Duration,
@@ -31,6 +59,7 @@ pub struct HickoryDriverConfig {
     each_timeout: Duration,
     each_tries: i32,
     retry_interval: Duration,
+    server_strategy: HickoryServerStrategy,
     positive_min_ttl: u32,
     positive_max_ttl: u32,
     negative_ttl: u32,
@@ -48,6 +77,7 @@ impl Default for HickoryDriverConfig {
             each_timeout: Duration::from_secs(5),
             each_tries: 2,
             retry_interval: Duration::from_secs(1),
+            server_strategy: HickoryServerStrategy::default(),
             positive_min_ttl: crate::config::RESOLVER_MINIMUM_CACHE_TTL,
             positive_max_ttl: crate::config::RESOLVER_MAXIMUM_CACHE_TTL,
             negative_ttl: crate::config::RESOLVER_MINIMUM_CACHE_TTL,
@@ -114,6 +144,14 @@ impl HickoryDriverConfig {
         self.each_tries = attempts;
     }
 
+    pub fn set_retry_interval(&mut self, interval: Duration) {
+        self.retry_interval = interval;
+    }
+
+    pub fn set_server_strategy(&mut self, strategy: HickoryServerStrategy) {
+        self.server_strategy = strategy;
+    }
+
     pub fn set_bind_ip(&mut self, ip: IpAddr) {
         self.bind_ip = Some(ip);
     }
@@ -136,8 +174,12 @@ impl HickoryDriverConfig {
     }
 
     pub(crate) fn spawn_resolver_driver(&self) -> anyhow::Result<BoxResolverDriver> {
-        let mut driver =
-            HickoryResolver::new(self.each_timeout, self.retry_interval, self.negative_ttl);
+        let mut driver = HickoryResolver::new(
+            self.server_strategy,
+            self.each_timeout,
+            self.retry_interval,
+            self.negative_ttl,
+        );
         let port = self.server_port.unwrap_or_else(|| {
             self.encryption
                 .as_ref()
@@ -20,13 +20,14 @@ use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::time::Instant;
 
-use super::DnsRequest;
+use super::{DnsRequest, HickoryServerStrategy};
 use crate::config::ResolverRuntimeConfig;
 use crate::message::ResolveDriverResponse;
 use crate::{ResolveDriver, ResolveDriverError, ResolveLocalError, ResolvedRecord};
 
 #[derive(Clone)]
 pub struct HickoryResolver {
+    server_strategy: HickoryServerStrategy,
     each_timeout: Duration,
     retry_interval: Duration,
     negative_min_ttl: u32,
@@ -82,11 +83,13 @@ async fn run_timed(
 
 impl HickoryResolver {
     pub(super) fn new(
+        server_strategy: HickoryServerStrategy,
         each_timeout: Duration,
         retry_interval: Duration,
         negative_min_ttl: u32,
     ) -> Self {
         HickoryResolver {
+            server_strategy,
             each_timeout,
             retry_interval,
             negative_min_ttl,
@@ -102,6 +105,105 @@ impl HickoryResolver {
     }
 
     async fn run(self, domain: Arc<str>, request: DnsRequest) -> ResolvedRecord {
+        match self.server_strategy {
+            HickoryServerStrategy::Staggered => self.run_staggered(domain, request).await,
+            HickoryServerStrategy::SequentialFailover => self.run_sequential(domain, request).await,
+            HickoryServerStrategy::ParallelRace => self.run_parallel(domain, request).await,
+        }
+    }
+
+    fn no_resolver_running(&self, domain: Arc<str>) -> ResolvedRecord {
+        ResolvedRecord::failed(
+            domain,
+            self.negative_min_ttl,
+            ResolveLocalError::NoResolverRunning.into(),
+        )
+    }
+
+    async fn run_sequential(self, domain: Arc<str>, request: DnsRequest) -> ResolvedRecord {
+        let mut last_err: Option<ResolvedRecord> = None;
+        for client in &self.clients {
+            let (rsp_sender, mut rsp_receiver) = mpsc::channel::<ResolvedRecord>(1);
+            if client
+                .send_async((request.clone(), rsp_sender))
+                .await
+                .is_err()
+            {
+                continue;
+            }
+
+            // the query will be cancelled if timed out, as the receiver is dropped
+            match tokio::time::timeout(self.each_timeout, rsp_receiver.recv()).await {
+                Ok(Some(v)) => {
+                    if v.is_ok() {
+                        return v;
+                    }
+                    last_err = Some(v);
+                }
+                Ok(None) => {}
+                Err(_) => {
+                    last_err = Some(ResolvedRecord::failed(
+                        domain.clone(),
+                        self.negative_min_ttl,
+                        ResolveDriverError::Timeout.into(),
+                    ));
+                }
+            }
+        }
+
+        last_err.unwrap_or_else(|| self.no_resolver_running(domain))
+    }
+
+    async fn run_parallel(self, domain: Arc<str>, request: DnsRequest) -> ResolvedRecord {
+        let (rsp_sender, mut rsp_receiver) =
+            mpsc::channel::<ResolvedRecord>(self.clients.len().max(1));
+
+        let mut wait_left = 0;
+        for client in &self.clients {
+            if client
+                .try_send((request.clone(), rsp_sender.clone()))
+                .is_ok()
+            {
+                wait_left += 1;
+            }
+        }
+        drop(rsp_sender);
+        if wait_left == 0 {
+            return self.no_resolver_running(domain);
+        }
+
+        let mut last_err: Option<ResolvedRecord> = None;
+        let r = tokio::time::timeout(self.each_timeout, async {
+            while let Some(v) = rsp_receiver.recv().await {
+                if v.is_ok() {
+                    return Some(v);
+                }
+                last_err = Some(v);
+            }
+            None
+        })
+        .await;
+        // the outstanding queries will be cancelled when the receiver is dropped
+        match r {
+            Ok(Some(v)) => v,
+            Ok(None) => last_err.unwrap_or_else(|| {
+                ResolvedRecord::failed(
+                    domain,
+                    self.negative_min_ttl,
+                    ResolveDriverError::Internal("no response received".to_string()).into(),
+                )
+            }),
+            Err(_) => last_err.unwrap_or_else(|| {
+                ResolvedRecord::failed(
+                    domain,
+                    self.negative_min_ttl,
+                    ResolveDriverError::Timeout.into(),
+                )
+            }),
+        }
+    }
+
+    async fn run_staggered(self, domain: Arc<str>, request: DnsRequest) -> ResolvedRecord {
         let (rsp_sender, mut rsp_receiver) = mpsc::channel::<ResolvedRecord>(1);
 
         let mut wait_left = self.clients.len();
@@ -177,3 +279,125 @@ impl HickoryResolver {
         last_err.unwrap_or(end_err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const FAST_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const SLOW_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    const DOMAIN: &str = "www.example.net";
+
+    #[derive(Default)]
+    struct MockStats {
+        received: AtomicUsize,
+        answered: AtomicUsize,
+    }
+
+    fn mock_client(
+        delay: Duration,
+        ip: IpAddr,
+    ) -> (
+        flume::Sender<(DnsRequest, mpsc::Sender<ResolvedRecord>)>,
+        Arc<MockStats>,
+    ) {
+        let (req_sender, req_receiver) =
+            flume::unbounded::<(DnsRequest, mpsc::Sender<ResolvedRecord>)>();
+        let stats = Arc::new(MockStats::default());
+        let stats2 = stats.clone();
+        tokio::spawn(async move {
+            while let Ok((_req, rsp_sender)) = req_receiver.recv_async().await {
+                stats2.received.fetch_add(1, Ordering::Relaxed);
+                let stats = stats2.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {
+                            stats.answered.fetch_add(1, Ordering::Relaxed);
+                            let r = ResolvedRecord::resolved(Arc::from(DOMAIN), 60, vec![ip]);
+                            let _ = rsp_sender.send(r).await;
+                        }
+                        _ = rsp_sender.closed() => {}
+                    }
+                });
+            }
+        });
+        (req_sender, stats)
+    }
+
+    fn new_resolver(
+        strategy: HickoryServerStrategy,
+    ) -> (HickoryResolver, Arc<MockStats>, Arc<MockStats>) {
+        let mut resolver = HickoryResolver::new(
+            strategy,
+            Duration::from_millis(200),
+            Duration::from_secs(1),
+            30,
+        );
+        let (slow, slow_stats) = mock_client(Duration::from_secs(2), SLOW_IP);
+        let (fast, fast_stats) = mock_client(Duration::from_millis(10), FAST_IP);
+        resolver.push_client(slow);
+        resolver.push_client(fast);
+        (resolver, slow_stats, fast_stats)
+    }
+
+    #[tokio::test]
+    async fn sequential_failover() {
+        let (resolver, slow_stats, fast_stats) =
+            new_resolver(HickoryServerStrategy::SequentialFailover);
+        let domain: Arc<str> = Arc::from(DOMAIN);
+
+        let start = Instant::now();
+        let r = resolver
+            .run(domain.clone(), DnsRequest::query_ipv4(domain))
+            .await;
+        let elapsed = start.elapsed();
+        assert_eq!(r.result.unwrap(), vec![FAST_IP]);
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_secs(1));
+        assert_eq!(slow_stats.received.load(Ordering::Relaxed), 1);
+        assert_eq!(fast_stats.received.load(Ordering::Relaxed), 1);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(slow_stats.answered.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn parallel_race() {
+        let (resolver, slow_stats, fast_stats) = new_resolver(HickoryServerStrategy::ParallelRace);
+        let domain: Arc<str> = Arc::from(DOMAIN);
+
+        let start = Instant::now();
+        let r = resolver
+            .run(domain.clone(), DnsRequest::query_ipv4(domain))
+            .await;
+        let elapsed = start.elapsed();
+        assert_eq!(r.result.unwrap(), vec![FAST_IP]);
+        assert!(elapsed < Duration::from_millis(200));
+        assert_eq!(slow_stats.received.load(Ordering::Relaxed), 1);
+        assert_eq!(fast_stats.answered.load(Ordering::Relaxed), 1);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(slow_stats.answered.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn parallel_race_timeout() {
+        let mut resolver = HickoryResolver::new(
+            HickoryServerStrategy::ParallelRace,
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            30,
+        );
+        let (slow, slow_stats) = mock_client(Duration::from_secs(2), SLOW_IP);
+        resolver.push_client(slow);
+        let domain: Arc<str> = Arc::from(DOMAIN);
+
+        let r = resolver
+            .run(domain.clone(), DnsRequest::query_ipv4(domain))
+            .await;
+        assert!(r.is_err());
+        assert_eq!(slow_stats.received.load(Ordering::Relaxed), 1);
+    }
+}
@@ -15,7 +15,7 @@
  */
 
 mod config;
-pub use config::HickoryDriverConfig;
+pub use config::{HickoryDriverConfig, HickoryServerStrategy};
 
 mod client;
 use client::{DnsRequest, HickoryClient, HickoryClientConfig};
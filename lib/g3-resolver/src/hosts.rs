@@ -0,0 +1,183 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use ahash::AHashMap;
+
+use super::ResolvedRecord;
+
+pub const SYSTEM_HOSTS_FILE: &str = "/etc/hosts";
+const DEFAULT_STATIC_HOSTS_TTL: u32 = 300;
+
+/// Hostname to address overrides that will be checked before the resolver cache and driver
+///
+/// Manually added records take precedence over the ones loaded from hosts files.
+/// If a hostname is found here, the result is authoritative for both address families,
+/// so an empty record will be returned if there is no address of the queried family.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResolverStaticHosts {
+    records: AHashMap<Arc<str>, Vec<IpAddr>>,
+    file_records: AHashMap<Arc<str>, Vec<IpAddr>>,
+    ttl: u32,
+}
+
+impl Default for ResolverStaticHosts {
+    fn default() -> Self {
+        ResolverStaticHosts {
+            records: AHashMap::new(),
+            file_records: AHashMap::new(),
+            ttl: DEFAULT_STATIC_HOSTS_TTL,
+        }
+    }
+}
+
+fn normalize_name(name: &str) -> Arc<str> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    Arc::from(name.to_ascii_lowercase())
+}
+
+fn push_ip(map: &mut AHashMap<Arc<str>, Vec<IpAddr>>, name: &str, ip: IpAddr) {
+    let ips = map.entry(normalize_name(name)).or_default();
+    if !ips.contains(&ip) {
+        ips.push(ip);
+    }
+}
+
+impl ResolverStaticHosts {
+    pub fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = ttl;
+    }
+
+    #[inline]
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty() && self.file_records.is_empty()
+    }
+
+    pub fn add_record(&mut self, name: &str, ip: IpAddr) {
+        push_ip(&mut self.records, name, ip);
+    }
+
+    pub fn load_hosts_file(&mut self, path: &Path) -> io::Result<()> {
+        let content = std::fs::read_to_string(path)?;
+        self.parse_hosts_content(&content);
+        Ok(())
+    }
+
+    fn parse_hosts_content(&mut self, content: &str) {
+        for line in content.lines() {
+            let line = match line.split_once('#') {
+                Some((l, _)) => l,
+                None => line,
+            };
+            let mut fields = line.split_whitespace();
+            let Some(ip) = fields.next() else {
+                continue;
+            };
+            // skip addresses with zone index or other invalid ones
+            let Ok(ip) = ip.parse::<IpAddr>() else {
+                continue;
+            };
+            for name in fields {
+                push_ip(&mut self.file_records, name, ip);
+            }
+        }
+    }
+
+    fn get(&self, domain: &str) -> Option<&Vec<IpAddr>> {
+        if domain.ends_with('.') || domain.bytes().any(|b| b.is_ascii_uppercase()) {
+            let domain = normalize_name(domain);
+            self.records
+                .get(&domain)
+                .or_else(|| self.file_records.get(&domain))
+        } else {
+            self.records
+                .get(domain)
+                .or_else(|| self.file_records.get(domain))
+        }
+    }
+
+    pub(crate) fn query_v4(&self, domain: &Arc<str>) -> Option<ResolvedRecord> {
+        self.get(domain).map(|ips| {
+            let ips = ips.iter().filter(|ip| ip.is_ipv4()).copied().collect();
+            ResolvedRecord::resolved(domain.clone(), self.ttl, ips)
+        })
+    }
+
+    pub(crate) fn query_v6(&self, domain: &Arc<str>) -> Option<ResolvedRecord> {
+        self.get(domain).map(|ips| {
+            let ips = ips.iter().filter(|ip| ip.is_ipv6()).copied().collect();
+            ResolvedRecord::resolved(domain.clone(), self.ttl, ips)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn query() {
+        let mut hosts = ResolverStaticHosts::default();
+        hosts.parse_hosts_content(
+            "# comment line\n\
+             127.0.0.1 localhost\n\
+             ::1 localhost ip6-localhost # inline comment\n\
+             192.168.1.1\tRouter.lan router\n\
+             fe80::1%eth0 link-local\n",
+        );
+        hosts.add_record("router.lan.", IpAddr::from_str("10.0.0.1").unwrap());
+        hosts.add_record("router.lan", IpAddr::from_str("10.0.0.2").unwrap());
+        hosts.add_record("router.lan", IpAddr::from_str("10.0.0.1").unwrap());
+        hosts.set_ttl(60);
+
+        let domain: Arc<str> = Arc::from("localhost");
+        let r = hosts.query_v4(&domain).unwrap();
+        assert_eq!(
+            r.result.unwrap(),
+            vec![IpAddr::from_str("127.0.0.1").unwrap()]
+        );
+        let r = hosts.query_v6(&domain).unwrap();
+        assert_eq!(r.result.unwrap(), vec![IpAddr::from_str("::1").unwrap()]);
+
+        let domain: Arc<str> = Arc::from("Router.LAN");
+        let r = hosts.query_v4(&domain).unwrap();
+        assert_eq!(
+            r.result.unwrap(),
+            vec![
+                IpAddr::from_str("10.0.0.1").unwrap(),
+                IpAddr::from_str("10.0.0.2").unwrap()
+            ]
+        );
+        let r = hosts.query_v6(&domain).unwrap();
+        assert!(!r.is_usable());
+
+        let domain: Arc<str> = Arc::from("router");
+        assert!(hosts.query_v4(&domain).is_some());
+        let domain: Arc<str> = Arc::from("link-local");
+        assert!(hosts.query_v6(&domain).is_none());
+        let domain: Arc<str> = Arc::from("example.com");
+        assert!(hosts.query_v4(&domain).is_none());
+    }
+}
@@ -22,6 +22,7 @@ pub(crate) use driver::{BoxResolverDriver, ResolveDriver};
 mod config;
 mod error;
 mod handle;
+mod hosts;
 mod message;
 mod query;
 mod record;
@@ -32,6 +33,7 @@ mod stats;
 pub use config::{ResolverConfig, ResolverRuntimeConfig};
 pub use error::{ResolveDriverError, ResolveError, ResolveLocalError, ResolveServerError};
 pub use handle::{ResolveJob, ResolveJobRecvResult, ResolverHandle};
+pub use hosts::{ResolverStaticHosts, SYSTEM_HOSTS_FILE};
 pub use query::ResolveQueryType;
 pub use record::{ArcResolvedRecord, ResolvedRecord, ResolvedRecordSource};
 pub use resolver::{Resolver, ResolverBuilder};
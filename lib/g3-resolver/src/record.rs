@@ -28,6 +28,7 @@ use crate::ResolveLocalError;
 pub enum ResolvedRecordSource {
     Cache,
     Query,
+    Static,
 }
 
 impl ResolvedRecordSource {
@@ -35,6 +36,7 @@ impl ResolvedRecordSource {
         match self {
             ResolvedRecordSource::Cache => "cache",
             ResolvedRecordSource::Query => "query",
+            ResolvedRecordSource::Static => "static",
         }
     }
 }
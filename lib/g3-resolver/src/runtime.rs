@@ -181,6 +181,13 @@ impl ResolverRuntime {
         match req {
             ResolveDriverRequest::GetV4(domain, sender) => {
                 self.stats.query_a.add_query_total();
+                if let Some(hosts) = &self.config.runtime.static_hosts {
+                    if let Some(record) = hosts.query_v4(&domain) {
+                        self.stats.query_a.add_query_cached();
+                        let _ = sender.send((Arc::new(record), ResolvedRecordSource::Static));
+                        return;
+                    }
+                }
                 match self.cache_v4.get(&domain) {
                     Some(r) => {
                         self.stats.query_a.add_query_cached();
@@ -209,6 +216,13 @@ impl ResolverRuntime {
             }
             ResolveDriverRequest::GetV6(domain, sender) => {
                 self.stats.query_aaaa.add_query_total();
+                if let Some(hosts) = &self.config.runtime.static_hosts {
+                    if let Some(record) = hosts.query_v6(&domain) {
+                        self.stats.query_aaaa.add_query_cached();
+                        let _ = sender.send((Arc::new(record), ResolvedRecordSource::Static));
+                        return;
+                    }
+                }
                 match self.cache_v6.get(&domain) {
                     Some(r) => {
                         self.stats.query_aaaa.add_query_cached();
@@ -21,6 +21,8 @@ use super::CommandLineError;
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct MailParam {
     reverse_path: String,
+    raw_reverse_path: String,
+    esmtp_params: Vec<(String, Option<String>)>,
 }
 
 impl MailParam {
@@ -29,6 +31,59 @@ impl MailParam {
         &self.reverse_path
     }
 
+    /// Iterate over the ESMTP parameters in their original order
+    pub fn esmtp_params(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.esmtp_params
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_deref()))
+    }
+
+    /// Get the value of the ESMTP parameter, the keyword is case-insensitive
+    pub fn esmtp_param(&self, keyword: &str) -> Option<Option<&str>> {
+        self.esmtp_params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(keyword))
+            .map(|(_, v)| v.as_deref())
+    }
+
+    /// Remove the ESMTP parameter, returns true if it was present
+    pub fn remove_esmtp_param(&mut self, keyword: &str) -> bool {
+        let len = self.esmtp_params.len();
+        self.esmtp_params
+            .retain(|(k, _)| !k.eq_ignore_ascii_case(keyword));
+        self.esmtp_params.len() != len
+    }
+
+    /// Set the value of the ESMTP parameter in place, or append it if not present
+    pub fn set_esmtp_param(&mut self, keyword: &str, value: Option<&str>) {
+        let value = value.map(|v| v.to_string());
+        match self
+            .esmtp_params
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(keyword))
+        {
+            Some((_, v)) => *v = value,
+            None => self.esmtp_params.push((keyword.to_string(), value)),
+        }
+    }
+
+    /// Encode a complete `MAIL FROM` command line with the current ESMTP parameters
+    pub fn encode_line(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(16 + self.raw_reverse_path.len());
+        buf.extend_from_slice(b"MAIL FROM:");
+        buf.extend_from_slice(self.raw_reverse_path.as_bytes());
+        for (k, v) in &self.esmtp_params {
+            buf.push(b' ');
+            buf.extend_from_slice(k.as_bytes());
+            if let Some(v) = v {
+                buf.push(b'=');
+                buf.extend_from_slice(v.as_bytes());
+            }
+        }
+        buf.extend_from_slice(b"\r\n");
+        buf
+    }
+
     pub(super) fn parse(msg: &[u8]) -> Result<Self, CommandLineError> {
         let msg = str::from_utf8(msg).map_err(CommandLineError::InvalidUtf8Command)?;
 
@@ -52,7 +107,73 @@ impl MailParam {
                 "invalid reverse path prefix",
             ));
         }
+        // the prefix is checked to be ascii "from:" above
+        let raw_reverse_path = s[5..].to_string();
+
+        // esmtp-param    = esmtp-keyword ["=" esmtp-value]
+        let mut esmtp_params = Vec::new();
+        for param in iter {
+            let (k, v) = match param.split_once('=') {
+                Some((k, v)) => (k, Some(v.to_string())),
+                None => (param, None),
+            };
+            if k.is_empty() {
+                return Err(CommandLineError::InvalidCommandParam(
+                    "MAIL",
+                    "empty esmtp parameter keyword",
+                ));
+            }
+            esmtp_params.push((k.to_string(), v));
+        }
+
+        Ok(MailParam {
+            reverse_path,
+            raw_reverse_path,
+            esmtp_params,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_params() {
+        let param = MailParam::parse(b"FROM:<Sender@example.net> SIZE=1024 BODY=8BITMIME").unwrap();
+        assert_eq!(param.reverse_path(), "<sender@example.net>");
+        assert_eq!(param.esmtp_param("size"), Some(Some("1024")));
+        assert_eq!(param.esmtp_param("Body"), Some(Some("8BITMIME")));
+        assert!(param.esmtp_param("SMTPUTF8").is_none());
+        assert_eq!(
+            param.encode_line(),
+            b"MAIL FROM:<Sender@example.net> SIZE=1024 BODY=8BITMIME\r\n"
+        );
+
+        assert!(MailParam::parse(b"FROM:<> =1024").is_err());
+    }
+
+    #[test]
+    fn strip_size() {
+        let mut param = MailParam::parse(b"FROM:<a@example.net> SIZE=1024 BODY=8BITMIME").unwrap();
+        assert!(param.remove_esmtp_param("size"));
+        assert!(!param.remove_esmtp_param("SIZE"));
+        assert_eq!(
+            param.encode_line(),
+            b"MAIL FROM:<a@example.net> BODY=8BITMIME\r\n"
+        );
+    }
+
+    #[test]
+    fn add_and_modify() {
+        let mut param = MailParam::parse(b"from:<> BODY=8BITMIME").unwrap();
+        param.set_esmtp_param("SMTPUTF8", None);
+        param.set_esmtp_param("BODY", Some("7BIT"));
+        let params: Vec<_> = param.esmtp_params().collect();
+        assert_eq!(params, vec![("BODY", Some("7BIT")), ("SMTPUTF8", None)]);
+        assert_eq!(param.encode_line(), b"MAIL FROM:<> BODY=7BIT SMTPUTF8\r\n");
 
-        Ok(MailParam { reverse_path })
+        assert!(param.remove_esmtp_param("smtputf8"));
+        assert_eq!(param.encode_line(), b"MAIL FROM:<> BODY=7BIT\r\n");
     }
 }
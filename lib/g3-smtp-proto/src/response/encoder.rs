@@ -14,12 +14,26 @@
  * limitations under the License.
  */
 
+use std::fmt::Write;
 use std::io;
 use std::net::IpAddr;
 
+use thiserror::Error;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 
-use crate::response::ResponseLineError;
+use crate::response::{ReplyCode, ResponseLineError, ResponseParser};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ResponseEncodeError {
+    #[error("invalid reply code {0}")]
+    InvalidReplyCode(u16),
+    #[error("CR or LF found in reply text")]
+    EmbeddedLineBreak,
+    #[error("reply line too long")]
+    TooLongLine,
+    #[error("no reply line")]
+    NoLine,
+}
 
 pub enum ResponseEncoder {
     Static(&'static str),
@@ -38,11 +52,20 @@ impl ResponseEncoder {
     impl_static!(COMMAND_LINE_TOO_LONG, "500 Line too long\r\n");
     impl_static!(COMMAND_NOT_IMPLEMENTED, "502 Command not implemented\r\n");
     impl_static!(BAD_SEQUENCE_OF_COMMANDS, "503 Bad sequence of commands\r\n");
+    impl_static!(TOO_MANY_RECIPIENTS, "452 Too many recipients\r\n");
     impl_static!(
         COMMAND_PARAMATER_NOT_IMPLEMENTED,
         "504 Command parameter not implemented\r\n"
     );
     impl_static!(AUTHENTICATION_REQUIRED, "530 Authentication required\r\n");
+    impl_static!(
+        STARTTLS_REQUIRED,
+        "530 Must issue a STARTTLS command first\r\n"
+    );
+    impl_static!(
+        MESSAGE_SIZE_EXCEEDED,
+        "552 Message size exceeds fixed maximum message size\r\n"
+    );
 
     pub fn local_service_closing(local_ip: IpAddr) -> Self {
         let msg = match local_ip {
@@ -76,6 +99,30 @@ impl ResponseEncoder {
         ResponseEncoder::Owned(msg)
     }
 
+    pub fn local_service_shutting_down(local_ip: IpAddr) -> Self {
+        let msg = match local_ip {
+            IpAddr::V4(v4) => {
+                format!("421 [{v4}] Service shutting down, closing transmission channel\r\n")
+            }
+            IpAddr::V6(v6) => {
+                format!("421 Ipv6:{v6} Service shutting down, closing transmission channel\r\n")
+            }
+        };
+        ResponseEncoder::Owned(msg)
+    }
+
+    pub fn local_idle_timeout(local_ip: IpAddr) -> Self {
+        let msg = match local_ip {
+            IpAddr::V4(v4) => {
+                format!("421 [{v4}] Timeout exceeded, closing transmission channel\r\n")
+            }
+            IpAddr::V6(v6) => {
+                format!("421 Ipv6:{v6} Timeout exceeded, closing transmission channel\r\n")
+            }
+        };
+        ResponseEncoder::Owned(msg)
+    }
+
     pub fn message_blocked(local_ip: IpAddr, reason: String) -> Self {
         let msg = match local_ip {
             IpAddr::V4(v4) => {
@@ -104,6 +151,14 @@ impl ResponseEncoder {
         ResponseEncoder::Owned(msg)
     }
 
+    pub fn upstream_starttls_not_offered(local_ip: IpAddr) -> Self {
+        let msg = match local_ip {
+            IpAddr::V4(v4) => format!("530 [{v4}] Upstream service doesn't offer STARTTLS\r\n"),
+            IpAddr::V6(v6) => format!("530 Ipv6:{v6} Upstream service doesn't offer STARTTLS\r\n"),
+        };
+        ResponseEncoder::Owned(msg)
+    }
+
     pub fn upstream_io_error(local_ip: IpAddr, e: &io::Error) -> Self {
         let msg = match local_ip {
             IpAddr::V4(v4) => format!("554 [{v4}] Upstream io error: {e}\r\n"),
@@ -136,6 +191,15 @@ impl ResponseEncoder {
         ResponseEncoder::Owned(msg)
     }
 
+    /// Build a multiline reply with the given code and text lines.
+    pub fn multiline<S: AsRef<str>>(code: u16, lines: &[S]) -> Result<Self, ResponseEncodeError> {
+        let mut builder = MultilineResponseBuilder::new(code)?;
+        for line in lines {
+            builder.push_line(line.as_ref())?;
+        }
+        builder.build()
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         match self {
             ResponseEncoder::Static(s) => s.as_bytes(),
@@ -151,3 +215,130 @@ impl ResponseEncoder {
         writer.flush().await
     }
 }
+
+/// Builder for multiline replies.
+///
+/// All but the last line will use the `-` continuation form, and the last line will use a space.
+pub struct MultilineResponseBuilder {
+    code: ReplyCode,
+    buf: String,
+    last_line_offset: Option<usize>,
+}
+
+impl MultilineResponseBuilder {
+    pub fn new(code: u16) -> Result<Self, ResponseEncodeError> {
+        let code = ReplyCode::from_u16(code).ok_or(ResponseEncodeError::InvalidReplyCode(code))?;
+        Ok(MultilineResponseBuilder {
+            code,
+            buf: String::with_capacity(256),
+            last_line_offset: None,
+        })
+    }
+
+    pub fn push_line(&mut self, text: &str) -> Result<(), ResponseEncodeError> {
+        if text.contains(['\r', '\n']) {
+            return Err(ResponseEncodeError::EmbeddedLineBreak);
+        }
+        // the code, the delimiter and the trailing CRLF
+        if text.len() + 6 > ResponseParser::MAX_LINE_SIZE {
+            return Err(ResponseEncodeError::TooLongLine);
+        }
+
+        self.last_line_offset = Some(self.buf.len());
+        let _ = write!(self.buf, "{}-{text}\r\n", self.code);
+        Ok(())
+    }
+
+    pub fn build(mut self) -> Result<ResponseEncoder, ResponseEncodeError> {
+        let offset = self.last_line_offset.ok_or(ResponseEncodeError::NoLine)?;
+        // the delimiter is always ascii, so it's safe to replace it in place
+        self.buf.replace_range(offset + 3..offset + 4, " ");
+        Ok(ResponseEncoder::Owned(self.buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiline() {
+        let rsp =
+            ResponseEncoder::multiline(250, &["mail.example.com greets you", "8BITMIME", "HELP"])
+                .unwrap();
+        assert_eq!(
+            rsp.as_bytes(),
+            b"250-mail.example.com greets you\r\n250-8BITMIME\r\n250 HELP\r\n"
+        );
+
+        let rsp = ResponseEncoder::multiline(221, &["Bye"]).unwrap();
+        assert_eq!(rsp.as_bytes(), b"221 Bye\r\n");
+    }
+
+    #[test]
+    fn multiline_parse() {
+        let rsp = ResponseEncoder::multiline(250, &["host", "SIZE 1024", "PIPELINING"]).unwrap();
+        let mut parser = ResponseParser::default();
+        let mut lines = Vec::new();
+        for line in rsp.as_bytes().split_inclusive(|b| *b == b'\n') {
+            lines.push(parser.feed_line(line).unwrap().to_vec());
+        }
+        assert!(parser.finished());
+        assert_eq!(parser.code(), ReplyCode::OK);
+        assert_eq!(
+            lines,
+            vec![
+                b"host".to_vec(),
+                b"SIZE 1024".to_vec(),
+                b"PIPELINING".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn multiline_invalid() {
+        assert_eq!(
+            ResponseEncoder::multiline(25, &["a"]).err(),
+            Some(ResponseEncodeError::InvalidReplyCode(25))
+        );
+        assert_eq!(
+            ResponseEncoder::multiline(2500, &["a"]).err(),
+            Some(ResponseEncodeError::InvalidReplyCode(2500))
+        );
+        assert_eq!(
+            ResponseEncoder::multiline(650, &["a"]).err(),
+            Some(ResponseEncodeError::InvalidReplyCode(650))
+        );
+        assert_eq!(
+            ResponseEncoder::multiline(250, &["a\r\n250 b"]).err(),
+            Some(ResponseEncodeError::EmbeddedLineBreak)
+        );
+        assert_eq!(
+            ResponseEncoder::multiline(250, &["a\nb"]).err(),
+            Some(ResponseEncodeError::EmbeddedLineBreak)
+        );
+        let long = "a".repeat(ResponseParser::MAX_LINE_SIZE);
+        assert_eq!(
+            ResponseEncoder::multiline(250, &[long]).err(),
+            Some(ResponseEncodeError::TooLongLine)
+        );
+        let empty: [&str; 0] = [];
+        assert_eq!(
+            ResponseEncoder::multiline(250, &empty).err(),
+            Some(ResponseEncodeError::NoLine)
+        );
+    }
+
+    #[test]
+    fn local_service_shutting_down() {
+        let rsp = ResponseEncoder::local_service_shutting_down(IpAddr::from([192, 0, 2, 1]));
+        assert_eq!(
+            rsp.as_bytes(),
+            b"421 [192.0.2.1] Service shutting down, closing transmission channel\r\n"
+        );
+        let mut parser = ResponseParser::default();
+        parser.feed_line(rsp.as_bytes()).unwrap();
+        assert!(parser.finished());
+        assert_eq!(parser.code(), ReplyCode::SERVICE_NOT_AVAILABLE);
+    }
+}
@@ -18,4 +18,4 @@ mod parser;
 pub use parser::{ReplyCode, ResponseLineError, ResponseParser};
 
 mod encoder;
-pub use encoder::ResponseEncoder;
+pub use encoder::{MultilineResponseBuilder, ResponseEncodeError, ResponseEncoder};
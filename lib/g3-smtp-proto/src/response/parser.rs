@@ -77,10 +77,24 @@ impl ReplyCode {
         Some(ReplyCode { a, b, c })
     }
 
+    pub fn from_u16(code: u16) -> Option<Self> {
+        if !(100..1000).contains(&code) {
+            return None;
+        }
+        let a = b'0' + (code / 100) as u8;
+        let b = b'0' + (code / 10 % 10) as u8;
+        let c = b'0' + (code % 10) as u8;
+        ReplyCode::new(a, b, c)
+    }
+
     fn is_set(&self) -> bool {
         self.a != 0
     }
 
+    pub fn is_positive_completion(&self) -> bool {
+        self.a == b'2'
+    }
+
     pub fn as_u16(&self) -> u16 {
         (self.a - b'0') as u16 * 100 + (self.b - b'0') as u16 * 10 + (self.c - b'0') as u16
     }
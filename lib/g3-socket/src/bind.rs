@@ -24,6 +24,8 @@ use g3_types::net::InterfaceName;
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use super::sockopt::set_bind_address_no_port;
+#[cfg(target_os = "linux")]
+use super::sockopt::set_ip_transparent_v6;
 #[cfg(windows)]
 use super::sockopt::set_reuse_unicastport;
 use crate::util::AddressFamily;
@@ -35,6 +37,9 @@ pub enum BindAddr {
     Ip(IpAddr),
     #[cfg(any(target_os = "linux", target_os = "android"))]
     Interface(InterfaceName),
+    /// Bind to a non-local address, such as the client address, with IP_TRANSPARENT and IP_FREEBIND
+    #[cfg(target_os = "linux")]
+    Transparent(IpAddr),
 }
 
 impl BindAddr {
@@ -43,10 +48,11 @@ impl BindAddr {
     }
 
     pub fn ip(&self) -> Option<IpAddr> {
-        if let BindAddr::Ip(ip) = self {
-            Some(*ip)
-        } else {
-            None
+        match self {
+            BindAddr::Ip(ip) => Some(*ip),
+            #[cfg(target_os = "linux")]
+            BindAddr::Transparent(ip) => Some(*ip),
+            _ => None,
         }
     }
 
@@ -68,15 +74,34 @@ impl BindAddr {
                 set_bind_address_no_port(socket, true)?;
                 #[cfg(windows)]
                 set_reuse_unicastport(socket, true)?;
-                let addr: SockAddr = SocketAddr::new(*ip, 0).into();
-                socket.bind(&addr)
+                bind_ip(socket, *ip)
             }
             #[cfg(any(target_os = "linux", target_os = "android"))]
             BindAddr::Interface(name) => {
                 set_bind_address_no_port(socket, true)?;
                 #[cfg(windows)]
                 set_reuse_unicastport(socket, true)?;
-                socket.bind_device(Some(name.as_bytes()))
+                bind_device(socket, name)
+            }
+            #[cfg(target_os = "linux")]
+            BindAddr::Transparent(ip) => {
+                if AddressFamily::from(ip) != peer_family {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "transparent bind ip should be of the same family with peer ip",
+                    ));
+                }
+                set_transparent(socket, peer_family)?;
+                let addr: SockAddr = SocketAddr::new(*ip, 0).into();
+                socket.bind(&addr).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!(
+                            "failed to bind to non-local address {ip}: {e}, \
+                             policy routing for the return traffic may be missing"
+                        ),
+                    )
+                })
             }
         }
     }
@@ -87,10 +112,15 @@ impl BindAddr {
                 AddressFamily::Ipv4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
                 AddressFamily::Ipv6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
             },
-            BindAddr::Ip(ip) => *ip,
+            BindAddr::Ip(ip) => return bind_ip(socket, *ip),
+            #[cfg(target_os = "linux")]
+            BindAddr::Transparent(ip) => {
+                set_transparent(socket, family)?;
+                *ip
+            }
             #[cfg(any(target_os = "linux", target_os = "android"))]
             BindAddr::Interface(name) => {
-                socket.bind_device(Some(name.as_bytes()))?;
+                bind_device(socket, name)?;
                 match family {
                     AddressFamily::Ipv4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
                     AddressFamily::Ipv6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
@@ -101,3 +131,38 @@ impl BindAddr {
         socket.bind(&bind_addr)
     }
 }
+
+fn bind_ip(socket: &Socket, ip: IpAddr) -> io::Result<()> {
+    let addr: SockAddr = SocketAddr::new(ip, 0).into();
+    socket.bind(&addr).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to bind to local address {ip}: {e}, the address may not be configured on this host"),
+        )
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_device(socket: &Socket, name: &InterfaceName) -> io::Result<()> {
+    socket
+        .bind_device(Some(name.as_bytes()))
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to bind to interface {name}: {e}")))
+}
+
+#[cfg(target_os = "linux")]
+fn set_transparent(socket: &Socket, family: AddressFamily) -> io::Result<()> {
+    let r = match family {
+        AddressFamily::Ipv4 => socket
+            .set_ip_transparent(true)
+            .and_then(|_| socket.set_freebind(true)),
+        AddressFamily::Ipv6 => {
+            set_ip_transparent_v6(socket, true).and_then(|_| socket.set_freebind_ipv6(true))
+        }
+    };
+    r.map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to enable transparent socket option, CAP_NET_ADMIN is required: {e}"),
+        )
+    })
+}
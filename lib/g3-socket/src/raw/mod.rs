@@ -18,13 +18,76 @@ use std::io;
 
 use socket2::Socket;
 
-use g3_types::net::{SocketBufferConfig, TcpMiscSockOpts, UdpMiscSockOpts};
+use g3_types::net::{SocketBufferConfig, TcpKeepAliveConfig, TcpMiscSockOpts, UdpMiscSockOpts};
 
 #[cfg(unix)]
 mod unix;
 #[cfg(windows)]
 mod windows;
 
+#[cfg(target_os = "linux")]
+fn set_netfilter_mark(socket: &Socket, mark: u32) -> io::Result<()> {
+    socket.set_mark(mark).map_err(|e| {
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            io::Error::new(
+                e.kind(),
+                format!("failed to set netfilter mark {mark}: {e}, CAP_NET_ADMIN is required"),
+            )
+        } else {
+            io::Error::new(
+                e.kind(),
+                format!("failed to set netfilter mark {mark}: {e}"),
+            )
+        }
+    })
+}
+
+fn set_type_of_service(socket: &Socket, tos: u8) -> io::Result<()> {
+    let is_ipv6 = socket.local_addr().map(|a| a.is_ipv6()).unwrap_or(false);
+    let r = if is_ipv6 {
+        set_ipv6_traffic_class(socket, tos)
+    } else {
+        socket.set_tos(tos as u32)
+    };
+    r.map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to set type of service {tos}: {e}"),
+        )
+    })
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn set_ipv6_traffic_class(socket: &Socket, tclass: u8) -> io::Result<()> {
+    socket.set_tclass_v6(tclass as u32)
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn set_ipv6_traffic_class(_socket: &Socket, _tclass: u8) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IPV6_TCLASS is not supported on this platform",
+    ))
+}
+
 #[derive(Debug)]
 pub struct RawSocket {
     inner: Option<Socket>,
@@ -67,15 +130,20 @@ impl RawSocket {
             socket.set_ttl(ttl)?;
         }
         if let Some(tos) = misc_opts.type_of_service {
-            socket.set_tos(tos as u32)?;
+            set_type_of_service(socket, tos)?;
         }
         #[cfg(target_os = "linux")]
         if let Some(mark) = misc_opts.netfilter_mark {
-            socket.set_mark(mark)?;
+            set_netfilter_mark(socket, mark)?;
         }
         Ok(())
     }
 
+    pub fn set_tcp_keepalive(&self, keepalive: &TcpKeepAliveConfig) -> io::Result<()> {
+        let socket = self.get_inner()?;
+        crate::tcp::set_tcp_keepalive(socket, keepalive)
+    }
+
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn trigger_tcp_quick_ack(&self) -> io::Result<()> {
         let socket = self.get_inner()?;
@@ -88,11 +156,11 @@ impl RawSocket {
             socket.set_ttl(ttl)?;
         }
         if let Some(tos) = misc_opts.type_of_service {
-            socket.set_tos(tos as u32)?;
+            set_type_of_service(socket, tos)?;
         }
         #[cfg(target_os = "linux")]
         if let Some(mark) = misc_opts.netfilter_mark {
-            socket.set_mark(mark)?;
+            set_netfilter_mark(socket, mark)?;
         }
         Ok(())
     }
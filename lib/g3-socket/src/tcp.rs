@@ -67,6 +67,12 @@ pub fn new_std_socket_to(
     let peer_family = AddressFamily::from(&peer_ip);
     let socket = new_tcp_socket(peer_family)?;
     bind.bind_for_connect(&socket, peer_family)?;
+    set_tcp_keepalive(&socket, keepalive)?;
+    RawSocket::from(&socket).set_tcp_misc_opts(misc_opts, default_set_nodelay)?;
+    Ok(std::net::TcpStream::from(socket))
+}
+
+pub(crate) fn set_tcp_keepalive(socket: &Socket, keepalive: &TcpKeepAliveConfig) -> io::Result<()> {
     #[cfg(windows)]
     if keepalive.is_enabled() {
         // set keepalive_idle
@@ -94,8 +100,7 @@ pub fn new_std_socket_to(
         let setting = TcpKeepalive::new().with_time(keepalive.idle_time());
         socket.set_tcp_keepalive(&setting)?;
     }
-    RawSocket::from(&socket).set_tcp_misc_opts(misc_opts, default_set_nodelay)?;
-    Ok(std::net::TcpStream::from(socket))
+    Ok(())
 }
 
 #[cfg(any(windows, target_os = "macos"))]
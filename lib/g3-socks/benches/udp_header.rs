@@ -0,0 +1,87 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![feature(test)]
+
+extern crate test;
+use test::Bencher;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use g3_types::net::UpstreamAddr;
+
+use g3_socks::v5::{UdpInput, UdpOutput};
+
+fn build_packet(ups: &UpstreamAddr) -> Vec<u8> {
+    let header_len = UdpOutput::calc_header_len(ups);
+    let mut buf = vec![0u8; header_len + 512];
+    UdpOutput::generate_header(&mut buf[..header_len], ups);
+    buf
+}
+
+fn ipv4_packet() -> Vec<u8> {
+    build_packet(&UpstreamAddr::from_ip_and_port(
+        IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+        53,
+    ))
+}
+
+fn ipv6_packet() -> Vec<u8> {
+    build_packet(&UpstreamAddr::from_ip_and_port(
+        IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888)),
+        53,
+    ))
+}
+
+fn domain_packet() -> Vec<u8> {
+    build_packet(&UpstreamAddr::from_host_str_and_port("dns.example.net", 53).unwrap())
+}
+
+#[bench]
+fn parse_header_ipv4(b: &mut Bencher) {
+    let buf = ipv4_packet();
+    b.iter(|| UdpInput::parse_header(test::black_box(&buf)).unwrap());
+}
+
+#[bench]
+fn parse_header_len_ipv4(b: &mut Bencher) {
+    let buf = ipv4_packet();
+    b.iter(|| UdpInput::parse_header_len(test::black_box(&buf)).unwrap());
+}
+
+#[bench]
+fn parse_header_ipv6(b: &mut Bencher) {
+    let buf = ipv6_packet();
+    b.iter(|| UdpInput::parse_header(test::black_box(&buf)).unwrap());
+}
+
+#[bench]
+fn parse_header_len_ipv6(b: &mut Bencher) {
+    let buf = ipv6_packet();
+    b.iter(|| UdpInput::parse_header_len(test::black_box(&buf)).unwrap());
+}
+
+#[bench]
+fn parse_header_domain(b: &mut Bencher) {
+    let buf = domain_packet();
+    b.iter(|| UdpInput::parse_header(test::black_box(&buf)).unwrap());
+}
+
+#[bench]
+fn parse_header_len_domain(b: &mut Bencher) {
+    let buf = domain_packet();
+    b.iter(|| UdpInput::parse_header_len(test::black_box(&buf)).unwrap());
+}
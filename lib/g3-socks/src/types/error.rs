@@ -15,6 +15,7 @@
  */
 
 use std::io;
+use std::net::SocketAddr;
 
 use thiserror::Error;
 
@@ -100,6 +101,10 @@ pub enum SocksConnectError {
     PeerTimeout,
     #[error("request failed: {0}")]
     RequestFailed(String),
+    #[error("udp associate not supported by peer")]
+    UdpAssociateUnsupported,
+    #[error("unusable udp relay address {0} returned by peer")]
+    InvalidUdpRelayAddr(SocketAddr),
 }
 
 impl From<SocksReplyParseError> for SocksConnectError {
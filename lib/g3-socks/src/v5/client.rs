@@ -14,7 +14,7 @@
  * limitations under the License.
  */
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
 use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 
@@ -72,9 +72,24 @@ where
     }
 }
 
+fn check_udp_relay_addr(addr: SocketAddr) -> Result<SocketAddr, SocksConnectError> {
+    if addr.port() == 0 {
+        return Err(SocksConnectError::InvalidUdpRelayAddr(addr));
+    }
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_multicast() || ip.is_broadcast() => {
+            Err(SocksConnectError::InvalidUdpRelayAddr(addr))
+        }
+        IpAddr::V6(ip) if ip.is_multicast() => Err(SocksConnectError::InvalidUdpRelayAddr(addr)),
+        // the unspecified ip means the same ip as the tcp peer
+        _ => Ok(addr),
+    }
+}
+
 /// udp associate to a socks5 proxy
 ///
-/// return the socket address that the client should send packets to
+/// return the socket address that the client should send packets to,
+/// the ip may be unspecified, which means the same as the tcp peer
 pub async fn socks5_udp_associate<S>(
     stream: &mut S,
     auth: &SocksAuth,
@@ -92,11 +107,59 @@ where
 
     let rsp = Socks5Reply::recv(stream).await?;
     match rsp {
-        Socks5Reply::Succeeded(addr) => Ok(addr),
+        Socks5Reply::Succeeded(addr) => check_udp_relay_addr(addr),
         Socks5Reply::ConnectionTimedOut => Err(SocksConnectError::PeerTimeout),
+        Socks5Reply::CommandNotSupported => Err(SocksConnectError::UdpAssociateUnsupported),
         _ => Err(SocksConnectError::RequestFailed(format!(
             "request failed: {}",
             rsp.error_message()
         ))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn udp_associate_with_reply(
+        reply: &'static [u8],
+    ) -> Result<SocketAddr, SocksConnectError> {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let server_task = tokio::spawn(async move {
+            let mut buf = [0u8; 3];
+            server.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, [0x05, 0x01, 0x00]);
+            server.write_all(&[0x05, 0x00]).await.unwrap();
+
+            let mut buf = [0u8; 10];
+            server.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf[1], 0x03);
+            server.write_all(reply).await.unwrap();
+        });
+
+        let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        let r = socks5_udp_associate(&mut client, &SocksAuth::None, local_addr).await;
+        server_task.await.unwrap();
+        r
+    }
+
+    #[tokio::test]
+    async fn udp_associate() {
+        let addr = udp_associate_with_reply(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0x04, 0x38])
+            .await
+            .unwrap();
+        assert_eq!(addr, SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1080));
+
+        let e = udp_associate_with_reply(&[0x05, 0x07, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap_err();
+        assert!(matches!(e, SocksConnectError::UdpAssociateUnsupported));
+
+        let e = udp_associate_with_reply(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0, 0])
+            .await
+            .unwrap_err();
+        assert!(matches!(e, SocksConnectError::InvalidUdpRelayAddr(_)));
+    }
+}
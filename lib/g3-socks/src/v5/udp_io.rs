@@ -89,6 +89,46 @@ impl UdpInput {
 
         Ok((off, addr))
     }
+
+    /// Get the header length without parsing the upstream address.
+    ///
+    /// This is the fast path for the receivers which don't care about the upstream address.
+    /// The header fields will still be validated, but no allocation will happen.
+    pub fn parse_header_len(buf: &[u8]) -> Result<usize, SocksUdpPacketError> {
+        let len = buf.len();
+        if len <= 8 {
+            return Err(SocksUdpPacketError::TooSmallPacket);
+        }
+
+        if buf[0] != 0x00 || buf[1] != 0x00 {
+            return Err(SocksUdpPacketError::ReservedNotZeroed);
+        }
+
+        if buf[2] != 0x00 {
+            return Err(SocksUdpPacketError::FragmentNotSupported);
+        }
+
+        let header_len = match buf[3] {
+            0x01 => UDP_HEADER_LEN_IPV4,
+            0x04 => UDP_HEADER_LEN_IPV6,
+            0x03 => {
+                let domain_len = buf[4] as usize;
+                let header_len = 4 + 1 + domain_len + 2;
+                if len < header_len {
+                    return Err(SocksUdpPacketError::TooSmallPacket);
+                }
+                if domain_len == 0 || std::str::from_utf8(&buf[5..5 + domain_len]).is_err() {
+                    return Err(SocksUdpPacketError::InvalidDomainString);
+                }
+                return Ok(header_len);
+            }
+            _ => return Err(SocksUdpPacketError::InvalidAddrType),
+        };
+        if len < header_len {
+            return Err(SocksUdpPacketError::TooSmallPacket);
+        }
+        Ok(header_len)
+    }
 }
 
 pub struct UdpOutput {}
@@ -178,3 +218,40 @@ impl Default for SocksUdpHeader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_len() {
+        let mut buf = [0u8; 64];
+
+        let ups = UpstreamAddr::from_ip_and_port(IpAddr::V4(Ipv4Addr::LOCALHOST), 53);
+        UdpOutput::generate_header(&mut buf, &ups);
+        let (off, addr) = UdpInput::parse_header(&buf).unwrap();
+        assert_eq!(addr, ups);
+        assert_eq!(UdpInput::parse_header_len(&buf).unwrap(), off);
+        assert!(UdpInput::parse_header_len(&buf[..off - 1]).is_err());
+
+        let ups = UpstreamAddr::from_ip_and_port(IpAddr::V6(Ipv6Addr::LOCALHOST), 443);
+        UdpOutput::generate_header(&mut buf, &ups);
+        let (off, addr) = UdpInput::parse_header(&buf).unwrap();
+        assert_eq!(addr, ups);
+        assert_eq!(UdpInput::parse_header_len(&buf).unwrap(), off);
+        assert!(UdpInput::parse_header_len(&buf[..off - 1]).is_err());
+
+        let ups = UpstreamAddr::from_host_str_and_port("www.example.net", 443).unwrap();
+        UdpOutput::generate_header(&mut buf, &ups);
+        let (off, addr) = UdpInput::parse_header(&buf).unwrap();
+        assert_eq!(addr, ups);
+        assert_eq!(UdpInput::parse_header_len(&buf).unwrap(), off);
+        assert!(UdpInput::parse_header_len(&buf[..off - 1]).is_err());
+
+        buf[2] = 0x01;
+        assert!(matches!(
+            UdpInput::parse_header_len(&buf),
+            Err(SocksUdpPacketError::FragmentNotSupported)
+        ));
+    }
+}
@@ -23,6 +23,7 @@ mod exact_host;
 mod exact_port;
 mod fx_hash;
 mod network;
+mod network_ordered;
 mod proxy_request;
 mod radix_trie;
 mod regex_set;
@@ -36,6 +37,7 @@ pub use child_domain::{AclChildDomainRule, AclChildDomainRuleBuilder};
 pub use exact_host::AclExactHostRule;
 pub use exact_port::AclExactPortRule;
 pub use network::{AclNetworkRule, AclNetworkRuleBuilder};
+pub use network_ordered::{AclOrderedNetworkRule, AclOrderedNetworkRuleBuilder};
 pub use proxy_request::AclProxyRequestRule;
 pub use regex_set::{AclRegexSetRule, AclRegexSetRuleBuilder};
 pub use user_agent::AclUserAgentRule;
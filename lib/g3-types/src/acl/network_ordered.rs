@@ -0,0 +1,164 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use ip_network::IpNetwork;
+
+use super::{AclAction, ActionContract};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct AclOrderedNetworkEntry<Action> {
+    network: IpNetwork,
+    action: Action,
+    reason: Option<Arc<str>>,
+}
+
+/// Network ACL rules which will be checked in the order they are added,
+/// and the first matched one takes effect.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AclOrderedNetworkRuleBuilder<Action = AclAction> {
+    rules: Vec<AclOrderedNetworkEntry<Action>>,
+    missed_action: Action,
+}
+
+impl<Action: ActionContract> AclOrderedNetworkRuleBuilder<Action> {
+    pub fn new(missed_action: Action) -> Self {
+        AclOrderedNetworkRuleBuilder {
+            rules: Vec::new(),
+            missed_action,
+        }
+    }
+
+    pub fn push_network(&mut self, network: IpNetwork, action: Action, reason: Option<String>) {
+        self.rules.push(AclOrderedNetworkEntry {
+            network,
+            action,
+            reason: reason.map(Arc::from),
+        });
+    }
+
+    #[inline]
+    pub fn missed_action(&self) -> Action {
+        self.missed_action
+    }
+
+    #[inline]
+    pub fn set_missed_action(&mut self, action: Action) {
+        self.missed_action = action;
+    }
+
+    pub fn build(&self) -> AclOrderedNetworkRule<Action> {
+        AclOrderedNetworkRule {
+            rules: self.rules.clone(),
+            default_action: self.missed_action,
+        }
+    }
+}
+
+pub struct AclOrderedNetworkRule<Action = AclAction> {
+    rules: Vec<AclOrderedNetworkEntry<Action>>,
+    default_action: Action,
+}
+
+impl<Action: ActionContract> AclOrderedNetworkRule<Action> {
+    /// Return the action and the optional reason of the first matched rule,
+    /// or the default action if no rule matches.
+    pub fn check(&self, ip: IpAddr) -> (bool, Action, Option<&str>) {
+        let ip = ip.to_canonical();
+        for rule in &self.rules {
+            if rule.network.contains(ip) {
+                return (true, rule.action, rule.reason.as_deref());
+            }
+        }
+        (false, self.default_action, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn net(s: &str) -> IpNetwork {
+        IpNetwork::from_str(s).unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn first_match() {
+        let mut builder = AclOrderedNetworkRuleBuilder::new(AclAction::Forbid);
+        builder.push_network(
+            net("192.168.1.0/24"),
+            AclAction::ForbidAndLog,
+            Some("guest network".to_string()),
+        );
+        builder.push_network(net("192.168.0.0/16"), AclAction::Permit, None);
+        builder.push_network(net("2001:db8::/32"), AclAction::Permit, None);
+        builder.push_network(net("2001:db8:1::/48"), AclAction::Forbid, None);
+
+        let rule = builder.build();
+        assert_eq!(
+            rule.check(ip("192.168.1.10")),
+            (true, AclAction::ForbidAndLog, Some("guest network"))
+        );
+        assert_eq!(
+            rule.check(ip("192.168.2.10")),
+            (true, AclAction::Permit, None)
+        );
+        // the wider network is added first, so the narrower one never takes effect
+        assert_eq!(
+            rule.check(ip("2001:db8:1::1")),
+            (true, AclAction::Permit, None)
+        );
+        assert_eq!(rule.check(ip("10.0.0.1")), (false, AclAction::Forbid, None));
+        assert_eq!(
+            rule.check(ip("2001:db9::1")),
+            (false, AclAction::Forbid, None)
+        );
+    }
+
+    #[test]
+    fn order_matters() {
+        let mut builder = AclOrderedNetworkRuleBuilder::new(AclAction::Permit);
+        builder.push_network(net("10.0.0.0/8"), AclAction::Permit, None);
+        builder.push_network(net("10.1.0.0/16"), AclAction::Forbid, None);
+        let rule = builder.build();
+        assert_eq!(rule.check(ip("10.1.0.1")), (true, AclAction::Permit, None));
+
+        let mut builder = AclOrderedNetworkRuleBuilder::new(AclAction::Permit);
+        builder.push_network(net("10.1.0.0/16"), AclAction::Forbid, None);
+        builder.push_network(net("10.0.0.0/8"), AclAction::Permit, None);
+        let rule = builder.build();
+        assert_eq!(rule.check(ip("10.1.0.1")), (true, AclAction::Forbid, None));
+        assert_eq!(rule.check(ip("10.2.0.1")), (true, AclAction::Permit, None));
+    }
+
+    #[test]
+    fn ipv4_mapped() {
+        let mut builder = AclOrderedNetworkRuleBuilder::new(AclAction::Permit);
+        builder.push_network(net("192.0.2.0/24"), AclAction::Forbid, None);
+        let rule = builder.build();
+        assert_eq!(
+            rule.check(ip("::ffff:192.0.2.1")),
+            (true, AclAction::Forbid, None)
+        );
+    }
+}
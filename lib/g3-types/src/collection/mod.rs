@@ -19,5 +19,7 @@ mod selective_vec;
 mod weighted_value;
 
 pub use named_value::NamedValue;
-pub use selective_vec::{SelectiveItem, SelectivePickPolicy, SelectiveVec, SelectiveVecBuilder};
+pub use selective_vec::{
+    SelectiveItem, SelectivePickOverride, SelectivePickPolicy, SelectiveVec, SelectiveVecBuilder,
+};
 pub use weighted_value::WeightedValue;
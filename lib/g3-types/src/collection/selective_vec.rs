@@ -49,6 +49,17 @@ impl FromStr for SelectivePickPolicy {
     }
 }
 
+/// Override the pick policy to get deterministic results.
+///
+/// This will disable load balancing, and should only be used for testing or debugging.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SelectivePickOverride {
+    /// Pick by the hash of the seed and the key, weight will be taken into account
+    Seeded(u64),
+    /// Always pick the node at this index in the configured order
+    Pinned(usize),
+}
+
 pub trait SelectiveItem {
     fn weight(&self) -> f64;
     fn weight_u32(&self) -> u32 {
@@ -91,19 +102,25 @@ impl<T: SelectiveItem> SelectiveVecBuilder<T> {
             }
         }
 
-        let mut nodes = self.inner;
+        let mut nodes: Vec<(usize, T)> = self.inner.into_iter().enumerate().collect();
         // reserve order for equal nodes
-        nodes.sort_by(|a, b| {
+        nodes.sort_by(|(_, a), (_, b)| {
             b.weight()
                 .partial_cmp(&a.weight())
                 .unwrap_or(Ordering::Equal)
         });
+        let mut config_order = vec![0; nodes.len()];
+        for (pos, (i, _)) in nodes.iter().enumerate() {
+            config_order[*i] = pos;
+        }
+        let nodes: Vec<T> = nodes.into_iter().map(|(_, v)| v).collect();
 
         let ketama_ring = ketama_ring_create(&nodes);
 
         Some(SelectiveVec {
             weighted,
             inner: nodes,
+            config_order,
             rr_id: atomic::AtomicUsize::new(0),
             ketama_ring,
         })
@@ -150,6 +167,8 @@ impl<T: SelectiveItem> Default for SelectiveVecBuilder<T> {
 pub struct SelectiveVec<T: SelectiveItem> {
     weighted: bool,
     inner: Vec<T>,
+    /// the position in `inner` of each node in the configured order
+    config_order: Vec<usize>,
     rr_id: atomic::AtomicUsize,
     ketama_ring: Vec<(usize, u32)>,
 }
@@ -309,6 +328,52 @@ impl<T: SelectiveItem> SelectiveVec<T> {
         b as u32
     }
 
+    /// Pick the node at `index` in the configured order, the index will wrap around if out of range
+    pub fn pick_pinned(&self, index: usize) -> &T {
+        match self.inner.len() {
+            0 => panic_on_empty!(),
+            1 => &self.inner[0],
+            len => {
+                let pos = self.config_order[index % len];
+                &self.inner[pos]
+            }
+        }
+    }
+
+    /// Pick a node by the hash of the seed and the key, the result is deterministic
+    /// for the same seed, key and nodes
+    pub fn pick_seeded<K>(&self, seed: u64, key: &K) -> &T
+    where
+        K: Hash + ?Sized,
+    {
+        match self.inner.len() {
+            0 => panic_on_empty!(),
+            1 => &self.inner[0],
+            len => {
+                let mut hasher = MetroHash64::with_seed(seed);
+                key.hash(&mut hasher);
+                let hash = hasher.finish();
+                if !self.weighted {
+                    return &self.inner[(hash % len as u64) as usize];
+                }
+
+                let total_weights: u64 = self.inner.iter().map(|v| v.weight_u32() as u64).sum();
+                if total_weights == 0 {
+                    return &self.inner[0];
+                }
+                let mut point = hash % total_weights;
+                for node in &self.inner {
+                    let weight = node.weight_u32() as u64;
+                    if point < weight {
+                        return node;
+                    }
+                    point -= weight;
+                }
+                &self.inner[0]
+            }
+        }
+    }
+
     pub fn pick_jump<K>(&self, key: &K) -> &T
     where
         K: Hash + ?Sized,
@@ -828,4 +893,61 @@ mod tests {
         assert!(r1[0].eq(r2[0]));
         assert!(r1[1].eq(r2[1]));
     }
+
+    #[test]
+    fn pick_pinned() {
+        let node1 = Node {
+            name: "node1".to_string(),
+            weight: 1f64,
+        };
+        let node2 = Node {
+            name: "node2".to_string(),
+            weight: 2f64,
+        };
+        let node3 = Node {
+            name: "node3".to_string(),
+            weight: 1f64,
+        };
+
+        let mut builder = SelectiveVecBuilder::with_capacity(3);
+        builder.insert(node1.clone());
+        builder.insert(node2.clone());
+        builder.insert(node3.clone());
+        let vec = builder.build().unwrap();
+
+        // the index is in the configured order, not the weight sorted order
+        assert!(node1.eq(vec.pick_pinned(0)));
+        assert!(node2.eq(vec.pick_pinned(1)));
+        assert!(node3.eq(vec.pick_pinned(2)));
+        assert!(node1.eq(vec.pick_pinned(3)));
+    }
+
+    #[test]
+    fn pick_seeded() {
+        let mut builder = SelectiveVecBuilder::with_capacity(3);
+        for i in 0..3 {
+            builder.insert(Node {
+                name: format!("node{i}"),
+                weight: (i + 1) as f64,
+            });
+        }
+        let vec = builder.build().unwrap();
+
+        for key in ["a", "b", "c", "d"] {
+            let r1 = vec.pick_seeded(42, key);
+            let r2 = vec.pick_seeded(42, key);
+            assert!(r1.eq(r2));
+        }
+
+        let mut seen = [false; 3];
+        for i in 0..64 {
+            let r = vec.pick_seeded(i, "k");
+            for (j, v) in seen.iter_mut().enumerate() {
+                if r.name == format!("node{j}") {
+                    *v = true;
+                }
+            }
+        }
+        assert_eq!(seen, [true; 3]);
+    }
 }
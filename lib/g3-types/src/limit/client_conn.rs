@@ -0,0 +1,245 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ahash::AHashMap;
+use anyhow::anyhow;
+
+const DEFAULT_IPV4_PREFIX: u8 = 32;
+const DEFAULT_IPV6_PREFIX: u8 = 64;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClientConnLimitConfig {
+    max_per_ip: NonZeroUsize,
+    ipv4_prefix: u8,
+    ipv6_prefix: u8,
+    tarpit_delay: Duration,
+}
+
+impl ClientConnLimitConfig {
+    pub fn new(max_per_ip: NonZeroUsize) -> Self {
+        ClientConnLimitConfig {
+            max_per_ip,
+            ipv4_prefix: DEFAULT_IPV4_PREFIX,
+            ipv6_prefix: DEFAULT_IPV6_PREFIX,
+            tarpit_delay: Duration::ZERO,
+        }
+    }
+
+    pub fn set_ipv4_prefix(&mut self, prefix: u8) -> anyhow::Result<()> {
+        if prefix == 0 || prefix > 32 {
+            return Err(anyhow!("invalid ipv4 prefix length {prefix}"));
+        }
+        self.ipv4_prefix = prefix;
+        Ok(())
+    }
+
+    pub fn set_ipv6_prefix(&mut self, prefix: u8) -> anyhow::Result<()> {
+        if prefix == 0 || prefix > 128 {
+            return Err(anyhow!("invalid ipv6 prefix length {prefix}"));
+        }
+        self.ipv6_prefix = prefix;
+        Ok(())
+    }
+
+    pub fn set_tarpit_delay(&mut self, delay: Duration) {
+        self.tarpit_delay = delay;
+    }
+
+    #[inline]
+    pub fn max_per_ip(&self) -> usize {
+        self.max_per_ip.get()
+    }
+
+    #[inline]
+    pub fn tarpit_delay(&self) -> Duration {
+        self.tarpit_delay
+    }
+
+    /// Get the network address that the client ip belongs to
+    fn network_of(&self, ip: IpAddr) -> IpAddr {
+        match ip.to_canonical() {
+            IpAddr::V4(ip4) => {
+                let mask = u32::MAX << (32 - self.ipv4_prefix as u32);
+                IpAddr::V4(Ipv4Addr::from(u32::from(ip4) & mask))
+            }
+            IpAddr::V6(ip6) => {
+                let mask = u128::MAX << (128 - self.ipv6_prefix as u32);
+                IpAddr::V6(Ipv6Addr::from(u128::from(ip6) & mask))
+            }
+        }
+    }
+}
+
+type ClientConnTable = Mutex<AHashMap<IpAddr, usize>>;
+
+/// Concurrent connection limiter for each client network
+pub struct ClientConnLimiter {
+    config: ClientConnLimitConfig,
+    table: Arc<ClientConnTable>,
+}
+
+impl ClientConnLimiter {
+    pub fn new(config: ClientConnLimitConfig) -> Self {
+        ClientConnLimiter {
+            config,
+            table: Arc::new(Mutex::new(AHashMap::new())),
+        }
+    }
+
+    /// Create a new limiter with the alive connections in the old one.
+    ///
+    /// The counts can only be kept if the prefix lengths are not changed.
+    #[must_use]
+    pub fn new_updated(&self, config: ClientConnLimitConfig) -> Self {
+        if self.config.ipv4_prefix == config.ipv4_prefix
+            && self.config.ipv6_prefix == config.ipv6_prefix
+        {
+            ClientConnLimiter {
+                config,
+                table: Arc::clone(&self.table),
+            }
+        } else {
+            ClientConnLimiter::new(config)
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &ClientConnLimitConfig {
+        &self.config
+    }
+
+    /// Try to get a permit for a new connection from the client ip.
+    ///
+    /// The connection count will be decreased when the returned permit is dropped.
+    pub fn try_acquire(&self, ip: IpAddr) -> Option<ClientConnPermit> {
+        let network = self.config.network_of(ip);
+        let mut table = self.table.lock().unwrap();
+        let count = table.entry(network).or_insert(0);
+        if *count >= self.config.max_per_ip() {
+            return None;
+        }
+        *count += 1;
+        Some(ClientConnPermit {
+            network,
+            table: Arc::clone(&self.table),
+        })
+    }
+
+    #[cfg(test)]
+    fn alive_count(&self, ip: IpAddr) -> usize {
+        let network = self.config.network_of(ip);
+        let table = self.table.lock().unwrap();
+        table.get(&network).copied().unwrap_or_default()
+    }
+}
+
+pub struct ClientConnPermit {
+    network: IpAddr,
+    table: Arc<ClientConnTable>,
+}
+
+impl Drop for ClientConnPermit {
+    fn drop(&mut self) {
+        let mut table = self.table.lock().unwrap();
+        if let Some(count) = table.get_mut(&self.network) {
+            if *count > 1 {
+                *count -= 1;
+            } else {
+                table.remove(&self.network);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn acquire_and_release() {
+        let config = ClientConnLimitConfig::new(NonZeroUsize::new(2).unwrap());
+        let limiter = ClientConnLimiter::new(config);
+
+        let ip = IpAddr::from_str("192.168.1.1").unwrap();
+        let p1 = limiter.try_acquire(ip).unwrap();
+        let p2 = limiter.try_acquire(ip).unwrap();
+        assert!(limiter.try_acquire(ip).is_none());
+        assert_eq!(limiter.alive_count(ip), 2);
+
+        let other = IpAddr::from_str("192.168.1.2").unwrap();
+        let p3 = limiter.try_acquire(other).unwrap();
+
+        drop(p1);
+        assert_eq!(limiter.alive_count(ip), 1);
+        let p4 = limiter.try_acquire(ip).unwrap();
+        assert!(limiter.try_acquire(ip).is_none());
+
+        drop(p2);
+        drop(p3);
+        drop(p4);
+        assert!(limiter.table.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn prefix_granularity() {
+        let mut config = ClientConnLimitConfig::new(NonZeroUsize::new(1).unwrap());
+        config.set_ipv4_prefix(24).unwrap();
+        assert!(config.set_ipv6_prefix(0).is_err());
+        let limiter = ClientConnLimiter::new(config);
+
+        let _p1 = limiter
+            .try_acquire(IpAddr::from_str("10.0.0.1").unwrap())
+            .unwrap();
+        assert!(limiter
+            .try_acquire(IpAddr::from_str("10.0.0.200").unwrap())
+            .is_none());
+        assert!(limiter
+            .try_acquire(IpAddr::from_str("::ffff:10.0.0.2").unwrap())
+            .is_none());
+        let _p2 = limiter
+            .try_acquire(IpAddr::from_str("10.0.1.1").unwrap())
+            .unwrap();
+
+        let _p3 = limiter
+            .try_acquire(IpAddr::from_str("2001:db8::1").unwrap())
+            .unwrap();
+        assert!(limiter
+            .try_acquire(IpAddr::from_str("2001:db8::ffff:1").unwrap())
+            .is_none());
+        let _p4 = limiter
+            .try_acquire(IpAddr::from_str("2001:db8:0:1::1").unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn keep_on_update() {
+        let config = ClientConnLimitConfig::new(NonZeroUsize::new(1).unwrap());
+        let limiter = ClientConnLimiter::new(config.clone());
+        let ip = IpAddr::from_str("127.0.0.1").unwrap();
+        let p1 = limiter.try_acquire(ip).unwrap();
+
+        let updated = limiter.new_updated(config);
+        assert!(updated.try_acquire(ip).is_none());
+        drop(p1);
+        let _p2 = updated.try_acquire(ip).unwrap();
+    }
+}
@@ -25,3 +25,6 @@ pub use stream_speed::GlobalStreamSpeedLimitConfig;
 
 mod datagram_speed;
 pub use datagram_speed::GlobalDatagramSpeedLimitConfig;
+
+mod client_conn;
+pub use client_conn::{ClientConnLimitConfig, ClientConnLimiter, ClientConnPermit};
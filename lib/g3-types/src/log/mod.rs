@@ -15,9 +15,11 @@
  */
 
 mod drop;
+mod sampling;
 mod stats;
 
 pub use drop::LogDropType;
+pub use sampling::{LogSampler, LogSamplingConfig, LogSamplingMethod};
 pub use stats::{LogDropSnapshot, LogDropStats, LogIoSnapshot, LogIoStats, LogSnapshot, LogStats};
 
 #[cfg(feature = "async-log")]
@@ -0,0 +1,152 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::num::NonZeroU32;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LogSamplingMethod {
+    /// log the 1st task of each N tasks
+    #[default]
+    Counter,
+    /// log the task if the hash of the task id falls in the 1/N range,
+    /// so the decision is deterministic for the same task id
+    TaskIdHash,
+}
+
+impl FromStr for LogSamplingMethod {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "counter" | "count" => Ok(LogSamplingMethod::Counter),
+            "task_id_hash" | "task_id" | "hash" => Ok(LogSamplingMethod::TaskIdHash),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Sample 1 of N tasks for logging.
+///
+/// The sampling only applies to normal events, error and block events should always be logged.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LogSamplingConfig {
+    rate: NonZeroU32,
+    method: LogSamplingMethod,
+}
+
+impl Default for LogSamplingConfig {
+    fn default() -> Self {
+        LogSamplingConfig {
+            rate: NonZeroU32::MIN,
+            method: LogSamplingMethod::default(),
+        }
+    }
+}
+
+impl LogSamplingConfig {
+    pub fn new(rate: NonZeroU32, method: LogSamplingMethod) -> Self {
+        LogSamplingConfig { rate, method }
+    }
+
+    #[inline]
+    pub fn rate(&self) -> NonZeroU32 {
+        self.rate
+    }
+
+    #[inline]
+    pub fn method(&self) -> LogSamplingMethod {
+        self.method
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.rate.get() > 1
+    }
+}
+
+pub struct LogSampler {
+    config: LogSamplingConfig,
+    counter: AtomicU64,
+}
+
+impl LogSampler {
+    pub fn new(config: LogSamplingConfig) -> Self {
+        LogSampler {
+            config,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Decide whether the normal events of the task should be logged
+    pub fn sample(&self, task_id: &[u8]) -> bool {
+        let rate = u64::from(self.config.rate.get());
+        if rate == 1 {
+            return true;
+        }
+        match self.config.method {
+            LogSamplingMethod::Counter => self.counter.fetch_add(1, Ordering::Relaxed) % rate == 0,
+            LogSamplingMethod::TaskIdHash => fnv1a_hash(task_id) % rate == 0,
+        }
+    }
+}
+
+/// The FNV-1a hash, which is stable across processes
+fn fnv1a_hash(data: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    data.iter().fold(OFFSET_BASIS, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter() {
+        let sampler = LogSampler::new(LogSamplingConfig::new(
+            NonZeroU32::new(4).unwrap(),
+            LogSamplingMethod::Counter,
+        ));
+        let sampled = (0u8..16).filter(|i| sampler.sample(&[*i])).count();
+        assert_eq!(sampled, 4);
+
+        let sampler = LogSampler::new(LogSamplingConfig::default());
+        assert!((0u8..16).all(|i| sampler.sample(&[i])));
+    }
+
+    #[test]
+    fn task_id_hash() {
+        let config =
+            LogSamplingConfig::new(NonZeroU32::new(8).unwrap(), LogSamplingMethod::TaskIdHash);
+        let sampler1 = LogSampler::new(config);
+        let sampler2 = LogSampler::new(config);
+        let mut sampled = 0;
+        for i in 0u32..1024 {
+            let id = i.to_be_bytes();
+            let r = sampler1.sample(&id);
+            assert_eq!(sampler2.sample(&id), r);
+            if r {
+                sampled += 1;
+            }
+        }
+        assert!(sampled > 0 && sampled < 1024);
+    }
+}
@@ -34,6 +34,30 @@ pub enum ConnectError {
     UnspecifiedError(io::Error),
 }
 
+impl ConnectError {
+    pub fn brief(&self) -> &'static str {
+        match self {
+            ConnectError::ConnectionRefused => "ConnectionRefused",
+            ConnectError::ConnectionReset => "ConnectionReset",
+            ConnectError::NetworkUnreachable => "NetworkUnreachable",
+            ConnectError::HostUnreachable => "HostUnreachable",
+            ConnectError::TimedOut => "TimedOut",
+            ConnectError::UnspecifiedError(_) => "UnspecifiedError",
+        }
+    }
+
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            ConnectError::ConnectionRefused => Some(libc::ECONNREFUSED),
+            ConnectError::ConnectionReset => Some(libc::ECONNRESET),
+            ConnectError::NetworkUnreachable => Some(libc::ENETUNREACH),
+            ConnectError::HostUnreachable => Some(libc::EHOSTUNREACH),
+            ConnectError::TimedOut => Some(libc::ETIMEDOUT),
+            ConnectError::UnspecifiedError(e) => e.raw_os_error(),
+        }
+    }
+}
+
 impl From<io::Error> for ConnectError {
     fn from(e: io::Error) -> Self {
         match e.kind() {
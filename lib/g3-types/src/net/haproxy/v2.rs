@@ -54,6 +54,7 @@ const PP2_TYPE_CUSTOM_TASK_ID: u8 = 0xE3;
 const PP2_TYPE_CUSTOM_PROTOCOL: u8 = 0xE4;
 const PP2_TYPE_CUSTOM_MATCH_ID: u8 = 0xE5;
 const PP2_TYPE_CUSTOM_PAYLOAD_LEN: u8 = 0xE6;
+const PP2_TYPE_CUSTOM_PAYLOAD_VERSION: u8 = 0xE7;
 
 pub struct ProxyProtocolV2Encoder {
     buf: [u8; V2_BUF_CAP],
@@ -168,6 +169,10 @@ impl ProxyProtocolV2Encoder {
         self.push_tlv(PP2_TYPE_CUSTOM_PAYLOAD_LEN, &bytes)
     }
 
+    pub fn push_payload_version(&mut self, version: u8) -> Result<(), ProxyProtocolEncodeError> {
+        self.push_tlv(PP2_TYPE_CUSTOM_PAYLOAD_VERSION, &[version])
+    }
+
     pub fn finalize(&mut self) -> &[u8] {
         let data_len = (self.len - V2_HDR_LEN) as u16; // won't overlap
         let b = data_len.to_be_bytes();
@@ -88,6 +88,27 @@ impl Host {
             Host::from_domain_str(s).ok()
         }
     }
+
+    /// Parse the host address like [Self::parse_smtp_host_address], and also accept bare IPv4 /
+    /// IPv6 addresses and the `[IPv6:...]` address literal defined in RFC 5321
+    pub fn parse_smtp_host_address_lenient(buf: &[u8]) -> Option<Self> {
+        let Ok(s) = std::str::from_utf8(buf) else {
+            return None;
+        };
+        let literal = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .unwrap_or(s);
+        if literal.len() > 5 && literal.as_bytes()[..5].eq_ignore_ascii_case(b"ipv6:") {
+            return Ipv6Addr::from_str(&literal[5..])
+                .map(|v6| Host::Ip(IpAddr::V6(v6)))
+                .ok();
+        }
+        if let Ok(ip) = IpAddr::from_str(literal) {
+            return Some(Host::Ip(ip));
+        }
+        Host::parse_smtp_host_address(buf)
+    }
 }
 
 impl fmt::Display for Host {
@@ -184,5 +205,28 @@ mod tests {
 
         let host = Host::parse_smtp_host_address(b"Ipv6:2001:db8::1").unwrap();
         assert_eq!(host, Host::Ip(IpAddr::from_str("2001:db8::1").unwrap()));
+
+        assert!(Host::parse_smtp_host_address(b"[IPv6:2001:db8::1]").is_none());
+    }
+
+    #[test]
+    fn smtp_address_lenient() {
+        let host = Host::parse_smtp_host_address_lenient(b"[IPv6:2001:db8::1]").unwrap();
+        assert_eq!(host, Host::Ip(IpAddr::from_str("2001:db8::1").unwrap()));
+
+        let host = Host::parse_smtp_host_address_lenient(b"2001:db8::1").unwrap();
+        assert_eq!(host, Host::Ip(IpAddr::from_str("2001:db8::1").unwrap()));
+
+        let host = Host::parse_smtp_host_address_lenient(b"192.0.2.1").unwrap();
+        assert_eq!(host, Host::Ip(IpAddr::from_str("192.0.2.1").unwrap()));
+
+        let host = Host::parse_smtp_host_address_lenient(b"[192.0.2.1]").unwrap();
+        assert_eq!(host, Host::Ip(IpAddr::from_str("192.0.2.1").unwrap()));
+
+        let host = Host::parse_smtp_host_address_lenient(b"mx.example.net").unwrap();
+        assert_eq!(host, Host::Domain(Arc::from("mx.example.net")));
+
+        assert!(Host::parse_smtp_host_address_lenient(b"[IPv6:192.0.2.1]").is_none());
+        assert!(Host::parse_smtp_host_address_lenient(b"").is_none());
     }
 }
@@ -0,0 +1,158 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Write;
+
+use bytes::Bytes;
+use http::{header, HeaderMap, HeaderName, HeaderValue, Response, StatusCode, Version};
+
+use super::{HttpHeaderMap, HttpHeaderValue};
+
+const RESERVED_HEADERS: [HeaderName; 6] = [
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::TRAILER,
+    header::UPGRADE,
+];
+
+/// A custom HTTP response that will be sent to the client when blocking a request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpBlockResponse {
+    status: StatusCode,
+    headers: HttpHeaderMap,
+    body: Bytes,
+}
+
+impl Default for HttpBlockResponse {
+    fn default() -> Self {
+        HttpBlockResponse::new(StatusCode::FORBIDDEN)
+    }
+}
+
+impl HttpBlockResponse {
+    pub fn new(status: StatusCode) -> Self {
+        HttpBlockResponse {
+            status,
+            headers: HttpHeaderMap::default(),
+            body: Bytes::new(),
+        }
+    }
+
+    #[inline]
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: StatusCode) {
+        self.status = status;
+    }
+
+    #[inline]
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    pub fn set_body<T: Into<Bytes>>(&mut self, body: T) {
+        self.body = body.into();
+    }
+
+    /// Add a custom header.
+    ///
+    /// The message framing headers are not allowed, as they will always be set automatically.
+    pub fn append_header(
+        &mut self,
+        name: HeaderName,
+        value: HttpHeaderValue,
+    ) -> Result<(), HeaderName> {
+        if RESERVED_HEADERS.contains(&name) {
+            return Err(name);
+        }
+        self.headers.append(name, value);
+        Ok(())
+    }
+
+    /// Serialize the whole response for HTTP/1.x, the connection should be closed after sent
+    pub fn serialize_h1(&self, version: Version) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(256 + self.body.len());
+        let version = match version {
+            Version::HTTP_09 | Version::HTTP_10 => Version::HTTP_10,
+            _ => Version::HTTP_11,
+        };
+        let _ = write!(
+            buf,
+            "{version:?} {} {}\r\n",
+            self.status.as_str(),
+            self.status
+                .canonical_reason()
+                .unwrap_or("<unknown status code>"),
+        );
+        self.headers.write_to_buf(&mut buf);
+        let _ = write!(buf, "Content-Length: {}\r\n", self.body.len());
+        buf.extend_from_slice(b"Connection: close\r\n\r\n");
+        buf.extend_from_slice(&self.body);
+        buf
+    }
+
+    /// Build the response head for HTTP/2, the body should be sent in data frames
+    pub fn build_h2_response(&self) -> Response<()> {
+        let mut rsp = Response::new(());
+        *rsp.status_mut() = self.status;
+        *rsp.version_mut() = Version::HTTP_2;
+        let mut headers: HeaderMap = (&self.headers).into();
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(self.body.len()));
+        *rsp.headers_mut() = headers;
+        rsp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn serialize_h1() {
+        let mut rsp = HttpBlockResponse::default();
+        rsp.append_header(
+            header::CONTENT_TYPE,
+            HttpHeaderValue::from_static("text/plain"),
+        )
+        .unwrap();
+        rsp.set_body(Bytes::from_static(b"blocked"));
+        assert!(rsp
+            .append_header(
+                header::CONTENT_LENGTH,
+                HttpHeaderValue::from_str("1").unwrap()
+            )
+            .is_err());
+
+        let data = rsp.serialize_h1(Version::HTTP_11);
+        assert_eq!(
+            data.as_slice(),
+            b"HTTP/1.1 403 Forbidden\r\n\
+              content-type: text/plain\r\n\
+              Content-Length: 7\r\n\
+              Connection: close\r\n\r\n\
+              blocked"
+        );
+
+        let h2_rsp = rsp.build_h2_response();
+        assert_eq!(h2_rsp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(h2_rsp.headers().get(header::CONTENT_LENGTH).unwrap(), "7");
+    }
+}
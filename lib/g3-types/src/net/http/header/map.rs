@@ -14,12 +14,12 @@
  * limitations under the License.
  */
 
-use http::header::{AsHeaderName, Drain, GetAll};
+use http::header::{AsHeaderName, Drain, Entry, GetAll};
 use http::{HeaderMap, HeaderName};
 
 use super::HttpHeaderValue;
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct HttpHeaderMap {
     inner: HeaderMap<HttpHeaderValue>,
 }
@@ -30,6 +30,18 @@ impl HttpHeaderMap {
         self.inner.is_empty()
     }
 
+    /// Get the number of distinct header names
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.keys_len()
+    }
+
+    /// Get the number of header values, which may be larger than [Self::len]
+    #[inline]
+    pub fn values_len(&self) -> usize {
+        self.inner.len()
+    }
+
     #[inline]
     pub fn insert(&mut self, name: HeaderName, value: HttpHeaderValue) -> Option<HttpHeaderValue> {
         self.inner.insert(name, value)
@@ -72,6 +84,38 @@ impl HttpHeaderMap {
     pub fn drain(&mut self) -> Drain<'_, HttpHeaderValue> {
         self.inner.drain()
     }
+
+    /// Append all values in `other` to this map
+    pub fn extend(&mut self, other: &HttpHeaderMap) {
+        other.for_each(|name, value| {
+            self.inner.append(name, value.clone());
+        });
+    }
+
+    /// Replace all values of the headers that also exist in `other` with the ones in `other`
+    pub fn set_all(&mut self, other: &HttpHeaderMap) {
+        for name in other.inner.keys() {
+            let mut values = other.inner.get_all(name).iter().cloned();
+            let Some(first) = values.next() else {
+                continue;
+            };
+            match self.inner.entry(name) {
+                Entry::Occupied(mut entry) => {
+                    entry.insert(first);
+                    values.for_each(|v| entry.append(v));
+                }
+                Entry::Vacant(entry) => {
+                    let mut entry = entry.insert_entry(first);
+                    values.for_each(|v| entry.append(v));
+                }
+            }
+        }
+    }
+
+    /// Write all headers as header lines
+    pub fn write_to_buf(&self, buf: &mut Vec<u8>) {
+        self.for_each(|name, value| value.write_to_buf(name, buf));
+    }
 }
 
 impl From<HttpHeaderMap> for HeaderMap {
@@ -16,11 +16,13 @@
 
 mod map;
 mod name;
+mod stack;
 mod value;
 
 pub use map::HttpHeaderMap;
 pub use name::HttpOriginalHeaderName;
-pub use value::HttpHeaderValue;
+pub use stack::{HttpHeaderMergeMode, HttpHeaderStack};
+pub use value::{HttpHeaderValue, HttpHeaderValueError};
 
 mod forwarded;
 mod server_id;
@@ -19,7 +19,7 @@ use std::ops::Deref;
 
 use smol_str::SmolStr;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct HttpOriginalHeaderName(SmolStr);
 
 impl HttpOriginalHeaderName {
@@ -0,0 +1,228 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::str::FromStr;
+
+use http::HeaderName;
+
+use super::{HttpHeaderMap, HttpHeaderValue};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HttpHeaderMergeMode {
+    /// Replace all values of the same header set by earlier layers
+    Set,
+    /// Append to the values of the same header set by earlier layers
+    #[default]
+    Append,
+}
+
+impl FromStr for HttpHeaderMergeMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "set" | "override" => Ok(HttpHeaderMergeMode::Set),
+            "append" => Ok(HttpHeaderMergeMode::Append),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An ordered stack of header maps, which should be pushed from the lowest precedence
+/// (e.g. global defaults) to the highest precedence (e.g. per-request).
+///
+/// Each layer is merged on top of the result of all layers before it, according to its mode.
+#[derive(Clone, Default)]
+pub struct HttpHeaderStack<'a> {
+    layers: Vec<(HttpHeaderMergeMode, &'a HttpHeaderMap)>,
+}
+
+impl<'a> HttpHeaderStack<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.layers.iter().all(|(_, map)| map.is_empty())
+    }
+
+    pub fn push(&mut self, mode: HttpHeaderMergeMode, headers: &'a HttpHeaderMap) {
+        if !headers.is_empty() {
+            self.layers.push((mode, headers));
+        }
+    }
+
+    /// Get the final merged headers in order, this should be called only once for each request.
+    ///
+    /// The values of a header replaced by a `set` layer will take the position of its first
+    /// existing value, and all other headers will be kept in the order they are pushed.
+    pub fn merge(&self) -> Vec<(HeaderName, HttpHeaderValue)> {
+        let mut merged: Vec<(HeaderName, HttpHeaderValue)> = Vec::new();
+        for (mode, headers) in &self.layers {
+            match mode {
+                HttpHeaderMergeMode::Set => {
+                    // values of the same name are iterated together
+                    let mut insert_at: Option<(HeaderName, usize)> = None;
+                    headers.for_each(|name, value| {
+                        let pos = match &mut insert_at {
+                            Some((last, pos)) if last == name => {
+                                *pos += 1;
+                                *pos
+                            }
+                            _ => {
+                                let pos = merged
+                                    .iter()
+                                    .position(|(n, _)| n == name)
+                                    .unwrap_or(merged.len());
+                                merged.retain(|(n, _)| n != name);
+                                insert_at = Some((name.clone(), pos));
+                                pos
+                            }
+                        };
+                        merged.insert(pos, (name.clone(), value.clone()));
+                    });
+                }
+                HttpHeaderMergeMode::Append => headers.for_each(|name, value| {
+                    merged.push((name.clone(), value.clone()));
+                }),
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::HttpHeaderValue;
+    use http::HeaderName;
+
+    fn single(name: &'static str, value: &'static str) -> HttpHeaderMap {
+        let mut map = HttpHeaderMap::default();
+        map.append(
+            HeaderName::from_static(name),
+            HttpHeaderValue::from_static(value),
+        );
+        map
+    }
+
+    fn values(merged: &[(HeaderName, HttpHeaderValue)], name: &'static str) -> Vec<String> {
+        merged
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.to_str().to_string())
+            .collect()
+    }
+
+    fn names(merged: &[(HeaderName, HttpHeaderValue)]) -> Vec<&str> {
+        merged.iter().map(|(n, _)| n.as_str()).collect()
+    }
+
+    #[test]
+    fn merge_mode() {
+        assert_eq!(
+            HttpHeaderMergeMode::from_str("Set").unwrap(),
+            HttpHeaderMergeMode::Set
+        );
+        assert_eq!(
+            HttpHeaderMergeMode::from_str("append").unwrap(),
+            HttpHeaderMergeMode::Append
+        );
+        assert!(HttpHeaderMergeMode::from_str("remove").is_err());
+    }
+
+    #[test]
+    fn four_layers() {
+        let mut global = single("x-tag", "global");
+        global.append(
+            HeaderName::from_static("x-global"),
+            HttpHeaderValue::from_static("1"),
+        );
+        let escaper = single("x-tag", "escaper");
+        let user = single("x-tag", "user");
+        let request = single("x-tag", "request");
+        let empty = HttpHeaderMap::default();
+
+        let mut stack = HttpHeaderStack::default();
+        assert!(stack.is_empty());
+        stack.push(HttpHeaderMergeMode::Append, &global);
+        stack.push(HttpHeaderMergeMode::Append, &escaper);
+        stack.push(HttpHeaderMergeMode::Set, &user);
+        stack.push(HttpHeaderMergeMode::Append, &empty);
+        stack.push(HttpHeaderMergeMode::Append, &request);
+        assert!(!stack.is_empty());
+
+        let merged = stack.merge();
+        assert_eq!(values(&merged, "x-tag"), vec!["user", "request"]);
+        assert_eq!(values(&merged, "x-global"), vec!["1"]);
+
+        let mut stack = HttpHeaderStack::default();
+        stack.push(HttpHeaderMergeMode::Append, &global);
+        stack.push(HttpHeaderMergeMode::Append, &escaper);
+        stack.push(HttpHeaderMergeMode::Append, &user);
+        stack.push(HttpHeaderMergeMode::Set, &request);
+        let merged = stack.merge();
+        assert_eq!(values(&merged, "x-tag"), vec!["request"]);
+        assert_eq!(values(&merged, "x-global"), vec!["1"]);
+
+        let mut stack = HttpHeaderStack::default();
+        stack.push(HttpHeaderMergeMode::Set, &global);
+        stack.push(HttpHeaderMergeMode::Append, &escaper);
+        stack.push(HttpHeaderMergeMode::Append, &user);
+        stack.push(HttpHeaderMergeMode::Append, &request);
+        let merged = stack.merge();
+        assert_eq!(
+            values(&merged, "x-tag"),
+            vec!["global", "escaper", "user", "request"]
+        );
+
+        let mut buf = Vec::new();
+        single("x-tag", "request").write_to_buf(&mut buf);
+        assert_eq!(buf.as_slice(), b"x-tag: request\r\n");
+    }
+
+    #[test]
+    fn keep_order() {
+        let mut global = single("x-a", "1");
+        global.append(
+            HeaderName::from_static("x-b"),
+            HttpHeaderValue::from_static("1"),
+        );
+        global.append(
+            HeaderName::from_static("x-c"),
+            HttpHeaderValue::from_static("1"),
+        );
+        let escaper = single("x-a", "2");
+        let mut user = single("x-b", "2");
+        user.append(
+            HeaderName::from_static("x-b"),
+            HttpHeaderValue::from_static("3"),
+        );
+        let request = single("x-a", "3");
+
+        let mut stack = HttpHeaderStack::default();
+        stack.push(HttpHeaderMergeMode::Append, &global);
+        stack.push(HttpHeaderMergeMode::Append, &escaper);
+        stack.push(HttpHeaderMergeMode::Set, &user);
+        stack.push(HttpHeaderMergeMode::Set, &request);
+        let merged = stack.merge();
+        assert_eq!(names(&merged), vec!["x-a", "x-b", "x-b", "x-c"]);
+        assert_eq!(values(&merged, "x-a"), vec!["3"]);
+        assert_eq!(values(&merged, "x-b"), vec!["2", "3"]);
+
+        let mut stack = HttpHeaderStack::default();
+        stack.push(HttpHeaderMergeMode::Append, &global);
+        stack.push(HttpHeaderMergeMode::Append, &escaper);
+        let merged = stack.merge();
+        assert_eq!(names(&merged), vec!["x-a", "x-b", "x-c", "x-a"]);
+    }
+}
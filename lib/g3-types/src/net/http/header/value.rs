@@ -18,10 +18,21 @@ use std::str::FromStr;
 
 use bytes::{BufMut, Bytes};
 use http::{HeaderName, HeaderValue};
+use thiserror::Error;
 
 use super::HttpOriginalHeaderName;
 
-#[derive(Clone)]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HttpHeaderValueError {
+    #[error("obsolete line folding found at offset {0}")]
+    ObsoleteLineFolding(usize),
+    #[error("invalid byte {1:#04x} at offset {0}")]
+    InvalidByte(usize, u8),
+    #[error("not valid utf-8")]
+    NotUtf8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct HttpHeaderValue {
     inner: Bytes,
     original_name: Option<HttpOriginalHeaderName>,
@@ -50,6 +61,54 @@ impl HttpHeaderValue {
         }
     }
 
+    /// Build from the raw bytes, which should not contain any line folding.
+    pub fn from_bytes_strict(buf: &[u8]) -> Result<Self, HttpHeaderValueError> {
+        for (i, b) in buf.iter().enumerate() {
+            if is_valid(*b) {
+                continue;
+            }
+            if is_obs_fold_start(buf, i) {
+                return Err(HttpHeaderValueError::ObsoleteLineFolding(i));
+            }
+            return Err(HttpHeaderValueError::InvalidByte(i, *b));
+        }
+        std::str::from_utf8(buf).map_err(|_| HttpHeaderValueError::NotUtf8)?;
+        Ok(HttpHeaderValue {
+            inner: Bytes::copy_from_slice(buf),
+            original_name: None,
+        })
+    }
+
+    /// Build from the raw bytes, and unfold the obsolete line folding if found.
+    ///
+    /// Each obs-fold, which is a line break followed by one or more SP / HTAB, will be replaced
+    /// by a single SP. This is lossy, as the original whitespaces can not be recovered.
+    pub fn from_bytes_unfold(buf: &[u8]) -> Result<Self, HttpHeaderValueError> {
+        let mut value = Vec::with_capacity(buf.len());
+        let mut i = 0;
+        while i < buf.len() {
+            let b = buf[i];
+            if is_valid(b) {
+                value.push(b);
+                i += 1;
+                continue;
+            }
+            if !is_obs_fold_start(buf, i) {
+                return Err(HttpHeaderValueError::InvalidByte(i, b));
+            }
+            i += if b == b'\r' { 2 } else { 1 };
+            while i < buf.len() && matches!(buf[i], b' ' | b'\t') {
+                i += 1;
+            }
+            value.push(b' ');
+        }
+        let value = String::from_utf8(value).map_err(|_| HttpHeaderValueError::NotUtf8)?;
+        Ok(HttpHeaderValue {
+            inner: Bytes::from(value),
+            original_name: None,
+        })
+    }
+
     pub fn from_static(value: &'static str) -> Self {
         HttpHeaderValue {
             inner: Bytes::from_static(value.as_bytes()),
@@ -117,3 +176,46 @@ impl From<&HttpHeaderValue> for HeaderValue {
 fn is_valid(b: u8) -> bool {
     b >= 32 && b != 127 || b == b'\t'
 }
+
+/// Check if there is a CRLF (or bare LF) followed by SP / HTAB at the offset
+fn is_obs_fold_start(buf: &[u8], offset: usize) -> bool {
+    let left = &buf[offset..];
+    let left = match left {
+        [b'\r', b'\n', ..] => &left[2..],
+        [b'\n', ..] => &left[1..],
+        _ => return false,
+    };
+    matches!(left.first(), Some(b' ' | b'\t'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes() {
+        let v = HttpHeaderValue::from_bytes_strict(b"text/html; charset=utf-8").unwrap();
+        assert_eq!(v.to_str(), "text/html; charset=utf-8");
+
+        let folded = b"a,\r\n \t b,\n\tc";
+        assert_eq!(
+            HttpHeaderValue::from_bytes_strict(folded),
+            Err(HttpHeaderValueError::ObsoleteLineFolding(2))
+        );
+        let v = HttpHeaderValue::from_bytes_unfold(folded).unwrap();
+        assert_eq!(v.to_str(), "a, b, c");
+
+        assert_eq!(
+            HttpHeaderValue::from_bytes_unfold(b"a\r\nb"),
+            Err(HttpHeaderValueError::InvalidByte(1, b'\r'))
+        );
+        assert_eq!(
+            HttpHeaderValue::from_bytes_unfold(b"a\x00b"),
+            Err(HttpHeaderValueError::InvalidByte(1, 0))
+        );
+        assert_eq!(
+            HttpHeaderValue::from_bytes_strict(b"a\xffb"),
+            Err(HttpHeaderValueError::NotUtf8)
+        );
+    }
+}
@@ -15,13 +15,17 @@
  */
 
 mod auth;
+mod block;
 mod capability;
 mod header;
 mod keepalive;
+mod rewrite;
 mod upgrade;
 
 pub use auth::{HttpAuth, HttpBasicAuth};
+pub use block::HttpBlockResponse;
 pub use capability::*;
 pub use header::*;
 pub use keepalive::HttpKeepAliveConfig;
+pub use rewrite::{HttpPathRewriteError, HttpPathRewriteRule, HttpPathRewriter};
 pub use upgrade::{HttpUpgradeToken, HttpUpgradeTokenParseError};
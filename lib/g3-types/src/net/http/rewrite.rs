@@ -0,0 +1,270 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::str::FromStr;
+
+use http::uri::{PathAndQuery, Uri};
+use percent_encoding::{AsciiSet, CONTROLS};
+use regex::Regex;
+use thiserror::Error;
+
+/// Chars that are not allowed in the path component and should be percent encoded
+const PATH_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'|')
+    .add(b'[')
+    .add(b']');
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HttpPathRewriteError {
+    #[error("the rewritten path {0} is not absolute")]
+    NotAbsolutePath(String),
+    #[error("the rewritten path {0} contains invalid percent encoding")]
+    InvalidPercentEncoding(String),
+    #[error("the rewritten request target {0} is invalid")]
+    InvalidRequestTarget(String),
+}
+
+#[derive(Clone, Debug)]
+pub enum HttpPathRewriteRule {
+    /// Strip the prefix if the path is equal to it or the next char after it is '/'
+    StripPrefix(String),
+    /// Add the prefix to the path
+    AddPrefix(String),
+    /// Replace the first match of the regex in the path
+    RegexReplace(Regex, String),
+}
+
+impl PartialEq for HttpPathRewriteRule {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (HttpPathRewriteRule::StripPrefix(a), HttpPathRewriteRule::StripPrefix(b)) => a == b,
+            (HttpPathRewriteRule::AddPrefix(a), HttpPathRewriteRule::AddPrefix(b)) => a == b,
+            (
+                HttpPathRewriteRule::RegexReplace(r1, s1),
+                HttpPathRewriteRule::RegexReplace(r2, s2),
+            ) => r1.as_str() == r2.as_str() && s1 == s2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for HttpPathRewriteRule {}
+
+impl HttpPathRewriteRule {
+    /// Apply the rule to the raw path, return `None` if not changed
+    fn apply(&self, path: &str) -> Option<String> {
+        match self {
+            HttpPathRewriteRule::StripPrefix(prefix) => {
+                let prefix = prefix.strip_suffix('/').unwrap_or(prefix);
+                let left = path.strip_prefix(prefix)?;
+                if left.is_empty() {
+                    Some("/".to_string())
+                } else if left.starts_with('/') {
+                    Some(left.to_string())
+                } else {
+                    None
+                }
+            }
+            HttpPathRewriteRule::AddPrefix(prefix) => {
+                let prefix = prefix.strip_suffix('/').unwrap_or(prefix);
+                if prefix.is_empty() {
+                    None
+                } else if path.starts_with('/') {
+                    Some(format!("{prefix}{path}"))
+                } else {
+                    Some(format!("{prefix}/{path}"))
+                }
+            }
+            HttpPathRewriteRule::RegexReplace(regex, replace) => {
+                if regex.is_match(path) {
+                    Some(regex.replace(path, replace.as_str()).into_owned())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Rewrite the path of the request target by an ordered list of rules.
+///
+/// The query will be kept unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpPathRewriter {
+    rules: Vec<HttpPathRewriteRule>,
+}
+
+impl HttpPathRewriter {
+    pub fn push(&mut self, rule: HttpPathRewriteRule) {
+        self.rules.push(rule);
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Rewrite the path and return the new path and query, or `None` if no rule matched
+    pub fn rewrite(&self, pa: &PathAndQuery) -> Result<Option<PathAndQuery>, HttpPathRewriteError> {
+        let mut path = None;
+        for rule in &self.rules {
+            let cur = path.as_deref().unwrap_or(pa.path());
+            if let Some(new) = rule.apply(cur) {
+                path = Some(new);
+            }
+        }
+        let Some(path) = path else {
+            return Ok(None);
+        };
+
+        if !path.starts_with('/') {
+            return Err(HttpPathRewriteError::NotAbsolutePath(path));
+        }
+        let path = percent_encoding::utf8_percent_encode(&path, PATH_ENCODE_SET).to_string();
+        if !valid_percent_encoding(&path) {
+            return Err(HttpPathRewriteError::InvalidPercentEncoding(path));
+        }
+
+        let target = match pa.query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path,
+        };
+        PathAndQuery::from_str(&target)
+            .map(Some)
+            .map_err(|_| HttpPathRewriteError::InvalidRequestTarget(target))
+    }
+
+    /// Rewrite the path of the uri, return `None` if no rule matched
+    pub fn rewrite_uri(&self, uri: &Uri) -> Result<Option<Uri>, HttpPathRewriteError> {
+        let Some(pa) = uri.path_and_query() else {
+            return Ok(None);
+        };
+        let Some(new_pa) = self.rewrite(pa)? else {
+            return Ok(None);
+        };
+        let mut parts = uri.clone().into_parts();
+        parts.path_and_query = Some(new_pa);
+        Uri::from_parts(parts)
+            .map(Some)
+            .map_err(|e| HttpPathRewriteError::InvalidRequestTarget(e.to_string()))
+    }
+}
+
+fn valid_percent_encoding(s: &str) -> bool {
+    let b = s.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' {
+            if i + 2 >= b.len() {
+                return false;
+            }
+            if !b[i + 1].is_ascii_hexdigit() || !b[i + 2].is_ascii_hexdigit() {
+                return false;
+            }
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewriter() -> HttpPathRewriter {
+        let mut rewriter = HttpPathRewriter::default();
+        rewriter.push(HttpPathRewriteRule::StripPrefix("/api/".to_string()));
+        rewriter.push(HttpPathRewriteRule::RegexReplace(
+            Regex::new("^/users/([0-9]+)$").unwrap(),
+            "/v2/user/$1/profile".to_string(),
+        ));
+        rewriter
+    }
+
+    #[test]
+    fn strip_and_regex() {
+        let rewriter = rewriter();
+
+        let pa = PathAndQuery::from_static("/api/users/123?a=1&b=%20");
+        let new = rewriter.rewrite(&pa).unwrap().unwrap();
+        assert_eq!(new.path(), "/v2/user/123/profile");
+        assert_eq!(new.query(), Some("a=1&b=%20"));
+
+        let pa = PathAndQuery::from_static("/api/users/abc");
+        let new = rewriter.rewrite(&pa).unwrap().unwrap();
+        assert_eq!(new.as_str(), "/users/abc");
+
+        let pa = PathAndQuery::from_static("/api");
+        let new = rewriter.rewrite(&pa).unwrap().unwrap();
+        assert_eq!(new.as_str(), "/");
+
+        let pa = PathAndQuery::from_static("/apis/users/1");
+        assert!(rewriter.rewrite(&pa).unwrap().is_none());
+
+        let uri = Uri::from_static("http://example.net/api/users/1?q");
+        let new = rewriter.rewrite_uri(&uri).unwrap().unwrap();
+        assert_eq!(new.to_string(), "http://example.net/v2/user/1/profile?q");
+    }
+
+    #[test]
+    fn encode() {
+        let mut rewriter = HttpPathRewriter::default();
+        rewriter.push(HttpPathRewriteRule::AddPrefix("/v1/".to_string()));
+        rewriter.push(HttpPathRewriteRule::RegexReplace(
+            Regex::new("/x$").unwrap(),
+            "/a b?c".to_string(),
+        ));
+
+        let pa = PathAndQuery::from_static("/x?k=v");
+        let new = rewriter.rewrite(&pa).unwrap().unwrap();
+        assert_eq!(new.as_str(), "/v1/a%20b%3Fc?k=v");
+    }
+
+    #[test]
+    fn invalid() {
+        let mut rewriter = HttpPathRewriter::default();
+        rewriter.push(HttpPathRewriteRule::RegexReplace(
+            Regex::new("^/").unwrap(),
+            String::new(),
+        ));
+        let pa = PathAndQuery::from_static("/abc");
+        assert_eq!(
+            rewriter.rewrite(&pa).unwrap_err(),
+            HttpPathRewriteError::NotAbsolutePath("abc".to_string())
+        );
+
+        let mut rewriter = HttpPathRewriter::default();
+        rewriter.push(HttpPathRewriteRule::AddPrefix("/%x".to_string()));
+        let pa = PathAndQuery::from_static("/abc");
+        assert!(matches!(
+            rewriter.rewrite(&pa).unwrap_err(),
+            HttpPathRewriteError::InvalidPercentEncoding(_)
+        ));
+    }
+}
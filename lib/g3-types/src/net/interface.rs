@@ -32,6 +32,16 @@ impl InterfaceName {
     pub fn as_str(&self) -> &str {
         unsafe { std::str::from_utf8_unchecked(&self.name[..self.len - 1]) }
     }
+
+    /// Get the index of the interface, which can be used to check if the interface exists
+    pub fn index(&self) -> io::Result<u32> {
+        let index = unsafe { libc::if_nametoindex(self.name.as_ptr().cast()) };
+        if index == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(index)
+        }
+    }
 }
 
 impl FromStr for InterfaceName {
@@ -63,3 +73,20 @@ impl fmt::Display for InterfaceName {
         f.write_str(self.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index() {
+        #[cfg(target_os = "linux")]
+        {
+            let lo = InterfaceName::from_str("lo").unwrap();
+            assert!(lo.index().is_ok());
+        }
+
+        let none = InterfaceName::from_str("g3-not-exist").unwrap();
+        assert!(none.index().is_err());
+    }
+}
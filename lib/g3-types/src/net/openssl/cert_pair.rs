@@ -16,7 +16,7 @@
 
 use anyhow::anyhow;
 use openssl::pkey::{PKey, Private};
-use openssl::ssl::SslContextBuilder;
+use openssl::ssl::{SslContextBuilder, SslRef};
 use openssl::x509::X509;
 
 use super::OpensslSessionIdContext;
@@ -67,6 +67,16 @@ impl OpensslCertificatePair {
         Ok(())
     }
 
+    /// Get the leaf cert and the issuer cert, which is the first cert in the chain
+    pub(crate) fn leaf_and_issuer(&self) -> (X509, Option<X509>) {
+        let leaf_cert = X509::from_der(self.leaf_cert.as_slice()).unwrap();
+        let issuer_cert = self
+            .chain_certs
+            .first()
+            .map(|cert| X509::from_der(cert.as_slice()).unwrap());
+        (leaf_cert, issuer_cert)
+    }
+
     pub fn set_private_key(&mut self, key: PKey<Private>) -> anyhow::Result<()> {
         let key_der = key
             .private_key_to_der()
@@ -87,6 +97,26 @@ impl OpensslCertificatePair {
         self.add_to_ssl_context(ssl_builder)
     }
 
+    /// Set the certificate and key on a single client ssl, which will override the ones set
+    /// on the ssl context
+    pub fn add_to_client_ssl(&self, ssl: &mut SslRef) -> anyhow::Result<()> {
+        let leaf_cert = X509::from_der(self.leaf_cert.as_slice())
+            .map_err(|e| anyhow!("failed to decode certificate: {e}"))?;
+        ssl.set_certificate(&leaf_cert)
+            .map_err(|e| anyhow!("failed to set certificate: {e}"))?;
+        for (i, cert) in self.chain_certs.iter().enumerate() {
+            let chain_cert = X509::from_der(cert.as_slice())
+                .map_err(|e| anyhow!("failed to decode chain certificate #{i}: {e}"))?;
+            ssl.add_chain_cert(chain_cert)
+                .map_err(|e| anyhow!("failed to add chain certificate #{i}: {e}"))?;
+        }
+        let key = PKey::private_key_from_der(self.key.as_slice())
+            .map_err(|e| anyhow!("failed to decode private key: {e}"))?;
+        ssl.set_private_key(&key)
+            .map_err(|e| anyhow!("failed to set private key: {e}"))?;
+        Ok(())
+    }
+
     pub fn add_to_server_ssl_context(
         &self,
         ssl_builder: &mut SslContextBuilder,
@@ -117,3 +147,60 @@ impl OpensslCertificatePair {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::ssl::{Ssl, SslContext, SslMethod};
+    use openssl::x509::{X509Builder, X509NameBuilder};
+
+    fn self_signed_pair() -> OpensslCertificatePair {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "client").unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+        let mut pair = OpensslCertificatePair::default();
+        pair.set_certificates(vec![builder.build()]).unwrap();
+        pair.set_private_key(key).unwrap();
+        pair
+    }
+
+    #[test]
+    fn add_to_client_ssl() {
+        let ctx = SslContext::builder(SslMethod::tls_client())
+            .unwrap()
+            .build();
+        let mut ssl = Ssl::new(&ctx).unwrap();
+        assert!(ssl.certificate().is_none());
+
+        let pair = self_signed_pair();
+        pair.check().unwrap();
+        pair.add_to_client_ssl(&mut ssl).unwrap();
+        let cert = ssl.certificate().unwrap();
+        assert_eq!(cert.to_der().unwrap(), pair.leaf_cert);
+
+        let mut invalid = pair.clone();
+        invalid.key = b"not a der key".to_vec();
+        let mut ssl = Ssl::new(&ctx).unwrap();
+        assert!(invalid.add_to_client_ssl(&mut ssl).is_err());
+    }
+}
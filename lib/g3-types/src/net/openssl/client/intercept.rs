@@ -25,7 +25,7 @@ use super::{
     OpensslClientSessionCache, OpensslSessionCacheConfig, DEFAULT_HANDSHAKE_TIMEOUT,
     MINIMAL_HANDSHAKE_TIMEOUT,
 };
-use crate::net::{TlsAlpn, TlsServerName, TlsVersion, UpstreamAddr};
+use crate::net::{OpensslCertificatePair, TlsAlpn, TlsServerName, TlsVersion, UpstreamAddr};
 
 #[derive(Clone)]
 struct ContextPair {
@@ -98,6 +98,7 @@ pub struct OpensslInterceptionClientConfigBuilder {
     max_tls_version: Option<TlsVersion>,
     ca_certs: Vec<Vec<u8>>,
     no_default_ca_certs: bool,
+    client_cert_pair: Option<OpensslCertificatePair>,
     handshake_timeout: Duration,
     session_cache: OpensslSessionCacheConfig,
     supported_groups: String,
@@ -116,6 +117,7 @@ impl Default for OpensslInterceptionClientConfigBuilder {
             max_tls_version: None,
             ca_certs: Vec::new(),
             no_default_ca_certs: false,
+            client_cert_pair: None,
             handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
             session_cache: OpensslSessionCacheConfig::new_for_many(),
             supported_groups: String::default(),
@@ -131,6 +133,10 @@ impl Default for OpensslInterceptionClientConfigBuilder {
 
 impl OpensslInterceptionClientConfigBuilder {
     pub fn check(&mut self) -> anyhow::Result<()> {
+        if let Some(cert_pair) = &self.client_cert_pair {
+            cert_pair.check()?;
+        }
+
         if self.handshake_timeout < MINIMAL_HANDSHAKE_TIMEOUT {
             self.handshake_timeout = MINIMAL_HANDSHAKE_TIMEOUT;
         }
@@ -166,6 +172,13 @@ impl OpensslInterceptionClientConfigBuilder {
         self.handshake_timeout = timeout;
     }
 
+    pub fn set_cert_pair(
+        &mut self,
+        pair: OpensslCertificatePair,
+    ) -> Option<OpensslCertificatePair> {
+        self.client_cert_pair.replace(pair)
+    }
+
     #[inline]
     pub fn set_no_session_cache(&mut self) {
         self.session_cache.set_no_session_cache();
@@ -309,6 +322,10 @@ impl OpensslInterceptionClientConfigBuilder {
 
         self.build_set_verify_cert_store(&mut ctx_builder)?;
 
+        if let Some(cert_pair) = &self.client_cert_pair {
+            cert_pair.add_to_client_ssl_context(&mut ctx_builder)?;
+        }
+
         let session_cache = self.session_cache.set_for_client(&mut ctx_builder)?;
 
         Ok(ContextPair {
@@ -351,6 +368,10 @@ impl OpensslInterceptionClientConfigBuilder {
 
         self.build_set_verify_cert_store(&mut ctx_builder)?;
 
+        if let Some(cert_pair) = &self.client_cert_pair {
+            cert_pair.add_to_client_ssl_context(&mut ctx_builder)?;
+        }
+
         let session_cache = self.session_cache.set_for_client(&mut ctx_builder)?;
 
         Ok(ContextPair {
@@ -22,9 +22,9 @@ pub use client::{
 
 mod server;
 pub use server::{
-    OpensslInterceptionServerConfig, OpensslInterceptionServerConfigBuilder, OpensslServerConfig,
-    OpensslServerConfigBuilder, OpensslServerSessionCache, OpensslSessionIdContext,
-    OpensslTicketKey, OpensslTicketKeyBuilder,
+    OpensslInterceptionServerConfig, OpensslInterceptionServerConfigBuilder, OpensslOcspStaple,
+    OpensslOcspStapleEntry, OpensslOcspStapler, OpensslServerConfig, OpensslServerConfigBuilder,
+    OpensslServerSessionCache, OpensslSessionIdContext, OpensslTicketKey, OpensslTicketKeyBuilder,
 };
 
 mod cert_pair;
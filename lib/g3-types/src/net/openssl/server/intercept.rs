@@ -20,11 +20,16 @@ use std::time::Duration;
 use anyhow::anyhow;
 use openssl::ex_data::Index;
 use openssl::ssl::{
-    AlpnError, Ssl, SslAcceptor, SslAcceptorBuilder, SslContext, SslRef, TlsExtType,
+    AlpnError, Ssl, SslAcceptor, SslAcceptorBuilder, SslContext, SslRef, SslVerifyMode, TlsExtType,
 };
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::X509;
 
-use super::{OpensslTicketKey, DEFAULT_ACCEPT_TIMEOUT, MINIMAL_ACCEPT_TIMEOUT};
-use crate::net::{RollingTicketer, TlsAlpn, TlsServerName};
+use super::{
+    OpensslSessionIdContext, OpensslTicketKey, DEFAULT_ACCEPT_TIMEOUT, MINIMAL_ACCEPT_TIMEOUT,
+};
+use crate::net::{RollingTicketer, TlsAlpn, TlsServerName, TlsVersion};
 
 pub struct OpensslInterceptionServerConfig {
     sni_index: Index<Ssl, TlsServerName>,
@@ -52,15 +57,23 @@ impl OpensslInterceptionServerConfig {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OpensslInterceptionServerConfigBuilder {
     accept_timeout: Duration,
+    client_auth: bool,
+    client_auth_certs: Vec<Vec<u8>>,
+    min_tls_version: Option<TlsVersion>,
+    max_tls_version: Option<TlsVersion>,
 }
 
 impl Default for OpensslInterceptionServerConfigBuilder {
     fn default() -> Self {
         OpensslInterceptionServerConfigBuilder {
             accept_timeout: DEFAULT_ACCEPT_TIMEOUT,
+            client_auth: false,
+            client_auth_certs: Vec::new(),
+            min_tls_version: None,
+            max_tls_version: None,
         }
     }
 }
@@ -78,6 +91,93 @@ impl OpensslInterceptionServerConfigBuilder {
         self.accept_timeout = timeout;
     }
 
+    pub fn set_min_tls_version(&mut self, version: TlsVersion) {
+        self.min_tls_version = Some(version);
+    }
+
+    pub fn set_max_tls_version(&mut self, version: TlsVersion) {
+        self.max_tls_version = Some(version);
+    }
+
+    pub fn enable_client_auth(&mut self) {
+        self.client_auth = true;
+    }
+
+    pub fn set_client_auth_certificates(&mut self, certs: Vec<X509>) -> anyhow::Result<()> {
+        for (i, cert) in certs.into_iter().enumerate() {
+            let bytes = cert
+                .to_der()
+                .map_err(|e| anyhow!("failed to encode client chain certificate #{i}: {e}"))?;
+            self.client_auth_certs.push(bytes);
+        }
+        Ok(())
+    }
+
+    fn set_tls_version(&self, builder: &mut SslAcceptorBuilder) -> anyhow::Result<()> {
+        if let Some(version) = self.min_tls_version {
+            builder
+                .set_min_proto_version(Some(version.into()))
+                .map_err(|e| anyhow!("failed to set min ssl version to {version}: {e}"))?;
+        }
+        if let Some(version) = self.max_tls_version {
+            builder
+                .set_max_proto_version(Some(version.into()))
+                .map_err(|e| anyhow!("failed to set max ssl version to {version}: {e}"))?;
+        }
+        Ok(())
+    }
+
+    fn set_client_auth(&self, builder: &mut SslAcceptorBuilder) -> anyhow::Result<()> {
+        if !self.client_auth {
+            return Ok(());
+        }
+
+        // the client certificate is requested in the initial handshake,
+        // so no renegotiation or post-handshake auth is needed for TLS 1.3
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+
+        let mut id_ctx = OpensslSessionIdContext::new()
+            .map_err(|e| anyhow!("failed to create session id context builder: {e}"))?;
+        let mut store_builder = X509StoreBuilder::new()
+            .map_err(|e| anyhow!("failed to create ca cert store builder: {e}"))?;
+        let mut subject_stack =
+            Stack::new().map_err(|e| anyhow!("failed to get new ca name stack: {e}"))?;
+
+        if self.client_auth_certs.is_empty() {
+            store_builder
+                .set_default_paths()
+                .map_err(|e| anyhow!("failed to load default ca certs: {e}"))?;
+        } else {
+            for (i, cert) in self.client_auth_certs.iter().enumerate() {
+                let ca_cert = X509::from_der(cert.as_slice())
+                    .map_err(|e| anyhow!("[#{i}] failed to decode ca certificate: {e}"))?;
+                let subject = ca_cert
+                    .subject_name()
+                    .to_owned()
+                    .map_err(|e| anyhow!("[#{i}] failed to get ca subject name: {e}"))?;
+                id_ctx
+                    .add_ca_subject(&subject)
+                    .map_err(|e| anyhow!("[#{i}] failed to add to session id context: {e}"))?;
+                store_builder
+                    .add_cert(ca_cert)
+                    .map_err(|e| anyhow!("[#{i}] failed to add ca certificate: {e}"))?;
+                subject_stack
+                    .push(subject)
+                    .map_err(|e| anyhow!("[#{i}] failed to push to ca name stack: {e}"))?;
+            }
+        }
+        builder
+            .set_verify_cert_store(store_builder.build())
+            .map_err(|e| anyhow!("failed to set verify ca certs: {e}"))?;
+        if !subject_stack.is_empty() {
+            builder.set_client_ca_list(subject_stack);
+        }
+
+        id_ctx
+            .build_set(builder)
+            .map_err(|e| anyhow!("failed to set session id context: {e}"))
+    }
+
     pub fn build(&self) -> anyhow::Result<OpensslInterceptionServerConfig> {
         self.build_with_ticketer(None)
     }
@@ -98,8 +198,13 @@ impl OpensslInterceptionServerConfigBuilder {
             SslContext::new_ex_index().map_err(|e| anyhow!("failed to create ex index: {e}"))?;
 
         macro_rules! build_ssl_context {
-            ($method:expr) => {{
+            ($method:expr, $set_version:expr) => {{
                 let mut builder = $method(retry_index, sni_index, alpn_index, alpn_name_index)?;
+                if $set_version {
+                    // the tls version bounds don't apply to TLCP
+                    self.set_tls_version(&mut builder)?;
+                }
+                self.set_client_auth(&mut builder)?;
                 if let Some(ticketer) = ticketer {
                     builder.set_ex_data(ticket_key_index, ticketer.clone());
                     super::set_ticket_key_callback(&mut builder, ticket_key_index)?;
@@ -108,9 +213,9 @@ impl OpensslInterceptionServerConfigBuilder {
             }};
         }
 
-        let ssl_context = build_ssl_context!(build_tls_context);
+        let ssl_context = build_ssl_context!(build_tls_context, true);
         #[cfg(feature = "tongsuo")]
-        let tlcp_context = build_ssl_context!(build_tlcp_context);
+        let tlcp_context = build_ssl_context!(build_tlcp_context, false);
 
         Ok(OpensslInterceptionServerConfig {
             sni_index,
@@ -274,3 +379,16 @@ fn set_alpn_select_callback(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_client_auth_cert() {
+        let mut builder = OpensslInterceptionServerConfigBuilder::default();
+        builder.enable_client_auth();
+        builder.client_auth_certs.push(b"not a der cert".to_vec());
+        assert!(builder.build().is_err());
+    }
+}
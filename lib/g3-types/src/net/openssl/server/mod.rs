@@ -18,11 +18,12 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{anyhow, Context};
-use bytes::BufMut;
+#[cfg(not(any(feature = "boringssl", feature = "aws-lc")))]
+use openssl::error::ErrorStack;
 use openssl::ex_data::Index;
 use openssl::ssl::{
-    SslAcceptor, SslAcceptorBuilder, SslContext, SslOptions, SslSessionCacheMode, SslVerifyMode,
-    TicketKeyStatus,
+    AlpnError, SslAcceptor, SslAcceptorBuilder, SslContext, SslOptions, SslSessionCacheMode,
+    SslVerifyMode, TicketKeyStatus,
 };
 use openssl::stack::Stack;
 use openssl::x509::store::X509StoreBuilder;
@@ -44,6 +45,9 @@ mod ticketer;
 mod session;
 pub use session::{OpensslServerSessionCache, OpensslSessionIdContext};
 
+mod ocsp;
+pub use ocsp::{OpensslOcspStaple, OpensslOcspStapleEntry, OpensslOcspStapler};
+
 const MINIMAL_ACCEPT_TIMEOUT: Duration = Duration::from_millis(100);
 const DEFAULT_ACCEPT_TIMEOUT: Duration = Duration::from_secs(10);
 
@@ -231,10 +235,34 @@ impl OpensslServerConfigBuilder {
         self.build_tls_acceptor(id_ctx)
     }
 
+    /// Create a new OCSP stapler for all the certs.
+    ///
+    /// The issuer cert should be the first cert in the chain of each cert pair.
+    pub fn new_ocsp_stapler(&self, hard_fail: bool) -> anyhow::Result<OpensslOcspStapler> {
+        let mut stapler = OpensslOcspStapler::new(hard_fail);
+        for (i, pair) in self.cert_pairs.iter().enumerate() {
+            let (leaf_cert, issuer_cert) = pair.leaf_and_issuer();
+            let Some(issuer_cert) = issuer_cert else {
+                return Err(anyhow!("no issuer cert found in cert pair #{i}"));
+            };
+            stapler.push_cert(leaf_cert, issuer_cert);
+        }
+        Ok(stapler)
+    }
+
     pub fn build_with_alpn_protocols(
         &self,
         alpn_protocols: Option<Vec<AlpnProtocol>>,
         ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
+    ) -> anyhow::Result<OpensslServerConfig> {
+        self.build_with_ocsp_stapler(alpn_protocols, ticketer, None)
+    }
+
+    pub fn build_with_ocsp_stapler(
+        &self,
+        alpn_protocols: Option<Vec<AlpnProtocol>>,
+        ticketer: Option<Arc<RollingTicketer<OpensslTicketKey>>>,
+        ocsp_stapler: Option<Arc<OpensslOcspStapler>>,
     ) -> anyhow::Result<OpensslServerConfig> {
         let mut id_ctx = OpensslSessionIdContext::new()
             .map_err(|e| anyhow!("failed to create session id context builder: {e}"))?;
@@ -260,6 +288,13 @@ impl OpensslServerConfigBuilder {
             set_ticket_key_callback(&mut ssl_builder, ticket_key_index)?;
         }
 
+        if let Some(stapler) = ocsp_stapler {
+            let stapler_index = SslContext::new_ex_index()
+                .map_err(|e| anyhow!("failed to create ex index: {e}"))?;
+            ssl_builder.set_ex_data(stapler_index, stapler);
+            set_ocsp_status_callback(&mut ssl_builder, stapler_index)?;
+        }
+
         if self.client_auth {
             ssl_builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
 
@@ -308,14 +343,8 @@ impl OpensslServerConfigBuilder {
         // ssl_builder.set_options() // TODO do we need it?
 
         if let Some(protocols) = alpn_protocols {
-            let mut buf = Vec::with_capacity(32);
-            protocols.iter().for_each(|p| {
-                buf.put_slice(p.wired_identification_sequence());
-            });
-            if !buf.is_empty() {
-                ssl_builder
-                    .set_alpn_protos(buf.as_slice())
-                    .map_err(|e| anyhow!("failed to set alpn protocols: {e}"))?;
+            if !protocols.is_empty() {
+                set_alpn_select_callback(&mut ssl_builder, protocols);
             }
         }
 
@@ -341,6 +370,30 @@ impl OpensslServerConfigBuilder {
     }
 }
 
+/// Select the first protocol in the server preference order that is offered by the client.
+///
+/// No ALPN extension will be sent back if there is no match, so the client can still continue.
+fn set_alpn_select_callback(builder: &mut SslAcceptorBuilder, protocols: Vec<AlpnProtocol>) {
+    builder.set_alpn_select_callback(move |_ssl, client_p: &[u8]| {
+        for protocol in &protocols {
+            let mut offset = 0;
+            while offset < client_p.len() {
+                let name_len = client_p[offset] as usize;
+                let end = offset + 1 + name_len;
+                if end > client_p.len() {
+                    return Err(AlpnError::NOACK);
+                }
+                let name = &client_p[offset + 1..end];
+                if name == protocol.identification_sequence() {
+                    return Ok(name);
+                }
+                offset = end;
+            }
+        }
+        Err(AlpnError::NOACK)
+    });
+}
+
 fn set_ticket_key_callback(
     builder: &mut SslAcceptorBuilder,
     ticket_key_index: Index<SslContext, Arc<RollingTicketer<OpensslTicketKey>>>,
@@ -360,3 +413,36 @@ fn set_ticket_key_callback(
         })
         .map_err(|e| anyhow!("failed to set ticket key callback: {e}"))
 }
+
+#[cfg(not(any(feature = "boringssl", feature = "aws-lc")))]
+fn set_ocsp_status_callback(
+    builder: &mut SslAcceptorBuilder,
+    stapler_index: Index<SslContext, Arc<OpensslOcspStapler>>,
+) -> anyhow::Result<()> {
+    builder
+        .set_status_callback(move |ssl| {
+            let Some(stapler) = ssl.ssl_context().ex_data(stapler_index) else {
+                return Ok(false);
+            };
+            let staple = ssl.certificate().and_then(|cert| stapler.find_staple(cert));
+            match staple {
+                Some(staple) => {
+                    ssl.set_ocsp_status(staple.response())?;
+                    Ok(true)
+                }
+                None if stapler.hard_fail() => Err(ErrorStack::get()),
+                None => Ok(false),
+            }
+        })
+        .map_err(|e| anyhow!("failed to set ocsp status callback: {e}"))
+}
+
+#[cfg(any(feature = "boringssl", feature = "aws-lc"))]
+fn set_ocsp_status_callback(
+    _builder: &mut SslAcceptorBuilder,
+    _stapler_index: Index<SslContext, Arc<OpensslOcspStapler>>,
+) -> anyhow::Result<()> {
+    Err(anyhow!(
+        "ocsp stapling is not supported with the current ssl library"
+    ))
+}
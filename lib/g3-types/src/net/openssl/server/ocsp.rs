@@ -0,0 +1,168 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use arc_swap::ArcSwapOption;
+use openssl::x509::{X509Ref, X509};
+
+/// A cached OCSP response that can be stapled in the server handshake
+pub struct OpensslOcspStaple {
+    response: Vec<u8>,
+    expire: Option<SystemTime>,
+}
+
+impl OpensslOcspStaple {
+    /// Create a new staple with the DER encoded OCSP response.
+    ///
+    /// The `expire` time should be set to the nextUpdate time of the response.
+    pub fn new(response: Vec<u8>, expire: Option<SystemTime>) -> Self {
+        OpensslOcspStaple { response, expire }
+    }
+
+    #[inline]
+    pub fn response(&self) -> &[u8] {
+        &self.response
+    }
+
+    #[inline]
+    pub fn expire(&self) -> Option<SystemTime> {
+        self.expire
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expire
+            .map(|expire| expire <= SystemTime::now())
+            .unwrap_or(false)
+    }
+}
+
+/// OCSP staple state of a server certificate
+pub struct OpensslOcspStapleEntry {
+    leaf_cert: X509,
+    issuer_cert: X509,
+    responders: Vec<String>,
+    staple: ArcSwapOption<OpensslOcspStaple>,
+}
+
+impl OpensslOcspStapleEntry {
+    #[inline]
+    pub fn leaf_cert(&self) -> &X509 {
+        &self.leaf_cert
+    }
+
+    #[inline]
+    pub fn issuer_cert(&self) -> &X509 {
+        &self.issuer_cert
+    }
+
+    /// The OCSP responder urls found in the AIA extension of the leaf cert
+    #[inline]
+    pub fn responders(&self) -> &[String] {
+        &self.responders
+    }
+
+    pub fn staple(&self) -> Option<Arc<OpensslOcspStaple>> {
+        self.staple.load_full()
+    }
+
+    pub fn set_staple(&self, staple: Option<Arc<OpensslOcspStaple>>) {
+        self.staple.store(staple);
+    }
+
+    pub(crate) fn valid_staple(&self) -> Option<Arc<OpensslOcspStaple>> {
+        self.staple.load_full().filter(|s| !s.is_expired())
+    }
+}
+
+/// The OCSP staples of all certificates in a server tls config.
+///
+/// The staples should be updated by an external task, which should stop when this is dropped.
+pub struct OpensslOcspStapler {
+    entries: Vec<OpensslOcspStapleEntry>,
+    hard_fail: bool,
+}
+
+impl OpensslOcspStapler {
+    pub(crate) fn new(hard_fail: bool) -> Self {
+        OpensslOcspStapler {
+            entries: Vec::with_capacity(1),
+            hard_fail,
+        }
+    }
+
+    pub(crate) fn push_cert(&mut self, leaf_cert: X509, issuer_cert: X509) {
+        let responders = leaf_cert
+            .ocsp_responders()
+            .map(|stack| stack.iter().map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+        self.entries.push(OpensslOcspStapleEntry {
+            leaf_cert,
+            issuer_cert,
+            responders,
+            staple: ArcSwapOption::empty(),
+        });
+    }
+
+    /// Reuse the staples of the same certs in the old stapler, which is useful on reload
+    pub fn inherit_staples(&self, old: &OpensslOcspStapler) {
+        for entry in &self.entries {
+            if let Some(old_entry) = old.entries.iter().find(|e| e.leaf_cert == entry.leaf_cert) {
+                entry.set_staple(old_entry.valid_staple());
+            }
+        }
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &[OpensslOcspStapleEntry] {
+        &self.entries
+    }
+
+    /// Whether to fail the handshake if the client requested a staple but none is available
+    #[inline]
+    pub fn hard_fail(&self) -> bool {
+        self.hard_fail
+    }
+
+    /// Find the unexpired staple for the certificate selected in the handshake
+    pub fn find_staple(&self, cert: &X509Ref) -> Option<Arc<OpensslOcspStaple>> {
+        self.entries
+            .iter()
+            .find(|e| *e.leaf_cert == *cert)
+            .and_then(|e| e.valid_staple())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn staple_expire() {
+        let staple = OpensslOcspStaple::new(vec![0x30], None);
+        assert!(!staple.is_expired());
+
+        let expire = SystemTime::now() + Duration::from_secs(60);
+        let staple = OpensslOcspStaple::new(vec![0x30], Some(expire));
+        assert!(!staple.is_expired());
+
+        let expire = SystemTime::now() - Duration::from_secs(1);
+        let staple = OpensslOcspStaple::new(vec![0x30], Some(expire));
+        assert!(staple.is_expired());
+    }
+}
@@ -66,6 +66,11 @@ impl RustlsCertificatePair {
         self.certs.clone()
     }
 
+    #[inline]
+    pub fn certs_ref(&self) -> &[CertificateDer<'static>] {
+        &self.certs
+    }
+
     pub fn key_owned(&self) -> PrivateKeyDer<'static> {
         self.key.clone_key()
     }
@@ -17,14 +17,30 @@
 use std::sync::Arc;
 
 use anyhow::anyhow;
+#[cfg(feature = "openssl")]
+use arc_swap::ArcSwapOption;
 #[cfg(feature = "aws-lc")]
 use rustls::crypto::aws_lc_rs::sign::any_supported_type;
 #[cfg(not(feature = "aws-lc"))]
 use rustls::crypto::ring::sign::any_supported_type;
 use rustls::server::{ClientHello, ResolvesServerCert};
 use rustls::sign::CertifiedKey;
+use rustls::InconsistentKeys;
 
 use super::RustlsCertificatePair;
+#[cfg(feature = "openssl")]
+use crate::net::{OpensslOcspStaple, OpensslOcspStapler};
+
+fn new_certified_key(pair: &RustlsCertificatePair) -> anyhow::Result<CertifiedKey> {
+    let signing_key =
+        any_supported_type(pair.key_ref()).map_err(|e| anyhow!("failed to add cert pair: {e}"))?;
+    let ck = CertifiedKey::new(pair.certs_owned(), signing_key);
+    match ck.keys_match() {
+        // the same as rustls, don't treat unknown consistency as an error
+        Ok(_) | Err(rustls::Error::InconsistentKeys(InconsistentKeys::Unknown)) => Ok(ck),
+        Err(e) => Err(anyhow!("the cert and key in the pair mismatch: {e}")),
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct MultipleCertResolver {
@@ -39,9 +55,7 @@ impl MultipleCertResolver {
     }
 
     pub fn push_cert_pair(&mut self, pair: &RustlsCertificatePair) -> anyhow::Result<()> {
-        let signing_key = any_supported_type(pair.key_ref())
-            .map_err(|e| anyhow!("failed to add cert pair: {e}"))?;
-        let ck = CertifiedKey::new(pair.certs_owned(), signing_key);
+        let ck = new_certified_key(pair)?;
         self.keys.push(Arc::new(ck));
         Ok(())
     }
@@ -58,3 +72,89 @@ impl ResolvesServerCert for MultipleCertResolver {
         None
     }
 }
+
+#[cfg(feature = "openssl")]
+struct StapledCertifiedKey {
+    staple: Arc<OpensslOcspStaple>,
+    key: Arc<CertifiedKey>,
+}
+
+#[cfg(feature = "openssl")]
+struct OcspStapleCertifiedKey {
+    key: Arc<CertifiedKey>,
+    stapled: ArcSwapOption<StapledCertifiedKey>,
+}
+
+#[cfg(feature = "openssl")]
+impl OcspStapleCertifiedKey {
+    /// Get the certified key with the staple set, which will be cached until the staple changes
+    fn get(&self, staple: Option<Arc<OpensslOcspStaple>>) -> Arc<CertifiedKey> {
+        let Some(staple) = staple else {
+            return self.key.clone();
+        };
+        if let Some(cached) = self.stapled.load().as_ref() {
+            if Arc::ptr_eq(&cached.staple, &staple) {
+                return cached.key.clone();
+            }
+        }
+
+        let mut ck = self.key.as_ref().clone();
+        ck.ocsp = Some(staple.response().to_vec());
+        let ck = Arc::new(ck);
+        self.stapled.store(Some(Arc::new(StapledCertifiedKey {
+            staple,
+            key: ck.clone(),
+        })));
+        ck
+    }
+}
+
+/// Cert resolver which will set the OCSP staples found in the stapler.
+///
+/// The cert pairs should be pushed in the same order as the entries in the stapler.
+#[cfg(feature = "openssl")]
+pub struct OcspStapleCertResolver {
+    keys: Vec<OcspStapleCertifiedKey>,
+    stapler: Arc<OpensslOcspStapler>,
+}
+
+#[cfg(feature = "openssl")]
+impl OcspStapleCertResolver {
+    pub fn new(stapler: Arc<OpensslOcspStapler>) -> Self {
+        OcspStapleCertResolver {
+            keys: Vec::with_capacity(stapler.entries().len()),
+            stapler,
+        }
+    }
+
+    pub fn push_cert_pair(&mut self, pair: &RustlsCertificatePair) -> anyhow::Result<()> {
+        let ck = new_certified_key(pair)?;
+        self.keys.push(OcspStapleCertifiedKey {
+            key: Arc::new(ck),
+            stapled: ArcSwapOption::empty(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl std::fmt::Debug for OcspStapleCertResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OcspStapleCertResolver")
+            .field("keys", &self.keys.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl ResolvesServerCert for OcspStapleCertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let schemes = client_hello.signature_schemes();
+        for (ck, entry) in self.keys.iter().zip(self.stapler.entries()) {
+            if ck.key.key.choose_scheme(schemes).is_some() {
+                return Some(ck.get(entry.valid_staple()));
+            }
+        }
+        None
+    }
+}
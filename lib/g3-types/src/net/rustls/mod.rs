@@ -35,6 +35,8 @@ pub use cert_pair::{RustlsCertificatePair, RustlsCertificatePairBuilder};
 
 mod cert_resolver;
 pub use cert_resolver::MultipleCertResolver;
+#[cfg(feature = "openssl")]
+pub use cert_resolver::OcspStapleCertResolver;
 
 mod ca_certs;
 pub use ca_certs::load_native_certs_for_rustls;
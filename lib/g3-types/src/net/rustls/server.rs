@@ -18,18 +18,22 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{anyhow, Context};
+#[cfg(feature = "openssl")]
+use openssl::x509::X509;
 #[cfg(feature = "quinn")]
 use quinn::crypto::rustls::QuicServerConfig;
 use rustls::server::{ProducesTickets, WebPkiClientVerifier};
 use rustls::{RootCertStore, ServerConfig};
 use rustls_pki_types::CertificateDer;
 
+#[cfg(feature = "openssl")]
+use super::OcspStapleCertResolver;
 use super::{
     MultipleCertResolver, RustlsCertificatePair, RustlsNoSessionTicketer, RustlsServerConfigExt,
 };
 use crate::net::tls::AlpnProtocol;
 #[cfg(feature = "openssl")]
-use crate::net::{OpensslTicketKey, RollingTicketer};
+use crate::net::{OpensslOcspStapler, OpensslTicketKey, RollingTicketer};
 
 #[derive(Clone)]
 pub struct RustlsServerConfig {
@@ -108,10 +112,32 @@ impl RustlsServerConfigBuilder {
         self.accept_timeout
     }
 
+    /// Create a new OCSP stapler for all the certs.
+    ///
+    /// The issuer cert should be the first cert in the chain of each cert pair.
+    /// Hard fail is not supported, as rustls doesn't tell if the client requested a staple.
+    #[cfg(feature = "openssl")]
+    pub fn new_ocsp_stapler(&self) -> anyhow::Result<OpensslOcspStapler> {
+        let mut stapler = OpensslOcspStapler::new(false);
+        for (i, pair) in self.cert_pairs.iter().enumerate() {
+            let certs = pair.certs_ref();
+            let leaf_cert = X509::from_der(certs[0].as_ref())
+                .map_err(|e| anyhow!("invalid leaf cert in cert pair #{i}: {e}"))?;
+            let Some(issuer_cert) = certs.get(1) else {
+                return Err(anyhow!("no issuer cert found in cert pair #{i}"));
+            };
+            let issuer_cert = X509::from_der(issuer_cert.as_ref())
+                .map_err(|e| anyhow!("invalid issuer cert in cert pair #{i}: {e}"))?;
+            stapler.push_cert(leaf_cert, issuer_cert);
+        }
+        Ok(stapler)
+    }
+
     fn build_server_config<T>(
         &self,
         alpn_protocols: Option<Vec<AlpnProtocol>>,
         ticketer: Option<Arc<T>>,
+        #[cfg(feature = "openssl")] ocsp_stapler: Option<Arc<OpensslOcspStapler>>,
     ) -> anyhow::Result<ServerConfig>
     where
         T: ProducesTickets + 'static,
@@ -141,6 +167,19 @@ impl RustlsServerConfigBuilder {
             config_builder.with_no_client_auth()
         };
 
+        #[cfg(feature = "openssl")]
+        if let Some(stapler) = ocsp_stapler {
+            let mut cert_resolver = OcspStapleCertResolver::new(stapler);
+            for (i, pair) in self.cert_pairs.iter().enumerate() {
+                cert_resolver
+                    .push_cert_pair(pair)
+                    .context(format!("failed to set server cert pair #{i}"))?;
+            }
+            let mut config = config_builder.with_cert_resolver(Arc::new(cert_resolver));
+            self.set_server_config(&mut config, alpn_protocols, ticketer)?;
+            return Ok(config);
+        }
+
         let mut config = match self.cert_pairs.len() {
             0 => return Err(anyhow!("no cert pair set")),
             1 => {
@@ -160,6 +199,19 @@ impl RustlsServerConfigBuilder {
             }
         };
 
+        self.set_server_config(&mut config, alpn_protocols, ticketer)?;
+        Ok(config)
+    }
+
+    fn set_server_config<T>(
+        &self,
+        config: &mut ServerConfig,
+        alpn_protocols: Option<Vec<AlpnProtocol>>,
+        ticketer: Option<Arc<T>>,
+    ) -> anyhow::Result<()>
+    where
+        T: ProducesTickets + 'static,
+    {
         config.set_session_cache(self.no_session_cache);
         config.set_session_ticketer(self.use_session_ticket, ticketer)?;
 
@@ -170,8 +222,7 @@ impl RustlsServerConfigBuilder {
                     .push(proto.to_identification_sequence());
             }
         }
-
-        Ok(config)
+        Ok(())
     }
 
     pub fn build_with_alpn_protocols<T>(
@@ -182,7 +233,12 @@ impl RustlsServerConfigBuilder {
     where
         T: ProducesTickets + 'static,
     {
-        let config = self.build_server_config(alpn_protocols, ticketer)?;
+        let config = self.build_server_config(
+            alpn_protocols,
+            ticketer,
+            #[cfg(feature = "openssl")]
+            None,
+        )?;
         Ok(RustlsServerConfig {
             driver: Arc::new(config),
             accept_timeout: self.accept_timeout,
@@ -197,6 +253,23 @@ impl RustlsServerConfigBuilder {
         self.build_with_alpn_protocols(None, ticketer)
     }
 
+    #[cfg(feature = "openssl")]
+    pub fn build_with_ocsp_stapler<T>(
+        &self,
+        alpn_protocols: Option<Vec<AlpnProtocol>>,
+        ticketer: Option<Arc<T>>,
+        ocsp_stapler: Option<Arc<OpensslOcspStapler>>,
+    ) -> anyhow::Result<RustlsServerConfig>
+    where
+        T: ProducesTickets + 'static,
+    {
+        let config = self.build_server_config(alpn_protocols, ticketer, ocsp_stapler)?;
+        Ok(RustlsServerConfig {
+            driver: Arc::new(config),
+            accept_timeout: self.accept_timeout,
+        })
+    }
+
     pub fn build(&self) -> anyhow::Result<RustlsServerConfig> {
         self.build_with_alpn_protocols::<RustlsNoSessionTicketer>(None, None)
     }
@@ -210,7 +283,12 @@ impl RustlsServerConfigBuilder {
     where
         T: ProducesTickets + 'static,
     {
-        let config = self.build_server_config(alpn_protocols, ticketer)?;
+        let config = self.build_server_config(
+            alpn_protocols,
+            ticketer,
+            #[cfg(feature = "openssl")]
+            None,
+        )?;
         let quic_config = QuicServerConfig::try_from(config)
             .map_err(|e| anyhow!("invalid quic tls config: {e}"))?;
         Ok(RustlsQuicServerConfig {
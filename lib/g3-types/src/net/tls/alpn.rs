@@ -190,6 +190,19 @@ impl TlsAlpn {
     pub fn is_empty(&self) -> bool {
         self.raw_list.is_empty()
     }
+
+    /// Iterate over the protocol names in the wired list sequence
+    pub fn protocol_names(&self) -> impl Iterator<Item = &[u8]> {
+        let mut offset = 0usize;
+        std::iter::from_fn(move || {
+            let len = *self.raw_list.get(offset)? as usize;
+            let start = offset + 1;
+            let end = start + len;
+            let name = self.raw_list.get(start..end)?;
+            offset = end;
+            Some(name)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -209,4 +222,13 @@ mod tests {
 
         assert_eq!(filtered, alpn2);
     }
+
+    #[test]
+    fn names() {
+        let v = b"\x00\x0C\x02h2\x08http/1.1";
+
+        let alpn = TlsAlpn::from_extension_value(v).unwrap();
+        let names: Vec<&[u8]> = alpn.protocol_names().collect();
+        assert_eq!(names, vec![b"h2".as_slice(), b"http/1.1".as_slice()]);
+    }
 }
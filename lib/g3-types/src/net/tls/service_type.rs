@@ -23,6 +23,7 @@ pub enum TlsServiceType {
     Http = 0,
     Smtp = 1,
     Imap = 2,
+    Pop3 = 3,
 }
 
 impl TlsServiceType {
@@ -31,6 +32,7 @@ impl TlsServiceType {
             TlsServiceType::Http => "http",
             TlsServiceType::Smtp => "smtp",
             TlsServiceType::Imap => "imap",
+            TlsServiceType::Pop3 => "pop3",
         }
     }
 }
@@ -57,6 +59,7 @@ impl TryFrom<u8> for TlsServiceType {
             0 => Ok(TlsServiceType::Http),
             1 => Ok(TlsServiceType::Smtp),
             2 => Ok(TlsServiceType::Imap),
+            3 => Ok(TlsServiceType::Pop3),
             _ => Err(InvalidServiceType),
         }
     }
@@ -70,6 +73,7 @@ impl FromStr for TlsServiceType {
             "http" | "HTTP" => Ok(TlsServiceType::Http),
             "smtp" | "SMTP" => Ok(TlsServiceType::Smtp),
             "imap" | "IMAP" => Ok(TlsServiceType::Imap),
+            "pop3" | "POP3" => Ok(TlsServiceType::Pop3),
             _ => Err(InvalidServiceType),
         }
     }
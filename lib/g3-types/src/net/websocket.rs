@@ -14,8 +14,15 @@
  * limitations under the License.
  */
 
+use base64::prelude::*;
 use http::header::Drain;
 use http::{header, HeaderMap, HeaderName, HeaderValue, Uri};
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+use super::{HttpHeaderMap, HttpHeaderValue};
+
+const WEBSOCKET_ACCEPT_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
 
 pub enum WebSocketSubProtocol {
     Mqtt,
@@ -152,4 +159,221 @@ impl WebSocketNotes {
     pub fn version(&self) -> Option<&HeaderValue> {
         self.headers.get(header::SEC_WEBSOCKET_VERSION)
     }
+
+    #[inline]
+    pub fn extensions(&self) -> header::GetAll<'_, HeaderValue> {
+        self.headers.get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+    }
+}
+
+/// Compute the expected `Sec-WebSocket-Accept` value for the `Sec-WebSocket-Key` value
+pub fn websocket_accept_value(key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key);
+    hasher.update(WEBSOCKET_ACCEPT_GUID);
+    BASE64_STANDARD.encode(hasher.finalize())
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum WebSocketHandshakeError {
+    #[error("no {0} header found")]
+    MissingHeader(HeaderName),
+    #[error("conflict values found for {0} header")]
+    ConflictHeader(HeaderName),
+    #[error("invalid {0} header value")]
+    InvalidHeaderValue(HeaderName),
+    #[error("unsupported websocket version {0}")]
+    UnsupportedVersion(String),
+    #[error("unmatched Sec-WebSocket-Accept value")]
+    UnmatchedAccept,
+}
+
+/// Get the single value of the header, duplicated headers with the same value will be merged
+fn take_single_value(
+    headers: &mut HttpHeaderMap,
+    name: HeaderName,
+) -> Result<Option<HttpHeaderValue>, WebSocketHandshakeError> {
+    let mut values = headers.get_all(&name).iter();
+    let Some(first) = values.next() else {
+        return Ok(None);
+    };
+    let mut duplicated = false;
+    for v in values {
+        if v.as_bytes() != first.as_bytes() {
+            return Err(WebSocketHandshakeError::ConflictHeader(name));
+        }
+        duplicated = true;
+    }
+    let value = first.clone();
+    if duplicated {
+        headers.insert(name, value.clone());
+    }
+    Ok(Some(value))
+}
+
+/// Strict validation for the websocket upgrade handshake
+pub struct WebSocketHandshakeValidator {
+    expected_accept: String,
+}
+
+impl WebSocketHandshakeValidator {
+    /// Validate and sanitize the headers in the client handshake request
+    pub fn check_request(headers: &mut HttpHeaderMap) -> Result<Self, WebSocketHandshakeError> {
+        // only valid in response
+        headers.remove(header::SEC_WEBSOCKET_ACCEPT);
+
+        let version = take_single_value(headers, header::SEC_WEBSOCKET_VERSION)?.ok_or(
+            WebSocketHandshakeError::MissingHeader(header::SEC_WEBSOCKET_VERSION),
+        )?;
+        if version.to_str().trim() != "13" {
+            return Err(WebSocketHandshakeError::UnsupportedVersion(
+                version.to_str().to_string(),
+            ));
+        }
+
+        let key = take_single_value(headers, header::SEC_WEBSOCKET_KEY)?.ok_or(
+            WebSocketHandshakeError::MissingHeader(header::SEC_WEBSOCKET_KEY),
+        )?;
+        let key = key.to_str().trim();
+        match BASE64_STANDARD.decode(key) {
+            Ok(nonce) if nonce.len() == 16 => {}
+            _ => {
+                return Err(WebSocketHandshakeError::InvalidHeaderValue(
+                    header::SEC_WEBSOCKET_KEY,
+                ))
+            }
+        }
+
+        Ok(WebSocketHandshakeValidator {
+            expected_accept: websocket_accept_value(key.as_bytes()),
+        })
+    }
+
+    /// Validate and sanitize the headers in the server 101 response,
+    /// return true if the headers has been changed
+    pub fn check_response(
+        &self,
+        headers: &mut HttpHeaderMap,
+    ) -> Result<bool, WebSocketHandshakeError> {
+        // only valid in request
+        let mut changed = headers.remove(header::SEC_WEBSOCKET_KEY).is_some();
+
+        let values_len = headers.values_len();
+        let accept = take_single_value(headers, header::SEC_WEBSOCKET_ACCEPT)?.ok_or(
+            WebSocketHandshakeError::MissingHeader(header::SEC_WEBSOCKET_ACCEPT),
+        )?;
+        if accept.to_str().trim() != self.expected_accept {
+            return Err(WebSocketHandshakeError::UnmatchedAccept);
+        }
+
+        // the server can select at most one sub protocol
+        let _ = take_single_value(headers, header::SEC_WEBSOCKET_PROTOCOL)?;
+        if headers.values_len() != values_len {
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_value() {
+        // the example in rfc6455
+        assert_eq!(
+            websocket_accept_value(b"dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn validate_handshake() {
+        let mut req_headers = HttpHeaderMap::default();
+        req_headers.append(
+            header::SEC_WEBSOCKET_KEY,
+            HttpHeaderValue::from_static("dGhlIHNhbXBsZSBub25jZQ=="),
+        );
+        req_headers.append(
+            header::SEC_WEBSOCKET_KEY,
+            HttpHeaderValue::from_static("dGhlIHNhbXBsZSBub25jZQ=="),
+        );
+        req_headers.append(
+            header::SEC_WEBSOCKET_ACCEPT,
+            HttpHeaderValue::from_static("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="),
+        );
+        assert_eq!(
+            WebSocketHandshakeValidator::check_request(&mut req_headers).err(),
+            Some(WebSocketHandshakeError::MissingHeader(
+                header::SEC_WEBSOCKET_VERSION
+            ))
+        );
+        req_headers.append(
+            header::SEC_WEBSOCKET_VERSION,
+            HttpHeaderValue::from_static("13"),
+        );
+        let validator = WebSocketHandshakeValidator::check_request(&mut req_headers).unwrap();
+        assert!(!req_headers.contains_key(header::SEC_WEBSOCKET_ACCEPT));
+        assert_eq!(
+            req_headers
+                .get_all(header::SEC_WEBSOCKET_KEY)
+                .iter()
+                .count(),
+            1
+        );
+
+        let mut rsp_headers = HttpHeaderMap::default();
+        rsp_headers.append(
+            header::SEC_WEBSOCKET_ACCEPT,
+            HttpHeaderValue::from_static("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="),
+        );
+        assert_eq!(validator.check_response(&mut rsp_headers), Ok(false));
+        rsp_headers.append(
+            header::SEC_WEBSOCKET_ACCEPT,
+            HttpHeaderValue::from_static("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="),
+        );
+        assert_eq!(validator.check_response(&mut rsp_headers), Ok(true));
+        rsp_headers.append(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HttpHeaderValue::from_static("mqtt"),
+        );
+        rsp_headers.append(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HttpHeaderValue::from_static("v12.stomp"),
+        );
+        assert_eq!(
+            validator.check_response(&mut rsp_headers),
+            Err(WebSocketHandshakeError::ConflictHeader(
+                header::SEC_WEBSOCKET_PROTOCOL
+            ))
+        );
+
+        let mut rsp_headers = HttpHeaderMap::default();
+        rsp_headers.append(
+            header::SEC_WEBSOCKET_ACCEPT,
+            HttpHeaderValue::from_static("dGhlIHNhbXBsZSBub25jZQ=="),
+        );
+        assert_eq!(
+            validator.check_response(&mut rsp_headers),
+            Err(WebSocketHandshakeError::UnmatchedAccept)
+        );
+
+        let mut req_headers = HttpHeaderMap::default();
+        req_headers.append(
+            header::SEC_WEBSOCKET_KEY,
+            HttpHeaderValue::from_static("dGhlIHNhbXBsZQ=="),
+        );
+        req_headers.append(
+            header::SEC_WEBSOCKET_VERSION,
+            HttpHeaderValue::from_static("13"),
+        );
+        assert_eq!(
+            WebSocketHandshakeValidator::check_request(&mut req_headers).err(),
+            Some(WebSocketHandshakeError::InvalidHeaderValue(
+                header::SEC_WEBSOCKET_KEY
+            ))
+        );
+    }
 }
@@ -44,8 +44,8 @@ impl FromStr for QueryStrategy {
         match s.to_lowercase().replace('-', "_").as_str() {
             "ipv4only" | "ipv4_only" => Ok(QueryStrategy::Ipv4Only),
             "ipv6only" | "ipv6_only" => Ok(QueryStrategy::Ipv6Only),
-            "ipv4first" | "ipv4_first" => Ok(QueryStrategy::Ipv4First),
-            "ipv6first" | "ipv6_first" => Ok(QueryStrategy::Ipv6First),
+            "ipv4first" | "ipv4_first" | "prefer_ipv4" => Ok(QueryStrategy::Ipv4First),
+            "ipv6first" | "ipv6_first" | "prefer_ipv6" => Ok(QueryStrategy::Ipv6First),
             _ => Err(()),
         }
     }
@@ -182,6 +182,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn t_query_parse() {
+        assert_eq!(
+            QueryStrategy::from_str("ipv4_only").unwrap(),
+            QueryStrategy::Ipv4Only
+        );
+        assert_eq!(
+            QueryStrategy::from_str("Ipv6Only").unwrap(),
+            QueryStrategy::Ipv6Only
+        );
+        assert_eq!(
+            QueryStrategy::from_str("prefer-ipv4").unwrap(),
+            QueryStrategy::Ipv4First
+        );
+        assert_eq!(
+            QueryStrategy::from_str("prefer_ipv6").unwrap(),
+            QueryStrategy::Ipv6First
+        );
+        assert!(QueryStrategy::from_str("prefer").is_err());
+        assert!(QueryStrategy::from_str("prefer_v4").is_err());
+    }
+
     #[test]
     fn t_resolve() {
         let mut s = ResolveStrategy::default();
@@ -20,6 +20,8 @@ use std::sync::atomic::{AtomicIsize, AtomicU64, Ordering};
 pub struct ConnectionPoolStats {
     total_connection: AtomicU64,
     alive_connection: AtomicIsize,
+    reaped_connection: AtomicU64,
+    validate_failed: AtomicU64,
 }
 
 impl ConnectionPoolStats {
@@ -39,4 +41,22 @@ impl ConnectionPoolStats {
             .try_into()
             .unwrap_or_default()
     }
+
+    /// Add an idle connection that has been closed as the idle timeout reached
+    pub fn add_reaped(&self) {
+        self.reaped_connection.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reaped_count(&self) -> u64 {
+        self.reaped_connection.load(Ordering::Relaxed)
+    }
+
+    /// Add an idle connection that is found to be dead when handing it out
+    pub fn add_validate_failed(&self) {
+        self.validate_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn validate_failed_count(&self) -> u64 {
+        self.validate_failed.load(Ordering::Relaxed)
+    }
 }
@@ -21,3 +21,8 @@ mod stream;
 pub use stream::{
     StreamDumpConfig, StreamDumper, ToClientStreamDumpWriter, ToRemoteStreamDumpWriter,
 };
+
+mod pcap;
+pub use pcap::{
+    PcapDumpConfig, PcapDumpReader, PcapDumpSession, PcapDumpWriter, PcapDumper, PcapPacketHeader,
+};
@@ -0,0 +1,44 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PcapDumpConfig {
+    /// the pcap-ng file to append to
+    pub file: PathBuf,
+    /// stop to write new packets if the file size reached this limit
+    pub max_file_size: u64,
+    /// the max number of sessions that can be captured by a single dumper
+    pub max_sessions: usize,
+    /// the max bytes that can be written to the file in a second, packets will be dropped if exceeded
+    pub max_bytes_per_second: u64,
+    pub packet_size: usize,
+    pub client_side: bool,
+}
+
+impl Default for PcapDumpConfig {
+    fn default() -> Self {
+        PcapDumpConfig {
+            file: PathBuf::new(),
+            max_file_size: 64 * 1024 * 1024,
+            max_sessions: 16,
+            max_bytes_per_second: 1024 * 1024,
+            packet_size: 1480,
+            client_side: false,
+        }
+    }
+}
@@ -0,0 +1,297 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::stream::PduHeader;
+
+const EPB_HEADER_LEN: usize = 28;
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const TCP_HEADER_LEN: usize = 20;
+
+pub(super) const TCP_FLAG_SYN: u8 = 0x02;
+pub(super) const TCP_FLAG_PSH: u8 = 0x08;
+pub(super) const TCP_FLAG_ACK: u8 = 0x10;
+
+/// Write the section header block and the interface description block for raw ip packets
+pub(super) fn file_header() -> Vec<u8> {
+    const LINKTYPE_RAW: u16 = 101;
+
+    let mut buf = Vec::with_capacity(48);
+    // section header block
+    buf.extend_from_slice(&0x0A0D0D0Au32.to_le_bytes());
+    buf.extend_from_slice(&28u32.to_le_bytes());
+    buf.extend_from_slice(&0x1A2B3C4Du32.to_le_bytes());
+    buf.extend_from_slice(&1u16.to_le_bytes()); // major version
+    buf.extend_from_slice(&0u16.to_le_bytes()); // minor version
+    buf.extend_from_slice(&(-1i64).to_le_bytes()); // section length not specified
+    buf.extend_from_slice(&28u32.to_le_bytes());
+    // interface description block
+    buf.extend_from_slice(&1u32.to_le_bytes());
+    buf.extend_from_slice(&20u32.to_le_bytes());
+    buf.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    buf.extend_from_slice(&0u16.to_le_bytes()); // reserved
+    buf.extend_from_slice(&0u32.to_le_bytes()); // no snap len limit
+    buf.extend_from_slice(&20u32.to_le_bytes());
+    buf
+}
+
+/// The sequence state of the synthetic tcp flow.
+///
+/// The ISN of both sides are 0, so the first data byte will be at seq 1.
+pub(super) struct PcapTcpState {
+    write_to_client: AtomicU32,
+    write_to_remote: AtomicU32,
+}
+
+impl Default for PcapTcpState {
+    fn default() -> Self {
+        PcapTcpState {
+            write_to_client: AtomicU32::new(1),
+            write_to_remote: AtomicU32::new(1),
+        }
+    }
+}
+
+/// Use ipv4-mapped ipv6 address if the address family is not the same
+fn normalize_addr_pair(a: SocketAddr, b: SocketAddr) -> (SocketAddr, SocketAddr) {
+    match (a.ip(), b.ip()) {
+        (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => (a, b),
+        (IpAddr::V4(ip4), IpAddr::V6(_)) => (
+            SocketAddr::new(IpAddr::V6(ip4.to_ipv6_mapped()), a.port()),
+            b,
+        ),
+        (IpAddr::V6(_), IpAddr::V4(ip4)) => (
+            a,
+            SocketAddr::new(IpAddr::V6(ip4.to_ipv6_mapped()), b.port()),
+        ),
+    }
+}
+
+#[derive(Clone)]
+pub struct PcapPacketHeader {
+    src: SocketAddr,
+    dst: SocketAddr,
+    to_client: bool,
+    state: Arc<PcapTcpState>,
+}
+
+impl PcapPacketHeader {
+    pub(super) fn new_pair(client: SocketAddr, remote: SocketAddr) -> (Self, Self) {
+        let (client, remote) = normalize_addr_pair(client, remote);
+        let state = Arc::new(PcapTcpState::default());
+        let to_client = PcapPacketHeader {
+            src: remote,
+            dst: client,
+            to_client: true,
+            state: state.clone(),
+        };
+        let to_remote = PcapPacketHeader {
+            src: client,
+            dst: remote,
+            to_client: false,
+            state,
+        };
+        (to_client, to_remote)
+    }
+
+    fn ip_header_len(&self) -> usize {
+        if self.src.is_ipv4() {
+            IPV4_HEADER_LEN
+        } else {
+            IPV6_HEADER_LEN
+        }
+    }
+
+    fn build_template(&self, capacity: usize) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(capacity);
+        // enhanced packet block header, will be updated later
+        buf.extend_from_slice(&6u32.to_le_bytes());
+        buf.resize(EPB_HEADER_LEN, 0);
+
+        match (self.src.ip(), self.dst.ip()) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                buf.extend_from_slice(&[0x45, 0x00, 0x00, 0x00]); // total length to be set
+                buf.extend_from_slice(&[0x00, 0x00, 0x40, 0x00]); // DF
+                buf.extend_from_slice(&[64, 6, 0x00, 0x00]); // ttl, tcp, checksum to be set
+                buf.extend_from_slice(&src.octets());
+                buf.extend_from_slice(&dst.octets());
+            }
+            (IpAddr::V6(src), IpAddr::V6(dst)) => {
+                buf.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+                buf.extend_from_slice(&[0x00, 0x00, 6, 64]); // payload length to be set
+                buf.extend_from_slice(&src.octets());
+                buf.extend_from_slice(&dst.octets());
+            }
+            _ => unreachable!(),
+        }
+
+        buf.extend_from_slice(&self.src.port().to_be_bytes());
+        buf.extend_from_slice(&self.dst.port().to_be_bytes());
+        buf.extend_from_slice(&[0u8; 8]); // seq and ack to be set
+        buf.extend_from_slice(&[0x50, TCP_FLAG_ACK | TCP_FLAG_PSH, 0xFF, 0xFF]);
+        buf.extend_from_slice(&[0u8; 4]); // checksum is not calculated
+        buf
+    }
+
+    fn finalize(&self, buf: &mut Vec<u8>, seq: u32, ack: u32, flags: u8, data_len: usize) {
+        let ip_offset = EPB_HEADER_LEN;
+        let tcp_offset = ip_offset + self.ip_header_len();
+        let packet_len = self.ip_header_len() + TCP_HEADER_LEN + data_len;
+
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or_default();
+        buf[12..16].copy_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        buf[16..20].copy_from_slice(&(ts as u32).to_le_bytes());
+        buf[20..24].copy_from_slice(&(packet_len as u32).to_le_bytes());
+        buf[24..28].copy_from_slice(&(packet_len as u32).to_le_bytes());
+
+        if self.src.is_ipv4() {
+            buf[ip_offset + 2..ip_offset + 4].copy_from_slice(&(packet_len as u16).to_be_bytes());
+            let checksum = ipv4_header_checksum(&buf[ip_offset..tcp_offset]);
+            buf[ip_offset + 10..ip_offset + 12].copy_from_slice(&checksum.to_be_bytes());
+        } else {
+            let payload_len = (TCP_HEADER_LEN + data_len) as u16;
+            buf[ip_offset + 4..ip_offset + 6].copy_from_slice(&payload_len.to_be_bytes());
+        }
+
+        buf[tcp_offset + 4..tcp_offset + 8].copy_from_slice(&seq.to_be_bytes());
+        buf[tcp_offset + 8..tcp_offset + 12].copy_from_slice(&ack.to_be_bytes());
+        buf[tcp_offset + 13] = flags;
+
+        while !buf.len().is_multiple_of(4) {
+            buf.push(0);
+        }
+        let block_len = (buf.len() + 4) as u32;
+        buf[4..8].copy_from_slice(&block_len.to_le_bytes());
+        buf.extend_from_slice(&block_len.to_le_bytes());
+    }
+
+    /// Get the next seq of this direction and the next seq of the reverse direction
+    pub(super) fn write_seq(&self) -> (u32, u32) {
+        let to_client = self.state.write_to_client.load(Ordering::Relaxed);
+        let to_remote = self.state.write_to_remote.load(Ordering::Relaxed);
+        if self.to_client {
+            (to_client, to_remote)
+        } else {
+            (to_remote, to_client)
+        }
+    }
+
+    /// Build a packet without data
+    pub(super) fn build_control_packet(&self, seq: u32, ack: u32, flags: u8) -> Vec<u8> {
+        let mut buf = self.build_template(128);
+        self.finalize(&mut buf, seq, ack, flags, 0);
+        buf
+    }
+}
+
+impl PduHeader for PcapPacketHeader {
+    fn new_header(&mut self, pkt_size: usize) -> Vec<u8> {
+        self.build_template(pkt_size + 4)
+    }
+
+    fn update_tcp_dissector_data(&self, hdr: &mut Vec<u8>, data_len: usize) {
+        let (seq, ack) = self.write_seq();
+        self.finalize(hdr, seq, ack, TCP_FLAG_ACK | TCP_FLAG_PSH, data_len);
+    }
+
+    fn record_written_data(&self, data_len: usize) {
+        if self.to_client {
+            self.state
+                .write_to_client
+                .fetch_add(data_len as u32, Ordering::Relaxed);
+        } else {
+            self.state
+                .write_to_remote
+                .fetch_add(data_len as u32, Ordering::Relaxed);
+        }
+    }
+}
+
+fn ipv4_header_checksum(hdr: &[u8]) -> u16 {
+    let mut sum = 0u32;
+    for (i, chunk) in hdr.chunks(2).enumerate() {
+        if i == 5 {
+            // skip the checksum field
+            continue;
+        }
+        let v = u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)]);
+        sum += v as u32;
+    }
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn checksum() {
+        let hdr = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0xb8, 0x61, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        assert_eq!(ipv4_header_checksum(&hdr), 0xb861);
+    }
+
+    #[test]
+    fn data_packet() {
+        let client = SocketAddr::from_str("192.168.0.1:50000").unwrap();
+        let remote = SocketAddr::from_str("10.0.0.1:443").unwrap();
+        let (mut to_client, mut to_remote) = PcapPacketHeader::new_pair(client, remote);
+
+        let mut buf = to_remote.new_header(1480);
+        assert_eq!(buf.len(), EPB_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN);
+        buf.extend_from_slice(b"GET");
+        to_remote.update_tcp_dissector_data(&mut buf, 3);
+        to_remote.record_written_data(3);
+        assert_eq!(buf.len() % 4, 0);
+        assert_eq!(&buf[4..8], &(buf.len() as u32).to_le_bytes());
+        assert_eq!(&buf[buf.len() - 4..], &(buf.len() as u32).to_le_bytes());
+        assert_eq!(&buf[20..24], &43u32.to_le_bytes());
+        assert_eq!(&buf[30..32], &43u16.to_be_bytes());
+        assert_eq!(&buf[52..56], &1u32.to_be_bytes()); // seq
+        assert_eq!(&buf[56..60], &1u32.to_be_bytes()); // ack
+
+        let mut buf = to_client.new_header(1480);
+        buf.extend_from_slice(b"HTTP");
+        to_client.update_tcp_dissector_data(&mut buf, 4);
+        assert_eq!(&buf[48..50], &443u16.to_be_bytes());
+        assert_eq!(&buf[52..56], &1u32.to_be_bytes()); // seq
+        assert_eq!(&buf[56..60], &4u32.to_be_bytes()); // ack
+    }
+
+    #[test]
+    fn mixed_family() {
+        let client = SocketAddr::from_str("192.168.0.1:50000").unwrap();
+        let remote = SocketAddr::from_str("[2001:db8::1]:443").unwrap();
+        let (_, to_remote) = PcapPacketHeader::new_pair(client, remote);
+        let buf = to_remote.build_control_packet(0, 0, TCP_FLAG_SYN);
+        assert_eq!(buf[EPB_HEADER_LEN] >> 4, 6);
+        assert_eq!(&buf[20..24], &60u32.to_le_bytes());
+    }
+}
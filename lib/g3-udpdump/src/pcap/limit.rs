@@ -0,0 +1,123 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use super::PcapDumpConfig;
+
+/// The file size and rate limit, which should be checked before the packet is queued
+pub(super) struct PcapDumpLimiter {
+    max_file_size: AtomicU64,
+    max_bytes_per_second: AtomicU64,
+    file_size: AtomicU64,
+    start: Instant,
+    second: AtomicU64,
+    second_written: AtomicU64,
+}
+
+impl PcapDumpLimiter {
+    pub(super) fn new(config: &PcapDumpConfig, file_size: u64) -> Self {
+        PcapDumpLimiter {
+            max_file_size: AtomicU64::new(config.max_file_size),
+            max_bytes_per_second: AtomicU64::new(config.max_bytes_per_second),
+            file_size: AtomicU64::new(file_size),
+            start: Instant::now(),
+            second: AtomicU64::new(0),
+            second_written: AtomicU64::new(0),
+        }
+    }
+
+    pub(super) fn update(&self, config: &PcapDumpConfig) {
+        self.max_file_size
+            .store(config.max_file_size, Ordering::Relaxed);
+        self.max_bytes_per_second
+            .store(config.max_bytes_per_second, Ordering::Relaxed);
+    }
+
+    /// Reserve the space for a packet, return false if it should be dropped
+    pub(super) fn acquire(&self, size: u64) -> bool {
+        let max_file_size = self.max_file_size.load(Ordering::Relaxed);
+        let file_size = self.file_size.fetch_add(size, Ordering::Relaxed);
+        if file_size + size > max_file_size {
+            self.file_size.fetch_sub(size, Ordering::Relaxed);
+            return false;
+        }
+
+        let cur_second = self.start.elapsed().as_secs();
+        let last_second = self.second.load(Ordering::Relaxed);
+        if cur_second != last_second
+            && self
+                .second
+                .compare_exchange(
+                    last_second,
+                    cur_second,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        {
+            self.second_written.store(0, Ordering::Relaxed);
+        }
+        let max_bytes_per_second = self.max_bytes_per_second.load(Ordering::Relaxed);
+        let second_written = self.second_written.fetch_add(size, Ordering::Relaxed);
+        if second_written + size > max_bytes_per_second {
+            self.second_written.fetch_sub(size, Ordering::Relaxed);
+            self.file_size.fetch_sub(size, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Release the space of a packet that has not been queued
+    pub(super) fn release(&self, size: u64) {
+        self.file_size.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_size() {
+        let config = PcapDumpConfig {
+            max_file_size: 100,
+            max_bytes_per_second: 1000,
+            ..Default::default()
+        };
+        let limiter = PcapDumpLimiter::new(&config, 60);
+        assert!(limiter.acquire(40));
+        assert!(!limiter.acquire(1));
+        limiter.release(40);
+        assert!(limiter.acquire(20));
+    }
+
+    #[test]
+    fn rate() {
+        let config = PcapDumpConfig {
+            max_file_size: 1000,
+            max_bytes_per_second: 100,
+            ..Default::default()
+        };
+        let limiter = PcapDumpLimiter::new(&config, 0);
+        assert!(limiter.acquire(60));
+        assert!(!limiter.acquire(60));
+        assert!(limiter.acquire(40));
+        // the dropped packet should not take the file space
+        assert_eq!(limiter.file_size.load(Ordering::Relaxed), 100);
+    }
+}
@@ -0,0 +1,285 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc;
+
+mod config;
+pub use config::PcapDumpConfig;
+
+mod header;
+pub use header::PcapPacketHeader;
+use header::{TCP_FLAG_ACK, TCP_FLAG_SYN};
+
+mod limit;
+use limit::PcapDumpLimiter;
+
+mod sink;
+use sink::PcapSinker;
+
+mod state;
+use state::PcapDumpState;
+
+mod read;
+pub use read::PcapDumpReader;
+
+mod write;
+pub use write::PcapDumpWriter;
+
+/// The max number of packets that can be queued, new packets will be dropped if full
+const PACKET_QUEUE_SIZE: usize = 1024;
+
+struct PcapDumperShared {
+    config: Mutex<PcapDumpConfig>,
+    sender: mpsc::Sender<Vec<u8>>,
+    limiter: PcapDumpLimiter,
+    sessions: AtomicUsize,
+}
+
+impl PcapDumperShared {
+    fn send_packet(&self, packet: Vec<u8>) {
+        let size = packet.len() as u64;
+        if !self.limiter.acquire(size) {
+            return;
+        }
+        if self.sender.try_send(packet).is_err() {
+            self.limiter.release(size);
+        }
+    }
+
+    fn acquire_session(&self) -> bool {
+        let max_sessions = self.config.lock().unwrap().max_sessions;
+        let mut cur = self.sessions.load(Ordering::Relaxed);
+        loop {
+            if cur >= max_sessions {
+                return false;
+            }
+            match self
+                .sessions
+                .compare_exchange(cur, cur + 1, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(v) => cur = v,
+            }
+        }
+    }
+}
+
+/// Dump the plaintext streams to a pcap-ng file, with synthetic ip and tcp headers
+pub struct PcapDumper {
+    shared: Arc<PcapDumperShared>,
+}
+
+impl PcapDumper {
+    pub fn new(config: PcapDumpConfig) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel(PACKET_QUEUE_SIZE);
+        let (sinker, file_size) =
+            PcapSinker::new(config.file.clone(), config.max_file_size, receiver)?;
+        std::thread::Builder::new()
+            .name("pcap-dump".to_string())
+            .spawn(move || sinker.into_running())?;
+        let limiter = PcapDumpLimiter::new(&config, file_size);
+        Ok(PcapDumper {
+            shared: Arc::new(PcapDumperShared {
+                config: Mutex::new(config),
+                sender,
+                limiter,
+                sessions: AtomicUsize::new(0),
+            }),
+        })
+    }
+
+    /// Update the limits of the running dumper, the file path won't be changed.
+    ///
+    /// The session count will be reset.
+    pub fn update_config(&self, config: PcapDumpConfig) {
+        self.shared.limiter.update(&config);
+        *self.shared.config.lock().unwrap() = config;
+        self.shared.sessions.store(0, Ordering::Relaxed);
+    }
+
+    /// Create a new capture session.
+    ///
+    /// The `selected` callback will be checked each time before the data is dumped, and the
+    /// capture will be started the first time it returns true, if the max sessions limit has
+    /// not been reached by then.
+    pub fn new_session<F>(
+        &self,
+        client_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        selected: F,
+    ) -> PcapDumpSession
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        let (packet_size, client_side) = {
+            let config = self.shared.config.lock().unwrap();
+            (config.packet_size, config.client_side)
+        };
+        let (to_client, to_remote) = PcapPacketHeader::new_pair(client_addr, remote_addr);
+        PcapDumpSession {
+            shared: Arc::new(PcapSessionShared {
+                dumper: self.shared.clone(),
+                to_client,
+                to_remote,
+                selected: Box::new(selected),
+                state: AtomicU8::new(SESSION_STATE_IDLE),
+                start_lock: Mutex::new(()),
+            }),
+            packet_size,
+            client_side,
+        }
+    }
+}
+
+const SESSION_STATE_IDLE: u8 = 0;
+const SESSION_STATE_STARTED: u8 = 1;
+const SESSION_STATE_REJECTED: u8 = 2;
+
+struct PcapSessionShared {
+    dumper: Arc<PcapDumperShared>,
+    to_client: PcapPacketHeader,
+    to_remote: PcapPacketHeader,
+    selected: Box<dyn Fn() -> bool + Send + Sync>,
+    state: AtomicU8,
+    start_lock: Mutex<()>,
+}
+
+impl PcapSessionShared {
+    /// Check whether the data should be dumped, and start the capture if needed
+    fn is_active(&self) -> bool {
+        match self.state.load(Ordering::Acquire) {
+            SESSION_STATE_STARTED => (self.selected)(),
+            SESSION_STATE_REJECTED => false,
+            _ => {
+                if !(self.selected)() {
+                    return false;
+                }
+                self.start()
+            }
+        }
+    }
+
+    fn start(&self) -> bool {
+        let _guard = self.start_lock.lock().unwrap();
+        match self.state.load(Ordering::Acquire) {
+            SESSION_STATE_STARTED => return true,
+            SESSION_STATE_REJECTED => return false,
+            _ => {}
+        }
+        if !self.dumper.acquire_session() {
+            self.state.store(SESSION_STATE_REJECTED, Ordering::Release);
+            return false;
+        }
+
+        // a fake handshake, so the flow can be followed by the tcp dissector,
+        // the ISN is chosen to match the data that has already been transferred
+        let (to_remote_seq, to_client_seq) = self.to_remote.write_seq();
+        let to_remote_isn = to_remote_seq.wrapping_sub(1);
+        let to_client_isn = to_client_seq.wrapping_sub(1);
+        self.dumper.send_packet(self.to_remote.build_control_packet(
+            to_remote_isn,
+            0,
+            TCP_FLAG_SYN,
+        ));
+        self.dumper.send_packet(self.to_client.build_control_packet(
+            to_client_isn,
+            to_remote_seq,
+            TCP_FLAG_SYN | TCP_FLAG_ACK,
+        ));
+        self.dumper.send_packet(self.to_remote.build_control_packet(
+            to_remote_seq,
+            to_client_seq,
+            TCP_FLAG_ACK,
+        ));
+        self.state.store(SESSION_STATE_STARTED, Ordering::Release);
+        true
+    }
+}
+
+pub struct PcapDumpSession {
+    shared: Arc<PcapSessionShared>,
+    packet_size: usize,
+    client_side: bool,
+}
+
+impl PcapDumpSession {
+    #[inline]
+    pub fn client_side(&self) -> bool {
+        self.client_side
+    }
+
+    pub fn wrap_remote_io<R, W>(
+        &self,
+        remote_reader: R,
+        remote_writer: W,
+    ) -> (PcapDumpReader<R>, PcapDumpWriter<W>)
+    where
+        R: AsyncRead,
+        W: AsyncWrite,
+    {
+        let r = PcapDumpReader::new(
+            remote_reader,
+            PcapDumpState::new(
+                self.shared.to_client.clone(),
+                self.shared.clone(),
+                self.packet_size,
+            ),
+        );
+        let w = PcapDumpWriter::new(
+            remote_writer,
+            PcapDumpState::new(
+                self.shared.to_remote.clone(),
+                self.shared.clone(),
+                self.packet_size,
+            ),
+        );
+        (r, w)
+    }
+
+    pub fn wrap_client_io<R, W>(
+        &self,
+        client_reader: R,
+        client_writer: W,
+    ) -> (PcapDumpReader<R>, PcapDumpWriter<W>)
+    where
+        R: AsyncRead,
+        W: AsyncWrite,
+    {
+        let r = PcapDumpReader::new(
+            client_reader,
+            PcapDumpState::new(
+                self.shared.to_remote.clone(),
+                self.shared.clone(),
+                self.packet_size,
+            ),
+        );
+        let w = PcapDumpWriter::new(
+            client_writer,
+            PcapDumpState::new(
+                self.shared.to_client.clone(),
+                self.shared.clone(),
+                self.packet_size,
+            ),
+        );
+        (r, w)
+    }
+}
@@ -0,0 +1,78 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+use log::warn;
+use tokio::sync::mpsc;
+
+pub(super) struct PcapSinker {
+    file: PathBuf,
+    receiver: mpsc::Receiver<Vec<u8>>,
+    writer: BufWriter<File>,
+}
+
+impl PcapSinker {
+    /// Open the pcap file and start a new section, return the sinker with the file size
+    pub(super) fn new(
+        file: PathBuf,
+        max_file_size: u64,
+        receiver: mpsc::Receiver<Vec<u8>>,
+    ) -> io::Result<(Self, u64)> {
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        // the file contains decrypted plaintext, so it should only be readable by the owner
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file_handle = options.open(&file)?;
+        let mut file_size = file_handle.metadata()?.len();
+        if file_size < max_file_size {
+            // always start a new section, so we can append to existing files
+            let hdr = super::header::file_header();
+            file_handle.write_all(&hdr)?;
+            file_size += hdr.len() as u64;
+        }
+        let sinker = PcapSinker {
+            file,
+            receiver,
+            writer: BufWriter::new(file_handle),
+        };
+        Ok((sinker, file_size))
+    }
+
+    pub(super) fn into_running(mut self) {
+        while let Some(packet) = self.receiver.blocking_recv() {
+            if let Err(e) = self.writer.write_all(&packet) {
+                warn!("failed to write to pcap file {}: {e}", self.file.display());
+                break;
+            }
+            while let Ok(packet) = self.receiver.try_recv() {
+                if let Err(e) = self.writer.write_all(&packet) {
+                    warn!("failed to write to pcap file {}: {e}", self.file.display());
+                    return;
+                }
+            }
+            if let Err(e) = self.writer.flush() {
+                warn!("failed to flush pcap file {}: {e}", self.file.display());
+                break;
+            }
+        }
+    }
+}
@@ -31,7 +31,7 @@ mod sink;
 use sink::Sinker;
 
 mod header;
-use header::PduHeader;
+pub(crate) use header::PduHeader;
 pub use header::{ToClientPduHeader, ToRemotePduHeader};
 
 mod state;
@@ -32,11 +32,14 @@ mod user_agent;
 pub(crate) use child_domain::as_child_domain_rule_builder;
 pub(crate) use exact_host::as_exact_host_rule;
 pub(crate) use network::as_dst_subnet_rule_builder;
-pub(crate) use regex_set::as_regex_set_rule_builder;
 
 pub use exact_port::as_exact_port_rule;
-pub use network::{as_egress_network_rule_builder, as_ingress_network_rule_builder};
+pub use network::{
+    as_egress_network_rule_builder, as_ingress_network_rule_builder,
+    as_ordered_network_rule_builder,
+};
 pub use proxy_request::as_proxy_request_rule;
+pub use regex_set::as_regex_set_rule_builder;
 pub use user_agent::as_user_agent_rule;
 
 fn as_action(value: &Yaml) -> anyhow::Result<AclAction> {
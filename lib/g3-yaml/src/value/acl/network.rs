@@ -14,9 +14,12 @@
  * limitations under the License.
  */
 
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
 use yaml_rust::Yaml;
 
-use g3_types::acl::{AclAction, AclNetworkRuleBuilder};
+use g3_types::acl::{AclAction, AclNetworkRuleBuilder, AclOrderedNetworkRuleBuilder};
 
 use super::AclRuleYamlParser;
 
@@ -55,3 +58,155 @@ pub fn as_ingress_network_rule_builder(value: &Yaml) -> anyhow::Result<AclNetwor
     builder.parse(value)?;
     Ok(builder)
 }
+
+fn add_ordered_network_rule(
+    builder: &mut AclOrderedNetworkRuleBuilder,
+    value: &Yaml,
+) -> anyhow::Result<()> {
+    let Yaml::Hash(map) = value else {
+        return Err(anyhow!(
+            "yaml value type for 'ordered network rule' should be 'map'"
+        ));
+    };
+
+    let mut reason: Option<String> = None;
+    let mut action_networks = None;
+    crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+        "reason" => {
+            let s =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            reason = Some(s);
+            Ok(())
+        }
+        _ => {
+            let action = AclAction::from_str(k)
+                .map_err(|_| anyhow!("the key {k} is not a valid AclAction"))?;
+            if action_networks.is_some() {
+                return Err(anyhow!("only one action is allowed in a single rule"));
+            }
+            let networks = if let Yaml::Array(seq) = v {
+                let mut networks = Vec::with_capacity(seq.len());
+                for (i, v) in seq.iter().enumerate() {
+                    let net = crate::value::as_ip_network(v)
+                        .context(format!("invalid ip network value for {k}#{i}"))?;
+                    networks.push(net);
+                }
+                networks
+            } else {
+                let net = crate::value::as_ip_network(v)
+                    .context(format!("invalid ip network value for key {k}"))?;
+                vec![net]
+            };
+            action_networks = Some((action, networks));
+            Ok(())
+        }
+    })?;
+
+    let Some((action, networks)) = action_networks else {
+        return Err(anyhow!("no action set in this rule"));
+    };
+    for net in networks {
+        builder.push_network(net, action, reason.clone());
+    }
+    Ok(())
+}
+
+fn add_ordered_network_rules(
+    builder: &mut AclOrderedNetworkRuleBuilder,
+    value: &Yaml,
+) -> anyhow::Result<()> {
+    if let Yaml::Array(seq) = value {
+        for (i, v) in seq.iter().enumerate() {
+            add_ordered_network_rule(builder, v).context(format!("invalid rule #{i}"))?;
+        }
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "yaml value type for 'ordered network rules' should be 'seq'"
+        ))
+    }
+}
+
+/// Parse network rules that will be checked in order.
+///
+/// The value should be a seq of rules, or a map with `default` and `rules` keys.
+/// Each rule is a map with a single action key, whose value is one or more networks,
+/// and an optional `reason` key.
+pub fn as_ordered_network_rule_builder(
+    value: &Yaml,
+) -> anyhow::Result<AclOrderedNetworkRuleBuilder> {
+    let mut builder = AclOrderedNetworkRuleBuilder::new(AclAction::Forbid);
+    match value {
+        Yaml::Hash(map) => {
+            crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+                "default" => {
+                    let action =
+                        super::as_action(v).context(format!("invalid value for key {k}"))?;
+                    builder.set_missed_action(action);
+                    Ok(())
+                }
+                "rules" => add_ordered_network_rules(&mut builder, v)
+                    .context(format!("invalid ordered network rules value for key {k}")),
+                _ => Err(anyhow!("invalid key {k}")),
+            })?;
+        }
+        Yaml::Array(_) => add_ordered_network_rules(&mut builder, value)?,
+        _ => {
+            return Err(anyhow!(
+                "yaml value type for 'ordered network rule' should be 'map' or 'seq'"
+            ))
+        }
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn ordered_network_rule() {
+        let yaml = r#"
+            default: permit
+            rules:
+              - forbid_log: 192.168.1.0/24
+                reason: guest network
+              - permit: [192.168.0.0/16, "2001:db8::/32"]
+              - forbid: 192.168.0.0/16
+        "#;
+        let docs = YamlLoader::load_from_str(yaml).unwrap();
+        let builder = as_ordered_network_rule_builder(&docs[0]).unwrap();
+        assert_eq!(builder.missed_action(), AclAction::Permit);
+
+        let rule = builder.build();
+        assert_eq!(
+            rule.check(IpAddr::from_str("192.168.1.1").unwrap()),
+            (true, AclAction::ForbidAndLog, Some("guest network"))
+        );
+        assert_eq!(
+            rule.check(IpAddr::from_str("192.168.2.1").unwrap()),
+            (true, AclAction::Permit, None)
+        );
+        assert_eq!(
+            rule.check(IpAddr::from_str("2001:db8::1").unwrap()),
+            (true, AclAction::Permit, None)
+        );
+        assert_eq!(
+            rule.check(IpAddr::from_str("10.0.0.1").unwrap()),
+            (false, AclAction::Permit, None)
+        );
+
+        let docs = YamlLoader::load_from_str("- permit: 10.0.0.0/8").unwrap();
+        let builder = as_ordered_network_rule_builder(&docs[0]).unwrap();
+        assert_eq!(builder.missed_action(), AclAction::Forbid);
+
+        let docs =
+            YamlLoader::load_from_str("- permit: 10.0.0.0/8\n  forbid: 10.0.0.0/16").unwrap();
+        assert!(as_ordered_network_rule_builder(&docs[0]).is_err());
+
+        let docs = YamlLoader::load_from_str("- reason: no action").unwrap();
+        assert!(as_ordered_network_rule_builder(&docs[0]).is_err());
+    }
+}
@@ -56,7 +56,7 @@ fn as_regex(value: &Yaml) -> anyhow::Result<Regex> {
     }
 }
 
-pub(crate) fn as_regex_set_rule_builder(value: &Yaml) -> anyhow::Result<AclRegexSetRuleBuilder> {
+pub fn as_regex_set_rule_builder(value: &Yaml) -> anyhow::Result<AclRegexSetRuleBuilder> {
     let mut builder = AclRegexSetRuleBuilder::new(AclAction::Forbid);
     builder.parse(value)?;
     Ok(builder)
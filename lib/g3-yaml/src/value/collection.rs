@@ -16,10 +16,10 @@
 
 use std::str::FromStr;
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use yaml_rust::Yaml;
 
-use g3_types::collection::SelectivePickPolicy;
+use g3_types::collection::{SelectivePickOverride, SelectivePickPolicy};
 
 pub fn as_selective_pick_policy(value: &Yaml) -> anyhow::Result<SelectivePickPolicy> {
     if let Yaml::String(s) = value {
@@ -32,3 +32,56 @@ pub fn as_selective_pick_policy(value: &Yaml) -> anyhow::Result<SelectivePickPol
         ))
     }
 }
+
+pub fn as_selective_pick_override(value: &Yaml) -> anyhow::Result<SelectivePickOverride> {
+    if let Yaml::Hash(map) = value {
+        let mut pick_override = None;
+        crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+            "seed" => {
+                let seed =
+                    crate::value::as_u64(v).context(format!("invalid u64 value for key {k}"))?;
+                pick_override = Some(SelectivePickOverride::Seeded(seed));
+                Ok(())
+            }
+            "pin" | "index" => {
+                let index = crate::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                pick_override = Some(SelectivePickOverride::Pinned(index));
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+        pick_override.ok_or_else(|| anyhow!("no seed or pin index set"))
+    } else {
+        Err(anyhow!(
+            "yaml value type for 'selective pick override' should be 'map'"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn pick_override() {
+        let docs = YamlLoader::load_from_str("seed: 42").unwrap();
+        assert_eq!(
+            as_selective_pick_override(&docs[0]).unwrap(),
+            SelectivePickOverride::Seeded(42)
+        );
+
+        let docs = YamlLoader::load_from_str("pin: 1").unwrap();
+        assert_eq!(
+            as_selective_pick_override(&docs[0]).unwrap(),
+            SelectivePickOverride::Pinned(1)
+        );
+
+        let docs = YamlLoader::load_from_str("{}").unwrap();
+        assert!(as_selective_pick_override(&docs[0]).is_err());
+
+        let docs = YamlLoader::load_from_str("42").unwrap();
+        assert!(as_selective_pick_override(&docs[0]).is_err());
+    }
+}
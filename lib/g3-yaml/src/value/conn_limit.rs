@@ -0,0 +1,101 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::num::NonZeroUsize;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_types::limit::ClientConnLimitConfig;
+
+fn as_max_per_ip(v: &Yaml) -> anyhow::Result<NonZeroUsize> {
+    let max = crate::value::as_usize(v)?;
+    NonZeroUsize::new(max).ok_or_else(|| anyhow!("the max connection count should not be 0"))
+}
+
+pub fn as_client_conn_limit_config(v: &Yaml) -> anyhow::Result<ClientConnLimitConfig> {
+    match v {
+        Yaml::Integer(_) => {
+            let max = as_max_per_ip(v)?;
+            Ok(ClientConnLimitConfig::new(max))
+        }
+        Yaml::Hash(map) => {
+            let v = crate::hash_get_required(map, "max_per_ip")?;
+            let max = as_max_per_ip(v).context("invalid value for key max_per_ip")?;
+            let mut config = ClientConnLimitConfig::new(max);
+
+            crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+                "max_per_ip" => Ok(()),
+                "ipv4_prefix" => {
+                    let prefix =
+                        crate::value::as_u8(v).context(format!("invalid u8 value for key {k}"))?;
+                    config.set_ipv4_prefix(prefix)
+                }
+                "ipv6_prefix" => {
+                    let prefix =
+                        crate::value::as_u8(v).context(format!("invalid u8 value for key {k}"))?;
+                    config.set_ipv6_prefix(prefix)
+                }
+                "tarpit_delay" => {
+                    let delay = crate::humanize::as_duration(v)
+                        .context(format!("invalid humanize duration value for key {k}"))?;
+                    config.set_tarpit_delay(delay);
+                    Ok(())
+                }
+                _ => Err(anyhow!("invalid key {k}")),
+            })?;
+
+            Ok(config)
+        }
+        _ => Err(anyhow!("invalid yaml value type")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn client_conn_limit() {
+        let v = Yaml::Integer(10);
+        let config = as_client_conn_limit_config(&v).unwrap();
+        assert_eq!(config.max_per_ip(), 10);
+
+        let v = Yaml::Integer(0);
+        assert!(as_client_conn_limit_config(&v).is_err());
+
+        let s = "\
+max_per_ip: 20
+ipv6_prefix: 56
+tarpit_delay: 1s
+        ";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let config = as_client_conn_limit_config(&docs[0]).unwrap();
+        let mut exp = ClientConnLimitConfig::new(NonZeroUsize::new(20).unwrap());
+        exp.set_ipv6_prefix(56).unwrap();
+        exp.set_tarpit_delay(Duration::from_secs(1));
+        assert_eq!(config, exp);
+
+        let s = "\
+max_per_ip: 20
+ipv4_prefix: 33
+        ";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        assert!(as_client_conn_limit_config(&docs[0]).is_err());
+    }
+}
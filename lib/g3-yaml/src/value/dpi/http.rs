@@ -14,10 +14,144 @@
  * limitations under the License.
  */
 
+use std::str::FromStr;
+
 use anyhow::{anyhow, Context};
+use http::HeaderName;
 use yaml_rust::Yaml;
 
-use g3_dpi::{H1InterceptionConfig, H2InterceptionConfig};
+use g3_dpi::{
+    H1InterceptionConfig, H2InterceptionConfig, HttpBodyHashMode, HttpBodySizeLimitPolicy,
+    HttpMultipartInspectionConfig, HttpResponseHeaderInjection, ProtocolInspectAction,
+};
+use g3_types::net::{HttpHeaderMergeMode, HttpHeaderValue};
+
+fn as_http_body_size_limit_policy(value: &Yaml) -> anyhow::Result<HttpBodySizeLimitPolicy> {
+    if let Yaml::String(s) = value {
+        HttpBodySizeLimitPolicy::from_str(s)
+            .map_err(|_| anyhow!("invalid http body size limit policy '{s}'"))
+    } else {
+        Err(anyhow!(
+            "yaml value type for 'http body size limit policy' should be 'string'"
+        ))
+    }
+}
+
+fn as_http_body_hash_mode(value: &Yaml) -> anyhow::Result<Option<HttpBodyHashMode>> {
+    match value {
+        Yaml::Boolean(true) => Ok(Some(HttpBodyHashMode::Raw)),
+        Yaml::Boolean(false) => Ok(None),
+        Yaml::String(s) => HttpBodyHashMode::from_str(s)
+            .map(Some)
+            .map_err(|_| anyhow!("invalid http body hash mode '{s}'")),
+        _ => Err(anyhow!(
+            "yaml value type for 'http body hash mode' should be 'boolean' or 'string'"
+        )),
+    }
+}
+
+fn as_http_response_header_injection(value: &Yaml) -> anyhow::Result<HttpResponseHeaderInjection> {
+    let Yaml::Hash(map) = value else {
+        return Err(anyhow!(
+            "yaml value type for 'http response header injection' should be 'map'"
+        ));
+    };
+
+    let mut injection = HttpResponseHeaderInjection::default();
+    crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+        "headers" | "header" => {
+            let Yaml::Hash(map) = v else {
+                return Err(anyhow!(
+                    "invalid yaml value type for key {k}, should be map"
+                ));
+            };
+            crate::foreach_kv(map, |name, value| {
+                let header_name = HeaderName::from_str(name)
+                    .map_err(|e| anyhow!("invalid header name {name}: {e}"))?;
+                let value = crate::value::as_string(value)?;
+                let mut header_value = HttpHeaderValue::from_str(&value)
+                    .map_err(|_| anyhow!("invalid value for header {name}"))?;
+                header_value.set_original_name(name);
+                injection.headers.append(header_name, header_value);
+                Ok(())
+            })
+            .context(format!("invalid http headers value for key {k}"))
+        }
+        "mode" => {
+            let mode =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            injection.mode = HttpHeaderMergeMode::from_str(&mode)
+                .map_err(|_| anyhow!("invalid header merge mode {mode}"))?;
+            Ok(())
+        }
+        "match_status" | "status" => {
+            injection.match_status = crate::value::as_list(v, crate::value::as_u16)
+                .context(format!("invalid http status code list value for key {k}"))?;
+            Ok(())
+        }
+        "match_content_type" | "content_type" => {
+            injection.match_content_type = crate::value::as_list(v, crate::value::as_string)
+                .context(format!("invalid content type list value for key {k}"))?;
+            Ok(())
+        }
+        _ => Err(anyhow!("invalid key {k}")),
+    })?;
+
+    if injection.headers.is_empty() {
+        return Err(anyhow!("no headers set"));
+    }
+    Ok(injection)
+}
+
+fn as_http_multipart_inspection_config(
+    value: &Yaml,
+) -> anyhow::Result<Option<HttpMultipartInspectionConfig>> {
+    let map = match value {
+        Yaml::Boolean(true) => return Ok(Some(HttpMultipartInspectionConfig::default())),
+        Yaml::Boolean(false) => return Ok(None),
+        Yaml::Hash(map) => map,
+        _ => {
+            return Err(anyhow!(
+                "yaml value type for 'multipart inspection' should be 'boolean' or 'map'"
+            ))
+        }
+    };
+
+    let mut config = HttpMultipartInspectionConfig::default();
+    crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+        "part_head_max_size" | "part_header_max_size" => {
+            config.part_head_max_size = crate::humanize::as_usize(v)
+                .context(format!("invalid humanize usize value for key {k}"))?;
+            Ok(())
+        }
+        "block_filename_ext" | "block_filename_extension" => {
+            let exts = crate::value::as_list(v, crate::value::as_string)
+                .context(format!("invalid filename extension list value for key {k}"))?;
+            config.block_filename_ext = exts
+                .into_iter()
+                .map(|s| s.trim_start_matches('.').to_lowercase())
+                .collect();
+            Ok(())
+        }
+        "block_content_type" => {
+            config.block_content_type = crate::value::as_list(v, crate::value::as_string)
+                .context(format!("invalid content type list value for key {k}"))?;
+            Ok(())
+        }
+        "part_max_size" => {
+            config.part_max_size = crate::humanize::as_u64(v)
+                .context(format!("invalid humanize u64 value for key {k}"))?;
+            Ok(())
+        }
+        "block_on_parse_error" => {
+            config.block_on_parse_error =
+                crate::value::as_bool(v).context(format!("invalid bool value for key {k}"))?;
+            Ok(())
+        }
+        _ => Err(anyhow!("invalid key {k}")),
+    })?;
+    Ok(Some(config))
+}
 
 pub fn as_h1_interception_config(value: &Yaml) -> anyhow::Result<H1InterceptionConfig> {
     if let Yaml::Hash(map) = value {
@@ -48,6 +182,11 @@ pub fn as_h1_interception_config(value: &Yaml) -> anyhow::Result<H1InterceptionC
                     .context(format!("invalid humanize usize value for key {k}"))?;
                 Ok(())
             }
+            "req_header_max_count" => {
+                config.req_head_max_count = crate::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                Ok(())
+            }
             "rsp_header_max_size" => {
                 config.rsp_head_max_size = crate::humanize::as_usize(v)
                     .context(format!("invalid humanize usize value for key {k}"))?;
@@ -62,6 +201,62 @@ pub fn as_h1_interception_config(value: &Yaml) -> anyhow::Result<H1InterceptionC
                 config.steal_forwarded_for = crate::value::as_bool(v)?;
                 Ok(())
             }
+            "rsp_body_decompress" | "response_body_decompress" => {
+                config.rsp_body_decompress = crate::value::as_bool(v)?;
+                Ok(())
+            }
+            "rsp_body_decompress_max_size" => {
+                config.rsp_body_decompress_max_size = crate::humanize::as_usize(v)
+                    .context(format!("invalid humanize usize value for key {k}"))?;
+                Ok(())
+            }
+            "rsp_body_decompress_max_ratio" => {
+                config.rsp_body_decompress_max_ratio =
+                    crate::value::as_u32(v).context(format!("invalid u32 value for key {k}"))?;
+                Ok(())
+            }
+            "rsp_body_decompress_exceed_action" => {
+                let action = crate::value::as_protocol_inspect_action(v)
+                    .context(format!("invalid protocol inspect action value for key {k}"))?;
+                match action {
+                    ProtocolInspectAction::Block | ProtocolInspectAction::Bypass => {
+                        config.rsp_body_decompress_exceed_action = action;
+                        Ok(())
+                    }
+                    _ => Err(anyhow!(
+                        "unsupported decompression limit exceed action {action}"
+                    )),
+                }
+            }
+            "rsp_body_max_size" | "response_body_max_size" => {
+                config.rsp_body_max_size = crate::humanize::as_u64(v)
+                    .context(format!("invalid humanize u64 value for key {k}"))?;
+                Ok(())
+            }
+            "rsp_body_exceed_policy" | "response_body_exceed_policy" => {
+                config.rsp_body_exceed_policy = as_http_body_size_limit_policy(v).context(
+                    format!("invalid http body size limit policy value for key {k}"),
+                )?;
+                Ok(())
+            }
+            "body_sha256" | "body_hash" => {
+                config.body_sha256 = as_http_body_hash_mode(v)
+                    .context(format!("invalid http body hash mode value for key {k}"))?;
+                Ok(())
+            }
+            "req_body_multipart" | "request_body_multipart" => {
+                config.req_body_multipart = as_http_multipart_inspection_config(v).context(
+                    format!("invalid http multipart inspection config value for key {k}"),
+                )?;
+                Ok(())
+            }
+            "rsp_header_inject" | "response_header_inject" => {
+                config.rsp_header_inject =
+                    crate::value::as_list(v, as_http_response_header_injection).context(
+                        format!("invalid http response header injection list value for key {k}"),
+                    )?;
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         })?;
 
@@ -87,6 +282,10 @@ pub fn as_h2_interception_config(value: &Yaml) -> anyhow::Result<H2InterceptionC
                 config.max_concurrent_streams = crate::value::as_u32(v)?;
                 Ok(())
             }
+            "max_upstream_concurrent_streams" => {
+                config.max_upstream_concurrent_streams = crate::value::as_u32(v)?;
+                Ok(())
+            }
             "max_frame_size" => {
                 config.max_frame_size = crate::humanize::as_u32(v)
                     .context(format!("invalid humanize u32 value for key {k}"))?;
@@ -125,6 +324,17 @@ pub fn as_h2_interception_config(value: &Yaml) -> anyhow::Result<H2InterceptionC
                 config.silent_drop_expect_header = crate::value::as_bool(v)?;
                 Ok(())
             }
+            "rsp_body_max_size" | "response_body_max_size" => {
+                config.rsp_body_max_size = crate::humanize::as_u64(v)
+                    .context(format!("invalid humanize u64 value for key {k}"))?;
+                Ok(())
+            }
+            "rsp_body_exceed_policy" | "response_body_exceed_policy" => {
+                config.rsp_body_exceed_policy = as_http_body_size_limit_policy(v).context(
+                    format!("invalid http body size limit policy value for key {k}"),
+                )?;
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         })?;
 
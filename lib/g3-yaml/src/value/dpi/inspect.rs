@@ -17,7 +17,7 @@
 use anyhow::{anyhow, Context};
 use yaml_rust::Yaml;
 
-use g3_dpi::{ProtocolInspectionConfig, ProtocolInspectionSizeLimit};
+use g3_dpi::{ProtocolInspectAction, ProtocolInspectionConfig, ProtocolInspectionSizeLimit};
 
 pub fn parse_inspect_size_limit(
     config: &mut ProtocolInspectionSizeLimit,
@@ -75,6 +75,17 @@ pub fn as_protocol_inspection_config(value: &Yaml) -> anyhow::Result<ProtocolIns
                 config.set_max_depth(depth);
                 Ok(())
             }
+            "inspect_max_depth_action" => {
+                let action = crate::value::as_protocol_inspect_action(v)
+                    .context(format!("invalid protocol inspect action value for key {k}"))?;
+                match action {
+                    ProtocolInspectAction::Block | ProtocolInspectAction::Bypass => {
+                        config.set_max_depth_action(action);
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("unsupported max depth action {action}")),
+                }
+            }
             "data0_wait_timeout" => {
                 let value = crate::humanize::as_duration(v)
                     .context(format!("invalid humanize duration value for key {k}"))?;
@@ -15,7 +15,7 @@
  */
 
 mod policy;
-pub use policy::as_protocol_inspect_policy_builder;
+pub use policy::{as_protocol_inspect_action, as_protocol_inspect_policy_builder};
 
 mod inspect;
 pub use inspect::as_protocol_inspection_config;
@@ -35,5 +35,11 @@ pub use smtp::as_smtp_interception_config;
 mod imap;
 pub use imap::as_imap_interception_config;
 
+mod pop3;
+pub use pop3::as_pop3_interception_config;
+
+mod websocket;
+pub use websocket::as_websocket_interception_config;
+
 mod dump;
 pub use dump::as_stream_dump_config;
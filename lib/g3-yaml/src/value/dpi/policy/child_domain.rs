@@ -14,11 +14,14 @@
  * limitations under the License.
  */
 
+use std::sync::Arc;
+
 use anyhow::anyhow;
 use yaml_rust::Yaml;
 
 use g3_dpi::ProtocolInspectAction;
 use g3_types::acl::AclChildDomainRuleBuilder;
+use g3_types::net::HttpBlockResponse;
 
 use super::InspectRuleYamlParser;
 
@@ -41,8 +44,11 @@ impl InspectRuleYamlParser for AclChildDomainRuleBuilder<ProtocolInspectAction>
 
 pub(super) fn as_child_domain_rule_builder(
     value: &Yaml,
-) -> anyhow::Result<AclChildDomainRuleBuilder<ProtocolInspectAction>> {
+) -> anyhow::Result<(
+    AclChildDomainRuleBuilder<ProtocolInspectAction>,
+    Option<Arc<HttpBlockResponse>>,
+)> {
     let mut builder = AclChildDomainRuleBuilder::new(ProtocolInspectAction::Intercept);
-    builder.parse(value)?;
-    Ok(builder)
+    let block_response = builder.parse(value)?;
+    Ok((builder, block_response))
 }
@@ -14,10 +14,13 @@
  * limitations under the License.
  */
 
+use std::sync::Arc;
+
 use yaml_rust::Yaml;
 
 use g3_dpi::ProtocolInspectAction;
 use g3_types::acl::AclExactHostRule;
+use g3_types::net::HttpBlockResponse;
 
 use super::InspectRuleYamlParser;
 
@@ -35,8 +38,11 @@ impl InspectRuleYamlParser for AclExactHostRule<ProtocolInspectAction> {
 
 pub(super) fn as_exact_host_rule(
     value: &Yaml,
-) -> anyhow::Result<AclExactHostRule<ProtocolInspectAction>> {
+) -> anyhow::Result<(
+    AclExactHostRule<ProtocolInspectAction>,
+    Option<Arc<HttpBlockResponse>>,
+)> {
     let mut builder = AclExactHostRule::new(ProtocolInspectAction::Intercept);
-    builder.parse(value)?;
-    Ok(builder)
+    let block_response = builder.parse(value)?;
+    Ok((builder, block_response))
 }
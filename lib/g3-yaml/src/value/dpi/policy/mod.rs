@@ -15,11 +15,13 @@
  */
 
 use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::{anyhow, Context};
 use yaml_rust::Yaml;
 
 use g3_dpi::{ProtocolInspectAction, ProtocolInspectPolicyBuilder};
+use g3_types::net::HttpBlockResponse;
 
 mod child_domain;
 mod exact_host;
@@ -32,9 +34,17 @@ trait InspectRuleYamlParser {
         value: &Yaml,
     ) -> anyhow::Result<()>;
 
-    fn parse(&mut self, value: &Yaml) -> anyhow::Result<()> {
+    /// Parse the rules, and the custom block response for this rule if set
+    fn parse(&mut self, value: &Yaml) -> anyhow::Result<Option<Arc<HttpBlockResponse>>> {
         if let Yaml::Hash(map) = value {
+            let mut block_response = None;
             crate::foreach_kv(map, |k, v| {
+                if crate::key::normalize(k).as_str() == "block_response" {
+                    let rsp = crate::value::as_http_block_response(v)
+                        .context(format!("invalid http block response value for key {k}"))?;
+                    block_response = Some(Arc::new(rsp));
+                    return Ok(());
+                }
                 let action = ProtocolInspectAction::from_str(k)
                     .map_err(|_| anyhow!("the key {k} is not a valid inspect action"))?;
                 if let Yaml::Array(seq) = v {
@@ -47,14 +57,15 @@ trait InspectRuleYamlParser {
                     self.add_rule_for_action(action, v)
                         .context(format!("invalid value for key {k}"))
                 }
-            })
+            })?;
+            Ok(block_response)
         } else {
             Err(anyhow!("invalid value type"))
         }
     }
 }
 
-fn as_protocol_inspect_action(value: &Yaml) -> anyhow::Result<ProtocolInspectAction> {
+pub fn as_protocol_inspect_action(value: &Yaml) -> anyhow::Result<ProtocolInspectAction> {
     if let Yaml::String(s) = value {
         ProtocolInspectAction::from_str(s)
             .map_err(|_| anyhow!("invalid protocol inspect action '{s}'"))
@@ -77,22 +88,38 @@ pub fn as_protocol_inspect_policy_builder(
                     Ok(())
                 }
                 "exact_match" | "exact" => {
-                    let exact_rule = exact_host::as_exact_host_rule(v)
+                    let (exact_rule, block_response) = exact_host::as_exact_host_rule(v)
                         .context(format!("invalid exact host inspect rule value for key {k}"))?;
                     builder.exact = Some(exact_rule);
+                    builder.exact_block_response = block_response;
                     Ok(())
                 }
                 "child_match" | "child" => {
-                    let child_builder = child_domain::as_child_domain_rule_builder(v).context(
-                        format!("invalid child domain inspect rule value for key {k}"),
-                    )?;
+                    let (child_builder, block_response) =
+                        child_domain::as_child_domain_rule_builder(v).context(format!(
+                            "invalid child domain inspect rule value for key {k}"
+                        ))?;
                     builder.child = Some(child_builder);
+                    builder.child_block_response = block_response;
+                    Ok(())
+                }
+                "block_response" => {
+                    let rsp = crate::value::as_http_block_response(v)
+                        .context(format!("invalid http block response value for key {k}"))?;
+                    builder.block_response = Some(Arc::new(rsp));
+                    Ok(())
+                }
+                "dry_run" => {
+                    builder.dry_run = crate::value::as_bool(v)
+                        .context(format!("invalid bool value for key {k}"))?;
                     Ok(())
                 }
                 "subnet_match" | "subnet" => {
-                    let subnet_builder = network::as_dst_subnet_rule_builder(v)
-                        .context(format!("invalid subnet inspect rule value for key {k}"))?;
+                    let (subnet_builder, block_response) =
+                        network::as_dst_subnet_rule_builder(v)
+                            .context(format!("invalid subnet inspect rule value for key {k}"))?;
                     builder.subnet = Some(subnet_builder);
+                    builder.subnet_block_response = block_response;
                     Ok(())
                 }
                 _ => Err(anyhow!("invalid key {k}")),
@@ -14,10 +14,13 @@
  * limitations under the License.
  */
 
+use std::sync::Arc;
+
 use yaml_rust::Yaml;
 
 use g3_dpi::ProtocolInspectAction;
 use g3_types::acl::AclNetworkRuleBuilder;
+use g3_types::net::HttpBlockResponse;
 
 use super::InspectRuleYamlParser;
 
@@ -35,8 +38,11 @@ impl InspectRuleYamlParser for AclNetworkRuleBuilder<ProtocolInspectAction> {
 
 pub(super) fn as_dst_subnet_rule_builder(
     value: &Yaml,
-) -> anyhow::Result<AclNetworkRuleBuilder<ProtocolInspectAction>> {
+) -> anyhow::Result<(
+    AclNetworkRuleBuilder<ProtocolInspectAction>,
+    Option<Arc<HttpBlockResponse>>,
+)> {
     let mut builder = AclNetworkRuleBuilder::new(ProtocolInspectAction::Intercept);
-    builder.parse(value)?;
-    Ok(builder)
+    let block_response = builder.parse(value)?;
+    Ok((builder, block_response))
 }
@@ -14,10 +14,331 @@
  * limitations under the License.
  */
 
+use std::str::FromStr;
+
 use anyhow::{anyhow, Context};
 use yaml_rust::Yaml;
 
-use g3_dpi::SmtpInterceptionConfig;
+use g3_dpi::{
+    SmtpGreetingBanner, SmtpInterceptionConfig, SmtpLongLineAction, SmtpMailParamRewrite,
+    SmtpReplyRemap, SmtpStartTlsPolicy,
+};
+
+fn as_smtp_starttls_policy(value: &Yaml) -> anyhow::Result<SmtpStartTlsPolicy> {
+    if let Yaml::String(s) = value {
+        SmtpStartTlsPolicy::from_str(s).map_err(|_| anyhow!("invalid smtp starttls policy '{s}'"))
+    } else {
+        Err(anyhow!(
+            "yaml value type for 'smtp starttls policy' should be 'string'"
+        ))
+    }
+}
+
+fn as_smtp_long_line_action(value: &Yaml) -> anyhow::Result<SmtpLongLineAction> {
+    if let Yaml::String(s) = value {
+        SmtpLongLineAction::from_str(s).map_err(|_| anyhow!("invalid smtp long line action '{s}'"))
+    } else {
+        Err(anyhow!(
+            "yaml value type for 'smtp long line action' should be 'string'"
+        ))
+    }
+}
+
+fn set_smtp_starttls_policy(
+    config: &mut SmtpInterceptionConfig,
+    value: &Yaml,
+) -> anyhow::Result<()> {
+    if let Yaml::Hash(map) = value {
+        crate::foreach_kv(map, |k, v| {
+            if crate::key::normalize(k).as_str() == "default" {
+                config.starttls_policy = as_smtp_starttls_policy(v)
+                    .context(format!("invalid smtp starttls policy value for key {k}"))?;
+                return Ok(());
+            }
+
+            let policy = SmtpStartTlsPolicy::from_str(k)
+                .map_err(|_| anyhow!("the key {k} is not a valid smtp starttls policy"))?;
+            if let Yaml::Array(seq) = v {
+                for (i, v) in seq.iter().enumerate() {
+                    let host = crate::value::as_host(v)
+                        .context(format!("invalid host value for {k}#{i}"))?;
+                    config.starttls_host_policy.insert(host, policy);
+                }
+            } else {
+                let host =
+                    crate::value::as_host(v).context(format!("invalid host value for key {k}"))?;
+                config.starttls_host_policy.insert(host, policy);
+            }
+            Ok(())
+        })
+    } else {
+        config.starttls_policy = as_smtp_starttls_policy(value)?;
+        Ok(())
+    }
+}
+
+fn check_smtp_reply_text(s: &str) -> anyhow::Result<()> {
+    if s.contains(['\r', '\n']) {
+        Err(anyhow!("CR or LF is not allowed in smtp reply text"))
+    } else {
+        Ok(())
+    }
+}
+
+fn as_smtp_greeting_banner(value: &Yaml) -> anyhow::Result<SmtpGreetingBanner> {
+    let mut banner = SmtpGreetingBanner::default();
+    match value {
+        Yaml::String(s) => {
+            check_smtp_reply_text(s)?;
+            banner.message.clone_from(s);
+        }
+        Yaml::Hash(map) => {
+            crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+                "hostname" | "host" => {
+                    let host = crate::value::as_string(v)
+                        .context(format!("invalid string value for key {k}"))?;
+                    if host.is_empty() || host.contains(|c: char| c.is_ascii_whitespace()) {
+                        return Err(anyhow!("invalid smtp greeting hostname {host}"));
+                    }
+                    banner.hostname = Some(host);
+                    Ok(())
+                }
+                "message" | "text" => {
+                    let message = crate::value::as_string(v)
+                        .context(format!("invalid string value for key {k}"))?;
+                    check_smtp_reply_text(&message)?;
+                    banner.message = message;
+                    Ok(())
+                }
+                "defer_upstream_connection" | "defer_upstream_connect" => {
+                    banner.defer_upstream_connection = crate::value::as_bool(v)
+                        .context(format!("invalid bool value for key {k}"))?;
+                    Ok(())
+                }
+                _ => Err(anyhow!("invalid key {k}")),
+            })?;
+        }
+        _ => {
+            return Err(anyhow!(
+                "yaml value type for 'smtp greeting banner' should be 'string' or 'map'"
+            ))
+        }
+    }
+    Ok(banner)
+}
+
+fn check_smtp_reply_code(code: u16) -> anyhow::Result<()> {
+    let class = code / 100;
+    let subject = code / 10 % 10;
+    if !(2..=5).contains(&class) || subject > 5 {
+        return Err(anyhow!("invalid smtp reply code {code}"));
+    }
+    Ok(())
+}
+
+fn check_smtp_enhanced_status(s: &str, reply_code: u16) -> anyhow::Result<()> {
+    let mut parts = s.split('.');
+    let (Some(class), Some(subject), Some(detail), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(anyhow!("invalid smtp enhanced status code {s}"));
+    };
+    let class_matched = match class {
+        "2" | "4" | "5" => class.as_bytes()[0] - b'0' == (reply_code / 100) as u8,
+        _ => return Err(anyhow!("invalid class in smtp enhanced status code {s}")),
+    };
+    for v in [subject, detail] {
+        if v.is_empty() || v.len() > 3 || !v.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(anyhow!("invalid smtp enhanced status code {s}"));
+        }
+    }
+    if !class_matched {
+        return Err(anyhow!(
+            "the class of smtp enhanced status code {s} doesn't match reply code {reply_code}"
+        ));
+    }
+    Ok(())
+}
+
+fn as_smtp_reply_match(value: &Yaml) -> anyhow::Result<(u16, Option<String>)> {
+    match value {
+        Yaml::Integer(_) => {
+            let code = crate::value::as_u16(value)?;
+            check_smtp_reply_code(code)?;
+            Ok((code, None))
+        }
+        Yaml::String(s) => {
+            let (code, status) = match s.split_once(' ') {
+                Some((code, status)) => (code, Some(status.trim())),
+                None => (s.as_str(), None),
+            };
+            let code =
+                u16::from_str(code).map_err(|e| anyhow!("invalid smtp reply code {code}: {e}"))?;
+            check_smtp_reply_code(code)?;
+            if let Some(status) = status {
+                check_smtp_enhanced_status(status, code)?;
+            }
+            Ok((code, status.map(|s| s.to_string())))
+        }
+        _ => Err(anyhow!(
+            "yaml value type for 'smtp reply match' should be 'u16' or 'string'"
+        )),
+    }
+}
+
+fn as_smtp_reply_remap(value: &Yaml) -> anyhow::Result<SmtpReplyRemap> {
+    let Yaml::Hash(map) = value else {
+        return Err(anyhow!(
+            "yaml value type for 'smtp reply remap' should be 'map'"
+        ));
+    };
+
+    let v = crate::hash_get_required(map, "match")?;
+    let (match_code, match_enhanced_status) =
+        as_smtp_reply_match(v).context("invalid smtp reply match value for key match")?;
+    let mut remap = SmtpReplyRemap {
+        match_code,
+        match_enhanced_status,
+        code: match_code,
+        enhanced_status: None,
+        text: None,
+    };
+
+    crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+        "match" => Ok(()),
+        "code" | "reply_code" => {
+            let code = crate::value::as_u16(v)?;
+            check_smtp_reply_code(code)?;
+            remap.code = code;
+            Ok(())
+        }
+        "enhanced_status" | "enhanced_status_code" => {
+            let status =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            remap.enhanced_status = Some(status);
+            Ok(())
+        }
+        "text" | "message" => {
+            let text =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            check_smtp_reply_text(&text)?;
+            if text.len() > 256 {
+                return Err(anyhow!("too long smtp reply text"));
+            }
+            remap.text = Some(text);
+            Ok(())
+        }
+        _ => Err(anyhow!("invalid key {k}")),
+    })?;
+
+    if remap.code / 100 != match_code / 100 {
+        return Err(anyhow!(
+            "the reply code {} is not in the same class as the matched code {match_code}",
+            remap.code
+        ));
+    }
+    if let Some(status) = &remap.enhanced_status {
+        check_smtp_enhanced_status(status, remap.code)?;
+    }
+    Ok(remap)
+}
+
+fn as_smtp_reply_remap_list(value: &Yaml) -> anyhow::Result<Vec<SmtpReplyRemap>> {
+    if let Yaml::Array(seq) = value {
+        let mut list = Vec::with_capacity(seq.len());
+        for (i, v) in seq.iter().enumerate() {
+            let remap = as_smtp_reply_remap(v)
+                .context(format!("invalid smtp reply remap value for #{i}"))?;
+            list.push(remap);
+        }
+        Ok(list)
+    } else {
+        let remap = as_smtp_reply_remap(value)?;
+        Ok(vec![remap])
+    }
+}
+
+fn check_esmtp_keyword(keyword: &str) -> anyhow::Result<()> {
+    // esmtp-keyword  = (ALPHA / DIGIT) *(ALPHA / DIGIT / "-")
+    let Some(c) = keyword.chars().next() else {
+        return Err(anyhow!("empty esmtp keyword"));
+    };
+    if !c.is_ascii_alphanumeric() {
+        return Err(anyhow!("invalid esmtp keyword {keyword}"));
+    }
+    if !keyword
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(anyhow!("invalid esmtp keyword {keyword}"));
+    }
+    Ok(())
+}
+
+fn as_esmtp_param(value: &Yaml) -> anyhow::Result<(String, Option<String>)> {
+    let s = crate::value::as_string(value)?;
+    match s.split_once('=') {
+        Some((k, v)) => {
+            check_esmtp_keyword(k)?;
+            // esmtp-value    = 1*(%d33-60 / %d62-126)
+            if v.is_empty() || !v.bytes().all(|c| matches!(c, 33..=60 | 62..=126)) {
+                return Err(anyhow!("invalid esmtp value for keyword {k}"));
+            }
+            Ok((k.to_uppercase(), Some(v.to_string())))
+        }
+        None => {
+            check_esmtp_keyword(&s)?;
+            Ok((s.to_uppercase(), None))
+        }
+    }
+}
+
+fn as_smtp_mail_param_rewrite(value: &Yaml) -> anyhow::Result<SmtpMailParamRewrite> {
+    let Yaml::Hash(map) = value else {
+        return Err(anyhow!(
+            "yaml value type for 'smtp mail param rewrite' should be 'map'"
+        ));
+    };
+    if map.len() != 1 {
+        return Err(anyhow!(
+            "exactly one action is required for 'smtp mail param rewrite'"
+        ));
+    }
+
+    let mut rewrite = None;
+    crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+        "remove" | "strip" => {
+            let keyword =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            check_esmtp_keyword(&keyword)?;
+            rewrite = Some(SmtpMailParamRewrite::Remove(keyword.to_uppercase()));
+            Ok(())
+        }
+        "set" => {
+            let (keyword, value) =
+                as_esmtp_param(v).context(format!("invalid esmtp param value for key {k}"))?;
+            rewrite = Some(SmtpMailParamRewrite::Set(keyword, value));
+            Ok(())
+        }
+        _ => Err(anyhow!("invalid key {k}")),
+    })?;
+    rewrite.ok_or_else(|| anyhow!("no action set for 'smtp mail param rewrite'"))
+}
+
+fn as_smtp_mail_param_rewrite_list(value: &Yaml) -> anyhow::Result<Vec<SmtpMailParamRewrite>> {
+    if let Yaml::Array(seq) = value {
+        let mut list = Vec::with_capacity(seq.len());
+        for (i, v) in seq.iter().enumerate() {
+            let rewrite = as_smtp_mail_param_rewrite(v)
+                .context(format!("invalid smtp mail param rewrite value for #{i}"))?;
+            list.push(rewrite);
+        }
+        Ok(list)
+    } else {
+        let rewrite = as_smtp_mail_param_rewrite(value)?;
+        Ok(vec![rewrite])
+    }
+}
 
 pub fn as_smtp_interception_config(value: &Yaml) -> anyhow::Result<SmtpInterceptionConfig> {
     if let Yaml::Hash(map) = value {
@@ -34,11 +355,26 @@ pub fn as_smtp_interception_config(value: &Yaml) -> anyhow::Result<SmtpIntercept
                     .context(format!("invalid humanize duration value for key {k}"))?;
                 Ok(())
             }
+            "drain_timeout" => {
+                config.drain_timeout = crate::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
             "command_wait_timeout" => {
                 config.command_wait_timeout = crate::humanize::as_duration(v)
                     .context(format!("invalid humanize duration value for key {k}"))?;
                 Ok(())
             }
+            "command_idle_timeout" => {
+                let timeout = crate::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                config.command_idle_timeout = if timeout.is_zero() {
+                    None
+                } else {
+                    Some(timeout)
+                };
+                Ok(())
+            }
             "response_wait_timeout" => {
                 config.response_wait_timeout = crate::humanize::as_duration(v)
                     .context(format!("invalid humanize duration value for key {k}"))?;
@@ -66,6 +402,51 @@ pub fn as_smtp_interception_config(value: &Yaml) -> anyhow::Result<SmtpIntercept
                 config.allow_burl_data = crate::value::as_bool(v)?;
                 Ok(())
             }
+            "max_message_size" => {
+                let size = crate::humanize::as_usize(v)
+                    .context(format!("invalid humanize usize value for key {k}"))?;
+                config.max_message_size = Some(size);
+                Ok(())
+            }
+            "max_recipients" | "recipient_limit" => {
+                let max = crate::value::as_usize(v)
+                    .context(format!("invalid usize value for key {k}"))?;
+                config.max_recipients = Some(max);
+                Ok(())
+            }
+            "starttls_policy" => set_smtp_starttls_policy(&mut config, v)
+                .context(format!("invalid smtp starttls policy value for key {k}")),
+            "greeting_banner" | "local_greeting" => {
+                let banner = as_smtp_greeting_banner(v)
+                    .context(format!("invalid smtp greeting banner value for key {k}"))?;
+                config.greeting_banner = Some(banner);
+                Ok(())
+            }
+            "upstream_proxy_protocol" => {
+                let version = crate::value::as_proxy_protocol_version(v)
+                    .context(format!("invalid proxy protocol version value for key {k}"))?;
+                config.upstream_proxy_protocol = Some(version);
+                Ok(())
+            }
+            "lenient_greeting_host" => {
+                config.lenient_greeting_host = crate::value::as_bool(v)?;
+                Ok(())
+            }
+            "long_response_line" | "long_response_line_action" => {
+                config.long_response_line = as_smtp_long_line_action(v)
+                    .context(format!("invalid smtp long line action value for key {k}"))?;
+                Ok(())
+            }
+            "reply_remap" | "reply_code_remap" => {
+                config.reply_remap = as_smtp_reply_remap_list(v)
+                    .context(format!("invalid smtp reply remap value for key {k}"))?;
+                Ok(())
+            }
+            "mail_param_rewrite" => {
+                config.mail_param_rewrite = as_smtp_mail_param_rewrite_list(v)
+                    .context(format!("invalid smtp mail param rewrite value for key {k}"))?;
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         })?;
 
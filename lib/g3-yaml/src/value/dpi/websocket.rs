@@ -0,0 +1,74 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_dpi::WebSocketInterceptionConfig;
+
+pub fn as_websocket_interception_config(
+    value: &Yaml,
+) -> anyhow::Result<WebSocketInterceptionConfig> {
+    if let Yaml::Hash(map) = value {
+        let mut config = WebSocketInterceptionConfig::default();
+
+        crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+            "client_frame_rate_limit" => {
+                let quota = crate::value::as_rate_limit_quota(v)
+                    .context(format!("invalid rate limit quota value for key {k}"))?;
+                config.client_frame_rate_limit = Some(quota);
+                Ok(())
+            }
+            "server_frame_rate_limit" => {
+                let quota = crate::value::as_rate_limit_quota(v)
+                    .context(format!("invalid rate limit quota value for key {k}"))?;
+                config.server_frame_rate_limit = Some(quota);
+                Ok(())
+            }
+            "rate_limit_count_ping_pong" => {
+                config.rate_limit_count_ping_pong = crate::value::as_bool(v)?;
+                Ok(())
+            }
+            "block_close_reason" => {
+                let reason = crate::value::as_string(v)?;
+                if reason.len() > 123 {
+                    return Err(anyhow!(
+                        "the close reason should be no longer than 123 bytes for key {k}"
+                    ));
+                }
+                config.block_close_reason = reason;
+                Ok(())
+            }
+            "strict_handshake" => {
+                config.strict_handshake = crate::value::as_bool(v)?;
+                Ok(())
+            }
+            "synthetic_pong_delay" => {
+                let delay = crate::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                config.synthetic_pong_delay = (!delay.is_zero()).then_some(delay);
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+
+        Ok(config)
+    } else {
+        Err(anyhow!(
+            "yaml value type for 'websocket interception config' should be 'map'"
+        ))
+    }
+}
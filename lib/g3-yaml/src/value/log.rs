@@ -0,0 +1,87 @@
+/*
+ * Copyright 2024 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use yaml_rust::Yaml;
+
+use g3_types::log::{LogSamplingConfig, LogSamplingMethod};
+
+pub fn as_log_sampling_config(value: &Yaml) -> anyhow::Result<LogSamplingConfig> {
+    match value {
+        Yaml::Integer(_) => {
+            let rate = crate::value::as_nonzero_u32(value)?;
+            Ok(LogSamplingConfig::new(rate, LogSamplingMethod::default()))
+        }
+        Yaml::Hash(map) => {
+            let default = LogSamplingConfig::default();
+            let mut rate = default.rate();
+            let mut method = default.method();
+            crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+                "rate" | "one_in" => {
+                    rate = crate::value::as_nonzero_u32(v)
+                        .context(format!("invalid nonzero u32 value for key {k}"))?;
+                    Ok(())
+                }
+                "method" => {
+                    let s = crate::value::as_string(v)
+                        .context(format!("invalid string value for key {k}"))?;
+                    method = LogSamplingMethod::from_str(&s)
+                        .map_err(|_| anyhow!("invalid log sampling method {s}"))?;
+                    Ok(())
+                }
+                _ => Err(anyhow!("invalid key {k}")),
+            })?;
+            Ok(LogSamplingConfig::new(rate, method))
+        }
+        _ => Err(anyhow!(
+            "yaml value type for 'log sampling config' should be 'u32' or 'map'"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn as_log_sampling_config_ok() {
+        let config = as_log_sampling_config(&Yaml::Integer(10)).unwrap();
+        assert_eq!(config.rate().get(), 10);
+        assert_eq!(config.method(), LogSamplingMethod::Counter);
+
+        let v = YamlLoader::load_from_str("rate: 100\nmethod: task_id_hash")
+            .unwrap()
+            .pop()
+            .unwrap();
+        let config = as_log_sampling_config(&v).unwrap();
+        assert_eq!(config.rate().get(), 100);
+        assert_eq!(config.method(), LogSamplingMethod::TaskIdHash);
+    }
+
+    #[test]
+    fn as_log_sampling_config_err() {
+        assert!(as_log_sampling_config(&Yaml::Integer(0)).is_err());
+
+        let v = YamlLoader::load_from_str("method: random")
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert!(as_log_sampling_config(&v).is_err());
+    }
+}
@@ -16,8 +16,10 @@
 
 mod auth;
 mod collection;
+mod conn_limit;
 mod datetime;
 mod fs;
+mod log;
 mod metrics;
 mod net;
 mod primary;
@@ -26,9 +28,11 @@ mod rate_limit;
 mod speed_limit;
 
 pub use auth::{as_password, as_username};
-pub use collection::as_selective_pick_policy;
+pub use collection::{as_selective_pick_override, as_selective_pick_policy};
+pub use conn_limit::as_client_conn_limit_config;
 pub use datetime::as_rfc3339_datetime;
 pub use fs::{as_absolute_path, as_config_file_format, as_dir_path, as_file, as_file_path};
+pub use log::as_log_sampling_config;
 pub use metrics::{as_metrics_name, as_static_metrics_tags, as_weighted_metrics_name};
 pub use net::*;
 pub use primary::{
@@ -75,7 +79,7 @@ pub use histogram::{as_histogram_metrics_config, as_quantile, as_quantile_list};
 #[cfg(feature = "resolve")]
 mod resolve;
 #[cfg(feature = "resolve")]
-pub use resolve::{as_resolve_redirection_builder, as_resolve_strategy};
+pub use resolve::{as_query_strategy, as_resolve_redirection_builder, as_resolve_strategy};
 
 #[cfg(feature = "rustls")]
 mod rustls;
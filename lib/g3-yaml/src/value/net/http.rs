@@ -18,11 +18,13 @@ use std::str::FromStr;
 
 use anyhow::{anyhow, Context};
 use http::uri::PathAndQuery;
-use http::HeaderName;
+use http::{HeaderName, StatusCode};
+use regex::Regex;
 use yaml_rust::Yaml;
 
 use g3_types::net::{
-    HttpForwardCapability, HttpForwardedHeaderType, HttpKeepAliveConfig, HttpServerId,
+    HttpBlockResponse, HttpForwardCapability, HttpForwardedHeaderType, HttpHeaderValue,
+    HttpKeepAliveConfig, HttpPathRewriteRule, HttpPathRewriter, HttpServerId,
 };
 
 pub fn as_http_keepalive_config(v: &Yaml) -> anyhow::Result<HttpKeepAliveConfig> {
@@ -147,3 +149,124 @@ pub fn as_http_path_and_query(value: &Yaml) -> anyhow::Result<PathAndQuery> {
         ))
     }
 }
+
+fn as_http_status_code(value: &Yaml) -> anyhow::Result<StatusCode> {
+    let code = crate::value::as_u16(value)?;
+    StatusCode::from_u16(code).map_err(|e| anyhow!("invalid status code {code}: {e}"))
+}
+
+pub fn as_http_block_response(value: &Yaml) -> anyhow::Result<HttpBlockResponse> {
+    match value {
+        Yaml::Hash(map) => {
+            let mut rsp = HttpBlockResponse::default();
+            crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+                "status" | "status_code" | "code" => {
+                    let status = as_http_status_code(v)
+                        .context(format!("invalid http status code value for key {k}"))?;
+                    rsp.set_status(status);
+                    Ok(())
+                }
+                "headers" | "header" => {
+                    if let Yaml::Hash(map) = v {
+                        crate::foreach_kv(map, |name, value| {
+                            let header_name = HeaderName::from_str(name)
+                                .map_err(|e| anyhow!("invalid header name {name}: {e}"))?;
+                            let value = crate::value::as_string(value)?;
+                            let header_value = HttpHeaderValue::from_str(&value)
+                                .map_err(|_| anyhow!("invalid value for header {name}"))?;
+                            rsp.append_header(header_name, header_value)
+                                .map_err(|name| anyhow!("header {name} is not allowed to set"))
+                        })
+                        .context(format!("invalid http headers value for key {k}"))
+                    } else {
+                        Err(anyhow!(
+                            "invalid yaml value type for key {k}, should be map"
+                        ))
+                    }
+                }
+                "body" => {
+                    let body = crate::value::as_string(v)
+                        .context(format!("invalid string value for key {k}"))?;
+                    rsp.set_body(body);
+                    Ok(())
+                }
+                _ => Err(anyhow!("invalid key {k}")),
+            })?;
+            Ok(rsp)
+        }
+        _ => {
+            let status = as_http_status_code(value)?;
+            Ok(HttpBlockResponse::new(status))
+        }
+    }
+}
+
+fn as_http_path_rewrite_rule(value: &Yaml) -> anyhow::Result<HttpPathRewriteRule> {
+    let Yaml::Hash(map) = value else {
+        return Err(anyhow!(
+            "yaml value type for 'HttpPathRewriteRule' should be 'map'"
+        ));
+    };
+
+    let mut rule = None;
+    let mut regex = None;
+    let mut replace = None;
+    crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+        "strip_prefix" => {
+            let prefix =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            rule = Some(HttpPathRewriteRule::StripPrefix(prefix));
+            Ok(())
+        }
+        "add_prefix" => {
+            let prefix =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            if !prefix.starts_with('/') {
+                return Err(anyhow!("the prefix should start with '/'"));
+            }
+            rule = Some(HttpPathRewriteRule::AddPrefix(prefix));
+            Ok(())
+        }
+        "regex" | "match" => {
+            let s =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            let re = Regex::new(&s).map_err(|e| anyhow!("invalid regex value: {e}"))?;
+            regex = Some(re);
+            Ok(())
+        }
+        "replace" | "replacement" => {
+            let s =
+                crate::value::as_string(v).context(format!("invalid string value for key {k}"))?;
+            replace = Some(s);
+            Ok(())
+        }
+        _ => Err(anyhow!("invalid key {k}")),
+    })?;
+
+    match (rule, regex, replace) {
+        (Some(rule), None, None) => Ok(rule),
+        (None, Some(regex), Some(replace)) => Ok(HttpPathRewriteRule::RegexReplace(regex, replace)),
+        (None, Some(_), None) => Err(anyhow!("no replace value set for the regex")),
+        (None, None, Some(_)) => Err(anyhow!("no regex set for the replace value")),
+        (None, None, None) => Err(anyhow!("no rewrite rule set")),
+        _ => Err(anyhow!("only one rewrite rule can be set in each map")),
+    }
+}
+
+pub fn as_http_path_rewriter(value: &Yaml) -> anyhow::Result<HttpPathRewriter> {
+    let mut rewriter = HttpPathRewriter::default();
+    match value {
+        Yaml::Array(seq) => {
+            for (i, v) in seq.iter().enumerate() {
+                let rule = as_http_path_rewrite_rule(v)
+                    .context(format!("invalid http path rewrite rule value for #{i}"))?;
+                rewriter.push(rule);
+            }
+        }
+        _ => {
+            let rule = as_http_path_rewrite_rule(value)?;
+            rewriter.push(rule);
+        }
+    }
+    Ok(rewriter)
+}
@@ -234,6 +234,8 @@ pub fn as_tcp_keepalive_config(v: &Yaml) -> anyhow::Result<TcpKeepAliveConfig> {
 
 pub fn as_tcp_misc_sock_opts(v: &Yaml) -> anyhow::Result<TcpMiscSockOpts> {
     let mut config = TcpMiscSockOpts::default();
+    let mut tos_set = false;
+    let mut dscp_set = false;
 
     if let Yaml::Hash(map) = v {
         crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
@@ -259,6 +261,17 @@ pub fn as_tcp_misc_sock_opts(v: &Yaml) -> anyhow::Result<TcpMiscSockOpts> {
                 let tos =
                     crate::value::as_u8(v).context(format!("invalid u8 value for key {k}"))?;
                 config.type_of_service = Some(tos);
+                tos_set = true;
+                Ok(())
+            }
+            "dscp" => {
+                let dscp =
+                    crate::value::as_u8(v).context(format!("invalid u8 value for key {k}"))?;
+                if dscp > 63 {
+                    return Err(anyhow!("dscp value {dscp} is out of range 0-63"));
+                }
+                config.type_of_service = Some(dscp << 2);
+                dscp_set = true;
                 Ok(())
             }
             "netfilter_mark" | "mark" => {
@@ -269,6 +282,9 @@ pub fn as_tcp_misc_sock_opts(v: &Yaml) -> anyhow::Result<TcpMiscSockOpts> {
             }
             _ => Err(anyhow!("invalid key {k}")),
         })?;
+        if tos_set && dscp_set {
+            return Err(anyhow!("tos and dscp can not be set at the same time"));
+        }
 
         Ok(config)
     } else {
@@ -277,3 +293,36 @@ pub fn as_tcp_misc_sock_opts(v: &Yaml) -> anyhow::Result<TcpMiscSockOpts> {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn parse(s: &str) -> anyhow::Result<TcpMiscSockOpts> {
+        let doc = YamlLoader::load_from_str(s).unwrap();
+        as_tcp_misc_sock_opts(&doc[0])
+    }
+
+    #[test]
+    fn dscp_range() {
+        let config = parse("dscp: 0").unwrap();
+        assert_eq!(config.type_of_service, Some(0));
+        let config = parse("dscp: 46").unwrap();
+        assert_eq!(config.type_of_service, Some(0xb8));
+        let config = parse("dscp: 63").unwrap();
+        assert_eq!(config.type_of_service, Some(0xfc));
+
+        assert!(parse("dscp: 64").is_err());
+        assert!(parse("dscp: -1").is_err());
+    }
+
+    #[test]
+    fn tos_and_dscp() {
+        let config = parse("tos: 0x10").unwrap();
+        assert_eq!(config.type_of_service, Some(0x10));
+
+        assert!(parse("{tos: 0x10, dscp: 46}").is_err());
+        assert!(parse("{dscp: 46, type_of_service: 0x10}").is_err());
+    }
+}
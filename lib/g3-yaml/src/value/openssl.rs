@@ -406,6 +406,7 @@ pub fn as_tls_interception_client_config_builder(
 ) -> anyhow::Result<OpensslInterceptionClientConfigBuilder> {
     if let Yaml::Hash(map) = value {
         let mut builder = OpensslInterceptionClientConfigBuilder::default();
+        let mut cert_pair = OpensslCertificatePair::default();
 
         crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
             "min_tls_version" | "tls_version_min" => {
@@ -436,6 +437,28 @@ pub fn as_tls_interception_client_config_builder(
                 }
                 Ok(())
             }
+            "certificate" | "cert" => {
+                let cert = as_openssl_certificates(v, lookup_dir)
+                    .context(format!("invalid certificates value for key {k}"))?;
+                cert_pair
+                    .set_certificates(cert)
+                    .context("failed to set certificate")?;
+                Ok(())
+            }
+            "private_key" | "key" => {
+                let key = as_openssl_private_key(v, lookup_dir)
+                    .context(format!("invalid private key value for key {k}"))?;
+                cert_pair
+                    .set_private_key(key)
+                    .context("failed to set private key")?;
+                Ok(())
+            }
+            "cert_pair" => {
+                let pair = as_openssl_certificate_pair(v, lookup_dir)
+                    .context(format!("invalid cert pair value for key {k}"))?;
+                builder.set_cert_pair(pair);
+                Ok(())
+            }
             "handshake_timeout" | "negotiation_timeout" => {
                 let timeout = crate::humanize::as_duration(v)
                     .context(format!("invalid humanize duration value for key {k}"))?;
@@ -490,6 +513,10 @@ pub fn as_tls_interception_client_config_builder(
             _ => Err(anyhow!("invalid key {k}")),
         })?;
 
+        if cert_pair.is_set() && builder.set_cert_pair(cert_pair).is_some() {
+            return Err(anyhow!("found duplicate client certificate config"));
+        }
+
         builder.check()?;
         Ok(builder)
     } else {
@@ -592,17 +619,43 @@ pub fn as_openssl_tls_server_config_builder(
 
 pub fn as_tls_interception_server_config_builder(
     value: &Yaml,
+    lookup_dir: Option<&Path>,
 ) -> anyhow::Result<OpensslInterceptionServerConfigBuilder> {
     if let Yaml::Hash(map) = value {
         let mut builder = OpensslInterceptionServerConfigBuilder::default();
 
         crate::foreach_kv(map, |k, v| match crate::key::normalize(k).as_str() {
+            "enable_client_auth" => {
+                let enable =
+                    crate::value::as_bool(v).context(format!("invalid value for key {k}"))?;
+                if enable {
+                    builder.enable_client_auth();
+                }
+                Ok(())
+            }
+            "ca_certificate" | "ca_cert" | "client_auth_certificate" | "client_auth_cert" => {
+                let certs = as_openssl_certificates(v, lookup_dir)
+                    .context(format!("invalid value for key {k}"))?;
+                builder.set_client_auth_certificates(certs)
+            }
             "handshake_timeout" | "negotiation_timeout" | "accept_timeout" => {
                 let timeout = crate::humanize::as_duration(v)
                     .context(format!("invalid humanize duration value for key {k}"))?;
                 builder.set_accept_timeout(timeout);
                 Ok(())
             }
+            "min_tls_version" | "tls_version_min" => {
+                let tls_version = crate::value::as_tls_version(v)
+                    .context(format!("invalid tls version value for key {k}"))?;
+                builder.set_min_tls_version(tls_version);
+                Ok(())
+            }
+            "max_tls_version" | "tls_version_max" => {
+                let tls_version = crate::value::as_tls_version(v)
+                    .context(format!("invalid tls version value for key {k}"))?;
+                builder.set_max_tls_version(tls_version);
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         })?;
 
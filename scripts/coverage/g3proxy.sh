@@ -96,6 +96,11 @@ do
 	"${SCRIPTS_DIR}/../test/socks5_dns_query.py" -x ${proxy} --dns-server 127.0.0.1 g3proxy.local httpbin.local -v || :
 done
 
+## UDP advertise
+echo "==== UDP advertise"
+"${SCRIPTS_DIR}/../test/socks5_udp_associate_reply.py" -x socks5h://127.0.0.1:11081 --expect-ip 127.0.0.1
+"${SCRIPTS_DIR}/../test/socks5_udp_associate_reply.py" -x socks5h://127.0.0.1:11083 --expect-ip 192.0.2.1
+
 ## FTP over HTTP
 echo "==== FTP over HTTP"
 for proxy in $all_http_proxies
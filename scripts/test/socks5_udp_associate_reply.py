@@ -0,0 +1,60 @@
+#!/usr/bin/env python3
+
+import argparse
+import ipaddress
+import socket
+import struct
+import sys
+from urllib.parse import urlparse
+
+
+def recv_exact(sock, n):
+    data = b''
+    while len(data) < n:
+        chunk = sock.recv(n - len(data))
+        if not chunk:
+            raise EOFError("connection closed by proxy")
+        data += chunk
+    return data
+
+
+def udp_associate(proxy_host, proxy_port):
+    sock = socket.create_connection((proxy_host, proxy_port), timeout=10)
+    sock.sendall(b'\x05\x01\x00')
+    ver, method = recv_exact(sock, 2)
+    if ver != 5 or method != 0:
+        raise ValueError("unexpected auth method {}".format(method))
+
+    sock.sendall(b'\x05\x03\x00\x01\x00\x00\x00\x00\x00\x00')
+    ver, rep, _, atyp = recv_exact(sock, 4)
+    if ver != 5 or rep != 0:
+        raise ValueError("udp associate failed with reply code {}".format(rep))
+    if atyp == 1:
+        ip = ipaddress.IPv4Address(recv_exact(sock, 4))
+    elif atyp == 4:
+        ip = ipaddress.IPv6Address(recv_exact(sock, 16))
+    else:
+        raise ValueError("unexpected address type {}".format(atyp))
+    (port,) = struct.unpack('!H', recv_exact(sock, 2))
+    return sock, ip, port
+
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(description="Check the udp relay address in the socks5 udp associate reply")
+    parser.add_argument("-x", "--proxy", nargs='?', required=True)
+    parser.add_argument("--expect-ip", nargs='?', required=True, help="the expected udp relay ip")
+    args = parser.parse_args()
+
+    url = urlparse(args.proxy)
+    expect_ip = ipaddress.ip_address(args.expect_ip)
+
+    (s, ip, port) = udp_associate(url.hostname, url.port)
+    print("udp relay address: {} port {}".format(ip, port))
+    s.close()
+
+    if ip != expect_ip:
+        print("expected udp relay ip {}".format(expect_ip))
+        sys.exit(1)
+    if port == 0:
+        print("invalid udp relay port")
+        sys.exit(1)